@@ -1,15 +1,24 @@
+use base64::Engine;
 use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::io::{self, BufRead, BufReader, Read, Write};
 use std::net::TcpStream;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::time::{Duration, Instant};
 
 // Performance optimizations
 const IPC_TIMEOUT: Duration = Duration::from_secs(10);
+// Rendering many conversations (PDF especially) takes a while
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(600);
 const BUFFER_SIZE: usize = 4096;
 
+// Exit codes for scripting; app errors map through `exit_code_for`
+const EXIT_FAILURE: i32 = 1;
+const EXIT_INVALID_INPUT: i32 = 2;
+const EXIT_UNREACHABLE: i32 = 15;
+
 #[derive(Parser)]
 #[command(name = "lai")]
 #[command(about = "Linux AI Assistant CLI - Terminal companion for the Linux AI Desktop Assistant")]
@@ -19,10 +28,12 @@ Linux AI Assistant CLI provides command-line access to the desktop AI assistant.
 
 Examples:
   lai ask \"How do I optimize this SQL query?\"
+  lai ask \"What does this error mean?\" --image screenshot.png --file build.log
   lai notify \"Build completed successfully\"
   lai last
   lai capture \"npm test\" --analyze
   lai capture \"make build\" --timeout 60 --ai-analyze
+  lai export ~/notes/ai --format markdown --tag work
   DEV_MODE=1 lai create \"Test assistant message\"
 
 For more information, see: https://github.com/tbmobb813/Linux-AI-Assistant---Project
@@ -53,6 +64,12 @@ enum Commands {
         /// Read from stdin if no message provided
         #[arg(long, default_value_t = false)]
         stdin: bool,
+        /// Attach a text file to the message (repeatable)
+        #[arg(long = "file", value_name = "PATH")]
+        files: Vec<PathBuf>,
+        /// Attach an image (PNG, JPEG, GIF or WebP) to the message (repeatable)
+        #[arg(long = "image", value_name = "PATH")]
+        images: Vec<PathBuf>,
     },
     /// Alias for 'ask' - send a question to the AI assistant
     Chat {
@@ -73,6 +90,12 @@ enum Commands {
         /// Read from stdin
         #[arg(long, default_value_t = false)]
         stdin: bool,
+        /// Attach a text file (repeatable)
+        #[arg(long = "file", value_name = "PATH")]
+        files: Vec<PathBuf>,
+        /// Attach an image (repeatable)
+        #[arg(long = "image", value_name = "PATH")]
+        images: Vec<PathBuf>,
     },
     /// Analyze text from stdin (e.g., cat error.log | lai analyze)
     Analyze {
@@ -121,6 +144,45 @@ enum Commands {
         #[arg(long, default_value_t = false)]
         ai_analyze: bool,
     },
+    /// Show token usage and spending
+    Usage {
+        /// today, week, month, year, all, Nd (last N days) or YYYY-MM-DD..YYYY-MM-DD
+        #[arg(long, default_value = "month")]
+        range: String,
+        /// Group rows by day, provider, model, profile or tag
+        #[arg(long, default_value = "model")]
+        group_by: String,
+        /// Print the raw report as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Export conversations to a directory, one file each; later runs only
+    /// rewrite conversations that changed
+    Export {
+        /// Directory to write to (created if missing)
+        dir: PathBuf,
+        /// json, markdown, html, pdf, org, epub or text
+        #[arg(long, default_value = "markdown")]
+        format: String,
+        /// Only conversations with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Only conversations whose title contains this text
+        #[arg(long)]
+        query: Option<String>,
+        /// Only conversations updated in this range (today, week, month, year, Nd or YYYY-MM-DD..YYYY-MM-DD)
+        #[arg(long)]
+        range: Option<String>,
+        /// Only this conversation (repeatable)
+        #[arg(long = "id", value_name = "ID")]
+        ids: Vec<String>,
+        /// Include every branch instead of only the active path
+        #[arg(long, default_value_t = false)]
+        whole_tree: bool,
+        /// Print the result as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
 }
 
 #[derive(Deserialize)]
@@ -129,6 +191,63 @@ struct IpcResponse {
     data: Option<serde_json::Value>,
 }
 
+impl IpcResponse {
+    /// Stable error code of an error response (`internal` if the app sent none)
+    fn error_code(&self) -> &str {
+        self.data
+            .as_ref()
+            .and_then(|d| d.get("code"))
+            .and_then(|c| c.as_str())
+            .unwrap_or("internal")
+    }
+
+    fn error_message(&self) -> String {
+        self.data
+            .as_ref()
+            .and_then(|d| d.get("message").or_else(|| d.get("error")))
+            .map(|m| {
+                m.as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| m.to_string())
+            })
+            .unwrap_or_else(|| "Unknown error".to_string())
+    }
+}
+
+/// Map an app error code to the documented `lai` exit status
+fn exit_code_for(code: &str) -> i32 {
+    match code {
+        "invalid_input" => EXIT_INVALID_INPUT,
+        "not_found" => 3,
+        "auth" => 4,
+        "rate_limited" => 5,
+        "budget_exceeded" => 6,
+        "network" => 7,
+        "database" => 8,
+        "database_locked" => 9,
+        "context_overflow" => 10,
+        "provider" => 11,
+        "conflict" => 12,
+        "io" => 13,
+        "unsupported" => 14,
+        "locked" => 16,
+        _ => EXIT_FAILURE,
+    }
+}
+
+/// Print the app's error and exit with the status for its code
+fn exit_with_error(response: &IpcResponse) -> ! {
+    eprintln!("Error: {}", response.error_message());
+    std::process::exit(exit_code_for(response.error_code()));
+}
+
+/// The app couldn't be reached or stopped answering
+fn exit_unreachable(context: &str, err: &str) -> ! {
+    eprintln!("{}: {}", context, err);
+    eprintln!("Is the Linux AI Assistant running?");
+    std::process::exit(EXIT_UNREACHABLE);
+}
+
 #[derive(Deserialize)]
 #[allow(dead_code)]
 struct Message {
@@ -140,6 +259,31 @@ struct Message {
     tokens_used: Option<i64>,
 }
 
+#[derive(Deserialize)]
+struct UsageRow {
+    label: String,
+    messages: i64,
+    prompt_tokens: i64,
+    completion_tokens: i64,
+    cost_usd: f64,
+    #[serde(default)]
+    unpriced_messages: i64,
+}
+
+#[derive(Deserialize)]
+struct UsageReport {
+    rows: Vec<UsageRow>,
+    total: UsageRow,
+}
+
+#[derive(Deserialize)]
+struct DirectoryExport {
+    directory: String,
+    written: Vec<String>,
+    unchanged: usize,
+    removed: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct CaptureResult {
     command: String,
@@ -163,6 +307,8 @@ fn main() {
             new,
             gui,
             stdin,
+            files,
+            images,
         }
         | Commands::Chat {
             message,
@@ -171,12 +317,14 @@ fn main() {
             new,
             gui,
             stdin,
+            files,
+            images,
         } => {
             // Get message from argument or stdin
             let msg = if *stdin || message.is_none() {
                 read_stdin().unwrap_or_else(|e| {
                     eprintln!("Failed to read from stdin: {}", e);
-                    std::process::exit(1);
+                    std::process::exit(EXIT_FAILURE);
                 })
             } else {
                 message.clone().unwrap_or_default()
@@ -184,10 +332,22 @@ fn main() {
 
             if msg.is_empty() {
                 eprintln!("No message provided. Use --stdin to read from stdin, or provide a message argument.");
-                std::process::exit(1);
+                std::process::exit(EXIT_INVALID_INPUT);
             }
 
-            handle_ask(&msg, model.as_deref(), provider.as_deref(), *new, *gui);
+            let attachments = read_attachments(files, images).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(EXIT_INVALID_INPUT);
+            });
+
+            handle_ask(
+                &msg,
+                model.as_deref(),
+                provider.as_deref(),
+                *new,
+                *gui,
+                &attachments,
+            );
         }
         Commands::Analyze {
             prompt,
@@ -197,12 +357,12 @@ fn main() {
         } => {
             let stdin_content = read_stdin().unwrap_or_else(|e| {
                 eprintln!("Failed to read from stdin: {}", e);
-                std::process::exit(1);
+                std::process::exit(EXIT_FAILURE);
             });
 
             if stdin_content.is_empty() {
                 eprintln!("No input from stdin. Usage: cat file.txt | lai analyze");
-                std::process::exit(1);
+                std::process::exit(EXIT_INVALID_INPUT);
             }
 
             let full_message = if let Some(p) = prompt {
@@ -217,12 +377,12 @@ fn main() {
                 provider.as_deref(),
                 false,
                 *gui,
+                &[],
             );
         }
         Commands::Notify { message } => {
             if let Err(e) = send_ipc("notify", Some(message.as_str()), None) {
-                eprintln!("Failed to send notify: {}", e);
-                std::process::exit(1);
+                exit_unreachable("Failed to send notify", &e);
             }
         }
         Commands::Last => match send_ipc_with_response("last", None, None) {
@@ -235,30 +395,18 @@ fn main() {
                             }
                             Err(e) => {
                                 eprintln!("Failed to parse message: {}", e);
-                                std::process::exit(1);
+                                std::process::exit(EXIT_FAILURE);
                             }
                         }
                     } else {
                         eprintln!("No data returned");
-                        std::process::exit(1);
+                        std::process::exit(EXIT_FAILURE);
                     }
                 } else {
-                    if let Some(data) = response.data {
-                        if let Some(error) = data.get("error") {
-                            eprintln!("Error: {}", error);
-                        } else {
-                            eprintln!("Error: {}", data);
-                        }
-                    } else {
-                        eprintln!("Unknown error");
-                    }
-                    std::process::exit(1);
+                    exit_with_error(&response);
                 }
             }
-            Err(e) => {
-                eprintln!("Failed to get last response: {}", e);
-                std::process::exit(1);
-            }
+            Err(e) => exit_unreachable("Failed to get last response", &e),
         },
         Commands::Create {
             message,
@@ -276,8 +424,7 @@ fn main() {
                 );
             }
             if let Err(e) = send_ipc("create", None, Some(serde_json::Value::Object(payload))) {
-                eprintln!("Failed to send create: {}", e);
-                std::process::exit(1);
+                exit_unreachable("Failed to send create", &e);
             } else {
                 // Ask for the created message back and print it
                 match send_ipc_with_response("last", None, None) {
@@ -317,9 +464,92 @@ fn main() {
             }
             Err(e) => {
                 eprintln!("Failed to execute command: {}", e);
-                std::process::exit(1);
+                std::process::exit(EXIT_FAILURE);
             }
         },
+        Commands::Usage {
+            range,
+            group_by,
+            json,
+        } => {
+            let payload = serde_json::json!({"range": range, "group_by": group_by});
+            match send_ipc_with_response("usage", None, Some(payload)) {
+                Ok(response) if response.status == "ok" => {
+                    let data = response.data.unwrap_or_default();
+                    if *json {
+                        println!(
+                            "{}",
+                            serde_json::to_string_pretty(&data).unwrap_or_default()
+                        );
+                        return;
+                    }
+                    match serde_json::from_value::<UsageReport>(data) {
+                        Ok(report) => print!("{}", format_usage_report(&report, group_by)),
+                        Err(e) => {
+                            eprintln!("Failed to parse usage report: {}", e);
+                            std::process::exit(EXIT_FAILURE);
+                        }
+                    }
+                }
+                Ok(response) => exit_with_error(&response),
+                Err(e) => exit_unreachable("Failed to get usage report", &e),
+            }
+        }
+        Commands::Export {
+            dir,
+            format,
+            tag,
+            query,
+            range,
+            ids,
+            whole_tree,
+            json,
+        } => {
+            // The app resolves paths from its own working directory
+            let dir = if dir.is_absolute() {
+                dir.clone()
+            } else {
+                match env::current_dir() {
+                    Ok(cwd) => cwd.join(dir),
+                    Err(e) => {
+                        eprintln!("Failed to resolve {}: {}", dir.display(), e);
+                        std::process::exit(EXIT_FAILURE);
+                    }
+                }
+            };
+            let payload = serde_json::json!({
+                "path": dir,
+                "format": format,
+                "filter": {
+                    "tag": tag,
+                    "query": query,
+                    "range": range,
+                    "conversation_ids": if ids.is_empty() { None } else { Some(ids) },
+                },
+                "whole_tree": whole_tree,
+            });
+            match send_ipc_with_timeout("export", None, Some(payload), EXPORT_TIMEOUT) {
+                Ok(response) if response.status == "ok" => {
+                    let data = response.data.unwrap_or_default();
+                    if *json {
+                        println!(
+                            "{}",
+                            serde_json::to_string_pretty(&data).unwrap_or_default()
+                        );
+                        return;
+                    }
+                    match serde_json::from_value::<DirectoryExport>(data) {
+                        Ok(export) => print!("{}", format_directory_export(&export)),
+                        Err(e) => {
+                            eprintln!("Failed to parse export result: {}", e);
+                            std::process::exit(EXIT_FAILURE);
+                        }
+                    }
+                }
+                Ok(response) => exit_with_error(&response),
+                Err(e) => exit_unreachable("Failed to export conversations", &e),
+            }
+        }
     }
 }
 
@@ -386,6 +616,16 @@ fn send_ipc_with_response(
     kind: &str,
     message: Option<&str>,
     payload: Option<serde_json::Value>,
+) -> Result<IpcResponse, String> {
+    send_ipc_with_timeout(kind, message, payload, IPC_TIMEOUT)
+}
+
+/// Like `send_ipc_with_response`, waiting up to `timeout` for the reply
+fn send_ipc_with_timeout(
+    kind: &str,
+    message: Option<&str>,
+    payload: Option<serde_json::Value>,
+    timeout: Duration,
 ) -> Result<IpcResponse, String> {
     let addr = "127.0.0.1:39871";
 
@@ -395,7 +635,7 @@ fn send_ipc_with_response(
 
     // Configure timeouts
     stream
-        .set_read_timeout(Some(IPC_TIMEOUT))
+        .set_read_timeout(Some(timeout))
         .map_err(|e| format!("set read timeout failed: {}", e))?;
     stream
         .set_write_timeout(Some(IPC_TIMEOUT))
@@ -564,18 +804,82 @@ fn read_stdin() -> Result<String, String> {
     Ok(content.trim().to_string())
 }
 
-fn handle_ask(message: &str, model: Option<&str>, provider: Option<&str>, new: bool, gui: bool) {
+/// Image extensions accepted by `--image`; the app checks the actual contents
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+
+/// Largest file the app accepts as an attachment (text files are limited
+/// to 1 MB there)
+const MAX_ATTACHMENT_BYTES: u64 = 20 * 1024 * 1024;
+
+/// A `--file` or `--image` argument as sent to the app, which never reads
+/// paths it is given
+#[derive(Serialize, Debug)]
+struct Attachment {
+    file_name: String,
+    data_base64: String,
+}
+
+/// Read the `--file` and `--image` arguments
+fn read_attachments(files: &[PathBuf], images: &[PathBuf]) -> Result<Vec<Attachment>, String> {
+    let is_image = |path: &Path| {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            .unwrap_or(false)
+    };
+    if let Some(path) = images.iter().find(|p| !is_image(p)) {
+        return Err(format!(
+            "Not an image: {} (expected {})",
+            path.display(),
+            IMAGE_EXTENSIONS.join(", ")
+        ));
+    }
+    files
+        .iter()
+        .chain(images)
+        .map(|path| {
+            let cannot_attach =
+                |e: &dyn std::fmt::Display| format!("Cannot attach {}: {}", path.display(), e);
+            let size = std::fs::metadata(path)
+                .map_err(|e| cannot_attach(&e))?
+                .len();
+            if size > MAX_ATTACHMENT_BYTES {
+                return Err(cannot_attach(&format!(
+                    "larger than {} MB",
+                    MAX_ATTACHMENT_BYTES / (1024 * 1024)
+                )));
+            }
+            let bytes = std::fs::read(path).map_err(|e| cannot_attach(&e))?;
+            Ok(Attachment {
+                file_name: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "attachment".to_string()),
+                data_base64: base64::engine::general_purpose::STANDARD.encode(bytes),
+            })
+        })
+        .collect()
+}
+
+fn handle_ask(
+    message: &str,
+    model: Option<&str>,
+    provider: Option<&str>,
+    new: bool,
+    gui: bool,
+    attachments: &[Attachment],
+) {
     let payload = serde_json::json!({
         "prompt": message,
         "model": model,
         "provider": provider,
         "new": new,
         "gui": gui,
+        "attachments": attachments,
     });
 
     if let Err(e) = send_ipc("ask", None, Some(payload)) {
-        eprintln!("Failed to send ask: {}", e);
-        std::process::exit(1);
+        exit_unreachable("Failed to send ask", &e);
     }
 
     if !gui {
@@ -601,28 +905,87 @@ fn handle_ask(message: &str, model: Option<&str>, provider: Option<&str>, new: b
                             }
                             Err(e) => {
                                 eprintln!("Failed to parse response: {}", e);
-                                std::process::exit(1);
+                                std::process::exit(EXIT_FAILURE);
                             }
                         }
                     } else {
                         eprintln!("No response data");
-                        std::process::exit(1);
+                        std::process::exit(EXIT_FAILURE);
                     }
                 } else {
-                    eprintln!("Request failed: {}", response.status);
-                    std::process::exit(1);
+                    exit_with_error(&response);
                 }
             }
-            Err(e) => {
-                eprintln!("Failed to get response: {}", e);
-                std::process::exit(1);
-            }
+            Err(e) => exit_unreachable("Failed to get response", &e),
         }
     } else {
         println!("Request sent. Check the GUI for the response.");
     }
 }
 
+/// Render a usage report as an aligned table with a totals line
+fn format_usage_report(report: &UsageReport, group_by: &str) -> String {
+    let mut chars = group_by.chars();
+    let header: String = chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default();
+    let width = report
+        .rows
+        .iter()
+        .map(|r| r.label.chars().count())
+        .chain([header.len(), "Total".len()])
+        .max()
+        .unwrap_or(5);
+
+    let line = |label: &str, row: &UsageRow| {
+        let unpriced = if row.unpriced_messages > 0 {
+            format!("  ({} unpriced)", row.unpriced_messages)
+        } else {
+            String::new()
+        };
+        format!(
+            "{:<width$}  {:>8}  {:>12}  {:>12}  {:>10}{}\n",
+            label,
+            row.messages,
+            row.prompt_tokens,
+            row.completion_tokens,
+            format!("${:.4}", row.cost_usd),
+            unpriced,
+        )
+    };
+
+    let mut out = format!(
+        "{:<width$}  {:>8}  {:>12}  {:>12}  {:>10}\n",
+        header, "Messages", "Prompt", "Completion", "Cost"
+    );
+    for row in &report.rows {
+        out.push_str(&line(&row.label, row));
+    }
+    out.push_str(&"-".repeat(width + 50));
+    out.push('\n');
+    out.push_str(&line("Total", &report.total));
+    out
+}
+
+/// Summarize a directory export: counts, then the files written and removed
+fn format_directory_export(export: &DirectoryExport) -> String {
+    let mut out = format!(
+        "{}: {} written, {} unchanged, {} removed\n",
+        export.directory,
+        export.written.len(),
+        export.unchanged,
+        export.removed.len()
+    );
+    for file in &export.written {
+        out.push_str(&format!("  + {}\n", file));
+    }
+    for file in &export.removed {
+        out.push_str(&format!("  - {}\n", file));
+    }
+    out
+}
+
 fn display_capture_result(result: &CaptureResult) {
     println!("Command: {}", result.command);
     println!("Working Directory: {}", result.working_dir);
@@ -702,6 +1065,29 @@ fn display_capture_analysis(result: &CaptureResult, use_ai: bool) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_read_attachments() {
+        let dir = std::env::temp_dir().join(format!("lai-attach-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("build.log");
+        let shot = dir.join("shot.PNG");
+        std::fs::write(&log, "error").unwrap();
+        std::fs::write(&shot, [0x89, b'P', b'N', b'G']).unwrap();
+
+        let attachments = read_attachments(std::slice::from_ref(&log), &[shot]).unwrap();
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0].file_name, "build.log");
+        assert_eq!(attachments[0].data_base64, "ZXJyb3I=");
+        assert_eq!(attachments[1].file_name, "shot.PNG");
+
+        assert!(read_attachments(&[], &[log])
+            .unwrap_err()
+            .starts_with("Not an image"));
+        assert!(read_attachments(&[dir.join("missing.txt")], &[]).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
     #[test]
     fn test_ipc_message_serialization() {
         let msg = IpcMessage {
@@ -762,6 +1148,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_error_codes_map_to_exit_codes() {
+        let json = r#"{"status":"error","data":{"error":"Conversation not found","code":"not_found","message":"Conversation not found"}}"#;
+        let response: IpcResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.error_code(), "not_found");
+        assert_eq!(response.error_message(), "Conversation not found");
+        assert_eq!(exit_code_for(response.error_code()), 3);
+
+        // Older app versions send only `error`
+        let legacy: IpcResponse =
+            serde_json::from_str(r#"{"status":"error","data":{"error":"boom"}}"#).unwrap();
+        assert_eq!(legacy.error_code(), "internal");
+        assert_eq!(legacy.error_message(), "boom");
+        assert_eq!(exit_code_for(legacy.error_code()), EXIT_FAILURE);
+
+        let codes = [
+            "invalid_input",
+            "not_found",
+            "auth",
+            "rate_limited",
+            "budget_exceeded",
+            "network",
+            "database",
+            "database_locked",
+            "context_overflow",
+            "provider",
+            "conflict",
+            "io",
+            "unsupported",
+        ];
+        let mut exits: Vec<i32> = codes.iter().map(|c| exit_code_for(c)).collect();
+        exits.dedup();
+        assert_eq!(exits, (2..=14).collect::<Vec<_>>());
+        assert!(!exits.contains(&EXIT_UNREACHABLE));
+        assert_eq!(exit_code_for("locked"), 16);
+    }
+
     #[test]
     fn test_capture_result_serialization() {
         let result = CaptureResult {
@@ -805,6 +1228,45 @@ mod tests {
         assert!(!capture.timed_out);
     }
 
+    #[test]
+    fn test_format_usage_report() {
+        let json = r#"{
+            "rows": [
+                {"label": "gpt-4o", "messages": 2, "prompt_tokens": 1000, "completion_tokens": 200, "cost_usd": 0.0045},
+                {"label": "my-finetune", "messages": 1, "prompt_tokens": 10, "completion_tokens": 5, "cost_usd": 0.0, "unpriced_messages": 1}
+            ],
+            "total": {"label": "Total", "messages": 3, "prompt_tokens": 1010, "completion_tokens": 205, "cost_usd": 0.0045, "unpriced_messages": 1}
+        }"#;
+        let report: UsageReport = serde_json::from_str(json).expect("report should parse");
+
+        let table = format_usage_report(&report, "model");
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("Model"));
+        assert!(lines[1].starts_with("gpt-4o") && lines[1].contains("$0.0045"));
+        assert!(lines[2].ends_with("(1 unpriced)"));
+        assert!(lines[4].starts_with("Total"));
+    }
+
+    #[test]
+    fn test_format_directory_export() {
+        let json = r#"{
+            "directory": "/home/me/notes",
+            "written": ["rust-tips-1a2b3c4d.md"],
+            "unchanged": 4,
+            "removed": ["old-title-1a2b3c4d.md"]
+        }"#;
+        let export: DirectoryExport = serde_json::from_str(json).expect("result should parse");
+
+        let summary = format_directory_export(&export);
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(
+            lines[0],
+            "/home/me/notes: 1 written, 4 unchanged, 1 removed"
+        );
+        assert_eq!(lines[1], "  + rust-tips-1a2b3c4d.md");
+        assert_eq!(lines[2], "  - old-title-1a2b3c4d.md");
+    }
+
     // Integration test that requires a running backend
     #[test]
     #[ignore] // Ignored by default since it requires backend to be running
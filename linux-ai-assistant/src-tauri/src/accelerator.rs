@@ -0,0 +1,487 @@
+//! Parser for global shortcut strings such as `CommandOrControl+Shift+Space`.
+//!
+//! Shortcuts are parsed into an [`Accelerator`]: one or more key combinations
+//! ("chords", separated by whitespace as in `Ctrl+K Ctrl+S`), each made of
+//! modifiers and a single key. Its `Display` form is the normalized string we
+//! store, and [`Accelerator::to_plugin_string`] gives the string handed to
+//! `tauri-plugin-global-shortcut`.
+
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Keys by canonical name (as accepted by the global-shortcut plugin) with
+/// the extra spellings we accept. Matching is case-insensitive.
+const KEYS: &[(&str, &[&str])] = &[
+    ("A", &[]),
+    ("B", &[]),
+    ("C", &[]),
+    ("D", &[]),
+    ("E", &[]),
+    ("F", &[]),
+    ("G", &[]),
+    ("H", &[]),
+    ("I", &[]),
+    ("J", &[]),
+    ("K", &[]),
+    ("L", &[]),
+    ("M", &[]),
+    ("N", &[]),
+    ("O", &[]),
+    ("P", &[]),
+    ("Q", &[]),
+    ("R", &[]),
+    ("S", &[]),
+    ("T", &[]),
+    ("U", &[]),
+    ("V", &[]),
+    ("W", &[]),
+    ("X", &[]),
+    ("Y", &[]),
+    ("Z", &[]),
+    ("0", &["Digit0"]),
+    ("1", &["Digit1"]),
+    ("2", &["Digit2"]),
+    ("3", &["Digit3"]),
+    ("4", &["Digit4"]),
+    ("5", &["Digit5"]),
+    ("6", &["Digit6"]),
+    ("7", &["Digit7"]),
+    ("8", &["Digit8"]),
+    ("9", &["Digit9"]),
+    ("Space", &[]),
+    ("Enter", &["Return"]),
+    ("Tab", &[]),
+    ("Backspace", &[]),
+    ("Delete", &["Del"]),
+    ("Insert", &["Ins"]),
+    ("Escape", &["Esc"]),
+    ("Home", &[]),
+    ("End", &[]),
+    ("PageUp", &["PgUp"]),
+    ("PageDown", &["PgDn"]),
+    ("Up", &["ArrowUp"]),
+    ("Down", &["ArrowDown"]),
+    ("Left", &["ArrowLeft"]),
+    ("Right", &["ArrowRight"]),
+    ("Comma", &[","]),
+    ("Period", &["."]),
+    ("Slash", &["/"]),
+    ("Backslash", &["\\"]),
+    ("Semicolon", &[";"]),
+    ("Quote", &["'"]),
+    ("Backquote", &["`", "Grave"]),
+    ("BracketLeft", &["["]),
+    ("BracketRight", &["]"]),
+    ("Minus", &["-"]),
+    ("Equal", &["="]),
+    ("CapsLock", &[]),
+    ("NumLock", &[]),
+    ("ScrollLock", &[]),
+    ("PrintScreen", &["Print"]),
+    ("Pause", &[]),
+    ("Numpad0", &["Num0"]),
+    ("Numpad1", &["Num1"]),
+    ("Numpad2", &["Num2"]),
+    ("Numpad3", &["Num3"]),
+    ("Numpad4", &["Num4"]),
+    ("Numpad5", &["Num5"]),
+    ("Numpad6", &["Num6"]),
+    ("Numpad7", &["Num7"]),
+    ("Numpad8", &["Num8"]),
+    ("Numpad9", &["Num9"]),
+    ("NumpadAdd", &["NumAdd"]),
+    ("NumpadSubtract", &["NumSubtract"]),
+    ("NumpadMultiply", &["NumMultiply"]),
+    ("NumpadDivide", &["NumDivide"]),
+    ("NumpadDecimal", &["NumDecimal"]),
+    ("NumpadEnter", &["NumEnter"]),
+    ("F1", &[]),
+    ("F2", &[]),
+    ("F3", &[]),
+    ("F4", &[]),
+    ("F5", &[]),
+    ("F6", &[]),
+    ("F7", &[]),
+    ("F8", &[]),
+    ("F9", &[]),
+    ("F10", &[]),
+    ("F11", &[]),
+    ("F12", &[]),
+    ("F13", &[]),
+    ("F14", &[]),
+    ("F15", &[]),
+    ("F16", &[]),
+    ("F17", &[]),
+    ("F18", &[]),
+    ("F19", &[]),
+    ("F20", &[]),
+    ("F21", &[]),
+    ("F22", &[]),
+    ("F23", &[]),
+    ("F24", &[]),
+    ("AudioVolumeUp", &["VolumeUp"]),
+    ("AudioVolumeDown", &["VolumeDown"]),
+    ("AudioVolumeMute", &["VolumeMute"]),
+    ("MediaPlayPause", &[]),
+    ("MediaStop", &[]),
+    ("MediaTrackNext", &[]),
+    ("MediaTrackPrevious", &["MediaTrackPrev"]),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AcceleratorError {
+    Empty,
+    /// A `+` with nothing on one side, e.g. `Ctrl++K`
+    EmptyToken(String),
+    UnknownKey(String),
+    /// Only modifiers, e.g. `Ctrl+Shift`
+    MissingKey(String),
+    /// More than one non-modifier key in a combination, e.g. `Ctrl+A+B`
+    MultipleKeys(String),
+    /// A plain key such as `K`, which would swallow normal typing
+    MissingModifier(String),
+    /// Chord sequences can be stored but not bound as OS-wide shortcuts
+    ChordUnsupported(String),
+}
+
+impl fmt::Display for AcceleratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Shortcut cannot be empty"),
+            Self::EmptyToken(s) => write!(f, "Empty key in shortcut: {}", s),
+            Self::UnknownKey(key) => write!(f, "Unknown key: {}", key),
+            Self::MissingKey(s) => write!(f, "Shortcut has no main key: {}", s),
+            Self::MultipleKeys(s) => write!(f, "Shortcut has more than one main key: {}", s),
+            Self::MissingModifier(s) => write!(
+                f,
+                "Shortcut needs a modifier (CommandOrControl, Control, Alt, Shift or Super): {}",
+                s
+            ),
+            Self::ChordUnsupported(s) => {
+                write!(
+                    f,
+                    "Chord sequences can't be used as global shortcuts: {}",
+                    s
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for AcceleratorError {}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct Modifiers {
+    /// `CommandOrControl`: Control on Linux, Command on macOS
+    pub command_or_control: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// Super/Meta/Windows key
+    pub super_key: bool,
+}
+
+impl Modifiers {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Set the modifier named by `token`; false when it isn't a modifier
+    fn set(&mut self, token: &str) -> bool {
+        match token.to_ascii_lowercase().as_str() {
+            "commandorcontrol" | "commandorctrl" | "cmdorctrl" | "cmdorcontrol" => {
+                self.command_or_control = true
+            }
+            "control" | "ctrl" => self.control = true,
+            "alt" | "option" => self.alt = true,
+            "shift" => self.shift = true,
+            "super" | "meta" | "command" | "cmd" | "win" => self.super_key = true,
+            _ => return false,
+        }
+        true
+    }
+
+    fn names(&self) -> Vec<&'static str> {
+        [
+            (self.command_or_control, "CommandOrControl"),
+            (self.control, "Control"),
+            (self.alt, "Alt"),
+            (self.shift, "Shift"),
+            (self.super_key, "Super"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect()
+    }
+}
+
+/// One key combination: modifiers plus a key, e.g. `Control+Shift+K`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct KeyCombo {
+    pub modifiers: Modifiers,
+    /// Canonical key name from the key table
+    pub key: &'static str,
+}
+
+impl KeyCombo {
+    fn parse(step: &str) -> Result<Self, AcceleratorError> {
+        let mut modifiers = Modifiers::default();
+        let mut key = None;
+        for token in step.split('+') {
+            let token = token.trim();
+            if token.is_empty() {
+                return Err(AcceleratorError::EmptyToken(step.to_string()));
+            }
+            if modifiers.set(token) {
+                continue;
+            }
+            let canonical =
+                lookup_key(token).ok_or_else(|| AcceleratorError::UnknownKey(token.to_string()))?;
+            if key.replace(canonical).is_some() {
+                return Err(AcceleratorError::MultipleKeys(step.to_string()));
+            }
+        }
+        let key = key.ok_or_else(|| AcceleratorError::MissingKey(step.to_string()))?;
+        if modifiers.is_empty() && !is_standalone(key) {
+            return Err(AcceleratorError::MissingModifier(step.to_string()));
+        }
+        Ok(Self { modifiers, key })
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in self.modifiers.names() {
+            write!(f, "{}+", name)?;
+        }
+        f.write_str(self.key)
+    }
+}
+
+/// A parsed shortcut: a single combination or a chord sequence
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Accelerator {
+    pub steps: Vec<KeyCombo>,
+}
+
+impl Accelerator {
+    pub fn parse(input: &str) -> Result<Self, AcceleratorError> {
+        let steps = split_steps(input)
+            .iter()
+            .map(|step| KeyCombo::parse(step))
+            .collect::<Result<Vec<_>, _>>()?;
+        if steps.is_empty() {
+            return Err(AcceleratorError::Empty);
+        }
+        Ok(Self { steps })
+    }
+
+    pub fn is_chord(&self) -> bool {
+        self.steps.len() > 1
+    }
+
+    /// The string `tauri-plugin-global-shortcut` parses. The plugin binds
+    /// single combinations only.
+    pub fn to_plugin_string(&self) -> Result<String, AcceleratorError> {
+        if self.is_chord() {
+            return Err(AcceleratorError::ChordUnsupported(self.to_string()));
+        }
+        Ok(self.steps[0].to_string())
+    }
+
+    /// The `preferred_trigger` for the XDG GlobalShortcuts portal, which uses
+    /// the shortcuts spec format (`CTRL+SHIFT+space`, XKB key names)
+    pub fn to_portal_trigger(&self) -> Result<String, AcceleratorError> {
+        if self.is_chord() {
+            return Err(AcceleratorError::ChordUnsupported(self.to_string()));
+        }
+        let combo = &self.steps[0];
+        let m = combo.modifiers;
+        let mut parts: Vec<String> = [
+            (m.command_or_control || m.control, "CTRL"),
+            (m.alt, "ALT"),
+            (m.shift, "SHIFT"),
+            (m.super_key, "LOGO"),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| name.to_string())
+        .collect();
+        parts.push(keysym(combo.key));
+        Ok(parts.join("+"))
+    }
+}
+
+/// XKB keysym name for a canonical key
+fn keysym(key: &str) -> String {
+    let name = match key {
+        "Enter" => "Return",
+        "Backspace" => "BackSpace",
+        "PageUp" => "Page_Up",
+        "PageDown" => "Page_Down",
+        "CapsLock" => "Caps_Lock",
+        "NumLock" => "Num_Lock",
+        "ScrollLock" => "Scroll_Lock",
+        "PrintScreen" => "Print",
+        "Space" => "space",
+        "Comma" => "comma",
+        "Period" => "period",
+        "Slash" => "slash",
+        "Backslash" => "backslash",
+        "Semicolon" => "semicolon",
+        "Quote" => "apostrophe",
+        "Backquote" => "grave",
+        "BracketLeft" => "bracketleft",
+        "BracketRight" => "bracketright",
+        "Minus" => "minus",
+        "Equal" => "equal",
+        "NumpadAdd" => "KP_Add",
+        "NumpadSubtract" => "KP_Subtract",
+        "NumpadMultiply" => "KP_Multiply",
+        "NumpadDivide" => "KP_Divide",
+        "NumpadDecimal" => "KP_Decimal",
+        "NumpadEnter" => "KP_Enter",
+        "AudioVolumeUp" => "XF86AudioRaiseVolume",
+        "AudioVolumeDown" => "XF86AudioLowerVolume",
+        "AudioVolumeMute" => "XF86AudioMute",
+        "MediaPlayPause" => "XF86AudioPlay",
+        "MediaStop" => "XF86AudioStop",
+        "MediaTrackNext" => "XF86AudioNext",
+        "MediaTrackPrevious" => "XF86AudioPrev",
+        _ => {
+            if let Some(digit) = key.strip_prefix("Numpad") {
+                return format!("KP_{}", digit);
+            }
+            if key.len() == 1 {
+                return key.to_ascii_lowercase();
+            }
+            key
+        }
+    };
+    name.to_string()
+}
+
+impl FromStr for Accelerator {
+    type Err = AcceleratorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Accelerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps: Vec<String> = self.steps.iter().map(|s| s.to_string()).collect();
+        f.write_str(&steps.join(" "))
+    }
+}
+
+/// Normalized form of a shortcut string
+pub fn normalize(input: &str) -> Result<String, AcceleratorError> {
+    Accelerator::parse(input).map(|a| a.to_string())
+}
+
+/// Split on whitespace into chord steps, keeping `Ctrl + K` together
+fn split_steps(input: &str) -> Vec<String> {
+    let mut steps: Vec<String> = Vec::new();
+    for token in input.split_whitespace() {
+        match steps.last_mut() {
+            Some(last) if last.ends_with('+') || token.starts_with('+') => last.push_str(token),
+            _ => steps.push(token.to_string()),
+        }
+    }
+    steps
+}
+
+fn lookup_key(token: &str) -> Option<&'static str> {
+    KEYS.iter()
+        .find(|(name, aliases)| {
+            name.eq_ignore_ascii_case(token)
+                || aliases.iter().any(|a| a.eq_ignore_ascii_case(token))
+        })
+        .map(|(name, _)| *name)
+}
+
+/// Function and media keys may be bound without a modifier
+fn is_standalone(key: &str) -> bool {
+    key.starts_with("Audio")
+        || key.starts_with("Media")
+        || (key.len() > 1 && key.starts_with('F') && key[1..].chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::shortcuts::ShortcutAction;
+    use tauri_plugin_global_shortcut::Shortcut;
+
+    #[test]
+    fn defaults_round_trip_and_match_the_plugin() {
+        for action in ShortcutAction::all_actions() {
+            let default = action.default_shortcut();
+            let parsed = Accelerator::parse(default).unwrap();
+            assert_eq!(parsed.to_string(), default);
+            assert_eq!(Accelerator::parse(&parsed.to_string()).unwrap(), parsed);
+
+            let plugin = parsed.to_plugin_string().unwrap();
+            assert_eq!(
+                Shortcut::from_str(&plugin).unwrap(),
+                Shortcut::from_str(default).unwrap(),
+                "{}",
+                default
+            );
+        }
+    }
+
+    #[test]
+    fn normalizes_aliases_order_and_chords() {
+        assert_eq!(normalize("shift + ctrl+k").unwrap(), "Control+Shift+K");
+        assert_eq!(normalize("CmdOrCtrl+,").unwrap(), "CommandOrControl+Comma");
+        assert_eq!(normalize("meta+esc").unwrap(), "Super+Escape");
+        assert_eq!(normalize("F5").unwrap(), "F5");
+
+        let chord = Accelerator::parse("Ctrl+K  ctrl+s").unwrap();
+        assert!(chord.is_chord());
+        assert_eq!(chord.to_string(), "Control+K Control+S");
+        assert!(matches!(
+            chord.to_plugin_string(),
+            Err(AcceleratorError::ChordUnsupported(_))
+        ));
+    }
+
+    #[test]
+    fn portal_triggers_use_xkb_names() {
+        let trigger = |s: &str| Accelerator::parse(s).unwrap().to_portal_trigger().unwrap();
+        assert_eq!(trigger("CommandOrControl+Space"), "CTRL+space");
+        assert_eq!(trigger("CommandOrControl+Shift+I"), "CTRL+SHIFT+i");
+        assert_eq!(trigger("Super+Alt+PageUp"), "ALT+LOGO+Page_Up");
+        assert_eq!(trigger("Ctrl+Numpad5"), "CTRL+KP_5");
+        assert_eq!(trigger("F12"), "F12");
+    }
+
+    #[test]
+    fn rejects_malformed_shortcuts() {
+        assert_eq!(normalize("  "), Err(AcceleratorError::Empty));
+        assert_eq!(
+            normalize("Ctrl+Foo"),
+            Err(AcceleratorError::UnknownKey("Foo".to_string()))
+        );
+        assert!(matches!(
+            normalize("Ctrl+Shift"),
+            Err(AcceleratorError::MissingKey(_))
+        ));
+        assert!(matches!(
+            normalize("Ctrl+A+B"),
+            Err(AcceleratorError::MultipleKeys(_))
+        ));
+        assert!(matches!(
+            normalize("Ctrl++"),
+            Err(AcceleratorError::EmptyToken(_))
+        ));
+        assert!(matches!(
+            normalize("K"),
+            Err(AcceleratorError::MissingModifier(_))
+        ));
+    }
+}
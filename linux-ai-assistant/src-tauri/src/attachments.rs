@@ -0,0 +1,379 @@
+// Message attachments: a content-addressed blob store under the app data dir,
+// type detection for attached bytes, and resolution of attachment ids into
+// what each provider request needs (inlined text, base64 images).
+
+use crate::commands::provider::ProviderMessage;
+use crate::database::attachments::{Attachment, NewAttachment};
+use crate::database::messages::Message;
+use crate::error::{AppError, AppResult};
+use base64::Engine;
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Largest image accepted (most providers reject bigger inline images)
+pub const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+/// Largest text file accepted; its contents are inlined into the prompt
+pub const MAX_TEXT_BYTES: usize = 1024 * 1024;
+
+/// An image ready to be sent inline to a provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineImage {
+    pub mime_type: String,
+    /// Base64 (standard alphabet) encoded bytes
+    pub data: String,
+}
+
+impl InlineImage {
+    pub fn data_uri(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data)
+    }
+}
+
+/// Blobs stored once per content hash as `<root>/<first two hex chars>/<rest>`
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+impl BlobStore {
+    pub fn new(root: PathBuf) -> Self {
+        BlobStore { root }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Path of the blob for a hex sha256 digest
+    pub fn path(&self, sha256: &str) -> AppResult<PathBuf> {
+        if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(AppError::invalid_input(format!(
+                "Invalid blob hash: {}",
+                sha256
+            )));
+        }
+        Ok(self.root.join(&sha256[..2]).join(&sha256[2..]))
+    }
+
+    /// Store `bytes` and return their sha256; existing blobs are not rewritten
+    pub fn put(&self, bytes: &[u8]) -> AppResult<String> {
+        let sha256 = hex_digest(bytes);
+        let path = self.path(&sha256)?;
+        if !path.exists() {
+            let dir = path.parent().unwrap_or(&self.root);
+            std::fs::create_dir_all(dir)
+                .map_err(|e| AppError::from(e).context("Failed to create blob directory"))?;
+            // Write then rename so a crash never leaves a truncated blob under its hash
+            let tmp = dir.join(format!(".{}.tmp", &sha256[2..]));
+            std::fs::write(&tmp, bytes)
+                .map_err(|e| AppError::from(e).context("Failed to write blob"))?;
+            std::fs::rename(&tmp, &path)
+                .map_err(|e| AppError::from(e).context("Failed to write blob"))?;
+        }
+        Ok(sha256)
+    }
+
+    pub fn get(&self, sha256: &str) -> AppResult<Vec<u8>> {
+        std::fs::read(self.path(sha256)?)
+            .map_err(|e| AppError::from(e).context(format!("Blob {}", sha256)))
+    }
+
+    pub fn remove(&self, sha256: &str) -> AppResult<()> {
+        match std::fs::remove_file(self.path(sha256)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn hex_digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn encode_base64(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+pub fn decode_base64(data: &str) -> AppResult<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| AppError::invalid_input(format!("Invalid base64 data: {}", e)))
+}
+
+/// Image formats every supported provider accepts inline
+fn image_mime(bytes: &[u8]) -> Option<&'static str> {
+    use image::ImageFormat;
+    match image::guess_format(bytes).ok()? {
+        ImageFormat::Png => Some("image/png"),
+        ImageFormat::Jpeg => Some("image/jpeg"),
+        ImageFormat::Gif => Some("image/gif"),
+        ImageFormat::WebP => Some("image/webp"),
+        _ => None,
+    }
+}
+
+fn text_mime(file_name: &str) -> &'static str {
+    let ext = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match ext.as_str() {
+        "md" | "markdown" => "text/markdown",
+        "json" => "application/json",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "toml" => "application/toml",
+        _ => "text/plain",
+    }
+}
+
+/// Kind (`image` or `file`) and mime type of attached bytes. Images are
+/// recognised by content; anything else must be UTF-8 text.
+pub fn detect(file_name: &str, bytes: &[u8]) -> AppResult<(&'static str, &'static str)> {
+    if let Some(mime) = image_mime(bytes) {
+        if bytes.len() > MAX_IMAGE_BYTES {
+            return Err(AppError::invalid_input(format!(
+                "Image {} is larger than {} MB",
+                file_name,
+                MAX_IMAGE_BYTES / (1024 * 1024)
+            )));
+        }
+        return Ok(("image", mime));
+    }
+    if std::str::from_utf8(bytes).is_err() {
+        return Err(AppError::unsupported(format!(
+            "{} is neither a supported image (PNG, JPEG, GIF, WebP) nor a UTF-8 text file",
+            file_name
+        )));
+    }
+    if bytes.len() > MAX_TEXT_BYTES {
+        return Err(AppError::invalid_input(format!(
+            "Text file {} is larger than {} KB",
+            file_name,
+            MAX_TEXT_BYTES / 1024
+        )));
+    }
+    Ok(("file", text_mime(file_name)))
+}
+
+/// Validate `bytes`, put them in the blob store and record the attachment
+pub fn store(
+    conn: &Connection,
+    blobs: &BlobStore,
+    message_id: &str,
+    file_name: &str,
+    bytes: &[u8],
+) -> AppResult<Attachment> {
+    if Message::get_by_id(conn, message_id)?.is_none() {
+        return Err(AppError::not_found(format!(
+            "Message not found: {}",
+            message_id
+        )));
+    }
+    let (kind, mime_type) = detect(file_name, bytes)?;
+    let sha256 = blobs.put(bytes)?;
+    Attachment::create(
+        conn,
+        NewAttachment {
+            message_id: message_id.to_string(),
+            kind: kind.to_string(),
+            file_name: file_name.to_string(),
+            mime_type: mime_type.to_string(),
+            size_bytes: bytes.len() as i64,
+            sha256,
+        },
+    )
+    .map_err(AppError::from)
+}
+
+/// Delete an attachment, and its blob once nothing else references it
+pub fn remove(conn: &Connection, blobs: &BlobStore, id: &str) -> AppResult<()> {
+    let attachment = Attachment::get_by_id(conn, id)?
+        .ok_or_else(|| AppError::not_found(format!("Attachment not found: {}", id)))?;
+    remove_all(conn, blobs, vec![attachment])
+}
+
+/// Delete attachments (e.g. of a deleted message or conversation), and the
+/// blobs nothing else references anymore
+pub fn remove_all(
+    conn: &Connection,
+    blobs: &BlobStore,
+    attachments: Vec<Attachment>,
+) -> AppResult<()> {
+    for attachment in &attachments {
+        Attachment::delete(conn, &attachment.id)?;
+    }
+    for attachment in &attachments {
+        if Attachment::count_by_sha256(conn, &attachment.sha256)? == 0 {
+            blobs.remove(&attachment.sha256)?;
+        }
+    }
+    Ok(())
+}
+
+/// Text file contents as a fenced block the model can read
+pub fn text_block(file_name: &str, content: &str) -> String {
+    let fence = if content.contains("```") {
+        "````"
+    } else {
+        "```"
+    };
+    format!(
+        "Attached file `{}`:\n{}\n{}\n{}",
+        file_name,
+        fence,
+        content.trim_end(),
+        fence
+    )
+}
+
+/// Load the attachments named on each message: text files are appended to the
+/// content, images are loaded for the provider's inline image format.
+/// Attachments that can't be read are skipped with a log line.
+pub fn resolve(
+    conn: &Connection,
+    blobs: &BlobStore,
+    mut messages: Vec<ProviderMessage>,
+) -> Vec<ProviderMessage> {
+    for message in messages.iter_mut() {
+        for id in &message.attachments {
+            let loaded = Attachment::get_by_id(conn, id)
+                .map_err(AppError::from)
+                .and_then(|a| {
+                    a.ok_or_else(|| AppError::not_found(format!("Attachment not found: {}", id)))
+                })
+                .and_then(|a| blobs.get(&a.sha256).map(|bytes| (a, bytes)));
+            match loaded {
+                Ok((a, bytes)) if a.kind == "image" => message.images.push(InlineImage {
+                    mime_type: a.mime_type,
+                    data: encode_base64(&bytes),
+                }),
+                Ok((a, bytes)) => {
+                    let text = String::from_utf8_lossy(&bytes);
+                    if !message.content.is_empty() {
+                        message.content.push_str("\n\n");
+                    }
+                    message.content.push_str(&text_block(&a.file_name, &text));
+                }
+                Err(e) => eprintln!("skipping attachment {}: {}", id, e),
+            }
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::conversations::{Conversation, NewConversation};
+    use crate::database::messages::NewMessage;
+    use crate::database::Database;
+
+    // Smallest valid PNG header: signature plus the start of an IHDR chunk
+    const PNG: &[u8] = &[
+        0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0, 0, 0, 13, b'I', b'H', b'D', b'R',
+    ];
+
+    #[test]
+    fn blob_store_dedups_by_content() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let blobs = BlobStore::new(dir.path().join("blobs"));
+
+        let a = blobs.put(b"same bytes").expect("put");
+        let b = blobs.put(b"same bytes").expect("put again");
+        assert_eq!(a, b);
+        assert_eq!(blobs.get(&a).expect("get"), b"same bytes");
+        assert!(blobs
+            .path(&a)
+            .unwrap()
+            .starts_with(dir.path().join("blobs").join(&a[..2])));
+        assert!(blobs.path("../../etc/passwd").is_err());
+
+        blobs.remove(&a).expect("remove");
+        assert!(blobs.get(&a).is_err());
+        blobs.remove(&a).expect("removing twice is fine");
+    }
+
+    #[test]
+    fn detect_images_and_text() {
+        assert_eq!(detect("x.png", PNG).unwrap(), ("image", "image/png"));
+        assert_eq!(
+            detect("notes.md", b"# hi").unwrap(),
+            ("file", "text/markdown")
+        );
+        let err = detect("bin", &[0xff, 0xfe, 0x00, 0x9f]).unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::Unsupported);
+    }
+
+    #[test]
+    fn resolve_inlines_text_and_loads_images() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let blobs = BlobStore::new(dir.path().to_path_buf());
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+        let conv = Conversation::create(
+            &conn,
+            NewConversation {
+                title: "Attachments".to_string(),
+                model: "gpt-test".to_string(),
+                provider: "local".to_string(),
+                system_prompt: None,
+            },
+        )
+        .expect("create conv");
+        let msg = Message::create(
+            &conn,
+            NewMessage {
+                conversation_id: conv.id.clone(),
+                role: "user".to_string(),
+                content: "What's in these?".to_string(),
+                tokens_used: None,
+            },
+        )
+        .expect("create msg");
+
+        let text = store(&conn, &blobs, &msg.id, "main.rs", b"fn main() {}").expect("text");
+        let image = store(&conn, &blobs, &msg.id, "shot.png", PNG).expect("image");
+        assert_eq!(image.kind, "image");
+        assert!(store(&conn, &blobs, "missing", "a.txt", b"x").is_err());
+
+        let resolved = resolve(
+            &conn,
+            &blobs,
+            vec![ProviderMessage {
+                role: "user".to_string(),
+                content: msg.content.clone(),
+                attachments: vec![text.id.clone(), image.id.clone()],
+                ..Default::default()
+            }],
+        );
+        assert!(resolved[0]
+            .content
+            .ends_with("Attached file `main.rs`:\n```\nfn main() {}\n```"));
+        assert_eq!(resolved[0].images.len(), 1);
+        assert_eq!(resolved[0].images[0].mime_type, "image/png");
+        assert_eq!(decode_base64(&resolved[0].images[0].data).unwrap(), PNG);
+
+        // The blob stays while another attachment shares it
+        let copy = store(&conn, &blobs, &msg.id, "copy.rs", b"fn main() {}").expect("copy");
+        remove(&conn, &blobs, &text.id).expect("remove");
+        assert!(blobs.get(&copy.sha256).is_ok());
+        remove(&conn, &blobs, &copy.id).expect("remove copy");
+        assert!(blobs.get(&copy.sha256).is_err());
+
+        // Deleting the message's attachments sweeps the image blob too
+        let attached = Attachment::get_by_message(&conn, &msg.id).expect("attachments");
+        assert_eq!(attached.len(), 1);
+        remove_all(&conn, &blobs, attached).expect("remove all");
+        assert!(Attachment::get_by_message(&conn, &msg.id)
+            .unwrap()
+            .is_empty());
+        assert!(blobs.get(&image.sha256).is_err());
+    }
+}
@@ -0,0 +1,1080 @@
+//! Whole-database backups.
+//!
+//! A backup is a zip archive holding `backup.json` (a manifest), a plaintext
+//! snapshot of `database.db` taken with SQLite's online backup API (exported
+//! instead when the database is encrypted, see `Database::snapshot`) and every
+//! attachment blob the snapshot references, stored as `blobs/<sha256>`.
+//! Settings, profiles, tags and templates live in the database, so they come
+//! along.
+//!
+//! With a passphrase the archive is encrypted: the key is derived with
+//! PBKDF2-HMAC-SHA256 under a random salt and the archive is sealed in 64 KiB
+//! ChaCha20-Poly1305 chunks whose nonces count up and flag the final chunk, so
+//! a reordered, truncated or altered file fails to open.
+//!
+//! An encrypted database is only backed up with a passphrase. The plaintext
+//! a backup passes through (the snapshot, the archive before encryption, the
+//! unpacked archive when restoring) is kept in a directory under
+//! `<app data>/staging` that only the user can read.
+//!
+//! Restoring checks everything before live data is touched: the passphrase,
+//! the manifest version, the snapshot's hash and `PRAGMA integrity_check`, and
+//! each blob's hash. Missing blobs are then added to the store and the snapshot
+//! replaces the open database through the backup API, keeping its encryption,
+//! after which the schema is brought up to date.
+//!
+//! Automatic backups are written daily into `<app data>/backups` (or a chosen
+//! directory) as `auto-<UTC time>.laibackup`; only the newest `keep` of those
+//! are kept.
+
+use crate::attachments::BlobStore;
+use crate::database::settings::Setting;
+use crate::database::Database;
+use crate::error::{AppError, AppResult, ErrorCode};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305};
+use ring::rand::{SecureRandom, SystemRandom};
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const BACKUP_EXTENSION: &str = "laibackup";
+/// Newest archive layout this build reads and the one it writes
+const FORMAT_VERSION: u32 = 1;
+const MANIFEST_ENTRY: &str = "backup.json";
+const DATABASE_ENTRY: &str = "database.db";
+const BLOB_PREFIX: &str = "blobs/";
+/// Under the app data directory; holds plaintext while backing up and restoring
+const STAGING_DIR: &str = "staging";
+
+const SETTINGS_KEY: &str = "backup_settings";
+/// Keyring entry holding the passphrase for automatic backups
+const KEYRING_ENTRY: &str = "backup-passphrase";
+const AUTO_PREFIX: &str = "auto-";
+const BACKUP_INTERVAL: i64 = 24 * 60 * 60;
+/// How often the scheduler checks whether a backup is due
+const BACKUP_POLL: Duration = Duration::from_secs(10 * 60);
+
+/// Starts every encrypted backup; a zip archive starts with `PK`
+const MAGIC: &[u8; 16] = b"lai-backup-enc1\n";
+const SALT_LEN: usize = 16;
+/// Magic, salt and the big-endian PBKDF2 iteration count
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + 4;
+/// Kept low in tests, where unoptimized PBKDF2 takes seconds per key
+const KDF_ITERATIONS: u32 = if cfg!(test) { 1_000 } else { 600_000 };
+/// Bounds the work a crafted header can ask for
+const MAX_KDF_ITERATIONS: u32 = 10_000_000;
+const CHUNK_LEN: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+
+/// Describes a backup; stored in the archive as `backup.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    /// Version of the app that wrote the backup
+    pub app_version: String,
+    pub created_at: i64,
+    /// sha256 of the `database.db` entry
+    pub database_sha256: String,
+    /// Including conversations in the trash
+    pub conversations: i64,
+    /// Attachment blobs in the archive
+    pub blobs: usize,
+}
+
+/// A backup that was just written
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupInfo {
+    pub path: String,
+    pub encrypted: bool,
+    pub size_bytes: u64,
+    pub manifest: BackupManifest,
+    /// Attachments whose blob was missing from the store, so isn't backed up
+    pub missing_blobs: Vec<String>,
+}
+
+/// A backup file found in the backup directory
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupFile {
+    pub path: String,
+    pub file_name: String,
+    pub size_bytes: u64,
+    /// Unix time the file was last written
+    pub modified: Option<i64>,
+    pub encrypted: bool,
+    /// Written by the daily schedule, so subject to rotation
+    pub automatic: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreOutcome {
+    pub manifest: BackupManifest,
+    /// Blobs that weren't in the store yet
+    pub blobs_restored: usize,
+    /// Backup of the replaced state, when one was made
+    pub safety_backup: Option<String>,
+}
+
+/// `<kind>-<UTC time>.laibackup`, e.g. `auto-20250131-020000.laibackup`
+pub fn file_name(kind: &str, now: i64) -> String {
+    let time = chrono::DateTime::from_timestamp(now, 0).unwrap_or_default();
+    format!(
+        "{}-{}.{}",
+        kind,
+        time.format("%Y%m%d-%H%M%S"),
+        BACKUP_EXTENSION
+    )
+}
+
+fn sha256_file(path: &Path) -> AppResult<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Fill `buf` from `reader`, stopping early only at the end of the input
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> AppResult<LessSafeKey> {
+    let iterations = NonZeroU32::new(iterations)
+        .filter(|n| n.get() <= MAX_KDF_ITERATIONS)
+        .ok_or_else(|| AppError::invalid_input("Damaged backup: bad encryption header"))?;
+    let mut key = [0u8; 32];
+    ring::pbkdf2::derive(
+        ring::pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    let key = UnboundKey::new(&CHACHA20_POLY1305, &key)
+        .map_err(|_| AppError::internal("Failed to set up backup encryption"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Nonce of chunk `index`; the last byte marks the final chunk
+fn chunk_nonce(index: u64, last: bool) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[3..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = u8::from(last);
+    Nonce::assume_unique_for_key(nonce)
+}
+
+/// Encrypt `input` with a key derived from `passphrase`
+pub fn encrypt(mut input: impl Read, mut output: impl Write, passphrase: &str) -> AppResult<()> {
+    let mut header = [0u8; HEADER_LEN];
+    header[..MAGIC.len()].copy_from_slice(MAGIC);
+    SystemRandom::new()
+        .fill(&mut header[MAGIC.len()..MAGIC.len() + SALT_LEN])
+        .map_err(|_| AppError::internal("No randomness available for the backup salt"))?;
+    header[MAGIC.len() + SALT_LEN..].copy_from_slice(&KDF_ITERATIONS.to_be_bytes());
+    let key = derive_key(
+        passphrase,
+        &header[MAGIC.len()..MAGIC.len() + SALT_LEN],
+        KDF_ITERATIONS,
+    )?;
+    output.write_all(&header)?;
+
+    // The final chunk is the first one shorter than CHUNK_LEN, so input that
+    // fills its last chunk exactly is followed by an empty final chunk
+    let mut chunk = vec![0u8; CHUNK_LEN];
+    let mut index = 0u64;
+    loop {
+        let len = read_full(&mut input, &mut chunk)?;
+        let last = len < CHUNK_LEN;
+        let mut sealed = chunk[..len].to_vec();
+        key.seal_in_place_append_tag(chunk_nonce(index, last), Aad::from(&header), &mut sealed)
+            .map_err(|_| AppError::internal("Failed to encrypt backup"))?;
+        output.write_all(&sealed)?;
+        if last {
+            break;
+        }
+        index += 1;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Decrypt what `encrypt` wrote
+pub fn decrypt(mut input: impl Read, mut output: impl Write, passphrase: &str) -> AppResult<()> {
+    let mut header = [0u8; HEADER_LEN];
+    if read_full(&mut input, &mut header)? < HEADER_LEN || !header.starts_with(MAGIC) {
+        return Err(AppError::invalid_input("Not an encrypted backup"));
+    }
+    let mut iterations = [0u8; 4];
+    iterations.copy_from_slice(&header[MAGIC.len() + SALT_LEN..]);
+    let key = derive_key(
+        passphrase,
+        &header[MAGIC.len()..MAGIC.len() + SALT_LEN],
+        u32::from_be_bytes(iterations),
+    )?;
+
+    let mut chunk = vec![0u8; CHUNK_LEN + TAG_LEN];
+    let mut index = 0u64;
+    loop {
+        let len = read_full(&mut input, &mut chunk)?;
+        let last = len < chunk.len();
+        let plain = key
+            .open_in_place(
+                chunk_nonce(index, last),
+                Aad::from(&header),
+                &mut chunk[..len],
+            )
+            .map_err(|_| AppError::auth("Wrong passphrase, or the backup is damaged"))?;
+        output.write_all(plain)?;
+        if last {
+            break;
+        }
+        index += 1;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+fn is_encrypted(file: &mut File) -> AppResult<bool> {
+    let mut magic = [0u8; MAGIC.len()];
+    let len = read_full(file, &mut magic)?;
+    file.rewind()?;
+    Ok(len == MAGIC.len() && &magic == MAGIC)
+}
+
+#[cfg(unix)]
+fn make_private(dir: &Path) -> AppResult<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+        .map_err(|e| AppError::from(e).context("Failed to restrict the staging directory"))
+}
+
+#[cfg(not(unix))]
+fn make_private(_dir: &Path) -> AppResult<()> {
+    Ok(())
+}
+
+/// A new directory under `<app data>/staging` only the user can read, removed
+/// when dropped
+fn staging_dir(data_dir: &Path) -> AppResult<tempfile::TempDir> {
+    let root = data_dir.join(STAGING_DIR);
+    fs::create_dir_all(&root)
+        .map_err(|e| AppError::from(e).context("Failed to create the staging directory"))?;
+    make_private(&root)?;
+    let dir = tempfile::tempdir_in(&root)?;
+    make_private(dir.path())?;
+    Ok(dir)
+}
+
+/// Create a new file at `path` only the user can read
+fn create_private(path: &Path) -> AppResult<File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    Ok(options.open(path)?)
+}
+
+fn write_error(err: zip::result::ZipError) -> AppError {
+    AppError::internal(format!("Failed to write backup: {}", err))
+}
+
+fn damaged(reason: impl std::fmt::Display) -> AppError {
+    AppError::invalid_input(format!("Damaged backup: {}", reason))
+}
+
+/// Back up `db` and the blobs it references to `dest`, encrypted when a
+/// passphrase is given, which it must be for an encrypted database. The
+/// database is locked only while SQLite copies it; the file appears at `dest`
+/// once complete.
+pub fn create_backup(
+    db: &Database,
+    blobs: &BlobStore,
+    data_dir: &Path,
+    dest: &Path,
+    passphrase: Option<&str>,
+    now: i64,
+) -> AppResult<BackupInfo> {
+    if passphrase.is_none() && db.is_encrypted() {
+        return Err(AppError::invalid_input(
+            "The database is encrypted, so its backups must be too; set a backup passphrase",
+        ));
+    }
+    let parent = dest
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .ok_or_else(|| AppError::invalid_input("Backup path has no directory"))?;
+    fs::create_dir_all(parent)
+        .map_err(|e| AppError::from(e).context("Failed to create backup directory"))?;
+
+    let staging = staging_dir(data_dir)?;
+    let snapshot_path = staging.path().join(DATABASE_ENTRY);
+    // Created first so SQLite writes into a private file
+    create_private(&snapshot_path)?;
+    db.snapshot(&snapshot_path)
+        .map_err(|e| e.context("Failed to snapshot the database"))?;
+
+    let snapshot = Connection::open_with_flags(&snapshot_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let conversations: i64 =
+        snapshot.query_row("SELECT COUNT(*) FROM conversations", [], |row| row.get(0))?;
+    let hashes = snapshot
+        .prepare("SELECT DISTINCT sha256 FROM attachments ORDER BY sha256")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(snapshot);
+
+    // Without a passphrase the archive is the backup itself, so it is written
+    // beside `dest`; otherwise it stays in staging until encrypted
+    let mut archive_file = match passphrase {
+        Some(_) => tempfile::NamedTempFile::new_in(staging.path())?,
+        None => tempfile::NamedTempFile::new_in(parent)?,
+    };
+    let mut zip = ZipWriter::new(BufWriter::new(&mut archive_file));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file(DATABASE_ENTRY, options)
+        .map_err(write_error)?;
+    io::copy(&mut File::open(&snapshot_path)?, &mut zip)?;
+
+    let mut stored = 0;
+    let mut missing_blobs = Vec::new();
+    for hash in hashes {
+        let Ok(mut blob) = blobs
+            .path(&hash)
+            .and_then(|path| File::open(path).map_err(AppError::from))
+        else {
+            missing_blobs.push(hash);
+            continue;
+        };
+        zip.start_file(format!("{}{}", BLOB_PREFIX, hash), options)
+            .map_err(write_error)?;
+        io::copy(&mut blob, &mut zip)?;
+        stored += 1;
+    }
+
+    let manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: now,
+        database_sha256: sha256_file(&snapshot_path)?,
+        conversations,
+        blobs: stored,
+    };
+    zip.start_file(MANIFEST_ENTRY, options)
+        .map_err(write_error)?;
+    serde_json::to_writer_pretty(&mut zip, &manifest)?;
+    zip.finish().map_err(write_error)?.flush()?;
+
+    let file = match passphrase {
+        Some(passphrase) => {
+            let mut encrypted = tempfile::NamedTempFile::new_in(parent)?;
+            encrypt(
+                BufReader::new(File::open(archive_file.path())?),
+                BufWriter::new(&mut encrypted),
+                passphrase,
+            )?;
+            encrypted
+        }
+        None => archive_file,
+    };
+    file.persist(dest)
+        .map_err(|e| AppError::from(e.error).context("Failed to save backup"))?;
+
+    Ok(BackupInfo {
+        path: dest.to_string_lossy().into_owned(),
+        encrypted: passphrase.is_some(),
+        size_bytes: fs::metadata(dest)?.len(),
+        manifest,
+        missing_blobs,
+    })
+}
+
+/// A backup that was decrypted, unpacked into a temporary directory and
+/// checked, ready to restore
+pub struct StagedBackup {
+    dir: tempfile::TempDir,
+    manifest: BackupManifest,
+    blobs: Vec<String>,
+}
+
+/// Unpack and check the backup at `path` in a staging directory under
+/// `data_dir`
+pub fn open_backup(
+    path: &Path,
+    passphrase: Option<&str>,
+    data_dir: &Path,
+) -> AppResult<StagedBackup> {
+    let dir = staging_dir(data_dir)?;
+    let mut file = File::open(path)
+        .map_err(|e| AppError::from(e).context(format!("Failed to open {}", path.display())))?;
+    let archive = if is_encrypted(&mut file)? {
+        let passphrase = passphrase
+            .ok_or_else(|| AppError::auth("This backup is encrypted; enter its passphrase"))?;
+        let archive_path = dir.path().join("backup.zip");
+        decrypt(
+            BufReader::new(file),
+            BufWriter::new(create_private(&archive_path)?),
+            passphrase,
+        )?;
+        File::open(&archive_path)?
+    } else {
+        file
+    };
+    let mut zip = ZipArchive::new(BufReader::new(archive))
+        .map_err(|_| AppError::invalid_input("Not a backup archive"))?;
+
+    let manifest: BackupManifest =
+        serde_json::from_reader(zip.by_name(MANIFEST_ENTRY).map_err(damaged)?).map_err(damaged)?;
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(AppError::unsupported(format!(
+            "This backup was written by a newer version of the app ({})",
+            manifest.app_version
+        )));
+    }
+
+    let database = dir.path().join(DATABASE_ENTRY);
+    extract(&mut zip, DATABASE_ENTRY, &database)?;
+    if sha256_file(&database)? != manifest.database_sha256 {
+        return Err(damaged("the database doesn't match its checksum"));
+    }
+    check_database(&database)?;
+
+    let blob_dir = dir.path().join("blobs");
+    fs::create_dir(&blob_dir)?;
+    let blobs: Vec<String> = zip
+        .file_names()
+        .filter_map(|name| name.strip_prefix(BLOB_PREFIX))
+        .map(str::to_string)
+        .collect();
+    if blobs.len() != manifest.blobs {
+        return Err(damaged("attachments are missing"));
+    }
+    for hash in &blobs {
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(damaged(format!("unexpected entry {}{}", BLOB_PREFIX, hash)));
+        }
+        let path = blob_dir.join(hash);
+        extract(&mut zip, &format!("{}{}", BLOB_PREFIX, hash), &path)?;
+        if sha256_file(&path)? != *hash {
+            return Err(damaged(format!(
+                "attachment {} doesn't match its hash",
+                hash
+            )));
+        }
+    }
+
+    Ok(StagedBackup {
+        dir,
+        manifest,
+        blobs,
+    })
+}
+
+fn extract<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str, dest: &Path) -> AppResult<()> {
+    let mut entry = zip
+        .by_name(name)
+        .map_err(|e| damaged(format!("{}: {}", name, e)))?;
+    io::copy(&mut entry, &mut BufWriter::new(create_private(dest)?))
+        .map_err(|e| damaged(format!("{}: {}", name, e)))?;
+    Ok(())
+}
+
+fn check_database(path: &Path) -> AppResult<()> {
+    // Read-write: checking the FTS5 index writes to it
+    let conn = Connection::open(path)?;
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(damaged)?;
+    if integrity != "ok" {
+        return Err(damaged(integrity));
+    }
+    conn.query_row("SELECT COUNT(*) FROM conversations", [], |row| {
+        row.get::<_, i64>(0)
+    })
+    .map_err(|_| damaged("it doesn't contain a conversation database"))?;
+    Ok(())
+}
+
+impl StagedBackup {
+    pub fn manifest(&self) -> &BackupManifest {
+        &self.manifest
+    }
+
+    /// Add the backup's blobs to the store, then replace the database with
+    /// the backup's
+    pub fn restore(self, db: &Database, blobs: &BlobStore) -> AppResult<RestoreOutcome> {
+        let mut blobs_restored = 0;
+        for hash in &self.blobs {
+            if blobs.path(hash)?.exists() {
+                continue;
+            }
+            blobs.put(&fs::read(self.dir.path().join("blobs").join(hash))?)?;
+            blobs_restored += 1;
+        }
+
+        db.replace_with(&self.dir.path().join(DATABASE_ENTRY))
+            .map_err(|e| e.context("Failed to restore the database"))?;
+
+        Ok(RestoreOutcome {
+            manifest: self.manifest,
+            blobs_restored,
+            safety_backup: None,
+        })
+    }
+}
+
+fn is_automatic(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == BACKUP_EXTENSION)
+        && path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with(AUTO_PREFIX))
+}
+
+/// Delete all but the newest `keep` automatic backups in `dir`
+pub fn rotate(dir: &Path, keep: usize) -> AppResult<Vec<PathBuf>> {
+    let mut automatic: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_automatic(path))
+        .collect();
+    // Names embed the UTC time, so they sort by age
+    automatic.sort();
+    let excess = automatic.len().saturating_sub(keep);
+    let removed: Vec<PathBuf> = automatic.drain(..excess).collect();
+    for path in &removed {
+        fs::remove_file(path)?;
+    }
+    Ok(removed)
+}
+
+/// Backups in `dir`, newest first
+pub fn list_backups(dir: &Path) -> AppResult<Vec<BackupFile>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut backups = Vec::new();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.extension().map_or(true, |ext| ext != BACKUP_EXTENSION) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|age| age.as_secs() as i64);
+        backups.push(BackupFile {
+            file_name: entry.file_name().to_string_lossy().into_owned(),
+            size_bytes: metadata.len(),
+            modified,
+            encrypted: is_encrypted(&mut File::open(&path)?)?,
+            automatic: is_automatic(&path),
+            path: path.to_string_lossy().into_owned(),
+        });
+    }
+    backups.sort_by(|a, b| {
+        b.modified
+            .cmp(&a.modified)
+            .then(b.file_name.cmp(&a.file_name))
+    });
+    Ok(backups)
+}
+
+/// Passphrase for automatic backups, from the system keyring
+pub fn stored_passphrase() -> AppResult<Option<String>> {
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    {
+        let entry = keyring::Entry::new("linux-ai-assistant", KEYRING_ENTRY)
+            .map_err(|e| AppError::from(e).context("keyring entry error"))?;
+        return match entry.get_password() {
+            Ok(passphrase) => Ok(Some(passphrase)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(AppError::from(e).context("keyring get failed")),
+        };
+    }
+    #[allow(unreachable_code)]
+    Ok(None)
+}
+
+/// Store the passphrase for automatic backups, or forget it with `None`
+pub fn set_stored_passphrase(passphrase: Option<&str>) -> AppResult<()> {
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    {
+        let entry = keyring::Entry::new("linux-ai-assistant", KEYRING_ENTRY)
+            .map_err(|e| AppError::from(e).context("keyring entry error"))?;
+        return match passphrase {
+            Some(passphrase) => entry
+                .set_password(passphrase)
+                .map_err(|e| AppError::from(e).context("keyring set failed")),
+            None => match entry.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(AppError::from(e).context("keyring delete failed")),
+            },
+        };
+    }
+    #[allow(unreachable_code)]
+    Err(AppError::unsupported(
+        "keyring unsupported on this platform",
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    /// Write a backup every day
+    pub enabled: bool,
+    /// Absolute path; `backups` in the app data directory when unset
+    pub directory: Option<String>,
+    /// Automatic backups kept; older ones are deleted
+    pub keep: u32,
+    /// Encrypt automatic backups with the passphrase stored in the keyring
+    pub encrypt: bool,
+    /// Unix time of the last automatic backup
+    pub last_run: Option<i64>,
+    /// Why the last automatic backup failed
+    pub last_error: Option<String>,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            directory: None,
+            keep: 7,
+            encrypt: false,
+            last_run: None,
+            last_error: None,
+        }
+    }
+}
+
+impl BackupSettings {
+    pub fn load(conn: &Connection) -> AppResult<Self> {
+        Ok(Setting::get_json(conn, SETTINGS_KEY)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, conn: &Connection) -> AppResult<()> {
+        Ok(Setting::set_json(conn, SETTINGS_KEY, self)?)
+    }
+
+    /// Whether an automatic backup is due at unix time `now`
+    pub fn is_due(&self, now: i64) -> bool {
+        self.enabled
+            && self
+                .last_run
+                .map_or(true, |last| now - last >= BACKUP_INTERVAL)
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        if self.keep == 0 {
+            return Err(AppError::invalid_input("Keep at least one backup"));
+        }
+        match self.directory.as_deref() {
+            Some(dir) if !Path::new(dir).is_absolute() => Err(AppError::invalid_input(
+                "Backup directory must be an absolute path",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Where backups go, given the app data directory
+    pub fn directory(&self, data_dir: &Path) -> PathBuf {
+        self.directory
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| data_dir.join("backups"))
+    }
+
+    /// The keyring passphrase when backups are encrypted
+    pub fn passphrase(&self) -> AppResult<Option<String>> {
+        if !self.encrypt {
+            return Ok(None);
+        }
+        stored_passphrase()?
+            .map(Some)
+            .ok_or_else(|| AppError::auth("No backup passphrase is stored in the keyring"))
+    }
+
+    /// Write an automatic backup now, rotate old ones and record the outcome
+    pub fn run(
+        &mut self,
+        db: &Database,
+        blobs: &BlobStore,
+        data_dir: &Path,
+        now: i64,
+    ) -> AppResult<BackupInfo> {
+        let dir = self.directory(data_dir);
+        let result = self.passphrase().and_then(|passphrase| {
+            let info = create_backup(
+                db,
+                blobs,
+                data_dir,
+                &dir.join(file_name("auto", now)),
+                passphrase.as_deref(),
+                now,
+            )?;
+            rotate(&dir, self.keep as usize)?;
+            Ok(info)
+        });
+        self.last_run = Some(now);
+        self.last_error = result.as_ref().err().map(|e| e.message.clone());
+        self.save(&*db.conn().lock_in_background()?)?;
+        result
+    }
+}
+
+/// Write the daily backup whenever it is due. `backup://created` carries the
+/// `BackupInfo` of each one.
+pub fn start_backups(app: AppHandle, data_dir: PathBuf) {
+    // Plaintext left behind by a backup or restore the app didn't finish
+    let _ = fs::remove_dir_all(data_dir.join(STAGING_DIR));
+    std::thread::spawn(move || loop {
+        let db = app.state::<Database>();
+        let blobs = app.state::<BlobStore>();
+        let now = chrono::Utc::now().timestamp();
+        let settings = db
+            .conn()
+            .lock_in_background()
+            .and_then(|conn| BackupSettings::load(&conn));
+        let outcome = settings.and_then(|mut settings| {
+            if !settings.is_due(now) {
+                return Ok(None);
+            }
+            settings.run(&db, &blobs, &data_dir, now).map(Some)
+        });
+        match outcome {
+            Ok(Some(info)) => {
+                let _ = app.emit("backup://created", &info);
+            }
+            Ok(None) => {}
+            // Resumes once the database is unlocked
+            Err(e) if e.code == ErrorCode::Locked => {}
+            Err(e) => eprintln!("automatic backup failed: {}", e),
+        }
+        std::thread::sleep(BACKUP_POLL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::attachments::{Attachment, NewAttachment};
+    use crate::database::conversations::{Conversation, NewConversation};
+    use crate::database::messages::{Message, NewMessage};
+
+    fn populated() -> (Database, BlobStore, tempfile::TempDir, Conversation) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(PathBuf::from(":memory:")).unwrap();
+        let blobs = BlobStore::new(dir.path().join("blobs"));
+        let conv = {
+            let conn = db.conn().lock().unwrap();
+            let conv = Conversation::create(
+                &conn,
+                NewConversation {
+                    title: "Backed up".to_string(),
+                    model: "gpt-4o".to_string(),
+                    provider: "openai".to_string(),
+                    system_prompt: None,
+                },
+            )
+            .unwrap();
+            let msg = Message::create(
+                &conn,
+                NewMessage {
+                    conversation_id: conv.id.clone(),
+                    role: "user".to_string(),
+                    content: "What's in this file?".to_string(),
+                    tokens_used: None,
+                },
+            )
+            .unwrap();
+            let bytes = b"fn main() {}\n";
+            Attachment::create(
+                &conn,
+                NewAttachment {
+                    message_id: msg.id,
+                    kind: "file".to_string(),
+                    file_name: "main.rs".to_string(),
+                    mime_type: "text/x-rust".to_string(),
+                    size_bytes: bytes.len() as i64,
+                    sha256: blobs.put(bytes).unwrap(),
+                },
+            )
+            .unwrap();
+            Setting::set(&conn, "theme", "dark").unwrap();
+            conv
+        };
+        (db, blobs, dir, conv)
+    }
+
+    #[test]
+    fn encryption_round_trips_and_detects_tampering() {
+        for len in [0, 100, CHUNK_LEN, 2 * CHUNK_LEN + 7] {
+            let plain: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let mut sealed = Vec::new();
+            encrypt(&plain[..], &mut sealed, "correct horse").unwrap();
+            assert_eq!(
+                sealed.len(),
+                HEADER_LEN + len + (len / CHUNK_LEN + 1) * TAG_LEN
+            );
+
+            let mut opened = Vec::new();
+            decrypt(&sealed[..], &mut opened, "correct horse").unwrap();
+            assert_eq!(opened, plain);
+
+            assert!(decrypt(&sealed[..], &mut Vec::new(), "wrong horse").is_err());
+            let mut flipped = sealed.clone();
+            *flipped.last_mut().unwrap() ^= 1;
+            assert!(decrypt(&flipped[..], &mut Vec::new(), "correct horse").is_err());
+            if len >= CHUNK_LEN {
+                // Dropping the final chunk must not pass as a shorter backup
+                let truncated = &sealed[..HEADER_LEN + CHUNK_LEN + TAG_LEN];
+                assert!(decrypt(truncated, &mut Vec::new(), "correct horse").is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_reordered_and_shortened_archives_are_rejected() {
+        let sealed_chunk = CHUNK_LEN + TAG_LEN;
+        let rejects = |sealed: &[u8]| decrypt(sealed, &mut Vec::new(), "correct horse").is_err();
+
+        for len in [2 * CHUNK_LEN, 2 * CHUNK_LEN + 7] {
+            let plain: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let mut sealed = Vec::new();
+            encrypt(&plain[..], &mut sealed, "correct horse").unwrap();
+
+            // Cut inside the header, inside each chunk and at each chunk boundary
+            for end in [
+                HEADER_LEN - 1,
+                HEADER_LEN,
+                HEADER_LEN + 1,
+                HEADER_LEN + sealed_chunk - 1,
+                HEADER_LEN + sealed_chunk,
+                HEADER_LEN + sealed_chunk + 1,
+                HEADER_LEN + 2 * sealed_chunk,
+                sealed.len() - 1,
+            ] {
+                assert!(
+                    rejects(&sealed[..end]),
+                    "accepted {} of {}",
+                    end,
+                    sealed.len()
+                );
+            }
+
+            // Full chunks swapped, and the final chunk (empty when the input
+            // fills its chunks exactly) dropped or repeated
+            let mut swapped = sealed[..HEADER_LEN].to_vec();
+            swapped.extend_from_slice(
+                &sealed[HEADER_LEN + sealed_chunk..HEADER_LEN + 2 * sealed_chunk],
+            );
+            swapped.extend_from_slice(&sealed[HEADER_LEN..HEADER_LEN + sealed_chunk]);
+            swapped.extend_from_slice(&sealed[HEADER_LEN + 2 * sealed_chunk..]);
+            assert_eq!(swapped.len(), sealed.len());
+            assert!(rejects(&swapped));
+
+            let final_chunk = &sealed[HEADER_LEN + 2 * sealed_chunk..];
+            assert_eq!(final_chunk.len(), len - 2 * CHUNK_LEN + TAG_LEN);
+            assert!(rejects(&sealed[..HEADER_LEN + 2 * sealed_chunk]));
+            let mut repeated = sealed.clone();
+            repeated.extend_from_slice(final_chunk);
+            assert!(rejects(&repeated));
+        }
+    }
+
+    #[test]
+    fn backups_restore_the_database_and_attachments() {
+        let (db, blobs, dir, conv) = populated();
+        let path = dir
+            .path()
+            .join("backups")
+            .join(file_name("backup", 1_700_000_000));
+        let info = create_backup(
+            &db,
+            &blobs,
+            dir.path(),
+            &path,
+            Some("hunter2"),
+            1_700_000_000,
+        )
+        .unwrap();
+        assert!(info.encrypted);
+        assert_eq!(info.manifest.conversations, 1);
+        assert_eq!(info.manifest.blobs, 1);
+        assert!(info.missing_blobs.is_empty());
+
+        assert!(open_backup(&path, None, dir.path()).is_err());
+        assert!(open_backup(&path, Some("hunter3"), dir.path()).is_err());
+
+        {
+            let conn = db.conn().lock().unwrap();
+            conn.execute("DELETE FROM conversations WHERE id = ?1", [&conv.id])
+                .unwrap();
+            Setting::set(&conn, "theme", "light").unwrap();
+        }
+        let fresh_blobs = BlobStore::new(dir.path().join("restored-blobs"));
+        let staged = open_backup(&path, Some("hunter2"), dir.path()).unwrap();
+        assert_eq!(staged.manifest(), &info.manifest);
+        let outcome = staged.restore(&db, &fresh_blobs).unwrap();
+        assert_eq!(outcome.blobs_restored, 1);
+
+        let conn = db.conn().lock().unwrap();
+        assert!(Conversation::get_by_id(&conn, &conv.id).unwrap().is_some());
+        assert_eq!(
+            Setting::get(&conn, "theme").unwrap().as_deref(),
+            Some("dark")
+        );
+        let attachments = Attachment::get_by_conversation(&conn, &conv.id).unwrap();
+        assert_eq!(
+            fresh_blobs.get(&attachments[0].sha256).unwrap(),
+            b"fn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn encrypted_databases_are_only_backed_up_encrypted_and_staged_privately() {
+        use crate::database::encryption::DatabaseKey;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().join("database.db")).unwrap();
+        db.set_key(Some(DatabaseKey::generate().unwrap())).unwrap();
+        let blobs = BlobStore::new(dir.path().join("blobs"));
+        let backups = dir.path().join("backups");
+
+        let plain = backups.join("plain.laibackup");
+        let err = create_backup(&db, &blobs, dir.path(), &plain, None, 1_700_000_000)
+            .err()
+            .unwrap();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+        assert!(!plain.exists());
+
+        let path = backups.join("sealed.laibackup");
+        let info = create_backup(
+            &db,
+            &blobs,
+            dir.path(),
+            &path,
+            Some("hunter2"),
+            1_700_000_000,
+        )
+        .unwrap();
+        assert!(info.encrypted);
+        // No plaintext beside the backup, and none left in staging
+        assert_eq!(fs::read_dir(&backups).unwrap().count(), 1);
+        let staging = dir.path().join(STAGING_DIR);
+        assert_eq!(fs::read_dir(&staging).unwrap().count(), 0);
+
+        let staged = open_backup(&path, Some("hunter2"), dir.path()).unwrap();
+        assert!(staged.dir.path().starts_with(&staging));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&staging), 0o700);
+            assert_eq!(mode(staged.dir.path()), 0o700);
+            assert_eq!(mode(&staged.dir.path().join(DATABASE_ENTRY)), 0o600);
+        }
+        drop(staged);
+        assert_eq!(fs::read_dir(&staging).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn damaged_backups_are_rejected_before_restoring() {
+        let (db, blobs, dir, _conv) = populated();
+        let path = dir.path().join("plain.laibackup");
+        let info = create_backup(&db, &blobs, dir.path(), &path, None, 1_700_000_000).unwrap();
+        assert!(!info.encrypted);
+        assert_eq!(
+            open_backup(&path, None, dir.path())
+                .unwrap()
+                .manifest()
+                .blobs,
+            1
+        );
+
+        let not_zip = dir.path().join("notes.laibackup");
+        fs::write(&not_zip, "just some text").unwrap();
+        assert!(open_backup(&not_zip, None, dir.path()).is_err());
+
+        // A database that doesn't match the manifest's checksum
+        let mut archive = ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let tampered = dir.path().join("tampered.laibackup");
+        let mut zip = ZipWriter::new(File::create(&tampered).unwrap());
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).unwrap();
+            let name = entry.name().to_string();
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes).unwrap();
+            if name == DATABASE_ENTRY {
+                let last = bytes.len() - 1;
+                bytes[last] ^= 1;
+            }
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(&bytes).unwrap();
+        }
+        zip.finish().unwrap();
+        assert!(open_backup(&tampered, None, dir.path())
+            .err()
+            .unwrap()
+            .message
+            .contains("checksum"));
+    }
+
+    #[test]
+    fn automatic_backups_run_daily_and_rotate() {
+        let (db, blobs, dir, _conv) = populated();
+        let mut settings = BackupSettings {
+            keep: 2,
+            ..Default::default()
+        };
+        let day = 1_700_000_000;
+        for i in 0..4 {
+            let now = day + i * BACKUP_INTERVAL;
+            assert!(settings.is_due(now));
+            settings.run(&db, &blobs, dir.path(), now).unwrap();
+            assert!(!settings.is_due(now + 60));
+        }
+        fs::write(dir.path().join("backups").join("mine.laibackup"), "").unwrap();
+
+        let names: Vec<String> = list_backups(&dir.path().join("backups"))
+            .unwrap()
+            .into_iter()
+            .filter(|b| b.automatic)
+            .map(|b| b.file_name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                file_name("auto", day + 3 * BACKUP_INTERVAL),
+                file_name("auto", day + 2 * BACKUP_INTERVAL)
+            ]
+        );
+        assert!(dir.path().join("backups").join("mine.laibackup").exists());
+
+        let stored = BackupSettings::load(&db.conn().lock().unwrap()).unwrap();
+        assert_eq!(stored.last_run, Some(day + 3 * BACKUP_INTERVAL));
+        assert_eq!(stored.last_error, None);
+    }
+}
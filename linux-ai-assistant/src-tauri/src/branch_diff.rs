@@ -0,0 +1,391 @@
+// Branch comparison: aligns two branches from their branch point and reports
+// where they diverge. A branch is a conversation's active path or, within one
+// conversation tree, the path to a leaf message. Branch conversations copy
+// their history with fresh message ids, so copies are traced back to the
+// parent's messages through the branch point; after it, messages are matched
+// by role and content.
+
+use crate::database::conversations::Conversation;
+use crate::database::messages::Message;
+use crate::error::{AppError, AppResult};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    /// Same role and content on both branches
+    Same,
+    /// Same role at the same position with different content
+    Changed,
+    /// Present only on branch A
+    OnlyA,
+    /// Present only on branch B
+    OnlyB,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffEntry {
+    pub kind: DiffKind,
+    pub a: Option<Message>,
+    pub b: Option<Message>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchDiff {
+    /// Conversation holding branch A (the conversation of a leaf message id)
+    pub conversation_a: String,
+    pub conversation_b: String,
+    /// Nearest conversation both branches descend from (either one may be it)
+    pub common_ancestor_id: Option<String>,
+    /// Number of leading messages up to the branch point
+    pub common_prefix_len: usize,
+    /// Aligned messages after the shared prefix
+    pub entries: Vec<DiffEntry>,
+}
+
+fn same_message(a: &Message, b: &Message) -> bool {
+    a.role == b.role && a.content == b.content
+}
+
+/// Conversation ids from `id` up through its branch parents, nearest first
+fn lineage(conn: &Connection, id: &str) -> rusqlite::Result<Vec<String>> {
+    let mut chain = vec![id.to_string()];
+    let mut current = Conversation::get_by_id(conn, id)?;
+    while let Some(parent_id) = current.and_then(|c| c.parent_conversation_id) {
+        // Guard against cycles in corrupted data
+        if chain.contains(&parent_id) {
+            break;
+        }
+        chain.push(parent_id.clone());
+        current = Conversation::get_by_id(conn, &parent_id)?;
+    }
+    Ok(chain)
+}
+
+/// Align two message sequences after their first `prefix` shared messages,
+/// matching the rest with a longest-common-subsequence so messages repeated on
+/// both sides line up. Adjacent removals and additions with the same role are
+/// reported as changes.
+pub fn align(a: &[Message], b: &[Message], prefix: usize) -> Vec<DiffEntry> {
+    let (a, b) = (&a[prefix.min(a.len())..], &b[prefix.min(b.len())..]);
+
+    // lcs[i][j] = length of the LCS of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if same_message(&a[i], &b[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut entries: Vec<DiffEntry> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && same_message(&a[i], &b[j]) {
+            entries.push(DiffEntry {
+                kind: DiffKind::Same,
+                a: Some(a[i].clone()),
+                b: Some(b[j].clone()),
+            });
+            i += 1;
+            j += 1;
+        } else if j >= b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            entries.push(DiffEntry {
+                kind: DiffKind::OnlyA,
+                a: Some(a[i].clone()),
+                b: None,
+            });
+            i += 1;
+        } else {
+            // Pair with a preceding removal of the same role when possible
+            let pending = entries
+                .iter_mut()
+                .rev()
+                .take_while(|e| e.kind == DiffKind::OnlyA);
+            let mut paired = false;
+            for entry in pending {
+                if entry.a.as_ref().map(|m| m.role.as_str()) == Some(b[j].role.as_str())
+                    && entry.b.is_none()
+                {
+                    entry.kind = DiffKind::Changed;
+                    entry.b = Some(b[j].clone());
+                    paired = true;
+                    break;
+                }
+            }
+            if !paired {
+                entries.push(DiffEntry {
+                    kind: DiffKind::OnlyB,
+                    a: None,
+                    b: Some(b[j].clone()),
+                });
+            }
+            j += 1;
+        }
+    }
+
+    entries
+}
+
+/// The conversation and message path of a branch: a conversation id stands for
+/// its active path, a message id for the path from the root to that message
+fn branch_path(conn: &Connection, id: &str) -> AppResult<(String, Vec<Message>)> {
+    if Conversation::get_by_id(conn, id)?.is_some() {
+        return Ok((id.to_string(), Message::get_by_conversation(conn, id)?));
+    }
+    match Message::get_by_id(conn, id)? {
+        Some(message) => Ok((message.conversation_id, Message::get_path_to(conn, id)?)),
+        None => Err(AppError::not_found(format!(
+            "Conversation or message not found: {}",
+            id
+        ))),
+    }
+}
+
+/// Message ids of `path` with copies made by branching replaced by the ids of
+/// the messages they were copied from, so paths of related conversations can
+/// be compared by id. Copying stops at the first message that no longer
+/// matches its original (edited or deleted since).
+fn origin_ids(
+    conn: &Connection,
+    conversation_id: &str,
+    path: &[Message],
+    seen: &mut Vec<String>,
+) -> AppResult<Vec<String>> {
+    let mut ids: Vec<String> = path.iter().map(|m| m.id.clone()).collect();
+    // Guard against cycles in corrupted data
+    if seen.iter().any(|id| id == conversation_id) {
+        return Ok(ids);
+    }
+    seen.push(conversation_id.to_string());
+
+    let Some(conversation) = Conversation::get_by_id(conn, conversation_id)? else {
+        return Ok(ids);
+    };
+    let (Some(parent_id), Some(branch_point)) = (
+        conversation.parent_conversation_id,
+        conversation.branch_point_message_id,
+    ) else {
+        return Ok(ids);
+    };
+    let Some(original) = Message::get_by_id(conn, &branch_point)? else {
+        return Ok(ids);
+    };
+    if original.conversation_id != parent_id {
+        return Ok(ids);
+    }
+
+    let parent_path = Message::get_path_to(conn, &branch_point)?;
+    let parent_ids = origin_ids(conn, &parent_id, &parent_path, seen)?;
+    for ((id, copy), (origin, original)) in ids
+        .iter_mut()
+        .zip(path)
+        .zip(parent_ids.into_iter().zip(&parent_path))
+    {
+        if !same_message(copy, original) {
+            break;
+        }
+        *id = origin;
+    }
+    Ok(ids)
+}
+
+/// Diff two branches, each given as a conversation id (its active path) or a
+/// leaf message id. The shared prefix runs up to the branch point, found by
+/// message ids rather than content.
+pub fn diff_branches(conn: &Connection, a: &str, b: &str) -> AppResult<BranchDiff> {
+    let (conversation_a, messages_a) = branch_path(conn, a)?;
+    let (conversation_b, messages_b) = branch_path(conn, b)?;
+
+    let lineage_a = lineage(conn, &conversation_a)?;
+    let lineage_b: HashSet<String> = lineage(conn, &conversation_b)?.into_iter().collect();
+    let common_ancestor_id = lineage_a.into_iter().find(|id| lineage_b.contains(id));
+
+    let origins_a = origin_ids(conn, &conversation_a, &messages_a, &mut Vec::new())?;
+    let origins_b = origin_ids(conn, &conversation_b, &messages_b, &mut Vec::new())?;
+    let common_prefix_len = origins_a
+        .iter()
+        .zip(&origins_b)
+        .take_while(|(x, y)| x == y)
+        .count();
+    let entries = align(&messages_a, &messages_b, common_prefix_len);
+
+    Ok(BranchDiff {
+        conversation_a,
+        conversation_b,
+        common_ancestor_id,
+        common_prefix_len,
+        entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(id: &str, role: &str, content: &str) -> Message {
+        Message {
+            id: id.to_string(),
+            conversation_id: "c".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: 0,
+            tokens_used: None,
+            parent_message_id: None,
+            prompt_tokens: None,
+            completion_tokens: None,
+            provider: None,
+            model: None,
+            cost_usd: None,
+        }
+    }
+
+    #[test]
+    fn align_reports_prefix_changes_and_additions() {
+        let a = vec![
+            msg("a1", "user", "hi"),
+            msg("a2", "assistant", "hello"),
+            msg("a3", "user", "use rust"),
+            msg("a4", "assistant", "rust answer"),
+        ];
+        let b = vec![
+            msg("b1", "user", "hi"),
+            msg("b2", "assistant", "hello"),
+            msg("b3", "user", "use go"),
+            msg("b4", "assistant", "go answer"),
+            msg("b5", "user", "thanks"),
+        ];
+
+        let entries = align(&a, &b, 2);
+        let kinds: Vec<DiffKind> = entries.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![DiffKind::Changed, DiffKind::Changed, DiffKind::OnlyB]
+        );
+        assert_eq!(entries[0].a.as_ref().unwrap().id, "a3");
+        assert_eq!(entries[0].b.as_ref().unwrap().id, "b3");
+    }
+
+    #[test]
+    fn align_matches_shared_messages_after_divergence() {
+        let a = vec![msg("a1", "user", "q"), msg("a2", "assistant", "x")];
+        let b = vec![msg("b1", "user", "q2"), msg("b2", "assistant", "x")];
+
+        let entries = align(&a, &b, 0);
+        let kinds: Vec<DiffKind> = entries.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![DiffKind::Changed, DiffKind::Same]);
+    }
+
+    #[test]
+    fn diff_and_merge_conversation_branches() {
+        use crate::database::conversations::NewConversation;
+        use crate::database::messages::NewMessage;
+        use crate::database::Database;
+        use std::path::PathBuf;
+
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+        let parent = Conversation::create(
+            &conn,
+            NewConversation {
+                title: "Parent".to_string(),
+                model: "gpt-test".to_string(),
+                provider: "local".to_string(),
+                system_prompt: None,
+            },
+        )
+        .expect("create conv");
+        let add = |conversation_id: &str, role: &str, content: &str| {
+            Message::create(
+                &conn,
+                NewMessage {
+                    conversation_id: conversation_id.to_string(),
+                    role: role.to_string(),
+                    content: content.to_string(),
+                    tokens_used: None,
+                },
+            )
+            .expect("create msg")
+        };
+
+        let question = add(&parent.id, "user", "which language?");
+        add(&parent.id, "assistant", "rust");
+        let branch = Conversation::create_branch(&conn, &parent.id, &question.id, "Alt".into())
+            .expect("branch");
+        let winner = add(&branch.id, "assistant", "go");
+
+        let diff = diff_branches(&conn, &parent.id, &branch.id).expect("diff");
+        assert_eq!(diff.common_ancestor_id.as_deref(), Some(parent.id.as_str()));
+        assert_eq!(diff.common_prefix_len, 1);
+        assert_eq!(diff.entries.len(), 1);
+        assert_eq!(diff.entries[0].kind, DiffKind::Changed);
+
+        let merged = Conversation::merge_messages(&conn, &branch.id, &parent.id, &[winner.id])
+            .expect("merge");
+        assert_eq!(merged.len(), 1);
+        let path = Message::get_by_conversation(&conn, &parent.id).expect("path");
+        assert_eq!(path.last().map(|m| m.content.as_str()), Some("go"));
+    }
+
+    #[test]
+    fn diff_aligns_from_the_branch_point() {
+        use crate::database::conversations::NewConversation;
+        use crate::database::messages::NewMessage;
+        use crate::database::Database;
+        use std::path::PathBuf;
+
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+        let parent = Conversation::create(
+            &conn,
+            NewConversation {
+                title: "Parent".to_string(),
+                model: "gpt-test".to_string(),
+                provider: "local".to_string(),
+                system_prompt: None,
+            },
+        )
+        .expect("create conv");
+        let new = |conversation_id: &str, role: &str, content: &str| NewMessage {
+            conversation_id: conversation_id.to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            tokens_used: None,
+        };
+
+        let question = Message::create(&conn, new(&parent.id, "user", "hi")).expect("msg");
+        let answer = Message::create(&conn, new(&parent.id, "assistant", "hello")).expect("msg");
+        Message::create(&conn, new(&parent.id, "user", "more")).expect("msg");
+
+        // The branch repeats the parent's next message after the branch point;
+        // it is aligned as the same message, not folded into the prefix
+        let branch = Conversation::create_branch(&conn, &parent.id, &question.id, "Alt".into())
+            .expect("branch");
+        Message::create(&conn, new(&branch.id, "assistant", "hello")).expect("msg");
+        let diff = diff_branches(&conn, &parent.id, &branch.id).expect("diff");
+        assert_eq!(diff.common_prefix_len, 1);
+        let kinds: Vec<DiffKind> = diff.entries.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![DiffKind::Same, DiffKind::OnlyA]);
+
+        // Leaves of one conversation tree share the path up to their common parent
+        let sibling = Message::create_with_parent(
+            &conn,
+            new(&parent.id, "assistant", "hey"),
+            Some(&question.id),
+        )
+        .expect("sibling");
+        let diff = diff_branches(&conn, &answer.id, &sibling.id).expect("diff");
+        assert_eq!(diff.conversation_a, parent.id);
+        assert_eq!(diff.common_ancestor_id.as_deref(), Some(parent.id.as_str()));
+        assert_eq!(diff.common_prefix_len, 1);
+        assert_eq!(diff.entries.len(), 1);
+        assert_eq!(diff.entries[0].kind, DiffKind::Changed);
+
+        assert!(diff_branches(&conn, &parent.id, "missing").is_err());
+    }
+}
@@ -0,0 +1,418 @@
+// Clipboard quick actions: classify clipboard text (stack trace, JSON, shell
+// command, URL, code, prose) and build the prompt for the action picked for it.
+
+use serde::{Deserialize, Serialize};
+
+/// What the clipboard holds, as far as simple heuristics can tell
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentKind {
+    StackTrace,
+    Json,
+    ShellCommand,
+    Url,
+    Code,
+    Text,
+}
+
+impl ContentKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContentKind::StackTrace => "stack trace",
+            ContentKind::Json => "JSON",
+            ContentKind::ShellCommand => "shell command",
+            ContentKind::Url => "URL",
+            ContentKind::Code => "code",
+            ContentKind::Text => "text",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuickAction {
+    Explain,
+    Format,
+    Translate,
+    Fix,
+    Summarize,
+}
+
+impl QuickAction {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            QuickAction::Explain => "Explain",
+            QuickAction::Format => "Format",
+            QuickAction::Translate => "Translate",
+            QuickAction::Fix => "Fix",
+            QuickAction::Summarize => "Summarize",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "explain" => Some(QuickAction::Explain),
+            "format" => Some(QuickAction::Format),
+            "translate" => Some(QuickAction::Translate),
+            "fix" => Some(QuickAction::Fix),
+            "summarize" => Some(QuickAction::Summarize),
+            _ => None,
+        }
+    }
+
+    /// Whether the reply is meant to stand in for the clipboard contents
+    pub fn produces_replacement(&self) -> bool {
+        matches!(
+            self,
+            QuickAction::Format | QuickAction::Translate | QuickAction::Fix
+        )
+    }
+}
+
+/// Classified clipboard contents with the actions that suit them; the first
+/// action is the default
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipboardAnalysis {
+    pub kind: ContentKind,
+    /// Programming language for code and stack traces, when recognised
+    pub language: Option<String>,
+    pub actions: Vec<QuickAction>,
+    /// First lines of the clipboard, for display
+    pub preview: String,
+}
+
+const SHELL_COMMANDS: &[&str] = &[
+    "apt",
+    "awk",
+    "cargo",
+    "cat",
+    "cd",
+    "chmod",
+    "chown",
+    "cp",
+    "curl",
+    "dnf",
+    "docker",
+    "echo",
+    "export",
+    "find",
+    "git",
+    "grep",
+    "journalctl",
+    "kubectl",
+    "ls",
+    "make",
+    "mkdir",
+    "mv",
+    "npm",
+    "pacman",
+    "pip",
+    "ps",
+    "rm",
+    "rsync",
+    "scp",
+    "sed",
+    "ssh",
+    "sudo",
+    "systemctl",
+    "tar",
+    "wget",
+    "yarn",
+];
+
+/// Language of a stack trace, or None when the text isn't one
+fn stack_trace_language(text: &str) -> Option<&'static str> {
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    let count = |f: &dyn Fn(&str) -> bool| lines.iter().filter(|l| f(l)).count();
+
+    if text.contains("Traceback (most recent call last):") {
+        return Some("python");
+    }
+    if text.contains("panicked at") && text.contains("thread '") {
+        return Some("rust");
+    }
+    if text.contains("goroutine ") && text.lines().any(|l| l.starts_with("panic:")) {
+        return Some("go");
+    }
+    let at_frames = count(&|l| l.starts_with("at ") && l.ends_with(')'));
+    if at_frames >= 2
+        && lines
+            .iter()
+            .any(|l| l.contains("Exception") || l.contains("Error"))
+    {
+        // JVM frames look like `at pkg.Class.method(File.java:12)`
+        let jvm = lines
+            .iter()
+            .any(|l| l.starts_with("at ") && (l.contains(".java:") || l.contains(".kt:")));
+        return Some(if jvm { "java" } else { "javascript" });
+    }
+    let js_frames = count(&|l| {
+        l.starts_with("at ")
+            && l.rsplit(':')
+                .take(2)
+                .all(|p| p.trim_end_matches(')').parse::<u32>().is_ok())
+    });
+    if js_frames >= 2 {
+        return Some("javascript");
+    }
+    None
+}
+
+fn code_language(text: &str) -> Option<&'static str> {
+    let has = |needle: &str| text.contains(needle);
+    let first = text.trim_start();
+    let upper = first.to_uppercase();
+
+    if first.starts_with("#!/bin/bash") || first.starts_with("#!/bin/sh") {
+        return Some("bash");
+    }
+    if has("#include") {
+        return Some(if has("std::") || has("class ") {
+            "cpp"
+        } else {
+            "c"
+        });
+    }
+    if has("fn ") && (has("let ") || has("->") || has("impl ") || has("pub ")) {
+        return Some("rust");
+    }
+    if has("package ") && has("func ") {
+        return Some("go");
+    }
+    if has("public class ") || has("System.out.") || has("public static void") {
+        return Some("java");
+    }
+    if (has("def ") || has("import ") || has("class "))
+        && text.lines().any(|l| l.trim_end().ends_with(':'))
+    {
+        return Some("python");
+    }
+    if (has("interface ") && has(": ")) || has(": string") || has(": number") {
+        return Some("typescript");
+    }
+    if has("const ") || has("function ") || has("=> ") || has("console.log") {
+        return Some("javascript");
+    }
+    if [
+        "SELECT ",
+        "INSERT INTO",
+        "UPDATE ",
+        "CREATE TABLE",
+        "DELETE FROM",
+    ]
+    .iter()
+    .any(|k| upper.starts_with(k))
+    {
+        return Some("sql");
+    }
+    None
+}
+
+fn is_shell_command(text: &str) -> bool {
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.is_empty() || lines.len() > 3 {
+        return false;
+    }
+    lines.iter().all(|line| {
+        let line = line.trim();
+        let line = line.strip_prefix("$ ").unwrap_or(line);
+        let first = line.split_whitespace().next().unwrap_or("");
+        SHELL_COMMANDS.contains(&first) || first.starts_with("./")
+    })
+}
+
+fn is_url(text: &str) -> bool {
+    !text.contains(char::is_whitespace)
+        && (text.starts_with("http://") || text.starts_with("https://"))
+        && text.len() > "https://".len()
+}
+
+/// Classify clipboard text
+pub fn detect(text: &str) -> (ContentKind, Option<String>) {
+    let text = text.trim();
+    if is_url(text) {
+        return (ContentKind::Url, None);
+    }
+    if (text.starts_with('{') || text.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(text).is_ok()
+    {
+        return (ContentKind::Json, Some("json".to_string()));
+    }
+    if let Some(language) = stack_trace_language(text) {
+        return (ContentKind::StackTrace, Some(language.to_string()));
+    }
+    if is_shell_command(text) {
+        return (ContentKind::ShellCommand, Some("bash".to_string()));
+    }
+    if let Some(language) = code_language(text) {
+        return (ContentKind::Code, Some(language.to_string()));
+    }
+    (ContentKind::Text, None)
+}
+
+pub fn actions_for(kind: &ContentKind) -> Vec<QuickAction> {
+    use QuickAction::*;
+    match kind {
+        ContentKind::StackTrace => vec![Explain, Fix],
+        ContentKind::Json => vec![Format, Explain],
+        ContentKind::ShellCommand => vec![Explain, Fix],
+        ContentKind::Url => vec![Explain],
+        ContentKind::Code => vec![Explain, Format, Fix, Translate],
+        ContentKind::Text => vec![Explain, Summarize, Translate],
+    }
+}
+
+pub fn analyze(text: &str) -> ClipboardAnalysis {
+    let (kind, language) = detect(text);
+    let preview = text.trim().lines().take(5).collect::<Vec<_>>().join("\n");
+    ClipboardAnalysis {
+        actions: actions_for(&kind),
+        kind,
+        language,
+        preview,
+    }
+}
+
+/// Prompt for `action` on clipboard `text`. `target` is the language to
+/// translate into (a natural language for text, a programming language for code).
+pub fn build_prompt(
+    action: QuickAction,
+    analysis: &ClipboardAnalysis,
+    text: &str,
+    target: &str,
+) -> String {
+    let kind = analysis.kind.label();
+    let language = analysis.language.as_deref().unwrap_or("");
+    let instruction = match action {
+        QuickAction::Explain => match analysis.kind {
+            ContentKind::StackTrace => {
+                "Explain this stack trace: what failed, where, and the most likely cause.".to_string()
+            }
+            ContentKind::ShellCommand => {
+                "Explain what this shell command does, step by step, and point out anything risky."
+                    .to_string()
+            }
+            ContentKind::Url => {
+                "Explain what this URL points to and break down its parts (host, path, query parameters)."
+                    .to_string()
+            }
+            _ => format!("Explain this {} clearly and concisely.", kind),
+        },
+        QuickAction::Format => format!(
+            "Reformat this {} with conventional style. Reply with only the formatted result in one code block.",
+            kind
+        ),
+        QuickAction::Translate if analysis.kind == ContentKind::Code => format!(
+            "Translate this code to {}. Reply with only the translated code in one code block.",
+            target
+        ),
+        QuickAction::Translate => format!(
+            "Translate this text to {}. Reply with only the translation.",
+            target
+        ),
+        QuickAction::Fix => match analysis.kind {
+            ContentKind::StackTrace => {
+                "Suggest a fix for the error in this stack trace, with the code change if possible."
+                    .to_string()
+            }
+            _ => format!(
+                "Fix any bugs in this {}. Reply with the corrected version in one code block, then a short list of what changed.",
+                kind
+            ),
+        },
+        QuickAction::Summarize => "Summarize this text in a few sentences.".to_string(),
+    };
+    let fence = if text.contains("```") { "````" } else { "```" };
+    format!(
+        "{}\n\n{}{}\n{}\n{}",
+        instruction,
+        fence,
+        language,
+        text.trim_end(),
+        fence
+    )
+}
+
+/// What goes back on the clipboard: the single code block of a reply if it has
+/// exactly one, otherwise the whole reply
+pub fn replacement_text(reply: &str) -> String {
+    let blocks: Vec<&str> = reply.split("```").collect();
+    // One fenced block splits the reply into exactly three parts
+    if blocks.len() == 3 {
+        let block = blocks[1];
+        let body = block
+            .split_once('\n')
+            .map(|(_, rest)| rest)
+            .unwrap_or(block);
+        return body.trim_end().to_string();
+    }
+    reply.trim().to_string()
+}
+
+/// Pretty-print JSON locally; formatting JSON doesn't need a model
+pub fn format_json(text: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(text.trim()).ok()?;
+    serde_json::to_string_pretty(&value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_content_kinds() {
+        let cases: &[(&str, ContentKind, Option<&str>)] = &[
+            ("https://example.com/a?b=1", ContentKind::Url, None),
+            ("{\"a\": [1, 2]}", ContentKind::Json, Some("json")),
+            (
+                "Traceback (most recent call last):\n  File \"x.py\", line 1\nValueError: bad",
+                ContentKind::StackTrace,
+                Some("python"),
+            ),
+            (
+                "thread 'main' panicked at src/main.rs:2:5:\nboom",
+                ContentKind::StackTrace,
+                Some("rust"),
+            ),
+            (
+                "Exception in thread \"main\" java.lang.NullPointerException\n\tat com.x.Main.run(Main.java:10)\n\tat com.x.Main.main(Main.java:4)",
+                ContentKind::StackTrace,
+                Some("java"),
+            ),
+            (
+                "TypeError: x is undefined\n    at render (app.js:10:5)\n    at main (app.js:20:3)",
+                ContentKind::StackTrace,
+                Some("javascript"),
+            ),
+            ("git log --oneline | head -5", ContentKind::ShellCommand, Some("bash")),
+            ("$ sudo systemctl restart nginx", ContentKind::ShellCommand, Some("bash")),
+            ("fn main() {\n    let x = 1;\n}", ContentKind::Code, Some("rust")),
+            ("def f(x):\n    return x", ContentKind::Code, Some("python")),
+            ("SELECT id FROM users WHERE a = 1", ContentKind::Code, Some("sql")),
+            ("The meeting moved to Thursday afternoon.", ContentKind::Text, None),
+        ];
+        for (text, kind, language) in cases {
+            let (got_kind, got_language) = detect(text);
+            assert_eq!(&got_kind, kind, "kind of {:?}", text);
+            assert_eq!(got_language.as_deref(), *language, "language of {:?}", text);
+        }
+    }
+
+    #[test]
+    fn prompts_and_replacements() {
+        let analysis = analyze("fn main() { let x = 1; }");
+        assert_eq!(analysis.actions[0], QuickAction::Explain);
+
+        let prompt = build_prompt(QuickAction::Translate, &analysis, "fn main() {}", "Python");
+        assert!(prompt.starts_with("Translate this code to Python."));
+        assert!(prompt.ends_with("```rust\nfn main() {}\n```"));
+
+        assert_eq!(
+            replacement_text("Here you go:\n```python\nprint(1)\n```\nDone."),
+            "print(1)"
+        );
+        assert_eq!(replacement_text("  Bonjour  "), "Bonjour");
+        assert_eq!(format_json("{\"a\":1}").unwrap(), "{\n  \"a\": 1\n}");
+        assert_eq!(QuickAction::parse("Format"), Some(QuickAction::Format));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::attachments::{self, BlobStore};
+use crate::database::{attachments::Attachment, Database};
+use crate::error::{AppError, AppResult};
+use tauri::State;
+
+/// Attach base64-encoded bytes (pasted or dropped in the UI, or sent by
+/// `lai ask --file`) to a message
+#[tauri::command]
+pub async fn attach_data(
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+    message_id: String,
+    file_name: String,
+    data_base64: String,
+) -> AppResult<Attachment> {
+    let bytes = attachments::decode_base64(&data_base64)?;
+    let conn = db.conn().lock()?;
+    attachments::store(&conn, &blobs, &message_id, &file_name, &bytes)
+}
+
+#[tauri::command]
+pub async fn get_message_attachments(
+    db: State<'_, Database>,
+    message_id: String,
+) -> AppResult<Vec<Attachment>> {
+    let conn = db.conn().lock()?;
+    Attachment::get_by_message(&conn, &message_id).map_err(AppError::from)
+}
+
+#[tauri::command]
+pub async fn get_conversation_attachments(
+    db: State<'_, Database>,
+    conversation_id: String,
+) -> AppResult<Vec<Attachment>> {
+    let conn = db.conn().lock()?;
+    Attachment::get_by_conversation(&conn, &conversation_id).map_err(AppError::from)
+}
+
+/// Contents of an attachment as base64, for previews
+#[tauri::command]
+pub async fn get_attachment_data(
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+    id: String,
+) -> AppResult<String> {
+    let attachment = {
+        let conn = db.conn().lock()?;
+        Attachment::get_by_id(&conn, &id)?
+            .ok_or_else(|| AppError::not_found(format!("Attachment not found: {}", id)))?
+    };
+    let bytes = blobs.get(&attachment.sha256)?;
+    Ok(attachments::encode_base64(&bytes))
+}
+
+#[tauri::command]
+pub async fn delete_attachment(
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+    id: String,
+) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    attachments::remove(&conn, &blobs, &id)
+}
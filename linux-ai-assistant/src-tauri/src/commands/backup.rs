@@ -0,0 +1,137 @@
+use crate::attachments::BlobStore;
+use crate::backup::{self, BackupFile, BackupInfo, BackupManifest, BackupSettings, RestoreOutcome};
+use crate::database::Database;
+use crate::error::{AppError, AppResult};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+fn data_dir(app: &AppHandle) -> AppResult<PathBuf> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| AppError::internal(format!("Failed to get app data directory: {}", e)))
+}
+
+fn settings(db: &Database) -> AppResult<BackupSettings> {
+    BackupSettings::load(&*db.conn().lock()?)
+}
+
+fn absolute(path: &str) -> AppResult<&Path> {
+    let path = Path::new(path);
+    if !path.is_absolute() {
+        return Err(AppError::invalid_input("Backup path must be absolute"));
+    }
+    Ok(path)
+}
+
+/// An empty passphrase means none
+fn non_empty(passphrase: &Option<String>) -> Option<&str> {
+    passphrase.as_deref().filter(|p| !p.is_empty())
+}
+
+/// Back up the database and attachments to `path`, or to a new file in the
+/// backup directory; encrypted when a passphrase is given
+#[tauri::command]
+pub fn create_backup(
+    app: AppHandle,
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+    path: Option<String>,
+    passphrase: Option<String>,
+) -> AppResult<BackupInfo> {
+    let now = chrono::Utc::now().timestamp();
+    let data_dir = data_dir(&app)?;
+    let dest = match path.as_deref() {
+        Some(path) => absolute(path)?.to_path_buf(),
+        None => settings(&db)?
+            .directory(&data_dir)
+            .join(backup::file_name("backup", now)),
+    };
+    backup::create_backup(&db, &blobs, &data_dir, &dest, non_empty(&passphrase), now)
+}
+
+/// Check a backup (passphrase, checksums, database integrity) without
+/// restoring it
+#[tauri::command]
+pub fn verify_backup(
+    app: AppHandle,
+    path: String,
+    passphrase: Option<String>,
+) -> AppResult<BackupManifest> {
+    let staged = backup::open_backup(absolute(&path)?, non_empty(&passphrase), &data_dir(&app)?)?;
+    Ok(staged.manifest().clone())
+}
+
+/// Replace the database with a backup's and add its attachments. The current
+/// state is first saved as a `pre-restore` backup in the backup directory.
+/// Emits `backup://restored` so the window reloads its data.
+#[tauri::command]
+pub fn restore_backup(
+    app: AppHandle,
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+    path: String,
+    passphrase: Option<String>,
+) -> AppResult<RestoreOutcome> {
+    // A bad archive or passphrase fails here, before anything is written
+    let data_dir = data_dir(&app)?;
+    let staged = backup::open_backup(absolute(&path)?, non_empty(&passphrase), &data_dir)?;
+
+    let now = chrono::Utc::now().timestamp();
+    let settings = settings(&db)?;
+    let safety = backup::create_backup(
+        &db,
+        &blobs,
+        &data_dir,
+        &settings
+            .directory(&data_dir)
+            .join(backup::file_name("pre-restore", now)),
+        settings.passphrase()?.as_deref(),
+        now,
+    )
+    .map_err(|e| e.context("Failed to back up the current data before restoring"))?;
+
+    let mut outcome = staged.restore(&db, &blobs)?;
+    outcome.safety_backup = Some(safety.path);
+    let _ = app.emit("backup://restored", &outcome);
+    Ok(outcome)
+}
+
+/// Backups in the backup directory, newest first
+#[tauri::command]
+pub fn list_backups(app: AppHandle, db: State<'_, Database>) -> AppResult<Vec<BackupFile>> {
+    backup::list_backups(&settings(&db)?.directory(&data_dir(&app)?))
+}
+
+#[tauri::command]
+pub fn get_backup_settings(db: State<'_, Database>) -> AppResult<BackupSettings> {
+    settings(&db)
+}
+
+/// Save backup settings. A non-empty `passphrase` replaces the one kept in
+/// the keyring for automatic backups and an empty one removes it.
+#[tauri::command]
+pub fn update_backup_settings(
+    db: State<'_, Database>,
+    settings: BackupSettings,
+    passphrase: Option<String>,
+) -> AppResult<BackupSettings> {
+    settings.validate()?;
+    if let Some(passphrase) = passphrase.as_deref() {
+        backup::set_stored_passphrase(Some(passphrase).filter(|p| !p.is_empty()))?;
+    }
+    if settings.encrypt && backup::stored_passphrase()?.is_none() {
+        return Err(AppError::invalid_input(
+            "Set a passphrase to encrypt automatic backups",
+        ));
+    }
+
+    let conn = db.conn().lock()?;
+    let stored = BackupSettings::load(&conn)?;
+    let settings = BackupSettings {
+        last_run: stored.last_run,
+        last_error: stored.last_error,
+        ..settings
+    };
+    settings.save(&conn)?;
+    Ok(settings)
+}
@@ -0,0 +1,203 @@
+use crate::clipboard::{self, ClipboardAnalysis, QuickAction};
+use crate::commands::messages::generate_reply;
+use crate::commands::provider::default_model;
+use crate::database::{
+    conversations::{Conversation, NewConversation},
+    messages::{Message, NewMessage},
+    profiles::Profile,
+    settings::Setting,
+    Database,
+};
+use crate::error::{AppError, AppResult};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+/// Outcome of a quick action. JSON formatting is done locally, so it has no
+/// conversation or reply.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardActionResult {
+    pub action: QuickAction,
+    pub analysis: ClipboardAnalysis,
+    pub conversation_id: Option<String>,
+    pub reply: Option<Message>,
+    /// Text written back to the clipboard, when replacing was requested
+    pub replaced_with: Option<String>,
+}
+
+fn read_clipboard(app: &AppHandle) -> AppResult<String> {
+    let text = app
+        .clipboard()
+        .read_text()
+        .map_err(|e| AppError::unsupported(format!("Failed to read clipboard: {}", e)))?;
+    if text.trim().is_empty() {
+        return Err(AppError::invalid_input("Clipboard is empty"));
+    }
+    Ok(text)
+}
+
+pub(crate) fn write_clipboard(app: &AppHandle, text: &str) -> AppResult<()> {
+    app.clipboard()
+        .write_text(text)
+        .map_err(|e| AppError::unsupported(format!("Failed to write clipboard: {}", e)))
+}
+
+/// Classify the current clipboard text and list the actions that suit it
+#[tauri::command]
+pub fn analyze_clipboard(app: AppHandle) -> AppResult<ClipboardAnalysis> {
+    let text = read_clipboard(&app)?;
+    Ok(clipboard::analyze(&text))
+}
+
+/// Run a quick action on the clipboard: open a conversation with the matching
+/// prompt and generate the reply, optionally writing the result back to the
+/// clipboard. `action` defaults to the first action suggested for the content.
+#[tauri::command]
+pub async fn run_clipboard_action(
+    app: AppHandle,
+    db: State<'_, Database>,
+    action: Option<String>,
+    target_language: Option<String>,
+    replace_clipboard: Option<bool>,
+) -> AppResult<ClipboardActionResult> {
+    let text = read_clipboard(&app)?;
+    let analysis = clipboard::analyze(&text);
+    let action = match action.as_deref() {
+        Some(name) => QuickAction::parse(name)
+            .ok_or_else(|| AppError::invalid_input(format!("Unknown quick action: {}", name)))?,
+        None => analysis.actions[0],
+    };
+    let replace = replace_clipboard.unwrap_or(false);
+
+    if action == QuickAction::Format && analysis.kind == clipboard::ContentKind::Json {
+        let formatted = clipboard::format_json(&text)
+            .ok_or_else(|| AppError::invalid_input("Clipboard JSON is not valid"))?;
+        if replace {
+            write_clipboard(&app, &formatted)?;
+        }
+        return Ok(ClipboardActionResult {
+            action,
+            analysis,
+            conversation_id: None,
+            reply: None,
+            replaced_with: replace.then_some(formatted),
+        });
+    }
+
+    let (conversation, prompt_message) = {
+        let conn = db.conn().lock()?;
+        let target = match target_language {
+            Some(target) => target,
+            None if action == QuickAction::Translate => {
+                let key = if analysis.kind == clipboard::ContentKind::Code {
+                    "clipboard_translate_code_target"
+                } else {
+                    "clipboard_translate_target"
+                };
+                Setting::get(&conn, key)?.ok_or_else(|| {
+                    AppError::invalid_input(format!(
+                        "No target language given and `{}` is not set",
+                        key
+                    ))
+                })?
+            }
+            None => String::new(),
+        };
+        let (provider, model) = match Profile::get_active(&conn)? {
+            Some(profile) => (profile.default_provider, profile.default_model),
+            None => ("openai".to_string(), default_model("openai").to_string()),
+        };
+        let conversation = Conversation::create(
+            &conn,
+            NewConversation {
+                title: format!("{}: {}", action.display_name(), analysis.kind.label()),
+                model,
+                provider,
+                system_prompt: None,
+            },
+        )?;
+        let prompt_message = Message::create(
+            &conn,
+            NewMessage {
+                conversation_id: conversation.id.clone(),
+                role: "user".to_string(),
+                content: clipboard::build_prompt(action, &analysis, &text, &target),
+                tokens_used: None,
+            },
+        )?;
+        (conversation, prompt_message)
+    };
+
+    let _ = app.emit(
+        "clipboard://action-started",
+        serde_json::json!({
+            "conversation_id": conversation.id,
+            "action": action,
+            "kind": analysis.kind,
+        }),
+    );
+
+    let reply = generate_reply(&app, &db, &prompt_message.id, None, None).await?;
+
+    let replaced_with = if replace && action.produces_replacement() {
+        let replacement = clipboard::replacement_text(&reply.content);
+        write_clipboard(&app, &replacement)?;
+        Some(replacement)
+    } else {
+        None
+    };
+
+    let _ = app.emit(
+        "clipboard://action-finished",
+        serde_json::json!({
+            "conversation_id": conversation.id,
+            "action": action,
+            "replaced": replaced_with.is_some(),
+        }),
+    );
+
+    Ok(ClipboardActionResult {
+        action,
+        analysis,
+        conversation_id: Some(conversation.id),
+        reply: Some(reply),
+        replaced_with,
+    })
+}
+
+/// Entry point for the `QuickCapture` shortcut: runs the action configured in
+/// `clipboard_quick_action` (`auto` picks the default for the content) and
+/// replaces the clipboard when `clipboard_replace_result` is `true`. Failures
+/// are reported as `clipboard://error` events.
+pub fn quick_capture(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let (action, replace) = {
+            let db = app.state::<Database>();
+            let Ok(conn) = db.conn().lock() else {
+                return;
+            };
+            let action = Setting::get(&conn, "clipboard_quick_action")
+                .ok()
+                .flatten()
+                .filter(|a| a != "auto");
+            let replace = Setting::get(&conn, "clipboard_replace_result")
+                .ok()
+                .flatten()
+                .map(|v| v == "true")
+                .unwrap_or(false);
+            (action, replace)
+        };
+        let result = run_clipboard_action(
+            app.clone(),
+            app.state::<Database>(),
+            action,
+            None,
+            Some(replace),
+        )
+        .await;
+        if let Err(err) = result {
+            let _ = app.emit("clipboard://error", &err);
+        }
+    });
+}
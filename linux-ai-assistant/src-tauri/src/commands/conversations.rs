@@ -1,5 +1,8 @@
-use crate::database::{conversations::*, Database};
-use tauri::State;
+use crate::attachments::{self, BlobStore};
+use crate::branch_diff::BranchDiff;
+use crate::database::{attachments::Attachment, conversations::*, messages::Message, Database};
+use crate::error::{AppError, AppResult};
+use tauri::{AppHandle, Manager, State};
 
 #[tauri::command]
 pub async fn create_conversation(
@@ -8,33 +11,39 @@ pub async fn create_conversation(
     model: String,
     provider: String,
     system_prompt: Option<String>,
-) -> Result<Conversation, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+    template_id: Option<String>,
+) -> AppResult<Conversation> {
+    let conn = db.conn().lock()?;
     let new_conv = NewConversation {
         title,
         model,
         provider,
         system_prompt,
     };
-    Conversation::create(&conn, new_conv).map_err(|e| e.to_string())
+    let mut conversation = Conversation::create(&conn, new_conv)?;
+    if template_id.is_some() {
+        Conversation::set_template(&conn, &conversation.id, template_id.as_deref())?;
+        conversation.template_id = template_id;
+    }
+    Ok(conversation)
 }
 
 #[tauri::command]
 pub async fn get_conversation(
     db: State<'_, Database>,
     id: String,
-) -> Result<Option<Conversation>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Conversation::get_by_id(&conn, &id).map_err(|e| e.to_string())
+) -> AppResult<Option<Conversation>> {
+    let conn = db.conn().lock()?;
+    Conversation::get_by_id(&conn, &id).map_err(AppError::from)
 }
 
 #[tauri::command]
 pub async fn get_all_conversations(
     db: State<'_, Database>,
     limit: i64,
-) -> Result<Vec<Conversation>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Conversation::get_all(&conn, limit).map_err(|e| e.to_string())
+) -> AppResult<Vec<Conversation>> {
+    let conn = db.conn().lock()?;
+    Conversation::get_all(&conn, limit).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -42,21 +51,28 @@ pub async fn update_conversation_title(
     db: State<'_, Database>,
     id: String,
     title: String,
-) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Conversation::update_title(&conn, &id, &title).map_err(|e| e.to_string())
+) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Conversation::update_title(&conn, &id, &title).map_err(AppError::from)
 }
 
+/// Soft-delete a conversation. Its attachments are removed for good, so
+/// restoring it brings back the messages only.
 #[tauri::command]
-pub async fn delete_conversation(db: State<'_, Database>, id: String) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Conversation::delete(&conn, &id).map_err(|e| e.to_string())
+pub async fn delete_conversation(
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+    id: String,
+) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Conversation::delete(&conn, &id)?;
+    attachments::remove_all(&conn, &blobs, Attachment::get_by_conversation(&conn, &id)?)
 }
 
 #[tauri::command]
-pub async fn restore_conversation(db: State<'_, Database>, id: String) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Conversation::restore(&conn, &id).map_err(|e| e.to_string())
+pub async fn restore_conversation(db: State<'_, Database>, id: String) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Conversation::restore(&conn, &id).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -64,24 +80,31 @@ pub async fn search_conversations(
     db: State<'_, Database>,
     query: String,
     limit: i64,
-) -> Result<Vec<Conversation>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Conversation::search(&conn, &query, limit).map_err(|e| e.to_string())
+) -> AppResult<Vec<Conversation>> {
+    let conn = db.conn().lock()?;
+    Conversation::search(&conn, &query, limit).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn cleanup_conversations(db: State<'_, Database>) -> Result<String, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+pub async fn cleanup_conversations(
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+) -> AppResult<String> {
+    let conn = db.conn().lock()?;
 
-    // Get all conversations and count them
-    let all_conversations = Conversation::get_all(&conn, 10000) // Get up to 10k conversations for cleanup
-        .map_err(|e| e.to_string())?;
+    // Get all conversations (up to 10k) and count them
+    let all_conversations = Conversation::get_all(&conn, 10000)?;
 
     let mut deleted_count = 0;
 
     // Mark all conversations as deleted (soft delete)
     for conv in &all_conversations {
-        Conversation::delete(&conn, &conv.id).map_err(|e| e.to_string())?;
+        Conversation::delete(&conn, &conv.id)?;
+        attachments::remove_all(
+            &conn,
+            &blobs,
+            Attachment::get_by_conversation(&conn, &conv.id)?,
+        )?;
         deleted_count += 1;
     }
 
@@ -94,22 +117,79 @@ pub async fn create_conversation_branch(
     parent_conversation_id: String,
     branch_point_message_id: String,
     title: String,
-) -> Result<Conversation, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+) -> AppResult<Conversation> {
+    let conn = db.conn().lock()?;
     Conversation::create_branch(
         &conn,
         &parent_conversation_id,
         &branch_point_message_id,
         title,
     )
-    .map_err(|e| e.to_string())
+    .map_err(AppError::from)
 }
 
 #[tauri::command]
 pub async fn get_conversation_branches(
     db: State<'_, Database>,
     conversation_id: String,
-) -> Result<Vec<Conversation>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Conversation::get_branches(&conn, &conversation_id).map_err(|e| e.to_string())
+) -> AppResult<Vec<Conversation>> {
+    let conn = db.conn().lock()?;
+    Conversation::get_branches(&conn, &conversation_id).map_err(AppError::from)
+}
+
+#[tauri::command]
+pub async fn diff_branches(db: State<'_, Database>, a: String, b: String) -> AppResult<BranchDiff> {
+    let conn = db.conn().lock()?;
+    crate::branch_diff::diff_branches(&conn, &a, &b)
+}
+
+#[tauri::command]
+pub async fn merge_branch(
+    db: State<'_, Database>,
+    source_conversation_id: String,
+    target_conversation_id: String,
+    message_ids: Vec<String>,
+) -> AppResult<Vec<Message>> {
+    let conn = db.conn().lock()?;
+    Conversation::merge_messages(
+        &conn,
+        &source_conversation_id,
+        &target_conversation_id,
+        &message_ids,
+    )
+    .map_err(AppError::from)
+}
+
+/// Rebuild the rolling summary from the whole active path with the summarizer
+/// model. With `retitle` the title is regenerated too, replacing a user-chosen one.
+#[tauri::command]
+pub async fn regenerate_conversation_summary(
+    app: AppHandle,
+    conversation_id: String,
+    retitle: Option<bool>,
+) -> AppResult<Conversation> {
+    let handle = app.clone();
+    let id = conversation_id.clone();
+    // Provider calls use blocking HTTP clients, so keep them off the async executor
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::summarizer::run(&handle, &id, true, retitle.unwrap_or(false))
+    })
+    .await??;
+
+    let db = app.state::<Database>();
+    let conn = db.conn().lock()?;
+    Conversation::get_by_id(&conn, &conversation_id)?
+        .ok_or_else(|| AppError::not_found("Conversation not found"))
+}
+
+#[tauri::command]
+pub async fn set_conversation_project_root(
+    db: State<'_, Database>,
+    id: String,
+    project_root: Option<String>,
+) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    // Store the same key the indexer uses so retrieval finds the project's chunks
+    let key = project_root.map(|p| crate::project_index::root_key(std::path::Path::new(&p)));
+    Conversation::set_project_root(&conn, &id, key.as_deref()).map_err(AppError::from)
 }
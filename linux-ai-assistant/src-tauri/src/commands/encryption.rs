@@ -0,0 +1,138 @@
+use crate::database::encryption::{DatabaseKey, EncryptionSettings, KeySource};
+use crate::database::Database;
+use crate::error::{AppError, AppResult};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptionStatus {
+    pub encrypted: bool,
+    pub key_source: Option<KeySource>,
+    /// Commands touching the database fail with `locked` until unlocked
+    pub locked: bool,
+    pub idle_lock_minutes: u32,
+}
+
+fn status(db: &Database) -> AppResult<EncryptionStatus> {
+    let settings = EncryptionSettings::load(db.path())?;
+    let locked = db.is_locked();
+    // An encrypted file without settings opens locked, waiting for a passphrase
+    let key_source = settings
+        .key_source
+        .or_else(|| locked.then_some(KeySource::Passphrase));
+    Ok(EncryptionStatus {
+        encrypted: key_source.is_some(),
+        key_source,
+        locked,
+        idle_lock_minutes: settings.idle_lock_minutes,
+    })
+}
+
+#[tauri::command]
+pub fn get_encryption_status(db: State<'_, Database>) -> AppResult<EncryptionStatus> {
+    status(&db)
+}
+
+/// Unlock the database with `passphrase`, or with the key in the keyring when
+/// none is given, and finish startup work that was waiting for it (shortcuts,
+/// window state). Emits `database://unlocked`.
+#[tauri::command]
+pub fn unlock_database(
+    app: AppHandle,
+    db: State<'_, Database>,
+    passphrase: Option<String>,
+) -> AppResult<EncryptionStatus> {
+    let key = match passphrase.filter(|p| !p.is_empty()) {
+        Some(passphrase) => DatabaseKey::passphrase(passphrase)?,
+        None => DatabaseKey::from_keyring()?,
+    };
+    db.unlock(key)?;
+    crate::load_database_settings(&app);
+    let _ = app.emit("database://unlocked", ());
+    status(&db)
+}
+
+/// Lock an encrypted database now. Emits `database://locked`.
+#[tauri::command]
+pub fn lock_database(app: AppHandle, db: State<'_, Database>) -> AppResult<EncryptionStatus> {
+    db.lock()?;
+    let _ = app.emit("database://locked", ());
+    status(&db)
+}
+
+/// Encrypt the database with a new key from `key_source`, or decrypt it when
+/// `key_source` is unset. The file is rewritten in place, so this can take a
+/// while for a large database.
+#[tauri::command]
+pub fn set_database_encryption(
+    db: State<'_, Database>,
+    key_source: Option<KeySource>,
+    passphrase: Option<String>,
+) -> AppResult<EncryptionStatus> {
+    let mut settings = EncryptionSettings::load(db.path())?;
+    // Put back if the database can't be rewritten with a new keyring key
+    let previous = match settings.key_source {
+        Some(KeySource::Keyring) => DatabaseKey::from_keyring().ok(),
+        _ => None,
+    };
+
+    let key = match key_source {
+        None => None,
+        Some(KeySource::Passphrase) => {
+            let passphrase = passphrase
+                .filter(|p| !p.is_empty())
+                .ok_or_else(|| AppError::invalid_input("Enter a passphrase for the database"))?;
+            Some(DatabaseKey::passphrase(passphrase)?)
+        }
+        Some(KeySource::Keyring) => {
+            let key = DatabaseKey::generate()?;
+            key.save_to_keyring()?;
+            Some(key)
+        }
+    };
+
+    if let Err(e) = db.set_key(key.clone()) {
+        if db.is_keyed_with(key.as_ref()) {
+            // Rewritten but not reopened: the new key is the only one that
+            // opens the file, so it stays in the keyring and the settings
+            settings.key_source = key_source;
+            settings.save(db.path())?;
+            return Err(e);
+        }
+        if key_source == Some(KeySource::Keyring) {
+            let _ = match &previous {
+                Some(key) => key.save_to_keyring(),
+                None => DatabaseKey::remove_from_keyring(),
+            };
+        }
+        return Err(e);
+    }
+    if key_source != Some(KeySource::Keyring) {
+        if let Err(e) = DatabaseKey::remove_from_keyring() {
+            eprintln!(
+                "database: removing the old key from the keyring failed: {}",
+                e
+            );
+        }
+    }
+
+    settings.key_source = key_source;
+    settings.save(db.path())?;
+    status(&db)
+}
+
+/// Save encryption settings other than the key source, which only
+/// `set_database_encryption` changes
+#[tauri::command]
+pub fn update_encryption_settings(
+    db: State<'_, Database>,
+    settings: EncryptionSettings,
+) -> AppResult<EncryptionStatus> {
+    let stored = EncryptionSettings::load(db.path())?;
+    EncryptionSettings {
+        key_source: stored.key_source,
+        ..settings
+    }
+    .save(db.path())?;
+    status(&db)
+}
@@ -1,15 +1,20 @@
-use crate::database::{
-    conversations::{Conversation, NewConversationWithId},
-    messages::{Message, NewMessageWithId},
-    Database,
-};
-use comrak::{markdown_to_html, ComrakOptions};
-use printpdf::*;
+use crate::attachments::BlobStore;
+use crate::database::{conversations::Conversation, Database};
+use crate::error::{AppError, AppResult};
+use crate::exporters::{self, DirectoryExport, ExportFilter, ExportFormat, MirrorSettings};
+use crate::importers::{self, ConflictStrategy, ImportFormat, ImportPreview, ImportResult};
+use rusqlite::Connection;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::io::BufWriter;
+use std::path::Path;
 use tauri::{Manager, State};
 
-#[derive(Serialize, Deserialize)]
+/// Version written to `ExportData::version`. Version 2 added tags, branch
+/// links and the profile and template a conversation was created under; the
+/// importer still accepts version 1.
+pub const EXPORT_VERSION: &str = "2.0.0";
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct ExportedConversation {
     pub id: String,
     pub title: String,
@@ -18,563 +23,286 @@ pub struct ExportedConversation {
     pub system_prompt: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
+    /// Leaf of the path shown in the app; absent in exports made before branching
+    #[serde(default)]
+    pub active_leaf_id: Option<String>,
+    /// Conversation this one was branched from (v2)
+    #[serde(default)]
+    pub parent_conversation_id: Option<String>,
+    /// Message of the parent conversation the branch was taken at (v2)
+    #[serde(default)]
+    pub branch_point_message_id: Option<String>,
+    /// Profile active when the conversation was created (v2)
+    #[serde(default)]
+    pub profile: Option<ExportedRef>,
+    /// Workspace template the conversation was started from (v2)
+    #[serde(default)]
+    pub template: Option<ExportedRef>,
+    #[serde(default)]
+    pub tags: Vec<ExportedTag>,
     pub messages: Vec<ExportedMessage>,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Tags are matched by name on import and created when missing
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ExportedTag {
+    pub name: String,
+    pub color: Option<String>,
+}
+
+/// A profile or workspace template, matched on import by id and then by name.
+/// Neither is created when missing.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExportedRef {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct ExportedMessage {
     pub id: String,
     pub role: String,
     pub content: String,
     pub timestamp: i64,
     pub tokens_used: Option<i64>,
+    /// Parent in the message tree; absent in older exports, whose messages form a
+    /// single linear path
+    #[serde(default)]
+    pub parent_message_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<ExportedAttachment>,
+}
+
+/// An attachment with its contents, so an export is self-contained
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ExportedAttachment {
+    pub kind: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub sha256: String,
+    /// Base64-encoded contents
+    pub data: String,
 }
 
-#[derive(Serialize, Deserialize)]
+/// A JSON export of conversations
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct ExportData {
+    /// Semver of the export schema; see `EXPORT_VERSION`
     pub version: String,
     pub export_timestamp: i64,
     pub conversations: Vec<ExportedConversation>,
 }
 
+/// Export conversations (all if no ids are given) as one file in `format`.
+/// Text formats come back as UTF-8 bytes like the binary ones.
+#[tauri::command]
+pub fn export_conversations(
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+    conversation_ids: Option<Vec<String>>,
+    format: ExportFormat,
+    whole_tree: Option<bool>,
+) -> AppResult<Vec<u8>> {
+    let conn = db.conn().lock()?;
+    let conversations = conversations_for_export(&conn, conversation_ids)?;
+    if conversations.is_empty() {
+        return Err(AppError::invalid_input("No conversations to export"));
+    }
+    exporters::export(
+        &conn,
+        &blobs,
+        conversations,
+        format,
+        whole_tree.unwrap_or(false),
+    )
+}
+
 #[tauri::command]
 pub fn export_conversations_json(
     db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
     conversation_ids: Option<Vec<String>>,
-) -> Result<String, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+) -> AppResult<String> {
+    let conn = db.conn().lock()?;
+    let conversations = conversations_for_export(&conn, conversation_ids)?;
+    let bytes = exporters::export(&conn, &blobs, conversations, ExportFormat::Json, true)?;
+    utf8(bytes)
+}
 
-    // Get conversations to export (all if none specified)
-    let conversations = if let Some(ids) = conversation_ids {
+/// The conversations with the given ids, skipping missing ones, or all of them
+/// if no ids are given
+fn conversations_for_export(
+    conn: &Connection,
+    conversation_ids: Option<Vec<String>>,
+) -> AppResult<Vec<Conversation>> {
+    if let Some(ids) = conversation_ids {
         let mut result = Vec::new();
         for id in ids {
-            match Conversation::get_by_id(&conn, &id) {
+            match Conversation::get_by_id(conn, &id) {
                 Ok(Some(conv)) => result.push(conv),
                 Ok(None) => continue,
-                Err(e) => return Err(format!("Failed to get conversation {}: {}", id, e)),
+                Err(e) => {
+                    return Err(
+                        AppError::from(e).context(format!("Failed to get conversation {}", id))
+                    )
+                }
             }
         }
-        result
+        Ok(result)
     } else {
-        Conversation::get_all(&conn, 1000) // Get up to 1000 conversations
-            .map_err(|e| format!("Failed to get conversations: {}", e))?
-    };
-
-    let mut exported_conversations = Vec::new();
-
-    for conv in conversations {
-        let messages = Message::get_by_conversation(&conn, &conv.id)
-            .map_err(|e| format!("Failed to get messages for conversation {}: {}", conv.id, e))?;
-
-        let exported_messages: Vec<ExportedMessage> = messages
-            .into_iter()
-            .map(|msg| ExportedMessage {
-                id: msg.id,
-                role: msg.role,
-                content: msg.content,
-                timestamp: msg.timestamp,
-                tokens_used: msg.tokens_used,
-            })
-            .collect();
-
-        exported_conversations.push(ExportedConversation {
-            id: conv.id,
-            title: conv.title,
-            provider: conv.provider,
-            model: conv.model,
-            system_prompt: conv.system_prompt,
-            created_at: conv.created_at,
-            updated_at: conv.updated_at,
-            messages: exported_messages,
-        });
+        Conversation::get_all(conn, 1000) // Get up to 1000 conversations
+            .map_err(|e| AppError::from(e).context("Failed to get conversations"))
     }
+}
 
-    let export_data = ExportData {
-        version: "1.0.0".to_string(),
-        export_timestamp: chrono::Utc::now().timestamp(),
-        conversations: exported_conversations,
-    };
+/// Render one conversation, which must exist
+fn export_one(
+    db: &Database,
+    blobs: &BlobStore,
+    conversation_id: &str,
+    format: ExportFormat,
+    whole_tree: bool,
+) -> AppResult<Vec<u8>> {
+    let conn = db.conn().lock()?;
+    let conversation = Conversation::get_by_id(&conn, conversation_id)
+        .map_err(|e| AppError::from(e).context("Failed to get conversation"))?
+        .ok_or_else(|| AppError::not_found("Conversation not found"))?;
+    exporters::export(&conn, blobs, vec![conversation], format, whole_tree)
+}
 
-    serde_json::to_string_pretty(&export_data)
-        .map_err(|e| format!("Failed to serialize export data: {}", e))
+fn utf8(bytes: Vec<u8>) -> AppResult<String> {
+    String::from_utf8(bytes).map_err(|e| AppError::internal(format!("Export is not UTF-8: {}", e)))
 }
 
+/// Export a conversation as Markdown. By default only the active path is
+/// rendered; with `whole_tree` every branch is included with branch markers.
+/// Attachments are linked from the blob store.
 #[tauri::command]
 pub fn export_conversation_markdown(
     db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
     conversation_id: String,
-) -> Result<String, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-
-    let conversation = Conversation::get_by_id(&conn, &conversation_id)
-        .map_err(|e| format!("Failed to get conversation: {}", e))?
-        .ok_or_else(|| "Conversation not found".to_string())?;
-
-    let messages = Message::get_by_conversation(&conn, &conversation_id)
-        .map_err(|e| format!("Failed to get messages: {}", e))?;
-
-    let mut markdown = String::new();
-
-    // Header
-    markdown.push_str(&format!("# {}\n\n", conversation.title));
-    markdown.push_str(&format!("**Provider:** {}\n", conversation.provider));
-    markdown.push_str(&format!("**Model:** {}\n", conversation.model));
-    markdown.push_str(&format!(
-        "**Created:** {}\n",
-        chrono::DateTime::from_timestamp(conversation.created_at, 0)
-            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
-            .unwrap_or_else(|| "Unknown".to_string())
-    ));
-    markdown.push_str("\n---\n\n");
-
-    // Messages
-    for msg in messages {
-        let role_header = match msg.role.as_str() {
-            "user" => "## 👤 User",
-            "assistant" => "## 🤖 Assistant",
-            "system" => "## ⚙️ System",
-            _ => &format!("## {}", msg.role),
-        };
-
-        markdown.push_str(&format!("{}\n\n", role_header));
-        markdown.push_str(&format!("{}\n\n", msg.content));
-
-        if let Some(tokens) = msg.tokens_used {
-            markdown.push_str(&format!("*Tokens used: {}*\n\n", tokens));
-        }
-
-        markdown.push_str("---\n\n");
-    }
-
-    Ok(markdown)
+    whole_tree: Option<bool>,
+) -> AppResult<String> {
+    utf8(export_one(
+        &db,
+        &blobs,
+        &conversation_id,
+        ExportFormat::Markdown,
+        whole_tree.unwrap_or(false),
+    )?)
 }
 
+/// Export a conversation as a standalone HTML page with attachments embedded
 #[tauri::command]
 pub fn export_conversation_html(
     db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
     conversation_id: String,
-) -> Result<String, String> {
-    let markdown_content = export_conversation_markdown(db, conversation_id)?;
-
-    // Configure comrak options for better HTML output
-    let mut options = ComrakOptions::default();
-    options.extension.strikethrough = true;
-    options.extension.tagfilter = false;
-    options.extension.table = true;
-    options.extension.autolink = true;
-    options.extension.tasklist = true;
-    options.extension.superscript = true;
-    options.extension.footnotes = true;
-    options.render.hardbreaks = true;
-    options.render.unsafe_ = false; // Keep safe
-
-    let html_body = markdown_to_html(&markdown_content, &options);
-
-    // Create a complete HTML document with CSS styling
-    let html = format!(
-        r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>AI Conversation Export</title>
-    <style>
-        body {{
-            font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, "Helvetica Neue", Arial, sans-serif;
-            line-height: 1.6;
-            color: #333;
-            max-width: 800px;
-            margin: 40px auto;
-            padding: 20px;
-            background-color: #fff;
-        }}
-
-        h1 {{
-            color: #2c3e50;
-            border-bottom: 3px solid #3498db;
-            padding-bottom: 10px;
-            margin-bottom: 30px;
-        }}
-
-        h2 {{
-            color: #34495e;
-            margin-top: 30px;
-            margin-bottom: 15px;
-            padding: 10px 15px;
-            border-left: 4px solid #3498db;
-            background-color: #f8f9fa;
-        }}
-
-        p {{
-            margin-bottom: 15px;
-            text-align: justify;
-        }}
-
-        pre {{
-            background-color: #f4f4f4;
-            border: 1px solid #ddd;
-            border-radius: 4px;
-            padding: 15px;
-            overflow-x: auto;
-            margin: 15px 0;
-        }}
-
-        code {{
-            background-color: #f4f4f4;
-            padding: 2px 4px;
-            border-radius: 3px;
-            font-family: "SF Mono", "Monaco", "Inconsolata", "Fira Code", "Fira Mono", "Droid Sans Mono", "Source Code Pro", monospace;
-        }}
-
-        blockquote {{
-            border-left: 4px solid #e74c3c;
-            margin: 15px 0;
-            padding: 10px 20px;
-            background-color: #fdf2f2;
-            font-style: italic;
-        }}
-
-        table {{
-            border-collapse: collapse;
-            width: 100%;
-            margin: 15px 0;
-        }}
-
-        th, td {{
-            border: 1px solid #ddd;
-            padding: 8px 12px;
-            text-align: left;
-        }}
-
-        th {{
-            background-color: #f2f2f2;
-            font-weight: bold;
-        }}
-
-        hr {{
-            border: none;
-            height: 2px;
-            background: linear-gradient(to right, #3498db, #transparent);
-            margin: 30px 0;
-        }}
-
-        .metadata {{
-            background-color: #ecf0f1;
-            padding: 15px;
-            border-radius: 5px;
-            margin-bottom: 30px;
-            font-size: 14px;
-        }}
-
-        .metadata strong {{
-            color: #2c3e50;
-        }}
-
-        .timestamp {{
-            color: #7f8c8d;
-            font-size: 12px;
-            font-style: italic;
-            margin-top: 10px;
-        }}
-
-        .tokens {{
-            color: #8e44ad;
-            font-size: 12px;
-            font-style: italic;
-            margin-top: 5px;
-        }}
-
-        @media print {{
-            body {{
-                max-width: none;
-                margin: 0;
-                padding: 20px;
-            }}
-
-            h2 {{
-                page-break-after: avoid;
-            }}
-
-            pre, blockquote {{
-                page-break-inside: avoid;
-            }}
-        }}
-
-        @media (max-width: 600px) {{
-            body {{
-                margin: 20px;
-                padding: 15px;
-            }}
-
-            h1 {{
-                font-size: 24px;
-            }}
-
-            h2 {{
-                font-size: 18px;
-                padding: 8px 12px;
-            }}
-        }}
-    </style>
-</head>
-<body>
-    {}
-    <div class="timestamp">
-        <hr>
-        <p><em>Exported on {}</em></p>
-    </div>
-</body>
-</html>"#,
-        html_body,
-        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
-    );
-
-    Ok(html)
+    whole_tree: Option<bool>,
+) -> AppResult<String> {
+    utf8(export_one(
+        &db,
+        &blobs,
+        &conversation_id,
+        ExportFormat::Html,
+        whole_tree.unwrap_or(false),
+    )?)
 }
 
+/// Export a conversation as PDF with its Markdown rendered. By default only the
+/// active path is included; with `whole_tree` every branch is.
 #[tauri::command]
 pub fn export_conversation_pdf(
     db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
     conversation_id: String,
-) -> Result<Vec<u8>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-
-    let conversation = Conversation::get_by_id(&conn, &conversation_id)
-        .map_err(|e| format!("Failed to get conversation: {}", e))?
-        .ok_or_else(|| "Conversation not found".to_string())?;
-
-    let messages = Message::get_by_conversation(&conn, &conversation_id)
-        .map_err(|e| format!("Failed to get messages: {}", e))?;
-
-    // Create PDF document
-    let (doc, page1, layer1) = PdfDocument::new(
-        "AI Conversation Export",
-        Mm(210.0), // A4 width
-        Mm(297.0), // A4 height
-        "Layer 1",
-    );
-
-    let current_layer = doc.get_page(page1).get_layer(layer1);
-
-    // Define fonts and sizes
-    let helvetica = doc
-        .add_builtin_font(BuiltinFont::Helvetica)
-        .map_err(|e| e.to_string())?;
-    let helvetica_bold = doc
-        .add_builtin_font(BuiltinFont::HelveticaBold)
-        .map_err(|e| e.to_string())?;
-    let _courier = doc
-        .add_builtin_font(BuiltinFont::Courier)
-        .map_err(|e| e.to_string())?;
-
-    let title_size = 18.0;
-    let header_size = 14.0;
-    let body_size = 11.0;
-    let small_size = 9.0;
-
-    let margin_left = Mm(20.0);
-    let _margin_right = Mm(190.0);
-    let page_width = Mm(170.0); // 210 - 20 - 20
-    let mut current_y = Mm(270.0); // Start near top
-    let line_height = Mm(5.0);
-
-    // Helper function to add text and handle page breaks
-    let add_text = |layer: &PdfLayerReference,
-                    text: &str,
-                    font: IndirectFontRef,
-                    size: f64,
-                    x: Mm,
-                    y: &mut Mm,
-                    _bold: bool|
-     -> Result<(), String> {
-        if *y < Mm(30.0) {
-            // Need new page
-            return Ok(()); // For simplicity, we'll truncate for now
-        }
-
-        layer.use_text(text, size as f32, x, *y, &font);
-        *y = *y - line_height;
-        Ok(())
-    };
-
-    // Title
-    add_text(
-        &current_layer,
-        &conversation.title,
-        helvetica_bold.clone(),
-        title_size,
-        margin_left,
-        &mut current_y,
-        true,
+    whole_tree: Option<bool>,
+) -> AppResult<Vec<u8>> {
+    export_one(
+        &db,
+        &blobs,
+        &conversation_id,
+        ExportFormat::Pdf,
+        whole_tree.unwrap_or(false),
     )
-    .map_err(|e| format!("Failed to add title: {}", e))?;
-
-    current_y = current_y - line_height;
-
-    // Metadata
-    let metadata_text = format!(
-        "Provider: {} | Model: {}",
-        conversation.provider, conversation.model
-    );
-    add_text(
-        &current_layer,
-        &metadata_text,
-        helvetica.clone(),
-        body_size,
-        margin_left,
-        &mut current_y,
-        false,
-    )
-    .map_err(|e| format!("Failed to add metadata: {}", e))?;
-
-    let created_text = format!(
-        "Created: {}",
-        chrono::DateTime::from_timestamp(conversation.created_at / 1000, 0)
-            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
-            .unwrap_or_else(|| "Unknown".to_string())
-    );
-    add_text(
-        &current_layer,
-        &created_text,
-        helvetica.clone(),
-        body_size,
-        margin_left,
-        &mut current_y,
-        false,
-    )
-    .map_err(|e| format!("Failed to add creation date: {}", e))?;
-
-    current_y = current_y - line_height * 2.0;
-
-    // Messages
-    for (_index, msg) in messages.iter().enumerate() {
-        if current_y < Mm(50.0) {
-            // Not enough space for message, should create new page
-            break; // For simplicity, truncate
-        }
-
-        let role_text = match msg.role.as_str() {
-            "user" => "👤 User",
-            "assistant" => "🤖 Assistant",
-            "system" => "⚙️ System",
-            _ => &msg.role,
-        };
-
-        // Role header
-        add_text(
-            &current_layer,
-            role_text,
-            helvetica_bold.clone(),
-            header_size,
-            margin_left,
-            &mut current_y,
-            true,
-        )
-        .map_err(|e| format!("Failed to add role header: {}", e))?;
-
-        // Message content (simplified - just first 500 chars)
-        let content = if msg.content.len() > 500 {
-            format!("{}...", &msg.content[..497])
-        } else {
-            msg.content.clone()
-        };
-
-        // Split content into lines that fit the page width
-        let words: Vec<&str> = content.split_whitespace().collect();
-        let mut current_line = String::new();
-
-        for word in words {
-            let test_line = if current_line.is_empty() {
-                word.to_string()
-            } else {
-                format!("{} {}", current_line, word)
-            };
-
-            // Rough estimation: assume each character is about 0.6mm wide
-            if test_line.len() as f64 * 0.6 > page_width.0 as f64 {
-                if !current_line.is_empty() {
-                    add_text(
-                        &current_layer,
-                        &current_line,
-                        helvetica.clone(),
-                        body_size,
-                        margin_left,
-                        &mut current_y,
-                        false,
-                    )
-                    .map_err(|e| format!("Failed to add content line: {}", e))?;
-                    current_line = word.to_string();
-                } else {
-                    // Single word is too long, truncate it
-                    current_line = format!("{}...", &word[..word.len().min(50)]);
-                }
-            } else {
-                current_line = test_line;
-            }
-
-            if current_y < Mm(30.0) {
-                break; // Page full
-            }
-        }
-
-        if !current_line.is_empty() && current_y >= Mm(30.0) {
-            add_text(
-                &current_layer,
-                &current_line,
-                helvetica.clone(),
-                body_size,
-                margin_left,
-                &mut current_y,
-                false,
-            )
-            .map_err(|e| format!("Failed to add final content line: {}", e))?;
-        }
-
-        // Token count if available
-        if let Some(tokens) = msg.tokens_used {
-            let token_text = format!("Tokens used: {}", tokens);
-            add_text(
-                &current_layer,
-                &token_text,
-                helvetica.clone(),
-                small_size,
-                margin_left,
-                &mut current_y,
-                false,
-            )
-            .map_err(|e| format!("Failed to add token count: {}", e))?;
-        }
+}
 
-        current_y = current_y - line_height;
+/// Export several conversations (all if no ids are given) as one PDF with a
+/// table of contents, each conversation starting on a new page
+#[tauri::command]
+pub fn export_conversations_pdf(
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+    conversation_ids: Option<Vec<String>>,
+    whole_tree: Option<bool>,
+) -> AppResult<Vec<u8>> {
+    export_conversations(db, blobs, conversation_ids, ExportFormat::Pdf, whole_tree)
+}
 
-        if current_y < Mm(30.0) {
-            break; // No more space
-        }
+/// Export conversations into `path`, one file each named after the title and
+/// id. Only conversations changed since the last export into `path` are
+/// rewritten, and files of deleted conversations are removed.
+#[tauri::command]
+pub fn export_to_directory(
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+    path: String,
+    format: ExportFormat,
+    filter: Option<ExportFilter>,
+    whole_tree: Option<bool>,
+) -> AppResult<DirectoryExport> {
+    let dir = Path::new(&path);
+    if !dir.is_absolute() {
+        return Err(AppError::invalid_input(
+            "Export directory must be an absolute path",
+        ));
     }
-
-    // Export timestamp
-    current_y = Mm(20.0);
-    let export_text = format!(
-        "Exported on {}",
-        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
-    );
-    add_text(
-        &current_layer,
-        &export_text,
-        helvetica.clone(),
-        small_size,
-        margin_left,
-        &mut current_y,
-        false,
+    let conn = db.conn().lock()?;
+    exporters::export_to_directory(
+        &conn,
+        &blobs,
+        dir,
+        format,
+        &filter.unwrap_or_default(),
+        whole_tree.unwrap_or(false),
     )
-    .map_err(|e| format!("Failed to add export timestamp: {}", e))?;
+}
 
-    // Save to bytes
-    let mut buffer = Vec::new();
-    doc.save(&mut BufWriter::new(&mut buffer))
-        .map_err(|e| format!("Failed to save PDF: {}", e))?;
+#[tauri::command]
+pub fn get_export_mirror_settings(db: State<'_, Database>) -> AppResult<MirrorSettings> {
+    let conn = db.conn().lock()?;
+    MirrorSettings::load(&conn)
+}
 
-    Ok(buffer)
+/// Directory, format, filter and interval of the scheduled mirror. The last
+/// run and its error are kept from the stored settings.
+#[tauri::command]
+pub fn update_export_mirror_settings(
+    db: State<'_, Database>,
+    settings: MirrorSettings,
+) -> AppResult<MirrorSettings> {
+    settings.validate()?;
+    let conn = db.conn().lock()?;
+    let stored = MirrorSettings::load(&conn)?;
+    let settings = MirrorSettings {
+        last_run: stored.last_run,
+        last_error: stored.last_error,
+        ..settings
+    };
+    settings.save(&conn)?;
+    Ok(settings)
+}
+
+/// Run the mirror now, whether or not it is due
+#[tauri::command]
+pub fn run_export_mirror(
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+) -> AppResult<DirectoryExport> {
+    let conn = db.conn().lock()?;
+    let mut settings = MirrorSettings::load(&conn)?;
+    settings.run(&conn, &blobs, chrono::Utc::now().timestamp())
 }
 
 #[tauri::command]
@@ -582,7 +310,7 @@ pub async fn save_export_file(
     app: tauri::AppHandle,
     content: String,
     filename: String,
-) -> Result<String, String> {
+) -> AppResult<String> {
     use std::sync::mpsc;
     use tauri_plugin_dialog::DialogExt;
 
@@ -598,79 +326,60 @@ pub async fn save_export_file(
     // Wait for the dialog to complete
     let file_path = rx.recv().unwrap();
 
-    let file_path = file_path.ok_or_else(|| "User cancelled file save".to_string())?;
+    let file_path = file_path.ok_or_else(|| AppError::cancelled("User cancelled file save"))?;
     let path = file_path
         .as_path()
-        .ok_or_else(|| "Invalid file path".to_string())?;
+        .ok_or_else(|| AppError::invalid_input("Invalid file path"))?;
 
-    std::fs::write(path, content).map_err(|e| format!("Failed to write file: {}", e))?;
+    std::fs::write(path, content).map_err(|e| AppError::from(e).context("Failed to write file"))?;
 
     Ok(path.to_string_lossy().to_string())
 }
+
+/// Dry-run an import: convert the export and report what `conflict` would do
+/// with each conversation without writing anything
 #[tauri::command]
-pub fn import_conversations_json(
+pub fn preview_import(
     db: State<'_, Database>,
     json_content: String,
-) -> Result<String, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-
-    let export_data: ExportData =
-        serde_json::from_str(&json_content).map_err(|e| format!("Failed to parse JSON: {}", e))?;
-
-    let mut imported_count = 0;
-    let mut skipped_count = 0;
-
-    for conv in export_data.conversations {
-        // Check if conversation already exists
-        if Conversation::get_by_id(&conn, &conv.id)
-            .map_err(|e| e.to_string())?
-            .is_some()
-        {
-            skipped_count += 1;
-            continue;
-        }
+    format: Option<ImportFormat>,
+    conflict: Option<ConflictStrategy>,
+) -> AppResult<ImportPreview> {
+    let parsed = importers::parse(&json_content, format)?;
+    let conn = db.conn().lock()?;
+    importers::preview(&conn, &parsed, conflict.unwrap_or_default())
+}
 
-        // Create conversation
-        let conversation = NewConversationWithId {
-            id: conv.id.clone(),
-            title: conv.title,
-            provider: conv.provider,
-            model: conv.model,
-            system_prompt: conv.system_prompt,
-            created_at: conv.created_at,
-            updated_at: conv.updated_at,
-        };
-
-        Conversation::create_with_id(&conn, conversation)
-            .map_err(|e| format!("Failed to create conversation {}: {}", conv.id, e))?;
-
-        // Import messages
-        for msg in conv.messages {
-            let msg_id = msg.id.clone();
-            let message = NewMessageWithId {
-                id: msg.id,
-                conversation_id: conv.id.clone(),
-                role: msg.role,
-                content: msg.content,
-                timestamp: msg.timestamp,
-                tokens_used: msg.tokens_used,
-            };
-
-            Message::create_with_id(&conn, message)
-                .map_err(|e| format!("Failed to create message {}: {}", msg_id, e))?;
-        }
+/// Import conversations from this app's JSON export, a ChatGPT or Claude.ai
+/// export, or a Markdown transcript. The format is detected unless given, and
+/// conversations that already exist are skipped unless `conflict` says
+/// otherwise. Nothing is written if any conversation fails.
+#[tauri::command]
+pub fn import_conversations_json(
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+    json_content: String,
+    format: Option<ImportFormat>,
+    conflict: Option<ConflictStrategy>,
+) -> AppResult<ImportResult> {
+    let parsed = importers::parse(&json_content, format)?;
+    let conn = db.conn().lock()?;
+    importers::import(&conn, &blobs, parsed, conflict.unwrap_or_default())
+}
 
-        imported_count += 1;
-    }
+/// JSON Schema of the export format, generated from the export types so
+/// other tools can validate exports
+pub fn export_schema() -> AppResult<serde_json::Value> {
+    serde_json::to_value(schemars::schema_for!(ExportData)).map_err(AppError::from)
+}
 
-    Ok(format!(
-        "Import completed: {} conversations imported, {} skipped (already exist)",
-        imported_count, skipped_count
-    ))
+#[tauri::command]
+pub fn get_export_schema() -> AppResult<serde_json::Value> {
+    export_schema()
 }
 
 #[tauri::command]
-pub async fn load_import_file(app: tauri::AppHandle) -> Result<String, String> {
+pub async fn load_import_file(app: tauri::AppHandle) -> AppResult<String> {
     use std::sync::{Arc, Mutex};
     use std::thread;
     use std::time::Duration;
@@ -681,7 +390,7 @@ pub async fn load_import_file(app: tauri::AppHandle) -> Result<String, String> {
 
     app.dialog()
         .file()
-        .add_filter("JSON files", &["json"])
+        .add_filter("Conversation exports", &["json", "md", "markdown", "txt"])
         .pick_file(move |file_path| {
             let mut res = result_clone.lock().unwrap();
             *res = Some(file_path);
@@ -696,20 +405,22 @@ pub async fn load_import_file(app: tauri::AppHandle) -> Result<String, String> {
         }
     };
 
-    let file_path = file_path.ok_or_else(|| "User cancelled file selection".to_string())?;
+    let file_path =
+        file_path.ok_or_else(|| AppError::cancelled("User cancelled file selection"))?;
     let path = file_path
         .as_path()
-        .ok_or_else(|| "Invalid file path".to_string())?;
+        .ok_or_else(|| AppError::invalid_input("Invalid file path"))?;
 
-    std::fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))
+    std::fs::read_to_string(path).map_err(|e| AppError::from(e).context("Failed to read file"))
 }
 
 #[tauri::command]
 pub fn export_single_conversation_json(
     db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
     conversation_id: String,
-) -> Result<String, String> {
-    export_conversations_json(db, Some(vec![conversation_id]))
+) -> AppResult<String> {
+    export_conversations_json(db, blobs, Some(vec![conversation_id]))
 }
 
 #[tauri::command]
@@ -718,30 +429,16 @@ pub async fn save_single_conversation_export(
     conversation_id: String,
     format: String,
     title: String,
-) -> Result<String, String> {
-    let db = app.state::<Database>();
-
-    let (content_result, extension): (Result<Vec<u8>, String>, &str) = match format.as_str() {
-        "json" => {
-            let content = export_single_conversation_json(db.clone(), conversation_id)?;
-            (Ok(content.into_bytes()), "json")
-        }
-        "markdown" => {
-            let content = export_conversation_markdown(db.clone(), conversation_id)?;
-            (Ok(content.into_bytes()), "md")
-        }
-        "html" => {
-            let content = export_conversation_html(db.clone(), conversation_id)?;
-            (Ok(content.into_bytes()), "html")
-        }
-        "pdf" => {
-            let content = export_conversation_pdf(db.clone(), conversation_id)?;
-            (Ok(content), "pdf")
-        }
-        _ => return Err("Invalid format. Supported: json, markdown, html, pdf".to_string()),
-    };
-
-    let content_bytes = content_result.map_err(|e| format!("Failed to generate content: {}", e))?;
+    whole_tree: Option<bool>,
+) -> AppResult<String> {
+    let format: ExportFormat = format.parse()?;
+    let content = export_one(
+        &app.state::<Database>(),
+        &app.state::<BlobStore>(),
+        &conversation_id,
+        format,
+        whole_tree.unwrap_or(false),
+    )?;
 
     let filename = format!(
         "{}_{}.{}",
@@ -755,11 +452,10 @@ pub async fn save_single_conversation_export(
             .collect::<String>()
             .trim_end_matches('_'),
         chrono::Utc::now().format("%Y%m%d_%H%M%S"),
-        extension
+        format.extension()
     );
 
-    // Save binary content for PDF, text content for others
-    save_export_file_bytes(app, content_bytes, filename).await
+    save_export_file_bytes(app, content, filename).await
 }
 
 #[tauri::command]
@@ -767,7 +463,7 @@ pub async fn save_export_file_bytes(
     app: tauri::AppHandle,
     content: Vec<u8>,
     filename: String,
-) -> Result<String, String> {
+) -> AppResult<String> {
     use std::sync::mpsc;
     use tauri_plugin_dialog::DialogExt;
 
@@ -783,12 +479,38 @@ pub async fn save_export_file_bytes(
     // Wait for the dialog to complete
     let file_path = rx.recv().unwrap();
 
-    let file_path = file_path.ok_or_else(|| "User cancelled file save".to_string())?;
+    let file_path = file_path.ok_or_else(|| AppError::cancelled("User cancelled file save"))?;
     let path = file_path
         .as_path()
-        .ok_or_else(|| "Invalid file path".to_string())?;
+        .ok_or_else(|| AppError::invalid_input("Invalid file path"))?;
 
-    std::fs::write(path, content).map_err(|e| format!("Failed to write file: {}", e))?;
+    std::fs::write(path, content).map_err(|e| AppError::from(e).context("Failed to write file"))?;
 
     Ok(path.to_string_lossy().to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The committed schema must match the export types. Regenerate it with
+    /// `UPDATE_EXPORT_SCHEMA=1 cargo test export_schema`.
+    #[test]
+    fn export_schema_is_up_to_date() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("schemas")
+            .join("conversation-export.schema.json");
+        let generated = export_schema().expect("schema");
+        if std::env::var_os("UPDATE_EXPORT_SCHEMA").is_some() {
+            let json = serde_json::to_string_pretty(&generated).expect("serialize schema");
+            std::fs::write(&path, json + "\n").expect("write schema");
+        }
+        let committed: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).expect("read schema"))
+                .expect("parse schema");
+        assert_eq!(
+            committed, generated,
+            "schemas/conversation-export.schema.json is stale"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::{AppError, AppResult};
 use serde::Serialize;
 use std::process::Command;
 
@@ -22,7 +23,7 @@ pub struct GitContext {
 /// Get comprehensive git context for a given path (defaults to current working directory).
 /// Returns JSON with { is_repo, branch, dirty, uncommitted_changes, recent_commits, remote_url }.
 #[tauri::command]
-pub async fn get_git_context(path: Option<String>) -> Result<GitContext, String> {
+pub async fn get_git_context(path: Option<String>) -> AppResult<GitContext> {
     let cwd = path.unwrap_or_else(|| String::from("."));
 
     // Check if inside a git work tree
@@ -32,7 +33,7 @@ pub async fn get_git_context(path: Option<String>) -> Result<GitContext, String>
         .arg("rev-parse")
         .arg("--is-inside-work-tree")
         .output()
-        .map_err(|e| format!("failed to run git: {}", e))?;
+        .map_err(|e| AppError::from(e).context("failed to run git"))?;
 
     if !inside.status.success() {
         return Ok(GitContext {
@@ -53,7 +54,7 @@ pub async fn get_git_context(path: Option<String>) -> Result<GitContext, String>
         .arg("--abbrev-ref")
         .arg("HEAD")
         .output()
-        .map_err(|e| format!("failed to run git: {}", e))?;
+        .map_err(|e| AppError::from(e).context("failed to run git"))?;
 
     let branch = if branch_out.status.success() {
         let s = String::from_utf8_lossy(&branch_out.stdout)
@@ -71,7 +72,7 @@ pub async fn get_git_context(path: Option<String>) -> Result<GitContext, String>
         .arg("status")
         .arg("--porcelain")
         .output()
-        .map_err(|e| format!("failed to run git: {}", e))?;
+        .map_err(|e| AppError::from(e).context("failed to run git"))?;
 
     let (dirty, uncommitted_changes) = if status_out.status.success() {
         let output_str = String::from_utf8_lossy(&status_out.stdout).to_string();
@@ -90,7 +91,7 @@ pub async fn get_git_context(path: Option<String>) -> Result<GitContext, String>
         .arg("-5")
         .arg("--pretty=format:%H%x00%an%x00%ar%x00%s")
         .output()
-        .map_err(|e| format!("failed to run git log: {}", e))?;
+        .map_err(|e| AppError::from(e).context("failed to run git log"))?;
 
     let recent_commits = if commits_out.status.success() {
         String::from_utf8_lossy(&commits_out.stdout)
@@ -143,7 +144,7 @@ pub async fn get_git_context(path: Option<String>) -> Result<GitContext, String>
 
 /// Format git context as human-readable text for AI consumption
 #[tauri::command]
-pub async fn format_git_context(path: Option<String>) -> Result<String, String> {
+pub async fn format_git_context(path: Option<String>) -> AppResult<String> {
     let context = get_git_context(path).await?;
 
     if !context.is_repo {
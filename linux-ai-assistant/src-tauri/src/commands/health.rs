@@ -1,6 +1,7 @@
+use crate::error::AppResult;
 use tauri::command;
 
 #[command]
-pub fn ping() -> Result<String, String> {
+pub fn ping() -> AppResult<String> {
     Ok("ok".into())
 }
@@ -1,65 +1,36 @@
-use crate::database::{messages::*, Database};
+use crate::attachments::{self, BlobStore};
+use crate::commands::provider::{
+    generate_with_provider, take_generation_report, ProviderMessage, TokenUsage,
+};
+use crate::database::{
+    attachments::Attachment, conversations::Conversation, messages::*, profiles::Profile,
+    usage::ModelPrice, Database,
+};
+use crate::error::{AppError, AppResult};
+use crate::tokenizer::{count_message_tokens, count_tokens};
+use crate::usage::is_free_provider;
 use rusqlite::Connection;
-use tauri::State;
+use tauri::{AppHandle, State};
 
 // Helper function for synchronous access (used by IPC)
-pub fn get_last_assistant_message_sync(conn: &Connection) -> Result<Option<Message>, String> {
+pub fn get_last_assistant_message_sync(conn: &Connection) -> AppResult<Option<Message>> {
     // Get the most recently updated conversation
-    let conversations = crate::database::conversations::Conversation::get_all(conn, 1)
-        .map_err(|e| e.to_string())?;
+    let conversations = crate::database::conversations::Conversation::get_all(conn, 1)?;
 
-    if conversations.is_empty() {
-        return Ok(None);
-    }
-
-    // Get messages from that conversation, filtered by role='assistant'
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, conversation_id, role, content, timestamp, tokens_used
-         FROM messages
-         WHERE conversation_id = ?1 AND role = 'assistant' AND deleted = 0
-         ORDER BY timestamp DESC
-         LIMIT 1",
-        )
-        .map_err(|e| e.to_string())?;
-
-    let mut messages = stmt
-        .query_map([&conversations[0].id], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                conversation_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                timestamp: row.get(4)?,
-                tokens_used: row.get(5)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
-
-    match messages.next() {
-        Some(Ok(msg)) => Ok(Some(msg)),
-        Some(Err(e)) => Err(e.to_string()),
+    match conversations.first() {
+        Some(conversation) => {
+            Message::get_last_assistant(conn, &conversation.id).map_err(AppError::from)
+        }
         None => Ok(None),
     }
 }
 
-#[tauri::command]
-pub async fn create_message(
-    db: State<'_, Database>,
-    conversation_id: String,
-    role: String,
-    content: String,
-    tokens_used: Option<i64>,
-) -> Result<Message, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    let new_msg = NewMessage {
-        conversation_id,
-        role,
-        content,
-        tokens_used,
-    };
+/// Store a message and, for an assistant reply, its token usage. The
+/// connection is released on return, before the hooks that lock it again.
+pub(crate) fn store_message(db: &Database, new_msg: NewMessage) -> AppResult<Message> {
+    let conn = db.conn().lock()?;
     // Create the user message
-    let created = Message::create(&conn, new_msg).map_err(|e| e.to_string())?;
+    let created = Message::create(&conn, new_msg)?;
 
     // Dev helper: if DEV_ECHO_RESPONSES=1 is set, automatically create an assistant reply
     // This is handy for local development to test end-to-end flow without an LLM provider.
@@ -74,6 +45,36 @@ pub async fn create_message(
         let _ = Message::create(&conn, assistant);
     }
 
+    if created.role != "assistant" {
+        return Ok(created);
+    }
+
+    let conversation = Conversation::get_by_id(&conn, &created.conversation_id)?
+        .ok_or_else(|| AppError::not_found("Conversation not found"))?;
+    record_token_usage(&conn, created, &conversation.provider, &conversation.model)
+}
+
+#[tauri::command]
+pub async fn create_message(
+    app: AppHandle,
+    db: State<'_, Database>,
+    conversation_id: String,
+    role: String,
+    content: String,
+    tokens_used: Option<i64>,
+) -> AppResult<Message> {
+    let new_msg = NewMessage {
+        conversation_id,
+        role,
+        content,
+        tokens_used,
+    };
+    let created = store_message(&db, new_msg)?;
+    if created.role == "assistant" {
+        crate::summarizer::schedule(&app, &created.conversation_id);
+        crate::dbus_service::message_added(&created);
+        crate::notifications::reply_stored(&app, &created);
+    }
     Ok(created)
 }
 
@@ -81,9 +82,9 @@ pub async fn create_message(
 pub async fn get_conversation_messages(
     db: State<'_, Database>,
     conversation_id: String,
-) -> Result<Vec<Message>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Message::get_by_conversation(&conn, &conversation_id).map_err(|e| e.to_string())
+) -> AppResult<Vec<Message>> {
+    let conn = db.conn().lock()?;
+    Message::get_by_conversation(&conn, &conversation_id).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -91,9 +92,9 @@ pub async fn get_last_messages(
     db: State<'_, Database>,
     conversation_id: String,
     n: i64,
-) -> Result<Vec<Message>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Message::get_last_n(&conn, &conversation_id, n).map_err(|e| e.to_string())
+) -> AppResult<Vec<Message>> {
+    let conn = db.conn().lock()?;
+    Message::get_last_n(&conn, &conversation_id, n).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -101,9 +102,9 @@ pub async fn search_messages(
     db: State<'_, Database>,
     query: String,
     limit: i64,
-) -> Result<Vec<Message>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Message::search(&conn, &query, limit).map_err(|e| e.to_string())
+) -> AppResult<Vec<Message>> {
+    let conn = db.conn().lock()?;
+    Message::search(&conn, &query, limit).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -111,67 +112,277 @@ pub async fn update_message(
     db: State<'_, Database>,
     id: String,
     content: String,
-) -> Result<Message, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Message::update(&conn, &id, &content).map_err(|e| e.to_string())
+) -> AppResult<Message> {
+    let conn = db.conn().lock()?;
+    Message::update(&conn, &id, &content).map_err(AppError::from)
 }
 
+/// Soft-delete a message, removing its attachments for good
 #[tauri::command]
-pub async fn delete_message(db: State<'_, Database>, id: String) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Message::delete(&conn, &id).map_err(|e| e.to_string())
+pub async fn delete_message(
+    db: State<'_, Database>,
+    blobs: State<'_, BlobStore>,
+    id: String,
+) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Message::delete(&conn, &id)?;
+    attachments::remove_all(&conn, &blobs, Attachment::get_by_message(&conn, &id)?)
 }
 
 #[tauri::command]
 pub async fn get_conversation_token_count(
     db: State<'_, Database>,
     conversation_id: String,
-) -> Result<i64, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Message::get_conversation_token_count(&conn, &conversation_id).map_err(|e| e.to_string())
+) -> AppResult<i64> {
+    let conn = db.conn().lock()?;
+    Message::get_conversation_token_count(&conn, &conversation_id).map_err(AppError::from)
+}
+
+#[tauri::command]
+pub async fn get_last_assistant_message(db: State<'_, Database>) -> AppResult<Option<Message>> {
+    let conn = db.conn().lock()?;
+    get_last_assistant_message_sync(&conn)
+}
+
+#[tauri::command]
+pub async fn get_conversation_tree(
+    db: State<'_, Database>,
+    conversation_id: String,
+) -> AppResult<Vec<Message>> {
+    let conn = db.conn().lock()?;
+    Message::get_tree(&conn, &conversation_id).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn get_last_assistant_message(
+pub async fn list_siblings(db: State<'_, Database>, message_id: String) -> AppResult<Vec<Message>> {
+    let conn = db.conn().lock()?;
+    Message::get_siblings(&conn, &message_id).map_err(AppError::from)
+}
+
+/// Switch the conversation to the path through `message_id` and return that path
+#[tauri::command]
+pub async fn set_active_leaf(
     db: State<'_, Database>,
-) -> Result<Option<Message>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+    message_id: String,
+) -> AppResult<Vec<Message>> {
+    let conn = db.conn().lock()?;
+    Message::set_active_leaf(&conn, &message_id).map_err(AppError::from)
+}
 
-    // Get the most recently updated conversation
-    let conversations = crate::database::conversations::Conversation::get_all(&conn, 1)
-        .map_err(|e| e.to_string())?;
+/// Generate a new assistant reply as a sibling of an assistant message, or as a
+/// new reply to a user message. The new reply becomes the active leaf.
+#[tauri::command]
+pub async fn regenerate_message(
+    app: AppHandle,
+    db: State<'_, Database>,
+    message_id: String,
+    provider: Option<String>,
+    model: Option<String>,
+) -> AppResult<Message> {
+    let parent_id = {
+        let conn = db.conn().lock()?;
+        let message = Message::get_by_id(&conn, &message_id)?
+            .ok_or_else(|| AppError::not_found("Message not found"))?;
+        if message.role == "assistant" {
+            message.parent_message_id.ok_or_else(|| {
+                AppError::invalid_input("Assistant message has no prompt to regenerate from")
+            })?
+        } else {
+            message.id
+        }
+    };
 
-    if conversations.is_empty() {
-        return Ok(None);
-    }
+    generate_reply(&app, &db, &parent_id, provider, model).await
+}
+
+/// Create an edited copy of a user message as a sibling of the original, then
+/// generate a reply to it. Returns the new user message followed by the reply.
+/// If generation fails the edited message is kept as the active leaf.
+#[tauri::command]
+pub async fn edit_and_resubmit(
+    app: AppHandle,
+    db: State<'_, Database>,
+    message_id: String,
+    content: String,
+    provider: Option<String>,
+    model: Option<String>,
+) -> AppResult<Vec<Message>> {
+    let edited = {
+        let conn = db.conn().lock()?;
+        let original = Message::get_by_id(&conn, &message_id)?
+            .ok_or_else(|| AppError::not_found("Message not found"))?;
+        if original.role != "user" {
+            return Err(AppError::invalid_input(
+                "Only user messages can be edited and resubmitted",
+            ));
+        }
+        Message::create_with_parent(
+            &conn,
+            NewMessage {
+                conversation_id: original.conversation_id,
+                role: "user".to_string(),
+                content,
+                tokens_used: None,
+            },
+            original.parent_message_id.as_deref(),
+        )?
+    };
 
-    // Get messages from that conversation, filtered by role='assistant'
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, conversation_id, role, content, timestamp, tokens_used
-         FROM messages
-         WHERE conversation_id = ?1 AND role = 'assistant' AND deleted = 0
-         ORDER BY timestamp DESC
-         LIMIT 1",
+    let reply = generate_reply(&app, &db, &edited.id, provider, model).await?;
+    Ok(vec![edited, reply])
+}
+
+/// Ask the provider for a reply to the path ending at `parent_id` and store it as
+/// a child of that message
+pub(crate) async fn generate_reply(
+    app: &AppHandle,
+    db: &State<'_, Database>,
+    parent_id: &str,
+    provider: Option<String>,
+    model: Option<String>,
+) -> AppResult<Message> {
+    let (conversation_id, provider, model, context) = {
+        let conn = db.conn().lock()?;
+        let path = Message::get_path_to(&conn, parent_id)?;
+        let conversation_id = path
+            .last()
+            .map(|m| m.conversation_id.clone())
+            .ok_or_else(|| AppError::not_found("Message not found"))?;
+        let conversation = Conversation::get_by_id(&conn, &conversation_id)?
+            .ok_or_else(|| AppError::not_found("Conversation not found"))?;
+
+        let context = provider_context(&conn, &conversation, path)?;
+        (
+            conversation_id,
+            provider.unwrap_or(conversation.provider),
+            model.or(Some(conversation.model)),
+            context,
         )
-        .map_err(|e| e.to_string())?;
-
-    let mut messages = stmt
-        .query_map([&conversations[0].id], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                conversation_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                timestamp: row.get(4)?,
-                tokens_used: row.get(5)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
-
-    match messages.next() {
-        Some(Ok(msg)) => Ok(Some(msg)),
-        Some(Err(e)) => Err(e.to_string()),
-        None => Ok(None),
+    };
+
+    // Provider calls use blocking HTTP clients, so keep them off the async executor
+    let app_handle = app.clone();
+    let generation_conversation_id = conversation_id.clone();
+    let generation_provider = provider.clone();
+    let generation_model = model.clone();
+    let content = tauri::async_runtime::spawn_blocking(move || {
+        generate_with_provider(
+            app_handle,
+            &generation_provider,
+            generation_conversation_id,
+            context,
+            generation_model,
+        )
+    })
+    .await??;
+
+    let reply = {
+        let conn = db.conn().lock()?;
+        let reply = Message::create_with_parent(
+            &conn,
+            NewMessage {
+                conversation_id,
+                role: "assistant".to_string(),
+                content,
+                tokens_used: None,
+            },
+            Some(parent_id),
+        )?;
+        record_token_usage(
+            &conn,
+            reply,
+            &provider,
+            model.as_deref().unwrap_or_default(),
+        )?
+    };
+    crate::summarizer::schedule(app, &reply.conversation_id);
+    crate::dbus_service::message_added(&reply);
+    crate::notifications::reply_stored(app, &reply);
+    Ok(reply)
+}
+
+/// Provider messages for a path: the conversation's system prompt, then the path
+/// with each message's attachment ids
+fn provider_context(
+    conn: &Connection,
+    conversation: &Conversation,
+    path: Vec<Message>,
+) -> AppResult<Vec<ProviderMessage>> {
+    let mut context = Vec::new();
+    if let Some(system_prompt) = conversation.system_prompt.clone().filter(|p| !p.is_empty()) {
+        context.push(ProviderMessage {
+            role: "system".to_string(),
+            content: system_prompt,
+            ..Default::default()
+        });
+    }
+    for m in path {
+        let attachments = Attachment::get_by_message(conn, &m.id)?
+            .into_iter()
+            .map(|a| a.id)
+            .collect();
+        context.push(ProviderMessage {
+            role: m.role,
+            content: m.content,
+            attachments,
+            ..Default::default()
+        });
     }
+    Ok(context)
+}
+
+/// Store token counts, cost and the provider/model that answered on a new
+/// assistant message. What the provider layer reported for the conversation
+/// wins over the caller's provider and model, since a fallback may have
+/// answered; without reported usage the prompt path and reply are counted with
+/// the model's tokenizer.
+fn record_token_usage(
+    conn: &Connection,
+    message: Message,
+    provider: &str,
+    model: &str,
+) -> AppResult<Message> {
+    let report = take_generation_report(&message.conversation_id);
+    let (provider, model) = match &report {
+        Some(report) => (report.provider.as_str(), report.model.as_str()),
+        None => (provider, model),
+    };
+    let usage = match report.as_ref().and_then(|r| r.usage) {
+        Some(usage) => usage,
+        None => {
+            let conversation = Conversation::get_by_id(conn, &message.conversation_id)?
+                .ok_or_else(|| AppError::not_found("Conversation not found"))?;
+            let path = match message.parent_message_id.as_deref() {
+                Some(parent_id) => Message::get_path_to(conn, parent_id)?,
+                None => Vec::new(),
+            };
+            let context = provider_context(conn, &conversation, path)?;
+            TokenUsage {
+                prompt_tokens: count_message_tokens(provider, model, &context) as i64,
+                completion_tokens: count_tokens(provider, model, &message.content) as i64,
+            }
+        }
+    };
+
+    let profile_id = Profile::get_active(conn)?.map(|p| p.id);
+    let cost_usd = if is_free_provider(provider) {
+        Some(0.0)
+    } else {
+        ModelPrice::find_for_model(conn, model)?
+            .map(|price| price.cost(usage.prompt_tokens, usage.completion_tokens))
+    };
+
+    Message::record_usage(
+        conn,
+        &message.id,
+        &UsageRecord {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            provider: provider.to_string(),
+            model: model.to_string(),
+            profile_id,
+            cost_usd,
+        },
+    )
+    .map_err(AppError::from)
 }
@@ -2,11 +2,16 @@
 // Public re-exports for the commands submodules. Each submodule lives in its
 // own file (conversations.rs, messages.rs, settings.rs).
 
+pub mod attachments;
+pub mod backup;
+pub mod clipboard;
 pub mod conversations;
+pub mod encryption;
 pub mod export;
 pub mod git;
 pub mod health;
 pub mod messages;
+pub mod notifications;
 pub mod performance;
 pub mod profiles;
 pub mod project;
@@ -16,6 +21,7 @@ pub mod settings;
 pub mod shortcuts;
 pub mod tags;
 pub mod updater;
+pub mod usage;
 pub mod window;
 pub mod workspace_templates;
 
@@ -0,0 +1,26 @@
+use crate::database::Database;
+use crate::error::{AppError, AppResult};
+use crate::notifications::NotificationSettings;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_notification_settings(db: State<'_, Database>) -> AppResult<NotificationSettings> {
+    let conn = db.conn().lock()?;
+    NotificationSettings::load(&conn)
+}
+
+/// Do-not-disturb, the minimum duration worth a notification and the rate limit
+#[tauri::command]
+pub async fn update_notification_settings(
+    db: State<'_, Database>,
+    settings: NotificationSettings,
+) -> AppResult<NotificationSettings> {
+    if settings.max_per_minute == 0 {
+        return Err(AppError::invalid_input(
+            "Allow at least one notification per minute, or turn on do not disturb",
+        ));
+    }
+    let conn = db.conn().lock()?;
+    settings.save(&conn)?;
+    Ok(settings)
+}
@@ -1,3 +1,4 @@
+use crate::error::{AppError, AppResult};
 use serde::{Deserialize, Serialize};
 use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, Instant};
@@ -56,12 +57,12 @@ fn init_last_update() -> &'static Mutex<Instant> {
     LAST_UPDATE.get_or_init(|| Mutex::new(Instant::now() - Duration::from_secs(10)))
 }
 
-pub fn get_system_metrics() -> Result<SystemMetrics, String> {
+pub fn get_system_metrics() -> AppResult<SystemMetrics> {
     let system_mutex = init_system();
     let last_update_mutex = init_last_update();
 
-    let mut system = system_mutex.lock().map_err(|e| e.to_string())?;
-    let mut last_update = last_update_mutex.lock().map_err(|e| e.to_string())?;
+    let mut system = system_mutex.lock()?;
+    let mut last_update = last_update_mutex.lock()?;
 
     // Only refresh if it's been more than 1 second since last update
     let now = Instant::now();
@@ -75,7 +76,7 @@ pub fn get_system_metrics() -> Result<SystemMetrics, String> {
     let current_pid = std::process::id();
     let process = system
         .process(Pid::from_u32(current_pid))
-        .ok_or("Failed to get current process info")?;
+        .ok_or_else(|| AppError::internal("Failed to get current process info"))?;
 
     let total_memory = system.total_memory();
     let used_memory = system.used_memory();
@@ -111,31 +112,29 @@ pub fn get_system_metrics() -> Result<SystemMetrics, String> {
 }
 
 #[tauri::command]
-pub async fn get_performance_metrics() -> Result<SystemMetrics, String> {
+pub async fn get_performance_metrics() -> AppResult<SystemMetrics> {
     get_system_metrics()
 }
 
 #[tauri::command]
 pub async fn get_database_metrics(
     db: tauri::State<'_, crate::database::Database>,
-) -> Result<DatabaseMetrics, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+) -> AppResult<DatabaseMetrics> {
+    let conn = db.conn().lock()?;
 
     // Get conversation count
     let conversation_count: i64 = conn
         .prepare("SELECT COUNT(*) FROM conversations WHERE deleted = 0")
-        .and_then(|mut stmt| stmt.query_row([], |row| row.get(0)))
-        .map_err(|e| e.to_string())?;
+        .and_then(|mut stmt| stmt.query_row([], |row| row.get(0)))?;
 
     // Get message count
     let message_count: i64 = conn
         .prepare("SELECT COUNT(*) FROM messages WHERE deleted = 0")
-        .and_then(|mut stmt| stmt.query_row([], |row| row.get(0)))
-        .map_err(|e| e.to_string())?;
+        .and_then(|mut stmt| stmt.query_row([], |row| row.get(0)))?;
 
     // Get database file size (handle in-memory DB gracefully)
     let database_size = match conn.path() {
-        Some(path) => std::fs::metadata(path).map_err(|e| e.to_string())?.len(),
+        Some(path) => std::fs::metadata(path)?.len(),
         None => 0, // In-memory DB or unavailable path
     };
 
@@ -155,7 +154,7 @@ pub struct PerformanceSnapshot {
 #[tauri::command]
 pub async fn get_full_performance_snapshot(
     db: tauri::State<'_, crate::database::Database>,
-) -> Result<PerformanceSnapshot, String> {
+) -> AppResult<PerformanceSnapshot> {
     let system = get_system_metrics()?;
     let database = get_database_metrics(db).await?;
 
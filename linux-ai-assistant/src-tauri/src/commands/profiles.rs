@@ -1,37 +1,42 @@
-use crate::database::{profiles::NewProfile, profiles::Profile, Database};
+use crate::commands::provider::PROVIDERS;
+use crate::database::{
+    profiles::{FallbackTarget, NewProfile, Profile},
+    Database,
+};
+use crate::error::{AppError, AppResult};
 use tauri::State;
 
 #[tauri::command]
 pub async fn create_profile(
     db: State<'_, Database>,
     profile_data: NewProfile,
-) -> Result<Profile, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Profile::create(&conn, profile_data).map_err(|e| e.to_string())
+) -> AppResult<Profile> {
+    let conn = db.conn().lock()?;
+    Profile::create(&conn, profile_data).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn get_profile(db: State<'_, Database>, id: String) -> Result<Option<Profile>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Profile::get_by_id(&conn, &id).map_err(|e| e.to_string())
+pub async fn get_profile(db: State<'_, Database>, id: String) -> AppResult<Option<Profile>> {
+    let conn = db.conn().lock()?;
+    Profile::get_by_id(&conn, &id).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn get_all_profiles(db: State<'_, Database>) -> Result<Vec<Profile>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Profile::get_all(&conn).map_err(|e| e.to_string())
+pub async fn get_all_profiles(db: State<'_, Database>) -> AppResult<Vec<Profile>> {
+    let conn = db.conn().lock()?;
+    Profile::get_all(&conn).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn get_active_profile(db: State<'_, Database>) -> Result<Option<Profile>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Profile::get_active(&conn).map_err(|e| e.to_string())
+pub async fn get_active_profile(db: State<'_, Database>) -> AppResult<Option<Profile>> {
+    let conn = db.conn().lock()?;
+    Profile::get_active(&conn).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn set_active_profile(db: State<'_, Database>, id: String) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Profile::set_active(&conn, &id).map_err(|e| e.to_string())
+pub async fn set_active_profile(db: State<'_, Database>, id: String) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Profile::set_active(&conn, &id).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -39,13 +44,33 @@ pub async fn update_profile(
     db: State<'_, Database>,
     id: String,
     profile_data: NewProfile,
-) -> Result<Profile, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Profile::update(&conn, &id, profile_data).map_err(|e| e.to_string())
+) -> AppResult<Profile> {
+    let conn = db.conn().lock()?;
+    Profile::update(&conn, &id, profile_data).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn delete_profile(db: State<'_, Database>, id: String) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Profile::delete(&conn, &id).map_err(|e| e.to_string())
+pub async fn delete_profile(db: State<'_, Database>, id: String) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Profile::delete(&conn, &id).map_err(AppError::from)
+}
+
+/// Providers to try, in order, when a request to the requested provider fails
+#[tauri::command]
+pub async fn set_profile_fallback_chain(
+    db: State<'_, Database>,
+    id: String,
+    chain: Vec<FallbackTarget>,
+) -> AppResult<Profile> {
+    if let Some(unknown) = chain
+        .iter()
+        .find(|t| !PROVIDERS.contains(&t.provider.as_str()))
+    {
+        return Err(AppError::invalid_input(format!(
+            "Unknown provider: {}",
+            unknown.provider
+        )));
+    }
+    let conn = db.conn().lock()?;
+    Profile::set_fallback_chain(&conn, &id, &chain).map_err(AppError::from)
 }
@@ -2,12 +2,17 @@ use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{mpsc, Mutex, OnceLock};
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
+
+use crate::database::Database;
+use crate::error::{AppError, AppResult};
+use crate::project_index::{self, IndexStats};
 
 static WATCHER: OnceLock<Mutex<Option<RecommendedWatcher>>> = OnceLock::new();
 static IGNORE_PATTERNS: OnceLock<Mutex<Option<Gitignore>>> = OnceLock::new();
+static PROJECT_ROOT: OnceLock<Mutex<Option<PathBuf>>> = OnceLock::new();
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileMatch {
@@ -28,14 +33,14 @@ pub struct SearchResult {
     pub search_time_ms: u64,
 }
 
-fn build_gitignore(patterns: &[String], root: &PathBuf) -> Result<Gitignore, String> {
+fn build_gitignore(patterns: &[String], root: &PathBuf) -> AppResult<Gitignore> {
     let mut builder = GitignoreBuilder::new(root);
 
     // Add provided patterns
     for pattern in patterns {
-        builder
-            .add_line(None, pattern)
-            .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+        builder.add_line(None, pattern).map_err(|e| {
+            AppError::invalid_input(e.to_string()).context(format!("Invalid pattern '{}'", pattern))
+        })?;
     }
 
     // Try to add existing .gitignore files
@@ -46,10 +51,10 @@ fn build_gitignore(patterns: &[String], root: &PathBuf) -> Result<Gitignore, Str
 
     builder
         .build()
-        .map_err(|e| format!("Failed to build gitignore: {}", e))
+        .map_err(|e| AppError::internal(e.to_string()).context("Failed to build gitignore"))
 }
 
-fn should_ignore_path(path: &PathBuf, root: &PathBuf) -> bool {
+fn should_ignore_path(path: &Path, root: &Path) -> bool {
     let ignore_cell = IGNORE_PATTERNS.get_or_init(|| Mutex::new(None));
     if let Ok(guard) = ignore_cell.lock() {
         if let Some(ref gitignore) = *guard {
@@ -62,14 +67,14 @@ fn should_ignore_path(path: &PathBuf, root: &PathBuf) -> bool {
     false
 }
 
-fn get_file_type(path: &PathBuf) -> String {
+fn get_file_type(path: &Path) -> String {
     path.extension()
         .and_then(|ext| ext.to_str())
         .unwrap_or("unknown")
         .to_lowercase()
 }
 
-fn is_text_file(file_type: &str) -> bool {
+pub(crate) fn is_text_file(file_type: &str) -> bool {
     matches!(
         file_type,
         "txt"
@@ -205,7 +210,7 @@ fn walk_directory(
     query: &str,
     case_sensitive: bool,
     max_results: usize,
-) -> Result<SearchResult, String> {
+) -> AppResult<SearchResult> {
     let start_time = std::time::Instant::now();
     let mut all_matches = Vec::new();
     let mut files_searched = 0;
@@ -218,19 +223,20 @@ fn walk_directory(
         matches: &mut Vec<FileMatch>,
         files_searched: &mut usize,
         max_results: usize,
-    ) -> Result<(), String> {
+    ) -> AppResult<()> {
         if matches.len() >= max_results {
             return Ok(());
         }
 
-        let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))?;
+        let entries =
+            fs::read_dir(dir).map_err(|e| AppError::from(e).context("Failed to read directory"))?;
 
         for entry in entries {
             if matches.len() >= max_results {
                 break;
             }
 
-            let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+            let entry = entry.map_err(|e| AppError::from(e).context("Failed to read entry"))?;
             let path = entry.path();
 
             // Skip ignored paths
@@ -329,10 +335,12 @@ pub fn set_project_root(
     path: String,
     patterns: Option<Vec<String>>,
     app: tauri::AppHandle,
-) -> Result<(), String> {
+) -> AppResult<()> {
     let root = PathBuf::from(path);
     if !root.exists() || !root.is_dir() {
-        return Err("path does not exist or is not a directory".into());
+        return Err(AppError::invalid_input(
+            "path does not exist or is not a directory",
+        ));
     }
 
     // Default ignore patterns if none provided
@@ -358,6 +366,12 @@ pub fn set_project_root(
         *guard = Some(gitignore);
     }
 
+    // Remember the root for project search and indexing
+    let root_cell = PROJECT_ROOT.get_or_init(|| Mutex::new(None));
+    if let Ok(mut guard) = root_cell.lock() {
+        *guard = Some(root.clone());
+    }
+
     // Stop existing watcher
     if let Some(cell) = WATCHER.get() {
         if let Ok(mut guard) = cell.lock() {
@@ -369,10 +383,10 @@ pub fn set_project_root(
 
     let (tx, rx) = mpsc::channel::<Result<Event, notify::Error>>();
     let mut watcher = RecommendedWatcher::new(tx, Config::default())
-        .map_err(|e| format!("watcher init failed: {}", e))?;
+        .map_err(|e| AppError::io(e.to_string()).context("watcher init failed"))?;
     watcher
         .watch(&root, RecursiveMode::Recursive)
-        .map_err(|e| format!("watch path failed: {}", e))?;
+        .map_err(|e| AppError::io(e.to_string()).context("watch path failed"))?;
 
     // store watcher
     let _ = WATCHER.get_or_init(|| Mutex::new(None));
@@ -382,21 +396,35 @@ pub fn set_project_root(
         }
     }
 
-    // spawn receiver thread emitting events
+    // Build (or refresh) the retrieval index in the background
+    spawn_index_job(app.clone(), root.clone());
+
+    // spawn receiver thread emitting events and keeping the index fresh
     let app_handle = app.clone();
     let project_root = root.clone();
     std::thread::spawn(move || {
         while let Ok(ev) = rx.recv() {
             if let Ok(event) = ev {
-                let paths: Vec<String> = event
+                let paths: Vec<PathBuf> = event
                     .paths
                     .into_iter()
                     .filter(|path| !should_ignore_path(path, &project_root))
-                    .map(|p| p.to_string_lossy().to_string())
                     .collect();
 
+                if let Some(db) = app_handle.try_state::<Database>() {
+                    for path in &paths {
+                        if let Err(e) = project_index::refresh_path(&db, &project_root, path) {
+                            eprintln!("project index refresh failed for {:?}: {}", path, e);
+                        }
+                    }
+                }
+
                 // Only emit if we have non-ignored paths
                 if !paths.is_empty() {
+                    let paths: Vec<String> = paths
+                        .into_iter()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .collect();
                     let _ = app_handle.emit("project://file-event", paths);
                 }
             }
@@ -406,8 +434,65 @@ pub fn set_project_root(
     Ok(())
 }
 
+fn current_project_root() -> Option<PathBuf> {
+    PROJECT_ROOT
+        .get()
+        .and_then(|cell| cell.lock().ok())
+        .and_then(|guard| guard.clone())
+}
+
+fn current_gitignore() -> Gitignore {
+    IGNORE_PATTERNS
+        .get()
+        .and_then(|cell| cell.lock().ok())
+        .and_then(|guard| guard.clone())
+        .unwrap_or_else(Gitignore::empty)
+}
+
+fn spawn_index_job(app: tauri::AppHandle, root: PathBuf) {
+    std::thread::spawn(move || {
+        let Some(db) = app.try_state::<Database>() else {
+            return;
+        };
+        let gitignore = current_gitignore();
+        match project_index::index_project(&db, &root, &gitignore) {
+            Ok(stats) => {
+                let _ = app.emit("project://index-updated", stats);
+            }
+            Err(e) => eprintln!("project indexing failed for {:?}: {}", root, e),
+        }
+    });
+}
+
+/// Re-index the current project root synchronously and return statistics
 #[tauri::command]
-pub fn update_ignore_patterns(_patterns: Vec<String>) -> Result<(), String> {
+pub fn index_project(db: tauri::State<'_, Database>) -> AppResult<IndexStats> {
+    let root =
+        current_project_root().ok_or_else(|| AppError::invalid_input("No project root set"))?;
+    let gitignore = current_gitignore();
+    project_index::index_project(&db, &root, &gitignore)
+}
+
+/// Ranked search over the indexed chunks of the current project
+#[tauri::command]
+pub fn search_project_index(
+    db: tauri::State<'_, Database>,
+    query: String,
+    top_k: Option<usize>,
+) -> AppResult<Vec<crate::database::project_chunks::ScoredChunk>> {
+    let root =
+        current_project_root().ok_or_else(|| AppError::invalid_input("No project root set"))?;
+    let conn = db.conn().lock()?;
+    project_index::retrieve(
+        &conn,
+        &project_index::root_key(&root),
+        &query,
+        top_k.unwrap_or(project_index::DEFAULT_TOP_K),
+    )
+}
+
+#[tauri::command]
+pub fn update_ignore_patterns(_patterns: Vec<String>) -> AppResult<()> {
     // For now, we'll just clear the current patterns
     // They'll be rebuilt when set_project_root is called again
     let ignore_cell = IGNORE_PATTERNS.get_or_init(|| Mutex::new(None));
@@ -418,7 +503,7 @@ pub fn update_ignore_patterns(_patterns: Vec<String>) -> Result<(), String> {
 }
 
 #[tauri::command]
-pub fn stop_project_watch() -> Result<(), String> {
+pub fn stop_project_watch() -> AppResult<()> {
     if let Some(cell) = WATCHER.get() {
         if let Ok(mut guard) = cell.lock() {
             if let Some(_w) = guard.take() {
@@ -434,15 +519,17 @@ pub fn search_project_files(
     query: String,
     case_sensitive: Option<bool>,
     max_results: Option<usize>,
-) -> Result<SearchResult, String> {
+) -> AppResult<SearchResult> {
     if query.trim().is_empty() {
-        return Err("Search query cannot be empty".to_string());
+        return Err(AppError::invalid_input("Search query cannot be empty"));
     }
 
-    // Get the current project root from the file watcher
-    // For now, we'll use the current working directory if no project is set
-    let project_root =
-        std::env::current_dir().map_err(|e| format!("Failed to get current directory: {}", e))?;
+    // Use the project root set by `set_project_root`, falling back to the working directory
+    let project_root = match current_project_root() {
+        Some(root) => root,
+        None => std::env::current_dir()
+            .map_err(|e| AppError::from(e).context("Failed to get current directory"))?,
+    };
 
     let case_sensitive = case_sensitive.unwrap_or(false);
     let max_results = max_results.unwrap_or(100);
@@ -456,14 +543,16 @@ pub fn search_project_files_in_path(
     query: String,
     case_sensitive: Option<bool>,
     max_results: Option<usize>,
-) -> Result<SearchResult, String> {
+) -> AppResult<SearchResult> {
     if query.trim().is_empty() {
-        return Err("Search query cannot be empty".to_string());
+        return Err(AppError::invalid_input("Search query cannot be empty"));
     }
 
     let search_path = PathBuf::from(path);
     if !search_path.exists() || !search_path.is_dir() {
-        return Err("Search path does not exist or is not a directory".to_string());
+        return Err(AppError::invalid_input(
+            "Search path does not exist or is not a directory",
+        ));
     }
 
     let case_sensitive = case_sensitive.unwrap_or(false);
@@ -475,11 +564,11 @@ pub fn search_project_files_in_path(
 use crate::project::ProjectInfo;
 
 #[tauri::command]
-pub async fn detect_project_type(path: Option<String>) -> Result<ProjectInfo, String> {
+pub async fn detect_project_type(path: Option<String>) -> AppResult<ProjectInfo> {
     let project_path = match path {
         Some(p) => PathBuf::from(p),
         None => std::env::current_dir()
-            .map_err(|e| format!("Failed to get current directory: {}", e))?,
+            .map_err(|e| AppError::from(e).context("Failed to get current directory"))?,
     };
 
     Ok(ProjectInfo::detect(&project_path))
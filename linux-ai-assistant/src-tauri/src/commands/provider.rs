@@ -1,55 +1,368 @@
+use crate::database::profiles::FallbackTarget;
+use crate::error::{AppError, AppResult};
+use crate::provider_retry::{
+    retry_after_from_headers, ProviderError, ProviderErrorKind, RetryPolicy,
+};
 use serde::{Deserialize, Serialize};
 use tauri::Emitter;
 use tauri::Manager;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct ProviderMessage {
     pub role: String,
     pub content: String,
+    /// Ids of attachments on this message; resolved before the request is sent
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<String>,
+    /// Images loaded from the blob store for multimodal requests
+    #[serde(skip)]
+    pub images: Vec<crate::attachments::InlineImage>,
 }
 
-#[tauri::command]
-pub fn provider_openai_generate(
-    _conversation_id: String,
+/// When the conversation is tied to an indexed project, insert a system message
+/// with the project chunks most relevant to the latest user message.
+fn with_project_context(
+    app: &tauri::AppHandle,
+    conversation_id: &str,
+    mut messages: Vec<ProviderMessage>,
+) -> Vec<ProviderMessage> {
+    use crate::database::{conversations::Conversation, settings::Setting, Database};
+
+    let Some(db) = app.try_state::<Database>() else {
+        return messages;
+    };
+    let Some(query) = messages
+        .iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .map(|m| m.content.clone())
+    else {
+        return messages;
+    };
+    let Ok(conn) = db.conn().lock() else {
+        return messages;
+    };
+    let Some(project_root) = Conversation::get_by_id(&conn, conversation_id)
+        .ok()
+        .flatten()
+        .and_then(|c| c.project_root)
+    else {
+        return messages;
+    };
+
+    let top_k = Setting::get(&conn, "project_context_top_k")
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(crate::project_index::DEFAULT_TOP_K);
+
+    match crate::project_index::retrieve(&conn, &project_root, &query, top_k) {
+        Ok(chunks) if !chunks.is_empty() => {
+            // Keep any leading system prompt first, then the project context
+            let insert_at = messages.iter().take_while(|m| m.role == "system").count();
+            messages.insert(
+                insert_at,
+                ProviderMessage {
+                    role: "system".to_string(),
+                    content: crate::project_index::format_context(&chunks),
+                    ..Default::default()
+                },
+            );
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("project context retrieval failed: {}", e),
+    }
+
+    messages
+}
+
+/// Load attachments from the blob store into the messages that name them
+fn with_attachments(
+    app: &tauri::AppHandle,
     messages: Vec<ProviderMessage>,
-    model: Option<String>,
-) -> Result<String, String> {
-    // Read API key from environment
-    let api_key =
-        std::env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY not set".to_string())?;
+) -> Vec<ProviderMessage> {
+    use crate::attachments::BlobStore;
+    use crate::database::Database;
 
-    let client = reqwest::blocking::Client::new();
+    if messages.iter().all(|m| m.attachments.is_empty()) {
+        return messages;
+    }
+    let (Some(db), Some(blobs)) = (app.try_state::<Database>(), app.try_state::<BlobStore>())
+    else {
+        return messages;
+    };
+    let Ok(conn) = db.conn().lock() else {
+        return messages;
+    };
+    crate::attachments::resolve(&conn, &blobs, messages)
+}
 
-    let api_url = "https://api.openai.com/v1/chat/completions";
+/// Resolve attachments and augment with project context, then trim to the
+/// model's context window (substituting the rolling summary for dropped turns).
+/// Fails when the newest message alone doesn't fit the window.
+fn prepare_messages(
+    app: &tauri::AppHandle,
+    provider: &str,
+    conversation_id: &str,
+    messages: Vec<ProviderMessage>,
+    model: &str,
+) -> Result<Vec<ProviderMessage>, ProviderError> {
+    use crate::database::{conversations::Conversation, settings::Setting, Database};
 
-    // Map our messages into the OpenAI chat format
-    let msgs: Vec<serde_json::Value> = messages
-        .into_iter()
-        .map(|m| serde_json::json!({"role": m.role, "content": m.content}))
-        .collect();
+    let messages = with_attachments(app, messages);
+    let messages = with_project_context(app, conversation_id, messages);
+    let Some(db) = app.try_state::<Database>() else {
+        return Ok(messages);
+    };
+    let Ok(conn) = db.conn().lock() else {
+        return Ok(messages);
+    };
 
-    let model_name = model.unwrap_or_else(|| "gpt-3.5-turbo".to_string());
-    let body = serde_json::json!({
-        "model": model_name,
-        "messages": msgs,
-        "temperature": 0.7
-    });
+    let limit = crate::tokenizer::context_limit(&conn, provider, model);
+    let reserve = Setting::get(&conn, "completion_token_reserve")
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(crate::context_builder::DEFAULT_COMPLETION_RESERVE);
+    let summary = Conversation::get_by_id(&conn, conversation_id)
+        .ok()
+        .flatten()
+        .and_then(|c| c.summary);
 
-    let resp = client
-        .post(api_url)
-        .bearer_auth(api_key)
-        .json(&body)
-        .send()
-        .map_err(|e| format!("request error: {}", e))?;
+    crate::context_builder::build_context(
+        provider,
+        model,
+        messages,
+        limit,
+        reserve,
+        summary.as_deref(),
+    )
+    .map(|built| built.messages)
+}
+
+/// Providers the app can route requests to
+pub const PROVIDERS: &[&str] = &["openai", "anthropic", "gemini", "ollama"];
+
+/// Model used when a request or fallback target doesn't name one
+pub fn default_model(provider: &str) -> &'static str {
+    match provider {
+        "anthropic" => "claude-3-5-sonnet-20240620",
+        "gemini" => "gemini-1.5-flash",
+        "ollama" => "llama3.2",
+        _ => "gpt-3.5-turbo",
+    }
+}
+
+/// Budget check for a paid request to `model` under the active profile:
+/// refuses once the hard limit is reached (or when the model has no price to
+/// count against it) and emits `budget://warning` past the soft limit
+fn enforce_budget(
+    app: &tauri::AppHandle,
+    provider: &str,
+    model: &str,
+) -> Result<(), ProviderError> {
+    use crate::database::Database;
 
+    if crate::usage::is_free_provider(provider) {
+        return Ok(());
+    }
+    let Some(db) = app.try_state::<Database>() else {
+        return Ok(());
+    };
+    let warning = {
+        let conn = db
+            .conn()
+            .lock()
+            .map_err(|e| ProviderError::new(ProviderErrorKind::Budget, provider, e.to_string()))?;
+        crate::usage::check_budget(&conn, model)
+            .map_err(|e| ProviderError::new(ProviderErrorKind::Budget, provider, e))?
+    };
+    if let Some(status) = warning {
+        let _ = app.emit("budget://warning", &status);
+    }
+    Ok(())
+}
+
+/// Token usage reported by a provider for one response
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+}
+
+/// Provider and model that answered the latest request of a conversation, with
+/// the usage it reported (if any)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationReport {
+    pub provider: String,
+    pub model: String,
+    pub usage: Option<TokenUsage>,
+}
+
+fn generation_reports(
+) -> &'static std::sync::Mutex<std::collections::HashMap<String, GenerationReport>> {
+    static REPORTS: std::sync::OnceLock<
+        std::sync::Mutex<std::collections::HashMap<String, GenerationReport>>,
+    > = std::sync::OnceLock::new();
+    REPORTS.get_or_init(Default::default)
+}
+
+fn usage_from(prompt_tokens: Option<i64>, completion_tokens: Option<i64>) -> Option<TokenUsage> {
+    Some(TokenUsage {
+        prompt_tokens: prompt_tokens?,
+        completion_tokens: completion_tokens?,
+    })
+}
+
+/// Remember who answered the latest request for a conversation until the reply
+/// is stored as a message
+fn report_generation(
+    conversation_id: &str,
+    provider: &str,
+    model: &str,
+    usage: Option<TokenUsage>,
+) {
+    if conversation_id.is_empty() {
+        return;
+    }
+    if let Ok(mut reports) = generation_reports().lock() {
+        reports.insert(
+            conversation_id.to_string(),
+            GenerationReport {
+                provider: provider.to_string(),
+                model: model.to_string(),
+                usage,
+            },
+        );
+    }
+}
+
+/// Report for the latest response of a conversation, if any
+pub fn take_generation_report(conversation_id: &str) -> Option<GenerationReport> {
+    generation_reports().lock().ok()?.remove(conversation_id)
+}
+
+/// A request for the conversation began. A report left by an earlier one
+/// whose reply was never stored must not be taken for this one's.
+fn start_generation(conversation_id: &str) {
+    take_generation_report(conversation_id);
+    crate::notifications::generation_started(conversation_id);
+}
+
+/// The request failed, so no reply will be stored for it
+fn fail_generation(conversation_id: &str) {
+    take_generation_report(conversation_id);
+    crate::notifications::generation_failed(conversation_id);
+}
+
+/// Reject non-success responses as typed errors, keeping any `Retry-After`
+fn check_status(
+    provider: &str,
+    resp: reqwest::blocking::Response,
+) -> Result<reqwest::blocking::Response, ProviderError> {
     let status = resp.status();
-    let json: serde_json::Value = resp
+    if status.is_success() {
+        return Ok(resp);
+    }
+    let retry_after = retry_after_from_headers(resp.headers());
+    let body = resp.text().unwrap_or_default();
+    Err(ProviderError::from_status(
+        provider,
+        status.as_u16(),
+        &body,
+        retry_after,
+    ))
+}
+
+fn send_json(
+    provider: &str,
+    request: reqwest::blocking::RequestBuilder,
+) -> Result<serde_json::Value, ProviderError> {
+    let resp = request
+        .send()
+        .map_err(|e| ProviderError::transport(provider, &e))?;
+    check_status(provider, resp)?
         .json()
-        .map_err(|e| format!("json parse error: {}", e))?;
+        .map_err(|e| ProviderError::transport(provider, &e))
+}
 
-    if !status.is_success() {
-        return Err(format!("OpenAI API returned {}: {}", status, json));
+type Completion = (String, Option<TokenUsage>);
+
+/// Messages in the OpenAI chat format; messages with images use content parts
+fn openai_messages(messages: &[ProviderMessage]) -> Vec<serde_json::Value> {
+    messages
+        .iter()
+        .map(|m| {
+            if m.images.is_empty() {
+                return serde_json::json!({"role": m.role, "content": m.content});
+            }
+            let mut parts = vec![serde_json::json!({"type": "text", "text": m.content})];
+            parts.extend(m.images.iter().map(|img| {
+                serde_json::json!({"type": "image_url", "image_url": {"url": img.data_uri()}})
+            }));
+            serde_json::json!({"role": m.role, "content": parts})
+        })
+        .collect()
+}
+
+/// Messages collapsed into one prompt, as Anthropic and Gemini requests send them
+fn collapsed_prompt(messages: &[ProviderMessage]) -> String {
+    messages
+        .iter()
+        .map(|m| format!("{}: {}", m.role, m.content))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Content blocks for the single Anthropic user turn: images, then the prompt
+fn anthropic_content(messages: &[ProviderMessage]) -> serde_json::Value {
+    let prompt = collapsed_prompt(messages);
+    if messages.iter().all(|m| m.images.is_empty()) {
+        return serde_json::Value::String(prompt);
     }
+    let mut blocks: Vec<serde_json::Value> = messages
+        .iter()
+        .flat_map(|m| &m.images)
+        .map(|img| {
+            serde_json::json!({
+                "type": "image",
+                "source": {"type": "base64", "media_type": img.mime_type, "data": img.data}
+            })
+        })
+        .collect();
+    blocks.push(serde_json::json!({"type": "text", "text": prompt}));
+    serde_json::Value::Array(blocks)
+}
+
+/// Parts for the single Gemini turn: the prompt, then any images as inline data
+fn gemini_parts(messages: &[ProviderMessage]) -> Vec<serde_json::Value> {
+    let mut parts = vec![serde_json::json!({"text": collapsed_prompt(messages)})];
+    parts.extend(messages.iter().flat_map(|m| &m.images).map(
+        |img| serde_json::json!({"inline_data": {"mime_type": img.mime_type, "data": img.data}}),
+    ));
+    parts
+}
+
+fn openai_request(model: &str, messages: &[ProviderMessage]) -> Result<Completion, ProviderError> {
+    // Read API key from environment
+    let api_key = std::env::var("OPENAI_API_KEY").map_err(|_| {
+        ProviderError::new(ProviderErrorKind::Auth, "openai", "OPENAI_API_KEY not set")
+    })?;
+
+    let client = reqwest::blocking::Client::new();
+
+    let api_url = "https://api.openai.com/v1/chat/completions";
+
+    let body = serde_json::json!({
+        "model": model,
+        "messages": openai_messages(messages),
+        "temperature": 0.7
+    });
+
+    let json = send_json(
+        "openai",
+        client.post(api_url).bearer_auth(api_key).json(&body),
+    )?;
 
     let content = json["choices"]
         .get(0)
@@ -58,8 +371,12 @@ pub fn provider_openai_generate(
         .and_then(|v| v.as_str())
         .unwrap_or("")
         .to_string();
+    let usage = usage_from(
+        json["usage"]["prompt_tokens"].as_i64(),
+        json["usage"]["completion_tokens"].as_i64(),
+    );
 
-    Ok(content)
+    Ok((content, usage))
 }
 
 fn get_keyring_secret(service: &str) -> Option<String> {
@@ -76,120 +393,100 @@ fn get_keyring_secret(service: &str) -> Option<String> {
     None
 }
 
-fn prefer_keyring_or_env(service: &str, env_name: &str) -> Result<String, String> {
+fn prefer_keyring_or_env(service: &str, env_name: &str) -> AppResult<String> {
     if let Some(s) = get_keyring_secret(service) {
         return Ok(s);
     }
-    std::env::var(env_name).map_err(|_| format!("{} not set", env_name))
+    std::env::var(env_name).map_err(|_| AppError::auth(format!("{} not set", env_name)))
 }
 
 #[tauri::command]
-pub fn set_api_key(provider: String, key: String) -> Result<(), String> {
+pub fn set_api_key(provider: String, key: String) -> AppResult<()> {
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
     {
         let entry = keyring::Entry::new("linux-ai-assistant", &provider)
-            .map_err(|e| format!("keyring entry error: {}", e))?;
+            .map_err(|e| AppError::from(e).context("keyring entry error"))?;
         entry
             .set_password(&key)
-            .map_err(|e| format!("keyring set failed: {}", e))?;
+            .map_err(|e| AppError::from(e).context("keyring set failed"))?;
         return Ok(());
     }
     #[allow(unreachable_code)]
-    Err("keyring unsupported on this platform".into())
+    Err(AppError::unsupported(
+        "keyring unsupported on this platform",
+    ))
 }
 
 #[tauri::command]
-pub fn get_api_key(provider: String) -> Result<String, String> {
+pub fn get_api_key(provider: String) -> AppResult<String> {
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
     {
         let entry = keyring::Entry::new("linux-ai-assistant", &provider)
-            .map_err(|e| format!("keyring entry error: {}", e))?;
+            .map_err(|e| AppError::from(e).context("keyring entry error"))?;
         let val = entry
             .get_password()
-            .map_err(|e| format!("keyring get failed: {}", e))?;
+            .map_err(|e| AppError::from(e).context("keyring get failed"))?;
         return Ok(val);
     }
     #[allow(unreachable_code)]
-    Err("keyring unsupported on this platform".into())
+    Err(AppError::unsupported(
+        "keyring unsupported on this platform",
+    ))
 }
 
-#[tauri::command]
-pub fn provider_anthropic_generate(
-    _conversation_id: String,
-    messages: Vec<ProviderMessage>,
-    model: Option<String>,
-) -> Result<String, String> {
-    let api_key = prefer_keyring_or_env("anthropic", "ANTHROPIC_API_KEY")?;
+fn anthropic_request(
+    model: &str,
+    messages: &[ProviderMessage],
+) -> Result<Completion, ProviderError> {
+    let api_key = prefer_keyring_or_env("anthropic", "ANTHROPIC_API_KEY")
+        .map_err(|e| ProviderError::new(ProviderErrorKind::Auth, "anthropic", e))?;
     let client = reqwest::blocking::Client::new();
     let api_url = "https://api.anthropic.com/v1/messages";
-    // Collapse messages into a single user prompt for simplicity
-    let prompt = messages
-        .into_iter()
-        .map(|m| format!("{}: {}", m.role, m.content))
-        .collect::<Vec<_>>()
-        .join("\n\n");
+    // Collapse messages into a single user turn for simplicity
     let body = serde_json::json!({
-        "model": model.unwrap_or_else(|| "claude-3-5-sonnet-20240620".to_string()),
+        "model": model,
         "max_tokens": 1024,
-        "messages": [ { "role": "user", "content": prompt } ]
+        "messages": [ { "role": "user", "content": anthropic_content(messages) } ]
     });
-    let resp = client
-        .post(api_url)
-        .header("x-api-key", api_key)
-        .header("anthropic-version", "2023-06-01")
-        .json(&body)
-        .send()
-        .map_err(|e| format!("request error: {}", e))?;
-    let status = resp.status();
-    let json: serde_json::Value = resp
-        .json()
-        .map_err(|e| format!("json parse error: {}", e))?;
-    if !status.is_success() {
-        return Err(format!("Anthropic API returned {}: {}", status, json));
-    }
+    let json = send_json(
+        "anthropic",
+        client
+            .post(api_url)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01")
+            .json(&body),
+    )?;
     let content = json["content"]
         .get(0)
         .and_then(|c| c.get("text"))
         .and_then(|t| t.as_str())
         .unwrap_or("")
         .to_string();
-    Ok(content)
+    let usage = usage_from(
+        json["usage"]["input_tokens"].as_i64(),
+        json["usage"]["output_tokens"].as_i64(),
+    );
+    Ok((content, usage))
 }
 
-#[tauri::command]
-pub fn provider_gemini_generate(
-    _conversation_id: String,
-    messages: Vec<ProviderMessage>,
-    model: Option<String>,
-) -> Result<String, String> {
-    let api_key = prefer_keyring_or_env("gemini", "GEMINI_API_KEY")?;
-    let model_name = model.unwrap_or_else(|| "gemini-1.5-flash".to_string());
+fn gemini_request(model: &str, messages: &[ProviderMessage]) -> Result<Completion, ProviderError> {
+    let api_key = prefer_keyring_or_env("gemini", "GEMINI_API_KEY")
+        .map_err(|e| ProviderError::new(ProviderErrorKind::Auth, "gemini", e))?;
     let url = format!(
         "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent",
-        model_name
+        model
     );
     let client = reqwest::blocking::Client::new();
-    let text = messages
-        .into_iter()
-        .map(|m| format!("{}: {}", m.role, m.content))
-        .collect::<Vec<_>>()
-        .join("\n\n");
     let body = serde_json::json!({
-        "contents": [ { "parts": [ { "text": text } ] } ]
+        "contents": [ { "parts": gemini_parts(messages) } ]
     });
-    let resp = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .json(&body)
-        .send()
-        .map_err(|e| format!("request error: {}", e))?;
-    let status = resp.status();
-    let json: serde_json::Value = resp
-        .json()
-        .map_err(|e| format!("json parse error: {}", e))?;
-    if !status.is_success() {
-        return Err(format!("Gemini API returned {}: {}", status, json));
-    }
+    let json = send_json(
+        "gemini",
+        client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .json(&body),
+    )?;
     let content = json["candidates"]
         .get(0)
         .and_then(|c| c.get("content"))
@@ -199,148 +496,361 @@ pub fn provider_gemini_generate(
         .and_then(|t| t.as_str())
         .unwrap_or("")
         .to_string();
+    let usage = usage_from(
+        json["usageMetadata"]["promptTokenCount"].as_i64(),
+        json["usageMetadata"]["candidatesTokenCount"].as_i64(),
+    );
+    Ok((content, usage))
+}
+
+fn ollama_endpoint() -> String {
+    // Default Ollama endpoint - can be configured later
+    std::env::var("OLLAMA_ENDPOINT").unwrap_or_else(|_| "http://localhost:11434".to_string())
+}
+
+/// Convert messages to a single prompt for Ollama
+fn ollama_prompt(messages: &[ProviderMessage]) -> String {
+    messages
+        .iter()
+        .map(|m| match m.role.as_str() {
+            "system" => format!("System: {}", m.content),
+            "user" => format!("Human: {}", m.content),
+            "assistant" => format!("Assistant: {}", m.content),
+            _ => format!("{}: {}", m.role, m.content),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Body for `/api/generate`; images from every message go in `images`
+fn ollama_body(model: &str, messages: &[ProviderMessage], stream: bool) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "model": model,
+        "prompt": ollama_prompt(messages),
+        "stream": stream
+    });
+    let images: Vec<&str> = messages
+        .iter()
+        .flat_map(|m| &m.images)
+        .map(|img| img.data.as_str())
+        .collect();
+    if !images.is_empty() {
+        body["images"] = serde_json::json!(images);
+    }
+    body
+}
+
+fn ollama_request(model: &str, messages: &[ProviderMessage]) -> Result<Completion, ProviderError> {
+    let client = reqwest::blocking::Client::new();
+    let api_url = format!("{}/api/generate", ollama_endpoint());
+
+    let body = ollama_body(model, messages, false);
+
+    let json = send_json("ollama", client.post(&api_url).json(&body))?;
+
+    let content = json["response"].as_str().unwrap_or("").to_string();
+    let usage = usage_from(
+        json["prompt_eval_count"].as_i64(),
+        json["eval_count"].as_i64(),
+    );
+
+    Ok((content, usage))
+}
+
+/// One attempt against a provider, without retries
+fn request_once(
+    provider: &str,
+    model: &str,
+    messages: &[ProviderMessage],
+) -> Result<Completion, ProviderError> {
+    match provider {
+        "openai" => openai_request(model, messages),
+        "anthropic" => anthropic_request(model, messages),
+        "gemini" => gemini_request(model, messages),
+        "ollama" => ollama_request(model, messages),
+        other => Err(ProviderError::new(
+            ProviderErrorKind::InvalidRequest,
+            other,
+            format!("Unknown provider: {}", other),
+        )),
+    }
+}
+
+/// Run `request` under the configured retry policy, emitting `provider://retry`
+/// before each wait
+fn with_retry<T, F>(app: &tauri::AppHandle, provider: &str, request: F) -> Result<T, ProviderError>
+where
+    F: FnMut(u32) -> Result<T, ProviderError>,
+{
+    use crate::database::Database;
+
+    let policy = app
+        .try_state::<Database>()
+        .and_then(|db| db.conn().lock().ok().map(|conn| RetryPolicy::load(&conn)))
+        .unwrap_or_default();
+    policy.run(request, |attempt, delay, err| {
+        let _ = app.emit(
+            "provider://retry",
+            serde_json::json!({
+                "provider": provider,
+                "attempt": attempt,
+                "delay_ms": delay.as_millis() as u64,
+                "error": err,
+            }),
+        );
+        std::thread::sleep(delay);
+    })
+}
+
+/// One step of a chain: budget check, context preparation, then the request
+/// under the retry policy. On success the provider and model that answered are
+/// reported for the conversation.
+fn run_provider(
+    app: &tauri::AppHandle,
+    provider: &str,
+    conversation_id: &str,
+    messages: Vec<ProviderMessage>,
+    model: Option<&str>,
+) -> Result<String, ProviderError> {
+    if !PROVIDERS.contains(&provider) {
+        return Err(ProviderError::new(
+            ProviderErrorKind::InvalidRequest,
+            provider,
+            format!("Unknown provider: {}", provider),
+        ));
+    }
+    let model = model.unwrap_or(default_model(provider)).to_string();
+    enforce_budget(app, provider, &model)?;
+    let messages = prepare_messages(app, provider, conversation_id, messages, &model)?;
+    let (content, usage) =
+        with_retry(app, provider, |_| request_once(provider, &model, &messages))?;
+    report_generation(conversation_id, provider, &model, usage);
     Ok(content)
 }
+
+/// The requested provider followed by the active profile's fallback chain
+fn fallback_chain(
+    app: &tauri::AppHandle,
+    provider: &str,
+    model: Option<String>,
+) -> Vec<FallbackTarget> {
+    use crate::database::{profiles::Profile, Database};
+
+    let mut chain = vec![FallbackTarget {
+        provider: provider.to_string(),
+        model,
+    }];
+    let fallbacks = app
+        .try_state::<Database>()
+        .and_then(|db| {
+            let conn = db.conn().lock().ok()?;
+            Profile::get_active(&conn).ok().flatten()
+        })
+        .map(|profile| profile.fallback_chain)
+        .unwrap_or_default();
+    for target in fallbacks {
+        if !chain.contains(&target) {
+            chain.push(target);
+        }
+    }
+    chain
+}
+
+fn emit_fallback(app: &tauri::AppHandle, err: &ProviderError, next: &FallbackTarget) {
+    eprintln!("{}; falling back to {}", err, next.provider);
+    let _ = app.emit(
+        "provider://fallback",
+        serde_json::json!({
+            "from": err.provider,
+            "to": next.provider,
+            "model": next.model,
+            "error": err,
+        }),
+    );
+}
+
+/// Try each target in turn until one answers, moving on after errors another
+/// provider might not hit
+fn generate_with_chain(
+    app: &tauri::AppHandle,
+    chain: &[FallbackTarget],
+    conversation_id: &str,
+    messages: Vec<ProviderMessage>,
+) -> Result<String, ProviderError> {
+    for (i, target) in chain.iter().enumerate() {
+        let result = run_provider(
+            app,
+            &target.provider,
+            conversation_id,
+            messages.clone(),
+            target.model.as_deref(),
+        );
+        match result {
+            Ok(content) => return Ok(content),
+            Err(err) => match chain.get(i + 1).filter(|_| err.should_fall_back()) {
+                Some(next) => emit_fallback(app, &err, next),
+                None => return Err(err),
+            },
+        }
+    }
+    Err(ProviderError::new(
+        ProviderErrorKind::InvalidRequest,
+        "",
+        "No provider configured",
+    ))
+}
+
+/// Generate a reply with `provider`, retrying transient failures and falling
+/// back along the active profile's chain
+pub fn generate_with_provider(
+    app: tauri::AppHandle,
+    provider: &str,
+    conversation_id: String,
+    messages: Vec<ProviderMessage>,
+    model: Option<String>,
+) -> Result<String, ProviderError> {
+    start_generation(&conversation_id);
+    let chain = fallback_chain(&app, provider, model);
+    generate_with_chain(&app, &chain, &conversation_id, messages)
+        .inspect_err(|_| fail_generation(&conversation_id))
+}
+
 #[tauri::command]
-pub fn provider_openai_stream(
+pub fn provider_openai_generate(
     app: tauri::AppHandle,
     conversation_id: String,
     messages: Vec<ProviderMessage>,
     model: Option<String>,
-) -> Result<String, String> {
-    // Generate final content using existing generator (best-effort). If OPENAI_API_KEY
-    // is not present, fall back to a deterministic mock.
-    let final_content = match provider_openai_generate(conversation_id.clone(), messages, model) {
-        Ok(c) => c,
-        Err(_) => format!("Mock response to conversation {}", conversation_id),
-    };
+) -> AppResult<String> {
+    generate_with_provider(app, "openai", conversation_id, messages, model).map_err(AppError::from)
+}
 
-    let session_id = uuid::Uuid::new_v4().to_string();
+#[tauri::command]
+pub fn provider_anthropic_generate(
+    app: tauri::AppHandle,
+    conversation_id: String,
+    messages: Vec<ProviderMessage>,
+    model: Option<String>,
+) -> AppResult<String> {
+    generate_with_provider(app, "anthropic", conversation_id, messages, model)
+        .map_err(AppError::from)
+}
 
-    // Spawn a thread to emit chunks to the frontend via Tauri events.
-    let session_id_clone = session_id.clone();
+#[tauri::command]
+pub fn provider_gemini_generate(
+    app: tauri::AppHandle,
+    conversation_id: String,
+    messages: Vec<ProviderMessage>,
+    model: Option<String>,
+) -> AppResult<String> {
+    generate_with_provider(app, "gemini", conversation_id, messages, model).map_err(AppError::from)
+}
+
+#[tauri::command]
+pub fn provider_ollama_generate(
+    app: tauri::AppHandle,
+    conversation_id: String,
+    messages: Vec<ProviderMessage>,
+    model: Option<String>,
+) -> AppResult<String> {
+    generate_with_provider(app, "ollama", conversation_id, messages, model).map_err(AppError::from)
+}
+
+/// Emit a finished reply to the frontend word by word as stream events
+fn emit_simulated_stream(app: tauri::AppHandle, session_id: String, content: String) {
     std::thread::spawn(move || {
-        let parts: Vec<String> = final_content
+        let parts: Vec<String> = content
             .split_whitespace()
             .map(|s| format!("{} ", s))
             .collect();
 
         for p in parts {
             // best-effort emit; ignore errors
-            let payload = serde_json::json!({"session_id": session_id_clone, "chunk": p});
+            let payload = serde_json::json!({"session_id": session_id, "chunk": p});
             if let Some(w) = app.get_webview_window("main") {
                 let _ = w.emit("provider-stream-chunk", payload.clone());
             }
             std::thread::sleep(std::time::Duration::from_millis(50));
         }
 
-        let payload = serde_json::json!({"session_id": session_id_clone});
+        let payload = serde_json::json!({"session_id": session_id});
         if let Some(w) = app.get_webview_window("main") {
             let _ = w.emit("provider-stream-end", payload.clone());
         }
     });
-
-    Ok(session_id)
 }
 
 #[tauri::command]
-pub fn provider_ollama_generate(
-    _conversation_id: String,
+pub fn provider_openai_stream(
+    app: tauri::AppHandle,
+    conversation_id: String,
     messages: Vec<ProviderMessage>,
     model: Option<String>,
-) -> Result<String, String> {
-    let client = reqwest::blocking::Client::new();
-
-    // Default Ollama endpoint - can be configured later
-    let endpoint =
-        std::env::var("OLLAMA_ENDPOINT").unwrap_or_else(|_| "http://localhost:11434".to_string());
-    let api_url = format!("{}/api/generate", endpoint);
-
-    // Convert messages to a single prompt for Ollama
-    let prompt = messages
-        .into_iter()
-        .map(|m| match m.role.as_str() {
-            "system" => format!("System: {}", m.content),
-            "user" => format!("Human: {}", m.content),
-            "assistant" => format!("Assistant: {}", m.content),
-            _ => format!("{}: {}", m.role, m.content),
-        })
-        .collect::<Vec<_>>()
-        .join("\n\n");
-
-    let model_name = model.unwrap_or_else(|| "llama3.2".to_string());
-
-    let body = serde_json::json!({
-        "model": model_name,
-        "prompt": prompt,
-        "stream": false
-    });
-
-    let resp = client
-        .post(&api_url)
-        .json(&body)
-        .send()
-        .map_err(|e| format!("Ollama request error: {}", e))?;
+) -> AppResult<String> {
+    // Generate the whole reply (with retries and fallbacks), then replay it as chunks
+    let final_content =
+        generate_with_provider(app.clone(), "openai", conversation_id, messages, model)?;
 
-    let status = resp.status();
-    let json: serde_json::Value = resp
-        .json()
-        .map_err(|e| format!("json parse error: {}", e))?;
-
-    if !status.is_success() {
-        return Err(format!("Ollama API returned {}: {}", status, json));
-    }
-
-    let content = json["response"].as_str().unwrap_or("").to_string();
-
-    Ok(content)
+    let session_id = uuid::Uuid::new_v4().to_string();
+    emit_simulated_stream(app, session_id.clone(), final_content);
+    Ok(session_id)
 }
 
 #[tauri::command]
 pub fn provider_ollama_stream(
     app: tauri::AppHandle,
-    _conversation_id: String,
+    conversation_id: String,
     messages: Vec<ProviderMessage>,
     model: Option<String>,
-) -> Result<String, String> {
+) -> AppResult<String> {
+    start_generation(&conversation_id);
+    let chain = fallback_chain(&app, "ollama", model);
+    let model_name = chain[0]
+        .model
+        .clone()
+        .unwrap_or_else(|| default_model("ollama").to_string());
     let client = reqwest::blocking::Client::new();
-
-    let endpoint =
-        std::env::var("OLLAMA_ENDPOINT").unwrap_or_else(|_| "http://localhost:11434".to_string());
-    let api_url = format!("{}/api/generate", endpoint);
-
-    let prompt = messages
-        .into_iter()
-        .map(|m| match m.role.as_str() {
-            "system" => format!("System: {}", m.content),
-            "user" => format!("Human: {}", m.content),
-            "assistant" => format!("Assistant: {}", m.content),
-            _ => format!("{}: {}", m.role, m.content),
-        })
-        .collect::<Vec<_>>()
-        .join("\n\n");
-
-    let model_name = model.unwrap_or_else(|| "llama3.2".to_string());
+    let api_url = format!("{}/api/generate", ollama_endpoint());
     let session_id = uuid::Uuid::new_v4().to_string();
 
-    let body = serde_json::json!({
-        "model": model_name,
-        "prompt": prompt,
-        "stream": true
+    // Prepare and connect up front so failures are retried and reported instead
+    // of leaving the frontend waiting on a stream that never starts
+    let connected = prepare_messages(
+        &app,
+        "ollama",
+        &conversation_id,
+        messages.clone(),
+        &model_name,
+    )
+    .and_then(|prepared| {
+        let body = ollama_body(&model_name, &prepared, true);
+        with_retry(&app, "ollama", |_| {
+            let resp = client
+                .post(&api_url)
+                .json(&body)
+                .send()
+                .map_err(|e| ProviderError::transport("ollama", &e))?;
+            check_status("ollama", resp)
+        })
     });
+    let resp = match connected {
+        Ok(resp) => resp,
+        Err(err) => {
+            let Some(next) = chain.get(1).filter(|_| err.should_fall_back()) else {
+                fail_generation(&conversation_id);
+                return Err(err.into());
+            };
+            emit_fallback(&app, &err, next);
+            let content = generate_with_chain(&app, &chain[1..], &conversation_id, messages)
+                .inspect_err(|_| fail_generation(&conversation_id))?;
+            emit_simulated_stream(app, session_id.clone(), content);
+            return Ok(session_id);
+        }
+    };
 
     // Spawn thread for streaming response
     let session_id_clone = session_id.clone();
     std::thread::spawn(move || {
-        let resp = match client.post(&api_url).json(&body).send() {
-            Ok(r) => r,
-            Err(_) => return,
-        };
-
-        if !resp.status().is_success() {
-            return;
-        }
-
         let reader = std::io::BufReader::new(resp);
         use std::io::BufRead;
 
@@ -363,24 +873,35 @@ pub fn provider_ollama_stream(
 
                 // Check if this is the final response
                 if json["done"].as_bool().unwrap_or(false) {
-                    let payload = serde_json::json!({
-                        "session_id": session_id_clone
-                    });
-
-                    if let Some(w) = app.get_webview_window("main") {
-                        let _ = w.emit("provider-stream-end", payload);
-                    }
+                    report_generation(
+                        &conversation_id,
+                        "ollama",
+                        &model_name,
+                        usage_from(
+                            json["prompt_eval_count"].as_i64(),
+                            json["eval_count"].as_i64(),
+                        ),
+                    );
                     break;
                 }
             }
         }
+
+        // End the stream even if the connection dropped before `done`
+        let payload = serde_json::json!({
+            "session_id": session_id_clone
+        });
+
+        if let Some(w) = app.get_webview_window("main") {
+            let _ = w.emit("provider-stream-end", payload);
+        }
     });
 
     Ok(session_id)
 }
 
 #[tauri::command]
-pub fn ollama_list_models() -> Result<Vec<String>, String> {
+pub fn ollama_list_models() -> AppResult<Vec<String>> {
     let client = reqwest::blocking::Client::new();
     let endpoint =
         std::env::var("OLLAMA_ENDPOINT").unwrap_or_else(|_| "http://localhost:11434".to_string());
@@ -389,15 +910,17 @@ pub fn ollama_list_models() -> Result<Vec<String>, String> {
     let resp = client
         .get(&api_url)
         .send()
-        .map_err(|e| format!("Ollama request error: {}", e))?;
+        .map_err(|e| AppError::from(e).context("Ollama request error"))?;
 
     let status = resp.status();
     let json: serde_json::Value = resp
         .json()
-        .map_err(|e| format!("json parse error: {}", e))?;
+        .map_err(|e| AppError::from(e).context("json parse error"))?;
 
     if !status.is_success() {
-        return Err(format!("Ollama API returned {}: {}", status, json));
+        return Err(
+            ProviderError::from_status("ollama", status.as_u16(), &json.to_string(), None).into(),
+        );
     }
 
     let models = json["models"]
@@ -412,7 +935,7 @@ pub fn ollama_list_models() -> Result<Vec<String>, String> {
 }
 
 #[tauri::command]
-pub fn ollama_pull_model(model: String) -> Result<String, String> {
+pub fn ollama_pull_model(model: String) -> AppResult<String> {
     let client = reqwest::blocking::Client::new();
     let endpoint =
         std::env::var("OLLAMA_ENDPOINT").unwrap_or_else(|_| "http://localhost:11434".to_string());
@@ -427,20 +950,22 @@ pub fn ollama_pull_model(model: String) -> Result<String, String> {
         .post(&api_url)
         .json(&body)
         .send()
-        .map_err(|e| format!("Ollama pull request error: {}", e))?;
+        .map_err(|e| AppError::from(e).context("Ollama pull request error"))?;
 
     let status = resp.status();
 
     if !status.is_success() {
         let error_text = resp.text().unwrap_or_default();
-        return Err(format!("Ollama pull failed {}: {}", status, error_text));
+        return Err(
+            ProviderError::from_status("ollama", status.as_u16(), &error_text, None).into(),
+        );
     }
 
     Ok(format!("Successfully pulled model: {}", model))
 }
 
 #[tauri::command]
-pub fn ollama_check_connection() -> Result<bool, String> {
+pub fn ollama_check_connection() -> AppResult<bool> {
     let client = reqwest::blocking::Client::new();
     let endpoint =
         std::env::var("OLLAMA_ENDPOINT").unwrap_or_else(|_| "http://localhost:11434".to_string());
@@ -451,3 +976,92 @@ pub fn ollama_check_connection() -> Result<bool, String> {
         Err(_) => Ok(false),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attachments::InlineImage;
+
+    fn with_image() -> Vec<ProviderMessage> {
+        vec![
+            ProviderMessage {
+                role: "system".to_string(),
+                content: "Be brief".to_string(),
+                ..Default::default()
+            },
+            ProviderMessage {
+                role: "user".to_string(),
+                content: "Describe this".to_string(),
+                images: vec![InlineImage {
+                    mime_type: "image/png".to_string(),
+                    data: "aGk=".to_string(),
+                }],
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn images_use_each_providers_format() {
+        let messages = with_image();
+
+        let openai = openai_messages(&messages);
+        assert_eq!(openai[0]["content"], "Be brief");
+        assert_eq!(openai[1]["content"][0]["text"], "Describe this");
+        assert_eq!(
+            openai[1]["content"][1]["image_url"]["url"],
+            "data:image/png;base64,aGk="
+        );
+
+        let anthropic = anthropic_content(&messages);
+        assert_eq!(anthropic[0]["type"], "image");
+        assert_eq!(anthropic[0]["source"]["media_type"], "image/png");
+        assert_eq!(anthropic[0]["source"]["data"], "aGk=");
+        assert_eq!(anthropic[1]["type"], "text");
+
+        let gemini = gemini_parts(&messages);
+        assert_eq!(gemini[1]["inline_data"]["mime_type"], "image/png");
+        assert_eq!(gemini[1]["inline_data"]["data"], "aGk=");
+
+        let ollama = ollama_body("llava", &messages, false);
+        assert_eq!(ollama["images"], serde_json::json!(["aGk="]));
+    }
+
+    #[test]
+    fn text_only_requests_are_unchanged() {
+        let messages = vec![ProviderMessage {
+            role: "user".to_string(),
+            content: "hi".to_string(),
+            ..Default::default()
+        }];
+        assert_eq!(openai_messages(&messages)[0]["content"], "hi");
+        assert_eq!(anthropic_content(&messages), "user: hi");
+        assert!(ollama_body("llama3.2", &messages, true)
+            .get("images")
+            .is_none());
+    }
+
+    #[test]
+    fn reports_of_unstored_replies_are_discarded() {
+        let conversation_id = uuid::Uuid::new_v4().to_string();
+        let usage = Some(TokenUsage {
+            prompt_tokens: 12,
+            completion_tokens: 34,
+        });
+
+        // A reply that was never stored, then a request that failed
+        report_generation(&conversation_id, "openai", "gpt-4o", usage);
+        start_generation(&conversation_id);
+        assert!(take_generation_report(&conversation_id).is_none());
+        report_generation(&conversation_id, "openai", "gpt-4o", usage);
+        fail_generation(&conversation_id);
+        assert!(take_generation_report(&conversation_id).is_none());
+
+        start_generation(&conversation_id);
+        report_generation(&conversation_id, "ollama", "llama3", None);
+        let report = take_generation_report(&conversation_id).unwrap();
+        assert_eq!(report.provider, "ollama");
+        assert!(report.usage.is_none());
+        crate::notifications::generation_failed(&conversation_id);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::{AppError, AppResult};
 use serde::Serialize;
 use std::fs::{self, OpenOptions};
 use std::io::Write;
@@ -5,7 +6,7 @@ use std::path::PathBuf;
 use std::process::{Command, Stdio};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct RunResult {
     pub stdout: String,
     pub stderr: String,
@@ -13,136 +14,173 @@ pub struct RunResult {
     pub timed_out: bool,
 }
 
+/// A snippet to run, kept so a notification can rerun it
+#[derive(Debug, Clone)]
+pub struct RunRequest {
+    pub language: String,
+    pub code: String,
+    pub timeout_ms: Option<u64>,
+    pub cwd: Option<String>,
+}
+
 /// Execute user-provided code snippet safely in a temporary file and return output.
-/// Only a small whitelist of languages is supported.
+/// Only a small whitelist of languages is supported. Runs that take long while
+/// the window is hidden raise a desktop notification.
 #[tauri::command]
 pub async fn run_code(
+    app: tauri::AppHandle,
     language: String,
     code: String,
     timeout_ms: Option<u64>,
     cwd: Option<String>,
-) -> Result<RunResult, String> {
-    // Whitelist languages we support
-    let lang = language.to_lowercase();
-    let supported = ["bash", "sh", "zsh", "python", "node", "javascript"];
-    if !supported.contains(&lang.as_str()) {
-        return Err(format!("Unsupported language: {}", language));
-    }
-
-    let timeout = Duration::from_millis(timeout_ms.unwrap_or(10_000));
-
-    // Create temporary file
-    let mut suffix = ".txt".to_string();
-    if lang == "python" {
-        suffix = ".py".to_string();
-    } else if lang == "node" || lang == "javascript" {
-        suffix = ".js".to_string();
-    } else if lang == "bash" || lang == "sh" || lang == "zsh" {
-        suffix = ".sh".to_string();
-    }
-
-    let mut tmp = tempfile::Builder::new()
-        .suffix(&suffix)
-        .tempfile()
-        .map_err(|e| format!("failed to create temp file: {}", e))?;
-
-    tmp.write_all(code.as_bytes())
-        .map_err(|e| format!("failed to write temp file: {}", e))?;
-
-    let path = tmp.path().to_owned();
-
-    // Build command
-    let mut cmd = if lang == "python" {
-        let mut c = Command::new("python3");
-        c.arg(path.clone());
-        c
-    } else if lang == "node" || lang == "javascript" {
-        let mut c = Command::new("node");
-        c.arg(path.clone());
-        c
-    } else {
-        // shell
-        let mut c = Command::new("sh");
-        c.arg(path.clone());
-        c
+) -> AppResult<RunResult> {
+    let request = RunRequest {
+        language,
+        code,
+        timeout_ms,
+        cwd,
     };
+    let start = Instant::now();
+    let result = request.execute()?;
+    crate::notifications::run_finished(&app, request, result.clone(), Some(start.elapsed()));
+    Ok(result)
+}
 
-    if let Some(ref dir) = cwd {
-        cmd.current_dir(dir);
-    }
+impl RunRequest {
+    pub fn execute(&self) -> AppResult<RunResult> {
+        let RunRequest {
+            language,
+            code,
+            timeout_ms,
+            cwd,
+        } = self;
+        // Whitelist languages we support
+        let lang = language.to_lowercase();
+        let supported = ["bash", "sh", "zsh", "python", "node", "javascript"];
+        if !supported.contains(&lang.as_str()) {
+            return Err(AppError::invalid_input(format!(
+                "Unsupported language: {}",
+                language
+            )));
+        }
 
-    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let timeout = Duration::from_millis(timeout_ms.unwrap_or(10_000));
 
-    let mut child = cmd.spawn().map_err(|e| format!("failed to spawn: {}", e))?;
+        // Create temporary file
+        let mut suffix = ".txt".to_string();
+        if lang == "python" {
+            suffix = ".py".to_string();
+        } else if lang == "node" || lang == "javascript" {
+            suffix = ".js".to_string();
+        } else if lang == "bash" || lang == "sh" || lang == "zsh" {
+            suffix = ".sh".to_string();
+        }
 
-    let start = Instant::now();
-    // Poll for completion with timeout
-    loop {
-        match child.try_wait() {
-            Ok(Some(status)) => {
-                let mut stdout = String::new();
-                if let Some(mut out) = child.stdout.take() {
-                    use std::io::Read;
-                    let _ = out.read_to_string(&mut stdout);
-                }
-                let mut stderr = String::new();
-                if let Some(mut err) = child.stderr.take() {
-                    use std::io::Read;
-                    let _ = err.read_to_string(&mut stderr);
+        let mut tmp = tempfile::Builder::new()
+            .suffix(&suffix)
+            .tempfile()
+            .map_err(|e| AppError::from(e).context("failed to create temp file"))?;
+
+        tmp.write_all(code.as_bytes())
+            .map_err(|e| AppError::from(e).context("failed to write temp file"))?;
+
+        let path = tmp.path().to_owned();
+
+        // Build command
+        let mut cmd = if lang == "python" {
+            let mut c = Command::new("python3");
+            c.arg(path.clone());
+            c
+        } else if lang == "node" || lang == "javascript" {
+            let mut c = Command::new("node");
+            c.arg(path.clone());
+            c
+        } else {
+            // shell
+            let mut c = Command::new("sh");
+            c.arg(path.clone());
+            c
+        };
+
+        if let Some(ref dir) = cwd {
+            cmd.current_dir(dir);
+        }
+
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| AppError::from(e).context("failed to spawn"))?;
+
+        let start = Instant::now();
+        // Poll for completion with timeout
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    let mut stdout = String::new();
+                    if let Some(mut out) = child.stdout.take() {
+                        use std::io::Read;
+                        let _ = out.read_to_string(&mut stdout);
+                    }
+                    let mut stderr = String::new();
+                    if let Some(mut err) = child.stderr.take() {
+                        use std::io::Read;
+                        let _ = err.read_to_string(&mut stderr);
+                    }
+                    let code = status.code();
+                    // Audit log
+                    let _ = append_audit(language, cwd.as_deref(), code, false, &stdout, &stderr);
+                    return Ok(RunResult {
+                        stdout,
+                        stderr,
+                        exit_code: code,
+                        timed_out: false,
+                    });
                 }
-                let code = status.code();
-                // Audit log
-                let _ = append_audit(&language, cwd.as_deref(), code, false, &stdout, &stderr);
-                return Ok(RunResult {
-                    stdout,
-                    stderr,
-                    exit_code: code,
-                    timed_out: false,
-                });
-            }
-            Ok(None) => {
-                if start.elapsed() > timeout {
-                    // kill
-                    let _ = child.kill();
-                    break;
+                Ok(None) => {
+                    if start.elapsed() > timeout {
+                        // kill
+                        let _ = child.kill();
+                        break;
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                    continue;
                 }
-                std::thread::sleep(Duration::from_millis(50));
-                continue;
+                Err(e) => return Err(AppError::from(e).context("failed to poll child")),
             }
-            Err(e) => return Err(format!("failed to poll child: {}", e)),
         }
-    }
 
-    // If we reach here, we timed out. Collect whatever output is available.
-    let mut stdout = String::new();
-    if let Some(mut out) = child.stdout.take() {
-        use std::io::Read;
-        let _ = out.read_to_string(&mut stdout);
-    }
-    let mut stderr = String::new();
-    if let Some(mut err) = child.stderr.take() {
-        use std::io::Read;
-        let _ = err.read_to_string(&mut stderr);
-    }
+        // If we reach here, we timed out. Collect whatever output is available.
+        let mut stdout = String::new();
+        if let Some(mut out) = child.stdout.take() {
+            use std::io::Read;
+            let _ = out.read_to_string(&mut stdout);
+        }
+        let mut stderr = String::new();
+        if let Some(mut err) = child.stderr.take() {
+            use std::io::Read;
+            let _ = err.read_to_string(&mut stderr);
+        }
 
-    // Audit log for timeout
-    let _ = append_audit(&language, cwd.as_deref(), None, true, &stdout, &stderr);
-    Ok(RunResult {
-        stdout,
-        stderr,
-        exit_code: None,
-        timed_out: true,
-    })
+        // Audit log for timeout
+        let _ = append_audit(language, cwd.as_deref(), None, true, &stdout, &stderr);
+        Ok(RunResult {
+            stdout,
+            stderr,
+            exit_code: None,
+            timed_out: true,
+        })
+    }
 }
 
 /// Read the audit log and return the last `lines` lines joined as a string.
 #[tauri::command]
-pub fn read_audit(lines: Option<usize>) -> Result<String, String> {
+pub fn read_audit(lines: Option<usize>) -> AppResult<String> {
     let log_path = get_audit_log_path();
 
     let content = match std::fs::read_to_string(&log_path) {
         Ok(s) => s,
-        Err(e) => return Err(format!("failed to read audit log: {}", e)),
+        Err(e) => return Err(AppError::from(e).context("failed to read audit log")),
     };
 
     let l = lines.unwrap_or(200);
@@ -154,14 +192,15 @@ pub fn read_audit(lines: Option<usize>) -> Result<String, String> {
 
 /// Rotate the audit log immediately (move executions.log -> executions.log.1).
 #[tauri::command]
-pub fn rotate_audit() -> Result<(), String> {
+pub fn rotate_audit() -> AppResult<()> {
     let log_path = get_audit_log_path();
     let mut rot = log_path.clone();
     rot.set_extension("log.1");
 
     // Remove old rotation
     let _ = std::fs::remove_file(&rot);
-    fs::rename(&log_path, &rot).map_err(|e| format!("failed to rotate audit log: {}", e))?;
+    fs::rename(&log_path, &rot)
+        .map_err(|e| AppError::from(e).context("failed to rotate audit log"))?;
     Ok(())
 }
 
@@ -180,7 +219,7 @@ fn append_audit(
     timed_out: bool,
     stdout: &str,
     stderr: &str,
-) -> Result<(), String> {
+) -> AppResult<()> {
     let log_path = get_audit_log_path();
 
     let ts = SystemTime::now()
@@ -237,6 +276,22 @@ fn append_audit(
 mod tests {
     use super::*;
 
+    // The command minus its notification, which needs a running app
+    async fn run_code(
+        language: String,
+        code: String,
+        timeout_ms: Option<u64>,
+        cwd: Option<String>,
+    ) -> AppResult<RunResult> {
+        RunRequest {
+            language,
+            code,
+            timeout_ms,
+            cwd,
+        }
+        .execute()
+    }
+
     // Basic test: run a simple echo in sh and ensure output is captured.
     #[tokio::test]
     async fn test_run_code_echo_sh() {
@@ -286,7 +341,8 @@ mod tests {
     #[tokio::test]
     async fn test_run_code_unsupported_language() {
         let r = run_code("ruby".into(), "puts 'test'".into(), Some(2000), None).await;
-        assert!(r.is_err());
-        assert!(r.unwrap_err().contains("Unsupported language"));
+        let err = r.unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::InvalidInput);
+        assert!(err.message.contains("Unsupported language"));
     }
 }
@@ -1,30 +1,27 @@
 use crate::database::{settings::*, Database};
+use crate::error::{AppError, AppResult};
 use tauri::State;
 
 #[tauri::command]
-pub async fn set_setting(
-    db: State<'_, Database>,
-    key: String,
-    value: String,
-) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Setting::set(&conn, &key, &value).map_err(|e| e.to_string())
+pub async fn set_setting(db: State<'_, Database>, key: String, value: String) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Setting::set(&conn, &key, &value).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn get_setting(db: State<'_, Database>, key: String) -> Result<Option<String>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Setting::get(&conn, &key).map_err(|e| e.to_string())
+pub async fn get_setting(db: State<'_, Database>, key: String) -> AppResult<Option<String>> {
+    let conn = db.conn().lock()?;
+    Setting::get(&conn, &key).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn get_all_settings(db: State<'_, Database>) -> Result<Vec<Setting>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Setting::get_all(&conn).map_err(|e| e.to_string())
+pub async fn get_all_settings(db: State<'_, Database>) -> AppResult<Vec<Setting>> {
+    let conn = db.conn().lock()?;
+    Setting::get_all(&conn).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn delete_setting(db: State<'_, Database>, key: String) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Setting::delete(&conn, &key).map_err(|e| e.to_string())
+pub async fn delete_setting(db: State<'_, Database>, key: String) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Setting::delete(&conn, &key).map_err(AppError::from)
 }
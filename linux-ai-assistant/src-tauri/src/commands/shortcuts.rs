@@ -1,10 +1,16 @@
-use rusqlite::OptionalExtension;
+use crate::accelerator::{self, Accelerator};
+use crate::error::{AppError, AppResult};
+use crate::shortcut_portal::{self, PortalSession, PortalShortcut};
+use futures_util::StreamExt;
+use rusqlite::{Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
 
 // Define available shortcut actions
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub enum ShortcutAction {
     ToggleWindow,
@@ -44,7 +50,7 @@ impl ShortcutAction {
             ShortcutAction::ToggleWindow => "Show/hide the main application window",
             ShortcutAction::NewConversation => "Create a new conversation",
             ShortcutAction::OpenSettings => "Open the settings panel",
-            ShortcutAction::QuickCapture => "Quick capture input without showing window",
+            ShortcutAction::QuickCapture => "Run a quick action on the clipboard contents",
             ShortcutAction::FocusInput => "Focus the chat input field",
             ShortcutAction::ClearConversation => "Clear the current conversation",
             ShortcutAction::ExportCurrent => "Export current conversation to file",
@@ -186,94 +192,497 @@ impl Default for ShortcutConfig {
     }
 }
 
+/// Enabled actions bound to the same key combination
+#[derive(Debug, Clone, Serialize)]
+pub struct ShortcutConflict {
+    pub shortcut: String,
+    pub actions: Vec<ShortcutAction>,
+}
+
+/// Outcome of binding one configured shortcut with the OS
+#[derive(Debug, Clone, Serialize)]
+pub struct ShortcutRegistration {
+    pub action: ShortcutAction,
+    pub shortcut: String,
+    pub registered: bool,
+    pub error: Option<String>,
+    /// Trigger the desktop portal reports, which the user may have changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_description: Option<String>,
+}
+
+/// How global shortcuts are bound
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortcutBackend {
+    /// `org.freedesktop.portal.GlobalShortcuts` (Wayland)
+    Portal,
+    /// `tauri-plugin-global-shortcut` key grabs (X11)
+    Plugin,
+}
+
+/// Which mechanism is active and why, for troubleshooting
+#[derive(Debug, Clone, Serialize)]
+pub struct ShortcutDiagnostics {
+    /// None until shortcuts have been applied once
+    pub backend: Option<ShortcutBackend>,
+    pub wayland: bool,
+    pub session_type: Option<String>,
+    pub portal_version: Option<u32>,
+    /// Why the portal wasn't used on a Wayland session
+    pub fallback_reason: Option<String>,
+    pub registrations: Vec<ShortcutRegistration>,
+}
+
+#[derive(Debug, Default)]
+struct BackendState {
+    backend: Option<ShortcutBackend>,
+    portal_version: Option<u32>,
+    fallback_reason: Option<String>,
+}
+
+/// Open portal session and the task forwarding its activations
+struct ActivePortal {
+    session: PortalSession,
+    listener: tauri::async_runtime::JoinHandle<()>,
+}
+
 // Global state for managing shortcuts
 lazy_static::lazy_static! {
     static ref SHORTCUT_CONFIG: Arc<Mutex<Option<ShortcutConfig>>> = Arc::new(Mutex::new(None));
+    static ref REGISTRATIONS: Mutex<Vec<ShortcutRegistration>> = Mutex::new(Vec::new());
+    static ref BACKEND: Mutex<BackendState> = Mutex::new(BackendState::default());
+    static ref PORTAL: tokio::sync::Mutex<Option<ActivePortal>> = tokio::sync::Mutex::new(None);
+}
+
+/// Parse a shortcut into the plugin's hotkey type
+fn plugin_shortcut(shortcut: &str) -> AppResult<Shortcut> {
+    let plugin = Accelerator::parse(shortcut)?.to_plugin_string()?;
+    Shortcut::from_str(&plugin)
+        .map_err(|e| AppError::invalid_input(format!("Invalid shortcut {}: {}", shortcut, e)))
+}
+
+/// Key used to compare shortcuts: the plugin's hotkey id when the string
+/// parses, so `Ctrl+N` and `CommandOrControl+n` collide on Linux
+fn shortcut_key(shortcut: &str) -> String {
+    match plugin_shortcut(shortcut) {
+        Ok(parsed) => parsed.id().to_string(),
+        Err(_) => {
+            accelerator::normalize(shortcut).unwrap_or_else(|_| shortcut.trim().to_lowercase())
+        }
+    }
+}
+
+/// Enabled shortcuts that more than one action uses
+pub fn find_conflicts(config: &ShortcutConfig) -> Vec<ShortcutConflict> {
+    let mut conflicts: Vec<(String, ShortcutConflict)> = Vec::new();
+    for entry in config.shortcuts.iter().filter(|s| s.enabled) {
+        let key = shortcut_key(&entry.shortcut);
+        match conflicts.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, conflict)) => conflict.actions.push(entry.action),
+            None => conflicts.push((
+                key,
+                ShortcutConflict {
+                    shortcut: entry.shortcut.clone(),
+                    actions: vec![entry.action],
+                },
+            )),
+        }
+    }
+    conflicts
+        .into_iter()
+        .map(|(_, c)| c)
+        .filter(|c| c.actions.len() > 1)
+        .collect()
 }
 
-pub fn initialize_shortcut_manager(_app_handle: AppHandle) {
-    // Store app handle for future use
-    // For now, we'll rely on the existing global shortcut system in lib.rs
-    // and just manage the configuration here
-    std::thread::spawn(move || {
-        // Initialize with default config
-        let mut guard = SHORTCUT_CONFIG.lock().unwrap();
-        *guard = Some(ShortcutConfig::default());
+/// Run the handler for a triggered shortcut. Window toggling and clipboard
+/// quick actions run in the backend; everything else shows the window and is
+/// forwarded to the frontend as a `shortcut://action` event.
+pub fn dispatch(app: &AppHandle, action: ShortcutAction) {
+    match action {
+        ShortcutAction::ToggleWindow => {
+            if let Err(e) = crate::commands::window::toggle_main_window(app.clone()) {
+                eprintln!("failed to toggle window: {}", e);
+            }
+        }
+        ShortcutAction::QuickCapture => crate::commands::clipboard::quick_capture(app),
+        _ => {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+            let _ = app.emit("shortcut://action", serde_json::json!({ "action": action }));
+        }
+    }
+}
+
+/// Id of an action in the portal, e.g. `ToggleWindow`
+fn action_id(action: ShortcutAction) -> String {
+    format!("{:?}", action)
+}
+
+fn action_from_id(id: &str) -> Option<ShortcutAction> {
+    ShortcutAction::all_actions()
+        .into_iter()
+        .find(|a| action_id(*a) == id)
+}
+
+/// Enabled entries with their parsed shortcut, or why they can't be bound
+fn candidates(config: &ShortcutConfig) -> Vec<(&GlobalShortcut, Result<Accelerator, String>)> {
+    let mut seen: Vec<(String, ShortcutAction)> = Vec::new();
+    config
+        .shortcuts
+        .iter()
+        .filter(|s| s.enabled)
+        .map(|entry| {
+            let key = shortcut_key(&entry.shortcut);
+            let result = match seen.iter().find(|(k, _)| *k == key) {
+                Some((_, other)) => Err(format!("Conflicts with {}", other.display_name())),
+                None => Accelerator::parse(&entry.shortcut).map_err(|e| e.to_string()),
+            };
+            if result.is_ok() {
+                seen.push((key, entry.action));
+            }
+            (entry, result)
+        })
+        .collect()
+}
+
+fn registration(
+    entry: &GlobalShortcut,
+    result: Result<Option<String>, String>,
+) -> ShortcutRegistration {
+    ShortcutRegistration {
+        action: entry.action,
+        shortcut: entry.shortcut.clone(),
+        registered: result.is_ok(),
+        trigger_description: result.as_ref().ok().cloned().flatten(),
+        error: result.err(),
+    }
+}
+
+fn bind_with_plugin(
+    app: &AppHandle,
+    candidates: &[(&GlobalShortcut, Result<Accelerator, String>)],
+) -> Vec<ShortcutRegistration> {
+    let global = app.global_shortcut();
+    if let Err(e) = global.unregister_all() {
+        eprintln!("failed to unregister shortcuts: {}", e);
+    }
+    candidates
+        .iter()
+        .map(|(entry, parsed)| {
+            let result = parsed.clone().and_then(|accelerator| {
+                let shortcut = accelerator
+                    .to_plugin_string()
+                    .map_err(|e| e.to_string())
+                    .and_then(|s| Shortcut::from_str(&s).map_err(|e| e.to_string()))?;
+                let action = entry.action;
+                global
+                    .on_shortcut(shortcut, move |app, _shortcut, event| {
+                        if event.state == ShortcutState::Pressed {
+                            dispatch(app, action);
+                        }
+                    })
+                    .map(|_| None)
+                    .map_err(|e| e.to_string())
+            });
+            registration(entry, result)
+        })
+        .collect()
+}
+
+/// Bind through the desktop portal in a fresh session, replacing the previous
+/// one. Returns the portal version and per-entry results.
+async fn bind_with_portal(
+    app: &AppHandle,
+    candidates: &[(&GlobalShortcut, Result<Accelerator, String>)],
+) -> AppResult<(u32, Vec<ShortcutRegistration>)> {
+    let conn = zbus::Connection::session()
+        .await
+        .map_err(|e| AppError::unsupported(format!("No D-Bus session bus: {}", e)))?;
+    let version = shortcut_portal::portal_version(&conn).await?;
+
+    let mut active = PORTAL.lock().await;
+    if let Some(previous) = active.take() {
+        previous.listener.abort();
+        previous.session.close().await;
+    }
+
+    let triggers: Vec<Result<PortalShortcut, String>> = candidates
+        .iter()
+        .map(|(entry, parsed)| {
+            let accelerator = parsed.clone()?;
+            Ok(PortalShortcut {
+                id: action_id(entry.action),
+                description: entry.action.display_name().to_string(),
+                preferred_trigger: Some(
+                    accelerator.to_portal_trigger().map_err(|e| e.to_string())?,
+                ),
+            })
+        })
+        .collect();
+    let requested: Vec<PortalShortcut> = triggers.iter().flatten().cloned().collect();
+
+    let session = PortalSession::create(&conn).await?;
+    let bound = if requested.is_empty() {
+        Vec::new()
+    } else {
+        session.bind(&requested).await?
+    };
+
+    let mut activations = Box::pin(session.activations().await?);
+    let handle = app.clone();
+    let listener = tauri::async_runtime::spawn(async move {
+        while let Some(id) = activations.next().await {
+            match action_from_id(&id) {
+                Some(action) => dispatch(&handle, action),
+                None => eprintln!("unknown shortcut activated: {}", id),
+            }
+        }
     });
+    *active = Some(ActivePortal { session, listener });
+
+    let registrations = candidates
+        .iter()
+        .zip(triggers)
+        .map(|((entry, _), trigger)| {
+            let result = trigger.and_then(|shortcut| {
+                bound
+                    .iter()
+                    .find(|b| b.id == shortcut.id)
+                    .map(|b| b.trigger_description.clone())
+                    .ok_or_else(|| "Not bound by the desktop".to_string())
+            });
+            registration(entry, result)
+        })
+        .collect();
+    Ok((version, registrations))
 }
 
-#[tauri::command]
-pub async fn get_shortcut_config(
-    db: tauri::State<'_, crate::database::Database>,
-) -> Result<ShortcutConfig, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+/// Replace all global shortcuts with the enabled ones in `config`, through the
+/// XDG GlobalShortcuts portal on Wayland and the global-shortcut plugin
+/// otherwise (or when the portal is unavailable). Shortcuts that don't parse,
+/// repeat an earlier binding or are refused are reported as not registered
+/// (and in a `shortcuts://registration-failed` event).
+pub async fn apply_shortcuts(
+    app: &AppHandle,
+    config: &ShortcutConfig,
+) -> Vec<ShortcutRegistration> {
+    let candidates = candidates(config);
+    let mut state = BackendState::default();
 
-    // Try to get existing config from database
+    let portal = if shortcut_portal::is_wayland_session() {
+        match bind_with_portal(app, &candidates).await {
+            Ok(result) => Some(result),
+            Err(e) => {
+                eprintln!("falling back to X11 shortcuts: {}", e);
+                state.fallback_reason = Some(e.message);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let registrations = match portal {
+        Some((version, registrations)) => {
+            // Drop any key grabs left from an earlier plugin binding
+            let _ = app.global_shortcut().unregister_all();
+            state.backend = Some(ShortcutBackend::Portal);
+            state.portal_version = Some(version);
+            registrations
+        }
+        None => {
+            if let Some(previous) = PORTAL.lock().await.take() {
+                previous.listener.abort();
+                previous.session.close().await;
+            }
+            state.backend = Some(ShortcutBackend::Plugin);
+            bind_with_plugin(app, &candidates)
+        }
+    };
+
+    let failed: Vec<&ShortcutRegistration> =
+        registrations.iter().filter(|r| !r.registered).collect();
+    if !failed.is_empty() {
+        for r in &failed {
+            eprintln!(
+                "shortcut {} for {} not registered: {}",
+                r.shortcut,
+                r.action.display_name(),
+                r.error.as_deref().unwrap_or_default()
+            );
+        }
+        let _ = app.emit("shortcuts://registration-failed", &failed);
+    }
+
+    if let Ok(mut guard) = REGISTRATIONS.lock() {
+        *guard = registrations.clone();
+    }
+    if let Ok(mut guard) = BACKEND.lock() {
+        *guard = state;
+    }
+    registrations
+}
+
+fn load_config(conn: &Connection) -> AppResult<ShortcutConfig> {
     let config_json: Option<String> = conn
         .prepare("SELECT value FROM settings WHERE key = 'shortcut_config'")
-        .and_then(|mut stmt| stmt.query_row([], |row| row.get(0)).optional())
-        .map_err(|e| e.to_string())?;
+        .and_then(|mut stmt| stmt.query_row([], |row| row.get(0)).optional())?;
 
     match config_json {
-        Some(json) => {
-            serde_json::from_str(&json).map_err(|e| format!("Failed to parse config: {}", e))
-        }
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::from(e).context("Failed to parse config")),
         None => Ok(ShortcutConfig::default()),
     }
 }
 
+/// Load the saved shortcut config (or the defaults) and bind it
+pub fn initialize_shortcut_manager(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let config = app_handle
+            .try_state::<crate::database::Database>()
+            .and_then(|db| {
+                let conn = db.conn().lock().ok()?;
+                load_config(&conn)
+                    .map_err(|e| eprintln!("failed to load shortcut config: {}", e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        apply_shortcuts(&app_handle, &config).await;
+        if let Ok(mut guard) = SHORTCUT_CONFIG.lock() {
+            *guard = Some(config);
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_shortcut_config(
+    db: tauri::State<'_, crate::database::Database>,
+) -> AppResult<ShortcutConfig> {
+    let conn = db.conn().lock()?;
+    load_config(&conn)
+}
+
+/// Registration result of each enabled shortcut from the last time they were bound
+#[tauri::command]
+pub async fn get_shortcut_status() -> AppResult<Vec<ShortcutRegistration>> {
+    Ok(REGISTRATIONS.lock()?.clone())
+}
+
+/// Report whether shortcuts go through the Wayland portal or X11 key grabs
+#[tauri::command]
+pub async fn get_shortcut_backend() -> AppResult<ShortcutDiagnostics> {
+    let state = BACKEND.lock()?;
+    Ok(ShortcutDiagnostics {
+        backend: state.backend,
+        wayland: shortcut_portal::is_wayland_session(),
+        session_type: std::env::var("XDG_SESSION_TYPE").ok(),
+        portal_version: state.portal_version,
+        fallback_reason: state.fallback_reason.clone(),
+        registrations: REGISTRATIONS.lock()?.clone(),
+    })
+}
+
+/// Save the config and re-bind shortcuts right away. Configs that bind one
+/// combination to several actions are rejected; shortcuts the OS refuses are
+/// returned as not registered.
 #[tauri::command]
 pub async fn update_shortcut_config(
     config: ShortcutConfig,
     db: tauri::State<'_, crate::database::Database>,
-    _app: AppHandle,
-) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+    app: AppHandle,
+) -> AppResult<Vec<ShortcutRegistration>> {
+    let mut config = config;
+    for entry in config.shortcuts.iter_mut() {
+        // Disabled entries may hold chords; enabled ones must be bindable
+        if entry.enabled {
+            plugin_shortcut(&entry.shortcut)?;
+        }
+        entry.shortcut = accelerator::normalize(&entry.shortcut)?;
+    }
 
-    // Save config to database
-    let config_json =
-        serde_json::to_string(&config).map_err(|e| format!("Failed to serialize config: {}", e))?;
+    let conflicts = find_conflicts(&config);
+    if !conflicts.is_empty() {
+        let summary = conflicts
+            .iter()
+            .map(|c| {
+                let actions: Vec<&str> = c.actions.iter().map(|a| a.display_name()).collect();
+                format!("{} ({})", c.shortcut, actions.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Err(
+            AppError::conflict(format!("Shortcut used more than once: {}", summary))
+                .with_details(serde_json::json!({ "conflicts": conflicts })),
+        );
+    }
 
-    conn.prepare("INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)")
-        .and_then(|mut stmt| stmt.execute(["shortcut_config", &config_json]))
-        .map_err(|e| e.to_string())?;
+    // Save config to database
+    let config_json = serde_json::to_string(&config)
+        .map_err(|e| AppError::from(e).context("Failed to serialize config"))?;
+    {
+        let conn = db.conn().lock()?;
+        conn.prepare("INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)")
+            .and_then(|mut stmt| stmt.execute(["shortcut_config", &config_json]))?;
+    }
 
-    drop(conn);
+    let registrations = apply_shortcuts(&app, &config).await;
 
     // Update the configuration
-    let mut guard = SHORTCUT_CONFIG.lock().map_err(|e| e.to_string())?;
+    let mut guard = SHORTCUT_CONFIG.lock()?;
     *guard = Some(config);
 
-    Ok(())
+    Ok(registrations)
 }
 
+/// Check that a shortcut parses and can be bound globally
 #[tauri::command]
-pub async fn validate_shortcut(shortcut: String) -> Result<bool, String> {
-    // Basic validation - check format
-    if shortcut.trim().is_empty() {
-        return Err("Shortcut cannot be empty".to_string());
-    }
-
-    // Check for basic modifier + key pattern
-    let has_modifier = shortcut.contains("Command")
-        || shortcut.contains("Control")
-        || shortcut.contains("Ctrl")
-        || shortcut.contains("Alt")
-        || shortcut.contains("Shift");
-
-    let has_plus = shortcut.contains("+");
-
-    if !has_modifier || !has_plus {
-        return Err(
-            "Shortcut must include a modifier key (CommandOrControl, Alt, Shift) and a main key"
-                .to_string(),
-        );
-    }
-
+pub async fn validate_shortcut(shortcut: String) -> AppResult<bool> {
+    plugin_shortcut(&shortcut)?;
     Ok(true)
 }
 
+/// Normalized form of a shortcut, e.g. `shift+ctrl+k` -> `Control+Shift+K`
 #[tauri::command]
-pub async fn get_available_actions() -> Result<Vec<ShortcutAction>, String> {
+pub async fn normalize_shortcut(shortcut: String) -> AppResult<String> {
+    accelerator::normalize(&shortcut).map_err(AppError::from)
+}
+
+#[tauri::command]
+pub async fn get_available_actions() -> AppResult<Vec<ShortcutAction>> {
     Ok(ShortcutAction::all_actions())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflicts_only_between_enabled_shortcuts() {
+        assert!(find_conflicts(&ShortcutConfig::default()).is_empty());
+
+        let mut config = ShortcutConfig::default();
+        for entry in config.shortcuts.iter_mut() {
+            match entry.action {
+                ShortcutAction::NewConversation | ShortcutAction::OpenSettings => {
+                    entry.shortcut = "CommandOrControl+N".to_string();
+                    entry.enabled = true;
+                }
+                ShortcutAction::ExportCurrent => entry.shortcut = "CommandOrControl+N".to_string(),
+                _ => {}
+            }
+        }
+        let conflicts = find_conflicts(&config);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].actions,
+            vec![
+                ShortcutAction::NewConversation,
+                ShortcutAction::OpenSettings
+            ]
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::database::{tags::*, Database};
+use crate::error::{AppError, AppResult};
 use tauri::State;
 
 #[tauri::command]
@@ -6,34 +7,34 @@ pub async fn create_tag(
     db: State<'_, Database>,
     name: String,
     color: Option<String>,
-) -> Result<Tag, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+) -> AppResult<Tag> {
+    let conn = db.conn().lock()?;
     let new_tag = NewTag { name, color };
-    Tag::create(&conn, new_tag).map_err(|e| e.to_string())
+    Tag::create(&conn, new_tag).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn get_tag(db: State<'_, Database>, id: String) -> Result<Option<Tag>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Tag::get_by_id(&conn, &id).map_err(|e| e.to_string())
+pub async fn get_tag(db: State<'_, Database>, id: String) -> AppResult<Option<Tag>> {
+    let conn = db.conn().lock()?;
+    Tag::get_by_id(&conn, &id).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn get_tag_by_name(db: State<'_, Database>, name: String) -> Result<Option<Tag>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Tag::get_by_name(&conn, &name).map_err(|e| e.to_string())
+pub async fn get_tag_by_name(db: State<'_, Database>, name: String) -> AppResult<Option<Tag>> {
+    let conn = db.conn().lock()?;
+    Tag::get_by_name(&conn, &name).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn get_all_tags(db: State<'_, Database>) -> Result<Vec<Tag>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Tag::get_all(&conn).map_err(|e| e.to_string())
+pub async fn get_all_tags(db: State<'_, Database>) -> AppResult<Vec<Tag>> {
+    let conn = db.conn().lock()?;
+    Tag::get_all(&conn).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn search_tags(db: State<'_, Database>, query: String) -> Result<Vec<Tag>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Tag::search(&conn, &query).map_err(|e| e.to_string())
+pub async fn search_tags(db: State<'_, Database>, query: String) -> AppResult<Vec<Tag>> {
+    let conn = db.conn().lock()?;
+    Tag::search(&conn, &query).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -42,24 +43,24 @@ pub async fn update_tag(
     id: String,
     name: String,
     color: Option<String>,
-) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Tag::update(&conn, &id, &name, color.as_deref()).map_err(|e| e.to_string())
+) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Tag::update(&conn, &id, &name, color.as_deref()).map_err(AppError::from)
 }
 
 #[tauri::command]
-pub async fn delete_tag(db: State<'_, Database>, id: String) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Tag::delete(&conn, &id).map_err(|e| e.to_string())
+pub async fn delete_tag(db: State<'_, Database>, id: String) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Tag::delete(&conn, &id).map_err(AppError::from)
 }
 
 #[tauri::command]
 pub async fn get_conversation_tags(
     db: State<'_, Database>,
     conversation_id: String,
-) -> Result<Vec<Tag>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Tag::get_for_conversation(&conn, &conversation_id).map_err(|e| e.to_string())
+) -> AppResult<Vec<Tag>> {
+    let conn = db.conn().lock()?;
+    Tag::get_for_conversation(&conn, &conversation_id).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -67,9 +68,9 @@ pub async fn add_tag_to_conversation(
     db: State<'_, Database>,
     conversation_id: String,
     tag_id: String,
-) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Tag::add_to_conversation(&conn, &conversation_id, &tag_id).map_err(|e| e.to_string())
+) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Tag::add_to_conversation(&conn, &conversation_id, &tag_id).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -77,18 +78,18 @@ pub async fn remove_tag_from_conversation(
     db: State<'_, Database>,
     conversation_id: String,
     tag_id: String,
-) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Tag::remove_from_conversation(&conn, &conversation_id, &tag_id).map_err(|e| e.to_string())
+) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    Tag::remove_from_conversation(&conn, &conversation_id, &tag_id).map_err(AppError::from)
 }
 
 #[tauri::command]
 pub async fn get_conversations_by_tag(
     db: State<'_, Database>,
     tag_id: String,
-) -> Result<Vec<String>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Tag::get_conversations_with_tag(&conn, &tag_id).map_err(|e| e.to_string())
+) -> AppResult<Vec<String>> {
+    let conn = db.conn().lock()?;
+    Tag::get_conversations_with_tag(&conn, &tag_id).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -96,9 +97,9 @@ pub async fn create_or_get_tag(
     db: State<'_, Database>,
     name: String,
     color: Option<String>,
-) -> Result<Tag, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
-    Tag::create_or_get(&conn, &name, color.as_deref()).map_err(|e| e.to_string())
+) -> AppResult<Tag> {
+    let conn = db.conn().lock()?;
+    Tag::create_or_get(&conn, &name, color.as_deref()).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -106,16 +107,16 @@ pub async fn add_tags_to_conversation_bulk(
     db: State<'_, Database>,
     conversation_id: String,
     tag_names: Vec<String>,
-) -> Result<Vec<Tag>, String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+) -> AppResult<Vec<Tag>> {
+    let conn = db.conn().lock()?;
     let mut created_tags = Vec::new();
 
     for tag_name in tag_names {
         // Create or get the tag
-        let tag = Tag::create_or_get(&conn, &tag_name, None).map_err(|e| e.to_string())?;
+        let tag = Tag::create_or_get(&conn, &tag_name, None)?;
 
         // Add to conversation
-        Tag::add_to_conversation(&conn, &conversation_id, &tag.id).map_err(|e| e.to_string())?;
+        Tag::add_to_conversation(&conn, &conversation_id, &tag.id)?;
 
         created_tags.push(tag);
     }
@@ -1,5 +1,19 @@
+use crate::database::settings::Setting;
+use crate::database::Database;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::updater::{self, update_public_key, Release, UpdateClient, UpdateSettings};
 use log::{error, info};
+use rusqlite::Connection;
+use semver::Version;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// Result of the last check, returned until the next one
+const STATUS_KEY: &str = "update_status";
+
+/// How often the scheduler looks whether a check is due
+const CHECK_POLL: Duration = Duration::from_secs(10 * 60);
 
 /// Version information returned from the API
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,6 +22,7 @@ pub struct VersionInfo {
     pub release_date: String,
     pub changelog: String,
     pub download_url: String,
+    /// SHA-256 of the AppImage from the release's signed manifest
     pub checksum: Option<String>,
     pub is_critical: bool,
 }
@@ -21,69 +36,124 @@ pub struct UpdateStatus {
     pub release_info: Option<VersionInfo>,
     pub is_installing: bool,
     pub error: Option<String>,
+    /// The newest version was skipped in settings (never set for critical updates)
+    #[serde(default)]
+    pub skipped: bool,
+}
+
+/// Result of downloading (and, when running as an AppImage, installing) an update
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallOutcome {
+    pub version: String,
+    /// The verified AppImage: the running one after an install, otherwise the download
+    pub path: String,
+    pub sha256: String,
+    /// Whether the running AppImage was replaced; a restart picks up the update
+    pub installed: bool,
+    /// The replaced AppImage, kept for `rollback_update`
+    pub backup_path: Option<String>,
 }
 
-/// Check for available updates from GitHub releases
-/// This queries the GitHub API for the latest release
+/// The result of the last check. With `force`, check the configured channel
+/// now instead; otherwise `start_update_checks` checks once the configured
+/// interval has passed.
 #[tauri::command]
-pub async fn check_for_updates(_app: tauri::AppHandle) -> Result<UpdateStatus, String> {
+pub async fn check_for_updates(
+    _app: tauri::AppHandle,
+    db: State<'_, Database>,
+    force: Option<bool>,
+) -> AppResult<UpdateStatus> {
     let current_version = env!("CARGO_PKG_VERSION");
+    let settings = {
+        let conn = db.conn().lock()?;
+        if !force.unwrap_or(false) {
+            return Ok(stored_status(&conn)?.unwrap_or_else(|| no_update(current_version, None)));
+        }
+        UpdateSettings::load(&conn)?
+    };
+    let now = chrono::Utc::now().timestamp();
+    let status = check_now(&settings).await;
+    record_check(&*db.conn().lock()?, now, &status)?;
+    Ok(status)
+}
 
-    info!(
-        "Checking for updates... Current version: {}",
-        current_version
-    );
+#[tauri::command]
+pub async fn get_update_settings(db: State<'_, Database>) -> AppResult<UpdateSettings> {
+    let conn = db.conn().lock()?;
+    UpdateSettings::load(&conn)
+}
 
-    match check_github_releases(current_version).await {
-        Ok(version_info) => {
-            info!(
-                "Update check successful. New version available: {}",
-                version_info.version
-            );
-            Ok(UpdateStatus {
-                has_update: version_info.version != current_version,
-                current_version: current_version.to_string(),
-                new_version: Some(version_info.version.clone()),
-                release_info: Some(version_info),
-                is_installing: false,
-                error: None,
-            })
-        }
-        Err(e) => {
-            error!("Failed to check for updates: {}", e);
-            Ok(UpdateStatus {
-                has_update: false,
-                current_version: current_version.to_string(),
-                new_version: None,
-                release_info: None,
-                is_installing: false,
-                error: Some(e),
-            })
+/// Channel, check interval, skipped version and mirror URL. The last check
+/// time is kept from the stored settings unless the channel or mirror changed.
+#[tauri::command]
+pub async fn update_update_settings(
+    db: State<'_, Database>,
+    settings: UpdateSettings,
+) -> AppResult<UpdateSettings> {
+    settings.validate()?;
+    let conn = db.conn().lock()?;
+    let stored = UpdateSettings::load(&conn)?;
+    // Another channel or mirror is checked right away
+    let same_source = stored.channel == settings.channel && stored.base_url == settings.base_url;
+    let settings = UpdateSettings {
+        last_checked: stored.last_checked.filter(|_| same_source),
+        ..settings
+    };
+    settings.save(&conn)?;
+    // The stored result may be for another channel, mirror or skipped version
+    Setting::delete(&conn, STATUS_KEY)?;
+    Ok(settings)
+}
+
+/// Stop offering `version` (unless it turns out to be critical)
+#[tauri::command]
+pub async fn skip_update_version(db: State<'_, Database>, version: String) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    let mut settings = UpdateSettings::load(&conn)?;
+    settings.skipped_version = Some(version.trim_start_matches('v').to_string());
+    settings.save(&conn)?;
+    if let Some(mut status) = stored_status(&conn)? {
+        if status.new_version.as_deref() == settings.skipped_version.as_deref()
+            && !status.release_info.as_ref().is_some_and(|r| r.is_critical)
+        {
+            status.has_update = false;
+            status.skipped = true;
+            Setting::set_json(&conn, STATUS_KEY, &status)?;
         }
     }
+    Ok(())
 }
 
-/// Download and install an update from GitHub
-/// This command handles the download and installation process
+/// Download an update, verify it against the release's signed checksum
+/// manifest, and replace the running AppImage with it. Outside an AppImage
+/// the verified file is left in `~/.local/share/linux-ai-assistant/`.
 #[tauri::command]
 pub async fn download_and_install_update(
     _app: tauri::AppHandle,
+    db: State<'_, Database>,
     version: String,
-) -> Result<String, String> {
+) -> AppResult<InstallOutcome> {
     info!("Starting update download for version: {}", version);
 
-    match download_release(&version).await {
-        Ok(file_path) => {
-            info!("Update downloaded to: {}", file_path);
+    let settings = {
+        let conn = db.conn().lock()?;
+        UpdateSettings::load(&conn)?
+    };
+    let client = UpdateClient::new(settings.api_base(), update_public_key());
+    install_release(&client, &version)
+        .await
+        .inspect_err(|e| error!("Failed to install update: {}", e))
+        .map_err(|e| e.context("Update failed"))
+}
 
-            // Installation will be handled by the system or through a restart
-            Ok(format!("Update {} downloaded successfully", version))
-        }
-        Err(e) => {
-            error!("Failed to download update: {}", e);
-            Err(format!("Download failed: {}", e))
-        }
-    }
+/// Restore the AppImage the last update replaced
+#[tauri::command]
+pub async fn rollback_update() -> AppResult<String> {
+    let target = updater::current_appimage()
+        .ok_or_else(|| AppError::unsupported("Not running from an AppImage"))?;
+    updater::rollback(&target)?;
+    info!("Rolled back {}", target.display());
+    Ok(target.to_string_lossy().to_string())
 }
 
 /// Get current application version
@@ -92,165 +162,304 @@ pub fn get_current_version() -> String {
     env!("CARGO_PKG_VERSION").to_string()
 }
 
-/// Check GitHub API for the latest release
-/// This is a helper function that queries the GitHub API
-async fn check_github_releases(_current_version: &str) -> Result<VersionInfo, String> {
-    let client = reqwest::Client::new();
-
-    // Query the GitHub API for latest releases
-    let url = "https://api.github.com/repos/tbmobb813/Linux-AI-Assistant---Project/releases/latest";
-
-    match client
-        .get(url)
-        .header("User-Agent", "linux-ai-assistant")
-        .send()
-        .await
-    {
-        Ok(response) => {
-            match response.json::<serde_json::Value>().await {
-                Ok(json) => {
-                    // Extract version from tag_name (e.g., "v0.2.0" -> "0.2.0")
-                    let version = json
-                        .get("tag_name")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("0.1.0")
-                        .trim_start_matches('v')
-                        .to_string();
-
-                    let changelog = json
-                        .get("body")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("No changelog available")
-                        .to_string();
-
-                    let published_at = json
-                        .get("published_at")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("Unknown")
-                        .to_string();
-
-                    // Find the Linux AppImage download URL
-                    let download_url = json
-                        .get("assets")
-                        .and_then(|assets| assets.as_array())
-                        .and_then(|assets_arr| {
-                            assets_arr.iter().find(|asset| {
-                                asset
-                                    .get("name")
-                                    .and_then(|n| n.as_str())
-                                    .map(|n| n.contains("AppImage") || n.contains("linux"))
-                                    .unwrap_or(false)
-                            })
-                        })
-                        .and_then(|asset| asset.get("browser_download_url"))
-                        .and_then(|url| url.as_str())
-                        .unwrap_or("")
-                        .to_string();
-
-                    info!("Latest version from GitHub: {}", version);
-
-                    Ok(VersionInfo {
-                        version,
-                        release_date: published_at,
-                        changelog,
-                        download_url,
-                        checksum: None,
-                        is_critical: false,
-                    })
-                }
-                Err(e) => Err(format!("Failed to parse GitHub API response: {}", e)),
+/// Check for updates whenever a check is due. `update://checked` carries the
+/// `UpdateStatus` of each check.
+pub fn start_update_checks(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        let db = app.state::<Database>();
+        let now = chrono::Utc::now().timestamp();
+        let settings = db
+            .conn()
+            .lock_in_background()
+            .and_then(|conn| UpdateSettings::load(&conn));
+        let outcome = settings.and_then(|settings| {
+            if !settings.is_due(now) {
+                return Ok(None);
             }
+            let status = tauri::async_runtime::block_on(check_now(&settings));
+            record_check(&*db.conn().lock_in_background()?, now, &status)?;
+            Ok(Some(status))
+        });
+        match outcome {
+            Ok(Some(status)) => {
+                let _ = app.emit("update://checked", &status);
+            }
+            Ok(None) => {}
+            // Resumes once the database is unlocked
+            Err(e) if e.code == ErrorCode::Locked => {}
+            Err(e) => eprintln!("update check failed: {}", e),
         }
-        Err(e) => Err(format!("Failed to reach GitHub API: {}", e)),
-    }
+        std::thread::sleep(CHECK_POLL);
+    });
 }
 
-/// Download a specific release from GitHub
-/// This is a helper function that downloads the release package
-async fn download_release(version: &str) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let version_tag = format!("v{}", version);
+/// The stored result of the last check, unless it was made by another version
+fn stored_status(conn: &Connection) -> AppResult<Option<UpdateStatus>> {
+    let status: Option<UpdateStatus> = Setting::get_json(conn, STATUS_KEY)?;
+    Ok(status.filter(|s| s.current_version == env!("CARGO_PKG_VERSION")))
+}
 
-    // First, get the release info to find the download URL
-    let url = format!(
-        "https://api.github.com/repos/tbmobb813/Linux-AI-Assistant---Project/releases/tags/{}",
-        version_tag
-    );
+/// Store the result of a check made at unix time `now`
+fn record_check(conn: &Connection, now: i64, status: &UpdateStatus) -> AppResult<()> {
+    let mut settings = UpdateSettings::load(conn)?;
+    settings.last_checked = Some(now);
+    settings.save(conn)?;
+    Ok(Setting::set_json(conn, STATUS_KEY, status)?)
+}
 
-    let response = client
-        .get(&url)
-        .header("User-Agent", "linux-ai-assistant")
-        .send()
-        .await
-        .map_err(|e| format!("Failed to fetch release info: {}", e))?;
+/// Check the configured channel, reporting failures in the status
+async fn check_now(settings: &UpdateSettings) -> UpdateStatus {
+    let current_version = env!("CARGO_PKG_VERSION");
+    info!(
+        "Checking for updates on the {:?} channel... Current version: {}",
+        settings.channel, current_version
+    );
+    let client = UpdateClient::new(settings.api_base(), update_public_key());
+    match check_releases(&client, current_version, settings).await {
+        Ok(status) => status,
+        Err(e) => {
+            error!("Failed to check for updates: {}", e);
+            no_update(current_version, Some(e.message))
+        }
+    }
+}
 
-    let json = response
-        .json::<serde_json::Value>()
-        .await
-        .map_err(|e| format!("Failed to parse release info: {}", e))?;
-
-    // Find the Linux AppImage download URL
-    let download_url = json
-        .get("assets")
-        .and_then(|assets| assets.as_array())
-        .and_then(|assets_arr| {
-            assets_arr.iter().find(|asset| {
-                asset
-                    .get("name")
-                    .and_then(|n| n.as_str())
-                    .map(|n| n.contains("AppImage") || n.ends_with(".AppImage"))
-                    .unwrap_or(false)
-            })
-        })
-        .and_then(|asset| asset.get("browser_download_url"))
-        .and_then(|url| url.as_str())
-        .ok_or("No AppImage found in release assets".to_string())?;
+fn no_update(current_version: &str, error: Option<String>) -> UpdateStatus {
+    UpdateStatus {
+        has_update: false,
+        current_version: current_version.to_string(),
+        new_version: None,
+        release_info: None,
+        is_installing: false,
+        error,
+        skipped: false,
+    }
+}
 
-    info!("Downloading from: {}", download_url);
+/// Find the newest release on the configured channel and describe it
+async fn check_releases(
+    client: &UpdateClient,
+    current_version: &str,
+    settings: &UpdateSettings,
+) -> AppResult<UpdateStatus> {
+    let current = Version::parse(current_version)
+        .map_err(|e| AppError::internal(format!("Bad application version: {}", e)))?;
+    let releases = client.releases().await?;
+    let Some(release) = updater::select_update(&releases, &current, settings.channel) else {
+        return Ok(no_update(current_version, None));
+    };
+    info!(
+        "Newest {:?} release: {}",
+        settings.channel,
+        release.version()
+    );
 
-    // Download the file to a temporary location
-    let response = client
-        .get(download_url)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to download release: {}", e))?;
+    // A release without a valid signed manifest can still be shown, just not
+    // installed, and only a signed minimum version makes it critical
+    let (checksum, is_critical) = match client.verified_release(release).await {
+        Ok(signed) => (
+            signed
+                .checksums
+                .appimage()
+                .map(|(_, digest)| digest.to_string()),
+            signed.is_critical_for(&current),
+        ),
+        Err(e) => {
+            error!("Release {} is not verifiable: {}", release.tag_name, e);
+            (None, false)
+        }
+    };
+    let info = version_info(release, checksum, is_critical);
+    let skipped = !info.is_critical && settings.skipped_version.as_deref() == Some(&info.version);
+    Ok(UpdateStatus {
+        has_update: !skipped,
+        current_version: current_version.to_string(),
+        new_version: Some(info.version.clone()),
+        release_info: Some(info),
+        is_installing: false,
+        error: None,
+        skipped,
+    })
+}
 
-    let content = response
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read download content: {}", e))?;
+fn version_info(release: &Release, checksum: Option<String>, is_critical: bool) -> VersionInfo {
+    VersionInfo {
+        version: release.version().to_string(),
+        release_date: release
+            .published_at
+            .clone()
+            .unwrap_or_else(|| "Unknown".to_string()),
+        changelog: release
+            .body
+            .clone()
+            .unwrap_or_else(|| "No changelog available".to_string()),
+        download_url: release
+            .appimage()
+            .map(|asset| asset.browser_download_url.clone())
+            .unwrap_or_default(),
+        checksum,
+        is_critical,
+    }
+}
 
-    // Save to a standard location (~/.local/share/linux-ai-assistant/)
-    let mut save_dir = dirs::home_dir().ok_or("Failed to get home directory".to_string())?;
-    save_dir.push(".local/share/linux-ai-assistant");
+fn download_dir() -> AppResult<std::path::PathBuf> {
+    let mut dir = dirs::home_dir().ok_or_else(|| AppError::io("Failed to get home directory"))?;
+    dir.push(".local/share/linux-ai-assistant");
+    Ok(dir)
+}
 
-    std::fs::create_dir_all(&save_dir)
-        .map_err(|e| format!("Failed to create download directory: {}", e))?;
+/// Download and verify `version`, then install it over the running AppImage
+/// if there is one. The download goes next to the AppImage so the final
+/// rename stays on one filesystem.
+async fn install_release(client: &UpdateClient, version: &str) -> AppResult<InstallOutcome> {
+    let release = client.release(version).await?;
+    let target = updater::current_appimage();
+    let dir = match target.as_deref().and_then(|t| t.parent()) {
+        Some(parent) => parent.to_path_buf(),
+        None => download_dir()?,
+    };
+    let download = client.download_verified(&release, &dir).await?;
+    info!("Verified update downloaded to: {}", download.path.display());
+
+    let Some(target) = target else {
+        return Ok(InstallOutcome {
+            version: download.version,
+            path: download.path.to_string_lossy().to_string(),
+            sha256: download.sha256,
+            installed: false,
+            backup_path: None,
+        });
+    };
+    let installed = updater::install(&download, &target)?;
+    info!(
+        "Installed update {} at {}",
+        download.version,
+        target.display()
+    );
+    Ok(InstallOutcome {
+        version: download.version,
+        path: installed.target.to_string_lossy().to_string(),
+        sha256: download.sha256,
+        installed: true,
+        backup_path: Some(installed.backup.to_string_lossy().to_string()),
+    })
+}
 
-    let filename = format!("linux-ai-assistant-{}.AppImage", version);
-    let mut temp_path = save_dir;
-    temp_path.push(&filename);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::updater::tests::{
+        critical_comment, fake_appimage, public_key, release_routes, signed_release_routes,
+        signing_key, ReleaseServer,
+    };
+    use crate::updater::UpdateChannel;
+
+    #[tokio::test]
+    async fn reports_the_signed_checksum() {
+        let appimage = fake_appimage("0.3.0");
+        let sha = crate::attachments::hex_digest(&appimage);
+        let server = ReleaseServer::start(|base| {
+            release_routes(base, "0.3.0", &appimage, &sha, &signing_key())
+        });
+        let settings = UpdateSettings::default();
+        let client = UpdateClient::new(format!("{}/releases", server.base), public_key());
+
+        let status = check_releases(&client, "0.1.0", &settings).await.unwrap();
+        assert!(status.has_update);
+        let info = status.release_info.unwrap();
+        assert_eq!(info.version, "0.3.0");
+        assert_eq!(info.checksum.as_deref(), Some(sha.as_str()));
+        assert!(info.download_url.ends_with(".AppImage"));
+
+        let unsigned = UpdateClient::new(format!("{}/releases", server.base), update_public_key());
+        let status = check_releases(&unsigned, "0.1.0", &settings).await.unwrap();
+        assert_eq!(status.release_info.unwrap().checksum, None);
+    }
 
-    std::fs::write(&temp_path, content)
-        .map_err(|e| format!("Failed to write download file: {}", e))?;
+    #[tokio::test]
+    async fn honors_channels_skips_and_critical_releases() {
+        let appimage = fake_appimage("0.3.0-beta.1");
+        let sha = crate::attachments::hex_digest(&appimage);
+        let server = ReleaseServer::start(|base| {
+            // The beta's signature makes it critical below 0.2.0; the stable
+            // release claims the same in its unsigned notes, which counts for nothing
+            let mut routes = signed_release_routes(
+                base,
+                "0.3.0-beta.1",
+                &critical_comment("0.3.0-beta.1", "0.2.0"),
+                &appimage,
+                &sha,
+                &signing_key(),
+            );
+            let mut beta: serde_json::Value =
+                serde_json::from_slice(&routes["/releases/latest"]).unwrap();
+            beta["prerelease"] = true.into();
+            let releases = serde_json::json!([
+                { "tag_name": "v0.2.0", "body": "Minimum-Version: 0.2.0" },
+                beta,
+            ]);
+            routes.insert(
+                "/mirror/releases".to_string(),
+                serde_json::to_vec(&releases).unwrap(),
+            );
+            routes
+        });
+        let mirror = UpdateSettings {
+            base_url: Some(format!("{}/mirror/releases", server.base)),
+            ..UpdateSettings::default()
+        };
+        let client = UpdateClient::new(mirror.api_base(), public_key());
+        let check = |settings: UpdateSettings| {
+            let client = client.clone();
+            async move { check_releases(&client, "0.1.0", &settings).await.unwrap() }
+        };
+
+        let stable = check(mirror.clone()).await;
+        assert_eq!(stable.new_version.as_deref(), Some("0.2.0"));
+        assert!(stable.has_update);
+        assert!(!stable.release_info.unwrap().is_critical);
+
+        let skipped = check(UpdateSettings {
+            skipped_version: Some("0.2.0".to_string()),
+            ..mirror.clone()
+        })
+        .await;
+        assert!(!skipped.has_update && skipped.skipped);
+
+        // The beta requires at least 0.2.0, so it is offered even when skipped
+        let beta = check(UpdateSettings {
+            channel: UpdateChannel::Beta,
+            skipped_version: Some("0.3.0-beta.1".to_string()),
+            ..mirror.clone()
+        })
+        .await;
+        assert_eq!(beta.new_version.as_deref(), Some("0.3.0-beta.1"));
+        assert!(beta.has_update && !beta.skipped);
+        assert!(beta.release_info.unwrap().is_critical);
 
-    // Make it executable
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let perms = std::fs::Permissions::from_mode(0o755);
-        std::fs::set_permissions(&temp_path, perms)
-            .map_err(|e| format!("Failed to make executable: {}", e))?;
+        let current = check_releases(&client, "0.3.0", &mirror).await.unwrap();
+        assert!(!current.has_update && current.new_version.is_none());
     }
 
-    Ok(temp_path.to_string_lossy().to_string())
-}
-
-#[cfg(test)]
-mod tests {
     #[test]
-    fn test_version_parsing() {
-        let version_str = "0.2.0";
-        assert_eq!(version_str, "0.2.0");
+    fn keeps_the_last_result_across_restarts() {
+        let db = Database::new(std::path::PathBuf::from(":memory:")).unwrap();
+        let conn = db.conn().lock().unwrap();
+        assert!(stored_status(&conn).unwrap().is_none());
+
+        let mut status = no_update(env!("CARGO_PKG_VERSION"), None);
+        status.has_update = true;
+        status.new_version = Some("99.0.0".to_string());
+        record_check(&conn, 1_000_000, &status).unwrap();
+        let settings = UpdateSettings::load(&conn).unwrap();
+        assert_eq!(settings.last_checked, Some(1_000_000));
+        assert!(!settings.is_due(1_000_000 + 3600));
+        let stored = stored_status(&conn).unwrap().unwrap();
+        assert_eq!(stored.new_version.as_deref(), Some("99.0.0"));
+
+        // After an update, the old result no longer applies
+        let old = no_update("0.0.1", None);
+        record_check(&conn, 1_000_000, &old).unwrap();
+        assert!(stored_status(&conn).unwrap().is_none());
     }
 }
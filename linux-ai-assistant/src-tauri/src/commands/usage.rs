@@ -0,0 +1,106 @@
+use crate::database::{
+    usage::{ModelPrice, ProfileBudget},
+    Database,
+};
+use crate::error::{AppError, AppResult};
+use crate::usage::{BudgetStatus, UsageReport};
+use tauri::State;
+
+#[tauri::command]
+pub async fn usage_report(
+    db: State<'_, Database>,
+    range: String,
+    group_by: String,
+) -> AppResult<UsageReport> {
+    let conn = db.conn().lock()?;
+    crate::usage::usage_report(&conn, &range, &group_by)
+}
+
+#[tauri::command]
+pub async fn get_model_prices(db: State<'_, Database>) -> AppResult<Vec<ModelPrice>> {
+    let conn = db.conn().lock()?;
+    ModelPrice::get_all(&conn).map_err(AppError::from)
+}
+
+#[tauri::command]
+pub async fn set_model_price(
+    db: State<'_, Database>,
+    model: String,
+    provider: String,
+    input_per_mtok: f64,
+    output_per_mtok: f64,
+) -> AppResult<ModelPrice> {
+    if model.trim().is_empty() {
+        return Err(AppError::invalid_input("Model name is required"));
+    }
+    if input_per_mtok < 0.0 || output_per_mtok < 0.0 {
+        return Err(AppError::invalid_input("Prices cannot be negative"));
+    }
+    let conn = db.conn().lock()?;
+    ModelPrice::upsert(
+        &conn,
+        model.trim(),
+        &provider,
+        input_per_mtok,
+        output_per_mtok,
+    )
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+pub async fn delete_model_price(db: State<'_, Database>, model: String) -> AppResult<()> {
+    let conn = db.conn().lock()?;
+    ModelPrice::delete(&conn, &model).map_err(AppError::from)
+}
+
+#[tauri::command]
+pub async fn get_profile_budget(
+    db: State<'_, Database>,
+    profile_id: String,
+) -> AppResult<Option<ProfileBudget>> {
+    let conn = db.conn().lock()?;
+    ProfileBudget::get(&conn, &profile_id).map_err(AppError::from)
+}
+
+/// Set a profile's caps; clearing both limits removes the budget
+#[tauri::command]
+pub async fn set_profile_budget(
+    db: State<'_, Database>,
+    profile_id: String,
+    soft_limit_usd: Option<f64>,
+    hard_limit_usd: Option<f64>,
+    period: Option<String>,
+) -> AppResult<Option<ProfileBudget>> {
+    let period = period.unwrap_or_else(|| "month".to_string());
+    if !matches!(period.as_str(), "day" | "week" | "month") {
+        return Err(AppError::invalid_input(format!(
+            "Unknown budget period: {}",
+            period
+        )));
+    }
+    if let (Some(soft), Some(hard)) = (soft_limit_usd, hard_limit_usd) {
+        if soft > hard {
+            return Err(AppError::invalid_input(
+                "Soft limit cannot exceed the hard limit",
+            ));
+        }
+    }
+
+    let conn = db.conn().lock()?;
+    if soft_limit_usd.is_none() && hard_limit_usd.is_none() {
+        ProfileBudget::delete(&conn, &profile_id)?;
+        return Ok(None);
+    }
+    ProfileBudget::set(&conn, &profile_id, soft_limit_usd, hard_limit_usd, &period)
+        .map(Some)
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+pub async fn get_budget_status(
+    db: State<'_, Database>,
+    profile_id: String,
+) -> AppResult<Option<BudgetStatus>> {
+    let conn = db.conn().lock()?;
+    crate::usage::budget_status(&conn, &profile_id)
+}
@@ -1,4 +1,5 @@
 use crate::database::settings::Setting;
+use crate::error::{AppError, AppResult};
 use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager, PhysicalPosition, PhysicalSize};
@@ -25,17 +26,17 @@ impl Default for WindowState {
 }
 
 #[tauri::command]
-pub fn toggle_main_window(app: AppHandle) -> Result<(), String> {
+pub fn toggle_main_window(app: AppHandle) -> AppResult<()> {
     if let Some(window) = app.get_webview_window("main") {
         match window.is_visible() {
-            Ok(true) => window.hide().map_err(|e| e.to_string()),
+            Ok(true) => window.hide().map_err(AppError::from),
             _ => {
-                window.show().map_err(|e| e.to_string())?;
-                window.set_focus().map_err(|e| e.to_string())
+                window.show()?;
+                window.set_focus().map_err(AppError::from)
             }
         }
     } else {
-        Err("Main window not found".to_string())
+        Err(AppError::not_found("Main window not found"))
     }
 }
 
@@ -43,11 +44,11 @@ pub fn toggle_main_window(app: AppHandle) -> Result<(), String> {
 pub async fn save_window_state(
     app: AppHandle,
     db: tauri::State<'_, crate::database::Database>,
-) -> Result<(), String> {
+) -> AppResult<()> {
     if let Some(window) = app.get_webview_window("main") {
-        let position = window.outer_position().map_err(|e| e.to_string())?;
-        let size = window.outer_size().map_err(|e| e.to_string())?;
-        let maximized = window.is_maximized().map_err(|e| e.to_string())?;
+        let position = window.outer_position()?;
+        let size = window.outer_size()?;
+        let maximized = window.is_maximized()?;
 
         let window_state = WindowState {
             x: position.x,
@@ -57,13 +58,13 @@ pub async fn save_window_state(
             maximized,
         };
 
-        let conn = db.conn().lock().map_err(|e| e.to_string())?;
+        let conn = db.conn().lock()?;
         // Use settings helper to ensure updated_at is set to avoid NOT NULL constraint errors
-        Setting::set_json(&conn, "window_state", &window_state).map_err(|e| e.to_string())?;
+        Setting::set_json(&conn, "window_state", &window_state)?;
 
         Ok(())
     } else {
-        Err("Main window not found".to_string())
+        Err(AppError::not_found("Main window not found"))
     }
 }
 
@@ -71,32 +72,31 @@ pub async fn save_window_state(
 pub async fn restore_window_state(
     app: AppHandle,
     db: tauri::State<'_, crate::database::Database>,
-) -> Result<(), String> {
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+) -> AppResult<()> {
+    let conn = db.conn().lock()?;
 
     let state_json: Option<String> = conn
         .prepare("SELECT value FROM settings WHERE key = 'window_state'")
-        .and_then(|mut stmt| stmt.query_row([], |row| row.get(0)).optional())
-        .map_err(|e| e.to_string())?;
+        .and_then(|mut stmt| stmt.query_row([], |row| row.get(0)).optional())?;
 
     drop(conn);
 
     if let Some(json) = state_json {
         let window_state: WindowState = serde_json::from_str(&json)
-            .map_err(|e| format!("Failed to parse window state: {}", e))?;
+            .map_err(|e| AppError::from(e).context("Failed to parse window state"))?;
 
         if let Some(window) = app.get_webview_window("main") {
             // Restore size first
             let size = PhysicalSize::new(window_state.width, window_state.height);
-            window.set_size(size).map_err(|e| e.to_string())?;
+            window.set_size(size)?;
 
             // Then restore position
             let position = PhysicalPosition::new(window_state.x, window_state.y);
-            window.set_position(position).map_err(|e| e.to_string())?;
+            window.set_position(position)?;
 
             // Finally restore maximized state
             if window_state.maximized {
-                window.maximize().map_err(|e| e.to_string())?;
+                window.maximize()?;
             }
         }
     }
@@ -105,11 +105,11 @@ pub async fn restore_window_state(
 }
 
 #[tauri::command]
-pub async fn get_window_state(app: AppHandle) -> Result<WindowState, String> {
+pub async fn get_window_state(app: AppHandle) -> AppResult<WindowState> {
     if let Some(window) = app.get_webview_window("main") {
-        let position = window.outer_position().map_err(|e| e.to_string())?;
-        let size = window.outer_size().map_err(|e| e.to_string())?;
-        let maximized = window.is_maximized().map_err(|e| e.to_string())?;
+        let position = window.outer_position()?;
+        let size = window.outer_size()?;
+        let maximized = window.is_maximized()?;
 
         Ok(WindowState {
             x: position.x,
@@ -119,7 +119,7 @@ pub async fn get_window_state(app: AppHandle) -> Result<WindowState, String> {
             maximized,
         })
     } else {
-        Err("Main window not found".to_string())
+        Err(AppError::not_found("Main window not found"))
     }
 }
 
@@ -127,12 +127,11 @@ pub async fn get_window_state(app: AppHandle) -> Result<WindowState, String> {
 pub async fn reset_window_state(
     app: AppHandle,
     db: tauri::State<'_, crate::database::Database>,
-) -> Result<(), String> {
+) -> AppResult<()> {
     // Delete stored window state
-    let conn = db.conn().lock().map_err(|e| e.to_string())?;
+    let conn = db.conn().lock()?;
     conn.prepare("DELETE FROM settings WHERE key = 'window_state'")
-        .and_then(|mut stmt| stmt.execute([]))
-        .map_err(|e| e.to_string())?;
+        .and_then(|mut stmt| stmt.execute([]))?;
     drop(conn);
 
     // Reset to default position and size
@@ -140,15 +139,15 @@ pub async fn reset_window_state(
         let default_state = WindowState::default();
 
         let size = PhysicalSize::new(default_state.width, default_state.height);
-        window.set_size(size).map_err(|e| e.to_string())?;
+        window.set_size(size)?;
 
         let position = PhysicalPosition::new(default_state.x, default_state.y);
-        window.set_position(position).map_err(|e| e.to_string())?;
+        window.set_position(position)?;
 
         if default_state.maximized {
-            window.maximize().map_err(|e| e.to_string())?;
+            window.maximize()?;
         } else {
-            window.unmaximize().map_err(|e| e.to_string())?;
+            window.unmaximize()?;
         }
     }
 
@@ -0,0 +1,178 @@
+// Fits a chat request into the model's context window. Leading system messages
+// and the newest message are always kept; older turns are dropped oldest first
+// and, when the conversation has a rolling summary, replaced by that summary.
+// A request whose newest message doesn't fit on its own is refused.
+
+use crate::commands::provider::ProviderMessage;
+use crate::provider_retry::{ProviderError, ProviderErrorKind};
+use crate::tokenizer::{count_message_tokens, message_tokens};
+
+/// Tokens left free for the reply when no setting overrides it
+pub const DEFAULT_COMPLETION_RESERVE: usize = 1024;
+
+#[derive(Debug)]
+pub struct BuiltContext {
+    pub messages: Vec<ProviderMessage>,
+    /// Estimated prompt tokens of `messages`
+    pub prompt_tokens: usize,
+    /// Number of conversation messages left out
+    pub dropped: usize,
+}
+
+fn summary_message(summary: &str) -> ProviderMessage {
+    ProviderMessage {
+        role: "system".to_string(),
+        content: format!("Summary of the earlier conversation:\n{}", summary),
+        ..Default::default()
+    }
+}
+
+/// Newest-first selection of `turns` that fits in `budget`, empty when even the
+/// newest turn doesn't fit. The selection is contiguous and doesn't start with a
+/// reply.
+fn fit_turns(provider: &str, model: &str, turns: &[ProviderMessage], budget: usize) -> usize {
+    let mut used = 0;
+    let mut kept = 0;
+    for turn in turns.iter().rev() {
+        let cost = message_tokens(provider, model, turn);
+        if used + cost > budget {
+            break;
+        }
+        used += cost;
+        kept += 1;
+    }
+
+    // An assistant reply without its prompt is confusing context; drop it too
+    while kept > 1 && turns[turns.len() - kept].role == "assistant" {
+        kept -= 1;
+    }
+    kept
+}
+
+pub fn build_context(
+    provider: &str,
+    model: &str,
+    messages: Vec<ProviderMessage>,
+    context_limit: usize,
+    completion_reserve: usize,
+    summary: Option<&str>,
+) -> Result<BuiltContext, ProviderError> {
+    let budget = context_limit.saturating_sub(completion_reserve);
+    let prefix_len = messages.iter().take_while(|m| m.role == "system").count();
+    let fixed = count_message_tokens(provider, model, &messages[..prefix_len]);
+    let turns = &messages[prefix_len..];
+
+    let available = budget.saturating_sub(fixed);
+    let mut kept = fit_turns(provider, model, turns, available);
+    if kept == 0 && !turns.is_empty() {
+        let needed = fixed + message_tokens(provider, model, &turns[turns.len() - 1]);
+        return Err(ProviderError::new(
+            ProviderErrorKind::ContextOverflow,
+            provider,
+            format!(
+                "the prompt needs {} tokens but {} allows {} with {} reserved for the reply",
+                needed, model, context_limit, completion_reserve
+            ),
+        ));
+    }
+    let mut summary_msg = None;
+
+    if kept < turns.len() {
+        if let Some(summary) = summary.filter(|s| !s.trim().is_empty()) {
+            let msg = summary_message(summary);
+            let cost = message_tokens(provider, model, &msg);
+            let with_summary = fit_turns(provider, model, turns, available.saturating_sub(cost));
+            if with_summary > 0 {
+                kept = with_summary;
+                summary_msg = Some(msg);
+            }
+        }
+    }
+
+    let dropped = turns.len() - kept;
+    let mut messages = messages;
+    let recent = messages.split_off(prefix_len + dropped);
+    messages.truncate(prefix_len);
+    messages.extend(summary_msg);
+    messages.extend(recent);
+
+    let prompt_tokens = count_message_tokens(provider, model, &messages);
+    Ok(BuiltContext {
+        messages,
+        prompt_tokens,
+        dropped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(role: &str, content: &str) -> ProviderMessage {
+        ProviderMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn conversation(turns: usize) -> Vec<ProviderMessage> {
+        let mut messages = vec![msg("system", "You are helpful.")];
+        for i in 0..turns {
+            messages.push(msg(
+                "user",
+                &format!("question number {} {}", i, "x ".repeat(40)),
+            ));
+            messages.push(msg(
+                "assistant",
+                &format!("answer number {} {}", i, "y ".repeat(40)),
+            ));
+        }
+        messages.push(msg("user", "latest question"));
+        messages
+    }
+
+    #[test]
+    fn keeps_everything_when_it_fits() {
+        let built = build_context("openai", "gpt-4", conversation(3), 8192, 1024, None).unwrap();
+        assert_eq!(built.dropped, 0);
+        assert_eq!(built.messages.len(), 8);
+    }
+
+    #[test]
+    fn drops_oldest_turns_and_keeps_system_and_latest() {
+        let built = build_context("openai", "gpt-4", conversation(10), 1500, 1024, None).unwrap();
+        assert!(built.dropped > 0);
+        assert!(built.prompt_tokens <= 1500 - 1024);
+        assert_eq!(built.messages[0].content, "You are helpful.");
+        assert_eq!(built.messages.last().unwrap().content, "latest question");
+        // The first kept turn is a prompt, not a dangling reply
+        assert_eq!(built.messages[1].role, "user");
+    }
+
+    #[test]
+    fn inserts_summary_for_dropped_turns() {
+        let built = build_context(
+            "openai",
+            "gpt-4",
+            conversation(10),
+            1500,
+            1024,
+            Some("Earlier they discussed questions."),
+        )
+        .unwrap();
+        assert!(built.dropped > 0);
+        assert_eq!(built.messages[1].role, "system");
+        assert!(built.messages[1].content.contains("Earlier they discussed"));
+        assert!(built.prompt_tokens <= 1500 - 1024);
+    }
+
+    #[test]
+    fn refuses_a_newest_message_that_cannot_fit() {
+        let mut messages = conversation(1);
+        messages.push(msg("user", &"word ".repeat(2000)));
+        let err =
+            build_context("openai", "gpt-4", messages, 1500, 1024, Some("summary")).unwrap_err();
+        assert_eq!(err.kind, ProviderErrorKind::ContextOverflow);
+    }
+}
@@ -0,0 +1,143 @@
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+
+/// An image or file attached to a message; the bytes live in the blob store
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Attachment {
+    pub id: String,
+    pub message_id: String,
+    /// `image` or `file`
+    pub kind: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size_bytes: i64,
+    /// Hex digest of the contents, which is also the blob store key
+    pub sha256: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewAttachment {
+    pub message_id: String,
+    pub kind: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size_bytes: i64,
+    pub sha256: String,
+}
+
+const SELECT_COLUMNS: &str =
+    "id, message_id, kind, file_name, mime_type, size_bytes, sha256, created_at";
+
+impl Attachment {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Attachment {
+            id: row.get(0)?,
+            message_id: row.get(1)?,
+            kind: row.get(2)?,
+            file_name: row.get(3)?,
+            mime_type: row.get(4)?,
+            size_bytes: row.get(5)?,
+            sha256: row.get(6)?,
+            created_at: row.get(7)?,
+        })
+    }
+
+    pub fn create(conn: &Connection, new: NewAttachment) -> Result<Self> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = chrono::Utc::now().timestamp();
+        conn.execute(
+            "INSERT INTO attachments (id, message_id, kind, file_name, mime_type, size_bytes, sha256, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                &id,
+                &new.message_id,
+                &new.kind,
+                &new.file_name,
+                &new.mime_type,
+                new.size_bytes,
+                &new.sha256,
+                now
+            ],
+        )?;
+        Ok(Attachment {
+            id,
+            message_id: new.message_id,
+            kind: new.kind,
+            file_name: new.file_name,
+            mime_type: new.mime_type,
+            size_bytes: new.size_bytes,
+            sha256: new.sha256,
+            created_at: now,
+        })
+    }
+
+    pub fn get_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
+        conn.query_row(
+            &format!("SELECT {} FROM attachments WHERE id = ?1", SELECT_COLUMNS),
+            params![id],
+            Self::from_row,
+        )
+        .optional()
+    }
+
+    pub fn get_by_message(conn: &Connection, message_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM attachments WHERE message_id = ?1 ORDER BY created_at ASC, rowid ASC",
+            SELECT_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![message_id], Self::from_row)?;
+        rows.collect()
+    }
+
+    /// All attachments of a conversation's messages, in message order
+    pub fn get_by_conversation(conn: &Connection, conversation_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT a.id, a.message_id, a.kind, a.file_name, a.mime_type, a.size_bytes, a.sha256, a.created_at
+             FROM attachments a JOIN messages m ON m.id = a.message_id
+             WHERE m.conversation_id = ?1
+             ORDER BY m.timestamp ASC, a.created_at ASC, a.rowid ASC",
+        )?;
+        let rows = stmt.query_map(params![conversation_id], Self::from_row)?;
+        rows.collect()
+    }
+
+    /// Copy a message's attachments onto another message. Blobs are keyed by
+    /// their contents, so the copies share them.
+    pub fn copy_to_message(
+        conn: &Connection,
+        from_message_id: &str,
+        to_message_id: &str,
+    ) -> Result<Vec<Self>> {
+        Self::get_by_message(conn, from_message_id)?
+            .into_iter()
+            .map(|attachment| {
+                Self::create(
+                    conn,
+                    NewAttachment {
+                        message_id: to_message_id.to_string(),
+                        kind: attachment.kind,
+                        file_name: attachment.file_name,
+                        mime_type: attachment.mime_type,
+                        size_bytes: attachment.size_bytes,
+                        sha256: attachment.sha256,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    pub fn delete(conn: &Connection, id: &str) -> Result<()> {
+        conn.execute("DELETE FROM attachments WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Number of attachments still pointing at a blob
+    pub fn count_by_sha256(conn: &Connection, sha256: &str) -> Result<i64> {
+        conn.query_row(
+            "SELECT COUNT(*) FROM attachments WHERE sha256 = ?1",
+            params![sha256],
+            |row| row.get(0),
+        )
+    }
+}
@@ -1,4 +1,4 @@
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -13,6 +13,18 @@ pub struct Conversation {
     pub system_prompt: Option<String>,
     pub parent_conversation_id: Option<String>,
     pub branch_point_message_id: Option<String>,
+    /// Project root this conversation is tied to for retrieval-augmented answers
+    pub project_root: Option<String>,
+    /// Last message of the currently selected path through the message tree
+    pub active_leaf_id: Option<String>,
+    /// Rolling summary of the active path, maintained by the background summarizer
+    pub summary: Option<String>,
+    /// Last message covered by `summary`
+    pub summary_message_id: Option<String>,
+    /// Profile active when the conversation was created
+    pub profile_id: Option<String>,
+    /// Workspace template the conversation was started from
+    pub template_id: Option<String>,
     // Note: 'deleted' and 'deleted_at' are stored in DB but are not exposed to the API struct
 }
 
@@ -33,19 +45,50 @@ pub struct NewConversationWithId {
     pub system_prompt: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
+    pub profile_id: Option<String>,
+    pub template_id: Option<String>,
 }
 
+const SELECT_COLUMNS: &str = "id, title, created_at, updated_at, model, provider, system_prompt, parent_conversation_id, branch_point_message_id, project_root, active_leaf_id, summary, summary_message_id, profile_id, template_id";
+
 impl Conversation {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Conversation {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            created_at: row.get(2)?,
+            updated_at: row.get(3)?,
+            model: row.get(4)?,
+            provider: row.get(5)?,
+            system_prompt: row.get(6)?,
+            parent_conversation_id: row.get(7)?,
+            branch_point_message_id: row.get(8)?,
+            project_root: row.get(9)?,
+            active_leaf_id: row.get(10)?,
+            summary: row.get(11)?,
+            summary_message_id: row.get(12)?,
+            profile_id: row.get(13)?,
+            template_id: row.get(14)?,
+        })
+    }
+
     pub fn create(conn: &Connection, new_conv: NewConversation) -> Result<Self> {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs() as i64;
         let id = uuid::Uuid::new_v4().to_string();
+        let profile_id: Option<String> = conn
+            .query_row(
+                "SELECT id FROM profiles WHERE is_active = 1 LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
         conn.execute(
-            "INSERT INTO conversations (id, title, created_at, updated_at, model, provider, system_prompt, parent_conversation_id, branch_point_message_id)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, NULL, NULL)",
-            params![&id, &new_conv.title, now, now, &new_conv.model, &new_conv.provider, &new_conv.system_prompt],
+            "INSERT INTO conversations (id, title, created_at, updated_at, model, provider, system_prompt, parent_conversation_id, branch_point_message_id, profile_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, NULL, NULL, ?8)",
+            params![&id, &new_conv.title, now, now, &new_conv.model, &new_conv.provider, &new_conv.system_prompt, &profile_id],
         )?;
 
         Ok(Conversation {
@@ -58,14 +101,20 @@ impl Conversation {
             system_prompt: new_conv.system_prompt,
             parent_conversation_id: None,
             branch_point_message_id: None,
+            project_root: None,
+            active_leaf_id: None,
+            summary: None,
+            summary_message_id: None,
+            profile_id,
+            template_id: None,
         })
     }
 
     pub fn create_with_id(conn: &Connection, new_conv: NewConversationWithId) -> Result<Self> {
         conn.execute(
-            "INSERT INTO conversations (id, title, created_at, updated_at, model, provider, system_prompt, parent_conversation_id, branch_point_message_id)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, NULL, NULL)",
-            params![&new_conv.id, &new_conv.title, new_conv.created_at, new_conv.updated_at, &new_conv.model, &new_conv.provider, &new_conv.system_prompt],
+            "INSERT INTO conversations (id, title, created_at, updated_at, model, provider, system_prompt, parent_conversation_id, branch_point_message_id, profile_id, template_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, NULL, NULL, ?8, ?9)",
+            params![&new_conv.id, &new_conv.title, new_conv.created_at, new_conv.updated_at, &new_conv.model, &new_conv.provider, &new_conv.system_prompt, &new_conv.profile_id, &new_conv.template_id],
         )?;
 
         Ok(Conversation {
@@ -78,45 +127,66 @@ impl Conversation {
             system_prompt: new_conv.system_prompt,
             parent_conversation_id: None,
             branch_point_message_id: None,
+            project_root: None,
+            active_leaf_id: None,
+            summary: None,
+            summary_message_id: None,
+            profile_id: new_conv.profile_id,
+            template_id: new_conv.template_id,
         })
     }
 
+    /// Reset an existing conversation to `new_conv`, permanently removing its
+    /// messages (attachments cascade) and tag links. The row is kept so
+    /// branches pointing at it stay linked.
+    pub fn overwrite(conn: &Connection, new_conv: NewConversationWithId) -> Result<Self> {
+        conn.execute(
+            "DELETE FROM messages WHERE conversation_id = ?1",
+            params![&new_conv.id],
+        )?;
+        conn.execute(
+            "DELETE FROM conversation_tags WHERE conversation_id = ?1",
+            params![&new_conv.id],
+        )?;
+        conn.execute(
+            "UPDATE conversations SET title = ?2, created_at = ?3, updated_at = ?4, model = ?5, provider = ?6, system_prompt = ?7,
+                 profile_id = ?8, template_id = ?9, parent_conversation_id = NULL, branch_point_message_id = NULL,
+                 deleted = 0, deleted_at = NULL, active_leaf_id = NULL, summary = NULL, summary_message_id = NULL
+             WHERE id = ?1",
+            params![&new_conv.id, &new_conv.title, new_conv.created_at, new_conv.updated_at, &new_conv.model, &new_conv.provider, &new_conv.system_prompt, &new_conv.profile_id, &new_conv.template_id],
+        )?;
+        Self::get_by_id(conn, &new_conv.id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)
+    }
+
+    /// Whether the id is in use, counting soft-deleted conversations
+    pub fn id_exists(conn: &Connection, id: &str) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM conversations WHERE id = ?1)",
+            params![id],
+            |row| row.get(0),
+        )
+    }
+
     pub fn get_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
         // Only return non-deleted conversations
-        let mut stmt = conn.prepare("SELECT id, title, created_at, updated_at, model, provider, system_prompt, parent_conversation_id, branch_point_message_id FROM conversations WHERE id = ?1 AND deleted = 0")?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM conversations WHERE id = ?1 AND deleted = 0",
+            SELECT_COLUMNS
+        ))?;
         let mut rows = stmt.query(params![id])?;
         if let Some(row) = rows.next()? {
-            Ok(Some(Conversation {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-                model: row.get(4)?,
-                provider: row.get(5)?,
-                system_prompt: row.get(6)?,
-                parent_conversation_id: row.get(7)?,
-                branch_point_message_id: row.get(8)?,
-            }))
+            Ok(Some(Self::from_row(row)?))
         } else {
             Ok(None)
         }
     }
 
     pub fn get_all(conn: &Connection, limit: i64) -> Result<Vec<Self>> {
-        let mut stmt = conn.prepare("SELECT id, title, created_at, updated_at, model, provider, system_prompt, parent_conversation_id, branch_point_message_id FROM conversations WHERE deleted = 0 ORDER BY updated_at DESC LIMIT ?1")?;
-        let conversations = stmt.query_map(params![limit], |row| {
-            Ok(Conversation {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-                model: row.get(4)?,
-                provider: row.get(5)?,
-                system_prompt: row.get(6)?,
-                parent_conversation_id: row.get(7)?,
-                branch_point_message_id: row.get(8)?,
-            })
-        })?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM conversations WHERE deleted = 0 ORDER BY updated_at DESC LIMIT ?1",
+            SELECT_COLUMNS
+        ))?;
+        let conversations = stmt.query_map(params![limit], Self::from_row)?;
         conversations.collect()
     }
 
@@ -132,6 +202,52 @@ impl Conversation {
         Ok(())
     }
 
+    pub fn set_project_root(conn: &Connection, id: &str, project_root: Option<&str>) -> Result<()> {
+        conn.execute(
+            "UPDATE conversations SET project_root = ?1 WHERE id = ?2",
+            params![project_root, id],
+        )?;
+        Ok(())
+    }
+
+    /// Store a new rolling summary. Doesn't touch `updated_at` so background
+    /// summarization doesn't reorder the conversation list.
+    pub fn set_summary(
+        conn: &Connection,
+        id: &str,
+        summary: Option<&str>,
+        summary_message_id: Option<&str>,
+    ) -> Result<()> {
+        conn.execute(
+            "UPDATE conversations SET summary = ?1, summary_message_id = ?2 WHERE id = ?3",
+            params![summary, summary_message_id, id],
+        )?;
+        Ok(())
+    }
+
+    /// Record the workspace template a conversation was started from
+    pub fn set_template(conn: &Connection, id: &str, template_id: Option<&str>) -> Result<()> {
+        conn.execute(
+            "UPDATE conversations SET template_id = ?1 WHERE id = ?2",
+            params![template_id, id],
+        )?;
+        Ok(())
+    }
+
+    /// Link a conversation to the conversation and message it branched from
+    pub fn set_branch_origin(
+        conn: &Connection,
+        id: &str,
+        parent_conversation_id: Option<&str>,
+        branch_point_message_id: Option<&str>,
+    ) -> Result<()> {
+        conn.execute(
+            "UPDATE conversations SET parent_conversation_id = ?1, branch_point_message_id = ?2 WHERE id = ?3",
+            params![parent_conversation_id, branch_point_message_id, id],
+        )?;
+        Ok(())
+    }
+
     pub fn touch(conn: &Connection, id: &str) -> Result<()> {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -171,20 +287,8 @@ impl Conversation {
 
     pub fn search(conn: &Connection, query: &str, limit: i64) -> Result<Vec<Self>> {
         let search_pattern = format!("%{}%", query);
-        let mut stmt = conn.prepare("SELECT id, title, created_at, updated_at, model, provider, system_prompt, parent_conversation_id, branch_point_message_id FROM conversations WHERE deleted = 0 AND title LIKE ?1 ORDER BY updated_at DESC LIMIT ?2")?;
-        let conversations = stmt.query_map(params![search_pattern, limit], |row| {
-            Ok(Conversation {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-                model: row.get(4)?,
-                provider: row.get(5)?,
-                system_prompt: row.get(6)?,
-                parent_conversation_id: row.get(7)?,
-                branch_point_message_id: row.get(8)?,
-            })
-        })?;
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM conversations WHERE deleted = 0 AND title LIKE ?1 ORDER BY updated_at DESC LIMIT ?2", SELECT_COLUMNS))?;
+        let conversations = stmt.query_map(params![search_pattern, limit], Self::from_row)?;
         conversations.collect()
     }
 
@@ -206,8 +310,8 @@ impl Conversation {
         let id = uuid::Uuid::new_v4().to_string();
 
         conn.execute(
-            "INSERT INTO conversations (id, title, created_at, updated_at, model, provider, system_prompt, parent_conversation_id, branch_point_message_id)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            "INSERT INTO conversations (id, title, created_at, updated_at, model, provider, system_prompt, parent_conversation_id, branch_point_message_id, project_root, profile_id, template_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
             params![
                 &id,
                 &title,
@@ -217,25 +321,37 @@ impl Conversation {
                 &parent.provider,
                 &parent.system_prompt,
                 parent_conversation_id,
-                branch_point_message_id
+                branch_point_message_id,
+                &parent.project_root,
+                &parent.profile_id,
+                &parent.template_id
             ],
         )?;
 
-        // Copy all messages from parent up to the branch point
+        // Copy the path from the root down to the branch point, re-linking
+        // parents and keeping attachments
+        let path = super::messages::Message::get_path_to(conn, branch_point_message_id)?;
+        let mut previous_id: Option<String> = None;
+        for message in path {
+            let new_id = uuid::Uuid::new_v4().to_string();
+            super::messages::Message::create_with_id(
+                conn,
+                super::messages::NewMessageWithId {
+                    id: new_id.clone(),
+                    conversation_id: id.clone(),
+                    role: message.role,
+                    content: message.content,
+                    timestamp: message.timestamp,
+                    tokens_used: message.tokens_used,
+                    parent_message_id: previous_id.take(),
+                },
+            )?;
+            super::attachments::Attachment::copy_to_message(conn, &message.id, &new_id)?;
+            previous_id = Some(new_id);
+        }
         conn.execute(
-            "INSERT INTO messages (id, conversation_id, role, content, timestamp, tokens_used)
-             SELECT 
-                lower(hex(randomblob(16))),
-                ?1,
-                role,
-                content,
-                timestamp,
-                tokens_used
-             FROM messages 
-             WHERE conversation_id = ?2 
-               AND timestamp <= (SELECT timestamp FROM messages WHERE id = ?3)
-             ORDER BY timestamp",
-            params![&id, parent_conversation_id, branch_point_message_id],
+            "UPDATE conversations SET active_leaf_id = ?1 WHERE id = ?2",
+            params![&previous_id, &id],
         )?;
 
         Ok(Conversation {
@@ -248,25 +364,64 @@ impl Conversation {
             system_prompt: parent.system_prompt,
             parent_conversation_id: Some(parent_conversation_id.to_string()),
             branch_point_message_id: Some(branch_point_message_id.to_string()),
+            project_root: parent.project_root,
+            active_leaf_id: previous_id,
+            summary: None,
+            summary_message_id: None,
+            profile_id: parent.profile_id,
+            template_id: parent.template_id,
         })
     }
 
+    /// Append copies of selected messages (with their attachments) from one
+    /// conversation onto the active path of another, in the source's order.
+    /// Returns the appended copies.
+    pub fn merge_messages(
+        conn: &Connection,
+        source_conversation_id: &str,
+        target_conversation_id: &str,
+        message_ids: &[String],
+    ) -> Result<Vec<super::messages::Message>> {
+        Self::get_by_id(conn, target_conversation_id)?
+            .ok_or_else(|| rusqlite::Error::InvalidPath("Target conversation not found".into()))?;
+
+        let source = super::messages::Message::get_tree(conn, source_conversation_id)?;
+        for id in message_ids {
+            if !source.iter().any(|m| &m.id == id) {
+                return Err(rusqlite::Error::InvalidPath(
+                    format!("Message {} is not part of the source conversation", id).into(),
+                ));
+            }
+        }
+
+        let tx = conn.unchecked_transaction()?;
+        let mut parent =
+            super::messages::Message::get_active_leaf(&tx, target_conversation_id)?.map(|m| m.id);
+        let mut merged = Vec::new();
+        for message in source.into_iter().filter(|m| message_ids.contains(&m.id)) {
+            let copy = super::messages::Message::create_with_parent(
+                &tx,
+                super::messages::NewMessage {
+                    conversation_id: target_conversation_id.to_string(),
+                    role: message.role,
+                    content: message.content,
+                    tokens_used: message.tokens_used,
+                },
+                parent.as_deref(),
+            )?;
+            super::attachments::Attachment::copy_to_message(&tx, &message.id, &copy.id)?;
+            parent = Some(copy.id.clone());
+            merged.push(copy);
+        }
+        tx.commit()?;
+
+        Ok(merged)
+    }
+
     // Get all branches of a conversation
     pub fn get_branches(conn: &Connection, conversation_id: &str) -> Result<Vec<Self>> {
-        let mut stmt = conn.prepare("SELECT id, title, created_at, updated_at, model, provider, system_prompt, parent_conversation_id, branch_point_message_id FROM conversations WHERE parent_conversation_id = ?1 AND deleted = 0 ORDER BY created_at DESC")?;
-        let conversations = stmt.query_map(params![conversation_id], |row| {
-            Ok(Conversation {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-                model: row.get(4)?,
-                provider: row.get(5)?,
-                system_prompt: row.get(6)?,
-                parent_conversation_id: row.get(7)?,
-                branch_point_message_id: row.get(8)?,
-            })
-        })?;
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM conversations WHERE parent_conversation_id = ?1 AND deleted = 0 ORDER BY created_at DESC", SELECT_COLUMNS))?;
+        let conversations = stmt.query_map(params![conversation_id], Self::from_row)?;
         conversations.collect()
     }
 }
@@ -0,0 +1,472 @@
+// At-rest encryption of the database with SQLCipher. The key is either 32
+// random bytes kept in the system keyring or a passphrase entered when the app
+// starts. Which one is recorded in `encryption.json` beside the database,
+// since nothing inside an encrypted database can be read before its key is
+// known. Enabling, disabling and changing the key rewrite the database file in
+// place. While unlocked the key is kept for those rewrites; locking closes the
+// connection, and with it SQLCipher's copy, and wipes ours.
+
+use super::{schema, ConnectionState, Database};
+use crate::error::{AppError, AppResult, ErrorCode};
+use ring::rand::{SecureRandom, SystemRandom};
+use rusqlite::backup::{Backup, Progress};
+use rusqlite::{params, Connection, DatabaseName};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::MutexGuard;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use zeroize::Zeroizing;
+
+const SETTINGS_FILE: &str = "encryption.json";
+/// Keyring entry holding the raw key of a keyring-encrypted database
+const KEYRING_ENTRY: &str = "database-key";
+/// First bytes of every plaintext SQLite database
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+/// How often the idle lock checks for inactivity
+const IDLE_POLL: Duration = Duration::from_secs(30);
+
+/// Where the key of an encrypted database comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    /// A random key in the system keyring; unlocks without asking
+    Keyring,
+    /// A passphrase entered when the app starts
+    Passphrase,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptionSettings {
+    /// Unset while the database is plaintext
+    pub key_source: Option<KeySource>,
+    /// Minutes without use after which an encrypted database locks; 0 never
+    pub idle_lock_minutes: u32,
+}
+
+impl EncryptionSettings {
+    fn path(db_path: &Path) -> PathBuf {
+        db_path.with_file_name(SETTINGS_FILE)
+    }
+
+    /// Settings of the database at `db_path`; plaintext when there are none
+    pub fn load(db_path: &Path) -> AppResult<Self> {
+        match fs::read(Self::path(db_path)) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(AppError::from(e).context("Failed to read encryption settings")),
+        }
+    }
+
+    pub fn save(&self, db_path: &Path) -> AppResult<()> {
+        let path = Self::path(db_path);
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.persist(&path)
+            .map_err(|e| AppError::from(e.error).context("Failed to save encryption settings"))?;
+        Ok(())
+    }
+}
+
+/// Key of an encrypted database as given to `PRAGMA key`; wiped from memory
+/// when dropped
+#[derive(Clone)]
+pub struct DatabaseKey(Zeroizing<String>);
+
+impl DatabaseKey {
+    pub fn passphrase(passphrase: String) -> AppResult<Self> {
+        let passphrase = Zeroizing::new(passphrase);
+        if passphrase.is_empty() {
+            return Err(AppError::invalid_input("The passphrase can't be empty"));
+        }
+        Ok(DatabaseKey(passphrase))
+    }
+
+    /// A random 256-bit key in SQLCipher's raw key syntax (`x'…'`), which
+    /// skips key derivation
+    pub fn generate() -> AppResult<Self> {
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        SystemRandom::new()
+            .fill(&mut bytes[..])
+            .map_err(|_| AppError::internal("No randomness available for the database key"))?;
+        // Sized up front so no reallocation leaves a copy behind
+        let mut key = Zeroizing::new(String::with_capacity(2 * bytes.len() + 3));
+        key.push_str("x'");
+        for byte in bytes.iter() {
+            let _ = write!(key, "{:02X}", byte);
+        }
+        key.push('\'');
+        Ok(DatabaseKey(key))
+    }
+
+    pub fn from_keyring() -> AppResult<Self> {
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        {
+            let entry = keyring::Entry::new("linux-ai-assistant", KEYRING_ENTRY)
+                .map_err(|e| AppError::from(e).context("keyring entry error"))?;
+            let key = entry
+                .get_password()
+                .map_err(|e| AppError::from(e).context("The database key isn't in the keyring"))?;
+            return Ok(DatabaseKey(Zeroizing::new(key)));
+        }
+        #[allow(unreachable_code)]
+        Err(AppError::unsupported(
+            "keyring unsupported on this platform",
+        ))
+    }
+
+    pub fn save_to_keyring(&self) -> AppResult<()> {
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        {
+            let entry = keyring::Entry::new("linux-ai-assistant", KEYRING_ENTRY)
+                .map_err(|e| AppError::from(e).context("keyring entry error"))?;
+            entry
+                .set_password(&self.0)
+                .map_err(|e| AppError::from(e).context("keyring set failed"))?;
+            return Ok(());
+        }
+        #[allow(unreachable_code)]
+        Err(AppError::unsupported(
+            "keyring unsupported on this platform",
+        ))
+    }
+
+    pub fn remove_from_keyring() -> AppResult<()> {
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        {
+            let entry = keyring::Entry::new("linux-ai-assistant", KEYRING_ENTRY)
+                .map_err(|e| AppError::from(e).context("keyring entry error"))?;
+            return match entry.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(AppError::from(e).context("keyring delete failed")),
+            };
+        }
+        #[allow(unreachable_code)]
+        Ok(())
+    }
+
+    pub(super) fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Whether the file at `path` is a database that isn't plaintext SQLite
+fn is_encrypted_file(path: &Path) -> bool {
+    let mut header = [0u8; SQLITE_HEADER.len()];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| &header != SQLITE_HEADER)
+}
+
+/// Open the database at `path` the way its settings say: plaintext, unlocked
+/// with the key from the keyring, or locked until the passphrase is entered.
+/// An encrypted file without settings is treated as passphrase-encrypted.
+pub fn open_database(path: PathBuf, settings: &EncryptionSettings) -> AppResult<Database> {
+    let source = settings
+        .key_source
+        .or_else(|| is_encrypted_file(&path).then_some(KeySource::Passphrase));
+    match source {
+        None => Ok(Database::new(path)?),
+        Some(KeySource::Passphrase) => Ok(Database::locked(path)),
+        Some(KeySource::Keyring) => {
+            let db = Database::locked(path);
+            // Stays locked if the keyring can't provide the key; unlocking
+            // again reports why
+            if let Err(e) = DatabaseKey::from_keyring().and_then(|key| db.unlock(key)) {
+                eprintln!("database: unlocking with the keyring failed: {}", e);
+            }
+            Ok(db)
+        }
+    }
+}
+
+fn wrong_key(err: rusqlite::Error) -> AppError {
+    match err.sqlite_error_code() {
+        Some(rusqlite::ErrorCode::NotADatabase) => {
+            AppError::auth("Wrong passphrase for the database")
+        }
+        _ => err.into(),
+    }
+}
+
+/// Copy the database open in `conn` into a new file at `dest`, encrypted with
+/// `key` or plaintext
+fn export(conn: &Connection, dest: &Path, key: Option<&DatabaseKey>) -> AppResult<()> {
+    conn.execute(
+        "ATTACH DATABASE ?1 AS migrated KEY ?2",
+        params![dest.to_string_lossy(), key.map_or("", |key| key.as_str())],
+    )?;
+    let exported = conn.query_row("SELECT sqlcipher_export('migrated')", [], |_| Ok(()));
+    conn.execute("DETACH DATABASE migrated", [])?;
+    Ok(exported?)
+}
+
+impl Database {
+    fn state(&self) -> AppResult<MutexGuard<'_, ConnectionState>> {
+        Ok(self.conn.state.lock()?)
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.state().map_or(true, |state| state.conn.is_none())
+    }
+
+    /// Whether the database is encrypted; a locked one always is
+    pub fn is_encrypted(&self) -> bool {
+        self.state()
+            .map_or(true, |state| state.conn.is_none() || state.key.is_some())
+    }
+
+    /// Open an encrypted database with `key`
+    pub fn unlock(&self, key: DatabaseKey) -> AppResult<()> {
+        let mut state = self.state()?;
+        if state.conn.is_none() {
+            let conn = Database::open_connection(&self.path, Some(&key)).map_err(wrong_key)?;
+            state.conn = Some(conn);
+            state.key = Some(key);
+        }
+        drop(state);
+        *self.conn.last_used.lock()? = Instant::now();
+        Ok(())
+    }
+
+    /// Close the connection of an encrypted database and wipe its key from
+    /// memory until `unlock`
+    pub fn lock(&self) -> AppResult<()> {
+        let mut state = self.state()?;
+        if state.conn.is_some() && state.key.is_none() {
+            return Err(AppError::invalid_input("The database isn't encrypted"));
+        }
+        state.conn = None;
+        state.key = None;
+        Ok(())
+    }
+
+    /// Encrypt the database with `key`, or decrypt it with `None`, rewriting
+    /// the file in place. The database must be unlocked.
+    pub fn set_key(&self, key: Option<DatabaseKey>) -> AppResult<()> {
+        let mut state = self.state()?;
+        let conn = state.conn.as_ref().ok_or_else(|| {
+            AppError::locked("Unlock the database before changing its encryption")
+        })?;
+        match (&state.key, &key) {
+            (None, None) => return Ok(()),
+            (Some(_), Some(new)) => {
+                conn.pragma_update(None, "rekey", new.as_str())?;
+                state.key = key;
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".migrating");
+        let migrated = self.path.with_file_name(name);
+        let _ = fs::remove_file(&migrated);
+        if let Err(e) = export(conn, &migrated, key.as_ref()) {
+            let _ = fs::remove_file(&migrated);
+            return Err(e.context("Failed to rewrite the database"));
+        }
+
+        state.conn = None;
+        if let Err(e) = fs::rename(&migrated, &self.path) {
+            let _ = fs::remove_file(&migrated);
+            state.conn = Some(Database::open_connection(&self.path, state.key.as_ref())?);
+            return Err(AppError::from(e).context("Failed to replace the database"));
+        }
+        // The file is under the new key from here on, so keep that key even
+        // if reopening fails
+        state.key = key;
+        state.conn = Some(Database::open_connection(&self.path, state.key.as_ref())?);
+        Ok(())
+    }
+
+    /// Whether the database file is encrypted with `key`, or plaintext for
+    /// `None`. Tells whether a failed `set_key` got as far as rewriting it.
+    pub fn is_keyed_with(&self, key: Option<&DatabaseKey>) -> bool {
+        self.state().is_ok_and(|state| {
+            state.key.as_ref().map(DatabaseKey::as_str) == key.map(DatabaseKey::as_str)
+        })
+    }
+
+    /// Copy the database into a new plaintext file at `dest`. SQLite's backup
+    /// API copies plaintext databases while they are in use; it can't copy an
+    /// encrypted one into plaintext, so those are exported instead.
+    pub fn snapshot(&self, dest: &Path) -> AppResult<()> {
+        let guard = self.conn.lock_in_background()?;
+        if guard.0.key.is_some() {
+            export(&guard, dest, None)
+        } else {
+            Ok(guard.backup(DatabaseName::Main, dest, None)?)
+        }
+    }
+
+    /// Replace the contents of the database with the plaintext database at
+    /// `src`, keeping the current encryption, and bring the schema up to date
+    pub fn replace_with(&self, src: &Path) -> AppResult<()> {
+        let mut guard = self.conn.lock_in_background()?;
+        match guard.0.key.clone() {
+            None => guard.restore(DatabaseName::Main, src, None::<fn(Progress)>)?,
+            Some(key) => {
+                // The backup API only copies between two plaintext or two
+                // encrypted databases, so encrypt a copy first. It is removed
+                // when dropped, after `source` closes it.
+                let encrypted =
+                    tempfile::NamedTempFile::new_in(src.parent().unwrap_or(Path::new(".")))?;
+                export(&Connection::open(src)?, encrypted.path(), Some(&key))?;
+                let source = Connection::open(encrypted.path())?;
+                source.pragma_update(None, "key", key.as_str())?;
+                Backup::new(&source, &mut guard)?.run_to_completion(256, Duration::ZERO, None)?;
+            }
+        }
+        schema::create_tables(&guard)?;
+        Ok(())
+    }
+}
+
+/// Lock an encrypted database once it has gone unused for the configured
+/// time. Emits `database://locked`.
+pub fn start_idle_lock(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(IDLE_POLL);
+        let db = app.state::<Database>();
+        let minutes = match EncryptionSettings::load(db.path()) {
+            Ok(settings) if settings.key_source.is_some() => settings.idle_lock_minutes,
+            _ => 0,
+        };
+        if minutes == 0
+            || db.is_locked()
+            || db.conn().idle() < Duration::from_secs(u64::from(minutes) * 60)
+        {
+            continue;
+        }
+        match db.lock() {
+            Ok(()) => {
+                let _ = app.emit("database://locked", ());
+            }
+            Err(e) if e.code == ErrorCode::InvalidInput => {}
+            Err(e) => eprintln!("database: idle lock failed: {}", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::conversations::{Conversation, NewConversation};
+
+    fn header(path: &Path) -> Vec<u8> {
+        fs::read(path).unwrap()[..SQLITE_HEADER.len()].to_vec()
+    }
+
+    fn create(db: &Database, title: &str) -> Conversation {
+        Conversation::create(
+            &db.conn().lock().unwrap(),
+            NewConversation {
+                title: title.to_string(),
+                model: "llama3".to_string(),
+                provider: "ollama".to_string(),
+                system_prompt: None,
+            },
+        )
+        .unwrap()
+    }
+
+    fn titles(db: &Database) -> Vec<String> {
+        Conversation::get_all(&db.conn().lock().unwrap(), 10)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.title)
+            .collect()
+    }
+
+    #[test]
+    fn encryption_is_enabled_changed_and_disabled_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("database.db");
+        let db = Database::new(path.clone()).unwrap();
+        create(&db, "Secret plans");
+
+        let passphrase = || DatabaseKey::passphrase("open sesame".to_string()).unwrap();
+        db.set_key(Some(passphrase())).unwrap();
+        assert_ne!(header(&path), SQLITE_HEADER);
+        assert_eq!(titles(&db), vec!["Secret plans"]);
+        assert!(!fs::read_dir(dir.path()).unwrap().any(|e| e
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .ends_with(".migrating")));
+
+        db.lock().unwrap();
+        assert!(db.is_locked());
+        assert_eq!(db.conn().lock().err().unwrap().code, ErrorCode::Locked);
+        let wrong = DatabaseKey::passphrase("open barley".to_string()).unwrap();
+        assert_eq!(db.unlock(wrong).err().unwrap().code, ErrorCode::Auth);
+        db.unlock(passphrase()).unwrap();
+        assert_eq!(titles(&db), vec!["Secret plans"]);
+
+        // Without settings, an encrypted file waits for its passphrase
+        let reopened = open_database(path.clone(), &EncryptionSettings::default()).unwrap();
+        assert!(reopened.is_locked());
+        drop(reopened);
+
+        let raw = DatabaseKey::generate().unwrap();
+        assert_eq!(raw.as_str().len(), 67);
+        assert!(db.is_keyed_with(Some(&passphrase())));
+        db.set_key(Some(raw.clone())).unwrap();
+        assert!(db.is_keyed_with(Some(&raw)));
+        assert!(!db.is_keyed_with(None));
+        drop(db);
+        let db = Database::locked(path.clone());
+        db.unlock(raw).unwrap();
+        assert_eq!(titles(&db), vec!["Secret plans"]);
+
+        db.set_key(None).unwrap();
+        assert_eq!(header(&path), SQLITE_HEADER);
+        assert!(db.lock().is_err());
+        drop(db);
+        assert_eq!(titles(&Database::new(path).unwrap()), vec!["Secret plans"]);
+    }
+
+    #[test]
+    fn encrypted_databases_snapshot_to_plaintext_and_restore_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("database.db");
+        let db = Database::new(path.clone()).unwrap();
+        create(&db, "Kept");
+        db.set_key(Some(DatabaseKey::generate().unwrap())).unwrap();
+
+        let snapshot = dir.path().join("snapshot.db");
+        db.snapshot(&snapshot).unwrap();
+        assert_eq!(header(&snapshot), SQLITE_HEADER);
+
+        create(&db, "Added after the snapshot");
+        assert_eq!(titles(&db).len(), 2);
+        db.replace_with(&snapshot).unwrap();
+        assert_eq!(titles(&db), vec!["Kept"]);
+        assert_ne!(header(&path), SQLITE_HEADER);
+
+        // No copy encrypted with the live key is left behind, even on failure
+        let junk = dir.path().join("junk.db");
+        fs::write(&junk, "not a database").unwrap();
+        assert!(db.replace_with(&junk).is_err());
+        let mut names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["database.db", "junk.db", "snapshot.db"]);
+        assert_eq!(titles(&db), vec!["Kept"]);
+
+        let settings = EncryptionSettings {
+            key_source: Some(KeySource::Keyring),
+            idle_lock_minutes: 15,
+        };
+        settings.save(&path).unwrap();
+        assert_eq!(EncryptionSettings::load(&path).unwrap(), settings);
+    }
+}
@@ -1,5 +1,6 @@
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -10,6 +11,30 @@ pub struct Message {
     pub content: String,
     pub timestamp: i64,
     pub tokens_used: Option<i64>,
+    /// Previous message in the conversation tree (None for a root message)
+    pub parent_message_id: Option<String>,
+    /// Tokens sent to the provider to produce this message (assistant messages)
+    pub prompt_tokens: Option<i64>,
+    /// Tokens generated for this message (assistant messages)
+    pub completion_tokens: Option<i64>,
+    /// Provider and model that produced this message (assistant messages)
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// Cost in USD from the pricing table at the time of the request
+    pub cost_usd: Option<f64>,
+}
+
+/// Provider usage recorded for an assistant message
+#[derive(Debug, Clone)]
+pub struct UsageRecord {
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub provider: String,
+    pub model: String,
+    /// Profile active when the request was made
+    pub profile_id: Option<String>,
+    /// None when the model has no price
+    pub cost_usd: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,19 +53,51 @@ pub struct NewMessageWithId {
     pub content: String,
     pub timestamp: i64,
     pub tokens_used: Option<i64>,
+    pub parent_message_id: Option<String>,
 }
 
+const SELECT_COLUMNS: &str = "id, conversation_id, role, content, timestamp, tokens_used, parent_message_id, prompt_tokens, completion_tokens, provider, model, cost_usd";
+
 impl Message {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Message {
+            id: row.get(0)?,
+            conversation_id: row.get(1)?,
+            role: row.get(2)?,
+            content: row.get(3)?,
+            timestamp: row.get(4)?,
+            tokens_used: row.get(5)?,
+            parent_message_id: row.get(6)?,
+            prompt_tokens: row.get(7)?,
+            completion_tokens: row.get(8)?,
+            provider: row.get(9)?,
+            model: row.get(10)?,
+            cost_usd: row.get(11)?,
+        })
+    }
+
+    /// Append a message to the active path of its conversation
     pub fn create(conn: &Connection, new_msg: NewMessage) -> Result<Self> {
+        let parent = Self::active_leaf_id(conn, &new_msg.conversation_id)?;
+        Self::create_with_parent(conn, new_msg, parent.as_deref())
+    }
+
+    /// Insert a message under an explicit parent and make it the active leaf
+    pub fn create_with_parent(
+        conn: &Connection,
+        new_msg: NewMessage,
+        parent_message_id: Option<&str>,
+    ) -> Result<Self> {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs() as i64;
         let id = uuid::Uuid::new_v4().to_string();
         conn.execute(
-            "INSERT INTO messages (id, conversation_id, role, content, timestamp, tokens_used) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![&id, &new_msg.conversation_id, &new_msg.role, &new_msg.content, now, new_msg.tokens_used],
+            "INSERT INTO messages (id, conversation_id, role, content, timestamp, tokens_used, parent_message_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![&id, &new_msg.conversation_id, &new_msg.role, &new_msg.content, now, new_msg.tokens_used, parent_message_id],
         )?;
+        Self::set_active_leaf_id(conn, &new_msg.conversation_id, &id)?;
         super::conversations::Conversation::touch(conn, &new_msg.conversation_id)?;
         Ok(Message {
             id,
@@ -49,13 +106,19 @@ impl Message {
             content: new_msg.content,
             timestamp: now,
             tokens_used: new_msg.tokens_used,
+            parent_message_id: parent_message_id.map(|p| p.to_string()),
+            prompt_tokens: None,
+            completion_tokens: None,
+            provider: None,
+            model: None,
+            cost_usd: None,
         })
     }
 
     pub fn create_with_id(conn: &Connection, new_msg: NewMessageWithId) -> Result<Self> {
         conn.execute(
-            "INSERT INTO messages (id, conversation_id, role, content, timestamp, tokens_used) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![&new_msg.id, &new_msg.conversation_id, &new_msg.role, &new_msg.content, new_msg.timestamp, new_msg.tokens_used],
+            "INSERT INTO messages (id, conversation_id, role, content, timestamp, tokens_used, parent_message_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![&new_msg.id, &new_msg.conversation_id, &new_msg.role, &new_msg.content, new_msg.timestamp, new_msg.tokens_used, &new_msg.parent_message_id],
         )?;
         Ok(Message {
             id: new_msg.id,
@@ -64,54 +127,184 @@ impl Message {
             content: new_msg.content,
             timestamp: new_msg.timestamp,
             tokens_used: new_msg.tokens_used,
+            parent_message_id: new_msg.parent_message_id,
+            prompt_tokens: None,
+            completion_tokens: None,
+            provider: None,
+            model: None,
+            cost_usd: None,
         })
     }
 
-    pub fn get_by_conversation(conn: &Connection, conversation_id: &str) -> Result<Vec<Self>> {
-        // Only return non-deleted messages
-        let mut stmt = conn.prepare("SELECT id, conversation_id, role, content, timestamp, tokens_used FROM messages WHERE conversation_id = ?1 AND deleted = 0 ORDER BY timestamp ASC")?;
-        let messages = stmt.query_map(params![conversation_id], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                conversation_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                timestamp: row.get(4)?,
-                tokens_used: row.get(5)?,
-            })
-        })?;
+    /// Whether the id is in use, counting soft-deleted messages
+    pub fn id_exists(conn: &Connection, id: &str) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM messages WHERE id = ?1)",
+            params![id],
+            |row| row.get(0),
+        )
+    }
+
+    /// Ids of every message in a conversation, counting soft-deleted ones
+    pub fn ids_in_conversation(
+        conn: &Connection,
+        conversation_id: &str,
+    ) -> Result<HashSet<String>> {
+        let mut stmt = conn.prepare("SELECT id FROM messages WHERE conversation_id = ?1")?;
+        let ids = stmt.query_map(params![conversation_id], |row| row.get(0))?;
+        ids.collect()
+    }
+
+    pub fn get_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
+        conn.query_row(
+            &format!(
+                "SELECT {} FROM messages WHERE id = ?1 AND deleted = 0",
+                SELECT_COLUMNS
+            ),
+            params![id],
+            Self::from_row,
+        )
+        .optional()
+    }
+
+    /// Every message of the conversation tree, oldest first
+    pub fn get_tree(conn: &Connection, conversation_id: &str) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM messages WHERE conversation_id = ?1 AND deleted = 0 ORDER BY timestamp ASC, rowid ASC",
+            SELECT_COLUMNS
+        ))?;
+        let messages = stmt.query_map(params![conversation_id], Self::from_row)?;
         messages.collect()
     }
 
+    /// Messages on the active path (root to active leaf), oldest first
+    pub fn get_by_conversation(conn: &Connection, conversation_id: &str) -> Result<Vec<Self>> {
+        let tree = Self::get_tree(conn, conversation_id)?;
+        let leaf_id = Self::resolve_leaf_id(conn, conversation_id, &tree)?;
+        Ok(match leaf_id {
+            Some(leaf_id) => path_to(tree, deleted_links(conn, conversation_id)?, &leaf_id),
+            None => Vec::new(),
+        })
+    }
+
     pub fn get_last_n(conn: &Connection, conversation_id: &str, n: i64) -> Result<Vec<Self>> {
-        let mut stmt = conn.prepare("SELECT id, conversation_id, role, content, timestamp, tokens_used FROM messages WHERE conversation_id = ?1 AND deleted = 0 ORDER BY timestamp DESC LIMIT ?2")?;
-        let messages = stmt.query_map(params![conversation_id, n], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                conversation_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                timestamp: row.get(4)?,
-                tokens_used: row.get(5)?,
-            })
-        })?;
-        let mut result: Vec<Self> = messages.collect::<Result<Vec<_>>>()?;
-        result.reverse();
-        Ok(result)
+        let mut path = Self::get_by_conversation(conn, conversation_id)?;
+        let keep = n.max(0) as usize;
+        if path.len() > keep {
+            path.drain(..path.len() - keep);
+        }
+        Ok(path)
+    }
+
+    /// Latest assistant message on the active path of a conversation
+    pub fn get_last_assistant(conn: &Connection, conversation_id: &str) -> Result<Option<Self>> {
+        Ok(Self::get_by_conversation(conn, conversation_id)?
+            .into_iter()
+            .rev()
+            .find(|m| m.role == "assistant"))
+    }
+
+    pub fn get_active_leaf(conn: &Connection, conversation_id: &str) -> Result<Option<Self>> {
+        match Self::active_leaf_id(conn, conversation_id)? {
+            Some(id) => Self::get_by_id(conn, &id),
+            None => Ok(None),
+        }
+    }
+
+    /// Id of the stored active leaf if it still exists, otherwise of the newest
+    /// message, read without loading the tree
+    fn active_leaf_id(conn: &Connection, conversation_id: &str) -> Result<Option<String>> {
+        conn.query_row(
+            "SELECT COALESCE(
+                 (SELECT m.id FROM conversations c
+                  JOIN messages m ON m.id = c.active_leaf_id
+                  WHERE c.id = ?1 AND m.conversation_id = ?1 AND m.deleted = 0),
+                 (SELECT id FROM messages WHERE conversation_id = ?1 AND deleted = 0
+                  ORDER BY timestamp DESC, rowid DESC LIMIT 1))",
+            params![conversation_id],
+            |row| row.get(0),
+        )
+    }
+
+    /// The stored active leaf if it still exists, otherwise the newest message
+    fn resolve_leaf_id(
+        conn: &Connection,
+        conversation_id: &str,
+        tree: &[Message],
+    ) -> Result<Option<String>> {
+        let stored: Option<String> = conn
+            .query_row(
+                "SELECT active_leaf_id FROM conversations WHERE id = ?1",
+                params![conversation_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+
+        Ok(match stored {
+            Some(id) if tree.iter().any(|m| m.id == id) => Some(id),
+            _ => tree.last().map(|m| m.id.clone()),
+        })
+    }
+
+    fn set_active_leaf_id(conn: &Connection, conversation_id: &str, leaf_id: &str) -> Result<()> {
+        conn.execute(
+            "UPDATE conversations SET active_leaf_id = ?1 WHERE id = ?2",
+            params![leaf_id, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    /// Make the path through `message_id` active. If the message has replies, the
+    /// most recent reply is followed down to a leaf.
+    pub fn set_active_leaf(conn: &Connection, message_id: &str) -> Result<Vec<Self>> {
+        let message = Self::get_by_id(conn, message_id)?
+            .ok_or_else(|| rusqlite::Error::InvalidPath("Message not found".into()))?;
+        let tree = Self::get_tree(conn, &message.conversation_id)?;
+
+        let mut leaf_id = message.id.clone();
+        while let Some(child) = tree
+            .iter()
+            .rev()
+            .find(|m| m.parent_message_id.as_deref() == Some(leaf_id.as_str()))
+        {
+            leaf_id = child.id.clone();
+        }
+
+        Self::set_active_leaf_id(conn, &message.conversation_id, &leaf_id)?;
+        let deleted = deleted_links(conn, &message.conversation_id)?;
+        Ok(path_to(tree, deleted, &leaf_id))
+    }
+
+    /// Path from the root of the tree down to `message_id`, oldest first
+    pub fn get_path_to(conn: &Connection, message_id: &str) -> Result<Vec<Self>> {
+        let message = Self::get_by_id(conn, message_id)?
+            .ok_or_else(|| rusqlite::Error::InvalidPath("Message not found".into()))?;
+        let tree = Self::get_tree(conn, &message.conversation_id)?;
+        let deleted = deleted_links(conn, &message.conversation_id)?;
+        Ok(path_to(tree, deleted, message_id))
+    }
+
+    /// Alternatives sharing the same parent (including the message itself), oldest first
+    pub fn get_siblings(conn: &Connection, message_id: &str) -> Result<Vec<Self>> {
+        let message = Self::get_by_id(conn, message_id)?
+            .ok_or_else(|| rusqlite::Error::InvalidPath("Message not found".into()))?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM messages
+             WHERE conversation_id = ?1 AND parent_message_id IS ?2 AND deleted = 0
+             ORDER BY timestamp ASC, rowid ASC",
+            SELECT_COLUMNS
+        ))?;
+        let messages = stmt.query_map(
+            params![&message.conversation_id, &message.parent_message_id],
+            Self::from_row,
+        )?;
+        messages.collect()
     }
 
     pub fn search(conn: &Connection, query: &str, limit: i64) -> Result<Vec<Self>> {
-        let mut stmt = conn.prepare("SELECT m.id, m.conversation_id, m.role, m.content, m.timestamp, m.tokens_used FROM messages m JOIN messages_fts fts ON m.rowid = fts.rowid WHERE messages_fts MATCH ?1 AND m.deleted = 0 ORDER BY m.timestamp DESC LIMIT ?2")?;
-        let messages = stmt.query_map(params![query, limit], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                conversation_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                timestamp: row.get(4)?,
-                tokens_used: row.get(5)?,
-            })
-        })?;
+        let mut stmt = conn.prepare("SELECT m.id, m.conversation_id, m.role, m.content, m.timestamp, m.tokens_used, m.parent_message_id, m.prompt_tokens, m.completion_tokens, m.provider, m.model, m.cost_usd FROM messages m JOIN messages_fts fts ON m.rowid = fts.rowid WHERE messages_fts MATCH ?1 AND m.deleted = 0 ORDER BY m.timestamp DESC LIMIT ?2")?;
+        let messages = stmt.query_map(params![query, limit], Self::from_row)?;
         messages.collect()
     }
 
@@ -123,17 +316,11 @@ impl Message {
         )?;
 
         // Get the updated message
-        let mut stmt = conn.prepare("SELECT id, conversation_id, role, content, timestamp, tokens_used FROM messages WHERE id = ?1")?;
-        let message = stmt.query_row(params![id], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                conversation_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                timestamp: row.get(4)?,
-                tokens_used: row.get(5)?,
-            })
-        })?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM messages WHERE id = ?1",
+            SELECT_COLUMNS
+        ))?;
+        let message = stmt.query_row(params![id], Self::from_row)?;
 
         // Touch the conversation to update its timestamp
         super::conversations::Conversation::touch(conn, &message.conversation_id)?;
@@ -141,6 +328,26 @@ impl Message {
         Ok(message)
     }
 
+    /// Record provider usage for a message; `tokens_used` becomes the token sum
+    pub fn record_usage(conn: &Connection, id: &str, usage: &UsageRecord) -> Result<Self> {
+        conn.execute(
+            "UPDATE messages SET prompt_tokens = ?1, completion_tokens = ?2, tokens_used = ?3,
+                provider = ?4, model = ?5, profile_id = ?6, cost_usd = ?7
+             WHERE id = ?8",
+            params![
+                usage.prompt_tokens,
+                usage.completion_tokens,
+                usage.prompt_tokens + usage.completion_tokens,
+                &usage.provider,
+                &usage.model,
+                &usage.profile_id,
+                usage.cost_usd,
+                id
+            ],
+        )?;
+        Self::get_by_id(conn, id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)
+    }
+
     pub fn delete(conn: &Connection, id: &str) -> Result<()> {
         // Soft-delete message by marking deleted flag
         let now = SystemTime::now()
@@ -154,12 +361,53 @@ impl Message {
         Ok(())
     }
 
+    /// Tokens of the messages on the active path; other branches aren't sent
+    /// with the next request
     pub fn get_conversation_token_count(conn: &Connection, conversation_id: &str) -> Result<i64> {
-        let count: Option<i64> = conn.query_row(
-            "SELECT SUM(tokens_used) FROM messages WHERE conversation_id = ?1",
-            params![conversation_id],
-            |row| row.get(0),
-        )?;
-        Ok(count.unwrap_or(0))
+        Ok(Self::get_by_conversation(conn, conversation_id)?
+            .iter()
+            .map(|m| match (m.prompt_tokens, m.completion_tokens) {
+                (Some(prompt), Some(completion)) => prompt + completion,
+                _ => m.tokens_used.unwrap_or(0),
+            })
+            .sum())
+    }
+}
+
+/// Parent links of a conversation's deleted messages
+fn deleted_links(
+    conn: &Connection,
+    conversation_id: &str,
+) -> Result<HashMap<String, Option<String>>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, parent_message_id FROM messages WHERE conversation_id = ?1 AND deleted = 1",
+    )?;
+    let links = stmt.query_map(params![conversation_id], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    links.collect()
+}
+
+/// Walk parent links from `leaf_id` up to the root and return the path root-first
+fn path_to(
+    tree: Vec<Message>,
+    mut deleted: HashMap<String, Option<String>>,
+    leaf_id: &str,
+) -> Vec<Message> {
+    let mut by_id: HashMap<String, Message> = tree.into_iter().map(|m| (m.id.clone(), m)).collect();
+    let mut path = Vec::new();
+    let mut current = by_id.remove(leaf_id);
+
+    while let Some(message) = current {
+        // A deleted message drops out of the path, not the history above it
+        let mut parent = message.parent_message_id.clone();
+        while let Some(grandparent) = parent.as_ref().and_then(|id| deleted.remove(id)) {
+            parent = grandparent;
+        }
+        current = parent.and_then(|parent| by_id.remove(&parent));
+        path.push(message);
     }
+
+    path.reverse();
+    path
 }
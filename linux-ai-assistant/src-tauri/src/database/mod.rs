@@ -1,27 +1,57 @@
 // src-tauri/src/database/mod.rs
 // Database module: declare submodules and provide the Database manager.
 
+pub mod attachments;
 pub mod conversations;
+pub mod encryption;
 pub mod messages;
 pub mod profiles;
+pub mod project_chunks;
 pub mod schema;
 pub mod settings;
 pub mod tags;
+pub mod usage;
 pub mod workspace_templates;
 
+use crate::error::{AppError, AppResult};
+use encryption::DatabaseKey;
 use rusqlite::{Connection, Result};
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
 /// Database manager that holds the connection
 pub struct Database {
-    conn: Mutex<Connection>,
+    path: PathBuf,
+    conn: SharedConnection,
 }
 
 impl Database {
     /// Initialize the database with schema
     pub fn new(db_path: PathBuf) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
+        let conn = Self::open_connection(&db_path, None)?;
+        Ok(Database {
+            path: db_path,
+            conn: SharedConnection::new(Some(conn)),
+        })
+    }
+
+    /// An encrypted database whose connection opens once `unlock` gets its key
+    pub fn locked(db_path: PathBuf) -> Self {
+        Database {
+            path: db_path,
+            conn: SharedConnection::new(None),
+        }
+    }
+
+    fn open_connection(path: &Path, key: Option<&DatabaseKey>) -> Result<Connection> {
+        let conn = Connection::open(path)?;
+
+        // SQLCipher needs the key before the first read
+        if let Some(key) = key {
+            conn.pragma_update(None, "key", key.as_str())?;
+        }
 
         // Enable foreign keys
         conn.execute("PRAGMA foreign_keys = ON", [])?;
@@ -29,15 +59,82 @@ impl Database {
         // Initialize schema
         schema::create_tables(&conn)?;
 
-        Ok(Database {
-            conn: Mutex::new(conn),
-        })
+        Ok(conn)
     }
 
     /// Get a reference to the connection
-    pub fn conn(&self) -> &Mutex<Connection> {
+    pub fn conn(&self) -> &SharedConnection {
         &self.conn
     }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// The connection, closed while an encrypted database is locked
+pub struct SharedConnection {
+    state: Mutex<ConnectionState>,
+    last_used: Mutex<Instant>,
+}
+
+struct ConnectionState {
+    conn: Option<Connection>,
+    /// Key of an encrypted database, kept while it is unlocked
+    key: Option<DatabaseKey>,
+}
+
+impl SharedConnection {
+    fn new(conn: Option<Connection>) -> Self {
+        SharedConnection {
+            state: Mutex::new(ConnectionState { conn, key: None }),
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for the connection; fails while the database is locked
+    pub fn lock(&self) -> AppResult<ConnectionGuard<'_>> {
+        let guard = self.lock_in_background()?;
+        *self.last_used.lock()? = Instant::now();
+        Ok(guard)
+    }
+
+    /// Like `lock`, but for scheduled work, which doesn't keep an encrypted
+    /// database from locking when idle
+    pub fn lock_in_background(&self) -> AppResult<ConnectionGuard<'_>> {
+        let state = self.state.lock()?;
+        if state.conn.is_none() {
+            return Err(AppError::locked(
+                "The database is locked; unlock it to continue",
+            ));
+        }
+        Ok(ConnectionGuard(state))
+    }
+
+    /// Time since the connection was last used for anything but scheduled work
+    pub fn idle(&self) -> Duration {
+        self.last_used
+            .lock()
+            .map(|last_used| last_used.elapsed())
+            .unwrap_or_default()
+    }
+}
+
+/// Exclusive use of the open connection
+pub struct ConnectionGuard<'a>(MutexGuard<'a, ConnectionState>);
+
+impl Deref for ConnectionGuard<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.0.conn.as_ref().expect("checked when locking")
+    }
+}
+
+impl DerefMut for ConnectionGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.0.conn.as_mut().expect("checked when locking")
+    }
 }
 
 #[cfg(test)]
@@ -91,4 +188,212 @@ mod tests {
             DbMessage::get_by_conversation(&conn, &conv.id).expect("get msgs after restore");
         assert_eq!(msgs_after.len(), 1);
     }
+
+    #[test]
+    fn message_tree_branches_and_active_leaf() {
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+
+        let conv = DbConversation::create(
+            &conn,
+            NewConversation {
+                title: "Tree".to_string(),
+                model: "gpt-test".to_string(),
+                provider: "local".to_string(),
+                system_prompt: None,
+            },
+        )
+        .expect("create conv");
+        let msg = |role: &str, content: &str| NewMessage {
+            conversation_id: conv.id.clone(),
+            role: role.to_string(),
+            content: content.to_string(),
+            tokens_used: None,
+        };
+
+        let question = DbMessage::create(&conn, msg("user", "question")).expect("create");
+        let first = DbMessage::create(&conn, msg("assistant", "first")).expect("create");
+        assert_eq!(
+            first.parent_message_id.as_deref(),
+            Some(question.id.as_str())
+        );
+
+        // A regenerated reply is a sibling and becomes the active leaf
+        let second =
+            DbMessage::create_with_parent(&conn, msg("assistant", "second"), Some(&question.id))
+                .expect("create sibling");
+        let siblings = DbMessage::get_siblings(&conn, &first.id).expect("siblings");
+        assert_eq!(siblings.len(), 2);
+        let path = DbMessage::get_by_conversation(&conn, &conv.id).expect("path");
+        assert_eq!(path.len(), 2);
+        assert_eq!(path[1].id, second.id);
+
+        // Switching back restores the first branch; the tree keeps both
+        let path = DbMessage::set_active_leaf(&conn, &first.id).expect("switch");
+        assert_eq!(path.last().map(|m| m.id.as_str()), Some(first.id.as_str()));
+        let last = DbMessage::get_last_assistant(&conn, &conv.id).expect("last");
+        assert_eq!(last.map(|m| m.content), Some("first".to_string()));
+        assert_eq!(DbMessage::get_tree(&conn, &conv.id).expect("tree").len(), 3);
+
+        // New messages continue the active path; a deleted leaf falls back to
+        // the newest remaining message
+        let follow_up = DbMessage::create(&conn, msg("user", "follow-up")).expect("create");
+        assert_eq!(
+            follow_up.parent_message_id.as_deref(),
+            Some(first.id.as_str())
+        );
+        DbMessage::delete(&conn, &follow_up.id).expect("delete");
+        let retry = DbMessage::create(&conn, msg("user", "retry")).expect("create");
+        assert_eq!(retry.parent_message_id.as_deref(), Some(second.id.as_str()));
+    }
+
+    #[test]
+    fn token_count_covers_only_the_active_path() {
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+
+        let conv = DbConversation::create(
+            &conn,
+            NewConversation {
+                title: "Tokens".to_string(),
+                model: "gpt-test".to_string(),
+                provider: "local".to_string(),
+                system_prompt: None,
+            },
+        )
+        .expect("create conv");
+        let msg = |role: &str, tokens: i64| NewMessage {
+            conversation_id: conv.id.clone(),
+            role: role.to_string(),
+            content: role.to_string(),
+            tokens_used: Some(tokens),
+        };
+
+        let question = DbMessage::create(&conn, msg("user", 10)).expect("create");
+        let first = DbMessage::create(&conn, msg("assistant", 100)).expect("create");
+        DbMessage::create_with_parent(&conn, msg("assistant", 1000), Some(&question.id))
+            .expect("create sibling");
+        assert_eq!(
+            DbMessage::get_conversation_token_count(&conn, &conv.id).expect("count"),
+            1010
+        );
+
+        DbMessage::set_active_leaf(&conn, &first.id).expect("switch");
+        assert_eq!(
+            DbMessage::get_conversation_token_count(&conn, &conv.id).expect("count"),
+            110
+        );
+    }
+
+    #[test]
+    fn deleting_a_middle_message_keeps_the_history_above_it() {
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+
+        let conv = DbConversation::create(
+            &conn,
+            NewConversation {
+                title: "Gaps".to_string(),
+                model: "gpt-test".to_string(),
+                provider: "local".to_string(),
+                system_prompt: None,
+            },
+        )
+        .expect("create conv");
+        let msg = |role: &str, content: &str| NewMessage {
+            conversation_id: conv.id.clone(),
+            role: role.to_string(),
+            content: content.to_string(),
+            tokens_used: None,
+        };
+        let ids: Vec<String> = [
+            ("user", "one"),
+            ("assistant", "two"),
+            ("user", "three"),
+            ("assistant", "four"),
+        ]
+        .into_iter()
+        .map(|(role, content)| {
+            DbMessage::create(&conn, msg(role, content))
+                .expect("create")
+                .id
+        })
+        .collect();
+
+        DbMessage::delete(&conn, &ids[1]).expect("delete");
+        DbMessage::delete(&conn, &ids[2]).expect("delete");
+        let contents =
+            |path: Vec<DbMessage>| -> Vec<String> { path.into_iter().map(|m| m.content).collect() };
+        assert_eq!(
+            contents(DbMessage::get_by_conversation(&conn, &conv.id).expect("path")),
+            vec!["one", "four"]
+        );
+        assert_eq!(
+            contents(DbMessage::get_path_to(&conn, &ids[3]).expect("path")),
+            vec!["one", "four"]
+        );
+    }
+
+    #[test]
+    fn branches_and_merges_keep_attachments() {
+        use crate::database::attachments::{Attachment, NewAttachment};
+
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+        let conv = DbConversation::create(
+            &conn,
+            NewConversation {
+                title: "Attachments".to_string(),
+                model: "gpt-test".to_string(),
+                provider: "local".to_string(),
+                system_prompt: None,
+            },
+        )
+        .expect("create conv");
+        let add = |conversation_id: &str, content: &str| {
+            let message = DbMessage::create(
+                &conn,
+                NewMessage {
+                    conversation_id: conversation_id.to_string(),
+                    role: "user".to_string(),
+                    content: content.to_string(),
+                    tokens_used: None,
+                },
+            )
+            .expect("create msg");
+            Attachment::create(
+                &conn,
+                NewAttachment {
+                    message_id: message.id.clone(),
+                    kind: "file".to_string(),
+                    file_name: format!("{}.txt", content),
+                    mime_type: "text/plain".to_string(),
+                    size_bytes: 2,
+                    sha256: "0".repeat(64),
+                },
+            )
+            .expect("attach");
+            message
+        };
+
+        let question = add(&conv.id, "question");
+        let branch = DbConversation::create_branch(&conn, &conv.id, &question.id, "Alt".into())
+            .expect("branch");
+        let copied = Attachment::get_by_conversation(&conn, &branch.id).expect("attachments");
+        assert_eq!(copied.len(), 1);
+        assert_eq!(copied[0].file_name, "question.txt");
+        assert_ne!(copied[0].message_id, question.id);
+
+        let log = add(&branch.id, "log");
+        let merged =
+            DbConversation::merge_messages(&conn, &branch.id, &conv.id, &[log.id]).expect("merge");
+        let merged_attachments = Attachment::get_by_message(&conn, &merged[0].id).expect("get");
+        assert_eq!(merged_attachments.len(), 1);
+        assert_eq!(merged_attachments[0].file_name, "log.txt");
+        // All four rows share the one blob
+        assert_eq!(
+            Attachment::count_by_sha256(&conn, &"0".repeat(64)).expect("count"),
+            4
+        );
+    }
 }
@@ -13,6 +13,17 @@ pub struct Profile {
     pub created_at: i64,
     pub updated_at: i64,
     pub is_active: bool,
+    /// Providers tried in order after the requested one fails
+    #[serde(default)]
+    pub fallback_chain: Vec<FallbackTarget>,
+}
+
+/// One step of a fallback chain; `model` None uses the provider's default
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FallbackTarget {
+    pub provider: String,
+    #[serde(default)]
+    pub model: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,6 +47,10 @@ impl Profile {
             created_at: row.get("created_at")?,
             updated_at: row.get("updated_at")?,
             is_active: row.get::<_, i64>("is_active")? == 1,
+            fallback_chain: row
+                .get::<_, Option<String>>("fallback_chain")?
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
         })
     }
 
@@ -68,7 +83,7 @@ impl Profile {
 
     pub fn get_by_id(conn: &Connection, id: &str) -> Result<Option<Self>> {
         let mut stmt = conn.prepare(
-            "SELECT id, name, description, default_model, default_provider, system_prompt, created_at, updated_at, is_active
+            "SELECT id, name, description, default_model, default_provider, system_prompt, created_at, updated_at, is_active, fallback_chain
              FROM profiles
              WHERE id = ?1"
         )?;
@@ -82,7 +97,7 @@ impl Profile {
 
     pub fn get_all(conn: &Connection) -> Result<Vec<Self>> {
         let mut stmt = conn.prepare(
-            "SELECT id, name, description, default_model, default_provider, system_prompt, created_at, updated_at, is_active
+            "SELECT id, name, description, default_model, default_provider, system_prompt, created_at, updated_at, is_active, fallback_chain
              FROM profiles
              ORDER BY is_active DESC, updated_at DESC"
         )?;
@@ -97,7 +112,7 @@ impl Profile {
 
     pub fn get_active(conn: &Connection) -> Result<Option<Self>> {
         let mut stmt = conn.prepare(
-            "SELECT id, name, description, default_model, default_provider, system_prompt, created_at, updated_at, is_active
+            "SELECT id, name, description, default_model, default_provider, system_prompt, created_at, updated_at, is_active, fallback_chain
              FROM profiles
              WHERE is_active = 1
              LIMIT 1"
@@ -164,6 +179,33 @@ impl Profile {
         })
     }
 
+    pub fn set_fallback_chain(
+        conn: &Connection,
+        id: &str,
+        chain: &[FallbackTarget],
+    ) -> Result<Self> {
+        let json = serde_json::to_string(chain)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let updated = conn.execute(
+            "UPDATE profiles SET fallback_chain = ?1, updated_at = ?2 WHERE id = ?3",
+            params![json, chrono::Utc::now().timestamp_millis(), id],
+        )?;
+
+        if updated == 0 {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_FAIL),
+                Some("Profile not found".to_string()),
+            ));
+        }
+
+        Self::get_by_id(conn, id)?.ok_or_else(|| {
+            rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_FAIL),
+                Some("Failed to retrieve updated profile".to_string()),
+            )
+        })
+    }
+
     pub fn delete(conn: &Connection, id: &str) -> Result<()> {
         // Don't allow deleting the last profile
         let count: i64 = conn.query_row("SELECT COUNT(*) FROM profiles", [], |row| row.get(0))?;
@@ -0,0 +1,128 @@
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectChunk {
+    pub id: i64,
+    pub project_root: String,
+    pub path: String,
+    pub start_line: i64,
+    pub end_line: i64,
+    pub content: String,
+    pub file_mtime: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewProjectChunk {
+    pub path: String,
+    pub start_line: i64,
+    pub end_line: i64,
+    pub content: String,
+}
+
+/// A chunk returned from a relevance search, with its bm25 rank (lower is better)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScoredChunk {
+    pub chunk: ProjectChunk,
+    pub score: f64,
+}
+
+impl ProjectChunk {
+    /// Replace all chunks of a single file in one transaction
+    pub fn replace_file(
+        conn: &Connection,
+        project_root: &str,
+        path: &str,
+        file_mtime: i64,
+        chunks: &[NewProjectChunk],
+    ) -> Result<()> {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM project_chunks WHERE project_root = ?1 AND path = ?2",
+            params![project_root, path],
+        )?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO project_chunks (project_root, path, start_line, end_line, content, file_mtime)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for chunk in chunks {
+                stmt.execute(params![
+                    project_root,
+                    &chunk.path,
+                    chunk.start_line,
+                    chunk.end_line,
+                    &chunk.content,
+                    file_mtime
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    pub fn delete_file(conn: &Connection, project_root: &str, path: &str) -> Result<()> {
+        conn.execute(
+            "DELETE FROM project_chunks WHERE project_root = ?1 AND path = ?2",
+            params![project_root, path],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_project(conn: &Connection, project_root: &str) -> Result<()> {
+        conn.execute(
+            "DELETE FROM project_chunks WHERE project_root = ?1",
+            params![project_root],
+        )?;
+        Ok(())
+    }
+
+    /// Indexed files of a project with the mtime they were indexed at
+    pub fn get_indexed_files(conn: &Connection, project_root: &str) -> Result<Vec<(String, i64)>> {
+        let mut stmt = conn.prepare(
+            "SELECT path, MAX(file_mtime) FROM project_chunks WHERE project_root = ?1 GROUP BY path",
+        )?;
+        let files = stmt.query_map(params![project_root], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        files.collect()
+    }
+
+    pub fn count(conn: &Connection, project_root: &str) -> Result<i64> {
+        conn.query_row(
+            "SELECT COUNT(*) FROM project_chunks WHERE project_root = ?1",
+            params![project_root],
+            |row| row.get(0),
+        )
+    }
+
+    /// Full-text search over the chunks of one project, best matches first
+    pub fn search(
+        conn: &Connection,
+        project_root: &str,
+        fts_query: &str,
+        limit: i64,
+    ) -> Result<Vec<ScoredChunk>> {
+        let mut stmt = conn.prepare(
+            "SELECT c.id, c.project_root, c.path, c.start_line, c.end_line, c.content, c.file_mtime,
+                    bm25(project_chunks_fts) AS score
+             FROM project_chunks c
+             JOIN project_chunks_fts ON c.id = project_chunks_fts.rowid
+             WHERE project_chunks_fts MATCH ?1 AND c.project_root = ?2
+             ORDER BY score
+             LIMIT ?3",
+        )?;
+        let chunks = stmt.query_map(params![fts_query, project_root, limit], |row| {
+            Ok(ScoredChunk {
+                chunk: ProjectChunk {
+                    id: row.get(0)?,
+                    project_root: row.get(1)?,
+                    path: row.get(2)?,
+                    start_line: row.get(3)?,
+                    end_line: row.get(4)?,
+                    content: row.get(5)?,
+                    file_mtime: row.get(6)?,
+                },
+                score: row.get(7)?,
+            })
+        })?;
+        chunks.collect()
+    }
+}
@@ -14,6 +14,10 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
             deleted_at INTEGER,
             parent_conversation_id TEXT,
             branch_point_message_id TEXT,
+            project_root TEXT,
+            active_leaf_id TEXT,
+            summary TEXT,
+            summary_message_id TEXT,
             FOREIGN KEY (parent_conversation_id) REFERENCES conversations(id) ON DELETE SET NULL,
             FOREIGN KEY (branch_point_message_id) REFERENCES messages(id) ON DELETE SET NULL
         )",
@@ -30,6 +34,13 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
             tokens_used INTEGER,
             deleted INTEGER NOT NULL DEFAULT 0,
             deleted_at INTEGER,
+            parent_message_id TEXT,
+            prompt_tokens INTEGER,
+            completion_tokens INTEGER,
+            provider TEXT,
+            model TEXT,
+            profile_id TEXT,
+            cost_usd REAL,
             FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
         )",
         [],
@@ -92,11 +103,16 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
             system_prompt TEXT,
             created_at INTEGER NOT NULL,
             updated_at INTEGER NOT NULL,
-            is_active INTEGER NOT NULL DEFAULT 0
+            is_active INTEGER NOT NULL DEFAULT 0,
+            fallback_chain TEXT
         )",
         [],
     )?;
 
+    // Providers tried in order when the requested one fails (JSON array)
+    conn.execute("ALTER TABLE profiles ADD COLUMN fallback_chain TEXT", [])
+        .ok(); // Ignore error if column already exists
+
     // Add index for active profile lookup
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_profiles_active
@@ -142,6 +158,199 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Tie conversations to an indexed project root for retrieval-augmented answers
+    conn.execute("ALTER TABLE conversations ADD COLUMN project_root TEXT", [])
+        .ok(); // Ignore error if column already exists
+
+    // Message tree: each message points at the message it replies to. Existing
+    // linear conversations are backfilled so every message follows its predecessor.
+    if conn
+        .execute("ALTER TABLE messages ADD COLUMN parent_message_id TEXT", [])
+        .is_ok()
+    {
+        conn.execute(
+            "UPDATE messages SET parent_message_id = (
+                SELECT p.id FROM messages p
+                WHERE p.conversation_id = messages.conversation_id
+                  AND (p.timestamp < messages.timestamp
+                       OR (p.timestamp = messages.timestamp AND p.rowid < messages.rowid))
+                ORDER BY p.timestamp DESC, p.rowid DESC
+                LIMIT 1
+            )",
+            [],
+        )?;
+    }
+
+    conn.execute(
+        "ALTER TABLE conversations ADD COLUMN active_leaf_id TEXT",
+        [],
+    )
+    .ok(); // Ignore error if column already exists
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_messages_parent
+         ON messages(parent_message_id)",
+        [],
+    )?;
+
+    // Profile and workspace template a conversation was created under
+    conn.execute("ALTER TABLE conversations ADD COLUMN profile_id TEXT", [])
+        .ok(); // Ignore error if column already exists
+    conn.execute("ALTER TABLE conversations ADD COLUMN template_id TEXT", [])
+        .ok(); // Ignore error if column already exists
+
+    // Rolling conversation summary written by the background summarizer
+    conn.execute("ALTER TABLE conversations ADD COLUMN summary TEXT", [])
+        .ok(); // Ignore error if column already exists
+    conn.execute(
+        "ALTER TABLE conversations ADD COLUMN summary_message_id TEXT",
+        [],
+    )
+    .ok(); // Ignore error if column already exists
+
+    // Per-message provider usage split into prompt and completion tokens
+    conn.execute("ALTER TABLE messages ADD COLUMN prompt_tokens INTEGER", [])
+        .ok(); // Ignore error if column already exists
+    conn.execute(
+        "ALTER TABLE messages ADD COLUMN completion_tokens INTEGER",
+        [],
+    )
+    .ok(); // Ignore error if column already exists
+
+    // Provider, model, active profile and cost recorded with each usage entry
+    for column in [
+        "provider TEXT",
+        "model TEXT",
+        "profile_id TEXT",
+        "cost_usd REAL",
+    ] {
+        conn.execute(&format!("ALTER TABLE messages ADD COLUMN {}", column), [])
+            .ok(); // Ignore error if column already exists
+    }
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_messages_profile_usage
+         ON messages(profile_id, timestamp)",
+        [],
+    )?;
+
+    // Locally editable model pricing in USD per million tokens. Entries match
+    // model names by prefix; the defaults below never overwrite user edits.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS model_pricing (
+            model TEXT PRIMARY KEY,
+            provider TEXT NOT NULL,
+            input_per_mtok REAL NOT NULL,
+            output_per_mtok REAL NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "INSERT OR IGNORE INTO model_pricing (
+            model, provider, input_per_mtok, output_per_mtok, updated_at
+        )
+        VALUES
+        ('gpt-4o', 'openai', 2.50, 10.00, strftime('%s', 'now')),
+        ('gpt-4o-mini', 'openai', 0.15, 0.60, strftime('%s', 'now')),
+        ('gpt-4.1', 'openai', 2.00, 8.00, strftime('%s', 'now')),
+        ('gpt-4.1-mini', 'openai', 0.40, 1.60, strftime('%s', 'now')),
+        ('gpt-4-turbo', 'openai', 10.00, 30.00, strftime('%s', 'now')),
+        ('gpt-4', 'openai', 30.00, 60.00, strftime('%s', 'now')),
+        ('gpt-3.5-turbo', 'openai', 0.50, 1.50, strftime('%s', 'now')),
+        ('o1', 'openai', 15.00, 60.00, strftime('%s', 'now')),
+        ('o3-mini', 'openai', 1.10, 4.40, strftime('%s', 'now')),
+        ('claude-3-5-sonnet', 'anthropic', 3.00, 15.00, strftime('%s', 'now')),
+        ('claude-3-5-haiku', 'anthropic', 0.80, 4.00, strftime('%s', 'now')),
+        ('claude-3-opus', 'anthropic', 15.00, 75.00, strftime('%s', 'now')),
+        ('claude-3-haiku', 'anthropic', 0.25, 1.25, strftime('%s', 'now')),
+        ('gemini-1.5-pro', 'gemini', 1.25, 5.00, strftime('%s', 'now')),
+        ('gemini-1.5-flash', 'gemini', 0.075, 0.30, strftime('%s', 'now')),
+        ('gemini-2.0-flash', 'gemini', 0.10, 0.40, strftime('%s', 'now'))",
+        [],
+    )?;
+
+    // Spending caps per profile
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS profile_budgets (
+            profile_id TEXT PRIMARY KEY,
+            soft_limit_usd REAL,
+            hard_limit_usd REAL,
+            period TEXT NOT NULL DEFAULT 'month' CHECK(period IN ('day', 'week', 'month')),
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Images and files attached to messages; bytes live in the blob store keyed by sha256
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS attachments (
+            id TEXT PRIMARY KEY,
+            message_id TEXT NOT NULL,
+            kind TEXT NOT NULL CHECK(kind IN ('image', 'file')),
+            file_name TEXT NOT NULL,
+            mime_type TEXT NOT NULL,
+            size_bytes INTEGER NOT NULL,
+            sha256 TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_attachments_message
+         ON attachments(message_id)",
+        [],
+    )?;
+
+    // Create project index tables (chunked file contents, searchable via FTS5)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS project_chunks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            project_root TEXT NOT NULL,
+            path TEXT NOT NULL,
+            start_line INTEGER NOT NULL,
+            end_line INTEGER NOT NULL,
+            content TEXT NOT NULL,
+            file_mtime INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_project_chunks_path
+         ON project_chunks(project_root, path)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS project_chunks_fts
+         USING fts5(content, path, tokenize='porter')",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS project_chunks_fts_insert
+         AFTER INSERT ON project_chunks
+         BEGIN
+            INSERT INTO project_chunks_fts(rowid, content, path)
+            VALUES (NEW.id, NEW.content, NEW.path);
+         END",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS project_chunks_fts_delete
+         AFTER DELETE ON project_chunks
+         BEGIN
+            DELETE FROM project_chunks_fts WHERE rowid = OLD.id;
+         END",
+        [],
+    )?;
+
     // Create tags table for conversation tagging
     conn.execute(
         "CREATE TABLE IF NOT EXISTS tags (
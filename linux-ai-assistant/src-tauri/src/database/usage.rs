@@ -0,0 +1,257 @@
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// Model name or prefix (e.g. `gpt-4o` also prices `gpt-4o-2024-08-06`)
+    pub model: String,
+    pub provider: String,
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+    pub updated_at: i64,
+}
+
+/// Spending caps for a profile over a rolling calendar period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileBudget {
+    pub profile_id: String,
+    /// Warn once spending in the period reaches this amount
+    pub soft_limit_usd: Option<f64>,
+    /// Refuse paid requests once spending in the period reaches this amount
+    pub hard_limit_usd: Option<f64>,
+    /// `day`, `week` or `month`
+    pub period: String,
+    pub updated_at: i64,
+}
+
+/// One assistant message's usage, keyed for grouping
+#[derive(Debug, Clone)]
+pub struct UsageEntry {
+    pub key: String,
+    pub label: String,
+    pub provider: String,
+    pub model: String,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost_usd: Option<f64>,
+}
+
+impl ModelPrice {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(ModelPrice {
+            model: row.get(0)?,
+            provider: row.get(1)?,
+            input_per_mtok: row.get(2)?,
+            output_per_mtok: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    }
+
+    pub fn get_all(conn: &Connection) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT model, provider, input_per_mtok, output_per_mtok, updated_at
+             FROM model_pricing ORDER BY provider, model",
+        )?;
+        let prices = stmt.query_map([], Self::from_row)?;
+        prices.collect()
+    }
+
+    /// Price for a model: the longest entry the name starts with (an exact
+    /// entry being the longest). Compared byte for byte, unlike `LIKE`, which
+    /// ignores case and treats `_` and `%` as wildcards.
+    pub fn find_for_model(conn: &Connection, model: &str) -> Result<Option<Self>> {
+        conn.query_row(
+            "SELECT model, provider, input_per_mtok, output_per_mtok, updated_at
+             FROM model_pricing
+             WHERE substr(?1, 1, length(model)) = model
+             ORDER BY length(model) DESC LIMIT 1",
+            params![model],
+            Self::from_row,
+        )
+        .optional()
+    }
+
+    pub fn upsert(
+        conn: &Connection,
+        model: &str,
+        provider: &str,
+        input_per_mtok: f64,
+        output_per_mtok: f64,
+    ) -> Result<Self> {
+        let now = chrono::Utc::now().timestamp();
+        conn.execute(
+            "INSERT INTO model_pricing (model, provider, input_per_mtok, output_per_mtok, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(model) DO UPDATE SET
+                provider = excluded.provider,
+                input_per_mtok = excluded.input_per_mtok,
+                output_per_mtok = excluded.output_per_mtok,
+                updated_at = excluded.updated_at",
+            params![model, provider, input_per_mtok, output_per_mtok, now],
+        )?;
+        Ok(ModelPrice {
+            model: model.to_string(),
+            provider: provider.to_string(),
+            input_per_mtok,
+            output_per_mtok,
+            updated_at: now,
+        })
+    }
+
+    pub fn delete(conn: &Connection, model: &str) -> Result<()> {
+        conn.execute("DELETE FROM model_pricing WHERE model = ?1", params![model])?;
+        Ok(())
+    }
+
+    /// Cost in USD of a request with the given token counts
+    pub fn cost(&self, prompt_tokens: i64, completion_tokens: i64) -> f64 {
+        (prompt_tokens as f64 * self.input_per_mtok
+            + completion_tokens as f64 * self.output_per_mtok)
+            / 1_000_000.0
+    }
+}
+
+impl ProfileBudget {
+    pub fn get(conn: &Connection, profile_id: &str) -> Result<Option<Self>> {
+        conn.query_row(
+            "SELECT profile_id, soft_limit_usd, hard_limit_usd, period, updated_at
+             FROM profile_budgets WHERE profile_id = ?1",
+            params![profile_id],
+            |row| {
+                Ok(ProfileBudget {
+                    profile_id: row.get(0)?,
+                    soft_limit_usd: row.get(1)?,
+                    hard_limit_usd: row.get(2)?,
+                    period: row.get(3)?,
+                    updated_at: row.get(4)?,
+                })
+            },
+        )
+        .optional()
+    }
+
+    pub fn set(
+        conn: &Connection,
+        profile_id: &str,
+        soft_limit_usd: Option<f64>,
+        hard_limit_usd: Option<f64>,
+        period: &str,
+    ) -> Result<Self> {
+        let now = chrono::Utc::now().timestamp();
+        conn.execute(
+            "INSERT INTO profile_budgets (profile_id, soft_limit_usd, hard_limit_usd, period, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(profile_id) DO UPDATE SET
+                soft_limit_usd = excluded.soft_limit_usd,
+                hard_limit_usd = excluded.hard_limit_usd,
+                period = excluded.period,
+                updated_at = excluded.updated_at",
+            params![profile_id, soft_limit_usd, hard_limit_usd, period, now],
+        )?;
+        Ok(ProfileBudget {
+            profile_id: profile_id.to_string(),
+            soft_limit_usd,
+            hard_limit_usd,
+            period: period.to_string(),
+            updated_at: now,
+        })
+    }
+
+    pub fn delete(conn: &Connection, profile_id: &str) -> Result<()> {
+        conn.execute(
+            "DELETE FROM profile_budgets WHERE profile_id = ?1",
+            params![profile_id],
+        )?;
+        Ok(())
+    }
+}
+
+/// Grouping dimensions for usage entries. The key/label SQL is fixed per
+/// variant, so nothing user-supplied reaches the query text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGroup {
+    Day,
+    Provider,
+    Model,
+    Profile,
+    Tag,
+}
+
+impl UsageGroup {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "day" => Some(Self::Day),
+            "provider" => Some(Self::Provider),
+            "model" => Some(Self::Model),
+            "profile" => Some(Self::Profile),
+            "tag" => Some(Self::Tag),
+            _ => None,
+        }
+    }
+
+    fn sql(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Self::Day => (
+                "strftime('%Y-%m-%d', m.timestamp, 'unixepoch')",
+                "strftime('%Y-%m-%d', m.timestamp, 'unixepoch')",
+                "",
+            ),
+            Self::Provider => (
+                "COALESCE(m.provider, c.provider)",
+                "COALESCE(m.provider, c.provider)",
+                "",
+            ),
+            Self::Model => (
+                "COALESCE(m.model, c.model)",
+                "COALESCE(m.model, c.model)",
+                "",
+            ),
+            Self::Profile => (
+                "COALESCE(m.profile_id, '')",
+                "COALESCE(p.name, 'No profile')",
+                "LEFT JOIN profiles p ON p.id = m.profile_id",
+            ),
+            Self::Tag => (
+                "COALESCE(t.id, '')",
+                "COALESCE(t.name, 'Untagged')",
+                "LEFT JOIN conversation_tags ct ON ct.conversation_id = m.conversation_id
+                 LEFT JOIN tags t ON t.id = ct.tag_id",
+            ),
+        }
+    }
+}
+
+/// Usage of assistant messages in `[from, to)` (unix seconds). Without a group
+/// every entry has an empty key. With `Tag`, a message appears once per tag.
+pub fn usage_entries(
+    conn: &Connection,
+    from: i64,
+    to: i64,
+    group: Option<UsageGroup>,
+) -> Result<Vec<UsageEntry>> {
+    let (key, label, join) = group.map(UsageGroup::sql).unwrap_or(("''", "''", ""));
+    let sql = format!(
+        "SELECT {key}, {label}, COALESCE(m.provider, c.provider), COALESCE(m.model, c.model),
+                COALESCE(m.prompt_tokens, 0), COALESCE(m.completion_tokens, 0), m.cost_usd
+         FROM messages m
+         JOIN conversations c ON c.id = m.conversation_id
+         {join}
+         WHERE m.role = 'assistant' AND m.deleted = 0 AND m.timestamp >= ?1 AND m.timestamp < ?2
+         ORDER BY m.timestamp",
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let entries = stmt.query_map(params![from, to], |row| {
+        Ok(UsageEntry {
+            key: row.get(0)?,
+            label: row.get(1)?,
+            provider: row.get(2)?,
+            model: row.get(3)?,
+            prompt_tokens: row.get(4)?,
+            completion_tokens: row.get(5)?,
+            cost_usd: row.get(6)?,
+        })
+    })?;
+    entries.collect()
+}
@@ -0,0 +1,408 @@
+//! `org.linuxai.Assistant` on the session bus: the IPC kinds from `ipc.rs` as
+//! D-Bus methods, plus a `MessageAdded` signal for new assistant messages, so
+//! desktop extensions and scripts can integrate without the TCP protocol.
+//!
+//! ```sh
+//! busctl --user call org.linuxai.Assistant /org/linuxai/Assistant \
+//!     org.linuxai.Assistant Ask 'sa{sv}' "Explain this error" 0
+//! ```
+
+use crate::attachments;
+use crate::database::messages::Message;
+use crate::error::{AppError, AppResult, ErrorCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tauri::{AppHandle, Emitter, Manager};
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{OwnedValue, Type};
+use zbus::Connection;
+
+pub const BUS_NAME: &str = "org.linuxai.Assistant";
+pub const OBJECT_PATH: &str = "/org/linuxai/Assistant";
+
+static CONNECTION: OnceLock<Connection> = OnceLock::new();
+
+/// Errors as `org.linuxai.Assistant.Error.<Name>`
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.linuxai.Assistant.Error")]
+pub enum ServiceError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NotFound(String),
+    InvalidInput(String),
+    Failed(String),
+}
+
+impl From<AppError> for ServiceError {
+    fn from(err: AppError) -> Self {
+        match err.code {
+            ErrorCode::NotFound => Self::NotFound(err.message),
+            ErrorCode::InvalidInput => Self::InvalidInput(err.message),
+            _ => Self::Failed(err.message),
+        }
+    }
+}
+
+/// Most recent assistant message, signature `(sssx)`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct LastMessage {
+    pub id: String,
+    pub conversation_id: String,
+    pub content: String,
+    pub timestamp: i64,
+}
+
+/// What the D-Bus methods do. Implemented for the app; tests use a double.
+pub trait AssistantBackend: Send + Sync + 'static {
+    /// Same payload as the `ask` IPC kind: `{prompt, model?, provider?, new?, attachments?}`
+    fn ask(&self, payload: serde_json::Value) -> AppResult<()>;
+    fn notify(&self, message: &str) -> AppResult<()>;
+    fn last(&self) -> AppResult<Option<Message>>;
+    fn toggle(&self) -> AppResult<()>;
+    fn new_conversation(&self) -> AppResult<()>;
+}
+
+impl AssistantBackend for AppHandle {
+    fn ask(&self, payload: serde_json::Value) -> AppResult<()> {
+        self.emit("cli://ask", payload).map_err(AppError::from)
+    }
+
+    fn notify(&self, message: &str) -> AppResult<()> {
+        self.emit("cli://notify", message)?;
+        crate::notifications::notify(
+            self,
+            crate::notifications::Completion::Message {
+                text: message.to_string(),
+            },
+            None,
+        );
+        Ok(())
+    }
+
+    fn last(&self) -> AppResult<Option<Message>> {
+        let db = self.state::<crate::database::Database>();
+        let conn = db.conn().lock()?;
+        crate::commands::messages::get_last_assistant_message_sync(&conn)
+    }
+
+    fn toggle(&self) -> AppResult<()> {
+        crate::commands::window::toggle_main_window(self.clone())
+    }
+
+    fn new_conversation(&self) -> AppResult<()> {
+        if let Some(window) = self.get_webview_window("main") {
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+        self.emit("tray://new-conversation", ())
+            .map_err(AppError::from)
+    }
+}
+
+pub struct AssistantService<B> {
+    backend: B,
+}
+
+/// JSON payload for `Ask` from its `a{sv}` options
+fn ask_payload(prompt: &str, options: &HashMap<String, OwnedValue>) -> serde_json::Value {
+    let string = |key: &str| {
+        options
+            .get(key)
+            .and_then(|v| String::try_from(v.clone()).ok())
+    };
+    let new = options
+        .get("new")
+        .and_then(|v| bool::try_from(v.clone()).ok())
+        .unwrap_or(false);
+    let attachments: Vec<serde_json::Value> = options
+        .get("attachments")
+        .and_then(|v| Vec::<(String, Vec<u8>)>::try_from(v.clone()).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(file_name, data)| {
+            serde_json::json!({
+                "file_name": file_name,
+                "data_base64": attachments::encode_base64(&data),
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "prompt": prompt,
+        "model": string("model"),
+        "provider": string("provider"),
+        "new": new,
+        "attachments": attachments,
+    })
+}
+
+#[zbus::interface(name = "org.linuxai.Assistant")]
+impl<B: AssistantBackend> AssistantService<B> {
+    /// Send a prompt to the chat. Options: `model` (s), `provider` (s),
+    /// `new` (b) to start a new conversation, `attachments` (a(say)) file
+    /// names and contents.
+    async fn ask(
+        &self,
+        prompt: String,
+        options: HashMap<String, OwnedValue>,
+    ) -> Result<(), ServiceError> {
+        if prompt.trim().is_empty() {
+            return Err(ServiceError::InvalidInput("Prompt is empty".to_string()));
+        }
+        Ok(self.backend.ask(ask_payload(&prompt, &options))?)
+    }
+
+    /// Show a toast in the app, or a desktop notification while it's hidden
+    async fn notify(&self, message: String) -> Result<(), ServiceError> {
+        Ok(self.backend.notify(&message)?)
+    }
+
+    /// Latest assistant message of the most recent conversation
+    async fn last(&self) -> Result<LastMessage, ServiceError> {
+        let message = self
+            .backend
+            .last()?
+            .ok_or_else(|| ServiceError::NotFound("No messages found".to_string()))?;
+        Ok(LastMessage {
+            id: message.id,
+            conversation_id: message.conversation_id,
+            content: message.content,
+            timestamp: message.timestamp,
+        })
+    }
+
+    /// Show or hide the main window
+    async fn toggle(&self) -> Result<(), ServiceError> {
+        Ok(self.backend.toggle()?)
+    }
+
+    /// Bring the window up with a new conversation
+    async fn new_conversation(&self) -> Result<(), ServiceError> {
+        Ok(self.backend.new_conversation()?)
+    }
+
+    /// An assistant message was stored
+    #[zbus(signal)]
+    pub async fn message_added(
+        emitter: &SignalEmitter<'_>,
+        conversation_id: &str,
+        message_id: &str,
+        content: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// Export the service on `builder`'s bus and claim the well-known name
+pub async fn serve<B: AssistantBackend>(
+    builder: zbus::connection::Builder<'_>,
+    backend: B,
+) -> zbus::Result<Connection> {
+    builder
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, AssistantService { backend })?
+        .build()
+        .await
+}
+
+/// Emit `MessageAdded` on `conn`
+pub async fn emit_message_added(conn: &Connection, message: &Message) -> zbus::Result<()> {
+    let emitter = SignalEmitter::new(conn, OBJECT_PATH)?;
+    AssistantService::<AppHandle>::message_added(
+        &emitter,
+        &message.conversation_id,
+        &message.id,
+        &message.content,
+    )
+    .await
+}
+
+/// Announce a new assistant message over D-Bus when the service is running
+pub fn message_added(message: &Message) {
+    if message.role != "assistant" {
+        return;
+    }
+    let Some(conn) = CONNECTION.get() else {
+        return;
+    };
+    let message = message.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = emit_message_added(conn, &message).await {
+            eprintln!("D-Bus: failed to emit MessageAdded: {}", e);
+        }
+    });
+}
+
+/// Start the service on the session bus. Failing to connect (no session bus,
+/// or another instance owns the name) leaves the TCP IPC as the only entry point.
+pub fn start_dbus_service(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let builder = match zbus::connection::Builder::session() {
+            Ok(builder) => builder,
+            Err(e) => {
+                eprintln!("D-Bus: no session bus: {}", e);
+                return;
+            }
+        };
+        match serve(builder, app).await {
+            Ok(conn) => {
+                println!("D-Bus: serving {} at {}", BUS_NAME, OBJECT_PATH);
+                let _ = CONNECTION.set(conn);
+            }
+            Err(e) => eprintln!("D-Bus: failed to start {}: {}", BUS_NAME, e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shortcut_portal::tests::TestBus;
+    use futures_util::StreamExt;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Clone, Default)]
+    struct FakeBackend {
+        calls: Arc<Mutex<Vec<String>>>,
+        last: Option<Message>,
+    }
+
+    impl AssistantBackend for FakeBackend {
+        fn ask(&self, payload: serde_json::Value) -> AppResult<()> {
+            self.calls.lock()?.push(format!("ask {}", payload));
+            Ok(())
+        }
+        fn notify(&self, message: &str) -> AppResult<()> {
+            self.calls.lock()?.push(format!("notify {}", message));
+            Ok(())
+        }
+        fn last(&self) -> AppResult<Option<Message>> {
+            Ok(self.last.clone())
+        }
+        fn toggle(&self) -> AppResult<()> {
+            Err(AppError::not_found("Main window not found"))
+        }
+        fn new_conversation(&self) -> AppResult<()> {
+            self.calls.lock()?.push("new".to_string());
+            Ok(())
+        }
+    }
+
+    fn message(id: &str, content: &str) -> Message {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "conversation_id": "conv-1",
+            "role": "assistant",
+            "content": content,
+            "timestamp": 1700000000,
+        }))
+        .unwrap()
+    }
+
+    async fn call<R>(
+        client: &Connection,
+        method: &str,
+        body: &(impl Serialize + Type),
+    ) -> zbus::Result<R>
+    where
+        R: for<'de> Deserialize<'de> + Type,
+    {
+        let reply = client
+            .call_method(Some(BUS_NAME), OBJECT_PATH, Some(BUS_NAME), method, body)
+            .await?;
+        reply.body().deserialize()
+    }
+
+    #[tokio::test]
+    async fn methods_reach_the_backend() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let backend = FakeBackend {
+            last: Some(message("m-1", "hello")),
+            ..Default::default()
+        };
+        let calls = backend.calls.clone();
+        let builder = zbus::connection::Builder::address(bus.address.as_str()).unwrap();
+        let _service = serve(builder, backend).await.unwrap();
+        let client = bus.connect().await;
+
+        let mut options: HashMap<&str, zbus::zvariant::Value> = HashMap::new();
+        options.insert("model", "gpt-4o".into());
+        options.insert("new", true.into());
+        let files = vec![("notes.txt".to_string(), b"hi".to_vec())];
+        options.insert("attachments", files.into());
+        call::<()>(&client, "Ask", &("Explain this", options))
+            .await
+            .unwrap();
+        call::<()>(&client, "Notify", &("done",)).await.unwrap();
+        call::<()>(&client, "NewConversation", &()).await.unwrap();
+
+        let calls = calls.lock().unwrap().clone();
+        assert_eq!(calls.len(), 3);
+        let ask: serde_json::Value =
+            serde_json::from_str(calls[0].strip_prefix("ask ").unwrap()).unwrap();
+        assert_eq!(ask["prompt"], "Explain this");
+        assert_eq!(ask["model"], "gpt-4o");
+        assert_eq!(ask["new"], true);
+        assert_eq!(ask["attachments"][0]["file_name"], "notes.txt");
+        assert_eq!(ask["attachments"][0]["data_base64"], "aGk=");
+        assert_eq!(calls[1], "notify done");
+        assert_eq!(calls[2], "new");
+
+        let last: LastMessage = call(&client, "Last", &()).await.unwrap();
+        assert_eq!(last.id, "m-1");
+        assert_eq!(last.content, "hello");
+
+        let err = call::<()>(&client, "Toggle", &()).await.unwrap_err();
+        match err {
+            zbus::Error::MethodError(name, _, _) => {
+                assert_eq!(name.as_str(), "org.linuxai.Assistant.Error.NotFound")
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(call::<()>(
+            &client,
+            "Ask",
+            &("  ", HashMap::<&str, zbus::zvariant::Value>::new())
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn message_added_signal_reaches_subscribers() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let builder = zbus::connection::Builder::address(bus.address.as_str()).unwrap();
+        let service = serve(builder, FakeBackend::default()).await.unwrap();
+        let client = bus.connect().await;
+
+        let rule = zbus::MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(BUS_NAME)
+            .unwrap()
+            .member("MessageAdded")
+            .unwrap()
+            .build();
+        let mut signals = zbus::MessageStream::for_match_rule(rule, &client, None)
+            .await
+            .unwrap();
+
+        emit_message_added(&service, &message("m-2", "reply"))
+            .await
+            .unwrap();
+        let signal = tokio::time::timeout(Duration::from_secs(5), signals.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let (conversation_id, message_id, content): (String, String, String) =
+            signal.body().deserialize().unwrap();
+        assert_eq!(conversation_id, "conv-1");
+        assert_eq!(message_id, "m-2");
+        assert_eq!(content, "reply");
+    }
+}
@@ -0,0 +1,300 @@
+// Crate-wide error type returned by Tauri commands and IPC handlers. It
+// serializes as `{code, message, details}` so the frontend and the `lai` CLI
+// can branch on a stable `code` instead of matching message text.
+
+use crate::accelerator::AcceleratorError;
+use crate::provider_retry::{ProviderError, ProviderErrorKind};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Stable, machine-readable error codes. Never rename a variant: the frontend
+/// and `lai` exit codes depend on these strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    NotFound,
+    InvalidInput,
+    Database,
+    /// SQLite busy/locked, or a poisoned connection mutex
+    DatabaseLocked,
+    Io,
+    Network,
+    /// Missing or rejected API key
+    Auth,
+    RateLimited,
+    ContextOverflow,
+    /// Any other provider-side failure
+    Provider,
+    BudgetExceeded,
+    /// Unique constraint or other state conflict
+    Conflict,
+    Unsupported,
+    /// The user dismissed a dialog or aborted the operation
+    Cancelled,
+    Internal,
+    /// The encrypted database is locked until the user unlocks it
+    Locked,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::NotFound => "not_found",
+            Self::InvalidInput => "invalid_input",
+            Self::Database => "database",
+            Self::DatabaseLocked => "database_locked",
+            Self::Io => "io",
+            Self::Network => "network",
+            Self::Auth => "auth",
+            Self::RateLimited => "rate_limited",
+            Self::ContextOverflow => "context_overflow",
+            Self::Provider => "provider",
+            Self::BudgetExceeded => "budget_exceeded",
+            Self::Conflict => "conflict",
+            Self::Unsupported => "unsupported",
+            Self::Cancelled => "cancelled",
+            Self::Internal => "internal",
+            Self::Locked => "locked",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Structured context, e.g. the provider and HTTP status of a failed request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Prefix the message with what was being attempted, keeping the code
+    pub fn context(mut self, context: impl fmt::Display) -> Self {
+        self.message = format!("{}: {}", context, self.message);
+        self
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidInput, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Conflict, message)
+    }
+
+    pub fn unsupported(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Unsupported, message)
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Cancelled, message)
+    }
+
+    pub fn auth(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Auth, message)
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Io, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+
+    pub fn locked(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Locked, message)
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(err: rusqlite::Error) -> Self {
+        use rusqlite::ErrorCode as SqliteCode;
+        let message = err.to_string();
+        let code = match &err {
+            rusqlite::Error::QueryReturnedNoRows => ErrorCode::NotFound,
+            rusqlite::Error::SqliteFailure(e, _) => match e.code {
+                SqliteCode::DatabaseBusy | SqliteCode::DatabaseLocked => ErrorCode::DatabaseLocked,
+                SqliteCode::ConstraintViolation => ErrorCode::Conflict,
+                // Model helpers report missing rows as SQLITE_FAIL "... not found"
+                _ if message.to_lowercase().contains("not found") => ErrorCode::NotFound,
+                _ => ErrorCode::Database,
+            },
+            rusqlite::Error::InvalidPath(path) if path.to_string_lossy().contains("not found") => {
+                ErrorCode::NotFound
+            }
+            _ => ErrorCode::Database,
+        };
+        Self::new(code, message)
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for AppError {
+    fn from(err: std::sync::PoisonError<T>) -> Self {
+        Self::new(ErrorCode::DatabaseLocked, err.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        let code = match err.kind() {
+            std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+            _ => ErrorCode::Io,
+        };
+        Self::new(code, err.to_string())
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(err: reqwest::Error) -> Self {
+        let code = if err.is_connect() || err.is_timeout() || err.is_request() {
+            ErrorCode::Network
+        } else {
+            ErrorCode::Provider
+        };
+        let mut error = Self::new(code, err.to_string());
+        if let Some(status) = err.status() {
+            error = error.with_details(serde_json::json!({ "status": status.as_u16() }));
+        }
+        error
+    }
+}
+
+impl From<keyring::Error> for AppError {
+    fn from(err: keyring::Error) -> Self {
+        let code = match err {
+            keyring::Error::NoEntry => ErrorCode::NotFound,
+            keyring::Error::PlatformFailure(_) | keyring::Error::NoStorageAccess(_) => {
+                ErrorCode::Unsupported
+            }
+            _ => ErrorCode::Internal,
+        };
+        Self::new(code, err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::invalid_input(err.to_string())
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(err: tauri::Error) -> Self {
+        Self::internal(err.to_string())
+    }
+}
+
+impl From<ProviderError> for AppError {
+    fn from(err: ProviderError) -> Self {
+        let code = match err.kind {
+            ProviderErrorKind::Auth => ErrorCode::Auth,
+            ProviderErrorKind::RateLimit => ErrorCode::RateLimited,
+            ProviderErrorKind::ContextOverflow => ErrorCode::ContextOverflow,
+            ProviderErrorKind::Network => ErrorCode::Network,
+            ProviderErrorKind::Budget => ErrorCode::BudgetExceeded,
+            ProviderErrorKind::Server | ProviderErrorKind::InvalidRequest => ErrorCode::Provider,
+        };
+        Self::new(code, err.to_string()).with_details(serde_json::json!({
+            "provider": err.provider,
+            "status": err.status,
+            "kind": err.kind,
+        }))
+    }
+}
+
+impl From<AcceleratorError> for AppError {
+    fn from(err: AcceleratorError) -> Self {
+        Self::invalid_input(err.to_string())
+    }
+}
+
+impl From<AppError> for String {
+    fn from(err: AppError) -> Self {
+        err.message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_sqlite_errors() {
+        let not_found: AppError = rusqlite::Error::QueryReturnedNoRows.into();
+        assert_eq!(not_found.code, ErrorCode::NotFound);
+
+        let failure = |code, msg: &str| {
+            AppError::from(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(code),
+                Some(msg.to_string()),
+            ))
+            .code
+        };
+        assert_eq!(
+            failure(rusqlite::ffi::SQLITE_BUSY, "database is locked"),
+            ErrorCode::DatabaseLocked
+        );
+        assert_eq!(
+            failure(rusqlite::ffi::SQLITE_CONSTRAINT, "UNIQUE constraint failed"),
+            ErrorCode::Conflict
+        );
+        assert_eq!(
+            failure(rusqlite::ffi::SQLITE_FAIL, "Profile not found"),
+            ErrorCode::NotFound
+        );
+        assert_eq!(
+            AppError::from(rusqlite::Error::InvalidPath("Message not found".into())).code,
+            ErrorCode::NotFound
+        );
+    }
+
+    #[test]
+    fn serializes_code_message_and_details() {
+        let err: AppError = ProviderError::from_status("openai", 429, "slow down", None).into();
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["code"], "rate_limited");
+        assert_eq!(json["details"]["provider"], "openai");
+        assert_eq!(json["details"]["status"], 429);
+
+        let plain = serde_json::to_value(AppError::not_found("Tag not found")).unwrap();
+        assert_eq!(
+            plain,
+            serde_json::json!({ "code": "not_found", "message": "Tag not found" })
+        );
+    }
+}
@@ -0,0 +1,639 @@
+// Exports into a directory, one file per conversation. A manifest in the
+// directory records the file each conversation went to and a revision hash of
+// what was rendered, so a later run only rewrites conversations that changed,
+// renames files after a title change and removes the files of deleted
+// conversations. The scheduled mirror repeats the export on an interval, e.g.
+// into a git-tracked notes folder.
+
+use super::{Document, ExportFormat};
+use crate::attachments::BlobStore;
+use crate::database::{
+    attachments::Attachment, conversations::Conversation, messages::Message, settings::Setting,
+    tags::Tag, Database,
+};
+use crate::error::{AppError, AppResult, ErrorCode};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Written next to the exported files; only files it lists are ever removed
+pub const MANIFEST_FILE: &str = ".lai-export.json";
+const MIRROR_SETTINGS_KEY: &str = "export_mirror";
+/// How often the mirror checks whether a run is due
+const MIRROR_POLL: Duration = Duration::from_secs(60);
+const MAX_SLUG_CHARS: usize = 60;
+
+/// Which conversations to export; unset fields don't filter
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportFilter {
+    pub conversation_ids: Option<Vec<String>>,
+    /// Tag name
+    pub tag: Option<String>,
+    /// Case-insensitive text the title must contain
+    pub query: Option<String>,
+    /// Last update within a range as accepted by usage reports: `week`,
+    /// `30d`, `2024-01-01..2024-03-31`, ...
+    pub range: Option<String>,
+}
+
+impl ExportFilter {
+    /// The matching conversations, most recently updated first
+    pub fn select(&self, conn: &Connection) -> AppResult<Vec<Conversation>> {
+        let mut conversations = Conversation::get_all(conn, i64::MAX)
+            .map_err(|e| AppError::from(e).context("Failed to get conversations"))?;
+        if let Some(ids) = &self.conversation_ids {
+            conversations.retain(|c| ids.contains(&c.id));
+        }
+        if let Some(name) = self.tag.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+            let tagged = match Tag::get_by_name(conn, name)? {
+                Some(tag) => Tag::get_conversations_with_tag(conn, &tag.id)?,
+                None => Vec::new(),
+            };
+            conversations.retain(|c| tagged.contains(&c.id));
+        }
+        if let Some(query) = self
+            .query
+            .as_deref()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+        {
+            let query = query.to_lowercase();
+            conversations.retain(|c| c.title.to_lowercase().contains(&query));
+        }
+        if let Some(range) = self.range.as_deref().filter(|r| !r.trim().is_empty()) {
+            let (from, to) = crate::usage::parse_range(range, chrono::Utc::now())?;
+            conversations.retain(|c| c.updated_at >= from && c.updated_at < to);
+        }
+        Ok(conversations)
+    }
+}
+
+/// What a directory export changed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DirectoryExport {
+    pub directory: String,
+    /// Files of new or changed conversations
+    pub written: Vec<String>,
+    /// Conversations whose file was already up to date
+    pub unchanged: usize,
+    /// Old names of renamed files and files of deleted conversations
+    pub removed: Vec<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Manifest {
+    /// Conversation id to its file
+    #[serde(default)]
+    conversations: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct ManifestEntry {
+    file: String,
+    revision: String,
+}
+
+impl Manifest {
+    /// The directory's manifest. A missing or unreadable one is treated as
+    /// empty, which rewrites every file and removes nothing. Entries that
+    /// aren't a plain file name in `dir` are dropped, since their files get
+    /// removed.
+    fn load(dir: &Path) -> Self {
+        let mut manifest: Self = fs::read(dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        manifest
+            .conversations
+            .retain(|_, entry| is_plain_file_name(&entry.file));
+        manifest
+    }
+
+    fn save(&self, dir: &Path) -> AppResult<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        write_atomic(dir, MANIFEST_FILE, format!("{}\n", json).as_bytes())
+    }
+}
+
+/// Whether `name` is a single file name other than the manifest's, e.g. not
+/// `../notes.md`, `/etc/passwd` or `sub/dir.md`
+fn is_plain_file_name(name: &str) -> bool {
+    name != MANIFEST_FILE && Path::new(name).file_name() == Some(OsStr::new(name))
+}
+
+/// File name for a conversation: its title as a slug plus the start of its id,
+/// so names stay put across runs and two conversations never share one
+pub fn file_name(conversation: &Conversation, format: ExportFormat) -> String {
+    let mut slug = String::new();
+    for c in conversation.title.chars().flat_map(char::to_lowercase) {
+        if slug.chars().count() >= MAX_SLUG_CHARS {
+            break;
+        }
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    let id: String = conversation
+        .id
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .take(8)
+        .collect();
+    format!(
+        "{}-{}.{}",
+        if slug.is_empty() {
+            "conversation"
+        } else {
+            slug
+        },
+        id,
+        format.extension()
+    )
+}
+
+/// Hash of everything a renderer sees, so a file is rewritten whenever its
+/// output could differ (new messages, a switched branch, retagging, ...). The
+/// rolling summary isn't rendered and is left out.
+fn revision(format: ExportFormat, doc: &Document) -> AppResult<String> {
+    type RenderedMessage<'a> = (&'a Message, Option<(usize, usize)>, &'a [Attachment]);
+
+    #[derive(Serialize)]
+    struct Rendered<'a> {
+        format: ExportFormat,
+        conversation: &'a Conversation,
+        messages: Vec<RenderedMessage<'a>>,
+        active_leaf_id: &'a Option<String>,
+        tags: &'a [Tag],
+        profile: Option<&'a str>,
+        template: Option<&'a str>,
+    }
+
+    let conversation = Conversation {
+        summary: None,
+        summary_message_id: None,
+        ..doc.conversation.clone()
+    };
+    let rendered = Rendered {
+        format,
+        conversation: &conversation,
+        messages: doc
+            .messages
+            .iter()
+            .map(|m| (&m.message, m.branch, m.attachments.as_slice()))
+            .collect(),
+        active_leaf_id: &doc.active_leaf_id,
+        tags: &doc.tags,
+        profile: doc.profile.as_ref().map(|p| p.name.as_str()),
+        template: doc.template.as_ref().map(|t| t.name.as_str()),
+    };
+    let json = serde_json::to_vec(&rendered)?;
+    Ok(crate::attachments::hex_digest(&json))
+}
+
+/// Write through a temporary file in the same directory so readers (or a
+/// commit) never see a half-written file
+fn write_atomic(dir: &Path, name: &str, bytes: &[u8]) -> AppResult<()> {
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(bytes)?;
+    file.persist(dir.join(name))
+        .map_err(|e| AppError::from(e.error).context(format!("Failed to write {}", name)))?;
+    Ok(())
+}
+
+fn remove(dir: &Path, name: &str) -> AppResult<()> {
+    match fs::remove_file(dir.join(name)) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(AppError::from(e).context(format!("Failed to remove {}", name)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Export the conversations matching `filter` into `dir`, one file each,
+/// rewriting only those that changed since the last export into `dir`
+pub fn export_to_directory(
+    conn: &Connection,
+    blobs: &BlobStore,
+    dir: &Path,
+    format: ExportFormat,
+    filter: &ExportFilter,
+    whole_tree: bool,
+) -> AppResult<DirectoryExport> {
+    fs::create_dir_all(dir)
+        .map_err(|e| AppError::from(e).context(format!("Failed to create {}", dir.display())))?;
+    let renderer = format.renderer();
+    let whole_tree = whole_tree || renderer.whole_tree();
+    let mut manifest = Manifest::load(dir);
+    let mut result = DirectoryExport {
+        directory: dir.display().to_string(),
+        ..Default::default()
+    };
+
+    for conversation in filter.select(conn)? {
+        let file = file_name(&conversation, format);
+        let doc = Document::load(conn, conversation, whole_tree)?;
+        let entry = ManifestEntry {
+            file,
+            revision: revision(format, &doc)?,
+        };
+        let previous = manifest.conversations.get(&doc.conversation.id);
+        if previous == Some(&entry) && dir.join(&entry.file).exists() {
+            result.unchanged += 1;
+            continue;
+        }
+        if let Some(old) = previous.filter(|old| old.file != entry.file) {
+            remove(dir, &old.file)?;
+            result.removed.push(old.file.clone());
+        }
+
+        let bytes = renderer.render(blobs, std::slice::from_ref(&doc))?;
+        write_atomic(dir, &entry.file, &bytes)?;
+        result.written.push(entry.file.clone());
+        manifest
+            .conversations
+            .insert(doc.conversation.id.clone(), entry);
+    }
+
+    // Conversations filtered out keep their files; deleted ones lose them
+    let live: HashSet<String> = Conversation::get_all(conn, i64::MAX)?
+        .into_iter()
+        .map(|c| c.id)
+        .collect();
+    let mut deleted = Vec::new();
+    manifest.conversations.retain(|id, entry| {
+        let keep = live.contains(id);
+        if !keep {
+            deleted.push(entry.file.clone());
+        }
+        keep
+    });
+    for file in deleted {
+        remove(dir, &file)?;
+        result.removed.push(file);
+    }
+
+    if !result.written.is_empty() || !result.removed.is_empty() {
+        manifest.save(dir)?;
+    }
+    Ok(result)
+}
+
+/// The scheduled mirror into a directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorSettings {
+    pub enabled: bool,
+    /// Absolute path of the directory to mirror into
+    pub directory: Option<String>,
+    pub format: ExportFormat,
+    pub filter: ExportFilter,
+    pub whole_tree: bool,
+    /// Minutes between runs
+    pub interval_minutes: u32,
+    /// Unix time of the last run
+    pub last_run: Option<i64>,
+    /// Why the last run failed
+    pub last_error: Option<String>,
+}
+
+impl Default for MirrorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: None,
+            format: ExportFormat::Markdown,
+            filter: ExportFilter::default(),
+            whole_tree: false,
+            interval_minutes: 60,
+            last_run: None,
+            last_error: None,
+        }
+    }
+}
+
+impl MirrorSettings {
+    pub fn load(conn: &Connection) -> AppResult<Self> {
+        Ok(Setting::get_json(conn, MIRROR_SETTINGS_KEY)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, conn: &Connection) -> AppResult<()> {
+        Ok(Setting::set_json(conn, MIRROR_SETTINGS_KEY, self)?)
+    }
+
+    /// Whether a scheduled run is due at unix time `now`
+    pub fn is_due(&self, now: i64) -> bool {
+        self.enabled
+            && self.directory.is_some()
+            && self.last_run.map_or(true, |last| {
+                now - last >= i64::from(self.interval_minutes) * 60
+            })
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        if self.interval_minutes == 0 {
+            return Err(AppError::invalid_input(
+                "Mirror interval must be at least a minute",
+            ));
+        }
+        match self.directory.as_deref() {
+            Some(dir) if !Path::new(dir).is_absolute() => Err(AppError::invalid_input(
+                "Mirror directory must be an absolute path",
+            )),
+            None if self.enabled => Err(AppError::invalid_input(
+                "Choose a directory to mirror conversations into",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Export into the mirror directory now and record the outcome
+    pub fn run(
+        &mut self,
+        conn: &Connection,
+        blobs: &BlobStore,
+        now: i64,
+    ) -> AppResult<DirectoryExport> {
+        let dir = self
+            .directory
+            .clone()
+            .ok_or_else(|| AppError::invalid_input("No mirror directory configured"))?;
+        let result = export_to_directory(
+            conn,
+            blobs,
+            Path::new(&dir),
+            self.format,
+            &self.filter,
+            self.whole_tree,
+        );
+        self.last_run = Some(now);
+        self.last_error = result.as_ref().err().map(|e| e.message.clone());
+        self.save(conn)?;
+        result
+    }
+}
+
+/// Run the mirror whenever it is due. `export://mirrored` carries the
+/// `DirectoryExport` of runs that changed something.
+pub fn start_mirror(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        let db = app.state::<Database>();
+        let blobs = app.state::<BlobStore>();
+        let now = chrono::Utc::now().timestamp();
+        let outcome = db.conn().lock_in_background().and_then(|conn| {
+            let mut settings = MirrorSettings::load(&conn)?;
+            if !settings.is_due(now) {
+                return Ok(None);
+            }
+            settings.run(&conn, &blobs, now).map(Some)
+        });
+        match outcome {
+            Ok(Some(result)) if !result.written.is_empty() || !result.removed.is_empty() => {
+                let _ = app.emit("export://mirrored", &result);
+            }
+            Ok(_) => {}
+            // Resumes once the database is unlocked
+            Err(e) if e.code == ErrorCode::Locked => {}
+            Err(e) => eprintln!("export mirror failed: {}", e),
+        }
+        std::thread::sleep(MIRROR_POLL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::conversations::NewConversation;
+    use crate::database::messages::NewMessage;
+    use crate::exporters::tests::fixture;
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn file_names_are_slugs_with_the_id() {
+        let (db, _blobs, _dir, mut conv) = fixture();
+        drop(db);
+        conv.id = "1a2b3c4d-5e6f-0000-0000-000000000000".to_string();
+        assert_eq!(
+            file_name(&conv, ExportFormat::Markdown),
+            "sorting-in-rust-1a2b3c4d.md"
+        );
+        conv.title = "¿Qué es «Rust»?  ".to_string();
+        assert_eq!(
+            file_name(&conv, ExportFormat::Org),
+            "qué-es-rust-1a2b3c4d.org"
+        );
+        conv.title = "!!!".to_string();
+        assert_eq!(
+            file_name(&conv, ExportFormat::Text),
+            "conversation-1a2b3c4d.txt"
+        );
+    }
+
+    #[test]
+    fn exports_are_incremental_and_follow_renames_and_deletions() {
+        let (db, blobs, _blob_dir, conv) = fixture();
+        let out = tempfile::tempdir().unwrap();
+        let conn = db.conn().lock().unwrap();
+        let other = Conversation::create(
+            &conn,
+            NewConversation {
+                title: "Shell tricks".to_string(),
+                model: "llama3".to_string(),
+                provider: "ollama".to_string(),
+                system_prompt: None,
+            },
+        )
+        .unwrap();
+        let export = |filter: &ExportFilter| {
+            export_to_directory(
+                &conn,
+                &blobs,
+                out.path(),
+                ExportFormat::Markdown,
+                filter,
+                false,
+            )
+            .unwrap()
+        };
+
+        let first = export(&ExportFilter::default());
+        assert_eq!(first.written.len(), 2);
+        assert_eq!(files(out.path()).len(), 3);
+        assert!(files(out.path()).contains(&MANIFEST_FILE.to_string()));
+
+        let again = export(&ExportFilter::default());
+        assert!(again.written.is_empty() && again.removed.is_empty());
+        assert_eq!(again.unchanged, 2);
+
+        // A new message rewrites only that conversation
+        Message::create(
+            &conn,
+            NewMessage {
+                conversation_id: other.id.clone(),
+                role: "user".to_string(),
+                content: "How do I find large files?".to_string(),
+                tokens_used: None,
+            },
+        )
+        .unwrap();
+        let changed = export(&ExportFilter::default());
+        assert_eq!(
+            changed.written,
+            vec![file_name(&other, ExportFormat::Markdown)]
+        );
+        assert_eq!(changed.unchanged, 1);
+
+        // A new title moves the file
+        Conversation::update_title(&conn, &conv.id, "Sorting vectors").unwrap();
+        let renamed = export(&ExportFilter::default());
+        assert_eq!(
+            renamed.removed,
+            vec![file_name(&conv, ExportFormat::Markdown)]
+        );
+        assert!(renamed.written[0].starts_with("sorting-vectors-"));
+
+        // Filtered-out conversations keep their files, deleted ones don't
+        let tagged = export(&ExportFilter {
+            tag: Some("rust".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(tagged.unchanged, 1);
+        assert!(tagged.removed.is_empty());
+        Conversation::delete(&conn, &other.id).unwrap();
+        let pruned = export(&ExportFilter::default());
+        assert_eq!(
+            pruned.removed,
+            vec![file_name(&other, ExportFormat::Markdown)]
+        );
+        assert_eq!(files(out.path()).len(), 2);
+    }
+
+    #[test]
+    fn manifest_entries_outside_the_directory_are_ignored() {
+        let (db, blobs, dir, conv) = fixture();
+        let conn = db.conn().lock().unwrap();
+        let out = dir.path().join("out");
+        fs::create_dir_all(&out).unwrap();
+        let victim = dir.path().join("victim.txt");
+        fs::write(&victim, "keep me").unwrap();
+        let victim_path = victim.display().to_string();
+        let manifest = serde_json::json!({
+            "conversations": {
+                conv.id.clone(): {"file": "../victim.txt", "revision": "old"},
+                "deleted-1": {"file": victim_path, "revision": "old"},
+                "deleted-2": {"file": MANIFEST_FILE, "revision": "old"},
+            }
+        });
+        fs::write(out.join(MANIFEST_FILE), manifest.to_string()).unwrap();
+
+        let result = export_to_directory(
+            &conn,
+            &blobs,
+            &out,
+            ExportFormat::Markdown,
+            &ExportFilter::default(),
+            false,
+        )
+        .unwrap();
+        assert!(result.removed.is_empty());
+        assert_eq!(
+            result.written,
+            vec![file_name(&conv, ExportFormat::Markdown)]
+        );
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "keep me");
+        assert!(out.join(MANIFEST_FILE).exists());
+
+        assert!(is_plain_file_name("notes-1a2b3c4d.md"));
+        for name in ["", ".", "..", "../x.md", "/tmp/x.md", "sub/x.md", "x.md/"] {
+            assert!(!is_plain_file_name(name), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn filters_select_by_tag_title_and_id() {
+        let (db, _blobs, _dir, conv) = fixture();
+        let conn = db.conn().lock().unwrap();
+        Conversation::create(
+            &conn,
+            NewConversation {
+                title: "Other".to_string(),
+                model: "m".to_string(),
+                provider: "p".to_string(),
+                system_prompt: None,
+            },
+        )
+        .unwrap();
+
+        let count = |filter: ExportFilter| filter.select(&conn).unwrap().len();
+        assert_eq!(count(ExportFilter::default()), 2);
+        assert_eq!(
+            count(ExportFilter {
+                tag: Some("rust".to_string()),
+                ..Default::default()
+            }),
+            1
+        );
+        assert_eq!(
+            count(ExportFilter {
+                query: Some("SORTING".to_string()),
+                ..Default::default()
+            }),
+            1
+        );
+        assert_eq!(
+            count(ExportFilter {
+                conversation_ids: Some(vec![conv.id.clone()]),
+                ..Default::default()
+            }),
+            1
+        );
+        assert_eq!(
+            count(ExportFilter {
+                range: Some("7d".to_string()),
+                ..Default::default()
+            }),
+            2
+        );
+    }
+
+    #[test]
+    fn mirror_runs_when_due_and_records_the_outcome() {
+        let (db, blobs, _dir, _conv) = fixture();
+        let out = tempfile::tempdir().unwrap();
+        let conn = db.conn().lock().unwrap();
+
+        let mut settings = MirrorSettings {
+            enabled: true,
+            directory: Some("notes".to_string()),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+        settings.directory = Some(out.path().display().to_string());
+        settings.validate().unwrap();
+        assert!(settings.is_due(1_000));
+
+        let result = settings.run(&conn, &blobs, 1_000).unwrap();
+        assert_eq!(result.written.len(), 1);
+        let stored = MirrorSettings::load(&conn).unwrap();
+        assert_eq!(stored.last_run, Some(1_000));
+        assert!(!stored.is_due(1_000 + 59 * 60));
+        assert!(stored.is_due(1_000 + 60 * 60));
+    }
+}
@@ -0,0 +1,460 @@
+// EPUB 3 export for e-readers: one chapter per conversation, a navigation
+// document plus an NCX table of contents for older readers, and image
+// attachments packaged alongside the chapters. The output only depends on the
+// conversations, so exporting the same data twice gives identical files.
+
+use super::{export_title, format_time, markdown_options, role_name, Document, Renderer};
+use crate::attachments::BlobStore;
+use crate::error::{AppError, AppResult};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const STYLE: &str = r#"body { font-family: serif; line-height: 1.5; margin: 0 0.5em; }
+h1 { font-size: 1.6em; margin-bottom: 0.2em; }
+h2 { font-size: 1.15em; margin: 1.5em 0 0.3em; border-bottom: 1px solid #ccc; }
+.meta, .time, .notes { color: #666; font-size: 0.85em; margin: 0.2em 0; }
+.branch { color: #666; font-weight: normal; font-size: 0.85em; }
+pre { font-family: monospace; font-size: 0.85em; white-space: pre-wrap; background: #f4f4f4; padding: 0.5em; }
+code { font-family: monospace; }
+blockquote { border-left: 3px solid #ccc; margin-left: 0; padding-left: 1em; color: #555; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.5em; }
+figure { margin: 1em 0; text-align: center; }
+img { max-width: 100%; }
+"#;
+
+pub struct EpubRenderer;
+
+impl Renderer for EpubRenderer {
+    fn render(&self, blobs: &BlobStore, documents: &[Document]) -> AppResult<Vec<u8>> {
+        let title = export_title(documents);
+        // Images keyed by blob hash so shared attachments are packaged once
+        let mut images = BTreeMap::new();
+        let chapters = documents
+            .iter()
+            .enumerate()
+            .map(|(i, doc)| {
+                Ok(Chapter {
+                    file: format!("chapter-{:03}.xhtml", i + 1),
+                    title: doc.conversation.title.clone(),
+                    body: chapter(blobs, doc, &mut images)?,
+                })
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+
+        let mut ids = Sha256::new();
+        for doc in documents {
+            ids.update(doc.conversation.id.as_bytes());
+            ids.update([0]);
+        }
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&ids.finalize()[..16]);
+        let identifier = format!(
+            "urn:uuid:{}",
+            uuid::Builder::from_sha1_bytes(bytes).into_uuid()
+        );
+        let modified = documents
+            .iter()
+            .map(|doc| doc.conversation.updated_at)
+            .max()
+            .and_then(|seconds| chrono::DateTime::from_timestamp(seconds, 0))
+            .map(|dt| dt.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+            .unwrap_or_else(|| "1970-01-01T00:00:00Z".to_string());
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        // The mimetype must come first and be stored uncompressed so readers
+        // can identify the file from its first bytes
+        add(
+            &mut zip,
+            "mimetype",
+            b"application/epub+zip",
+            CompressionMethod::Stored,
+        )?;
+        add(
+            &mut zip,
+            "META-INF/container.xml",
+            CONTAINER.as_bytes(),
+            CompressionMethod::Deflated,
+        )?;
+        add(
+            &mut zip,
+            "OEBPS/content.opf",
+            package(&title, &identifier, &modified, &chapters, &images).as_bytes(),
+            CompressionMethod::Deflated,
+        )?;
+        add(
+            &mut zip,
+            "OEBPS/nav.xhtml",
+            navigation(&title, &chapters).as_bytes(),
+            CompressionMethod::Deflated,
+        )?;
+        add(
+            &mut zip,
+            "OEBPS/toc.ncx",
+            ncx(&title, &identifier, &chapters).as_bytes(),
+            CompressionMethod::Deflated,
+        )?;
+        add(
+            &mut zip,
+            "OEBPS/style.css",
+            STYLE.as_bytes(),
+            CompressionMethod::Deflated,
+        )?;
+        for chapter in &chapters {
+            add(
+                &mut zip,
+                &format!("OEBPS/{}", chapter.file),
+                xhtml(&chapter.title, &chapter.body).as_bytes(),
+                CompressionMethod::Deflated,
+            )?;
+        }
+        for (sha256, image) in &images {
+            let bytes = blobs.get(sha256)?;
+            // Images are already compressed
+            add(
+                &mut zip,
+                &format!("OEBPS/{}", image.href),
+                &bytes,
+                CompressionMethod::Stored,
+            )?;
+        }
+
+        let cursor = zip
+            .finish()
+            .map_err(|e| AppError::internal(format!("Failed to write EPUB: {}", e)))?;
+        Ok(cursor.into_inner())
+    }
+}
+
+struct Chapter {
+    file: String,
+    title: String,
+    body: String,
+}
+
+struct Image {
+    href: String,
+    mime_type: String,
+}
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+fn add(
+    zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+    name: &str,
+    bytes: &[u8],
+    method: CompressionMethod,
+) -> AppResult<()> {
+    let options = SimpleFileOptions::default().compression_method(method);
+    zip.start_file(name, options)
+        .map_err(|e| AppError::internal(format!("Failed to write EPUB: {}", e)))?;
+    zip.write_all(bytes)?;
+    Ok(())
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// File extension for an image type, as packaged in the book
+fn image_extension(mime_type: &str) -> &str {
+    match mime_type {
+        "image/jpeg" => "jpg",
+        "image/svg+xml" => "svg",
+        other => other.strip_prefix("image/").unwrap_or("bin"),
+    }
+}
+
+/// Message HTML from comrak, made well-formed XML. comrak writes void
+/// elements self-closed already; only the footnote markers use attributes
+/// without values.
+fn message_html(markdown: &str) -> String {
+    comrak::markdown_to_html(markdown, &markdown_options())
+        .replace(" data-footnotes>", " data-footnotes=\"\">")
+        .replace(" data-footnote-ref>", " data-footnote-ref=\"\">")
+        .replace(" data-footnote-backref ", " data-footnote-backref=\"\" ")
+}
+
+fn chapter(
+    blobs: &BlobStore,
+    doc: &Document,
+    images: &mut BTreeMap<String, Image>,
+) -> AppResult<String> {
+    let conversation = &doc.conversation;
+    let mut body = format!("<h1>{}</h1>\n", xml_escape(&conversation.title));
+    body.push_str(&format!(
+        "<p class=\"meta\">Provider: {} · Model: {} · Created: {}</p>\n",
+        xml_escape(&conversation.provider),
+        xml_escape(&conversation.model),
+        format_time(conversation.created_at)
+    ));
+    if !doc.tags.is_empty() {
+        let names: Vec<String> = doc.tags.iter().map(|t| xml_escape(&t.name)).collect();
+        body.push_str(&format!(
+            "<p class=\"meta\">Tags: {}</p>\n",
+            names.join(", ")
+        ));
+    }
+
+    for entry in &doc.messages {
+        let msg = &entry.message;
+        body.push_str(&format!(
+            "<section class=\"message {}\">\n<h2>{}",
+            xml_escape(&msg.role),
+            xml_escape(role_name(&msg.role))
+        ));
+        if let Some((index, count)) = entry.branch {
+            body.push_str(&format!(
+                " <span class=\"branch\">branch {} of {}</span>",
+                index, count
+            ));
+        }
+        body.push_str("</h2>\n");
+        body.push_str(&format!(
+            "<p class=\"time\">{}</p>\n",
+            format_time(msg.timestamp)
+        ));
+        body.push_str(&message_html(&msg.content));
+
+        for attachment in &entry.attachments {
+            let name = xml_escape(&attachment.file_name);
+            if attachment.kind == "image" {
+                let image = images
+                    .entry(attachment.sha256.clone())
+                    .or_insert_with(|| Image {
+                        href: format!(
+                            "images/{}.{}",
+                            attachment.sha256,
+                            image_extension(&attachment.mime_type)
+                        ),
+                        mime_type: attachment.mime_type.clone(),
+                    });
+                body.push_str(&format!(
+                    "<figure><img src=\"{}\" alt=\"{}\"/><figcaption>{}</figcaption></figure>\n",
+                    image.href, name, name
+                ));
+            } else {
+                let bytes = blobs.get(&attachment.sha256)?;
+                body.push_str(&format!(
+                    "<p class=\"notes\">Attachment: {}</p>\n<pre><code>{}</code></pre>\n",
+                    name,
+                    xml_escape(String::from_utf8_lossy(&bytes).trim_end())
+                ));
+            }
+        }
+        if let Some(tokens) = msg.tokens_used {
+            body.push_str(&format!("<p class=\"notes\">Tokens used: {}</p>\n", tokens));
+        }
+        body.push_str("</section>\n");
+    }
+    Ok(body)
+}
+
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="en" lang="en">
+<head>
+<meta charset="UTF-8"/>
+<title>{}</title>
+<link rel="stylesheet" type="text/css" href="style.css"/>
+</head>
+<body>
+{}</body>
+</html>
+"#,
+        xml_escape(title),
+        body
+    )
+}
+
+fn package(
+    title: &str,
+    identifier: &str,
+    modified: &str,
+    chapters: &[Chapter],
+    images: &BTreeMap<String, Image>,
+) -> String {
+    let mut manifest = String::from(
+        "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+         \x20   <item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/>\n\
+         \x20   <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+    );
+    let mut spine = String::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        manifest.push_str(&format!(
+            "    <item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            i + 1,
+            chapter.file
+        ));
+        spine.push_str(&format!("    <itemref idref=\"chapter-{}\"/>\n", i + 1));
+    }
+    for (i, image) in images.values().enumerate() {
+        manifest.push_str(&format!(
+            "    <item id=\"image-{}\" href=\"{}\" media-type=\"{}\"/>\n",
+            i + 1,
+            image.href,
+            xml_escape(&image.mime_type)
+        ));
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="en">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">{}</dc:identifier>
+    <dc:title>{}</dc:title>
+    <dc:language>en</dc:language>
+    <dc:creator>Linux AI Assistant</dc:creator>
+    <meta property="dcterms:modified">{}</meta>
+  </metadata>
+  <manifest>
+{}  </manifest>
+  <spine toc="ncx">
+{}  </spine>
+</package>
+"#,
+        identifier,
+        xml_escape(title),
+        modified,
+        manifest,
+        spine
+    )
+}
+
+fn navigation(title: &str, chapters: &[Chapter]) -> String {
+    let items: String = chapters
+        .iter()
+        .map(|c| {
+            format!(
+                "      <li><a href=\"{}\">{}</a></li>\n",
+                c.file,
+                xml_escape(&c.title)
+            )
+        })
+        .collect();
+    xhtml(
+        title,
+        &format!(
+            "<nav epub:type=\"toc\" id=\"toc\">\n  <h1>Contents</h1>\n  <ol>\n{}  </ol>\n</nav>\n",
+            items
+        ),
+    )
+}
+
+fn ncx(title: &str, identifier: &str, chapters: &[Chapter]) -> String {
+    let points: String = chapters
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            format!(
+                "    <navPoint id=\"nav-{n}\" playOrder=\"{n}\">\n      <navLabel><text>{}</text></navLabel>\n      <content src=\"{}\"/>\n    </navPoint>\n",
+                xml_escape(&c.title),
+                c.file,
+                n = i + 1
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <head>
+    <meta name="dtb:uid" content="{}"/>
+  </head>
+  <docTitle><text>{}</text></docTitle>
+  <navMap>
+{}  </navMap>
+</ncx>
+"#,
+        identifier,
+        xml_escape(title),
+        points
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::tests::fixture;
+    use crate::exporters::{export, ExportFormat};
+    use std::io::Read;
+
+    #[test]
+    fn books_are_valid_zip_containers_with_a_chapter_per_conversation() {
+        let (db, blobs, _dir, conv) = fixture();
+        let conn = db.conn().lock().unwrap();
+        let bytes = export(
+            &conn,
+            &blobs,
+            vec![conv.clone(), conv.clone()],
+            ExportFormat::Epub,
+            false,
+        )
+        .unwrap();
+
+        // Readers sniff the uncompressed mimetype at a fixed offset
+        assert_eq!(&bytes[30..38], b"mimetype");
+        assert_eq!(&bytes[38..58], b"application/epub+zip");
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        let mut read = |name: &str| {
+            let mut text = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            text
+        };
+        let opf = read("OEBPS/content.opf");
+        assert!(opf.contains("<dc:title>AI Conversations Export</dc:title>"));
+        assert!(opf.contains("<itemref idref=\"chapter-2\"/>"));
+        assert!(
+            read("OEBPS/nav.xhtml").contains("<a href=\"chapter-001.xhtml\">Sorting in Rust</a>")
+        );
+        let chapter = read("OEBPS/chapter-001.xhtml");
+        assert!(chapter.contains("<h1>Sorting in Rust</h1>"));
+        assert!(chapter.contains("<p>Try <code>sort_unstable</code>.</p>"));
+        assert!(chapter.contains("<pre><code>fn main() {}</code></pre>"));
+        assert!(read("META-INF/container.xml").contains("OEBPS/content.opf"));
+
+        let again = export(
+            &conn,
+            &blobs,
+            vec![conv.clone(), conv],
+            ExportFormat::Epub,
+            false,
+        )
+        .unwrap();
+        assert_eq!(bytes, again);
+    }
+
+    #[test]
+    fn footnote_markup_is_well_formed() {
+        let html = message_html("Note[^1].\n\n[^1]: Detail.");
+        assert!(html.contains("data-footnotes=\"\""));
+        assert!(html.contains("data-footnote-ref=\"\""));
+        assert!(!html.contains("data-footnote-backref "));
+    }
+}
@@ -0,0 +1,98 @@
+// The native JSON export: lossless, with every branch and attachment contents,
+// and the format `importers` reads back.
+
+use super::{Document, Renderer};
+use crate::attachments::{self, BlobStore};
+use crate::commands::export::{
+    ExportData, ExportedAttachment, ExportedConversation, ExportedMessage, ExportedTag,
+    EXPORT_VERSION,
+};
+use crate::database::attachments::Attachment;
+use crate::error::{AppError, AppResult};
+
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    // Export every branch so an import restores the full tree
+    fn whole_tree(&self) -> bool {
+        true
+    }
+
+    fn render(&self, blobs: &BlobStore, documents: &[Document]) -> AppResult<Vec<u8>> {
+        let conversations = documents
+            .iter()
+            .map(|doc| exported_conversation(blobs, doc))
+            .collect::<AppResult<Vec<_>>>()?;
+
+        let export_data = ExportData {
+            version: EXPORT_VERSION.to_string(),
+            export_timestamp: chrono::Utc::now().timestamp(),
+            conversations,
+        };
+
+        let json = serde_json::to_string_pretty(&export_data)
+            .map_err(|e| AppError::from(e).context("Failed to serialize export data"))?;
+        Ok(json.into_bytes())
+    }
+}
+
+fn exported_conversation(blobs: &BlobStore, doc: &Document) -> AppResult<ExportedConversation> {
+    let conv = &doc.conversation;
+    let messages = doc
+        .messages
+        .iter()
+        .map(|m| {
+            let msg = &m.message;
+            Ok(ExportedMessage {
+                id: msg.id.clone(),
+                role: msg.role.clone(),
+                content: msg.content.clone(),
+                timestamp: msg.timestamp,
+                tokens_used: msg.tokens_used,
+                parent_message_id: msg.parent_message_id.clone(),
+                attachments: m
+                    .attachments
+                    .iter()
+                    .map(|a| exported_attachment(blobs, a))
+                    .collect::<AppResult<Vec<_>>>()?,
+            })
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    Ok(ExportedConversation {
+        id: conv.id.clone(),
+        title: conv.title.clone(),
+        provider: conv.provider.clone(),
+        model: conv.model.clone(),
+        system_prompt: conv.system_prompt.clone(),
+        created_at: conv.created_at,
+        updated_at: conv.updated_at,
+        active_leaf_id: doc.active_leaf_id.clone(),
+        parent_conversation_id: conv.parent_conversation_id.clone(),
+        branch_point_message_id: conv.branch_point_message_id.clone(),
+        profile: doc.profile.clone(),
+        template: doc.template.clone(),
+        tags: doc
+            .tags
+            .iter()
+            .map(|t| ExportedTag {
+                name: t.name.clone(),
+                color: t.color.clone(),
+            })
+            .collect(),
+        messages,
+    })
+}
+
+fn exported_attachment(blobs: &BlobStore, a: &Attachment) -> AppResult<ExportedAttachment> {
+    let bytes = blobs
+        .get(&a.sha256)
+        .map_err(|e| e.context(format!("Failed to read attachment {}", a.file_name)))?;
+    Ok(ExportedAttachment {
+        kind: a.kind.clone(),
+        file_name: a.file_name.clone(),
+        mime_type: a.mime_type.clone(),
+        sha256: a.sha256.clone(),
+        data: attachments::encode_base64(&bytes),
+    })
+}
@@ -0,0 +1,285 @@
+// Markdown and HTML exports. HTML is the Markdown export run through comrak,
+// with attachments embedded so the page stands alone.
+
+use super::{format_time, markdown_options, Document, Renderer};
+use crate::attachments::{self, BlobStore};
+use crate::database::attachments::Attachment;
+use crate::error::AppResult;
+use comrak::markdown_to_html;
+
+/// How attachments appear in rendered Markdown
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum AttachmentMode {
+    /// Link to the file in the blob store
+    Link,
+    /// Images as data URIs, text files inline as code blocks
+    Embed,
+}
+
+pub(super) fn attachment_markdown(
+    blobs: &BlobStore,
+    attachment: &Attachment,
+    mode: AttachmentMode,
+) -> AppResult<String> {
+    if mode == AttachmentMode::Link {
+        let path = blobs.path(&attachment.sha256)?;
+        let link = format!("[{}](file://{})", attachment.file_name, path.display());
+        return Ok(if attachment.kind == "image" {
+            format!("!{}", link)
+        } else {
+            format!("📎 {}", link)
+        });
+    }
+    let bytes = blobs.get(&attachment.sha256)?;
+    Ok(if attachment.kind == "image" {
+        format!(
+            "![{}](data:{};base64,{})",
+            attachment.file_name,
+            attachment.mime_type,
+            attachments::encode_base64(&bytes)
+        )
+    } else {
+        attachments::text_block(&attachment.file_name, &String::from_utf8_lossy(&bytes))
+    })
+}
+
+/// Markdown for one conversation. `importers` reads this layout back, so keep
+/// the role headings, branch markers and token lines stable.
+fn render_markdown(blobs: &BlobStore, doc: &Document, mode: AttachmentMode) -> AppResult<String> {
+    let conversation = &doc.conversation;
+    let mut markdown = String::new();
+
+    // Header
+    markdown.push_str(&format!("# {}\n\n", conversation.title));
+    markdown.push_str(&format!("**Provider:** {}\n", conversation.provider));
+    markdown.push_str(&format!("**Model:** {}\n", conversation.model));
+    markdown.push_str(&format!(
+        "**Created:** {}\n",
+        format_time(conversation.created_at)
+    ));
+    markdown.push_str("\n---\n\n");
+
+    // Messages
+    for entry in &doc.messages {
+        let msg = &entry.message;
+        if let Some((index, count)) = entry.branch {
+            markdown.push_str(&format!("> *Branch {} of {}*\n\n", index, count));
+        }
+
+        let role_header = match msg.role.as_str() {
+            "user" => "## 👤 User",
+            "assistant" => "## 🤖 Assistant",
+            "system" => "## ⚙️ System",
+            _ => &format!("## {}", msg.role),
+        };
+
+        markdown.push_str(&format!("{}\n\n", role_header));
+        markdown.push_str(&format!("{}\n\n", msg.content));
+
+        for attachment in &entry.attachments {
+            markdown.push_str(&attachment_markdown(blobs, attachment, mode)?);
+            markdown.push_str("\n\n");
+        }
+
+        if let Some(tokens) = msg.tokens_used {
+            markdown.push_str(&format!("*Tokens used: {}*\n\n", tokens));
+        }
+
+        markdown.push_str("---\n\n");
+    }
+
+    Ok(markdown)
+}
+
+fn render_all(
+    blobs: &BlobStore,
+    documents: &[Document],
+    mode: AttachmentMode,
+) -> AppResult<String> {
+    documents
+        .iter()
+        .map(|doc| render_markdown(blobs, doc, mode))
+        .collect::<AppResult<Vec<_>>>()
+        .map(|parts| parts.join("\n"))
+}
+
+/// Markdown with attachments linked from the blob store
+pub struct MarkdownRenderer;
+
+impl Renderer for MarkdownRenderer {
+    fn render(&self, blobs: &BlobStore, documents: &[Document]) -> AppResult<Vec<u8>> {
+        Ok(render_all(blobs, documents, AttachmentMode::Link)?.into_bytes())
+    }
+}
+
+/// A styled, self-contained HTML page
+pub struct HtmlRenderer;
+
+impl Renderer for HtmlRenderer {
+    fn render(&self, blobs: &BlobStore, documents: &[Document]) -> AppResult<Vec<u8>> {
+        // Embed attachments so the page stands alone
+        let markdown_content = render_all(blobs, documents, AttachmentMode::Embed)?;
+        let html_body = markdown_to_html(&markdown_content, &markdown_options());
+
+        // Create a complete HTML document with CSS styling
+        let html = format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>AI Conversation Export</title>
+    <style>
+        body {{
+            font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, "Helvetica Neue", Arial, sans-serif;
+            line-height: 1.6;
+            color: #333;
+            max-width: 800px;
+            margin: 40px auto;
+            padding: 20px;
+            background-color: #fff;
+        }}
+
+        h1 {{
+            color: #2c3e50;
+            border-bottom: 3px solid #3498db;
+            padding-bottom: 10px;
+            margin-bottom: 30px;
+        }}
+
+        h2 {{
+            color: #34495e;
+            margin-top: 30px;
+            margin-bottom: 15px;
+            padding: 10px 15px;
+            border-left: 4px solid #3498db;
+            background-color: #f8f9fa;
+        }}
+
+        p {{
+            margin-bottom: 15px;
+            text-align: justify;
+        }}
+
+        pre {{
+            background-color: #f4f4f4;
+            border: 1px solid #ddd;
+            border-radius: 4px;
+            padding: 15px;
+            overflow-x: auto;
+            margin: 15px 0;
+        }}
+
+        code {{
+            background-color: #f4f4f4;
+            padding: 2px 4px;
+            border-radius: 3px;
+            font-family: "SF Mono", "Monaco", "Inconsolata", "Fira Code", "Fira Mono", "Droid Sans Mono", "Source Code Pro", monospace;
+        }}
+
+        blockquote {{
+            border-left: 4px solid #e74c3c;
+            margin: 15px 0;
+            padding: 10px 20px;
+            background-color: #fdf2f2;
+            font-style: italic;
+        }}
+
+        table {{
+            border-collapse: collapse;
+            width: 100%;
+            margin: 15px 0;
+        }}
+
+        th, td {{
+            border: 1px solid #ddd;
+            padding: 8px 12px;
+            text-align: left;
+        }}
+
+        th {{
+            background-color: #f2f2f2;
+            font-weight: bold;
+        }}
+
+        hr {{
+            border: none;
+            height: 2px;
+            background: linear-gradient(to right, #3498db, #transparent);
+            margin: 30px 0;
+        }}
+
+        .metadata {{
+            background-color: #ecf0f1;
+            padding: 15px;
+            border-radius: 5px;
+            margin-bottom: 30px;
+            font-size: 14px;
+        }}
+
+        .metadata strong {{
+            color: #2c3e50;
+        }}
+
+        .timestamp {{
+            color: #7f8c8d;
+            font-size: 12px;
+            font-style: italic;
+            margin-top: 10px;
+        }}
+
+        .tokens {{
+            color: #8e44ad;
+            font-size: 12px;
+            font-style: italic;
+            margin-top: 5px;
+        }}
+
+        @media print {{
+            body {{
+                max-width: none;
+                margin: 0;
+                padding: 20px;
+            }}
+
+            h2 {{
+                page-break-after: avoid;
+            }}
+
+            pre, blockquote {{
+                page-break-inside: avoid;
+            }}
+        }}
+
+        @media (max-width: 600px) {{
+            body {{
+                margin: 20px;
+                padding: 15px;
+            }}
+
+            h1 {{
+                font-size: 24px;
+            }}
+
+            h2 {{
+                font-size: 18px;
+                padding: 8px 12px;
+            }}
+        }}
+    </style>
+</head>
+<body>
+    {}
+    <div class="timestamp">
+        <hr>
+        <p><em>Exported on {}</em></p>
+    </div>
+</body>
+</html>"#,
+            html_body,
+            chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+        );
+
+        Ok(html.into_bytes())
+    }
+}
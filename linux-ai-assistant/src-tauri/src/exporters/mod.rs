@@ -0,0 +1,399 @@
+// Export formats. A conversation is loaded once into a `Document` (its
+// metadata plus the messages to show) and every format is a `Renderer` that
+// turns one or more documents into a single file, so single-conversation
+// exports, multi-conversation exports and the save dialog share one path.
+// `directory` writes one file per conversation into a folder and keeps it in
+// sync.
+
+mod directory;
+mod epub;
+mod json;
+mod markdown;
+mod org;
+mod pdf;
+mod text;
+
+use crate::attachments::BlobStore;
+use crate::commands::export::ExportedRef;
+use crate::database::{
+    attachments::Attachment, conversations::Conversation, messages::Message, profiles::Profile,
+    tags::Tag, workspace_templates::WorkspaceTemplate,
+};
+use crate::error::{AppError, AppResult};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+pub use directory::{
+    export_to_directory, start_mirror, DirectoryExport, ExportFilter, MirrorSettings,
+};
+pub use epub::EpubRenderer;
+pub use json::JsonRenderer;
+pub use markdown::{HtmlRenderer, MarkdownRenderer};
+pub use org::OrgRenderer;
+pub use pdf::PdfRenderer;
+pub use text::TextRenderer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Json,
+    Markdown,
+    Html,
+    Pdf,
+    Org,
+    Epub,
+    Text,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Org => "org",
+            ExportFormat::Epub => "epub",
+            ExportFormat::Text => "txt",
+        }
+    }
+
+    pub fn renderer(self) -> Box<dyn Renderer> {
+        match self {
+            ExportFormat::Json => Box::new(JsonRenderer),
+            ExportFormat::Markdown => Box::new(MarkdownRenderer),
+            ExportFormat::Html => Box::new(HtmlRenderer),
+            ExportFormat::Pdf => Box::new(PdfRenderer),
+            ExportFormat::Org => Box::new(OrgRenderer),
+            ExportFormat::Epub => Box::new(EpubRenderer),
+            ExportFormat::Text => Box::new(TextRenderer),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> AppResult<Self> {
+        match s {
+            "json" => Ok(ExportFormat::Json),
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "html" => Ok(ExportFormat::Html),
+            "pdf" => Ok(ExportFormat::Pdf),
+            "org" => Ok(ExportFormat::Org),
+            "epub" => Ok(ExportFormat::Epub),
+            "text" | "txt" => Ok(ExportFormat::Text),
+            _ => Err(AppError::invalid_input(
+                "Invalid format. Supported: json, markdown, html, pdf, org, epub, text",
+            )),
+        }
+    }
+}
+
+/// Turns loaded conversations into the bytes of one file
+pub trait Renderer {
+    /// Whether the format always carries every branch, whatever was asked for
+    fn whole_tree(&self) -> bool {
+        false
+    }
+
+    fn render(&self, blobs: &BlobStore, documents: &[Document]) -> AppResult<Vec<u8>>;
+}
+
+pub struct DocumentMessage {
+    pub message: Message,
+    /// Position among sibling branches, when there are alternatives
+    pub branch: Option<(usize, usize)>,
+    pub attachments: Vec<Attachment>,
+}
+
+/// A conversation as the renderers see it
+pub struct Document {
+    pub conversation: Conversation,
+    /// The active path, or every branch depth-first
+    pub messages: Vec<DocumentMessage>,
+    pub active_leaf_id: Option<String>,
+    pub tags: Vec<Tag>,
+    pub profile: Option<ExportedRef>,
+    pub template: Option<ExportedRef>,
+}
+
+impl Document {
+    /// Load a conversation with its active path, or with `whole_tree` every
+    /// branch in order with branch labels
+    pub fn load(
+        conn: &Connection,
+        conversation: Conversation,
+        whole_tree: bool,
+    ) -> AppResult<Self> {
+        let messages = if whole_tree {
+            let tree = Message::get_tree(conn, &conversation.id)
+                .map_err(|e| AppError::from(e).context("Failed to get messages"))?;
+            tree_in_order(tree)
+        } else {
+            Message::get_by_conversation(conn, &conversation.id)
+                .map_err(|e| AppError::from(e).context("Failed to get messages"))?
+                .into_iter()
+                .map(|msg| (msg, None))
+                .collect()
+        };
+        let messages = messages
+            .into_iter()
+            .map(|(message, branch)| {
+                Ok(DocumentMessage {
+                    attachments: Attachment::get_by_message(conn, &message.id)?,
+                    message,
+                    branch,
+                })
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+
+        let active_leaf_id = Message::get_active_leaf(conn, &conversation.id)
+            .map_err(|e| AppError::from(e).context("Failed to get messages"))?
+            .map(|m| m.id);
+        let profile = match conversation.profile_id.as_deref() {
+            Some(id) => Profile::get_by_id(conn, id)?.map(|p| ExportedRef {
+                id: p.id,
+                name: p.name,
+            }),
+            None => None,
+        };
+        let template = match conversation.template_id.as_deref() {
+            Some(id) => WorkspaceTemplate::get_by_id(conn, id)?.map(|t| ExportedRef {
+                id: t.id,
+                name: t.name,
+            }),
+            None => None,
+        };
+
+        Ok(Self {
+            tags: Tag::get_for_conversation(conn, &conversation.id)?,
+            conversation,
+            messages,
+            active_leaf_id,
+            profile,
+            template,
+        })
+    }
+}
+
+/// Load `conversations` and render them as one file in `format`
+pub fn export(
+    conn: &Connection,
+    blobs: &BlobStore,
+    conversations: Vec<Conversation>,
+    format: ExportFormat,
+    whole_tree: bool,
+) -> AppResult<Vec<u8>> {
+    let renderer = format.renderer();
+    let whole_tree = whole_tree || renderer.whole_tree();
+    let documents = conversations
+        .into_iter()
+        .map(|conv| Document::load(conn, conv, whole_tree))
+        .collect::<AppResult<Vec<_>>>()?;
+    renderer.render(blobs, &documents)
+}
+
+/// Order a message tree depth-first (each branch follows its parent) and label
+/// messages that have alternatives with their position among their siblings
+fn tree_in_order(tree: Vec<Message>) -> Vec<(Message, Option<(usize, usize)>)> {
+    fn visit(
+        parent: Option<&str>,
+        tree: &[Message],
+        out: &mut Vec<(Message, Option<(usize, usize)>)>,
+    ) {
+        let children: Vec<&Message> = tree
+            .iter()
+            .filter(|m| m.parent_message_id.as_deref() == parent)
+            .collect();
+        let count = children.len();
+        for (i, child) in children.into_iter().enumerate() {
+            let label = if count > 1 {
+                Some((i + 1, count))
+            } else {
+                None
+            };
+            out.push((child.clone(), label));
+            visit(Some(&child.id), tree, out);
+        }
+    }
+
+    let mut out = Vec::with_capacity(tree.len());
+    visit(None, &tree, &mut out);
+    out
+}
+
+/// Title of an export: the conversation's own for a single one
+fn export_title(documents: &[Document]) -> String {
+    match documents {
+        [document] => document.conversation.title.clone(),
+        _ => "AI Conversations Export".to_string(),
+    }
+}
+
+fn role_name(role: &str) -> &str {
+    match role {
+        "user" => "User",
+        "assistant" => "Assistant",
+        "system" => "System",
+        other => other,
+    }
+}
+
+fn format_time(seconds: i64) -> String {
+    chrono::DateTime::from_timestamp(seconds, 0)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| "Unknown".to_string())
+}
+
+fn exported_on() -> String {
+    format!(
+        "Exported on {}",
+        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+    )
+}
+
+/// The same Markdown extensions everywhere message content is parsed
+fn markdown_options() -> comrak::ComrakOptions {
+    let mut options = comrak::ComrakOptions::default();
+    options.extension.strikethrough = true;
+    options.extension.tagfilter = false;
+    options.extension.table = true;
+    options.extension.autolink = true;
+    options.extension.tasklist = true;
+    options.extension.superscript = true;
+    options.extension.footnotes = true;
+    options.render.hardbreaks = true;
+    options.render.unsafe_ = false; // Keep safe
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::attachments::NewAttachment;
+    use crate::database::conversations::NewConversation;
+    use crate::database::messages::NewMessage;
+    use crate::database::Database;
+    use std::path::PathBuf;
+
+    /// A conversation with a regenerated answer, a code block, a table and a
+    /// text attachment
+    pub(super) fn fixture() -> (Database, BlobStore, tempfile::TempDir, Conversation) {
+        let db = Database::new(PathBuf::from(":memory:")).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::new(dir.path().to_path_buf());
+        let conn = db.conn().lock().unwrap();
+
+        let conv = Conversation::create(
+            &conn,
+            NewConversation {
+                title: "Sorting in Rust".to_string(),
+                model: "gpt-4o".to_string(),
+                provider: "openai".to_string(),
+                system_prompt: None,
+            },
+        )
+        .unwrap();
+        let tag = Tag::create_or_get(&conn, "rust", Some("#ff0000")).unwrap();
+        Tag::add_to_conversation(&conn, &conv.id, &tag.id).unwrap();
+
+        let question = Message::create(
+            &conn,
+            NewMessage {
+                conversation_id: conv.id.clone(),
+                role: "user".to_string(),
+                content: "How do I sort a *vector*?".to_string(),
+                tokens_used: None,
+            },
+        )
+        .unwrap();
+        let sha = blobs.put(b"fn main() {}\n").unwrap();
+        Attachment::create(
+            &conn,
+            NewAttachment {
+                message_id: question.id.clone(),
+                kind: "file".to_string(),
+                file_name: "main.rs".to_string(),
+                mime_type: "text/x-rust".to_string(),
+                size_bytes: 13,
+                sha256: sha,
+            },
+        )
+        .unwrap();
+        for answer in [
+            "Use `sort`:\n\n```rust\nlet mut v = vec![3, 1];\nv.sort();\n```\n\n| Method | Stable |\n|---|---|\n| sort | yes |",
+            "Try `sort_unstable`.",
+        ] {
+            Message::create_with_parent(
+                &conn,
+                NewMessage {
+                    conversation_id: conv.id.clone(),
+                    role: "assistant".to_string(),
+                    content: answer.to_string(),
+                    tokens_used: Some(42),
+                },
+                Some(&question.id),
+            )
+            .unwrap();
+        }
+        drop(conn);
+        (db, blobs, dir, conv)
+    }
+
+    #[test]
+    fn documents_hold_the_path_or_the_whole_tree() {
+        let (db, _blobs, _dir, conv) = fixture();
+        let conn = db.conn().lock().unwrap();
+
+        let path = Document::load(&conn, conv.clone(), false).unwrap();
+        assert_eq!(path.messages.len(), 2);
+        assert_eq!(path.messages[0].attachments.len(), 1);
+        assert_eq!(path.tags[0].name, "rust");
+
+        let tree = Document::load(&conn, conv, true).unwrap();
+        assert_eq!(tree.messages.len(), 3);
+        assert_eq!(tree.messages[1].branch, Some((1, 2)));
+        assert_eq!(tree.messages[2].branch, Some((2, 2)));
+    }
+
+    #[test]
+    fn formats_parse_from_names_and_extensions() {
+        assert_eq!(
+            "md".parse::<ExportFormat>().unwrap(),
+            ExportFormat::Markdown
+        );
+        assert_eq!("txt".parse::<ExportFormat>().unwrap(), ExportFormat::Text);
+        assert_eq!("org".parse::<ExportFormat>().unwrap().extension(), "org");
+        assert!("docx".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn every_format_renders_one_or_many_conversations() {
+        let (db, blobs, _dir, conv) = fixture();
+        let conn = db.conn().lock().unwrap();
+        let formats = [
+            ExportFormat::Json,
+            ExportFormat::Markdown,
+            ExportFormat::Html,
+            ExportFormat::Pdf,
+            ExportFormat::Org,
+            ExportFormat::Epub,
+            ExportFormat::Text,
+        ];
+        for format in formats {
+            let one = export(&conn, &blobs, vec![conv.clone()], format, false).unwrap();
+            let two = export(
+                &conn,
+                &blobs,
+                vec![conv.clone(), conv.clone()],
+                format,
+                true,
+            )
+            .unwrap();
+            assert!(!one.is_empty(), "{:?}", format);
+            assert!(two.len() > one.len(), "{:?}", format);
+        }
+    }
+}
@@ -0,0 +1,414 @@
+// Org-mode export. Each conversation is a top-level heading with its metadata
+// in a properties drawer and its tags as Org tags; each message is a
+// second-level heading. Message Markdown is converted through the comrak AST:
+// code fences become src blocks, quotes quote blocks, and inline markup,
+// links and tables their Org equivalents.
+
+use super::{export_title, markdown_options, role_name, Document, Renderer};
+use crate::attachments::BlobStore;
+use crate::error::AppResult;
+use comrak::nodes::{AstNode, ListDelimType, ListType, NodeValue};
+use comrak::{parse_document, Arena};
+
+pub struct OrgRenderer;
+
+impl Renderer for OrgRenderer {
+    fn render(&self, blobs: &BlobStore, documents: &[Document]) -> AppResult<Vec<u8>> {
+        let mut org = format!("#+TITLE: {}\n", one_line(&export_title(documents)));
+        org.push_str("#+STARTUP: overview\n\n");
+        for doc in documents {
+            conversation(&mut org, blobs, doc)?;
+        }
+        Ok(org.into_bytes())
+    }
+}
+
+/// Inactive Org timestamp, e.g. `[2024-05-01 Wed 10:00]`
+fn timestamp(seconds: i64) -> String {
+    chrono::DateTime::from_timestamp(seconds, 0)
+        .map(|dt| dt.format("[%Y-%m-%d %a %H:%M]").to_string())
+        .unwrap_or_default()
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Org tags may only contain letters, digits, `_`, `@`, `#` and `%`
+fn org_tag(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || "_@#%".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn drawer(org: &mut String, properties: &[(&str, String)]) {
+    org.push_str(":PROPERTIES:\n");
+    for (name, value) in properties {
+        if !value.is_empty() {
+            org.push_str(&format!(":{}: {}\n", name, one_line(value)));
+        }
+    }
+    org.push_str(":END:\n");
+}
+
+fn conversation(org: &mut String, blobs: &BlobStore, doc: &Document) -> AppResult<()> {
+    let conv = &doc.conversation;
+    org.push_str(&format!("* {}", one_line(&conv.title)));
+    if !doc.tags.is_empty() {
+        let tags: Vec<String> = doc.tags.iter().map(|t| org_tag(&t.name)).collect();
+        org.push_str(&format!(" :{}:", tags.join(":")));
+    }
+    org.push('\n');
+    drawer(
+        org,
+        &[
+            ("ID", conv.id.clone()),
+            ("PROVIDER", conv.provider.clone()),
+            ("MODEL", conv.model.clone()),
+            ("CREATED", timestamp(conv.created_at)),
+            (
+                "PROFILE",
+                doc.profile
+                    .as_ref()
+                    .map(|p| p.name.clone())
+                    .unwrap_or_default(),
+            ),
+            (
+                "TEMPLATE",
+                doc.template
+                    .as_ref()
+                    .map(|t| t.name.clone())
+                    .unwrap_or_default(),
+            ),
+        ],
+    );
+    if let Some(prompt) = conv
+        .system_prompt
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+    {
+        org.push_str("#+begin_quote\n");
+        org.push_str(&escape_lines(prompt.trim_end()));
+        org.push_str("\n#+end_quote\n");
+    }
+    org.push('\n');
+
+    for entry in &doc.messages {
+        let msg = &entry.message;
+        org.push_str(&format!("** {}\n", role_name(&msg.role)));
+        drawer(
+            org,
+            &[
+                ("ID", msg.id.clone()),
+                ("TIMESTAMP", timestamp(msg.timestamp)),
+                (
+                    "BRANCH",
+                    entry
+                        .branch
+                        .map(|(index, count)| format!("{}/{}", index, count))
+                        .unwrap_or_default(),
+                ),
+                (
+                    "TOKENS",
+                    msg.tokens_used.map(|t| t.to_string()).unwrap_or_default(),
+                ),
+            ],
+        );
+        org.push_str(&markdown_to_org(&msg.content));
+
+        for attachment in &entry.attachments {
+            if attachment.kind == "image" {
+                let path = blobs.path(&attachment.sha256)?;
+                org.push_str(&format!(
+                    "#+caption: {}\n[[file:{}]]\n\n",
+                    one_line(&attachment.file_name),
+                    path.display()
+                ));
+            } else {
+                let bytes = blobs.get(&attachment.sha256)?;
+                org.push_str(&format!(
+                    "Attached file ={}=:\n#+begin_example\n{}\n#+end_example\n\n",
+                    attachment.file_name,
+                    escape_block(String::from_utf8_lossy(&bytes).trim_end())
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Convert Markdown to Org. The result ends with a blank line.
+pub(super) fn markdown_to_org(markdown: &str) -> String {
+    let arena = Arena::new();
+    let root = parse_document(&arena, markdown, &markdown_options());
+    let mut org = String::new();
+    blocks(root, &mut org);
+    if !org.is_empty() && !org.ends_with("\n\n") {
+        org.push('\n');
+    }
+    org
+}
+
+/// Keep text lines from being read as headings, keywords or comments
+fn escape_lines(text: &str) -> String {
+    text.split('\n')
+        .map(|line| {
+            let stars = line.len() - line.trim_start_matches('*').len();
+            let heading = stars > 0 && line[stars..].starts_with(' ');
+            if heading || line.starts_with("#+") || line.starts_with("# ") || line == "#" {
+                format!("\u{200B}{}", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Inside src and example blocks Org escapes such lines with a comma, like
+/// `org-escape-code-in-string`: a comma goes after the indentation of any line
+/// matching `^[ \t]*,*(\*|#\+)`
+fn escape_block(text: &str) -> String {
+    text.split('\n')
+        .map(|line| {
+            let body = line.trim_start_matches([' ', '\t']);
+            let unescaped = body.trim_start_matches(',');
+            if unescaped.starts_with('*') || unescaped.starts_with("#+") {
+                let indent = &line[..line.len() - body.len()];
+                format!("{},{}", indent, body)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn indent(text: &str, first: &str, rest: &str) -> String {
+    let mut out = String::new();
+    for (i, line) in text.trim_end_matches('\n').split('\n').enumerate() {
+        let prefix = if i == 0 { first } else { rest };
+        if line.is_empty() {
+            out.push('\n');
+        } else {
+            out.push_str(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn blocks<'a>(node: &'a AstNode<'a>, org: &mut String) {
+    for child in node.children() {
+        block(child, org);
+    }
+}
+
+fn block<'a>(node: &'a AstNode<'a>, org: &mut String) {
+    match &node.data.borrow().value {
+        NodeValue::Paragraph => {
+            org.push_str(&escape_lines(&inline_text(node)));
+            org.push_str("\n\n");
+        }
+        // Message headings can't be Org headings without breaking the outline
+        NodeValue::Heading(_) => {
+            org.push_str(&format!("*{}*\n\n", one_line(&inline_text(node))));
+        }
+        NodeValue::BlockQuote => {
+            org.push_str("#+begin_quote\n");
+            let mut inner = String::new();
+            blocks(node, &mut inner);
+            org.push_str(inner.trim_end_matches('\n'));
+            org.push_str("\n#+end_quote\n\n");
+        }
+        NodeValue::List(list) => {
+            for (i, item) in node.children().enumerate() {
+                let mut marker = match list.list_type {
+                    ListType::Bullet => "-".to_string(),
+                    ListType::Ordered => {
+                        let delimiter = match list.delimiter {
+                            ListDelimType::Period => '.',
+                            ListDelimType::Paren => ')',
+                        };
+                        format!("{}{}", list.start + i, delimiter)
+                    }
+                };
+                match &item.data.borrow().value {
+                    NodeValue::TaskItem(Some(_)) => marker.push_str(" [X]"),
+                    NodeValue::TaskItem(None) => marker.push_str(" [ ]"),
+                    _ => {}
+                }
+                let mut inner = String::new();
+                blocks(item, &mut inner);
+                if list.tight {
+                    inner = inner.replace("\n\n", "\n");
+                }
+                let continuation = " ".repeat(marker.chars().count() + 1);
+                org.push_str(&indent(&inner, &format!("{} ", marker), &continuation));
+            }
+            org.push('\n');
+        }
+        NodeValue::CodeBlock(code) => {
+            let language = code.info.split_whitespace().next().unwrap_or("");
+            let body = escape_block(code.literal.trim_end_matches('\n'));
+            if language.is_empty() {
+                org.push_str(&format!("#+begin_example\n{}\n#+end_example\n\n", body));
+            } else {
+                org.push_str(&format!(
+                    "#+begin_src {}\n{}\n#+end_src\n\n",
+                    language, body
+                ));
+            }
+        }
+        NodeValue::HtmlBlock(html) => {
+            org.push_str(&format!(
+                "#+begin_export html\n{}\n#+end_export\n\n",
+                escape_block(html.literal.trim_end_matches('\n'))
+            ));
+        }
+        NodeValue::ThematicBreak => org.push_str("-----\n\n"),
+        NodeValue::Table(_) => {
+            for row in node.children() {
+                let header = matches!(row.data.borrow().value, NodeValue::TableRow(true));
+                let cells: Vec<String> = row
+                    .children()
+                    .map(|cell| one_line(&inline_text(cell)).replace('|', "\\vert{}"))
+                    .collect();
+                org.push_str(&format!("| {} |\n", cells.join(" | ")));
+                if header {
+                    let rules: Vec<String> = cells
+                        .iter()
+                        .map(|c| "-".repeat(c.chars().count() + 2))
+                        .collect();
+                    org.push_str(&format!("|{}|\n", rules.join("+")));
+                }
+            }
+            org.push('\n');
+        }
+        NodeValue::FootnoteDefinition(name) => {
+            let mut inner = String::new();
+            blocks(node, &mut inner);
+            org.push_str(&format!("[fn:{}] {}\n\n", name, inner.trim()));
+        }
+        NodeValue::FrontMatter(_) => {}
+        _ => blocks(node, org),
+    }
+}
+
+fn inline_text<'a>(node: &'a AstNode<'a>) -> String {
+    let mut out = String::new();
+    inline(node, &mut out);
+    out
+}
+
+fn inline<'a>(node: &'a AstNode<'a>, out: &mut String) {
+    for child in node.children() {
+        match &child.data.borrow().value {
+            NodeValue::Text(text) => out.push_str(text),
+            NodeValue::Code(code) => {
+                let marker = if code.literal.contains('~') { '=' } else { '~' };
+                out.push_str(&format!("{}{}{}", marker, code.literal, marker));
+            }
+            // Chat messages rely on single newlines, as with the HTML export's
+            // hard breaks
+            NodeValue::SoftBreak => out.push('\n'),
+            NodeValue::LineBreak => out.push_str("\\\\\n"),
+            NodeValue::Emph => {
+                out.push('/');
+                inline(child, out);
+                out.push('/');
+            }
+            NodeValue::Strong => {
+                out.push('*');
+                inline(child, out);
+                out.push('*');
+            }
+            NodeValue::Strikethrough => {
+                out.push('+');
+                inline(child, out);
+                out.push('+');
+            }
+            NodeValue::Superscript => {
+                out.push_str("^{");
+                inline(child, out);
+                out.push('}');
+            }
+            NodeValue::Link(link) => {
+                let label = inline_text(child);
+                if label.is_empty() || label == link.url {
+                    out.push_str(&format!("[[{}]]", link.url));
+                } else {
+                    out.push_str(&format!("[[{}][{}]]", link.url, one_line(&label)));
+                }
+            }
+            NodeValue::Image(image) => out.push_str(&format!("[[{}]]", image.url)),
+            NodeValue::HtmlInline(html) => out.push_str(&format!("@@html:{}@@", html)),
+            NodeValue::FootnoteReference(name) => out.push_str(&format!("[fn:{}]", name)),
+            _ => inline(child, out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::tests::fixture;
+    use crate::exporters::{export, ExportFormat};
+
+    #[test]
+    fn markdown_becomes_org_markup() {
+        let org = markdown_to_org(
+            "# Title\n\nSome **bold**, *italic* and `code` with a [link](https://example.com).\n\n\
+             - one\n- [x] done\n\n1. first\n2. second\n\n\
+             ```python\nprint(1)\n* not a heading\n```\n\n> quoted\n\n\
+             | A | B |\n|---|---|\n| 1 | 2 |",
+        );
+        assert!(org.contains("*Title*\n"));
+        assert!(
+            org.contains("Some *bold*, /italic/ and ~code~ with a [[https://example.com][link]].")
+        );
+        assert!(org.contains("- one\n- [X] done\n"));
+        assert!(org.contains("1. first\n2. second\n"));
+        assert!(org.contains("#+begin_src python\nprint(1)\n,* not a heading\n#+end_src\n"));
+        assert!(org.contains("#+begin_quote\nquoted\n#+end_quote\n"));
+        assert!(org.contains("| A | B |\n|---+---|\n| 1 | 2 |\n"));
+    }
+
+    #[test]
+    fn block_lines_are_escaped_like_org() {
+        assert_eq!(
+            escape_block("* a\n  #+end_src\n\t,,*b\n,#+x\n#comment\n  text, *"),
+            ",* a\n  ,#+end_src\n\t,,,*b\n,,#+x\n#comment\n  text, *"
+        );
+    }
+
+    #[test]
+    fn nested_list_items_are_indented() {
+        let org = markdown_to_org("- outer\n  - inner\n- next");
+        assert_eq!(org, "- outer\n  - inner\n- next\n\n");
+    }
+
+    #[test]
+    fn conversations_become_headings_with_properties() {
+        let (db, blobs, _dir, conv) = fixture();
+        let conn = db.conn().lock().unwrap();
+        let bytes = export(&conn, &blobs, vec![conv.clone()], ExportFormat::Org, true).unwrap();
+        let org = String::from_utf8(bytes).unwrap();
+
+        assert!(org.starts_with("#+TITLE: Sorting in Rust\n"));
+        assert!(org.contains("* Sorting in Rust :rust:\n:PROPERTIES:\n"));
+        assert!(org.contains(&format!(":ID: {}\n", conv.id)));
+        assert!(org.contains(":PROVIDER: openai\n:MODEL: gpt-4o\n"));
+        assert!(org.contains("** User\n:PROPERTIES:\n"));
+        assert!(org.contains(":BRANCH: 2/2\n:TOKENS: 42\n:END:\nTry ~sort_unstable~.\n"));
+        assert!(org.contains("#+begin_src rust\nlet mut v = vec![3, 1];\nv.sort();\n#+end_src"));
+        assert!(org.contains("Attached file =main.rs=:\n#+begin_example\nfn main() {}\n"));
+    }
+}
@@ -0,0 +1,74 @@
+// PDF export: documents become `pdf::PdfSection`s for the layout engine in
+// `crate::pdf`.
+
+use super::markdown::{attachment_markdown, AttachmentMode};
+use super::{export_title, format_time, role_name, Document, Renderer};
+use crate::attachments::BlobStore;
+use crate::error::AppResult;
+use crate::pdf::{self, PdfMessage, PdfSection};
+
+pub struct PdfRenderer;
+
+impl Renderer for PdfRenderer {
+    fn render(&self, blobs: &BlobStore, documents: &[Document]) -> AppResult<Vec<u8>> {
+        let sections = documents
+            .iter()
+            .map(|doc| pdf_section(blobs, doc))
+            .collect::<AppResult<Vec<_>>>()?;
+        pdf::render(&export_title(documents), &sections)
+    }
+}
+
+/// A conversation laid out for the PDF renderer. Text attachments are included
+/// as code blocks; images are listed by name.
+fn pdf_section(blobs: &BlobStore, doc: &Document) -> AppResult<PdfSection> {
+    let conversation = &doc.conversation;
+    let mut messages = Vec::new();
+    for entry in &doc.messages {
+        let msg = &entry.message;
+        let mut heading = role_name(&msg.role).to_string();
+        if let Some((index, count)) = entry.branch {
+            heading.push_str(&format!(" · branch {} of {}", index, count));
+        }
+
+        let mut content = msg.content.clone();
+        let mut notes = Vec::new();
+        for attachment in &entry.attachments {
+            if attachment.kind == "image" {
+                notes.push(format!(
+                    "Attachment: {} ({})",
+                    attachment.file_name, attachment.mime_type
+                ));
+            } else {
+                content.push_str("\n\n");
+                content.push_str(&attachment_markdown(
+                    blobs,
+                    attachment,
+                    AttachmentMode::Embed,
+                )?);
+            }
+        }
+        if let Some(tokens) = msg.tokens_used {
+            notes.push(format!("Tokens used: {}", tokens));
+        }
+
+        messages.push(PdfMessage {
+            role: msg.role.clone(),
+            heading,
+            content,
+            notes,
+        });
+    }
+
+    Ok(PdfSection {
+        title: conversation.title.clone(),
+        metadata: vec![
+            format!(
+                "Provider: {} · Model: {}",
+                conversation.provider, conversation.model
+            ),
+            format!("Created: {}", format_time(conversation.created_at)),
+        ],
+        messages,
+    })
+}
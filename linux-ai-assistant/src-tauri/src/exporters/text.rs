@@ -0,0 +1,95 @@
+// Plain-text transcripts: message content verbatim under a timestamped speaker
+// line, for pasting into mail or reading in a terminal.
+
+use super::{exported_on, format_time, role_name, Document, Renderer};
+use crate::attachments::BlobStore;
+use crate::error::AppResult;
+
+const SEPARATOR: &str = "------------------------------------------------------------------------";
+
+pub struct TextRenderer;
+
+impl Renderer for TextRenderer {
+    fn render(&self, _blobs: &BlobStore, documents: &[Document]) -> AppResult<Vec<u8>> {
+        let mut text = String::new();
+        for (i, doc) in documents.iter().enumerate() {
+            if i > 0 {
+                text.push_str("\n\n");
+            }
+            transcript(&mut text, doc);
+        }
+        text.push_str(&format!("{}\n", exported_on()));
+        Ok(text.into_bytes())
+    }
+}
+
+fn transcript(text: &mut String, doc: &Document) {
+    let conversation = &doc.conversation;
+    let title = conversation.title.trim();
+    text.push_str(&format!(
+        "{}\n{}\n\n",
+        title,
+        "=".repeat(title.chars().count().max(3))
+    ));
+    text.push_str(&format!(
+        "Provider: {}\nModel: {}\nCreated: {}\n",
+        conversation.provider,
+        conversation.model,
+        format_time(conversation.created_at)
+    ));
+    if !doc.tags.is_empty() {
+        let names: Vec<&str> = doc.tags.iter().map(|t| t.name.as_str()).collect();
+        text.push_str(&format!("Tags: {}\n", names.join(", ")));
+    }
+    text.push_str(&format!("\n{}\n\n", SEPARATOR));
+
+    for entry in &doc.messages {
+        let msg = &entry.message;
+        text.push_str(&format!(
+            "[{}] {}",
+            format_time(msg.timestamp),
+            role_name(&msg.role)
+        ));
+        if let Some((index, count)) = entry.branch {
+            text.push_str(&format!(" (branch {} of {})", index, count));
+        }
+        text.push_str(":\n\n");
+        text.push_str(msg.content.trim_end());
+        text.push('\n');
+
+        if !entry.attachments.is_empty() || msg.tokens_used.is_some() {
+            text.push('\n');
+        }
+        for attachment in &entry.attachments {
+            text.push_str(&format!(
+                "  Attachment: {} ({})\n",
+                attachment.file_name, attachment.mime_type
+            ));
+        }
+        if let Some(tokens) = msg.tokens_used {
+            text.push_str(&format!("  Tokens used: {}\n", tokens));
+        }
+        text.push_str(&format!("\n{}\n\n", SEPARATOR));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exporters::tests::fixture;
+    use crate::exporters::{export, ExportFormat};
+
+    #[test]
+    fn transcripts_list_speakers_branches_and_attachments() {
+        let (db, blobs, _dir, conv) = fixture();
+        let conn = db.conn().lock().unwrap();
+        let bytes = export(&conn, &blobs, vec![conv], ExportFormat::Text, true).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+
+        assert!(text.starts_with("Sorting in Rust\n===============\n"));
+        assert!(text.contains("Tags: rust\n"));
+        assert!(text.contains("] User:\n\nHow do I sort a *vector*?\n"));
+        assert!(text.contains("  Attachment: main.rs (text/x-rust)\n"));
+        assert!(text.contains("] Assistant (branch 2 of 2):\n\nTry `sort_unstable`.\n"));
+        assert!(text.contains("  Tokens used: 42\n"));
+    }
+}
@@ -0,0 +1,1568 @@
+// Importers for this app's JSON export and other assistants' exports: the
+// ChatGPT `conversations.json` (including its mapping tree), the Claude.ai data
+// export and generic Markdown transcripts. Each one maps its source onto the
+// native `ExportData` shape; `preview` reports what an import would do and
+// `import` inserts it in one transaction with a conflict strategy.
+
+use crate::attachments::{self, BlobStore};
+use crate::commands::export::{
+    ExportData, ExportedAttachment, ExportedConversation, ExportedMessage, ExportedRef,
+    EXPORT_VERSION,
+};
+use crate::database::{
+    attachments::{Attachment, NewAttachment},
+    conversations::{Conversation, NewConversationWithId},
+    messages::{Message, NewMessageWithId},
+    profiles::Profile,
+    tags::Tag,
+    workspace_templates::WorkspaceTemplate,
+};
+use crate::error::{AppError, AppResult};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+
+/// Model recorded on ChatGPT conversations that don't name one
+const CHATGPT_DEFAULT_MODEL: &str = "gpt-4o";
+/// Claude.ai exports don't record the model
+const CLAUDE_DEFAULT_MODEL: &str = "claude-3-5-sonnet";
+/// Parent id Claude.ai uses for the first message of a conversation
+const CLAUDE_ROOT_PARENT: &str = "00000000-0000-4000-8000-000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    /// This app's own JSON export
+    Native,
+    /// OpenAI ChatGPT `conversations.json`
+    Chatgpt,
+    /// Claude.ai data export `conversations.json`
+    Claude,
+    /// A single conversation as a Markdown transcript
+    Markdown,
+}
+
+impl ImportFormat {
+    /// Guess the format from the content. Anything that isn't JSON is treated
+    /// as a Markdown transcript.
+    pub fn detect(content: &str) -> AppResult<Self> {
+        let value: Value = match serde_json::from_str(content) {
+            Ok(value) => value,
+            Err(_) => return Ok(Self::Markdown),
+        };
+        let first = match &value {
+            Value::Array(items) => items.first(),
+            other => Some(other),
+        };
+        match first {
+            Some(v) if v.get("conversations").is_some() && v.get("version").is_some() => {
+                Ok(Self::Native)
+            }
+            Some(v) if v.get("mapping").is_some() => Ok(Self::Chatgpt),
+            Some(v) if v.get("chat_messages").is_some() => Ok(Self::Claude),
+            None => Err(AppError::invalid_input(
+                "The export contains no conversations",
+            )),
+            Some(_) => Err(AppError::invalid_input("Unrecognized export format")),
+        }
+    }
+}
+
+/// An export converted to the native shape, with anything that was dropped
+pub struct ParsedImport {
+    pub format: ImportFormat,
+    pub data: ExportData,
+    pub warnings: Vec<String>,
+}
+
+/// Convert `content` to the native export shape, detecting the format unless
+/// one is given
+pub fn parse(content: &str, format: Option<ImportFormat>) -> AppResult<ParsedImport> {
+    let format = match format {
+        Some(format) => format,
+        None => ImportFormat::detect(content)?,
+    };
+    let mut warnings = Vec::new();
+    let conversations = match format {
+        ImportFormat::Native => {
+            let mut data: ExportData = serde_json::from_str(content)
+                .map_err(|e| AppError::from(e).context("Failed to parse JSON"))?;
+            check_version(&data.version)?;
+            data.conversations.iter_mut().for_each(normalize);
+            return Ok(ParsedImport {
+                format,
+                data,
+                warnings,
+            });
+        }
+        ImportFormat::Chatgpt => json_items(content)?
+            .into_iter()
+            .map(|item| parse_chatgpt(item, &mut warnings))
+            .collect::<AppResult<Vec<_>>>()?,
+        ImportFormat::Claude => json_items(content)?
+            .into_iter()
+            .map(|item| parse_claude(item, &mut warnings))
+            .collect::<AppResult<Vec<_>>>()?,
+        ImportFormat::Markdown => vec![parse_markdown(content)?],
+    };
+    Ok(ParsedImport {
+        format,
+        data: ExportData {
+            version: EXPORT_VERSION.to_string(),
+            export_timestamp: chrono::Utc::now().timestamp(),
+            conversations,
+        },
+        warnings,
+    })
+}
+
+/// Accept every schema version up to the one this build writes. Fields added
+/// since version 1 default to empty, so one set of types reads them all.
+fn check_version(version: &str) -> AppResult<()> {
+    let major = |v: &str| v.split('.').next().and_then(|m| m.parse::<u32>().ok());
+    match (major(version), major(EXPORT_VERSION)) {
+        (Some(found), Some(current)) if (1..=current).contains(&found) => Ok(()),
+        _ => Err(AppError::unsupported(format!(
+            "Export version {} is not supported; this version reads up to {}",
+            version, EXPORT_VERSION
+        ))),
+    }
+}
+
+/// Exports hold an array of conversations; accept a single one too
+fn json_items(content: &str) -> AppResult<Vec<Value>> {
+    let value: Value = serde_json::from_str(content)
+        .map_err(|e| AppError::from(e).context("Failed to parse JSON"))?;
+    Ok(match value {
+        Value::Array(items) => items,
+        other => vec![other],
+    })
+}
+
+/// What to do with an incoming conversation whose id is already taken
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Leave the existing conversation untouched
+    #[default]
+    Skip,
+    /// Replace the existing conversation's messages, tags and metadata
+    Overwrite,
+    /// Add the incoming messages the existing conversation doesn't have
+    Merge,
+    /// Import under new conversation and message ids
+    Copy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportAction {
+    Created,
+    Skipped,
+    Overwritten,
+    Merged,
+    Copied,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportPreviewEntry {
+    pub id: String,
+    pub title: String,
+    pub message_count: usize,
+    /// Leaves of the message tree; 1 for a linear conversation
+    pub branch_count: usize,
+    pub attachment_count: usize,
+    /// A conversation with this id exists
+    pub exists: bool,
+    pub action: ImportAction,
+    /// Messages the import would insert; fewer than `message_count` when merging
+    pub messages_to_import: usize,
+}
+
+/// Dry-run report of what an import would create
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub format: ImportFormat,
+    pub conflict: ConflictStrategy,
+    pub conversations: Vec<ImportPreviewEntry>,
+    /// Conversations that would be created or changed
+    pub conversations_to_create: usize,
+    pub messages_to_create: usize,
+    /// Content the importer couldn't map, such as images or tool calls
+    pub warnings: Vec<String>,
+}
+
+fn action_for(
+    conn: &Connection,
+    conversation_id: &str,
+    conflict: ConflictStrategy,
+) -> AppResult<ImportAction> {
+    if !Conversation::id_exists(conn, conversation_id)? {
+        return Ok(ImportAction::Created);
+    }
+    Ok(match conflict {
+        ConflictStrategy::Skip => ImportAction::Skipped,
+        ConflictStrategy::Overwrite => ImportAction::Overwritten,
+        ConflictStrategy::Merge => ImportAction::Merged,
+        ConflictStrategy::Copy => ImportAction::Copied,
+    })
+}
+
+pub fn preview(
+    conn: &Connection,
+    parsed: &ParsedImport,
+    conflict: ConflictStrategy,
+) -> AppResult<ImportPreview> {
+    let mut conversations = Vec::with_capacity(parsed.data.conversations.len());
+    for conv in &parsed.data.conversations {
+        let parents: HashSet<&str> = conv
+            .messages
+            .iter()
+            .filter_map(|m| m.parent_message_id.as_deref())
+            .collect();
+        let action = action_for(conn, &conv.id, conflict)?;
+        let messages_to_import = match action {
+            ImportAction::Skipped => 0,
+            ImportAction::Merged => {
+                let existing = Message::ids_in_conversation(conn, &conv.id)?;
+                conv.messages
+                    .iter()
+                    .filter(|m| !existing.contains(&m.id))
+                    .count()
+            }
+            _ => conv.messages.len(),
+        };
+        conversations.push(ImportPreviewEntry {
+            id: conv.id.clone(),
+            title: conv.title.clone(),
+            message_count: conv.messages.len(),
+            branch_count: conv
+                .messages
+                .iter()
+                .filter(|m| !parents.contains(m.id.as_str()))
+                .count(),
+            attachment_count: conv.messages.iter().map(|m| m.attachments.len()).sum(),
+            exists: action != ImportAction::Created,
+            action,
+            messages_to_import,
+        });
+    }
+    let changed = conversations
+        .iter()
+        .filter(|c| c.action != ImportAction::Skipped);
+    Ok(ImportPreview {
+        format: parsed.format,
+        conflict,
+        conversations_to_create: changed.clone().count(),
+        messages_to_create: changed.map(|c| c.messages_to_import).sum(),
+        conversations,
+        warnings: parsed.warnings.clone(),
+    })
+}
+
+/// What happened to one incoming conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportOutcome {
+    /// Id in the export
+    pub source_id: String,
+    /// Id in the database; differs from `source_id` for copies
+    pub conversation_id: String,
+    pub title: String,
+    pub action: ImportAction,
+    pub messages_imported: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub format: ImportFormat,
+    pub conflict: ConflictStrategy,
+    pub outcomes: Vec<ImportOutcome>,
+    pub messages_imported: usize,
+    pub warnings: Vec<String>,
+}
+
+/// Insert a parsed export in a single transaction, so a failure part-way
+/// leaves the database as it was
+pub fn import(
+    conn: &Connection,
+    blobs: &BlobStore,
+    parsed: ParsedImport,
+    conflict: ConflictStrategy,
+) -> AppResult<ImportResult> {
+    // Blobs are written as attachments are inserted. A rollback leaves them
+    // unreferenced, which is harmless in a content-addressed store.
+    let tx = conn.unchecked_transaction()?;
+    let mut outcomes = Vec::with_capacity(parsed.data.conversations.len());
+    let mut batch = ImportBatch::default();
+    for conv in parsed.data.conversations {
+        let title = conv.title.clone();
+        let outcome = import_conversation(&tx, blobs, conv, conflict, &mut batch)
+            .map_err(|e| e.context(format!("Failed to import \"{}\"", title)))?;
+        outcomes.push(outcome);
+    }
+    link_branches(&tx, &batch)?;
+    tx.commit()?;
+
+    // Overwritten attachments can only be dropped once the delete is committed
+    for sha256 in batch.replaced_blobs {
+        if Attachment::count_by_sha256(conn, &sha256)? == 0 {
+            blobs.remove(&sha256)?;
+        }
+    }
+
+    Ok(ImportResult {
+        format: parsed.format,
+        conflict,
+        messages_imported: outcomes.iter().map(|o| o.messages_imported).sum(),
+        outcomes,
+        warnings: parsed.warnings,
+    })
+}
+
+/// State shared across the conversations of one import
+#[derive(Default)]
+struct ImportBatch {
+    /// Source ids of copied conversations and of messages given fresh ids
+    renamed_conversations: HashMap<String, String>,
+    renamed_messages: HashMap<String, String>,
+    /// Branch links to restore once every conversation is in place:
+    /// (conversation, parent conversation, branch point), as in the source
+    branch_links: Vec<(String, String, Option<String>)>,
+    /// Blobs of overwritten attachments, removed after commit if unreferenced
+    replaced_blobs: Vec<String>,
+}
+
+/// Id of a profile or template in this database: the exported id if it
+/// exists, else one with the same name
+fn resolve_ref<T>(
+    exported: Option<&ExportedRef>,
+    by_id: impl Fn(&str) -> rusqlite::Result<Option<T>>,
+    all: impl Fn() -> rusqlite::Result<Vec<T>>,
+    id_and_name: impl Fn(&T) -> (&str, &str),
+) -> AppResult<Option<String>> {
+    let Some(exported) = exported else {
+        return Ok(None);
+    };
+    if by_id(&exported.id)?.is_some() {
+        return Ok(Some(exported.id.clone()));
+    }
+    Ok(all()?
+        .iter()
+        .map(&id_and_name)
+        .find(|(_, name)| *name == exported.name)
+        .map(|(id, _)| id.to_string()))
+}
+
+/// Restore links between branches. A parent that isn't in the database
+/// (neither imported nor already present) leaves the branch unlinked.
+fn link_branches(conn: &Connection, batch: &ImportBatch) -> AppResult<()> {
+    for (conversation_id, parent_id, branch_point_id) in &batch.branch_links {
+        let parent_id = batch
+            .renamed_conversations
+            .get(parent_id)
+            .unwrap_or(parent_id);
+        if !Conversation::id_exists(conn, parent_id)? {
+            continue;
+        }
+        let branch_point_id = match branch_point_id {
+            Some(id) => {
+                let id = batch.renamed_messages.get(id).unwrap_or(id);
+                Message::id_exists(conn, id)?.then_some(id.as_str())
+            }
+            None => None,
+        };
+        Conversation::set_branch_origin(conn, conversation_id, Some(parent_id), branch_point_id)?;
+    }
+    Ok(())
+}
+
+fn import_conversation(
+    conn: &Connection,
+    blobs: &BlobStore,
+    conv: ExportedConversation,
+    conflict: ConflictStrategy,
+    batch: &mut ImportBatch,
+) -> AppResult<ImportOutcome> {
+    let action = action_for(conn, &conv.id, conflict)?;
+    let mut outcome = ImportOutcome {
+        source_id: conv.id.clone(),
+        conversation_id: conv.id.clone(),
+        title: conv.title.clone(),
+        action,
+        messages_imported: 0,
+    };
+    if action == ImportAction::Skipped {
+        return Ok(outcome);
+    }
+
+    let new_conv = NewConversationWithId {
+        id: if action == ImportAction::Copied {
+            uuid::Uuid::new_v4().to_string()
+        } else {
+            conv.id.clone()
+        },
+        title: if action == ImportAction::Copied {
+            format!("{} (copy)", conv.title)
+        } else {
+            conv.title.clone()
+        },
+        provider: conv.provider,
+        model: conv.model,
+        system_prompt: conv.system_prompt,
+        created_at: conv.created_at,
+        updated_at: conv.updated_at,
+        profile_id: resolve_ref(
+            conv.profile.as_ref(),
+            |id| Profile::get_by_id(conn, id),
+            || Profile::get_all(conn),
+            |p: &Profile| (&p.id, &p.name),
+        )?,
+        template_id: resolve_ref(
+            conv.template.as_ref(),
+            |id| WorkspaceTemplate::get_by_id(conn, id),
+            || WorkspaceTemplate::get_all(conn),
+            |t: &WorkspaceTemplate| (&t.id, &t.name),
+        )?,
+    };
+    outcome.conversation_id = new_conv.id.clone();
+    if action == ImportAction::Copied {
+        batch
+            .renamed_conversations
+            .insert(conv.id.clone(), new_conv.id.clone());
+    }
+    // A merge leaves the existing conversation's links alone
+    if action != ImportAction::Merged {
+        if let Some(parent_id) = conv.parent_conversation_id.clone() {
+            batch.branch_links.push((
+                new_conv.id.clone(),
+                parent_id,
+                conv.branch_point_message_id.clone(),
+            ));
+        }
+    }
+    outcome.title = new_conv.title.clone();
+
+    let mut existing = HashSet::new();
+    match action {
+        ImportAction::Created | ImportAction::Copied => {
+            Conversation::create_with_id(conn, new_conv)?;
+        }
+        ImportAction::Overwritten => {
+            batch.replaced_blobs.extend(
+                Attachment::get_by_conversation(conn, &conv.id)?
+                    .into_iter()
+                    .map(|a| a.sha256),
+            );
+            Conversation::overwrite(conn, new_conv)?;
+        }
+        ImportAction::Merged => existing = Message::ids_in_conversation(conn, &conv.id)?,
+        ImportAction::Skipped => unreachable!(),
+    }
+
+    // Messages keep their ids unless the id is taken elsewhere (or this is a
+    // copy); children follow their parent's new id
+    let renamed = &mut batch.renamed_messages;
+    let mut inserted = HashSet::new();
+    for msg in conv.messages {
+        if existing.contains(&msg.id) {
+            continue;
+        }
+        let id = if action == ImportAction::Copied || Message::id_exists(conn, &msg.id)? {
+            let fresh = uuid::Uuid::new_v4().to_string();
+            renamed.insert(msg.id.clone(), fresh.clone());
+            fresh
+        } else {
+            msg.id
+        };
+        let parent_message_id = msg
+            .parent_message_id
+            .map(|p| renamed.get(&p).cloned().unwrap_or(p));
+        Message::create_with_id(
+            conn,
+            NewMessageWithId {
+                id: id.clone(),
+                conversation_id: outcome.conversation_id.clone(),
+                role: msg.role,
+                content: msg.content,
+                timestamp: msg.timestamp,
+                tokens_used: msg.tokens_used,
+                parent_message_id,
+            },
+        )
+        .map_err(|e| AppError::from(e).context(format!("Failed to create message {}", id)))?;
+        for attachment in msg.attachments {
+            import_attachment(conn, blobs, &id, attachment)?;
+        }
+        inserted.insert(id);
+        outcome.messages_imported += 1;
+    }
+
+    // A merge keeps the branch the user is on unless the export's active
+    // branch is one of the messages just added
+    let active_leaf_id = conv
+        .active_leaf_id
+        .map(|id| renamed.get(&id).cloned().unwrap_or(id))
+        .filter(|id| inserted.contains(id));
+    if let Some(leaf_id) = active_leaf_id {
+        Message::set_active_leaf(conn, &leaf_id)
+            .map_err(|e| AppError::from(e).context("Failed to restore the active branch"))?;
+    }
+    if action == ImportAction::Merged && outcome.messages_imported > 0 {
+        Conversation::touch(conn, &outcome.conversation_id)?;
+    }
+
+    for tag in conv.tags {
+        let tag = Tag::create_or_get(conn, &tag.name, tag.color.as_deref())?;
+        Tag::add_to_conversation(conn, &outcome.conversation_id, &tag.id)?;
+    }
+
+    Ok(outcome)
+}
+
+fn import_attachment(
+    conn: &Connection,
+    blobs: &BlobStore,
+    message_id: &str,
+    attachment: ExportedAttachment,
+) -> AppResult<Attachment> {
+    let context = || format!("Attachment {}", attachment.file_name);
+    let bytes = attachments::decode_base64(&attachment.data).map_err(|e| e.context(context()))?;
+    // Label and key by the actual contents rather than trusting the recorded
+    // kind, mime type and hash
+    let (kind, mime_type) =
+        attachments::detect(&attachment.file_name, &bytes).map_err(|e| e.context(context()))?;
+    let sha256 = blobs.put(&bytes)?;
+    Attachment::create(
+        conn,
+        NewAttachment {
+            message_id: message_id.to_string(),
+            kind: kind.to_string(),
+            file_name: attachment.file_name,
+            mime_type: mime_type.to_string(),
+            size_bytes: bytes.len() as i64,
+            sha256,
+        },
+    )
+    .map_err(|e| AppError::from(e).context("Failed to import attachment"))
+}
+
+/// Chain the messages of exports made before branching, which carry no
+/// parent links, into their linear path, and put parents before children
+fn normalize(conv: &mut ExportedConversation) {
+    if conv.messages.iter().all(|m| m.parent_message_id.is_none()) {
+        let mut previous: Option<String> = None;
+        for msg in &mut conv.messages {
+            msg.parent_message_id = previous.replace(msg.id.clone());
+        }
+    }
+    conv.messages = order_tree(std::mem::take(&mut conv.messages));
+}
+
+/// Order messages so every parent precedes its children, clearing parent
+/// links that point outside the conversation. A message on a parent cycle
+/// becomes a root instead, so no message is left out.
+fn order_tree(messages: Vec<ExportedMessage>) -> Vec<ExportedMessage> {
+    let order: Vec<String> = messages.iter().map(|m| m.id.clone()).collect();
+    let ids: HashSet<&String> = order.iter().collect();
+    let mut parents: HashMap<String, Option<String>> = HashMap::new();
+    let mut children: HashMap<Option<String>, Vec<ExportedMessage>> = HashMap::new();
+    for mut msg in messages {
+        if msg
+            .parent_message_id
+            .as_ref()
+            .is_some_and(|p| !ids.contains(p) || *p == msg.id)
+        {
+            msg.parent_message_id = None;
+        }
+        parents.insert(msg.id.clone(), msg.parent_message_id.clone());
+        children
+            .entry(msg.parent_message_id.clone())
+            .or_default()
+            .push(msg);
+    }
+
+    let mut out = Vec::with_capacity(order.len());
+    let mut stack: Vec<ExportedMessage> = children.remove(&None).unwrap_or_default();
+    let mut unplaced = order.iter();
+    loop {
+        stack.reverse();
+        while let Some(msg) = stack.pop() {
+            if let Some(mut kids) = children.remove(&Some(msg.id.clone())) {
+                kids.reverse();
+                stack.extend(kids);
+            }
+            out.push(msg);
+        }
+        // Messages left over sit on a parent cycle; the first of them in file
+        // order is cut loose from its parent, which breaks the cycle
+        let leftover = unplaced.by_ref().find_map(|id| {
+            let siblings = children.get_mut(&parents[id])?;
+            let index = siblings.iter().position(|m| &m.id == id)?;
+            Some(siblings.remove(index))
+        });
+        match leftover {
+            Some(mut msg) => {
+                msg.parent_message_id = None;
+                stack.push(msg);
+            }
+            None => break,
+        }
+    }
+    out
+}
+
+/// Stable UUID for content without ids of its own, so re-importing the same
+/// file finds the existing conversation
+fn derived_id(seed: &str) -> String {
+    let digest = Sha256::digest(seed.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_custom_bytes(bytes)
+        .into_uuid()
+        .to_string()
+}
+
+fn text_attachment(file_name: &str, content: &str) -> ExportedAttachment {
+    ExportedAttachment {
+        kind: "file".to_string(),
+        file_name: file_name.to_string(),
+        mime_type: "text/plain".to_string(),
+        sha256: attachments::hex_digest(content.as_bytes()),
+        data: attachments::encode_base64(content.as_bytes()),
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatgptConversation {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    conversation_id: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    create_time: Option<f64>,
+    #[serde(default)]
+    update_time: Option<f64>,
+    #[serde(default)]
+    mapping: HashMap<String, ChatgptNode>,
+    #[serde(default)]
+    current_node: Option<String>,
+    #[serde(default)]
+    default_model_slug: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatgptNode {
+    #[serde(default)]
+    message: Option<ChatgptMessage>,
+    #[serde(default)]
+    parent: Option<String>,
+    #[serde(default)]
+    children: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatgptMessage {
+    #[serde(default)]
+    id: Option<String>,
+    author: ChatgptAuthor,
+    #[serde(default)]
+    create_time: Option<f64>,
+    #[serde(default)]
+    content: Option<ChatgptContent>,
+    /// `all` for visible messages; tool calls address the tool instead
+    #[serde(default)]
+    recipient: Option<String>,
+    #[serde(default)]
+    metadata: Value,
+}
+
+#[derive(Deserialize)]
+struct ChatgptAuthor {
+    role: String,
+}
+
+#[derive(Deserialize)]
+struct ChatgptContent {
+    #[serde(default)]
+    content_type: String,
+    #[serde(default)]
+    parts: Vec<Value>,
+}
+
+fn is_tool_call(msg: &ChatgptMessage) -> bool {
+    msg.author.role == "tool" || msg.recipient.as_deref().is_some_and(|r| r != "all")
+}
+
+/// A ChatGPT message as it will be imported, or None when it is dropped
+fn chatgpt_text(msg: &ChatgptMessage, images: &mut usize) -> Option<(String, String)> {
+    let hidden = msg
+        .metadata
+        .get("is_visually_hidden_from_conversation")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    if hidden || is_tool_call(msg) {
+        return None;
+    }
+    let content = msg.content.as_ref()?;
+    if !matches!(content.content_type.as_str(), "text" | "multimodal_text") {
+        return None;
+    }
+    let mut parts = Vec::new();
+    for part in &content.parts {
+        match part {
+            Value::String(text) if !text.is_empty() => parts.push(text.as_str()),
+            Value::String(_) => {}
+            // Image asset pointers reference files the export doesn't carry
+            _ => *images += 1,
+        }
+    }
+    let text = parts.join("\n");
+    if text.trim().is_empty() {
+        return None;
+    }
+    Some((msg.author.role.clone(), text))
+}
+
+fn parse_chatgpt(item: Value, warnings: &mut Vec<String>) -> AppResult<ExportedConversation> {
+    let conv: ChatgptConversation = serde_json::from_value(item)
+        .map_err(|e| AppError::from(e).context("Invalid ChatGPT conversation"))?;
+    let title = conv
+        .title
+        .clone()
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or_else(|| "Imported ChatGPT conversation".to_string());
+    let id = match conv.id.clone().or(conv.conversation_id.clone()) {
+        Some(id) => id,
+        None => derived_id(&serde_json::to_string(
+            &conv.mapping.keys().collect::<Vec<_>>(),
+        )?),
+    };
+    let created_at = conv.create_time.unwrap_or(0.0) as i64;
+
+    // Walk from the roots in each node's child order, keeping user and
+    // assistant text and attaching children of dropped nodes (the hidden
+    // system root, tool calls) to their nearest kept ancestor
+    let mut roots: Vec<&String> = conv
+        .mapping
+        .iter()
+        .filter(|(_, node)| {
+            node.parent
+                .as_ref()
+                .map_or(true, |p| !conv.mapping.contains_key(p))
+        })
+        .map(|(key, _)| key)
+        .collect();
+    roots.sort();
+
+    let mut messages = Vec::new();
+    let mut kept_ids: HashMap<&str, String> = HashMap::new();
+    let mut system_prompt = None;
+    let mut skipped = 0;
+    let mut images = 0;
+    let mut model = conv.default_model_slug.clone();
+    let mut visited = HashSet::new();
+    let mut stack: Vec<(&String, Option<String>)> =
+        roots.into_iter().rev().map(|key| (key, None)).collect();
+
+    while let Some((key, parent)) = stack.pop() {
+        if !visited.insert(key.as_str()) {
+            continue;
+        }
+        let Some(node) = conv.mapping.get(key) else {
+            continue;
+        };
+        let mut kept_parent = parent.clone();
+        if let Some(msg) = &node.message {
+            match chatgpt_text(msg, &mut images) {
+                Some((role, text)) if role == "system" => {
+                    system_prompt.get_or_insert(text);
+                }
+                Some((role, text)) if role == "user" || role == "assistant" => {
+                    if role == "assistant" && model.is_none() {
+                        model = msg
+                            .metadata
+                            .get("model_slug")
+                            .and_then(Value::as_str)
+                            .map(str::to_string);
+                    }
+                    let msg_id = msg.id.clone().unwrap_or_else(|| key.clone());
+                    messages.push(ExportedMessage {
+                        id: msg_id.clone(),
+                        role,
+                        content: text,
+                        timestamp: msg.create_time.map(|t| t as i64).unwrap_or(created_at),
+                        tokens_used: None,
+                        parent_message_id: parent,
+                        attachments: Vec::new(),
+                    });
+                    kept_ids.insert(key.as_str(), msg_id.clone());
+                    kept_parent = Some(msg_id);
+                }
+                Some(_) => skipped += 1,
+                None if is_tool_call(msg) => skipped += 1,
+                None => {}
+            }
+        }
+        for child in node.children.iter().rev() {
+            stack.push((child, kept_parent.clone()));
+        }
+    }
+
+    // The node shown in ChatGPT may be a dropped one; select its nearest kept
+    // ancestor instead
+    let mut active_leaf_id = None;
+    let mut cursor = conv.current_node.clone();
+    let mut seen = HashSet::new();
+    while let Some(key) = cursor {
+        if !seen.insert(key.clone()) {
+            break;
+        }
+        if let Some(id) = kept_ids.get(key.as_str()) {
+            active_leaf_id = Some(id.clone());
+            break;
+        }
+        cursor = conv.mapping.get(&key).and_then(|n| n.parent.clone());
+    }
+
+    if skipped > 0 {
+        warnings.push(format!(
+            "\"{}\": skipped {} tool or non-text message(s)",
+            title, skipped
+        ));
+    }
+    if images > 0 {
+        warnings.push(format!(
+            "\"{}\": {} image(s) are not included in the export and were left out",
+            title, images
+        ));
+    }
+
+    Ok(ExportedConversation {
+        id,
+        title,
+        provider: "openai".to_string(),
+        model: model.unwrap_or_else(|| CHATGPT_DEFAULT_MODEL.to_string()),
+        system_prompt,
+        created_at,
+        updated_at: conv.update_time.map(|t| t as i64).unwrap_or(created_at),
+        active_leaf_id: active_leaf_id.or_else(|| messages.last().map(|m| m.id.clone())),
+        parent_conversation_id: None,
+        branch_point_message_id: None,
+        profile: None,
+        template: None,
+        tags: Vec::new(),
+        messages: order_tree(messages),
+    })
+}
+
+#[derive(Deserialize)]
+struct ClaudeConversation {
+    uuid: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    created_at: Option<String>,
+    #[serde(default)]
+    updated_at: Option<String>,
+    #[serde(default)]
+    chat_messages: Vec<ClaudeMessage>,
+    #[serde(default)]
+    current_leaf_message_uuid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ClaudeMessage {
+    uuid: String,
+    /// `human` or `assistant`
+    sender: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    content: Vec<ClaudeContent>,
+    #[serde(default)]
+    created_at: Option<String>,
+    /// Present in exports since Claude.ai added message editing
+    #[serde(default)]
+    parent_message_uuid: Option<String>,
+    #[serde(default)]
+    attachments: Vec<ClaudeAttachment>,
+    /// Uploaded images and PDFs; the export lists them without contents
+    #[serde(default)]
+    files: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct ClaudeContent {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ClaudeAttachment {
+    #[serde(default)]
+    file_name: String,
+    #[serde(default)]
+    extracted_content: Option<String>,
+}
+
+fn parse_timestamp(value: Option<&str>) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(value?)
+        .ok()
+        .map(|dt| dt.timestamp())
+}
+
+fn parse_claude(item: Value, warnings: &mut Vec<String>) -> AppResult<ExportedConversation> {
+    let conv: ClaudeConversation = serde_json::from_value(item)
+        .map_err(|e| AppError::from(e).context("Invalid Claude.ai conversation"))?;
+    let title = if conv.name.trim().is_empty() {
+        "Imported Claude conversation".to_string()
+    } else {
+        conv.name.clone()
+    };
+    let created_at = parse_timestamp(conv.created_at.as_deref()).unwrap_or(0);
+    // Exports without parent links list a single linear path
+    let linear = conv
+        .chat_messages
+        .iter()
+        .all(|m| m.parent_message_uuid.is_none());
+
+    let mut messages = Vec::with_capacity(conv.chat_messages.len());
+    let mut files = 0;
+    let mut previous: Option<String> = None;
+    for msg in conv.chat_messages {
+        let role = match msg.sender.as_str() {
+            "human" => "user",
+            "assistant" => "assistant",
+            _ => continue,
+        };
+        let blocks: Vec<&str> = msg
+            .content
+            .iter()
+            .filter(|c| c.kind == "text")
+            .filter_map(|c| c.text.as_deref())
+            .filter(|t| !t.is_empty())
+            .collect();
+        let content = if blocks.is_empty() {
+            msg.text.clone()
+        } else {
+            blocks.join("\n\n")
+        };
+        files += msg.files.len();
+        let parent_message_id = if linear {
+            previous.clone()
+        } else {
+            msg.parent_message_uuid.filter(|p| p != CLAUDE_ROOT_PARENT)
+        };
+        messages.push(ExportedMessage {
+            id: msg.uuid.clone(),
+            role: role.to_string(),
+            content,
+            timestamp: parse_timestamp(msg.created_at.as_deref()).unwrap_or(created_at),
+            tokens_used: None,
+            parent_message_id,
+            attachments: msg
+                .attachments
+                .iter()
+                .filter_map(|a| {
+                    let text = a.extracted_content.as_deref()?;
+                    Some(text_attachment(&a.file_name, text))
+                })
+                .collect(),
+        });
+        previous = Some(msg.uuid);
+    }
+
+    if files > 0 {
+        warnings.push(format!(
+            "\"{}\": {} uploaded file(s) are not included in the export and were left out",
+            title, files
+        ));
+    }
+
+    let active_leaf_id = conv
+        .current_leaf_message_uuid
+        .filter(|id| messages.iter().any(|m| &m.id == id))
+        .or_else(|| {
+            messages
+                .iter()
+                .max_by_key(|m| m.timestamp)
+                .map(|m| m.id.clone())
+        });
+
+    Ok(ExportedConversation {
+        id: conv.uuid,
+        title,
+        provider: "anthropic".to_string(),
+        model: CLAUDE_DEFAULT_MODEL.to_string(),
+        system_prompt: None,
+        created_at,
+        updated_at: parse_timestamp(conv.updated_at.as_deref()).unwrap_or(created_at),
+        active_leaf_id,
+        parent_conversation_id: None,
+        branch_point_message_id: None,
+        profile: None,
+        template: None,
+        tags: Vec::new(),
+        messages: order_tree(messages),
+    })
+}
+
+/// Role named by a transcript line, with any text following it on the same
+/// line. Accepts `## User`, `## 👤 User` (this app's Markdown export),
+/// `**Assistant:**`, `Assistant: text` and similar.
+fn markdown_role(line: &str) -> Option<(&'static str, &str)> {
+    let trimmed = line.trim();
+    let heading = trimmed.starts_with('#');
+    let body = trimmed.trim_start_matches('#').trim_start();
+    let body = body.trim_start_matches(|c: char| !c.is_alphanumeric() && c != '*');
+    let body = body.trim_start_matches("**").trim_start();
+
+    let word_end = body
+        .find(|c: char| !c.is_alphanumeric())
+        .unwrap_or(body.len());
+    let role = match body[..word_end].to_ascii_lowercase().as_str() {
+        "user" | "you" | "human" => "user",
+        "assistant" | "ai" | "chatgpt" | "claude" | "gemini" | "bot" => "assistant",
+        "system" => "system",
+        _ => return None,
+    };
+    let rest = body[word_end..].trim_start_matches("**");
+    let (colon, rest) = match rest.strip_prefix(':') {
+        Some(after) => (true, after.trim_start_matches("**")),
+        None => (false, rest),
+    };
+    let rest = rest.trim();
+    // A plain line needs the colon; a heading must name only the role
+    if colon || (heading && rest.is_empty()) {
+        Some((role, rest))
+    } else {
+        None
+    }
+}
+
+/// `**Key:** value` header line of this app's Markdown export
+fn markdown_field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    line.trim()
+        .strip_prefix(&format!("**{}:**", key))
+        .map(str::trim)
+}
+
+/// Drop export chrome trailing a message: separators, branch markers and the
+/// token count line, which is returned
+fn strip_trailer(content: &mut String) -> Option<i64> {
+    let mut tokens = None;
+    loop {
+        let trimmed = content.trim_end();
+        let last = trimmed.rsplit('\n').next().unwrap_or("").trim();
+        let tokens_line = last
+            .strip_prefix("*Tokens used: ")
+            .and_then(|t| t.strip_suffix('*'))
+            .and_then(|t| t.parse::<i64>().ok());
+        let is_chrome = last == "---"
+            || (last.starts_with("> *Branch ") && last.ends_with('*'))
+            || tokens_line.is_some();
+        if !is_chrome {
+            content.truncate(trimmed.len());
+            return tokens;
+        }
+        tokens = tokens.or(tokens_line);
+        let cut = trimmed.len() - last.len();
+        content.truncate(cut);
+    }
+}
+
+fn parse_markdown(content: &str) -> AppResult<ExportedConversation> {
+    let id = derived_id(content);
+    let mut title = None;
+    let mut provider = None;
+    let mut model = None;
+    let mut created_at = None;
+    let mut turns: Vec<(&str, String)> = Vec::new();
+    let mut in_fence = false;
+
+    for line in content.lines() {
+        if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        if !in_fence {
+            if let Some((role, rest)) = markdown_role(line) {
+                turns.push((role, rest.to_string()));
+                continue;
+            }
+        }
+        match turns.last_mut() {
+            Some((_, text)) => {
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(line);
+            }
+            None => {
+                let trimmed = line.trim();
+                if let Some(h1) = trimmed.strip_prefix("# ") {
+                    title.get_or_insert_with(|| h1.trim().to_string());
+                } else if let Some(value) = markdown_field(line, "Provider") {
+                    provider = Some(value.to_string());
+                } else if let Some(value) = markdown_field(line, "Model") {
+                    model = Some(value.to_string());
+                } else if let Some(value) = markdown_field(line, "Created") {
+                    created_at = chrono::NaiveDateTime::parse_from_str(
+                        value.trim_end_matches(" UTC"),
+                        "%Y-%m-%d %H:%M:%S",
+                    )
+                    .ok()
+                    .map(|dt| dt.and_utc().timestamp());
+                }
+            }
+        }
+    }
+
+    if turns.is_empty() {
+        return Err(AppError::invalid_input(
+            "No messages found: expected headings or lines such as \"## User\" or \"Assistant:\"",
+        ));
+    }
+
+    let created_at = created_at.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let mut system_prompt = None;
+    let mut messages: Vec<ExportedMessage> = Vec::with_capacity(turns.len());
+    for (index, (role, mut text)) in turns.into_iter().enumerate() {
+        let tokens_used = strip_trailer(&mut text);
+        let text = text.trim().to_string();
+        if role == "system" && messages.is_empty() && system_prompt.is_none() {
+            system_prompt = Some(text);
+            continue;
+        }
+        messages.push(ExportedMessage {
+            id: derived_id(&format!("{}:{}", id, index)),
+            role: role.to_string(),
+            content: text,
+            // Transcripts carry no per-message times; keep them in order
+            timestamp: created_at + index as i64,
+            tokens_used,
+            parent_message_id: messages.last().map(|m| m.id.clone()),
+            attachments: Vec::new(),
+        });
+    }
+
+    Ok(ExportedConversation {
+        id,
+        title: title.unwrap_or_else(|| "Imported transcript".to_string()),
+        provider: provider.unwrap_or_else(|| "imported".to_string()),
+        model: model.unwrap_or_else(|| "unknown".to_string()),
+        system_prompt,
+        created_at,
+        updated_at: created_at + messages.len() as i64,
+        active_leaf_id: messages.last().map(|m| m.id.clone()),
+        parent_conversation_id: None,
+        branch_point_message_id: None,
+        profile: None,
+        template: None,
+        tags: Vec::new(),
+        messages,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn chatgpt_node(
+        id: &str,
+        role: &str,
+        text: &str,
+        parent: Option<&str>,
+        children: &[&str],
+    ) -> Value {
+        json!({
+            "id": id,
+            "message": {
+                "id": id,
+                "author": {"role": role},
+                "create_time": 1700000000.5,
+                "content": {"content_type": "text", "parts": [text]},
+                "recipient": "all",
+                "metadata": {}
+            },
+            "parent": parent,
+            "children": children,
+        })
+    }
+
+    fn chatgpt_export() -> String {
+        json!([{
+            "title": "Rust help",
+            "create_time": 1700000000.0,
+            "update_time": 1700000100.0,
+            "conversation_id": "conv-1",
+            "current_node": "a2",
+            "default_model_slug": "gpt-4o-mini",
+            "mapping": {
+                "root": {"id": "root", "message": null, "parent": null, "children": ["sys"]},
+                "sys": chatgpt_node("sys", "system", "", Some("root"), &["u1"]),
+                "u1": chatgpt_node("u1", "user", "hi", Some("sys"), &["a1", "a2"]),
+                "a1": chatgpt_node("a1", "assistant", "first answer", Some("u1"), &[]),
+                "a2": chatgpt_node("a2", "assistant", "second answer", Some("u1"), &[]),
+            }
+        }])
+        .to_string()
+    }
+
+    #[test]
+    fn detect_recognizes_each_format() {
+        assert_eq!(
+            ImportFormat::detect(&chatgpt_export()).unwrap(),
+            ImportFormat::Chatgpt
+        );
+        let claude = json!([{"uuid": "c", "chat_messages": []}]).to_string();
+        assert_eq!(ImportFormat::detect(&claude).unwrap(), ImportFormat::Claude);
+        let native = json!({"version": "1.0.0", "export_timestamp": 0, "conversations": []});
+        assert_eq!(
+            ImportFormat::detect(&native.to_string()).unwrap(),
+            ImportFormat::Native
+        );
+        assert_eq!(
+            ImportFormat::detect("## User\nhi").unwrap(),
+            ImportFormat::Markdown
+        );
+        assert!(ImportFormat::detect("{\"foo\": 1}").is_err());
+    }
+
+    #[test]
+    fn chatgpt_mapping_keeps_branches_and_current_node() {
+        let parsed = parse(&chatgpt_export(), None).expect("parse");
+        let conv = &parsed.data.conversations[0];
+        assert_eq!(conv.id, "conv-1");
+        assert_eq!(conv.model, "gpt-4o-mini");
+        // The empty system root is dropped and the user message becomes the root
+        let ids: Vec<&str> = conv.messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["u1", "a1", "a2"]);
+        assert_eq!(conv.messages[0].parent_message_id, None);
+        assert_eq!(conv.messages[1].parent_message_id.as_deref(), Some("u1"));
+        assert_eq!(conv.messages[2].parent_message_id.as_deref(), Some("u1"));
+        assert_eq!(conv.active_leaf_id.as_deref(), Some("a2"));
+        assert_eq!(conv.messages[0].timestamp, 1700000000);
+    }
+
+    #[test]
+    fn claude_export_follows_parent_links_and_keeps_attachments() {
+        let export = json!([{
+            "uuid": "claude-1",
+            "name": "Trip",
+            "created_at": "2024-05-01T10:00:00.000000Z",
+            "updated_at": "2024-05-01T10:05:00.000000Z",
+            "chat_messages": [
+                {"uuid": "m1", "sender": "human", "text": "plan a trip",
+                 "created_at": "2024-05-01T10:00:00Z",
+                 "parent_message_uuid": CLAUDE_ROOT_PARENT,
+                 "attachments": [{"file_name": "notes.txt", "file_type": "txt",
+                                  "extracted_content": "Lisbon"}],
+                 "files": []},
+                {"uuid": "m2", "sender": "assistant", "text": "",
+                 "content": [{"type": "text", "text": "Sure"}],
+                 "created_at": "2024-05-01T10:01:00Z", "parent_message_uuid": "m1"},
+                {"uuid": "m3", "sender": "assistant", "text": "Retry",
+                 "created_at": "2024-05-01T10:02:00Z", "parent_message_uuid": "m1",
+                 "files": [{"file_name": "map.png"}]}
+            ]
+        }])
+        .to_string();
+
+        let parsed = parse(&export, None).expect("parse");
+        assert_eq!(parsed.format, ImportFormat::Claude);
+        assert_eq!(parsed.warnings.len(), 1);
+        let conv = &parsed.data.conversations[0];
+        assert_eq!(conv.provider, "anthropic");
+        assert_eq!(conv.messages[0].role, "user");
+        assert_eq!(conv.messages[0].parent_message_id, None);
+        assert_eq!(conv.messages[0].attachments[0].kind, "file");
+        assert_eq!(conv.messages[1].content, "Sure");
+        assert_eq!(conv.messages[2].parent_message_id.as_deref(), Some("m1"));
+        assert_eq!(conv.active_leaf_id.as_deref(), Some("m3"));
+    }
+
+    #[test]
+    fn markdown_reads_own_export_and_plain_transcripts() {
+        let exported = "# Debugging\n\n**Provider:** openai\n**Model:** gpt-4o\n\
+            **Created:** 2024-01-02 03:04:05 UTC\n\n---\n\n## 👤 User\n\nWhy?\n\n---\n\n\
+            ## 🤖 Assistant\n\nBecause.\n\n```md\n## User\n```\n\n*Tokens used: 12*\n\n---\n\n";
+        let conv = parse(exported, None)
+            .expect("parse")
+            .data
+            .conversations
+            .remove(0);
+        assert_eq!(conv.title, "Debugging");
+        assert_eq!(conv.provider, "openai");
+        assert_eq!(conv.created_at, 1704164645);
+        assert_eq!(conv.messages.len(), 2);
+        assert_eq!(conv.messages[0].content, "Why?");
+        assert_eq!(conv.messages[1].content, "Because.\n\n```md\n## User\n```");
+        assert_eq!(conv.messages[1].tokens_used, Some(12));
+        assert_eq!(
+            conv.messages[1].parent_message_id.as_deref(),
+            Some(conv.messages[0].id.as_str())
+        );
+
+        let plain = "System: be terse\nUser: hello\nthere\n**Assistant:** hi";
+        let conv = parse(plain, None)
+            .expect("parse")
+            .data
+            .conversations
+            .remove(0);
+        assert_eq!(conv.system_prompt.as_deref(), Some("be terse"));
+        assert_eq!(conv.messages[0].content, "hello\nthere");
+        assert_eq!(conv.messages[1].content, "hi");
+        // Ids are derived from the content so a re-import is recognized
+        assert_eq!(
+            conv.id,
+            parse(plain, None).unwrap().data.conversations[0].id
+        );
+
+        assert!(parse("just some notes", Some(ImportFormat::Markdown)).is_err());
+    }
+
+    fn native_export(conversations: Value) -> String {
+        json!({"version": "1.0.0", "export_timestamp": 0, "conversations": conversations})
+            .to_string()
+    }
+
+    fn native_conversation(id: &str, messages: &[(&str, &str, &str)]) -> Value {
+        json!({
+            "id": id,
+            "title": "Notes",
+            "provider": "openai",
+            "model": "gpt-4o",
+            "system_prompt": null,
+            "created_at": 1,
+            "updated_at": 2,
+            "tags": [{"name": "work", "color": "#ff0000"}],
+            "messages": messages
+                .iter()
+                .enumerate()
+                .map(|(i, (id, role, content))| json!({
+                    "id": id, "role": role, "content": content,
+                    "timestamp": i, "tokens_used": null,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    #[test]
+    fn preview_counts_branches_and_existing_conversations() {
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+        let dir = tempfile::tempdir().expect("tempdir");
+        let blobs = BlobStore::new(dir.path().to_path_buf());
+        let parsed = parse(&chatgpt_export(), None).expect("parse");
+
+        let report = preview(&conn, &parsed, ConflictStrategy::Skip).expect("preview");
+        assert_eq!(report.conversations_to_create, 1);
+        assert_eq!(report.messages_to_create, 3);
+        assert_eq!(report.conversations[0].branch_count, 2);
+        assert_eq!(report.conversations[0].action, ImportAction::Created);
+
+        import(&conn, &blobs, parsed, ConflictStrategy::Skip).expect("import");
+        let parsed = parse(&chatgpt_export(), None).expect("parse");
+        let report = preview(&conn, &parsed, ConflictStrategy::Merge).expect("preview");
+        assert!(report.conversations[0].exists);
+        assert_eq!(report.conversations[0].action, ImportAction::Merged);
+        assert_eq!(report.messages_to_create, 0);
+    }
+
+    #[test]
+    fn parent_cycles_are_broken_instead_of_dropping_messages() {
+        let mut conversation = native_conversation("c1", &[]);
+        conversation["messages"] = json!([
+            {"id": "a", "role": "user", "content": "a", "timestamp": 0, "tokens_used": null},
+            {"id": "b", "role": "assistant", "content": "b", "timestamp": 1,
+             "tokens_used": null, "parent_message_id": "c"},
+            {"id": "c", "role": "user", "content": "c", "timestamp": 2,
+             "tokens_used": null, "parent_message_id": "b"},
+            {"id": "d", "role": "assistant", "content": "d", "timestamp": 3,
+             "tokens_used": null, "parent_message_id": "c"},
+        ]);
+        let parsed = parse(&native_export(json!([conversation])), None).expect("parse");
+
+        let messages = &parsed.data.conversations[0].messages;
+        let ids: Vec<&str> = messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c", "d"]);
+        assert_eq!(messages[1].parent_message_id, None);
+        assert_eq!(messages[2].parent_message_id.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn failed_import_writes_nothing() {
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+        let dir = tempfile::tempdir().expect("tempdir");
+        let blobs = BlobStore::new(dir.path().to_path_buf());
+        let export = native_export(json!([
+            native_conversation("good", &[("g1", "user", "hi")]),
+            // Rejected by the role CHECK constraint
+            native_conversation("bad", &[("b1", "tool", "output")]),
+        ]));
+
+        let parsed = parse(&export, None).expect("parse");
+        assert!(import(&conn, &blobs, parsed, ConflictStrategy::Skip).is_err());
+        assert!(!Conversation::id_exists(&conn, "good").unwrap());
+        assert!(Tag::get_by_name(&conn, "work").unwrap().is_none());
+    }
+
+    #[test]
+    fn attachments_are_labelled_by_their_contents() {
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+        let dir = tempfile::tempdir().expect("tempdir");
+        let blobs = BlobStore::new(dir.path().to_path_buf());
+        let with_attachment = |id: &str, data: &[u8]| {
+            let mut conv = native_conversation(id, &[(&format!("{}-m", id), "user", "hi")]);
+            // Claims to be an image, whatever the contents
+            conv["messages"][0]["attachments"] = json!([{
+                "kind": "image", "file_name": "shot.png", "mime_type": "image/png",
+                "sha256": "0".repeat(64), "data": attachments::encode_base64(data),
+            }]);
+            conv
+        };
+
+        let export = native_export(json!([with_attachment("text", b"plain text")]));
+        let parsed = parse(&export, None).expect("parse");
+        import(&conn, &blobs, parsed, ConflictStrategy::Skip).expect("import");
+        let imported = Attachment::get_by_conversation(&conn, "text").expect("attachments");
+        assert_eq!(imported[0].kind, "file");
+        assert_eq!(imported[0].mime_type, "text/plain");
+
+        let export = native_export(json!([with_attachment("binary", &[0xff, 0xfe, 0x00])]));
+        let parsed = parse(&export, None).expect("parse");
+        let err = import(&conn, &blobs, parsed, ConflictStrategy::Skip).unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::Unsupported);
+        assert!(!Conversation::id_exists(&conn, "binary").unwrap());
+    }
+
+    #[test]
+    fn conflict_strategies() {
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+        let dir = tempfile::tempdir().expect("tempdir");
+        let blobs = BlobStore::new(dir.path().to_path_buf());
+        let run = |messages: &[(&str, &str, &str)], conflict| {
+            let export = native_export(json!([native_conversation("c1", messages)]));
+            import(&conn, &blobs, parse(&export, None).unwrap(), conflict).expect("import")
+        };
+        let original = [("m1", "user", "hi"), ("m2", "assistant", "hello")];
+        let extended = [
+            ("m1", "user", "hi"),
+            ("m2", "assistant", "hello"),
+            ("m3", "user", "more"),
+        ];
+
+        let result = run(&original, ConflictStrategy::Skip);
+        assert_eq!(result.outcomes[0].action, ImportAction::Created);
+        let tags = Tag::get_for_conversation(&conn, "c1").unwrap();
+        assert_eq!(tags[0].name, "work");
+        assert_eq!(tags[0].color.as_deref(), Some("#ff0000"));
+
+        let result = run(&extended, ConflictStrategy::Skip);
+        assert_eq!(result.outcomes[0].action, ImportAction::Skipped);
+        assert_eq!(result.messages_imported, 0);
+
+        let result = run(&extended, ConflictStrategy::Merge);
+        assert_eq!(result.outcomes[0].action, ImportAction::Merged);
+        assert_eq!(result.messages_imported, 1);
+        let path = Message::get_by_conversation(&conn, "c1").unwrap();
+        assert_eq!(path.len(), 3);
+        assert_eq!(path[2].parent_message_id.as_deref(), Some("m2"));
+
+        let result = run(&[("n1", "user", "replaced")], ConflictStrategy::Overwrite);
+        assert_eq!(result.outcomes[0].action, ImportAction::Overwritten);
+        let tree = Message::get_tree(&conn, "c1").unwrap();
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].content, "replaced");
+        assert_eq!(Tag::get_for_conversation(&conn, "c1").unwrap().len(), 1);
+
+        let result = run(&[("n1", "user", "replaced")], ConflictStrategy::Copy);
+        let outcome = &result.outcomes[0];
+        assert_eq!(outcome.action, ImportAction::Copied);
+        assert_ne!(outcome.conversation_id, "c1");
+        assert_eq!(outcome.title, "Notes (copy)");
+        let copy = Message::get_tree(&conn, &outcome.conversation_id).unwrap();
+        assert_eq!(copy.len(), 1);
+        assert_ne!(copy[0].id, "n1");
+    }
+
+    #[test]
+    fn branch_links_profile_and_template_survive_import() {
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().expect("lock conn");
+        let dir = tempfile::tempdir().expect("tempdir");
+        let blobs = BlobStore::new(dir.path().to_path_buf());
+
+        let mut branch = native_conversation("branch", &[("b1", "user", "hi")]);
+        branch["parent_conversation_id"] = json!("parent");
+        branch["branch_point_message_id"] = json!("p1");
+        // Matched by name when the id is unknown; unknown templates are dropped
+        branch["profile"] = json!({"id": "elsewhere", "name": "Default"});
+        branch["template"] = json!({"id": "missing", "name": "Missing"});
+        let parent =
+            native_conversation("parent", &[("p1", "user", "hi"), ("p2", "assistant", "yo")]);
+        // The branch comes first, so links are restored after every insert
+        let mut export: Value =
+            serde_json::from_str(&native_export(json!([branch, parent]))).unwrap();
+        export["version"] = json!(EXPORT_VERSION);
+        let export = export.to_string();
+
+        import(
+            &conn,
+            &blobs,
+            parse(&export, None).unwrap(),
+            ConflictStrategy::Skip,
+        )
+        .expect("import");
+        let imported = Conversation::get_by_id(&conn, "branch").unwrap().unwrap();
+        assert_eq!(imported.parent_conversation_id.as_deref(), Some("parent"));
+        assert_eq!(imported.branch_point_message_id.as_deref(), Some("p1"));
+        assert_eq!(imported.profile_id.as_deref(), Some("default"));
+        assert_eq!(imported.template_id, None);
+
+        // Copies link to the copied parent and message
+        let result = import(
+            &conn,
+            &blobs,
+            parse(&export, None).unwrap(),
+            ConflictStrategy::Copy,
+        )
+        .expect("import copy");
+        let branch_copy = &result.outcomes[0].conversation_id;
+        let parent_copy = &result.outcomes[1].conversation_id;
+        let copied = Conversation::get_by_id(&conn, branch_copy)
+            .unwrap()
+            .unwrap();
+        assert_eq!(copied.parent_conversation_id.as_ref(), Some(parent_copy));
+        let branch_point = copied.branch_point_message_id.expect("branch point");
+        assert_eq!(
+            Message::get_by_id(&conn, &branch_point)
+                .unwrap()
+                .unwrap()
+                .conversation_id,
+            *parent_copy
+        );
+    }
+
+    #[test]
+    fn native_versions_up_to_current_are_accepted() {
+        let export = |version: &str| {
+            json!({"version": version, "export_timestamp": 0, "conversations": []}).to_string()
+        };
+        assert!(parse(&export("1.0.0"), None).is_ok());
+        assert!(parse(&export(EXPORT_VERSION), None).is_ok());
+        assert!(parse(&export("3.0.0"), None).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::error::{AppError, AppResult};
+use crate::notifications::Completion;
 use serde_json::Value as JsonValue;
 use std::io::{BufRead, BufReader, Write};
 use std::net::{TcpListener, TcpStream};
@@ -9,7 +11,8 @@ use tauri::{AppHandle, Emitter, Manager};
 /// Configuration for IPC server performance tuning
 const CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
 const BUFFER_SIZE: usize = 8192;
-const MAX_MESSAGE_SIZE: usize = 1024 * 1024; // 1MB limit
+// Room for base64-encoded attachments from `lai ask` (20 MB each)
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
 
 #[derive(serde::Deserialize, Debug)]
 struct IpcMessage {
@@ -28,6 +31,19 @@ struct IpcResponse {
     data: Option<JsonValue>,
 }
 
+impl IpcResponse {
+    /// Error response carrying the error's stable code. `error` repeats the
+    /// message for clients written before codes existed.
+    fn error(err: AppError) -> Self {
+        let mut data = serde_json::to_value(&err).unwrap_or_default();
+        data["error"] = JsonValue::String(err.message);
+        IpcResponse {
+            status: "error".to_string(),
+            data: Some(data),
+        }
+    }
+}
+
 /// Performance metrics for monitoring
 #[derive(Debug)]
 struct ConnectionMetrics {
@@ -62,10 +78,7 @@ fn handle_client(mut stream: TcpStream, app: AppHandle, dev_mode_enabled: bool)
 
                 // Check message size limit
                 if line.len() > MAX_MESSAGE_SIZE {
-                    let response = IpcResponse {
-                        status: "error".to_string(),
-                        data: Some(serde_json::json!({"error": "Message too large"})),
-                    };
+                    let response = IpcResponse::error(AppError::invalid_input("Message too large"));
                     let _ = write_response(&mut stream, &response);
                     continue;
                 }
@@ -81,10 +94,7 @@ fn handle_client(mut stream: TcpStream, app: AppHandle, dev_mode_enabled: bool)
                         handle_message(&mut stream, &app, &msg, dev_mode_enabled);
                     }
                     Err(_) => {
-                        let response = IpcResponse {
-                            status: "error".to_string(),
-                            data: Some(serde_json::json!({"error": "Invalid JSON"})),
-                        };
+                        let response = IpcResponse::error(AppError::invalid_input("Invalid JSON"));
                         let _ = write_response(&mut stream, &response);
                     }
                 }
@@ -126,7 +136,9 @@ fn handle_message(
 ) {
     let response = match msg.kind.as_str() {
         "notify" => {
-            let _ = app.emit("cli://notify", msg.message.as_deref().unwrap_or_default());
+            let text = msg.message.clone().unwrap_or_default();
+            let _ = app.emit("cli://notify", &text);
+            crate::notifications::notify(app, Completion::Message { text }, None);
             IpcResponse {
                 status: "ok".to_string(),
                 data: None,
@@ -145,16 +157,15 @@ fn handle_message(
             }
         }
         "last" => handle_last_message(app),
+        "usage" => handle_usage_report(app, msg),
+        "export" => handle_export(app, msg),
         "create" => {
             if dev_mode_enabled {
                 handle_create_message(app, msg)
             } else {
-                IpcResponse {
-                    status: "error".to_string(),
-                    data: Some(
-                        serde_json::json!({"error": "create command only available in DEV_MODE"}),
-                    ),
-                }
+                IpcResponse::error(AppError::unsupported(
+                    "create command only available in DEV_MODE",
+                ))
             }
         }
         _ => {
@@ -182,24 +193,102 @@ fn handle_last_message(app: &AppHandle) -> IpcResponse {
             status: "ok".to_string(),
             data: serde_json::to_value(&message).ok(),
         },
-        Ok(None) => IpcResponse {
-            status: "error".to_string(),
-            data: Some(serde_json::json!({"error": "No messages found"})),
+        Ok(None) => IpcResponse::error(AppError::not_found("No messages found")),
+        Err(e) => IpcResponse::error(e),
+    }
+}
+
+/// Spending report for `lai usage`; payload `{range, group_by}`
+fn handle_usage_report(app: &AppHandle, msg: &IpcMessage) -> IpcResponse {
+    let field = |name: &str, default: &str| {
+        msg.payload
+            .as_ref()
+            .and_then(|p| p.get(name))
+            .and_then(|v| v.as_str())
+            .unwrap_or(default)
+            .to_string()
+    };
+    let range = field("range", "month");
+    let group_by = field("group_by", "model");
+
+    let db = app.state::<crate::database::Database>();
+    let result = db
+        .conn()
+        .lock()
+        .and_then(|conn| crate::usage::usage_report(&conn, &range, &group_by));
+
+    match result {
+        Ok(report) => IpcResponse {
+            status: "ok".to_string(),
+            data: serde_json::to_value(&report).ok(),
         },
-        Err(e) => IpcResponse {
-            status: "error".to_string(),
-            data: Some(serde_json::json!({"error": e})),
+        Err(e) => IpcResponse::error(e),
+    }
+}
+
+/// Directory export for `lai export`; payload `{path, format, filter, whole_tree}`
+fn handle_export(app: &AppHandle, msg: &IpcMessage) -> IpcResponse {
+    #[derive(serde::Deserialize)]
+    struct ExportRequest {
+        path: String,
+        #[serde(default)]
+        format: Option<String>,
+        #[serde(default)]
+        filter: crate::exporters::ExportFilter,
+        #[serde(default)]
+        whole_tree: bool,
+    }
+
+    let request = match msg
+        .payload
+        .clone()
+        .map(serde_json::from_value::<ExportRequest>)
+    {
+        Some(Ok(request)) => request,
+        Some(Err(e)) => {
+            return IpcResponse::error(AppError::invalid_input(format!(
+                "Invalid export request: {}",
+                e
+            )))
+        }
+        None => {
+            return IpcResponse::error(AppError::invalid_input(
+                "No payload provided for export command",
+            ))
+        }
+    };
+
+    let result = request
+        .format
+        .as_deref()
+        .unwrap_or("markdown")
+        .parse()
+        .and_then(|format| {
+            crate::commands::export::export_to_directory(
+                app.state(),
+                app.state(),
+                request.path,
+                format,
+                Some(request.filter),
+                Some(request.whole_tree),
+            )
+        });
+
+    match result {
+        Ok(export) => IpcResponse {
+            status: "ok".to_string(),
+            data: serde_json::to_value(&export).ok(),
         },
+        Err(e) => IpcResponse::error(e),
     }
 }
 
 /// Optimized create message handler with transaction management
 fn handle_create_message(app: &AppHandle, msg: &IpcMessage) -> IpcResponse {
     let Some(ref payload) = msg.payload else {
-        return IpcResponse {
-            status: "error".to_string(),
-            data: Some(serde_json::json!({"error": "No payload provided for create command"})),
-        };
+        return IpcResponse::error(AppError::invalid_input(
+            "No payload provided for create command",
+        ));
     };
 
     let content = payload
@@ -214,25 +303,25 @@ fn handle_create_message(app: &AppHandle, msg: &IpcMessage) -> IpcResponse {
         .map(|s| s.to_string());
 
     let db = app.state::<crate::database::Database>();
-    let result = tokio::runtime::Handle::current().block_on(async {
+    let result: AppResult<_> = tokio::runtime::Handle::current().block_on(async {
         // Optimize conversation creation by reusing connections
         let conv_id = if let Some(cid) = conversation_id {
             cid
         } else {
             // Create conversation in a single transaction
-            let conn = db.conn().lock().map_err(|e| e.to_string())?;
+            let conn = db.conn().lock()?;
             let new_conv = crate::database::conversations::NewConversation {
                 title: "Dev Test Conversation".to_string(),
                 model: "dev-model".to_string(),
                 provider: "dev-provider".to_string(),
                 system_prompt: None,
             };
-            let conv = crate::database::conversations::Conversation::create(&conn, new_conv)
-                .map_err(|e| e.to_string())?;
+            let conv = crate::database::conversations::Conversation::create(&conn, new_conv)?;
             conv.id
         };
 
         crate::commands::messages::create_message(
+            app.clone(),
             db,
             conv_id,
             "assistant".to_string(),
@@ -247,10 +336,7 @@ fn handle_create_message(app: &AppHandle, msg: &IpcMessage) -> IpcResponse {
             status: "ok".to_string(),
             data: serde_json::to_value(&message).ok(),
         },
-        Err(e) => IpcResponse {
-            status: "error".to_string(),
-            data: Some(serde_json::json!({"error": e})),
-        },
+        Err(e) => IpcResponse::error(e),
     }
 }
 
@@ -1,14 +1,61 @@
 // Consolidated Tauri entrypoint: initializes database, registers plugins and commands
 // This is the authoritative run() that `src/main.rs` calls.
+pub mod accelerator;
+pub mod attachments;
+pub mod backup;
+pub mod branch_diff;
+pub mod clipboard;
 pub mod commands;
+pub mod context_builder;
 pub mod database;
+mod dbus_service;
+pub mod error;
+pub mod exporters;
 pub mod git;
+pub mod importers;
 mod ipc;
+pub mod notifications;
+pub mod pdf;
 pub mod project;
+pub mod project_index;
+pub mod provider_retry;
+pub mod shortcut_portal;
+pub mod summarizer;
+pub mod tokenizer;
+pub mod updater;
+pub mod usage;
 
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tauri::{Emitter, Manager};
 
+/// Startup work that reads the database: binding the configured global
+/// shortcuts and restoring the window state. While an encrypted database waits
+/// for its key only the default shortcuts are bound, and `unlock_database`
+/// calls this again to finish the job after the first unlock.
+pub(crate) fn load_database_settings(app: &tauri::AppHandle) {
+    static LOADED: AtomicBool = AtomicBool::new(false);
+
+    if app.state::<database::Database>().is_locked() {
+        commands::shortcuts::initialize_shortcut_manager(app.clone());
+        return;
+    }
+    if LOADED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    commands::shortcuts::initialize_shortcut_manager(app.clone());
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Some(db_state) = app_handle.try_state::<database::Database>() {
+            if let Err(e) =
+                commands::window::restore_window_state(app_handle.clone(), db_state).await
+            {
+                eprintln!("Failed to restore window state: {}", e);
+            }
+        }
+    });
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -36,39 +83,16 @@ pub fn run() {
             std::fs::create_dir_all(&app_data_dir).expect("Failed to create app data directory");
 
             let db_path: PathBuf = app_data_dir.join("database.db");
-            let db = database::Database::new(db_path).expect("Failed to initialize database");
+            // Encrypted databases open locked until their key is given
+            let encryption = database::encryption::EncryptionSettings::load(&db_path)
+                .unwrap_or_else(|e| {
+                    eprintln!("Failed to load encryption settings: {}", e);
+                    Default::default()
+                });
+            let db = database::encryption::open_database(db_path, &encryption)
+                .expect("Failed to initialize database");
             app.manage(db);
-
-            // Register a global shortcut (CommandOrControl+Space) to toggle main window.
-            // Do this by constructing the plugin with its handler here (registering it once).
-            #[cfg(not(any(target_os = "android", target_os = "ios")))]
-            {
-                use tauri_plugin_global_shortcut::{Builder as ShortcutBuilder, ShortcutState};
-
-                let builder = ShortcutBuilder::new()
-                    .with_shortcuts(["CommandOrControl+Space"])
-                    .unwrap_or_else(|e| {
-                        eprintln!("failed to parse shortcut: {}", e);
-                        ShortcutBuilder::new()
-                    })
-                    .with_handler(|app, _shortcut, event| {
-                        if event.state == ShortcutState::Pressed {
-                            if let Some(window) = app.get_webview_window("main") {
-                                match window.is_visible() {
-                                    Ok(true) => {
-                                        let _ = window.hide();
-                                    }
-                                    _ => {
-                                        let _ = window.show();
-                                        let _ = window.set_focus();
-                                    }
-                                }
-                            }
-                        }
-                    });
-
-                let _ = app.handle().plugin(builder.build());
-            }
+            app.manage(attachments::BlobStore::new(app_data_dir.join("blobs")));
 
             // Create a system tray (desktop only)
             #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -190,21 +214,8 @@ pub fn run() {
 
             println!("Database initialized successfully!");
 
-            // Initialize shortcut manager
-            commands::shortcuts::initialize_shortcut_manager(app.handle().clone());
-
-            // Restore window state on startup
-            let app_handle = app.handle().clone();
-            let db_handle = app.handle().clone();
-            tauri::async_runtime::spawn(async move {
-                if let Some(db_state) = db_handle.try_state::<database::Database>() {
-                    if let Err(e) =
-                        commands::window::restore_window_state(app_handle.clone(), db_state).await
-                    {
-                        eprintln!("Failed to restore window state: {}", e);
-                    }
-                }
-            });
+            // Bind the configured global shortcuts and restore window state
+            load_database_settings(app.handle());
 
             // Set up window event listeners for automatic state saving
             if let Some(window) = app.get_webview_window("main") {
@@ -238,6 +249,18 @@ pub fn run() {
                 });
             } // Start CLI IPC server
             crate::ipc::start_ipc_server(app.handle().clone());
+            // Desktop integration over the session bus
+            crate::dbus_service::start_dbus_service(app.handle().clone());
+            // Native notifications with action buttons
+            crate::notifications::start_notifications(app.handle().clone());
+            // Scheduled export mirror
+            crate::exporters::start_mirror(app.handle().clone());
+            // Daily automatic backups
+            crate::backup::start_backups(app.handle().clone(), app_data_dir);
+            // Update checks on the configured interval
+            crate::commands::updater::start_update_checks(app.handle().clone());
+            // Lock an encrypted database after idle timeout
+            crate::database::encryption::start_idle_lock(app.handle().clone());
             Ok(())
         })
         // Register Tauri commands implemented in `src-tauri/src/commands`
@@ -253,6 +276,10 @@ pub fn run() {
             commands::conversations::cleanup_conversations,
             commands::conversations::create_conversation_branch,
             commands::conversations::get_conversation_branches,
+            commands::conversations::diff_branches,
+            commands::conversations::merge_branch,
+            commands::conversations::regenerate_conversation_summary,
+            commands::conversations::set_conversation_project_root,
             // messages
             commands::messages::create_message,
             commands::messages::get_conversation_messages,
@@ -262,6 +289,20 @@ pub fn run() {
             commands::messages::delete_message,
             commands::messages::get_conversation_token_count,
             commands::messages::get_last_assistant_message,
+            commands::messages::get_conversation_tree,
+            commands::messages::list_siblings,
+            commands::messages::set_active_leaf,
+            commands::messages::regenerate_message,
+            commands::messages::edit_and_resubmit,
+            // attachments
+            commands::attachments::attach_data,
+            commands::attachments::get_message_attachments,
+            commands::attachments::get_conversation_attachments,
+            commands::attachments::get_attachment_data,
+            commands::attachments::delete_attachment,
+            // clipboard quick actions
+            commands::clipboard::analyze_clipboard,
+            commands::clipboard::run_clipboard_action,
             // settings
             commands::settings::set_setting,
             commands::settings::get_setting,
@@ -287,14 +328,43 @@ pub fn run() {
             commands::provider::ollama_check_connection,
             commands::provider::set_api_key,
             commands::provider::get_api_key,
+            // usage and budgets
+            commands::usage::usage_report,
+            commands::usage::get_model_prices,
+            commands::usage::set_model_price,
+            commands::usage::delete_model_price,
+            commands::usage::get_profile_budget,
+            commands::usage::set_profile_budget,
+            commands::usage::get_budget_status,
             // export/import
             commands::export::export_conversations_json,
             commands::export::export_conversation_markdown,
             commands::export::export_conversation_html,
             commands::export::export_conversation_pdf,
+            commands::export::export_conversations_pdf,
+            commands::export::export_conversations,
+            commands::export::export_to_directory,
+            commands::export::get_export_mirror_settings,
+            commands::export::update_export_mirror_settings,
+            commands::export::run_export_mirror,
+            // backups
+            commands::backup::create_backup,
+            commands::backup::verify_backup,
+            commands::backup::restore_backup,
+            commands::backup::list_backups,
+            commands::backup::get_backup_settings,
+            commands::backup::update_backup_settings,
+            // database encryption
+            commands::encryption::get_encryption_status,
+            commands::encryption::unlock_database,
+            commands::encryption::lock_database,
+            commands::encryption::set_database_encryption,
+            commands::encryption::update_encryption_settings,
             commands::export::save_export_file,
             commands::export::save_export_file_bytes,
             commands::export::import_conversations_json,
+            commands::export::preview_import,
+            commands::export::get_export_schema,
             commands::export::load_import_file,
             commands::export::export_single_conversation_json,
             commands::export::save_single_conversation_export,
@@ -307,6 +377,8 @@ pub fn run() {
             commands::project::update_ignore_patterns,
             commands::project::search_project_files,
             commands::project::search_project_files_in_path,
+            commands::project::index_project,
+            commands::project::search_project_index,
             commands::project::detect_project_type,
             // performance monitoring
             commands::performance::get_performance_metrics,
@@ -320,11 +392,18 @@ pub fn run() {
             commands::profiles::set_active_profile,
             commands::profiles::update_profile,
             commands::profiles::delete_profile,
+            commands::profiles::set_profile_fallback_chain,
             // shortcuts
             commands::shortcuts::get_shortcut_config,
             commands::shortcuts::update_shortcut_config,
+            commands::shortcuts::get_shortcut_status,
+            commands::shortcuts::get_shortcut_backend,
             commands::shortcuts::validate_shortcut,
+            commands::shortcuts::normalize_shortcut,
             commands::shortcuts::get_available_actions,
+            // notifications
+            commands::notifications::get_notification_settings,
+            commands::notifications::update_notification_settings,
             // tags
             commands::tags::create_tag,
             commands::tags::get_tag,
@@ -352,6 +431,10 @@ pub fn run() {
             commands::updater::check_for_updates,
             commands::updater::download_and_install_update,
             commands::updater::get_current_version,
+            commands::updater::rollback_update,
+            commands::updater::get_update_settings,
+            commands::updater::update_update_settings,
+            commands::updater::skip_update_version,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,774 @@
+//! Native notifications for work that finishes while the window is hidden: a
+//! long generation, a `run_code` execution, or a `notify` from the CLI.
+//!
+//! Notifications go through `org.freedesktop.Notifications` so they can carry
+//! action buttons ("Open", "Copy answer", "Rerun"); the server answers with an
+//! `ActionInvoked` signal carrying the notification id and the action key. When
+//! there is no notification server on the session bus they fall back to
+//! `tauri-plugin-notification`, without buttons.
+//!
+//! Every notification respects the do-not-disturb setting and a per-minute cap.
+
+use crate::commands::run::{RunRequest, RunResult};
+use crate::database::messages::Message;
+use crate::database::settings::Setting;
+use crate::database::Database;
+use crate::error::{AppError, AppResult};
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use zbus::message::Type as MessageType;
+use zbus::zvariant::Value;
+use zbus::{Connection, MatchRule, MessageStream};
+
+pub const NOTIFICATIONS_BUS_NAME: &str = "org.freedesktop.Notifications";
+pub const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const NOTIFICATIONS_INTERFACE: &str = "org.freedesktop.Notifications";
+const SETTINGS_KEY: &str = "notification_settings";
+const APP_NAME: &str = "Linux AI Assistant";
+const PREVIEW_CHARS: usize = 120;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    pub do_not_disturb: bool,
+    /// Generations and runs shorter than this finish silently
+    pub min_duration_secs: u64,
+    /// Notifications beyond this many in the last minute are dropped
+    pub max_per_minute: u32,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            do_not_disturb: false,
+            min_duration_secs: 10,
+            max_per_minute: 4,
+        }
+    }
+}
+
+impl NotificationSettings {
+    pub fn load(conn: &rusqlite::Connection) -> AppResult<Self> {
+        Ok(Setting::get_json(conn, SETTINGS_KEY)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, conn: &rusqlite::Connection) -> AppResult<()> {
+        Ok(Setting::set_json(conn, SETTINGS_KEY, self)?)
+    }
+
+    /// Whether work that took `elapsed` should raise a notification. `None`
+    /// means the user asked for it (e.g. a rerun), so the threshold is skipped.
+    pub fn should_notify(&self, elapsed: Option<Duration>, window_hidden: bool) -> bool {
+        self.enabled
+            && !self.do_not_disturb
+            && window_hidden
+            && elapsed.map_or(true, |e| e >= Duration::from_secs(self.min_duration_secs))
+    }
+}
+
+/// Sliding one-minute window of sent notifications
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    /// Record a notification at `now` unless `max_per_minute` were already sent
+    pub fn allow(&mut self, now: Instant, max_per_minute: u32) -> bool {
+        while self
+            .sent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(60))
+        {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= max_per_minute as usize {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationAction {
+    Open,
+    Copy,
+    Rerun,
+}
+
+impl NotificationAction {
+    /// Key sent to the notification server and returned in `ActionInvoked`.
+    /// `default` is what servers report for a click on the notification body.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Open => "default",
+            Self::Copy => "copy",
+            Self::Rerun => "rerun",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "default" | "open" => Some(Self::Open),
+            "copy" => Some(Self::Copy),
+            "rerun" => Some(Self::Rerun),
+            _ => None,
+        }
+    }
+}
+
+/// Finished work a notification is about, kept until the notification closes
+/// so its actions can be carried out
+#[derive(Debug, Clone)]
+pub enum Completion {
+    Reply {
+        conversation_id: String,
+        title: String,
+        message: Message,
+    },
+    Run {
+        request: RunRequest,
+        result: RunResult,
+    },
+    Message {
+        text: String,
+    },
+}
+
+fn preview(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= PREVIEW_CHARS {
+        return text;
+    }
+    let cut: String = text.chars().take(PREVIEW_CHARS - 1).collect();
+    format!("{}…", cut.trim_end())
+}
+
+impl Completion {
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Reply { title, .. } => format!("Response ready: {}", title),
+            Self::Run { request, result } => match (result.timed_out, result.exit_code) {
+                (true, _) => format!("{} run timed out", request.language),
+                (false, Some(0)) => format!("{} run finished", request.language),
+                (false, Some(code)) => format!("{} run failed (exit {})", request.language, code),
+                (false, None) => format!("{} run was killed", request.language),
+            },
+            Self::Message { .. } => APP_NAME.to_string(),
+        }
+    }
+
+    pub fn body(&self) -> String {
+        match self {
+            Self::Reply { message, .. } => preview(&message.content),
+            Self::Run { .. } => preview(&self.copy_text().unwrap_or_default()),
+            Self::Message { text } => preview(text),
+        }
+    }
+
+    /// Buttons with their labels, in display order
+    pub fn actions(&self) -> Vec<(NotificationAction, &'static str)> {
+        match self {
+            Self::Reply { .. } => vec![
+                (NotificationAction::Open, "Open"),
+                (NotificationAction::Copy, "Copy answer"),
+                (NotificationAction::Rerun, "Rerun"),
+            ],
+            Self::Run { .. } => vec![
+                (NotificationAction::Open, "Open"),
+                (NotificationAction::Copy, "Copy output"),
+                (NotificationAction::Rerun, "Rerun"),
+            ],
+            Self::Message { .. } => vec![(NotificationAction::Open, "Open")],
+        }
+    }
+
+    /// What "Copy" puts on the clipboard
+    pub fn copy_text(&self) -> Option<String> {
+        match self {
+            Self::Reply { message, .. } => Some(message.content.clone()),
+            Self::Run { result, .. } => Some(
+                [result.stdout.trim_end(), result.stderr.trim_end()]
+                    .into_iter()
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            Self::Message { .. } => None,
+        }
+    }
+}
+
+fn notifications_error(err: impl std::fmt::Display) -> AppError {
+    AppError::unsupported(format!("Notifications: {}", err))
+}
+
+/// A signal from the notification server
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerSignal {
+    ActionInvoked { id: u32, action: String },
+    Closed { id: u32 },
+}
+
+/// Client for `org.freedesktop.Notifications`
+#[derive(Debug, Clone)]
+pub struct NotificationClient {
+    conn: Connection,
+}
+
+impl NotificationClient {
+    pub fn new(conn: Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Show a notification and return the id the server assigned
+    pub async fn send(
+        &self,
+        summary: &str,
+        body: &str,
+        actions: &[(NotificationAction, &str)],
+    ) -> AppResult<u32> {
+        let actions: Vec<&str> = actions
+            .iter()
+            .flat_map(|(action, label)| [action.key(), *label])
+            .collect();
+        let mut hints: HashMap<&str, Value> = HashMap::new();
+        hints.insert("desktop-entry", Value::from("linux-ai-assistant"));
+        hints.insert("urgency", Value::U8(1));
+
+        let reply = self
+            .conn
+            .call_method(
+                Some(NOTIFICATIONS_BUS_NAME),
+                NOTIFICATIONS_PATH,
+                Some(NOTIFICATIONS_INTERFACE),
+                "Notify",
+                &(APP_NAME, 0u32, "", summary, body, actions, hints, -1i32),
+            )
+            .await
+            .map_err(notifications_error)?;
+        reply.body().deserialize().map_err(notifications_error)
+    }
+
+    /// `ActionInvoked` and `NotificationClosed` signals from the server
+    pub async fn signals(&self) -> AppResult<impl Stream<Item = ServerSignal> + Send + 'static> {
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .interface(NOTIFICATIONS_INTERFACE)
+            .map_err(notifications_error)?
+            .build();
+        let stream = MessageStream::for_match_rule(rule, &self.conn, None)
+            .await
+            .map_err(notifications_error)?;
+        Ok(stream.filter_map(|message| {
+            let signal = message.ok().and_then(|message| {
+                let header = message.header();
+                match header.member()?.as_str() {
+                    "ActionInvoked" => {
+                        let (id, action): (u32, String) = message.body().deserialize().ok()?;
+                        Some(ServerSignal::ActionInvoked { id, action })
+                    }
+                    "NotificationClosed" => {
+                        let (id, _reason): (u32, u32) = message.body().deserialize().ok()?;
+                        Some(ServerSignal::Closed { id })
+                    }
+                    _ => None,
+                }
+            });
+            futures_util::future::ready(signal)
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Generation {
+    started: Instant,
+    /// Requested from a notification, so it notifies however long it takes
+    requested: bool,
+}
+
+lazy_static::lazy_static! {
+    static ref GENERATIONS: Mutex<HashMap<String, Generation>> = Mutex::new(HashMap::new());
+    static ref LIMITER: Mutex<RateLimiter> = Mutex::new(RateLimiter::default());
+    static ref PENDING: Mutex<HashMap<u32, Completion>> = Mutex::new(HashMap::new());
+}
+
+static CLIENT: OnceLock<NotificationClient> = OnceLock::new();
+
+/// A generation for `conversation_id` began; its reply is timed from here
+pub fn generation_started(conversation_id: &str) {
+    let now = Instant::now();
+    if let Ok(mut generations) = GENERATIONS.lock() {
+        generations
+            .entry(conversation_id.to_string())
+            .and_modify(|g| g.started = now)
+            .or_insert(Generation {
+                started: now,
+                requested: false,
+            });
+    }
+}
+
+/// A generation failed, so no reply will be stored for it
+pub fn generation_failed(conversation_id: &str) {
+    if let Ok(mut generations) = GENERATIONS.lock() {
+        generations.remove(conversation_id);
+    }
+}
+
+fn window_hidden(app: &AppHandle) -> bool {
+    app.get_webview_window("main")
+        .map(|w| !w.is_visible().unwrap_or(false) || w.is_minimized().unwrap_or(false))
+        .unwrap_or(true)
+}
+
+/// Raise a notification for `completion` if the settings, window state and
+/// rate limit allow it
+pub fn notify(app: &AppHandle, completion: Completion, elapsed: Option<Duration>) {
+    let settings = app
+        .try_state::<Database>()
+        .and_then(|db| {
+            let conn = db.conn().lock().ok()?;
+            NotificationSettings::load(&conn).ok()
+        })
+        .unwrap_or_default();
+    if !settings.should_notify(elapsed, window_hidden(app)) {
+        return;
+    }
+    let allowed = LIMITER
+        .lock()
+        .map(|mut limiter| limiter.allow(Instant::now(), settings.max_per_minute))
+        .unwrap_or(false);
+    if !allowed {
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let summary = completion.summary();
+        let body = completion.body();
+        if let Some(client) = CLIENT.get() {
+            match client.send(&summary, &body, &completion.actions()).await {
+                Ok(id) => {
+                    if let Ok(mut pending) = PENDING.lock() {
+                        pending.insert(id, completion);
+                    }
+                    return;
+                }
+                Err(e) => eprintln!("{}; falling back to the notification plugin", e),
+            }
+        }
+        use tauri_plugin_notification::NotificationExt;
+        if let Err(e) = app
+            .notification()
+            .builder()
+            .title(summary)
+            .body(body)
+            .show()
+        {
+            eprintln!("Failed to show notification: {}", e);
+        }
+    });
+}
+
+/// The notification for a stored assistant reply, if its generation was
+/// being timed. Locks the database, so the caller must not hold it.
+fn completed_reply(db: &Database, message: &Message) -> Option<(Completion, Option<Duration>)> {
+    if message.role != "assistant" {
+        return None;
+    }
+    let generation = GENERATIONS
+        .lock()
+        .ok()
+        .and_then(|mut generations| generations.remove(&message.conversation_id))?;
+    let title = db
+        .conn()
+        .lock()
+        .ok()
+        .and_then(|conn| {
+            crate::database::conversations::Conversation::get_by_id(&conn, &message.conversation_id)
+                .ok()
+                .flatten()
+        })
+        .map(|c| c.title)
+        .unwrap_or_else(|| "Assistant".to_string());
+    let elapsed = (!generation.requested).then(|| generation.started.elapsed());
+    let completion = Completion::Reply {
+        conversation_id: message.conversation_id.clone(),
+        title,
+        message: message.clone(),
+    };
+    Some((completion, elapsed))
+}
+
+/// An assistant reply was stored; notify if its generation ran long
+pub fn reply_stored(app: &AppHandle, message: &Message) {
+    let Some(db) = app.try_state::<Database>() else {
+        return;
+    };
+    if let Some((completion, elapsed)) = completed_reply(&db, message) {
+        notify(app, completion, elapsed);
+    }
+}
+
+/// A `run_code` execution finished after `elapsed`
+pub fn run_finished(
+    app: &AppHandle,
+    request: RunRequest,
+    result: RunResult,
+    elapsed: Option<Duration>,
+) {
+    notify(app, Completion::Run { request, result }, elapsed);
+}
+
+fn show_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Carry out `action` for a notification's completion
+pub fn handle_action(app: &AppHandle, completion: Completion, action: NotificationAction) {
+    match action {
+        NotificationAction::Open => {
+            show_window(app);
+            if let Completion::Reply {
+                conversation_id, ..
+            } = &completion
+            {
+                let _ = app.emit(
+                    "notification://open",
+                    serde_json::json!({ "conversation_id": conversation_id }),
+                );
+            }
+        }
+        NotificationAction::Copy => {
+            if let Some(text) = completion.copy_text() {
+                if let Err(e) = crate::commands::clipboard::write_clipboard(app, &text) {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+        NotificationAction::Rerun => rerun(app, completion),
+    }
+}
+
+fn rerun(app: &AppHandle, completion: Completion) {
+    let app = app.clone();
+    match completion {
+        Completion::Reply {
+            conversation_id,
+            message,
+            ..
+        } => {
+            let Some(parent_id) = message.parent_message_id else {
+                return;
+            };
+            if let Ok(mut generations) = GENERATIONS.lock() {
+                generations.insert(
+                    conversation_id.clone(),
+                    Generation {
+                        started: Instant::now(),
+                        requested: true,
+                    },
+                );
+            }
+            tauri::async_runtime::spawn(async move {
+                let db = app.state::<Database>();
+                match crate::commands::messages::generate_reply(&app, &db, &parent_id, None, None)
+                    .await
+                {
+                    Ok(reply) => {
+                        let _ = app.emit("notification://rerun", &reply);
+                    }
+                    Err(e) => {
+                        generation_failed(&conversation_id);
+                        eprintln!("Rerun from notification failed: {}", e);
+                    }
+                }
+            });
+        }
+        Completion::Run { request, .. } => {
+            tauri::async_runtime::spawn(async move {
+                let run = request.clone();
+                match tauri::async_runtime::spawn_blocking(move || run.execute()).await {
+                    Ok(Ok(result)) => run_finished(&app, request, result, None),
+                    Ok(Err(e)) => eprintln!("Rerun from notification failed: {}", e),
+                    Err(e) => eprintln!("Rerun from notification failed: {}", e),
+                }
+            });
+        }
+        Completion::Message { .. } => {}
+    }
+}
+
+fn handle_signal(app: &AppHandle, signal: ServerSignal) {
+    let Ok(mut pending) = PENDING.lock() else {
+        return;
+    };
+    match signal {
+        ServerSignal::ActionInvoked { id, action } => {
+            let Some(completion) = pending.remove(&id) else {
+                return;
+            };
+            drop(pending);
+            if let Some(action) = NotificationAction::from_key(&action) {
+                handle_action(app, completion, action);
+            }
+        }
+        ServerSignal::Closed { id } => {
+            pending.remove(&id);
+        }
+    }
+}
+
+/// Connect to the notification server and handle its action signals. Without
+/// a session bus, notifications use the plugin and have no buttons.
+pub fn start_notifications(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let conn = match Connection::session().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                eprintln!("Notifications: no session bus: {}", e);
+                return;
+            }
+        };
+        let client = NotificationClient::new(conn);
+        let mut signals = match client.signals().await {
+            Ok(signals) => Box::pin(signals),
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        let _ = CLIENT.set(client);
+        while let Some(signal) = signals.next().await {
+            handle_signal(&app, signal);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shortcut_portal::tests::TestBus;
+    use std::sync::Arc;
+    use zbus::object_server::SignalEmitter;
+    use zbus::zvariant::OwnedValue;
+
+    #[test]
+    fn settings_gate_notifications() {
+        let settings = NotificationSettings::default();
+        let long = Some(Duration::from_secs(30));
+        let short = Some(Duration::from_secs(2));
+        assert!(settings.should_notify(long, true));
+        assert!(!settings.should_notify(long, false));
+        assert!(!settings.should_notify(short, true));
+        assert!(settings.should_notify(None, true));
+
+        let dnd = NotificationSettings {
+            do_not_disturb: true,
+            ..NotificationSettings::default()
+        };
+        assert!(!dnd.should_notify(long, true));
+        assert!(!dnd.should_notify(None, true));
+    }
+
+    #[test]
+    fn rate_limiter_uses_a_sliding_minute() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        assert!(limiter.allow(start, 2));
+        assert!(limiter.allow(start + Duration::from_secs(10), 2));
+        assert!(!limiter.allow(start + Duration::from_secs(20), 2));
+        assert!(limiter.allow(start + Duration::from_secs(60), 2));
+        assert!(!limiter.allow(start + Duration::from_secs(61), 2));
+        assert!(limiter.allow(start + Duration::from_secs(71), 2));
+        assert!(!limiter.allow(start, 0));
+    }
+
+    #[test]
+    fn storing_a_timed_reply_releases_the_connection_first() {
+        use crate::database::conversations::{Conversation, NewConversation};
+        use crate::database::messages::NewMessage;
+
+        let db = Database::new(std::path::PathBuf::from(":memory:")).unwrap();
+        let conversation = Conversation::create(
+            &db.conn().lock().unwrap(),
+            NewConversation {
+                title: "Long answer".to_string(),
+                model: "llama3".to_string(),
+                provider: "ollama".to_string(),
+                system_prompt: None,
+            },
+        )
+        .unwrap();
+        generation_started(&conversation.id);
+
+        // Deadlocked while `create_message` held the connection over the hooks
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let stored = crate::commands::messages::store_message(
+                &db,
+                NewMessage {
+                    conversation_id: conversation.id,
+                    role: "assistant".to_string(),
+                    content: "Done".to_string(),
+                    tokens_used: None,
+                },
+            )
+            .unwrap();
+            let _ = tx.send(completed_reply(&db, &stored));
+        });
+        let (completion, elapsed) = rx
+            .recv_timeout(Duration::from_secs(10))
+            .expect("storing the reply deadlocked")
+            .expect("the generation was timed");
+        assert!(elapsed.is_some());
+        match completion {
+            Completion::Reply { title, message, .. } => {
+                assert_eq!(title, "Long answer");
+                assert_eq!(message.content, "Done");
+            }
+            _ => panic!("expected a reply"),
+        }
+    }
+
+    #[test]
+    fn run_completions_describe_the_outcome() {
+        let completion = Completion::Run {
+            request: RunRequest {
+                language: "python".to_string(),
+                code: "print(1)".to_string(),
+                timeout_ms: None,
+                cwd: None,
+            },
+            result: RunResult {
+                stdout: "1\n".to_string(),
+                stderr: "warning\n".to_string(),
+                exit_code: Some(2),
+                timed_out: false,
+            },
+        };
+        assert_eq!(completion.summary(), "python run failed (exit 2)");
+        assert_eq!(completion.copy_text().as_deref(), Some("1\nwarning"));
+        assert_eq!(completion.body(), "1 warning");
+        assert_eq!(preview(&"x".repeat(500)).chars().count(), PREVIEW_CHARS);
+    }
+
+    /// Summary and actions of a `Notify` call
+    type Call = (String, Vec<String>);
+
+    #[derive(Default)]
+    struct MockServer {
+        calls: Arc<Mutex<Vec<Call>>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl MockServer {
+        #[allow(clippy::too_many_arguments)]
+        async fn notify(
+            &self,
+            _app_name: String,
+            _replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            _body: String,
+            actions: Vec<String>,
+            _hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let mut calls = self.calls.lock().unwrap();
+            calls.push((summary, actions));
+            calls.len() as u32
+        }
+
+        #[zbus(signal)]
+        async fn action_invoked(
+            emitter: &SignalEmitter<'_>,
+            id: u32,
+            action_key: &str,
+        ) -> zbus::Result<()>;
+
+        #[zbus(signal)]
+        async fn notification_closed(
+            emitter: &SignalEmitter<'_>,
+            id: u32,
+            reason: u32,
+        ) -> zbus::Result<()>;
+    }
+
+    #[tokio::test]
+    async fn client_sends_actions_and_receives_signals() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not available; skipping");
+            return;
+        };
+        let server = MockServer::default();
+        let calls = server.calls.clone();
+        let server_conn = zbus::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name(NOTIFICATIONS_BUS_NAME)
+            .unwrap()
+            .serve_at(NOTIFICATIONS_PATH, server)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        let client = NotificationClient::new(bus.connect().await);
+        let mut signals = Box::pin(client.signals().await.unwrap());
+        let completion = Completion::Message {
+            text: "done".to_string(),
+        };
+        let id = client
+            .send(&completion.summary(), "done", &completion.actions())
+            .await
+            .unwrap();
+        assert_eq!(id, 1);
+        assert_eq!(
+            calls.lock().unwrap()[0],
+            (
+                APP_NAME.to_string(),
+                vec!["default".to_string(), "Open".to_string()]
+            )
+        );
+
+        let emitter = SignalEmitter::new(&server_conn, NOTIFICATIONS_PATH).unwrap();
+        MockServer::action_invoked(&emitter, id, "rerun")
+            .await
+            .unwrap();
+        MockServer::notification_closed(&emitter, id, 2)
+            .await
+            .unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(5), async {
+            vec![signals.next().await.unwrap(), signals.next().await.unwrap()]
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            received,
+            vec![
+                ServerSignal::ActionInvoked {
+                    id,
+                    action: "rerun".to_string()
+                },
+                ServerSignal::Closed { id },
+            ]
+        );
+        assert_eq!(
+            NotificationAction::from_key("rerun"),
+            Some(NotificationAction::Rerun)
+        );
+    }
+}
@@ -0,0 +1,1357 @@
+//! PDF layout for conversation exports.
+//!
+//! Message bodies are parsed with comrak, the Markdown parser behind the HTML
+//! export, and laid out onto A4 pages: headings, paragraphs with bold, italic
+//! and inline code, lists, block quotes, tables and wrapped monospace code
+//! blocks. Text is measured with rusttype against the DejaVu fonts bundled in
+//! `fonts/`, which cover Latin, Greek, Cyrillic and many other alphabets.
+//! Characters the fonts lack print as U+FFFD instead of vanishing; scripts that
+//! need shaping (Arabic, Indic) or CJK fonts are not handled.
+//!
+//! Layout happens before anything is written: the body is laid out into pages
+//! of drawing operations first, so running headers, "page N of M" footers and
+//! the table of contents of a multi-conversation export know the final page
+//! numbers.
+
+use crate::error::{AppError, AppResult};
+use comrak::nodes::{AstNode, ListDelimType, ListType, NodeValue};
+use comrak::{parse_document, Arena, ComrakOptions};
+use printpdf::path::PaintMode;
+use printpdf::{
+    Color, IndirectFontRef, Line, Mm, PdfDocument, PdfLayerReference, Point, Rect, Rgb,
+};
+use rusttype::{Font, Scale};
+use std::collections::HashMap;
+use std::io::{BufWriter, Cursor};
+
+const FACES: [&[u8]; 4] = [
+    include_bytes!("../fonts/DejaVuSans.ttf"),
+    include_bytes!("../fonts/DejaVuSans-Bold.ttf"),
+    include_bytes!("../fonts/DejaVuSans-Oblique.ttf"),
+    include_bytes!("../fonts/DejaVuSansMono.ttf"),
+];
+
+// Page geometry in millimetres (A4)
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+/// Top of the text area; the running header sits above it
+const CONTENT_TOP: f32 = PAGE_HEIGHT - 25.0;
+/// Bottom of the text area; the footer sits below it
+const CONTENT_BOTTOM: f32 = 22.0;
+
+/// Millimetres per point
+const PT: f32 = 25.4 / 72.0;
+/// Line height as a multiple of the font size
+const LINE_SPACING: f32 = 1.35;
+
+// Font sizes in points
+const TITLE_SIZE: f32 = 18.0;
+const ROLE_SIZE: f32 = 12.5;
+const HEADING_SIZES: [f32; 6] = [15.0, 13.5, 12.0, 11.0, 10.5, 10.5];
+const BODY_SIZE: f32 = 10.5;
+const TABLE_SIZE: f32 = 9.5;
+const CODE_SIZE: f32 = 8.5;
+const SMALL_SIZE: f32 = 8.0;
+
+const LIST_INDENT: f32 = 6.0;
+const QUOTE_INDENT: f32 = 5.0;
+const CELL_PADDING: f32 = 1.5;
+const CODE_PADDING: f32 = 2.0;
+
+type Color3 = (f32, f32, f32);
+
+const TEXT: Color3 = (0.12, 0.12, 0.12);
+const MUTED: Color3 = (0.45, 0.45, 0.45);
+const RULE: Color3 = (0.8, 0.8, 0.8);
+const LINK: Color3 = (0.1, 0.35, 0.7);
+const INLINE_CODE: Color3 = (0.6, 0.15, 0.2);
+const CODE_BACKGROUND: Color3 = (0.95, 0.95, 0.95);
+const HEADER_BACKGROUND: Color3 = (0.92, 0.93, 0.95);
+
+/// One conversation in a PDF export
+pub struct PdfSection {
+    pub title: String,
+    /// Lines shown under the title, e.g. provider and creation date
+    pub metadata: Vec<String>,
+    pub messages: Vec<PdfMessage>,
+}
+
+pub struct PdfMessage {
+    /// "user", "assistant" or "system"; picks the heading colour
+    pub role: String,
+    pub heading: String,
+    /// Message body as Markdown
+    pub content: String,
+    /// Small print under the body, e.g. attachments and token counts
+    pub notes: Vec<String>,
+}
+
+/// Render `sections` as a PDF titled `title`. With more than one section each
+/// starts on a new page and a table of contents comes first.
+pub fn render(title: &str, sections: &[PdfSection]) -> AppResult<Vec<u8>> {
+    let fonts = Fonts::load()?;
+    let exported = format!(
+        "Exported on {}",
+        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    let pages = paginate(&fonts, title, sections, &exported);
+    write(title, pages)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Style {
+    Regular = 0,
+    Bold = 1,
+    Italic = 2,
+    Mono = 3,
+}
+
+struct Fonts {
+    faces: Vec<Font<'static>>,
+}
+
+impl Fonts {
+    fn load() -> AppResult<Self> {
+        let faces = FACES
+            .iter()
+            .map(|bytes| {
+                Font::try_from_bytes(bytes)
+                    .ok_or_else(|| AppError::internal("Bundled PDF font could not be parsed"))
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+        Ok(Self { faces })
+    }
+
+    fn face(&self, style: Style) -> &Font<'static> {
+        &self.faces[style as usize]
+    }
+
+    /// Width of `text` in millimetres. PDF text is drawn without kerning, so
+    /// this sums plain advance widths.
+    fn width(&self, text: &str, style: Style, size: f32) -> f32 {
+        let font = self.face(style);
+        let metrics = font.v_metrics_unscaled();
+        // Scale so that advances come out in font units
+        let scale = Scale::uniform(metrics.ascent - metrics.descent);
+        let units: f32 = text
+            .chars()
+            .map(|c| font.glyph(c).scaled(scale).h_metrics().advance_width)
+            .sum();
+        units / font.units_per_em() as f32 * size * PT
+    }
+
+    /// Replace characters the face has no glyph for, so they show up as
+    /// U+FFFD instead of disappearing. Newlines are kept, other whitespace
+    /// becomes a space and control characters are dropped.
+    fn clean(&self, text: &str, style: Style) -> String {
+        let font = self.face(style);
+        text.chars()
+            .filter_map(|c| match c {
+                '\n' => Some('\n'),
+                c if c.is_whitespace() => Some(' '),
+                c if c.is_control() => None,
+                c if font.glyph(c).id().0 == 0 => Some('\u{FFFD}'),
+                c => Some(c),
+            })
+            .collect()
+    }
+
+    /// `text` shortened with an ellipsis to fit `width`
+    fn truncate(&self, text: &str, style: Style, size: f32, width: f32) -> String {
+        let mut text = self.clean(text, style).replace('\n', " ");
+        if self.width(&text, style, size) <= width {
+            return text;
+        }
+        while !text.is_empty() && self.width(&format!("{}…", text), style, size) > width {
+            text.pop();
+        }
+        format!("{}…", text.trim_end())
+    }
+}
+
+/// A drawing operation; coordinates are millimetres from the bottom left
+#[derive(Debug)]
+enum Op {
+    Text {
+        x: f32,
+        y: f32,
+        size: f32,
+        style: Style,
+        color: Color3,
+        text: String,
+    },
+    Fill {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: Color3,
+    },
+    Rule {
+        from: (f32, f32),
+        to: (f32, f32),
+        thickness: f32,
+        color: Color3,
+    },
+}
+
+/// A run of text in one style
+#[derive(Clone, Debug, PartialEq)]
+struct Span {
+    text: String,
+    style: Style,
+    color: Color3,
+}
+
+impl Span {
+    fn new(text: impl Into<String>, style: Style, color: Color3) -> Self {
+        Self {
+            text: text.into(),
+            style,
+            color,
+        }
+    }
+}
+
+/// Append to a line, merging with the previous span when the style matches
+fn push_span(line: &mut Vec<Span>, span: Span) {
+    match line.last_mut() {
+        Some(last) if last.style == span.style && last.color == span.color => {
+            last.text.push_str(&span.text)
+        }
+        _ => line.push(span),
+    }
+}
+
+enum Token {
+    /// Text between spaces, possibly in several styles
+    Word(Vec<Span>),
+    /// A space, in the style of the text it came from
+    Space(Span),
+    Break,
+}
+
+fn tokenize(fonts: &Fonts, spans: &[Span]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut word = Vec::new();
+    for span in spans {
+        let mut piece = String::new();
+        for c in fonts.clean(&span.text, span.style).chars() {
+            if c != ' ' && c != '\n' {
+                piece.push(c);
+                continue;
+            }
+            if !piece.is_empty() {
+                word.push(Span::new(
+                    std::mem::take(&mut piece),
+                    span.style,
+                    span.color,
+                ));
+            }
+            if !word.is_empty() {
+                tokens.push(Token::Word(std::mem::take(&mut word)));
+            }
+            tokens.push(if c == '\n' {
+                Token::Break
+            } else {
+                Token::Space(Span::new(" ", span.style, span.color))
+            });
+        }
+        if !piece.is_empty() {
+            word.push(Span::new(piece, span.style, span.color));
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(Token::Word(word));
+    }
+    tokens
+}
+
+/// Break `spans` into lines no wider than `width`, at spaces where possible
+/// and between characters for words longer than a line
+fn wrap(fonts: &Fonts, spans: &[Span], size: f32, width: f32) -> Vec<Vec<Span>> {
+    let mut lines = Vec::new();
+    let mut line: Vec<Span> = Vec::new();
+    let mut used = 0.0;
+    let mut space: Option<Span> = None;
+
+    for token in tokenize(fonts, spans) {
+        let pieces = match token {
+            Token::Break => {
+                lines.push(std::mem::take(&mut line));
+                used = 0.0;
+                space = None;
+                continue;
+            }
+            Token::Space(separator) => {
+                if !line.is_empty() {
+                    space = Some(separator);
+                }
+                continue;
+            }
+            Token::Word(pieces) => pieces,
+        };
+
+        let word_width: f32 = pieces
+            .iter()
+            .map(|p| fonts.width(&p.text, p.style, size))
+            .sum();
+        if let Some(separator) = space.take() {
+            let gap = fonts.width(" ", separator.style, size);
+            if used + gap + word_width <= width {
+                push_span(&mut line, separator);
+                used += gap;
+            } else {
+                lines.push(std::mem::take(&mut line));
+                used = 0.0;
+            }
+        }
+
+        if used + word_width <= width {
+            for piece in pieces {
+                push_span(&mut line, piece);
+            }
+            used += word_width;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+            used = 0.0;
+        }
+        for piece in pieces {
+            for c in piece.text.chars() {
+                let char_width = fonts.width(c.encode_utf8(&mut [0; 4]), piece.style, size);
+                if used + char_width > width && !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                    used = 0.0;
+                }
+                push_span(&mut line, Span::new(c, piece.style, piece.color));
+                used += char_width;
+            }
+        }
+    }
+
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn line_height(size: f32) -> f32 {
+    size * PT * LINE_SPACING
+}
+
+/// Distance from the top of a line box to the text baseline
+fn baseline(size: f32) -> f32 {
+    size * PT * 1.02
+}
+
+/// Where block content goes: its left edge plus what is drawn beside each line
+#[derive(Clone)]
+struct Frame {
+    /// Offset from the left margin
+    indent: f32,
+    /// x positions of block quote bars
+    quote_bars: Vec<f32>,
+    color: Color3,
+    list_depth: usize,
+    tight: bool,
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self {
+            indent: 0.0,
+            quote_bars: Vec::new(),
+            color: TEXT,
+            list_depth: 0,
+            tight: false,
+        }
+    }
+}
+
+impl Frame {
+    fn left(&self) -> f32 {
+        MARGIN + self.indent
+    }
+
+    fn width(&self) -> f32 {
+        CONTENT_WIDTH - self.indent
+    }
+}
+
+struct Layout<'a> {
+    fonts: &'a Fonts,
+    pages: Vec<Vec<Op>>,
+    /// Top of the free space on the current page
+    y: f32,
+    /// List marker waiting for the first line of its item, with the x its
+    /// right edge aligns to
+    marker: Option<(f32, String)>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(fonts: &'a Fonts) -> Self {
+        Self {
+            fonts,
+            pages: vec![Vec::new()],
+            y: CONTENT_TOP,
+            marker: None,
+        }
+    }
+
+    fn page(&self) -> usize {
+        self.pages.len() - 1
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Vec::new());
+        self.y = CONTENT_TOP;
+    }
+
+    /// Start a new page unless `height` still fits on this one
+    fn reserve(&mut self, height: f32) {
+        if self.y - height < CONTENT_BOTTOM && self.y < CONTENT_TOP {
+            self.new_page();
+        }
+    }
+
+    /// Vertical space, dropped at the top of a page
+    fn skip(&mut self, height: f32) {
+        if self.y < CONTENT_TOP {
+            self.y = (self.y - height).max(CONTENT_BOTTOM);
+        }
+    }
+
+    fn push(&mut self, op: Op) {
+        if let Some(page) = self.pages.last_mut() {
+            page.push(op);
+        }
+    }
+
+    fn text(&mut self, x: f32, y: f32, size: f32, span: Span) {
+        if span.text.is_empty() {
+            return;
+        }
+        self.push(Op::Text {
+            x,
+            y,
+            size,
+            style: span.style,
+            color: span.color,
+            text: span.text,
+        });
+    }
+
+    fn rule(&mut self, from: (f32, f32), to: (f32, f32), thickness: f32, color: Color3) {
+        self.push(Op::Rule {
+            from,
+            to,
+            thickness,
+            color,
+        });
+    }
+
+    /// Draw quote bars beside a line box of `height` at the current position,
+    /// and the pending list marker if there is one
+    fn decorate(&mut self, frame: &Frame, height: f32) {
+        for &x in &frame.quote_bars {
+            self.rule((x, self.y), (x, self.y - height), 1.2, RULE);
+        }
+        if let Some((right, marker)) = self.marker.take() {
+            let width = self.fonts.width(&marker, Style::Regular, BODY_SIZE);
+            let y = self.y - baseline(BODY_SIZE);
+            self.text(
+                right - width,
+                y,
+                BODY_SIZE,
+                Span::new(marker, Style::Regular, frame.color),
+            );
+        }
+    }
+
+    /// Draw already wrapped lines at the frame's left edge
+    fn lines(&mut self, frame: &Frame, lines: Vec<Vec<Span>>, size: f32) {
+        let height = line_height(size);
+        for line in lines {
+            self.reserve(height);
+            self.decorate(frame, height);
+            let y = self.y - baseline(size);
+            let mut x = frame.left();
+            for span in line {
+                let width = self.fonts.width(&span.text, span.style, size);
+                self.text(x, y, size, span);
+                x += width;
+            }
+            self.y -= height;
+        }
+    }
+
+    fn paragraph(&mut self, frame: &Frame, spans: &[Span], size: f32) {
+        let lines = wrap(self.fonts, spans, size, frame.width());
+        self.lines(frame, lines, size);
+    }
+
+    /// Monospace block on a shaded background; long lines wrap
+    fn code_block(&mut self, frame: &Frame, info: &str, literal: &str) {
+        let height = line_height(CODE_SIZE);
+        let text_width = frame.width() - 2.0 * CODE_PADDING;
+
+        let mut lines: Vec<Span> = Vec::new();
+        if let Some(language) = info.split_whitespace().next() {
+            lines.push(Span::new(language, Style::Regular, MUTED));
+        }
+        for raw in literal.trim_end_matches('\n').split('\n') {
+            let raw = raw.trim_end_matches('\r').replace('\t', "    ");
+            let span = Span::new(raw, Style::Mono, frame.color);
+            // Keep indentation: wrap characters rather than words
+            let mut line = String::new();
+            let mut used = 0.0;
+            for c in self.fonts.clean(&span.text, Style::Mono).chars() {
+                let width = self
+                    .fonts
+                    .width(c.encode_utf8(&mut [0; 4]), Style::Mono, CODE_SIZE);
+                if used + width > text_width && !line.is_empty() {
+                    lines.push(Span::new(
+                        std::mem::take(&mut line),
+                        Style::Mono,
+                        span.color,
+                    ));
+                    used = 0.0;
+                }
+                line.push(c);
+                used += width;
+            }
+            lines.push(Span::new(line, Style::Mono, span.color));
+        }
+
+        let count = lines.len();
+        for (i, span) in lines.into_iter().enumerate() {
+            let top = if i == 0 { CODE_PADDING } else { 0.0 };
+            let bottom = if i + 1 == count { CODE_PADDING } else { 0.0 };
+            let box_height = top + height + bottom;
+            self.reserve(box_height);
+            self.push(Op::Fill {
+                x: frame.left(),
+                y: self.y - box_height,
+                width: frame.width(),
+                height: box_height,
+                color: CODE_BACKGROUND,
+            });
+            self.decorate(frame, box_height);
+            let size = if span.style == Style::Mono {
+                CODE_SIZE
+            } else {
+                SMALL_SIZE
+            };
+            let y = self.y - top - baseline(CODE_SIZE);
+            self.text(frame.left() + CODE_PADDING, y, size, span);
+            self.y -= box_height;
+        }
+    }
+
+    /// A table with wrapped cells, drawn a line at a time so long tables
+    /// continue on the next page
+    fn table(&mut self, frame: &Frame, rows: Vec<(bool, Vec<Vec<Span>>)>) {
+        let columns = rows.iter().map(|(_, cells)| cells.len()).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        let mut natural = vec![0.0f32; columns];
+        for (_, cells) in &rows {
+            for (column, cell) in cells.iter().enumerate() {
+                let width: f32 = cell
+                    .iter()
+                    .map(|s| self.fonts.width(&s.text, s.style, TABLE_SIZE))
+                    .sum();
+                natural[column] = natural[column].max(width + 2.0 * CELL_PADDING);
+            }
+        }
+        let widths = column_widths(&natural, frame.width());
+        let mut edges = vec![frame.left()];
+        for width in &widths {
+            edges.push(edges[edges.len() - 1] + width);
+        }
+        let right = edges[columns];
+        let height = line_height(TABLE_SIZE);
+
+        for (header, cells) in rows {
+            let wrapped: Vec<Vec<Vec<Span>>> = (0..columns)
+                .map(|column| match cells.get(column) {
+                    Some(cell) => wrap(
+                        self.fonts,
+                        cell,
+                        TABLE_SIZE,
+                        widths[column] - 2.0 * CELL_PADDING,
+                    ),
+                    None => Vec::new(),
+                })
+                .collect();
+            let count = wrapped.iter().map(Vec::len).max().unwrap_or(0).max(1);
+
+            for i in 0..count {
+                let top = if i == 0 { CELL_PADDING } else { 0.0 };
+                let bottom = if i + 1 == count { CELL_PADDING } else { 0.0 };
+                let box_height = top + height + bottom;
+                let page = self.page();
+                self.reserve(box_height);
+                if header {
+                    self.push(Op::Fill {
+                        x: frame.left(),
+                        y: self.y - box_height,
+                        width: right - frame.left(),
+                        height: box_height,
+                        color: HEADER_BACKGROUND,
+                    });
+                }
+                if i == 0 || self.page() != page {
+                    self.rule((frame.left(), self.y), (right, self.y), 0.3, RULE);
+                }
+                self.decorate(frame, box_height);
+                for &x in &edges {
+                    self.rule((x, self.y), (x, self.y - box_height), 0.3, RULE);
+                }
+                let y = self.y - top - baseline(TABLE_SIZE);
+                for (column, lines) in wrapped.iter().enumerate() {
+                    let mut x = edges[column] + CELL_PADDING;
+                    for span in lines.get(i).cloned().unwrap_or_default() {
+                        let width = self.fonts.width(&span.text, span.style, TABLE_SIZE);
+                        self.text(x, y, TABLE_SIZE, span);
+                        x += width;
+                    }
+                }
+                self.y -= box_height;
+            }
+            self.rule((frame.left(), self.y), (right, self.y), 0.3, RULE);
+        }
+    }
+}
+
+/// Share `available` between table columns. Everything fits at its natural
+/// width if it can; otherwise each column gets up to an equal share and the
+/// space narrow columns leave goes to the wider ones in proportion to how much
+/// more they want.
+fn column_widths(natural: &[f32], available: f32) -> Vec<f32> {
+    if natural.iter().sum::<f32>() <= available {
+        return natural.to_vec();
+    }
+    let share = available / natural.len() as f32;
+    let mut widths: Vec<f32> = natural.iter().map(|w| w.min(share)).collect();
+    let spare = available - widths.iter().sum::<f32>();
+    let wanted: f32 = natural.iter().zip(&widths).map(|(n, w)| n - w).sum();
+    if wanted > 0.0 {
+        for (width, natural) in widths.iter_mut().zip(natural) {
+            *width += spare * (natural - *width) / wanted;
+        }
+    }
+    widths
+}
+
+fn markdown_options() -> ComrakOptions {
+    // The same extensions as the HTML export
+    let mut options = ComrakOptions::default();
+    options.extension.strikethrough = true;
+    options.extension.table = true;
+    options.extension.autolink = true;
+    options.extension.tasklist = true;
+    options.extension.superscript = true;
+    options.extension.footnotes = true;
+    options
+}
+
+#[derive(Clone, Copy)]
+struct Format {
+    bold: bool,
+    italic: bool,
+    color: Color3,
+}
+
+impl Format {
+    fn span(&self, text: impl Into<String>) -> Span {
+        let style = if self.bold {
+            Style::Bold
+        } else if self.italic {
+            Style::Italic
+        } else {
+            Style::Regular
+        };
+        Span::new(text, style, self.color)
+    }
+}
+
+/// Plain text of an inline subtree, e.g. a link's label
+fn plain_text<'n>(node: &'n AstNode<'n>) -> String {
+    let mut text = String::new();
+    for child in node.descendants() {
+        match &child.data.borrow().value {
+            NodeValue::Text(t) => text.push_str(t),
+            NodeValue::Code(code) => text.push_str(&code.literal),
+            _ => {}
+        }
+    }
+    text
+}
+
+fn inline<'n>(node: &'n AstNode<'n>, format: Format, out: &mut Vec<Span>) {
+    for child in node.children() {
+        match &child.data.borrow().value {
+            NodeValue::Text(text) => out.push(format.span(text.as_str())),
+            NodeValue::Code(code) => {
+                out.push(Span::new(code.literal.as_str(), Style::Mono, INLINE_CODE))
+            }
+            // Chat messages rely on single newlines, as with the HTML export's
+            // hard breaks
+            NodeValue::SoftBreak | NodeValue::LineBreak => out.push(format.span("\n")),
+            NodeValue::Emph => inline(
+                child,
+                Format {
+                    italic: true,
+                    ..format
+                },
+                out,
+            ),
+            NodeValue::Strong => inline(
+                child,
+                Format {
+                    bold: true,
+                    ..format
+                },
+                out,
+            ),
+            NodeValue::Link(link) => {
+                inline(
+                    child,
+                    Format {
+                        color: LINK,
+                        ..format
+                    },
+                    out,
+                );
+                // Paper has no hyperlinks; show where the link goes
+                let url = link.url.trim_start_matches("mailto:");
+                if !url.is_empty() && !url.starts_with('#') && plain_text(child) != url {
+                    out.push(Span::new(format!(" <{}>", url), Style::Regular, MUTED));
+                }
+            }
+            NodeValue::Image(image) => {
+                let alt = plain_text(child);
+                let label = if alt.is_empty() { &image.url } else { &alt };
+                out.push(Span::new(
+                    format!("[image: {}]", label),
+                    Style::Italic,
+                    MUTED,
+                ));
+            }
+            NodeValue::HtmlInline(html) => out.push(format.span(html.as_str())),
+            NodeValue::FootnoteReference(name) => out.push(format.span(format!("[{}]", name))),
+            // Strikethrough, superscript: keep the text
+            _ => inline(child, format, out),
+        }
+    }
+}
+
+fn spans<'n>(node: &'n AstNode<'n>, frame: &Frame, bold: bool) -> Vec<Span> {
+    let mut out = Vec::new();
+    let format = Format {
+        bold,
+        italic: false,
+        color: frame.color,
+    };
+    inline(node, format, &mut out);
+    out
+}
+
+fn block_gap(frame: &Frame) -> f32 {
+    if frame.tight {
+        0.8
+    } else {
+        2.5
+    }
+}
+
+fn blocks<'n>(out: &mut Layout, node: &'n AstNode<'n>, frame: &Frame) {
+    for child in node.children() {
+        block(out, child, frame);
+    }
+}
+
+fn block<'n>(out: &mut Layout, node: &'n AstNode<'n>, frame: &Frame) {
+    match &node.data.borrow().value {
+        NodeValue::Paragraph => {
+            out.paragraph(frame, &spans(node, frame, false), BODY_SIZE);
+            out.skip(block_gap(frame));
+        }
+        NodeValue::Heading(heading) => {
+            let size = HEADING_SIZES[(heading.level.max(1) as usize - 1).min(5)];
+            out.skip(size * PT * 0.5);
+            // Keep the heading with the first line that follows it
+            out.reserve(line_height(size) + line_height(BODY_SIZE));
+            out.paragraph(frame, &spans(node, frame, true), size);
+            out.skip(1.5);
+        }
+        NodeValue::BlockQuote => {
+            let mut inner = frame.clone();
+            inner.quote_bars.push(frame.left() + 1.0);
+            inner.indent += QUOTE_INDENT;
+            inner.color = MUTED;
+            blocks(out, node, &inner);
+        }
+        NodeValue::List(list) => {
+            let mut inner = frame.clone();
+            inner.indent += LIST_INDENT;
+            inner.list_depth += 1;
+            inner.tight = list.tight;
+            for (i, item) in node.children().enumerate() {
+                let marker = match (&item.data.borrow().value, list.list_type) {
+                    (NodeValue::TaskItem(Some(_)), _) => "☑".to_string(),
+                    (NodeValue::TaskItem(None), _) => "☐".to_string(),
+                    (_, ListType::Ordered) => {
+                        let delimiter = match list.delimiter {
+                            ListDelimType::Period => '.',
+                            ListDelimType::Paren => ')',
+                        };
+                        format!("{}{}", list.start + i, delimiter)
+                    }
+                    (_, ListType::Bullet) => match frame.list_depth % 3 {
+                        0 => "•",
+                        1 => "◦",
+                        _ => "▪",
+                    }
+                    .to_string(),
+                };
+                out.marker = Some((inner.left() - 1.5, marker));
+                blocks(out, item, &inner);
+                if out.marker.is_some() {
+                    // Empty item
+                    out.lines(&inner, vec![Vec::new()], BODY_SIZE);
+                }
+            }
+            if !frame.tight {
+                out.skip(block_gap(frame));
+            }
+        }
+        NodeValue::CodeBlock(code) => {
+            out.code_block(frame, &code.info, &code.literal);
+            out.skip(block_gap(frame).max(2.0));
+        }
+        NodeValue::HtmlBlock(html) => {
+            out.code_block(frame, "", &html.literal);
+            out.skip(block_gap(frame).max(2.0));
+        }
+        NodeValue::ThematicBreak => {
+            out.reserve(4.0);
+            out.decorate(frame, 4.0);
+            let y = out.y - 2.0;
+            out.rule(
+                (frame.left(), y),
+                (frame.left() + frame.width(), y),
+                0.5,
+                RULE,
+            );
+            out.y -= 4.0;
+        }
+        NodeValue::Table(_) => {
+            let rows = node
+                .children()
+                .map(|row| {
+                    let header = matches!(row.data.borrow().value, NodeValue::TableRow(true));
+                    let cells = row
+                        .children()
+                        .map(|cell| spans(cell, frame, header))
+                        .collect();
+                    (header, cells)
+                })
+                .collect();
+            out.table(frame, rows);
+            out.skip(block_gap(frame).max(2.0));
+        }
+        NodeValue::FootnoteDefinition(name) => {
+            let mut inner = frame.clone();
+            inner.indent += LIST_INDENT;
+            out.marker = Some((inner.left() - 1.5, format!("[{}]", name)));
+            blocks(out, node, &inner);
+        }
+        NodeValue::FrontMatter(_) => {}
+        _ => blocks(out, node, frame),
+    }
+}
+
+fn role_color(role: &str) -> Color3 {
+    match role {
+        "user" => (0.13, 0.38, 0.68),
+        "assistant" => (0.13, 0.52, 0.33),
+        _ => MUTED,
+    }
+}
+
+fn section(out: &mut Layout, section: &PdfSection) {
+    let frame = Frame::default();
+    out.paragraph(
+        &frame,
+        &[Span::new(section.title.as_str(), Style::Bold, TEXT)],
+        TITLE_SIZE,
+    );
+    out.skip(1.5);
+    for line in &section.metadata {
+        out.paragraph(
+            &frame,
+            &[Span::new(line.as_str(), Style::Regular, MUTED)],
+            SMALL_SIZE + 1.0,
+        );
+    }
+    out.skip(2.0);
+    let y = out.y;
+    out.rule((MARGIN, y), (MARGIN + CONTENT_WIDTH, y), 0.8, RULE);
+    out.skip(5.0);
+
+    let options = markdown_options();
+    for message in &section.messages {
+        // Keep the role heading with the start of the message
+        out.reserve(line_height(ROLE_SIZE) + 2.0 * line_height(BODY_SIZE));
+        out.paragraph(
+            &frame,
+            &[Span::new(
+                message.heading.as_str(),
+                Style::Bold,
+                role_color(&message.role),
+            )],
+            ROLE_SIZE,
+        );
+        out.skip(1.5);
+
+        let arena = Arena::new();
+        let root = parse_document(&arena, &message.content, &options);
+        blocks(out, root, &frame);
+
+        for note in &message.notes {
+            out.paragraph(
+                &frame,
+                &[Span::new(note.as_str(), Style::Italic, MUTED)],
+                SMALL_SIZE,
+            );
+        }
+        out.skip(3.0);
+        let y = out.y;
+        out.rule((MARGIN, y), (MARGIN + CONTENT_WIDTH, y), 0.3, RULE);
+        out.skip(4.0);
+    }
+}
+
+/// Table of contents pages; `offset` is the number of pages it takes up, so
+/// the page numbers printed account for it
+fn contents(
+    fonts: &Fonts,
+    sections: &[PdfSection],
+    starts: &[usize],
+    offset: usize,
+) -> Vec<Vec<Op>> {
+    let mut out = Layout::new(fonts);
+    out.paragraph(
+        &Frame::default(),
+        &[Span::new("Contents", Style::Bold, TEXT)],
+        TITLE_SIZE,
+    );
+    out.skip(4.0);
+
+    let height = line_height(BODY_SIZE);
+    let dot = fonts.width(".", Style::Regular, BODY_SIZE);
+    for (section, start) in sections.iter().zip(starts) {
+        out.reserve(height);
+        let y = out.y - baseline(BODY_SIZE);
+        let number = (offset + start + 1).to_string();
+        let number_width = fonts.width(&number, Style::Regular, BODY_SIZE);
+        let title = fonts.truncate(
+            &section.title,
+            Style::Regular,
+            BODY_SIZE,
+            CONTENT_WIDTH - number_width - 10.0,
+        );
+        let title_width = fonts.width(&title, Style::Regular, BODY_SIZE);
+        out.text(MARGIN, y, BODY_SIZE, Span::new(title, Style::Regular, TEXT));
+
+        let leader_start = MARGIN + title_width + 2.0;
+        let leader_end = MARGIN + CONTENT_WIDTH - number_width - 2.0;
+        let dots = ((leader_end - leader_start) / dot).floor().max(0.0) as usize;
+        out.text(
+            leader_end - dots as f32 * dot,
+            y,
+            BODY_SIZE,
+            Span::new(".".repeat(dots), Style::Regular, MUTED),
+        );
+        out.text(
+            MARGIN + CONTENT_WIDTH - number_width,
+            y,
+            BODY_SIZE,
+            Span::new(number, Style::Regular, TEXT),
+        );
+        out.y -= height;
+    }
+    out.pages
+}
+
+/// Running header and footer for page `number` of `total`
+fn page_chrome(
+    fonts: &Fonts,
+    page: &mut Vec<Op>,
+    title: &str,
+    number: usize,
+    total: usize,
+    exported: &str,
+) {
+    let header = fonts.truncate(title, Style::Regular, SMALL_SIZE, CONTENT_WIDTH);
+    page.push(Op::Text {
+        x: MARGIN,
+        y: PAGE_HEIGHT - 15.0,
+        size: SMALL_SIZE,
+        style: Style::Regular,
+        color: MUTED,
+        text: header,
+    });
+    page.push(Op::Rule {
+        from: (MARGIN, PAGE_HEIGHT - 17.0),
+        to: (MARGIN + CONTENT_WIDTH, PAGE_HEIGHT - 17.0),
+        thickness: 0.3,
+        color: RULE,
+    });
+    page.push(Op::Text {
+        x: MARGIN,
+        y: 12.0,
+        size: SMALL_SIZE,
+        style: Style::Regular,
+        color: MUTED,
+        text: exported.to_string(),
+    });
+    let numbering = format!("Page {} of {}", number, total);
+    page.push(Op::Text {
+        x: MARGIN + CONTENT_WIDTH - fonts.width(&numbering, Style::Regular, SMALL_SIZE),
+        y: 12.0,
+        size: SMALL_SIZE,
+        style: Style::Regular,
+        color: MUTED,
+        text: numbering,
+    });
+}
+
+/// Laid out pages and the outline entries that point into them
+struct Pages {
+    pages: Vec<Vec<Op>>,
+    bookmarks: Vec<(usize, String)>,
+}
+
+fn paginate(fonts: &Fonts, title: &str, sections: &[PdfSection], exported: &str) -> Pages {
+    let mut body = Layout::new(fonts);
+    let mut starts = Vec::with_capacity(sections.len());
+    for (i, s) in sections.iter().enumerate() {
+        if i > 0 {
+            body.new_page();
+        }
+        starts.push(body.page());
+        section(&mut body, s);
+    }
+
+    let mut pages = Vec::new();
+    let mut bookmarks = Vec::new();
+    if sections.len() > 1 {
+        // Every entry takes one line, so the page count does not depend on
+        // the numbers printed
+        let length = contents(fonts, sections, &starts, 0).len();
+        pages = contents(fonts, sections, &starts, length);
+        bookmarks.push((0, "Contents".to_string()));
+    }
+    let offset = pages.len();
+    for (s, start) in sections.iter().zip(&starts) {
+        bookmarks.push((offset + start, s.title.clone()));
+    }
+    pages.extend(body.pages);
+
+    let total = pages.len();
+    for (i, page) in pages.iter_mut().enumerate() {
+        page_chrome(fonts, page, title, i + 1, total, exported);
+    }
+    Pages { pages, bookmarks }
+}
+
+fn rgb((r, g, b): Color3) -> Color {
+    Color::Rgb(Rgb::new(r, g, b, None))
+}
+
+fn draw(layer: &PdfLayerReference, op: &Op, fonts: &HashMap<Style, IndirectFontRef>) {
+    match op {
+        Op::Text {
+            x,
+            y,
+            size,
+            style,
+            color,
+            text,
+        } => {
+            if let Some(font) = fonts.get(style) {
+                layer.set_fill_color(rgb(*color));
+                layer.use_text(text.as_str(), *size, Mm(*x), Mm(*y), font);
+            }
+        }
+        Op::Fill {
+            x,
+            y,
+            width,
+            height,
+            color,
+        } => {
+            layer.set_fill_color(rgb(*color));
+            layer.add_rect(
+                Rect::new(Mm(*x), Mm(*y), Mm(x + width), Mm(y + height)).with_mode(PaintMode::Fill),
+            );
+        }
+        Op::Rule {
+            from,
+            to,
+            thickness,
+            color,
+        } => {
+            layer.set_outline_color(rgb(*color));
+            layer.set_outline_thickness(*thickness);
+            layer.add_line(Line {
+                points: vec![
+                    (Point::new(Mm(from.0), Mm(from.1)), false),
+                    (Point::new(Mm(to.0), Mm(to.1)), false),
+                ],
+                is_closed: false,
+            });
+        }
+    }
+}
+
+fn write(title: &str, pages: Pages) -> AppResult<Vec<u8>> {
+    let (doc, first_page, first_layer) =
+        PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+
+    // Fonts are embedded whole, so only embed the faces that are used
+    let mut fonts = HashMap::new();
+    for op in pages.pages.iter().flatten() {
+        if let Op::Text { style, .. } = op {
+            if !fonts.contains_key(style) {
+                let font = doc
+                    .add_external_font(Cursor::new(FACES[*style as usize]))
+                    .map_err(|e| {
+                        AppError::internal(e.to_string()).context("Failed to embed font")
+                    })?;
+                fonts.insert(*style, font);
+            }
+        }
+    }
+
+    let mut indices = Vec::with_capacity(pages.pages.len());
+    for (i, ops) in pages.pages.iter().enumerate() {
+        let (page, layer) = if i == 0 {
+            (first_page, first_layer)
+        } else {
+            doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1")
+        };
+        indices.push(page);
+        let layer = doc.get_page(page).get_layer(layer);
+        for op in ops {
+            draw(&layer, op, &fonts);
+        }
+    }
+    for (page, name) in pages.bookmarks {
+        if let Some(&index) = indices.get(page) {
+            doc.add_bookmark(name, index);
+        }
+    }
+
+    let mut buffer = Vec::new();
+    doc.save(&mut BufWriter::new(&mut buffer))
+        .map_err(|e| AppError::io(e.to_string()).context("Failed to save PDF"))?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> PdfMessage {
+        PdfMessage {
+            role: role.to_string(),
+            heading: role.to_string(),
+            content: content.to_string(),
+            notes: Vec::new(),
+        }
+    }
+
+    fn section_of(title: &str, messages: Vec<PdfMessage>) -> PdfSection {
+        PdfSection {
+            title: title.to_string(),
+            metadata: vec!["Provider: test".to_string()],
+            messages,
+        }
+    }
+
+    fn texts(ops: &[Op]) -> Vec<(&str, Style)> {
+        ops.iter()
+            .filter_map(|op| match op {
+                Op::Text { text, style, .. } => Some((text.as_str(), *style)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn line_width(fonts: &Fonts, line: &[Span], size: f32) -> f32 {
+        line.iter()
+            .map(|s| fonts.width(&s.text, s.style, size))
+            .sum()
+    }
+
+    #[test]
+    fn wrapping_fits_width_and_breaks_long_words() {
+        let fonts = Fonts::load().unwrap();
+        let text = format!(
+            "{} {}",
+            "lorem ipsum dolor sit amet ".repeat(20),
+            "x".repeat(300)
+        );
+        let spans = [Span::new(text, Style::Regular, TEXT)];
+        let lines = wrap(&fonts, &spans, BODY_SIZE, 80.0);
+
+        assert!(lines.len() > 5);
+        for line in &lines {
+            assert!(line_width(&fonts, line, BODY_SIZE) <= 80.0 + 0.01);
+        }
+        // Ordinary words are never split
+        assert!(lines
+            .iter()
+            .filter(|l| !l[0].text.starts_with('x'))
+            .all(|l| !l[0].text.starts_with(' ')
+                && l[0]
+                    .text
+                    .split(' ')
+                    .all(|w| { ["", "lorem", "ipsum", "dolor", "sit", "amet"].contains(&w) })));
+    }
+
+    #[test]
+    fn styles_survive_wrapping_and_hard_breaks() {
+        let fonts = Fonts::load().unwrap();
+        let spans = [
+            Span::new("plain ", Style::Regular, TEXT),
+            Span::new("bold", Style::Bold, TEXT),
+            Span::new("\nnext", Style::Regular, TEXT),
+        ];
+        let lines = wrap(&fonts, &spans, BODY_SIZE, 100.0);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0][1], Span::new("bold", Style::Bold, TEXT));
+        assert_eq!(lines[1][0].text, "next");
+    }
+
+    #[test]
+    fn unicode_text_is_kept_and_missing_glyphs_are_marked() {
+        let fonts = Fonts::load().unwrap();
+        assert_eq!(
+            fonts.clean("Привет, κόσμε — ünïcödé", Style::Regular),
+            "Привет, κόσμε — ünïcödé"
+        );
+        assert_eq!(fonts.clean("漢字\tx", Style::Regular), "\u{FFFD}\u{FFFD} x");
+    }
+
+    #[test]
+    fn columns_share_the_available_width() {
+        assert_eq!(column_widths(&[20.0, 30.0], 100.0), vec![20.0, 30.0]);
+
+        let widths = column_widths(&[10.0, 200.0, 100.0], 150.0);
+        assert!((widths.iter().sum::<f32>() - 150.0).abs() < 0.01);
+        assert_eq!(widths[0], 10.0);
+        assert!(widths[1] > widths[2]);
+    }
+
+    #[test]
+    fn markdown_elements_are_laid_out() {
+        let fonts = Fonts::load().unwrap();
+        let content = "# Heading\n\nSome **bold** and `code`.\n\n\
+            - one\n- two\n\n\
+            ```rust\nfn main() {}\n```\n\n\
+            | Name | Value |\n|---|---|\n| a | 1 |\n\n> quoted";
+        let sections = [section_of("Markdown", vec![message("assistant", content)])];
+        let pages = paginate(&fonts, "Markdown", &sections, "Exported");
+        let ops = &pages.pages[0];
+        let text = texts(ops);
+
+        assert!(text.contains(&("Heading", Style::Bold)));
+        assert!(text.contains(&("bold", Style::Bold)));
+        assert!(text.contains(&("code", Style::Mono)));
+        assert!(text.contains(&("•", Style::Regular)));
+        assert!(text.contains(&("fn main() {}", Style::Mono)));
+        assert!(text.contains(&("Name", Style::Bold)));
+        assert!(text.contains(&("a", Style::Regular)));
+        assert!(text.contains(&("quoted", Style::Regular)));
+        assert!(ops
+            .iter()
+            .any(|op| matches!(op, Op::Fill { color, .. } if *color == CODE_BACKGROUND)));
+        assert!(ops
+            .iter()
+            .any(|op| matches!(op, Op::Fill { color, .. } if *color == HEADER_BACKGROUND)));
+    }
+
+    #[test]
+    fn long_conversations_paginate_within_margins() {
+        let fonts = Fonts::load().unwrap();
+        let code = format!("```\n{}\n```", "let x = 1; // long line ".repeat(20));
+        let messages = (0..60)
+            .map(|i| message("user", &format!("Message {}\n\n{}", i, code)))
+            .collect();
+        let sections = [section_of("Long", messages)];
+        let pages = paginate(&fonts, "Long", &sections, "Exported");
+
+        let total = pages.pages.len();
+        assert!(total > 3);
+        for (i, page) in pages.pages.iter().enumerate() {
+            let numbering = format!("Page {} of {}", i + 1, total);
+            assert!(texts(page).iter().any(|(t, _)| *t == numbering));
+            for op in page {
+                if let Op::Text {
+                    x,
+                    text,
+                    size,
+                    style,
+                    ..
+                } = op
+                {
+                    assert!(*x >= MARGIN);
+                    assert!(x + fonts.width(text, *style, *size) <= MARGIN + CONTENT_WIDTH + 0.01);
+                }
+            }
+        }
+        // Every message made it
+        let all: Vec<_> = pages.pages.iter().flat_map(|p| texts(p)).collect();
+        assert!(all.iter().any(|(t, _)| *t == "Message 59"));
+    }
+
+    #[test]
+    fn multi_conversation_exports_get_a_table_of_contents() {
+        let fonts = Fonts::load().unwrap();
+        let sections: Vec<PdfSection> = (0..3)
+            .map(|i| section_of(&format!("Conversation {}", i), vec![message("user", "Hi")]))
+            .collect();
+        let pages = paginate(&fonts, "Export", &sections, "Exported");
+
+        assert_eq!(pages.pages.len(), 4);
+        let contents = texts(&pages.pages[0]);
+        assert!(contents.contains(&("Contents", Style::Bold)));
+        assert!(contents.contains(&("Conversation 2", Style::Regular)));
+        assert!(contents.contains(&("4", Style::Regular)));
+
+        assert_eq!(pages.bookmarks[0], (0, "Contents".to_string()));
+        for (page, title) in &pages.bookmarks[1..] {
+            assert!(texts(&pages.pages[*page]).contains(&(title.as_str(), Style::Bold)));
+        }
+    }
+
+    #[test]
+    fn render_writes_a_pdf() {
+        let sections = [section_of(
+            "Ünïcode — Привет",
+            vec![message("user", "Hello *world*")],
+        )];
+        let bytes = render("Ünïcode — Привет", &sections).unwrap();
+        assert!(bytes.starts_with(b"%PDF"));
+    }
+}
@@ -0,0 +1,342 @@
+// Project indexer: splits text files under a project root into overlapping
+// line chunks stored in the `project_chunks` FTS5 table, and retrieves the
+// most relevant chunks (with file/line citations) for prompt augmentation.
+
+use crate::commands::project::is_text_file;
+use crate::database::project_chunks::{NewProjectChunk, ProjectChunk, ScoredChunk};
+use crate::database::Database;
+use crate::error::{AppError, AppResult};
+use ignore::gitignore::Gitignore;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Number of lines per chunk
+pub const CHUNK_LINES: usize = 40;
+/// Lines shared between consecutive chunks so matches near a boundary keep context
+pub const CHUNK_OVERLAP: usize = 8;
+/// Files larger than this are not indexed (generated or vendored content)
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+/// Default number of chunks added to a prompt
+pub const DEFAULT_TOP_K: usize = 5;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct IndexStats {
+    pub project_root: String,
+    pub files_indexed: usize,
+    pub files_unchanged: usize,
+    pub files_removed: usize,
+    pub total_chunks: i64,
+    pub index_time_ms: u64,
+}
+
+/// Key under which a project's chunks are stored (canonical path when available)
+pub fn root_key(root: &Path) -> String {
+    fs::canonicalize(root)
+        .unwrap_or_else(|_| root.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Split file content into overlapping line chunks (1-based, inclusive line numbers)
+pub fn chunk_text(path: &str, content: &str) -> Vec<NewProjectChunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut chunks = Vec::new();
+    let step = CHUNK_LINES - CHUNK_OVERLAP;
+    let mut start = 0;
+
+    while start < lines.len() {
+        let end = (start + CHUNK_LINES).min(lines.len());
+        let text = lines[start..end].join("\n");
+        if !text.trim().is_empty() {
+            chunks.push(NewProjectChunk {
+                path: path.to_string(),
+                start_line: start as i64 + 1,
+                end_line: end as i64,
+                content: text,
+            });
+        }
+        if end == lines.len() {
+            break;
+        }
+        start += step;
+    }
+
+    chunks
+}
+
+fn file_mtime(path: &Path) -> Option<i64> {
+    fs::metadata(path)
+        .ok()?
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs() as i64)
+}
+
+fn is_indexable(path: &Path) -> bool {
+    let file_type = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("unknown")
+        .to_lowercase();
+    if !is_text_file(&file_type) {
+        return false;
+    }
+    matches!(fs::metadata(path), Ok(meta) if meta.is_file() && meta.len() <= MAX_FILE_BYTES)
+}
+
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    path.strip_prefix(root)
+        .ok()
+        .map(|rel| rel.to_string_lossy().to_string())
+}
+
+/// Collect indexable files under `root`, skipping anything matched by `gitignore`
+fn collect_files(root: &Path, gitignore: &Gitignore) -> Vec<PathBuf> {
+    fn visit(dir: &Path, root: &Path, gitignore: &Gitignore, files: &mut Vec<PathBuf>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_dir = path.is_dir();
+            if let Ok(rel) = path.strip_prefix(root) {
+                if gitignore.matched(rel, is_dir).is_ignore() {
+                    continue;
+                }
+            }
+            if is_dir {
+                visit(&path, root, gitignore, files);
+            } else if is_indexable(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    visit(root, root, gitignore, &mut files);
+    files
+}
+
+/// Index (or refresh) every text file under a project root. Files whose
+/// modification time matches the stored index are skipped, and chunks of
+/// files that disappeared are removed.
+pub fn index_project(db: &Database, root: &Path, gitignore: &Gitignore) -> AppResult<IndexStats> {
+    let start_time = std::time::Instant::now();
+    let key = root_key(root);
+    let canonical_root = PathBuf::from(&key);
+
+    let existing: HashMap<String, i64> = {
+        let conn = db.conn().lock()?;
+        ProjectChunk::get_indexed_files(&conn, &key)?
+            .into_iter()
+            .collect()
+    };
+
+    let mut stats = IndexStats {
+        project_root: key.clone(),
+        ..Default::default()
+    };
+    let mut seen = HashSet::new();
+
+    for path in collect_files(&canonical_root, gitignore) {
+        let Some(rel) = relative_path(&canonical_root, &path) else {
+            continue;
+        };
+        let Some(mtime) = file_mtime(&path) else {
+            continue;
+        };
+        seen.insert(rel.clone());
+
+        if existing.get(&rel) == Some(&mtime) {
+            stats.files_unchanged += 1;
+            continue;
+        }
+
+        // Binary or non-UTF-8 files are silently skipped
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let chunks = chunk_text(&rel, &content);
+
+        // Lock per file so indexing a large project doesn't block other commands
+        let conn = db.conn().lock()?;
+        ProjectChunk::replace_file(&conn, &key, &rel, mtime, &chunks)
+            .map_err(|e| AppError::from(e).context(format!("Failed to index {}", rel)))?;
+        stats.files_indexed += 1;
+    }
+
+    let conn = db.conn().lock()?;
+    for rel in existing.keys().filter(|rel| !seen.contains(*rel)) {
+        ProjectChunk::delete_file(&conn, &key, rel)?;
+        stats.files_removed += 1;
+    }
+    stats.total_chunks = ProjectChunk::count(&conn, &key)?;
+    stats.index_time_ms = start_time.elapsed().as_millis() as u64;
+
+    Ok(stats)
+}
+
+/// Refresh the index for a single path reported by the file watcher
+pub fn refresh_path(db: &Database, root: &Path, path: &Path) -> AppResult<()> {
+    let key = root_key(root);
+    let canonical_root = PathBuf::from(&key);
+    // Deleted files can't be canonicalized; fall back to rebasing onto the canonical root
+    let resolved = fs::canonicalize(path).unwrap_or_else(|_| {
+        path.strip_prefix(root)
+            .map(|rel| canonical_root.join(rel))
+            .unwrap_or_else(|_| path.to_path_buf())
+    });
+    let Some(rel) = relative_path(&canonical_root, &resolved) else {
+        return Ok(());
+    };
+
+    if resolved.is_dir() {
+        return Ok(());
+    }
+
+    let conn = db.conn().lock()?;
+    if !resolved.exists() || !is_indexable(&resolved) {
+        return ProjectChunk::delete_file(&conn, &key, &rel).map_err(AppError::from);
+    }
+
+    let (Some(mtime), Ok(content)) = (file_mtime(&resolved), fs::read_to_string(&resolved)) else {
+        return Ok(());
+    };
+    let chunks = chunk_text(&rel, &content);
+    ProjectChunk::replace_file(&conn, &key, &rel, mtime, &chunks).map_err(AppError::from)
+}
+
+/// Turn free text into an FTS5 query: distinct terms OR-ed together, each quoted
+/// so punctuation in the prompt can't produce FTS syntax errors
+pub fn build_fts_query(text: &str) -> Option<String> {
+    const STOPWORDS: &[&str] = &[
+        "the", "and", "for", "with", "this", "that", "what", "how", "why", "does", "are", "is",
+        "to", "of", "in", "it", "on", "a", "an", "can", "you", "me", "my", "do", "be", "or",
+    ];
+
+    let mut seen = HashSet::new();
+    let terms: Vec<String> = text
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map(|term| term.to_lowercase())
+        .filter(|term| term.chars().count() >= 2 && !STOPWORDS.contains(&term.as_str()))
+        .filter(|term| seen.insert(term.clone()))
+        .take(32)
+        .map(|term| format!("\"{}\"", term))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" OR "))
+    }
+}
+
+/// Top-k chunks of a project relevant to `query`
+pub fn retrieve(
+    conn: &Connection,
+    project_root: &str,
+    query: &str,
+    top_k: usize,
+) -> AppResult<Vec<ScoredChunk>> {
+    match build_fts_query(query) {
+        Some(fts_query) => ProjectChunk::search(conn, project_root, &fts_query, top_k as i64)
+            .map_err(AppError::from),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Format retrieved chunks as a system prompt section with `path:start-end` citations
+pub fn format_context(chunks: &[ScoredChunk]) -> String {
+    let mut output = String::from(
+        "Relevant excerpts from the user's project. Cite them as path:line when you use them.\n",
+    );
+    for (i, scored) in chunks.iter().enumerate() {
+        let chunk = &scored.chunk;
+        output.push_str(&format!(
+            "\n[{}] {}:{}-{}\n```\n{}\n```\n",
+            i + 1,
+            chunk.path,
+            chunk.start_line,
+            chunk.end_line,
+            chunk.content
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ignore::gitignore::GitignoreBuilder;
+    use tempfile::TempDir;
+
+    #[test]
+    fn chunk_text_overlaps_and_tracks_lines() {
+        let content = (1..=100)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let chunks = chunk_text("src/lib.rs", &content);
+
+        assert_eq!(chunks[0].start_line, 1);
+        assert_eq!(chunks[0].end_line, CHUNK_LINES as i64);
+        assert_eq!(
+            chunks[1].start_line,
+            (CHUNK_LINES - CHUNK_OVERLAP) as i64 + 1
+        );
+        assert_eq!(chunks.last().unwrap().end_line, 100);
+        assert!(chunks[1].content.starts_with("line 33"));
+    }
+
+    #[test]
+    fn build_fts_query_quotes_terms() {
+        assert_eq!(
+            build_fts_query("How does parse_config() work?").as_deref(),
+            Some("\"parse_config\" OR \"work\"")
+        );
+        assert_eq!(build_fts_query("?? a"), None);
+    }
+
+    #[test]
+    fn index_and_retrieve_respects_ignore_and_refresh() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("src/config.rs"), "fn parse_config() {}\n").unwrap();
+        fs::write(root.join("target/config.rs"), "fn parse_config() {}\n").unwrap();
+
+        let mut builder = GitignoreBuilder::new(root);
+        builder.add_line(None, "target/**").unwrap();
+        let gitignore = builder.build().unwrap();
+
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let stats = index_project(&db, root, &gitignore).expect("index");
+        assert_eq!(stats.files_indexed, 1);
+
+        let key = root_key(root);
+        {
+            let conn = db.conn().lock().unwrap();
+            let hits = retrieve(&conn, &key, "where is parse_config defined", 5).unwrap();
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].chunk.path, "src/config.rs");
+            assert!(format_context(&hits).contains("src/config.rs:1-1"));
+        }
+
+        // Unchanged files are skipped on re-index
+        let stats = index_project(&db, root, &gitignore).expect("reindex");
+        assert_eq!(stats.files_unchanged, 1);
+
+        // Watcher refresh drops deleted files
+        fs::remove_file(root.join("src/config.rs")).unwrap();
+        refresh_path(&db, root, &root.join("src/config.rs")).unwrap();
+        let conn = db.conn().lock().unwrap();
+        assert!(retrieve(&conn, &key, "parse_config", 5).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,334 @@
+// Typed provider failures and the retry policy every provider request runs
+// under. Transient failures (rate limits, overloaded or failing servers, network
+// errors) are retried with exponential backoff, honouring `Retry-After`; the
+// rest surface immediately so a fallback provider can take over.
+
+use crate::database::settings::Setting;
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderErrorKind {
+    /// Missing, invalid or unauthorized API key
+    Auth,
+    /// Too many requests (HTTP 429)
+    RateLimit,
+    /// The prompt doesn't fit the model's context window
+    ContextOverflow,
+    /// Connection, DNS, TLS or timeout failure before a response arrived
+    Network,
+    /// Provider-side failure or overload (5xx, Anthropic's 529)
+    Server,
+    /// Refused by the active profile's hard budget limit
+    Budget,
+    /// Anything else the provider rejected, e.g. an unknown model
+    InvalidRequest,
+}
+
+impl ProviderErrorKind {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Auth => "authentication failed",
+            Self::RateLimit => "rate limited",
+            Self::ContextOverflow => "context window exceeded",
+            Self::Network => "network error",
+            Self::Server => "server error",
+            Self::Budget => "budget exceeded",
+            Self::InvalidRequest => "request rejected",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderError {
+    pub kind: ProviderErrorKind,
+    pub provider: String,
+    /// HTTP status, when the provider answered
+    pub status: Option<u16>,
+    pub message: String,
+    /// Delay requested by the provider through `Retry-After`
+    #[serde(skip)]
+    pub retry_after: Option<Duration>,
+}
+
+impl ProviderError {
+    pub fn new(kind: ProviderErrorKind, provider: &str, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            provider: provider.to_string(),
+            status: None,
+            message: message.into(),
+            retry_after: None,
+        }
+    }
+
+    /// Classify a non-success HTTP response
+    pub fn from_status(
+        provider: &str,
+        status: u16,
+        body: &str,
+        retry_after: Option<Duration>,
+    ) -> Self {
+        let lower = body.to_lowercase();
+        let overflow = [
+            "context_length_exceeded",
+            "maximum context length",
+            "prompt is too long",
+            "too many tokens",
+            "exceeds the maximum number of tokens",
+        ]
+        .iter()
+        .any(|needle| lower.contains(needle));
+
+        let kind = match status {
+            401 | 403 => ProviderErrorKind::Auth,
+            429 => ProviderErrorKind::RateLimit,
+            400 | 413 | 422 if overflow => ProviderErrorKind::ContextOverflow,
+            408 | 500..=599 => ProviderErrorKind::Server,
+            _ => ProviderErrorKind::InvalidRequest,
+        };
+        Self {
+            kind,
+            provider: provider.to_string(),
+            status: Some(status),
+            message: format!("HTTP {}: {}", status, body),
+            retry_after,
+        }
+    }
+
+    /// A transport failure from reqwest (no usable response)
+    pub fn transport(provider: &str, err: &reqwest::Error) -> Self {
+        let kind = if err.is_decode() {
+            ProviderErrorKind::Server
+        } else {
+            ProviderErrorKind::Network
+        };
+        Self::new(kind, provider, err.to_string())
+    }
+
+    /// Worth retrying against the same provider
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind,
+            ProviderErrorKind::RateLimit | ProviderErrorKind::Network | ProviderErrorKind::Server
+        )
+    }
+
+    /// Worth trying the next provider in a fallback chain. A malformed request
+    /// would most likely fail the same way everywhere.
+    pub fn should_fall_back(&self) -> bool {
+        self.kind != ProviderErrorKind::InvalidRequest
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {}",
+            self.provider,
+            self.kind.label(),
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl From<ProviderError> for String {
+    fn from(err: ProviderError) -> Self {
+        err.to_string()
+    }
+}
+
+/// Parse a `Retry-After` value: delay seconds or an HTTP date
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return (seconds >= 0.0).then(|| Duration::from_secs_f64(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Delay requested by response headers. OpenAI also sends `retry-after-ms`.
+pub fn retry_after_from_headers(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.trim().parse::<u64>().ok()) {
+        return Some(Duration::from_millis(ms));
+    }
+    header("retry-after").and_then(|v| parse_retry_after(v, Utc::now()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts per provider, including the first
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    /// Longest single wait; a longer `Retry-After` ends retries for the provider
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Read `provider_retry_*` settings, falling back to defaults
+    pub fn load(conn: &Connection) -> Self {
+        let get = |key: &str| {
+            Setting::get(conn, key)
+                .ok()
+                .flatten()
+                .and_then(|v| v.parse::<u64>().ok())
+        };
+        let defaults = Self::default();
+        Self {
+            max_attempts: get("provider_retry_max_attempts")
+                .map(|n| n.clamp(1, 10) as u32)
+                .unwrap_or(defaults.max_attempts),
+            base_delay: get("provider_retry_base_delay_ms")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.base_delay),
+            max_delay: get("provider_retry_max_delay_ms")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.max_delay),
+        }
+    }
+
+    /// Wait before attempt `attempt + 1` after `err`, or None to stop retrying
+    pub fn delay_for(&self, attempt: u32, err: &ProviderError) -> Option<Duration> {
+        if !err.is_retryable() || attempt >= self.max_attempts {
+            return None;
+        }
+        if let Some(retry_after) = err.retry_after {
+            return (retry_after <= self.max_delay).then_some(retry_after);
+        }
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        // Up to 25% jitter so parallel requests don't retry in lockstep
+        let jitter_range = exponential.as_millis() as u64 / 4;
+        let jitter = if jitter_range > 0 {
+            (uuid::Uuid::new_v4().as_u128() % jitter_range as u128) as u64
+        } else {
+            0
+        };
+        Some((exponential + Duration::from_millis(jitter)).min(self.max_delay))
+    }
+
+    /// Run `request` until it succeeds, fails permanently or attempts run out.
+    /// `wait` is called with the attempt that failed, the delay and the error.
+    pub fn run<T, F, W>(&self, mut request: F, mut wait: W) -> Result<T, ProviderError>
+    where
+        F: FnMut(u32) -> Result<T, ProviderError>,
+        W: FnMut(u32, Duration, &ProviderError),
+    {
+        let mut attempt = 1;
+        loop {
+            match request(attempt) {
+                Ok(value) => return Ok(value),
+                Err(err) => match self.delay_for(attempt, &err) {
+                    Some(delay) => {
+                        wait(attempt, delay, &err);
+                        attempt += 1;
+                    }
+                    None => return Err(err),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn classifies_http_failures() {
+        let kind = |status, body| ProviderError::from_status("openai", status, body, None).kind;
+        assert_eq!(kind(401, "bad key"), ProviderErrorKind::Auth);
+        assert_eq!(kind(429, "slow down"), ProviderErrorKind::RateLimit);
+        assert_eq!(kind(529, "overloaded"), ProviderErrorKind::Server);
+        assert_eq!(
+            kind(400, r#"{"error":{"code":"context_length_exceeded"}}"#),
+            ProviderErrorKind::ContextOverflow
+        );
+        assert_eq!(
+            kind(404, "no such model"),
+            ProviderErrorKind::InvalidRequest
+        );
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap();
+        assert_eq!(parse_retry_after("7", now), Some(Duration::from_secs(7)));
+        assert_eq!(
+            parse_retry_after("Wed, 15 May 2024 12:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn retries_transient_errors_and_honours_retry_after() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+        };
+        let mut waits = Vec::new();
+        let result = policy.run(
+            |attempt| {
+                if attempt < 3 {
+                    Err(ProviderError::from_status(
+                        "anthropic",
+                        529,
+                        "overloaded",
+                        (attempt == 2).then(|| Duration::from_secs(2)),
+                    ))
+                } else {
+                    Ok("done")
+                }
+            },
+            |attempt, delay, _| waits.push((attempt, delay)),
+        );
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(waits.len(), 2);
+        assert!(
+            waits[0].1 >= Duration::from_millis(100) && waits[0].1 < Duration::from_millis(126)
+        );
+        assert_eq!(waits[1], (2, Duration::from_secs(2)));
+
+        // Permanent failures and over-long Retry-After values are not retried
+        let mut calls = 0;
+        let err = policy
+            .run::<(), _, _>(
+                |_| {
+                    calls += 1;
+                    Err(ProviderError::from_status("openai", 401, "bad key", None))
+                },
+                |_, _, _| {},
+            )
+            .unwrap_err();
+        assert_eq!((calls, err.kind), (1, ProviderErrorKind::Auth));
+        let limited = ProviderError::from_status("openai", 429, "", Some(Duration::from_secs(60)));
+        assert_eq!(policy.delay_for(1, &limited), None);
+    }
+}
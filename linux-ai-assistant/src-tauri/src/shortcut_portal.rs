@@ -0,0 +1,472 @@
+//! Client for the `org.freedesktop.portal.GlobalShortcuts` D-Bus interface.
+//!
+//! X11-style key grabs (what `tauri-plugin-global-shortcut` does) never fire
+//! on GNOME and KDE Wayland sessions; there the desktop portal owns global
+//! shortcuts. The flow is: create a session, bind shortcuts to it (the desktop
+//! may ask the user and may bind only some of them or pick other triggers),
+//! then listen for `Activated` signals carrying the shortcut id.
+//!
+//! Portal methods answer through a `org.freedesktop.portal.Request` object
+//! whose `Response` signal carries the result, so each call subscribes to
+//! `Response` before calling and then waits for the one on the returned path.
+
+use crate::error::{AppError, AppResult};
+use futures_util::{Stream, StreamExt};
+use std::collections::HashMap;
+use zbus::message::Type as MessageType;
+use zbus::zvariant::{DeserializeDict, ObjectPath, OwnedObjectPath, OwnedValue, Type, Value};
+use zbus::{Connection, MatchRule, MessageStream};
+
+pub const PORTAL_BUS_NAME: &str = "org.freedesktop.portal.Desktop";
+pub const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+pub const GLOBAL_SHORTCUTS_INTERFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+const REQUEST_INTERFACE: &str = "org.freedesktop.portal.Request";
+const SESSION_INTERFACE: &str = "org.freedesktop.portal.Session";
+
+/// A shortcut to bind: `id` comes back in `Activated` signals
+#[derive(Debug, Clone)]
+pub struct PortalShortcut {
+    pub id: String,
+    pub description: String,
+    /// Suggested trigger in the shortcuts spec format, e.g. `CTRL+space`
+    pub preferred_trigger: Option<String>,
+}
+
+/// A shortcut the desktop accepted
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundShortcut {
+    pub id: String,
+    /// Human-readable trigger chosen by the desktop, e.g. `Ctrl+Space`
+    pub trigger_description: Option<String>,
+}
+
+#[derive(Debug, DeserializeDict, Type)]
+#[zvariant(signature = "dict")]
+struct ShortcutInfo {
+    trigger_description: Option<String>,
+}
+
+#[derive(Debug, DeserializeDict, Type)]
+#[zvariant(signature = "dict")]
+struct BindResults {
+    shortcuts: Option<Vec<(String, ShortcutInfo)>>,
+}
+
+fn portal_error(err: impl std::fmt::Display) -> AppError {
+    AppError::unsupported(format!("GlobalShortcuts portal: {}", err))
+}
+
+/// True when the desktop session is Wayland, where only the portal works
+pub fn is_wayland_session() -> bool {
+    std::env::var("XDG_SESSION_TYPE")
+        .map(|t| t.eq_ignore_ascii_case("wayland"))
+        .unwrap_or(false)
+        || std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// Interface version of the portal, or an error when the desktop has none
+pub async fn portal_version(conn: &Connection) -> AppResult<u32> {
+    let reply = conn
+        .call_method(
+            Some(PORTAL_BUS_NAME),
+            PORTAL_PATH,
+            Some("org.freedesktop.DBus.Properties"),
+            "Get",
+            &(GLOBAL_SHORTCUTS_INTERFACE, "version"),
+        )
+        .await
+        .map_err(portal_error)?;
+    let value: OwnedValue = reply.body().deserialize().map_err(portal_error)?;
+    u32::try_from(value).map_err(portal_error)
+}
+
+fn new_token() -> String {
+    format!("linuxai_{}", uuid::Uuid::new_v4().simple())
+}
+
+/// Call a portal method that answers through a Request object and return the
+/// decoded `Response` results
+async fn request<B, R>(conn: &Connection, method: &str, body: &B) -> AppResult<R>
+where
+    B: serde::Serialize + Type,
+    R: for<'de> serde::Deserialize<'de> + Type,
+{
+    let rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .interface(REQUEST_INTERFACE)
+        .map_err(portal_error)?
+        .member("Response")
+        .map_err(portal_error)?
+        .build();
+    let mut responses = MessageStream::for_match_rule(rule, conn, None)
+        .await
+        .map_err(portal_error)?;
+
+    let reply = conn
+        .call_method(
+            Some(PORTAL_BUS_NAME),
+            PORTAL_PATH,
+            Some(GLOBAL_SHORTCUTS_INTERFACE),
+            method,
+            body,
+        )
+        .await
+        .map_err(portal_error)?;
+    let handle: OwnedObjectPath = reply.body().deserialize().map_err(portal_error)?;
+
+    while let Some(message) = responses.next().await {
+        let message = message.map_err(portal_error)?;
+        if message.header().path().map(|p| p.as_str()) != Some(handle.as_str()) {
+            continue;
+        }
+        let (code, results): (u32, R) = message.body().deserialize().map_err(portal_error)?;
+        return match code {
+            0 => Ok(results),
+            1 => Err(AppError::cancelled(format!("{} was dismissed", method))),
+            _ => Err(portal_error(format!("{} failed", method))),
+        };
+    }
+    Err(portal_error("connection closed before the portal replied"))
+}
+
+/// An open GlobalShortcuts session
+#[derive(Debug)]
+pub struct PortalSession {
+    conn: Connection,
+    handle: OwnedObjectPath,
+}
+
+impl PortalSession {
+    pub async fn create(conn: &Connection) -> AppResult<Self> {
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        options.insert("handle_token", Value::from(new_token()));
+        options.insert("session_handle_token", Value::from(new_token()));
+        let results: HashMap<String, OwnedValue> =
+            request(conn, "CreateSession", &(options,)).await?;
+
+        // The spec says `s`, some implementations send `o`
+        let handle = results
+            .get("session_handle")
+            .and_then(|value| match &**value {
+                Value::Str(s) => ObjectPath::try_from(s.as_str())
+                    .ok()
+                    .map(OwnedObjectPath::from),
+                Value::ObjectPath(p) => Some(OwnedObjectPath::from(p.clone())),
+                _ => None,
+            })
+            .ok_or_else(|| portal_error("CreateSession returned no session handle"))?;
+        Ok(Self {
+            conn: conn.clone(),
+            handle,
+        })
+    }
+
+    pub fn handle(&self) -> &str {
+        self.handle.as_str()
+    }
+
+    /// Bind shortcuts to the session, returning the ones the desktop accepted
+    pub async fn bind(&self, shortcuts: &[PortalShortcut]) -> AppResult<Vec<BoundShortcut>> {
+        let shortcuts: Vec<(&str, HashMap<&str, Value>)> = shortcuts
+            .iter()
+            .map(|s| {
+                let mut info: HashMap<&str, Value> = HashMap::new();
+                info.insert("description", Value::from(s.description.as_str()));
+                if let Some(trigger) = &s.preferred_trigger {
+                    info.insert("preferred_trigger", Value::from(trigger.as_str()));
+                }
+                (s.id.as_str(), info)
+            })
+            .collect();
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        options.insert("handle_token", Value::from(new_token()));
+
+        let results: BindResults = request(
+            &self.conn,
+            "BindShortcuts",
+            &(self.handle.as_ref(), shortcuts, "", options),
+        )
+        .await?;
+        Ok(results
+            .shortcuts
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(id, info)| BoundShortcut {
+                id,
+                trigger_description: info.trigger_description,
+            })
+            .collect())
+    }
+
+    /// Ids of this session's shortcuts as they are pressed
+    pub async fn activations(&self) -> AppResult<impl Stream<Item = String> + Send + 'static> {
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .interface(GLOBAL_SHORTCUTS_INTERFACE)
+            .map_err(portal_error)?
+            .member("Activated")
+            .map_err(portal_error)?
+            .build();
+        let stream = MessageStream::for_match_rule(rule, &self.conn, None)
+            .await
+            .map_err(portal_error)?;
+        let session = self.handle.clone();
+        Ok(stream.filter_map(move |message| {
+            let id = message.ok().and_then(|message| {
+                let (handle, id, _timestamp, _options): (
+                    OwnedObjectPath,
+                    String,
+                    u64,
+                    HashMap<String, OwnedValue>,
+                ) = message.body().deserialize().ok()?;
+                (handle == session).then_some(id)
+            });
+            futures_util::future::ready(id)
+        }))
+    }
+
+    /// Close the session, releasing its shortcuts
+    pub async fn close(self) {
+        let _ = self
+            .conn
+            .call_method(
+                Some(PORTAL_BUS_NAME),
+                self.handle.as_str(),
+                Some(SESSION_INTERFACE),
+                "Close",
+                &(),
+            )
+            .await;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::time::Duration;
+
+    /// A private `dbus-daemon`, killed on drop
+    pub(crate) struct TestBus {
+        child: Child,
+        pub address: String,
+    }
+
+    impl TestBus {
+        /// None when `dbus-daemon` isn't installed
+        pub(crate) fn start() -> Option<Self> {
+            let mut child = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(child.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(Self {
+                child,
+                address: address.trim().to_string(),
+            })
+        }
+
+        pub(crate) async fn connect(&self) -> Connection {
+            zbus::connection::Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .await
+                .unwrap()
+        }
+    }
+
+    impl Drop for TestBus {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    /// Portal that binds every shortcut except the ids in `refuse`
+    struct MockPortal {
+        refuse: Vec<String>,
+    }
+
+    fn request_path(token: &str) -> OwnedObjectPath {
+        ObjectPath::try_from(format!("{}/request/test/{}", PORTAL_PATH, token))
+            .unwrap()
+            .into()
+    }
+
+    fn token(options: &HashMap<String, OwnedValue>, key: &str) -> String {
+        options
+            .get(key)
+            .and_then(|v| String::try_from(v.clone()).ok())
+            .unwrap_or_else(|| "t".to_string())
+    }
+
+    #[zbus::interface(name = "org.freedesktop.portal.GlobalShortcuts")]
+    impl MockPortal {
+        async fn create_session(
+            &self,
+            #[zbus(connection)] conn: &Connection,
+            options: HashMap<String, OwnedValue>,
+        ) -> OwnedObjectPath {
+            let path = request_path(&token(&options, "handle_token"));
+            let session = format!(
+                "{}/session/test/{}",
+                PORTAL_PATH,
+                token(&options, "session_handle_token")
+            );
+            let mut results: HashMap<&str, Value> = HashMap::new();
+            results.insert("session_handle", Value::from(session));
+            conn.emit_signal(
+                None::<()>,
+                &path,
+                REQUEST_INTERFACE,
+                "Response",
+                &(0u32, results),
+            )
+            .await
+            .unwrap();
+            path
+        }
+
+        async fn bind_shortcuts(
+            &self,
+            #[zbus(connection)] conn: &Connection,
+            _session_handle: OwnedObjectPath,
+            shortcuts: Vec<(String, HashMap<String, OwnedValue>)>,
+            _parent_window: String,
+            options: HashMap<String, OwnedValue>,
+        ) -> OwnedObjectPath {
+            let path = request_path(&token(&options, "handle_token"));
+            let bound: Vec<(String, HashMap<String, Value>)> = shortcuts
+                .into_iter()
+                .filter(|(id, _)| !self.refuse.contains(id))
+                .map(|(id, info)| {
+                    let trigger = info
+                        .get("preferred_trigger")
+                        .and_then(|v| String::try_from(v.clone()).ok())
+                        .unwrap_or_default();
+                    let mut out = HashMap::new();
+                    out.insert("trigger_description".to_string(), Value::from(trigger));
+                    (id, out)
+                })
+                .collect();
+            let mut results: HashMap<&str, Value> = HashMap::new();
+            results.insert("shortcuts", Value::from(bound));
+            conn.emit_signal(
+                None::<()>,
+                &path,
+                REQUEST_INTERFACE,
+                "Response",
+                &(0u32, results),
+            )
+            .await
+            .unwrap();
+            path
+        }
+
+        #[zbus(property, name = "version")]
+        fn version(&self) -> u32 {
+            1
+        }
+    }
+
+    /// Serve a mock portal on `bus` under the portal's well-known name
+    pub(crate) async fn serve_mock_portal(bus: &TestBus, refuse: &[&str]) -> Connection {
+        let conn = bus.connect().await;
+        conn.object_server()
+            .at(
+                PORTAL_PATH,
+                MockPortal {
+                    refuse: refuse.iter().map(|s| s.to_string()).collect(),
+                },
+            )
+            .await
+            .unwrap();
+        conn.request_name(PORTAL_BUS_NAME).await.unwrap();
+        conn
+    }
+
+    /// Emit `Activated` for a shortcut id of `session`
+    pub(crate) async fn activate(portal: &Connection, session: &str, id: &str) {
+        let session = ObjectPath::try_from(session).unwrap();
+        portal
+            .emit_signal(
+                None::<()>,
+                PORTAL_PATH,
+                GLOBAL_SHORTCUTS_INTERFACE,
+                "Activated",
+                &(session, id, 0u64, HashMap::<&str, Value>::new()),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn binds_shortcuts_and_reports_activations() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let portal = serve_mock_portal(&bus, &["QuickCapture"]).await;
+        let client = bus.connect().await;
+
+        assert_eq!(portal_version(&client).await.unwrap(), 1);
+
+        let session = PortalSession::create(&client).await.unwrap();
+        assert!(session.handle().starts_with(PORTAL_PATH));
+
+        let bound = session
+            .bind(&[
+                PortalShortcut {
+                    id: "ToggleWindow".to_string(),
+                    description: "Toggle Window".to_string(),
+                    preferred_trigger: Some("CTRL+space".to_string()),
+                },
+                PortalShortcut {
+                    id: "QuickCapture".to_string(),
+                    description: "Quick Capture".to_string(),
+                    preferred_trigger: None,
+                },
+            ])
+            .await
+            .unwrap();
+        assert_eq!(
+            bound,
+            vec![BoundShortcut {
+                id: "ToggleWindow".to_string(),
+                trigger_description: Some("CTRL+space".to_string()),
+            }]
+        );
+
+        let mut activations = Box::pin(session.activations().await.unwrap());
+        activate(
+            &portal,
+            "/org/freedesktop/portal/desktop/session/test/other",
+            "Nope",
+        )
+        .await;
+        activate(&portal, session.handle(), "ToggleWindow").await;
+        let id = tokio::time::timeout(Duration::from_secs(5), activations.next())
+            .await
+            .unwrap();
+        assert_eq!(id.as_deref(), Some("ToggleWindow"));
+
+        session.close().await;
+    }
+
+    #[tokio::test]
+    async fn missing_portal_is_unsupported() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let client = bus.connect().await;
+        let err = portal_version(&client).await.unwrap_err();
+        assert_eq!(err.code, crate::error::ErrorCode::Unsupported);
+    }
+}
@@ -0,0 +1,371 @@
+// Background conversation titling and rolling summaries. A cheap model (local
+// Ollama by default) names a conversation after its first exchange and keeps a
+// condensed summary of the active path that lists and context building can use.
+
+use crate::commands::provider::{generate_with_provider, ProviderMessage};
+use crate::database::{
+    conversations::Conversation, messages::Message, settings::Setting, Database,
+};
+use crate::error::{AppError, AppResult};
+use rusqlite::Connection;
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const DEFAULT_PROVIDER: &str = "ollama";
+pub const DEFAULT_MODEL: &str = "llama3.2";
+/// New messages on the active path before the rolling summary is refreshed
+pub const DEFAULT_SUMMARY_INTERVAL: usize = 10;
+const MAX_TITLE_CHARS: usize = 80;
+/// Per-message cap in prompts so a large paste doesn't overflow a small model
+const MAX_MESSAGE_CHARS: usize = 4000;
+
+#[derive(Debug, Clone)]
+pub struct SummarizerSettings {
+    pub provider: String,
+    pub model: String,
+    pub auto_title: bool,
+    pub auto_summary: bool,
+    pub summary_interval: usize,
+}
+
+impl Default for SummarizerSettings {
+    fn default() -> Self {
+        Self {
+            provider: DEFAULT_PROVIDER.to_string(),
+            model: DEFAULT_MODEL.to_string(),
+            auto_title: true,
+            auto_summary: true,
+            summary_interval: DEFAULT_SUMMARY_INTERVAL,
+        }
+    }
+}
+
+impl SummarizerSettings {
+    /// Read `summarizer_*` settings, falling back to defaults for missing values
+    pub fn load(conn: &Connection) -> Self {
+        let get = |key: &str| Setting::get(conn, key).ok().flatten();
+        let defaults = Self::default();
+        Self {
+            provider: get("summarizer_provider")
+                .filter(|v| !v.is_empty())
+                .unwrap_or(defaults.provider),
+            model: get("summarizer_model")
+                .filter(|v| !v.is_empty())
+                .unwrap_or(defaults.model),
+            auto_title: get("summarizer_auto_title")
+                .map(|v| v != "false")
+                .unwrap_or(defaults.auto_title),
+            auto_summary: get("summarizer_auto_summary")
+                .map(|v| v != "false")
+                .unwrap_or(defaults.auto_summary),
+            summary_interval: get("summarizer_summary_interval")
+                .and_then(|v| v.parse::<usize>().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(defaults.summary_interval),
+        }
+    }
+}
+
+/// Titles the frontend uses before anything better is known
+pub fn is_placeholder_title(title: &str) -> bool {
+    matches!(
+        title.trim().to_lowercase().as_str(),
+        "" | "new conversation" | "new chat" | "untitled"
+    )
+}
+
+/// Reduce a model reply to a single clean title line
+pub fn clean_title(raw: &str) -> Option<String> {
+    let line = raw.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = line
+        .strip_prefix("Title:")
+        .or_else(|| line.strip_prefix("title:"))
+        .unwrap_or(line);
+    let title = line
+        .trim()
+        .trim_matches(|c: char| c == '"' || c == '\'' || c == '*' || c == '#' || c == '`')
+        .trim_end_matches('.')
+        .trim();
+    if title.is_empty() {
+        return None;
+    }
+    Some(title.chars().take(MAX_TITLE_CHARS).collect())
+}
+
+fn transcript(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .map(|m| {
+            let role = match m.role.as_str() {
+                "user" => "User",
+                "assistant" => "Assistant",
+                "system" => "System",
+                other => other,
+            };
+            let content: String = m.content.chars().take(MAX_MESSAGE_CHARS).collect();
+            format!("{}: {}", role, content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+pub fn title_prompt(messages: &[Message]) -> Vec<ProviderMessage> {
+    vec![
+        ProviderMessage {
+            role: "system".to_string(),
+            content: "You name conversations. Reply with a short, specific title of at most \
+                      six words. No quotes, no punctuation at the end, nothing else."
+                .to_string(),
+            ..Default::default()
+        },
+        ProviderMessage {
+            role: "user".to_string(),
+            content: transcript(messages),
+            ..Default::default()
+        },
+    ]
+}
+
+pub fn summary_prompt(previous: Option<&str>, messages: &[Message]) -> Vec<ProviderMessage> {
+    let mut content = String::new();
+    if let Some(previous) = previous {
+        content.push_str(&format!("Summary so far:\n{}\n\n", previous));
+    }
+    content.push_str(&format!("New messages:\n{}", transcript(messages)));
+
+    vec![
+        ProviderMessage {
+            role: "system".to_string(),
+            content: "You maintain a running summary of a conversation. Combine the summary \
+                      so far (if any) with the new messages into one concise summary of at \
+                      most 150 words. Keep decisions, facts and open questions. Reply with \
+                      the summary only."
+                .to_string(),
+            ..Default::default()
+        },
+        ProviderMessage {
+            role: "user".to_string(),
+            content,
+            ..Default::default()
+        },
+    ]
+}
+
+/// One titling/summary pass over a conversation using `generate` for model
+/// calls. `force` rebuilds the summary from the whole active path and `retitle`
+/// replaces the title even if the user already chose one. Returns whether the
+/// conversation changed.
+pub fn refresh<F>(
+    db: &Database,
+    conversation_id: &str,
+    settings: &SummarizerSettings,
+    force: bool,
+    retitle: bool,
+    generate: F,
+) -> AppResult<bool>
+where
+    F: Fn(Vec<ProviderMessage>) -> AppResult<String>,
+{
+    // Don't hold the connection lock across model calls
+    let (conversation, path) = {
+        let conn = db.conn().lock()?;
+        let conversation = Conversation::get_by_id(&conn, conversation_id)?
+            .ok_or_else(|| AppError::not_found("Conversation not found"))?;
+        let path = Message::get_by_conversation(&conn, conversation_id)?;
+        (conversation, path)
+    };
+    let mut changed = false;
+
+    let first_reply = path.iter().position(|m| m.role == "assistant");
+    let has_exchange = first_reply
+        .map(|i| path[..i].iter().any(|m| m.role == "user"))
+        .unwrap_or(false);
+    if has_exchange
+        && (retitle || (settings.auto_title && is_placeholder_title(&conversation.title)))
+    {
+        let first_exchange = &path[..=first_reply.unwrap_or(0)];
+        if let Some(title) = clean_title(&generate(title_prompt(first_exchange))?) {
+            let conn = db.conn().lock()?;
+            Conversation::update_title(&conn, conversation_id, &title)?;
+            changed = true;
+        }
+    }
+
+    if force || settings.auto_summary {
+        // Continue from the last summarized message if it is still on the active path
+        let covered = if force {
+            None
+        } else {
+            conversation
+                .summary_message_id
+                .as_ref()
+                .and_then(|id| path.iter().position(|m| &m.id == id))
+        };
+        let previous = covered.and(conversation.summary.as_deref());
+        let pending = &path[covered.map_or(0, |i| i + 1)..];
+
+        if !pending.is_empty() && (force || pending.len() >= settings.summary_interval) {
+            let summary = generate(summary_prompt(previous, pending))?;
+            let summary = summary.trim();
+            if !summary.is_empty() {
+                let conn = db.conn().lock()?;
+                Conversation::set_summary(
+                    &conn,
+                    conversation_id,
+                    Some(summary),
+                    pending.last().map(|m| m.id.as_str()),
+                )?;
+                changed = true;
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Run a pass with the configured summarizer model
+pub fn run(app: &AppHandle, conversation_id: &str, force: bool, retitle: bool) -> AppResult<bool> {
+    let db = app
+        .try_state::<Database>()
+        .ok_or_else(|| AppError::internal("Database not initialized"))?;
+    let settings = {
+        let conn = db.conn().lock()?;
+        SummarizerSettings::load(&conn)
+    };
+
+    refresh(
+        &db,
+        conversation_id,
+        &settings,
+        force,
+        retitle,
+        |messages| {
+            // No conversation id, so summarizer prompts don't pick up project context
+            generate_with_provider(
+                app.clone(),
+                &settings.provider,
+                String::new(),
+                messages,
+                Some(settings.model.clone()),
+            )
+            .map_err(AppError::from)
+        },
+    )
+}
+
+fn in_flight() -> &'static Mutex<HashSet<String>> {
+    static IN_FLIGHT: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    IN_FLIGHT.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Queue a background pass after a new assistant message. At most one pass per
+/// conversation runs at a time; `conversation://updated` is emitted on change.
+pub fn schedule(app: &AppHandle, conversation_id: &str) {
+    match in_flight().lock() {
+        Ok(mut running) => {
+            if !running.insert(conversation_id.to_string()) {
+                return;
+            }
+        }
+        Err(_) => return,
+    }
+
+    let app = app.clone();
+    let conversation_id = conversation_id.to_string();
+    std::thread::spawn(move || {
+        let result = run(&app, &conversation_id, false, false);
+        if let Ok(mut running) = in_flight().lock() {
+            running.remove(&conversation_id);
+        }
+        match result {
+            Ok(true) => {
+                let _ = app.emit("conversation://updated", &conversation_id);
+            }
+            Ok(false) => {}
+            Err(e) => eprintln!("summarizer failed for {}: {}", conversation_id, e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::conversations::NewConversation;
+    use crate::database::messages::NewMessage;
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+
+    #[test]
+    fn clean_title_strips_decoration() {
+        assert_eq!(
+            clean_title("\n\"Rust borrow checker tips.\"\nextra").as_deref(),
+            Some("Rust borrow checker tips")
+        );
+        assert_eq!(
+            clean_title("Title: **Deploying Tauri**").as_deref(),
+            Some("Deploying Tauri")
+        );
+        assert_eq!(clean_title("  \n "), None);
+    }
+
+    #[test]
+    fn refresh_titles_placeholder_and_rolls_summary() {
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conv_id = {
+            let conn = db.conn().lock().unwrap();
+            let conv = Conversation::create(
+                &conn,
+                NewConversation {
+                    title: "New conversation".to_string(),
+                    model: "gpt-test".to_string(),
+                    provider: "local".to_string(),
+                    system_prompt: None,
+                },
+            )
+            .unwrap();
+            for (role, content) in [("user", "How do I tune SQLite?"), ("assistant", "Use WAL.")] {
+                Message::create(
+                    &conn,
+                    NewMessage {
+                        conversation_id: conv.id.clone(),
+                        role: role.to_string(),
+                        content: content.to_string(),
+                        tokens_used: None,
+                    },
+                )
+                .unwrap();
+            }
+            conv.id
+        };
+
+        let settings = SummarizerSettings {
+            summary_interval: 2,
+            ..Default::default()
+        };
+        let prompts = RefCell::new(Vec::new());
+        let generate = |messages: Vec<ProviderMessage>| {
+            let system = messages[0].content.clone();
+            prompts.borrow_mut().push(messages[1].content.clone());
+            Ok(if system.contains("name conversations") {
+                "\"SQLite tuning\"".to_string()
+            } else {
+                "User asked about SQLite; WAL suggested.".to_string()
+            })
+        };
+
+        assert!(refresh(&db, &conv_id, &settings, false, false, generate).unwrap());
+        let conn = db.conn().lock().unwrap();
+        let conv = Conversation::get_by_id(&conn, &conv_id).unwrap().unwrap();
+        assert_eq!(conv.title, "SQLite tuning");
+        assert_eq!(
+            conv.summary.as_deref(),
+            Some("User asked about SQLite; WAL suggested.")
+        );
+        drop(conn);
+
+        // Nothing new since the summary and the title is no longer a placeholder
+        assert!(!refresh(&db, &conv_id, &settings, false, false, generate).unwrap());
+        assert_eq!(prompts.borrow().len(), 2);
+    }
+}
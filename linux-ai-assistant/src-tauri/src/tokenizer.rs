@@ -0,0 +1,153 @@
+// Token counting and model context limits. OpenAI models are counted with the
+// matching tiktoken BPE; other providers use cl100k_base scaled by a per-provider
+// factor, which tracks their tokenizers closely enough for budgeting.
+
+use crate::commands::provider::ProviderMessage;
+use crate::database::settings::Setting;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+
+/// Tokens OpenAI adds around every chat message (role and separators)
+const TOKENS_PER_MESSAGE: usize = 3;
+/// Tokens that prime the assistant reply
+const TOKENS_PER_REPLY: usize = 3;
+/// Rough cost of one inline image; providers bill images by size, this is a
+/// mid-sized estimate so trimming leaves room for them
+const TOKENS_PER_IMAGE: usize = 800;
+/// Limit used for models we know nothing about
+pub const DEFAULT_CONTEXT_LIMIT: usize = 8192;
+
+fn is_openai(provider: &str) -> bool {
+    provider == "openai"
+}
+
+/// Over-estimate for non-OpenAI tokenizers so trimmed prompts still fit
+fn approximation_factor(provider: &str) -> f64 {
+    match provider {
+        "anthropic" => 1.15,
+        "gemini" => 1.05,
+        _ => 1.10,
+    }
+}
+
+fn bpe_count(tokenizer: Tokenizer, text: &str) -> usize {
+    let bpe = match tokenizer {
+        Tokenizer::O200kBase => tiktoken_rs::o200k_base_singleton(),
+        Tokenizer::P50kBase => tiktoken_rs::p50k_base_singleton(),
+        Tokenizer::P50kEdit => tiktoken_rs::p50k_edit_singleton(),
+        Tokenizer::R50kBase | Tokenizer::Gpt2 => tiktoken_rs::r50k_base_singleton(),
+        Tokenizer::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+    };
+    let bpe = bpe.lock();
+    bpe.encode_with_special_tokens(text).len()
+}
+
+/// Number of tokens `text` uses for the given provider and model
+pub fn count_tokens(provider: &str, model: &str, text: &str) -> usize {
+    if text.is_empty() {
+        return 0;
+    }
+    if is_openai(provider) {
+        let tokenizer = get_tokenizer(model).unwrap_or(Tokenizer::Cl100kBase);
+        return bpe_count(tokenizer, text);
+    }
+    let approx = bpe_count(Tokenizer::Cl100kBase, text) as f64 * approximation_factor(provider);
+    approx.ceil() as usize
+}
+
+/// Tokens one chat message uses, including role and separator overhead
+pub fn message_tokens(provider: &str, model: &str, message: &ProviderMessage) -> usize {
+    TOKENS_PER_MESSAGE
+        + count_tokens(provider, model, &message.content)
+        + message.images.len() * TOKENS_PER_IMAGE
+}
+
+/// Tokens a chat request with these messages uses, including reply priming
+pub fn count_message_tokens(provider: &str, model: &str, messages: &[ProviderMessage]) -> usize {
+    messages
+        .iter()
+        .map(|m| message_tokens(provider, model, m))
+        .sum::<usize>()
+        + TOKENS_PER_REPLY
+}
+
+/// Built-in context window sizes, matched by model name prefix
+pub fn default_context_limit(provider: &str, model: &str) -> usize {
+    const LIMITS: &[(&str, usize)] = &[
+        ("gpt-4o", 128_000),
+        ("gpt-4.1", 1_047_576),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-4-32k", 32_768),
+        ("gpt-4", 8_192),
+        ("gpt-3.5-turbo", 16_385),
+        ("o1", 200_000),
+        ("o3", 200_000),
+        ("o4", 200_000),
+        ("claude", 200_000),
+        ("gemini-1.5-pro", 2_097_152),
+        ("gemini-1.5-flash", 1_048_576),
+        ("gemini-2", 1_048_576),
+        ("gemini-pro", 32_760),
+        ("llama3.1", 131_072),
+        ("llama3.2", 131_072),
+        ("llama3", 8_192),
+        ("mistral", 32_768),
+        ("qwen2.5", 32_768),
+    ];
+
+    if let Some((_, limit)) = LIMITS.iter().find(|(prefix, _)| model.starts_with(prefix)) {
+        return *limit;
+    }
+    match provider {
+        "openai" => tiktoken_rs::model::get_context_size(model),
+        "anthropic" => 200_000,
+        "gemini" => 1_048_576,
+        _ => DEFAULT_CONTEXT_LIMIT,
+    }
+}
+
+/// Context limit for a model, honouring per-model overrides stored in the
+/// `model_context_limits` setting (a JSON object of model name to tokens)
+pub fn context_limit(conn: &Connection, provider: &str, model: &str) -> usize {
+    Setting::get_json::<HashMap<String, usize>>(conn, "model_context_limits")
+        .ok()
+        .flatten()
+        .and_then(|overrides| overrides.get(model).copied())
+        .unwrap_or_else(|| default_context_limit(provider, model))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openai_counts_match_tiktoken() {
+        assert_eq!(count_tokens("openai", "gpt-4", "hello world"), 2);
+        assert_eq!(count_tokens("openai", "gpt-4o", ""), 0);
+        // Unknown OpenAI model names fall back to cl100k_base
+        assert_eq!(count_tokens("openai", "my-finetune", "hello world"), 2);
+    }
+
+    #[test]
+    fn other_providers_over_estimate() {
+        let text = "The quick brown fox jumps over the lazy dog";
+        let openai = count_tokens("openai", "gpt-4", text);
+        assert!(count_tokens("anthropic", "claude-3-5-sonnet", text) > openai);
+        assert!(count_tokens("ollama", "llama3.2", text) > openai);
+    }
+
+    #[test]
+    fn context_limits_by_prefix() {
+        assert_eq!(default_context_limit("openai", "gpt-4o-mini"), 128_000);
+        assert_eq!(default_context_limit("openai", "gpt-4-0613"), 8_192);
+        assert_eq!(
+            default_context_limit("anthropic", "claude-3-haiku-20240307"),
+            200_000
+        );
+        assert_eq!(
+            default_context_limit("ollama", "some-local-model"),
+            DEFAULT_CONTEXT_LIMIT
+        );
+    }
+}
@@ -0,0 +1,978 @@
+//! Verified self-update for the AppImage build.
+//!
+//! Each release publishes `SHA256SUMS` (the `sha256sum` output for its assets)
+//! and `SHA256SUMS.minisig`, a minisign signature of that manifest made with the
+//! release key. The signature's trusted comment carries `version:x.y.z`, so a
+//! mirror can't pass off an older signed release as a newer one. An update is
+//! only used when the signature checks out against the pinned public key, the
+//! signed version is the release's version, and the downloaded AppImage hashes
+//! to the manifest's entry for it.
+//!
+//! Installing renames the verified file over the running AppImage (`$APPIMAGE`)
+//! so the swap is atomic, after hard-linking the old one to `<name>.bak`. If the
+//! installed file doesn't check out the backup is renamed back.
+//!
+//! Which release counts as an update is decided by semver and the channel
+//! chosen in settings: stable takes plain releases, beta adds `-beta`/`-rc`
+//! and GitHub prereleases, nightly adds `-nightly`/`-alpha`/`-dev`. A release
+//! whose signature carries `minimum-version:x.y.z` is critical for anything
+//! older than that and is offered even when skipped.
+
+use crate::database::settings::Setting;
+use crate::error::{AppError, AppResult};
+use minisign_verify::{PublicKey, Signature};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+pub const RELEASES_API: &str =
+    "https://api.github.com/repos/tbmobb813/Linux-AI-Assistant---Project/releases";
+
+/// minisign public key file of the release signing key, whose secret half
+/// signs `SHA256SUMS` in the release workflow. CONTRIBUTING.md covers
+/// generating and rotating it.
+const UPDATE_PUBLIC_KEY_FILE: &str = include_str!("../keys/update-signing.pub");
+
+/// The release signing key (base64) from `keys/update-signing.pub`; empty
+/// until one is committed, and then updates can be found but not installed
+pub fn update_public_key() -> &'static str {
+    UPDATE_PUBLIC_KEY_FILE
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+        .unwrap_or_default()
+}
+
+/// Overrides the releases API URL, e.g. for a mirror set up by an administrator
+pub const BASE_URL_ENV: &str = "LAI_UPDATE_BASE_URL";
+
+const SETTINGS_KEY: &str = "update_settings";
+
+pub const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+pub const SIGNATURE_ASSET: &str = "SHA256SUMS.minisig";
+
+/// ELF files carry `AI\x02` at offset 8 when they are type 2 AppImages
+const APPIMAGE_MAGIC: &[u8] = b"\x7fELF\x02\x01\x01\x00AI\x02";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+/// A release as the GitHub releases API describes it
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+}
+
+impl Release {
+    /// Version from the tag, e.g. `v0.2.0` -> `0.2.0`
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    pub fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+
+    pub fn appimage(&self) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|a| a.name.ends_with(".AppImage"))
+    }
+
+    pub fn semver(&self) -> Option<Version> {
+        Version::parse(self.version()).ok()
+    }
+
+    pub fn channel(&self) -> Option<UpdateChannel> {
+        let version = self.semver()?;
+        let pre = version.pre.as_str();
+        Some(
+            if ["nightly", "alpha", "dev"]
+                .iter()
+                .any(|p| pre.starts_with(p))
+            {
+                UpdateChannel::Nightly
+            } else if !pre.is_empty() || self.prerelease {
+                UpdateChannel::Beta
+            } else {
+                UpdateChannel::Stable
+            },
+        )
+    }
+}
+
+/// Release channels, each including the ones before it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    Beta,
+    Nightly,
+}
+
+/// The newest non-draft release on `channel` that is newer than `current`
+pub fn select_update<'a>(
+    releases: &'a [Release],
+    current: &Version,
+    channel: UpdateChannel,
+) -> Option<&'a Release> {
+    releases
+        .iter()
+        .filter(|r| !r.draft && r.channel().is_some_and(|c| c <= channel))
+        .filter_map(|r| Some((r.semver()?, r)))
+        .filter(|(version, _)| version > current)
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateSettings {
+    pub channel: UpdateChannel,
+    /// Hours between automatic checks; 0 turns them off
+    pub check_interval_hours: u32,
+    /// Version the user chose to skip; critical updates are offered anyway
+    pub skipped_version: Option<String>,
+    /// Releases API of a mirror to use instead of GitHub
+    pub base_url: Option<String>,
+    /// Unix time of the last check
+    pub last_checked: Option<i64>,
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            channel: UpdateChannel::Stable,
+            check_interval_hours: 24,
+            skipped_version: None,
+            base_url: None,
+            last_checked: None,
+        }
+    }
+}
+
+impl UpdateSettings {
+    pub fn load(conn: &rusqlite::Connection) -> AppResult<Self> {
+        Ok(Setting::get_json(conn, SETTINGS_KEY)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, conn: &rusqlite::Connection) -> AppResult<()> {
+        Ok(Setting::set_json(conn, SETTINGS_KEY, self)?)
+    }
+
+    /// `$LAI_UPDATE_BASE_URL`, then the configured mirror, then GitHub
+    pub fn api_base(&self) -> String {
+        std::env::var(BASE_URL_ENV)
+            .ok()
+            .filter(|url| !url.trim().is_empty())
+            .or_else(|| self.base_url.clone().filter(|url| !url.trim().is_empty()))
+            .unwrap_or_else(|| RELEASES_API.to_string())
+    }
+
+    /// Whether an automatic check is due at unix time `now`
+    pub fn is_due(&self, now: i64) -> bool {
+        self.check_interval_hours > 0
+            && self.last_checked.map_or(true, |last| {
+                now - last >= i64::from(self.check_interval_hours) * 3600
+            })
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        if let Some(url) = self.base_url.as_deref().filter(|u| !u.trim().is_empty()) {
+            let parsed = reqwest::Url::parse(url)
+                .map_err(|e| AppError::invalid_input(format!("Invalid update URL: {}", e)))?;
+            if !matches!(parsed.scheme(), "http" | "https") {
+                return Err(AppError::invalid_input("Update URL must be http or https"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parsed `SHA256SUMS`: asset name to lowercase hex digest
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChecksumManifest {
+    entries: HashMap<String, String>,
+}
+
+impl ChecksumManifest {
+    /// Parse `sha256sum` output: `<hex>  <name>`, or `<hex> *<name>` in binary mode
+    pub fn parse(text: &str) -> AppResult<Self> {
+        let mut entries = HashMap::new();
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (digest, name) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| AppError::invalid_input(format!("Bad checksum line: {}", line)))?;
+            let name = name.trim_start().trim_start_matches('*');
+            if digest.len() != 64
+                || !digest.chars().all(|c| c.is_ascii_hexdigit())
+                || name.is_empty()
+            {
+                return Err(AppError::invalid_input(format!(
+                    "Bad checksum line: {}",
+                    line
+                )));
+            }
+            entries.insert(name.to_string(), digest.to_ascii_lowercase());
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(String::as_str)
+    }
+
+    /// Name and digest of the AppImage, when exactly one is listed
+    pub fn appimage(&self) -> Option<(&str, &str)> {
+        let mut appimages = self
+            .entries
+            .iter()
+            .filter(|(name, _)| name.ends_with(".AppImage"));
+        match (appimages.next(), appimages.next()) {
+            (Some((name, digest)), None) => Some((name, digest)),
+            _ => None,
+        }
+    }
+}
+
+/// Check a minisign `signature` of `data` against `public_key` (base64) and
+/// return its trusted comment, which the signature covers too. Only
+/// prehashed signatures, the minisign default, are accepted.
+pub fn verify_signature(data: &[u8], signature: &str, public_key: &str) -> AppResult<String> {
+    if public_key.is_empty() {
+        return Err(AppError::unsupported(
+            "This build has no release signing key, so updates can't be installed",
+        ));
+    }
+    let public_key = PublicKey::from_base64(public_key)
+        .map_err(|e| AppError::internal(format!("Invalid update public key: {}", e)))?;
+    let signature = Signature::decode(signature)
+        .map_err(|e| AppError::auth(format!("Malformed update signature: {}", e)))?;
+    public_key
+        .verify(data, &signature, false)
+        .map_err(|e| AppError::auth(format!("Update signature check failed: {}", e)))?;
+    Ok(signature.trusted_comment().to_string())
+}
+
+/// `key:value` fields of a trusted comment, which minisign separates with tabs
+fn signed_field<'a>(trusted_comment: &'a str, key: &str) -> Option<&'a str> {
+    trusted_comment.split('\t').find_map(|field| {
+        let (k, value) = field.split_once(':')?;
+        (k.trim() == key).then(|| value.trim())
+    })
+}
+
+/// What a release signature vouches for
+#[derive(Debug, Clone)]
+pub struct SignedRelease {
+    /// From `version:` in the trusted comment
+    pub version: Version,
+    /// From `minimum-version:`: older versions must update
+    pub minimum_version: Option<Version>,
+    pub checksums: ChecksumManifest,
+}
+
+impl SignedRelease {
+    fn parse(trusted_comment: &str, manifest: &[u8]) -> AppResult<Self> {
+        let version = |key: &str| {
+            signed_field(trusted_comment, key)
+                .map(|v| {
+                    Version::parse(v.trim_start_matches('v'))
+                        .map_err(|e| AppError::auth(format!("Bad signed {}: {}", key, e)))
+                })
+                .transpose()
+        };
+        let text = std::str::from_utf8(manifest)
+            .map_err(|_| AppError::invalid_input("Checksum manifest is not UTF-8"))?;
+        Ok(Self {
+            version: version("version")?
+                .ok_or_else(|| AppError::auth("The update signature doesn't name a version"))?,
+            minimum_version: version("minimum-version")?,
+            checksums: ChecksumManifest::parse(text)?,
+        })
+    }
+
+    pub fn is_critical_for(&self, current: &Version) -> bool {
+        self.minimum_version
+            .as_ref()
+            .is_some_and(|min| current < min)
+    }
+}
+
+fn sha256_file(path: &Path) -> AppResult<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn is_appimage(path: &Path) -> AppResult<bool> {
+    let mut head = [0u8; APPIMAGE_MAGIC.len()];
+    let mut file = fs::File::open(path)?;
+    Ok(file.read_exact(&mut head).is_ok() && head == APPIMAGE_MAGIC)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> AppResult<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .map_err(|e| AppError::from(e).context("Failed to make executable"))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> AppResult<()> {
+    Ok(())
+}
+
+/// An AppImage whose checksum matched the signed manifest
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifiedDownload {
+    pub version: String,
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+/// Fetches releases from a GitHub-style releases API
+#[derive(Debug, Clone)]
+pub struct UpdateClient {
+    http: reqwest::Client,
+    api_base: String,
+    public_key: String,
+}
+
+impl UpdateClient {
+    pub fn new(api_base: impl Into<String>, public_key: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_base: api_base.into().trim_end_matches('/').to_string(),
+            public_key: public_key.into(),
+        }
+    }
+
+    async fn get(&self, url: &str) -> AppResult<reqwest::Response> {
+        let response = self
+            .http
+            .get(url)
+            .header("User-Agent", "linux-ai-assistant")
+            .send()
+            .await
+            .map_err(|e| AppError::from(e).context(format!("Failed to fetch {}", url)))?;
+        if !response.status().is_success() {
+            return Err(AppError::io(format!(
+                "Fetching {} failed with HTTP {}",
+                url,
+                response.status()
+            )));
+        }
+        Ok(response)
+    }
+
+    async fn get_release(&self, url: &str) -> AppResult<Release> {
+        self.get(url)
+            .await?
+            .json()
+            .await
+            .map_err(|e| AppError::from(e).context("Failed to parse release info"))
+    }
+
+    pub async fn latest(&self) -> AppResult<Release> {
+        self.get_release(&format!("{}/latest", self.api_base)).await
+    }
+
+    /// Recent releases, including prereleases
+    pub async fn releases(&self) -> AppResult<Vec<Release>> {
+        self.get(&self.api_base)
+            .await?
+            .json()
+            .await
+            .map_err(|e| AppError::from(e).context("Failed to parse release list"))
+    }
+
+    pub async fn release(&self, version: &str) -> AppResult<Release> {
+        self.get_release(&format!(
+            "{}/tags/v{}",
+            self.api_base,
+            version.trim_start_matches('v')
+        ))
+        .await
+    }
+
+    /// The release's `SHA256SUMS` and signed version, after checking the
+    /// signature and that it was made for this release's version
+    pub async fn verified_release(&self, release: &Release) -> AppResult<SignedRelease> {
+        let asset = |name: &str| {
+            release.asset(name).ok_or_else(|| {
+                AppError::not_found(format!("Release {} has no {}", release.tag_name, name))
+            })
+        };
+        let manifest_url = &asset(CHECKSUMS_ASSET)?.browser_download_url;
+        let signature_url = &asset(SIGNATURE_ASSET)?.browser_download_url;
+
+        let manifest = self.get(manifest_url).await?.bytes().await?;
+        let signature = self.get(signature_url).await?.text().await?;
+        let trusted_comment = verify_signature(&manifest, &signature, &self.public_key)?;
+        let signed = SignedRelease::parse(&trusted_comment, &manifest)?;
+        if release.semver().as_ref() != Some(&signed.version) {
+            return Err(AppError::auth(format!(
+                "Release {} is signed as version {}",
+                release.tag_name, signed.version
+            )));
+        }
+        Ok(signed)
+    }
+
+    /// Download the AppImage listed in the release's signed manifest into
+    /// `dir`, keeping it only if its SHA-256 matches
+    pub async fn download_verified(
+        &self,
+        release: &Release,
+        dir: &Path,
+    ) -> AppResult<VerifiedDownload> {
+        let signed = self.verified_release(release).await?;
+        let (name, expected) = signed
+            .checksums
+            .appimage()
+            .ok_or_else(|| AppError::not_found(format!("{} lists no AppImage", CHECKSUMS_ASSET)))?;
+        let appimage = release
+            .asset(name)
+            .ok_or_else(|| AppError::not_found(format!("Release has no asset {}", name)))?;
+        let version = signed.version.to_string();
+
+        fs::create_dir_all(dir)
+            .map_err(|e| AppError::from(e).context("Failed to create download directory"))?;
+        let path = dir.join(format!("linux-ai-assistant-{}.AppImage", version));
+        let partial = dir.join(format!(".linux-ai-assistant-{}.part", version));
+
+        let result = self
+            .download_to(&appimage.browser_download_url, &partial)
+            .await;
+        let sha256 = match result {
+            Ok(sha256) if sha256 == expected => sha256,
+            Ok(sha256) => {
+                let _ = fs::remove_file(&partial);
+                return Err(AppError::auth(format!(
+                    "Checksum mismatch for {}: expected {}, got {}",
+                    appimage.name, expected, sha256
+                )));
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&partial);
+                return Err(e);
+            }
+        };
+        make_executable(&partial)?;
+        fs::rename(&partial, &path)
+            .map_err(|e| AppError::from(e).context("Failed to move download into place"))?;
+
+        Ok(VerifiedDownload {
+            version,
+            path,
+            sha256,
+        })
+    }
+
+    /// Stream `url` to `path`, returning the SHA-256 of what was written
+    async fn download_to(&self, url: &str, path: &Path) -> AppResult<String> {
+        let mut response = self.get(url).await?;
+        let mut file = fs::File::create(path)
+            .map_err(|e| AppError::from(e).context("Failed to create download file"))?;
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AppError::from(e).context("Failed to read download content"))?
+        {
+            hasher.update(&chunk);
+            file.write_all(&chunk)
+                .map_err(|e| AppError::from(e).context("Failed to write download file"))?;
+        }
+        file.sync_all()?;
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+}
+
+/// The running AppImage, when the app was started from one
+pub fn current_appimage() -> Option<PathBuf> {
+    std::env::var_os("APPIMAGE")
+        .map(PathBuf::from)
+        .filter(|p| p.is_file())
+}
+
+pub fn backup_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    target.with_file_name(name)
+}
+
+/// Where an install ended up
+#[derive(Debug, Clone, Serialize)]
+pub struct Installed {
+    pub target: PathBuf,
+    pub backup: PathBuf,
+}
+
+/// Replace `target` with the verified download. The download must be on the
+/// same filesystem as `target` for the rename to be atomic.
+pub fn install(download: &VerifiedDownload, target: &Path) -> AppResult<Installed> {
+    install_checked(download, target, |path| {
+        if !is_appimage(path)? {
+            return Err(AppError::invalid_input("Installed file is not an AppImage"));
+        }
+        if sha256_file(path)? != download.sha256 {
+            return Err(AppError::io("Installed file does not match the download"));
+        }
+        Ok(())
+    })
+}
+
+fn install_checked(
+    download: &VerifiedDownload,
+    target: &Path,
+    check: impl Fn(&Path) -> AppResult<()>,
+) -> AppResult<Installed> {
+    let backup = backup_path(target);
+    let _ = fs::remove_file(&backup);
+    if fs::hard_link(target, &backup).is_err() {
+        fs::copy(target, &backup)
+            .map_err(|e| AppError::from(e).context("Failed to back up the current AppImage"))?;
+    }
+
+    fs::rename(&download.path, target)
+        .map_err(|e| AppError::from(e).context("Failed to replace the current AppImage"))?;
+
+    if let Err(e) = check(target) {
+        fs::rename(&backup, target).map_err(|re| {
+            AppError::from(re).context(format!("Rollback after a failed install ({}) failed", e))
+        })?;
+        return Err(e.context("Install rolled back"));
+    }
+    Ok(Installed {
+        target: target.to_path_buf(),
+        backup,
+    })
+}
+
+/// Put the AppImage that the last install replaced back in place
+pub fn rollback(target: &Path) -> AppResult<()> {
+    let backup = backup_path(target);
+    if !backup.is_file() {
+        return Err(AppError::not_found("No previous version to roll back to"));
+    }
+    fs::rename(&backup, target)
+        .map_err(|e| AppError::from(e).context("Failed to restore the previous AppImage"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::attachments::hex_digest;
+    use base64::Engine;
+    use blake2::Blake2b512;
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::sync::Arc;
+
+    const KEY_ID: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    pub(crate) fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7u8; 32])
+    }
+
+    /// Base64 minisign public key for `signing_key`
+    pub(crate) fn public_key() -> String {
+        let mut bytes = b"Ed".to_vec();
+        bytes.extend_from_slice(&KEY_ID);
+        bytes.extend_from_slice(signing_key().verifying_key().as_bytes());
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    /// The trusted comment the release workflow signs for `version`
+    pub(crate) fn trusted_comment(version: &str) -> String {
+        format!("timestamp:0\tfile:SHA256SUMS\tversion:{}", version)
+    }
+
+    /// `trusted_comment` for a release critical below `minimum`
+    pub(crate) fn critical_comment(version: &str, minimum: &str) -> String {
+        format!("{}\tminimum-version:{}", trusted_comment(version), minimum)
+    }
+
+    /// A prehashed minisign signature of `data` with `trusted` as its trusted
+    /// comment, as `minisign -S -t` writes it
+    pub(crate) fn sign(key: &SigningKey, data: &[u8], trusted: &str) -> String {
+        let b64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+        let signature = key.sign(&Blake2b512::digest(data)).to_bytes();
+        let mut line = b"ED".to_vec();
+        line.extend_from_slice(&KEY_ID);
+        line.extend_from_slice(&signature);
+        let mut global = signature.to_vec();
+        global.extend_from_slice(trusted.as_bytes());
+        format!(
+            "untrusted comment: signature from minisign secret key\n{}\ntrusted comment: {}\n{}\n",
+            b64(&line),
+            trusted,
+            b64(&key.sign(&global).to_bytes())
+        )
+    }
+
+    pub(crate) fn fake_appimage(tag: &str) -> Vec<u8> {
+        let mut bytes = APPIMAGE_MAGIC.to_vec();
+        bytes.extend_from_slice(tag.as_bytes());
+        bytes
+    }
+
+    /// Serves fixed bodies by path on 127.0.0.1 until dropped
+    pub(crate) struct ReleaseServer {
+        server: Arc<tiny_http::Server>,
+        pub base: String,
+    }
+
+    impl ReleaseServer {
+        /// Serve `routes_for(base)`; routes embed the base URL, which isn't
+        /// known until the port is bound
+        pub(crate) fn start(routes_for: impl Fn(&str) -> HashMap<String, Vec<u8>>) -> Self {
+            let server = Arc::new(tiny_http::Server::http("127.0.0.1:0").unwrap());
+            let port = server.server_addr().to_ip().unwrap().port();
+            let base = format!("http://127.0.0.1:{}", port);
+            let routes = routes_for(&base);
+            let worker = server.clone();
+            std::thread::spawn(move || {
+                for request in worker.incoming_requests() {
+                    let response = match routes.get(request.url()) {
+                        Some(body) => tiny_http::Response::from_data(body.clone()),
+                        None => tiny_http::Response::from_data(Vec::new()).with_status_code(404),
+                    };
+                    let _ = request.respond(response);
+                }
+            });
+            Self { server, base }
+        }
+    }
+
+    impl Drop for ReleaseServer {
+        fn drop(&mut self) {
+            self.server.unblock();
+        }
+    }
+
+    /// Routes for a release `version` at `/releases`: the API JSON, the AppImage,
+    /// and a manifest signed for `version` with `key` listing `listed_sha` for it
+    pub(crate) fn release_routes(
+        base: &str,
+        version: &str,
+        appimage: &[u8],
+        listed_sha: &str,
+        key: &SigningKey,
+    ) -> HashMap<String, Vec<u8>> {
+        signed_release_routes(
+            base,
+            version,
+            &trusted_comment(version),
+            appimage,
+            listed_sha,
+            key,
+        )
+    }
+
+    /// `release_routes` with the manifest signed under `trusted`
+    pub(crate) fn signed_release_routes(
+        base: &str,
+        version: &str,
+        trusted: &str,
+        appimage: &[u8],
+        listed_sha: &str,
+        key: &SigningKey,
+    ) -> HashMap<String, Vec<u8>> {
+        let name = format!("linux-ai-assistant_{}_amd64.AppImage", version);
+        let manifest = format!("{}  {}\n{}  other.deb\n", listed_sha, name, "0".repeat(64));
+        let release = serde_json::json!({
+            "tag_name": format!("v{}", version),
+            "body": "Fixes",
+            "published_at": "2026-01-01T00:00:00Z",
+            "assets": [
+                { "name": name, "browser_download_url": format!("{}/dl/{}", base, name) },
+                { "name": CHECKSUMS_ASSET, "browser_download_url": format!("{}/dl/{}", base, CHECKSUMS_ASSET) },
+                { "name": SIGNATURE_ASSET, "browser_download_url": format!("{}/dl/{}", base, SIGNATURE_ASSET) },
+            ],
+        });
+        let list = serde_json::to_vec(&[&release]).unwrap();
+        let release = serde_json::to_vec(&release).unwrap();
+        HashMap::from([
+            ("/releases".to_string(), list),
+            ("/releases/latest".to_string(), release.clone()),
+            (format!("/releases/tags/v{}", version), release),
+            (format!("/dl/{}", name), appimage.to_vec()),
+            (
+                format!("/dl/{}", CHECKSUMS_ASSET),
+                manifest.clone().into_bytes(),
+            ),
+            (
+                format!("/dl/{}", SIGNATURE_ASSET),
+                sign(key, manifest.as_bytes(), trusted).into_bytes(),
+            ),
+        ])
+    }
+
+    fn client_for(server: &ReleaseServer) -> UpdateClient {
+        UpdateClient::new(format!("{}/releases", server.base), public_key())
+    }
+
+    pub(crate) fn release(tag: &str, body: &str, prerelease: bool) -> Release {
+        serde_json::from_value(serde_json::json!({
+            "tag_name": tag,
+            "body": body,
+            "prerelease": prerelease,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn channels_and_semver_pick_the_update() {
+        let releases = vec![
+            release("v0.9.0", "", false),
+            release("v0.10.0", "", false),
+            release("v0.11.0-rc.1", "", false),
+            release("v0.11.0-beta.2", "", true),
+            release("v0.12.0-nightly.20260101", "", true),
+            release("not-a-version", "", false),
+        ];
+        let current = Version::parse("0.9.5").unwrap();
+        let pick = |channel| select_update(&releases, &current, channel).map(Release::version);
+        assert_eq!(pick(UpdateChannel::Stable), Some("0.10.0"));
+        assert_eq!(pick(UpdateChannel::Beta), Some("0.11.0-rc.1"));
+        assert_eq!(
+            pick(UpdateChannel::Nightly),
+            Some("0.12.0-nightly.20260101")
+        );
+
+        let newest = Version::parse("0.12.0").unwrap();
+        assert!(select_update(&releases, &newest, UpdateChannel::Nightly).is_none());
+
+        let mut draft = release("v1.0.0", "", false);
+        draft.draft = true;
+        assert!(select_update(&[draft], &current, UpdateChannel::Stable).is_none());
+    }
+
+    #[test]
+    fn signed_minimum_version_marks_releases_critical() {
+        let manifest = format!("{}  app.AppImage\n", "a".repeat(64));
+        let critical =
+            SignedRelease::parse(&critical_comment("0.3.0", "v0.2.1"), manifest.as_bytes())
+                .unwrap();
+        assert_eq!(critical.minimum_version, Some(Version::new(0, 2, 1)));
+        assert!(critical.is_critical_for(&Version::new(0, 2, 0)));
+        assert!(!critical.is_critical_for(&Version::new(0, 2, 1)));
+
+        let plain = SignedRelease::parse(&trusted_comment("0.3.0"), manifest.as_bytes()).unwrap();
+        assert!(!plain.is_critical_for(&Version::new(0, 1, 0)));
+        let bad = critical_comment("0.3.0", "soon");
+        assert!(SignedRelease::parse(&bad, manifest.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn settings_schedule_checks_and_validate_mirrors() {
+        let settings = UpdateSettings::default();
+        assert!(settings.is_due(1_000_000));
+        let checked = UpdateSettings {
+            last_checked: Some(1_000_000),
+            ..UpdateSettings::default()
+        };
+        assert!(!checked.is_due(1_000_000 + 3600));
+        assert!(checked.is_due(1_000_000 + 24 * 3600));
+        let off = UpdateSettings {
+            check_interval_hours: 0,
+            ..UpdateSettings::default()
+        };
+        assert!(!off.is_due(i64::MAX));
+
+        let mirror = UpdateSettings {
+            base_url: Some("https://mirror.example.com/releases".to_string()),
+            ..UpdateSettings::default()
+        };
+        assert!(mirror.validate().is_ok());
+        let bad = UpdateSettings {
+            base_url: Some("ftp://mirror.example.com".to_string()),
+            ..UpdateSettings::default()
+        };
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn manifest_parses_sha256sum_output() {
+        let digest = "A".repeat(64);
+        let manifest =
+            ChecksumManifest::parse(&format!("{}  app.AppImage\n{} *bin.deb\n", digest, digest))
+                .unwrap();
+        assert_eq!(manifest.get("app.AppImage"), Some("a".repeat(64).as_str()));
+        assert_eq!(manifest.get("bin.deb"), Some("a".repeat(64).as_str()));
+        assert!(ChecksumManifest::parse("abc  short.AppImage").is_err());
+    }
+
+    #[test]
+    fn signatures_must_match_the_pinned_key() {
+        let data = b"manifest";
+        let signature = sign(&signing_key(), data, &trusted_comment("0.2.0"));
+        assert_eq!(
+            verify_signature(data, &signature, &public_key()).unwrap(),
+            trusted_comment("0.2.0")
+        );
+        assert!(verify_signature(b"tampered", &signature, &public_key()).is_err());
+        let edited = signature.replace("version:0.2.0", "version:9.9.9");
+        assert!(verify_signature(data, &edited, &public_key()).is_err());
+        let other = sign(&SigningKey::from_bytes(&[9u8; 32]), data, "");
+        assert!(verify_signature(data, &other, &public_key()).is_err());
+        assert!(verify_signature(data, &signature, update_public_key()).is_err());
+        assert!(verify_signature(data, &signature, "").is_err());
+    }
+
+    #[tokio::test]
+    async fn downloads_and_installs_a_verified_release() {
+        let appimage = fake_appimage("0.2.0");
+        let sha = hex_digest(&appimage);
+        let server = ReleaseServer::start(|base| {
+            release_routes(base, "0.2.0", &appimage, &sha, &signing_key())
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("linux-ai-assistant.AppImage");
+        fs::write(&target, fake_appimage("0.1.0")).unwrap();
+
+        let client = client_for(&server);
+        let release = client.latest().await.unwrap();
+        assert_eq!(release.version(), "0.2.0");
+        let download = client
+            .download_verified(&release, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(download.sha256, sha);
+
+        let installed = install(&download, &target).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), appimage);
+        assert_eq!(fs::read(&installed.backup).unwrap(), fake_appimage("0.1.0"));
+        assert!(!download.path.exists());
+
+        rollback(&target).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), fake_appimage("0.1.0"));
+        assert!(rollback(&target).is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_checksum_mismatches_and_bad_signatures() {
+        let appimage = fake_appimage("0.2.0");
+        let dir = tempfile::tempdir().unwrap();
+
+        let wrong_sha = hex_digest(b"something else");
+        let server = ReleaseServer::start(|base| {
+            release_routes(base, "0.2.0", &appimage, &wrong_sha, &signing_key())
+        });
+        let client = client_for(&server);
+        let release = client.release("0.2.0").await.unwrap();
+        let err = client
+            .download_verified(&release, dir.path())
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("Checksum mismatch"), "{}", err.message);
+
+        let sha = hex_digest(&appimage);
+        let forged = SigningKey::from_bytes(&[9u8; 32]);
+        let server =
+            ReleaseServer::start(|base| release_routes(base, "0.2.0", &appimage, &sha, &forged));
+        let client = client_for(&server);
+        let release = client.latest().await.unwrap();
+        let err = client
+            .download_verified(&release, dir.path())
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("signature"), "{}", err.message);
+
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn rejects_releases_signed_for_another_version() {
+        let appimage = fake_appimage("0.2.0");
+        let sha = hex_digest(&appimage);
+        let dir = tempfile::tempdir().unwrap();
+
+        // An old signed release served as a newer one
+        let server = ReleaseServer::start(|base| {
+            signed_release_routes(
+                base,
+                "0.3.0",
+                &trusted_comment("0.2.0"),
+                &appimage,
+                &sha,
+                &signing_key(),
+            )
+        });
+        let client = client_for(&server);
+        let release = client.latest().await.unwrap();
+        let err = client
+            .download_verified(&release, dir.path())
+            .await
+            .unwrap_err();
+        assert!(
+            err.message.contains("signed as version 0.2.0"),
+            "{}",
+            err.message
+        );
+
+        // A signature without a version
+        let server = ReleaseServer::start(|base| {
+            signed_release_routes(
+                base,
+                "0.2.0",
+                "timestamp:0\tfile:SHA256SUMS",
+                &appimage,
+                &sha,
+                &signing_key(),
+            )
+        });
+        let client = client_for(&server);
+        let release = client.latest().await.unwrap();
+        assert!(client
+            .download_verified(&release, dir.path())
+            .await
+            .is_err());
+
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn failed_install_check_rolls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("app.AppImage");
+        fs::write(&target, fake_appimage("old")).unwrap();
+        let staged = dir.path().join("new.AppImage");
+        fs::write(&staged, b"not an appimage").unwrap();
+        let download = VerifiedDownload {
+            version: "0.2.0".to_string(),
+            path: staged,
+            sha256: hex_digest(b"not an appimage"),
+        };
+
+        let err = install(&download, &target).unwrap_err();
+        assert!(err.message.contains("rolled back"), "{}", err.message);
+        assert_eq!(fs::read(&target).unwrap(), fake_appimage("old"));
+    }
+}
@@ -0,0 +1,426 @@
+// Spending reports and per-profile budgets. Costs come from the local pricing
+// table when a reply is stored; messages recorded before their model had a
+// price are costed at report time. All date boundaries are in UTC.
+
+use crate::database::profiles::Profile;
+use crate::database::usage::{usage_entries, ModelPrice, ProfileBudget, UsageEntry, UsageGroup};
+use crate::error::{AppError, AppResult, ErrorCode};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageRow {
+    pub key: String,
+    pub label: String,
+    pub messages: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost_usd: f64,
+    /// Messages whose model has no price and so add nothing to `cost_usd`
+    pub unpriced_messages: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub range: String,
+    pub group_by: UsageGroup,
+    /// Inclusive start, unix seconds
+    pub from: i64,
+    /// Exclusive end, unix seconds
+    pub to: i64,
+    pub rows: Vec<UsageRow>,
+    /// Totals over distinct messages (tag groups can count a message twice)
+    pub total: UsageRow,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetStatus {
+    pub profile_id: String,
+    pub profile_name: String,
+    pub period: String,
+    /// Start of the current budget period, unix seconds
+    pub period_start: i64,
+    pub spent_usd: f64,
+    /// Replies in the period whose model has no price, so their spending is
+    /// unknown and missing from `spent_usd`
+    pub unpriced_messages: i64,
+    pub soft_limit_usd: Option<f64>,
+    pub hard_limit_usd: Option<f64>,
+    pub soft_exceeded: bool,
+    pub hard_exceeded: bool,
+}
+
+/// Local providers cost nothing and are never blocked by budgets
+pub fn is_free_provider(provider: &str) -> bool {
+    provider == "ollama"
+}
+
+fn day_start(date: NaiveDate) -> i64 {
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .timestamp()
+}
+
+fn parse_date(value: &str) -> AppResult<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").map_err(|_| {
+        AppError::invalid_input(format!("Invalid date '{}', expected YYYY-MM-DD", value))
+    })
+}
+
+/// Resolve a range name to `[from, to)` in unix seconds. Accepts `today`,
+/// `week`, `month`, `year` (calendar periods so far), `all`, `Nd` for the last
+/// N days, a single `YYYY-MM-DD` day or an inclusive `YYYY-MM-DD..YYYY-MM-DD`.
+pub fn parse_range(range: &str, now: DateTime<Utc>) -> AppResult<(i64, i64)> {
+    let today = now.date_naive();
+    let until_now = now.timestamp() + 1;
+    let range = range.trim();
+
+    let from = match range {
+        "today" | "day" => day_start(today),
+        "week" => day_start(today - Duration::days(today.weekday().num_days_from_monday() as i64)),
+        "month" => day_start(today.with_day(1).unwrap_or(today)),
+        "year" => day_start(today.with_ordinal(1).unwrap_or(today)),
+        "all" => 0,
+        _ => {
+            if let Some((start, end)) = range.split_once("..") {
+                let (start, end) = (parse_date(start)?, parse_date(end)?);
+                if end < start {
+                    return Err(AppError::invalid_input(format!(
+                        "Range end {} is before its start {}",
+                        end, start
+                    )));
+                }
+                return Ok((day_start(start), day_start(end + Duration::days(1))));
+            }
+            if let Some(days) = range.strip_suffix('d').and_then(|n| n.parse::<i64>().ok()) {
+                if days <= 0 {
+                    return Err(AppError::invalid_input("Day count must be positive"));
+                }
+                now.timestamp() - days * 86_400
+            } else if let Ok(day) = parse_date(range) {
+                return Ok((day_start(day), day_start(day + Duration::days(1))));
+            } else {
+                return Err(AppError::invalid_input(format!(
+                    "Unknown range '{}' (use today, week, month, year, all, Nd or YYYY-MM-DD..YYYY-MM-DD)",
+                    range
+                )));
+            }
+        }
+    };
+    Ok((from, until_now))
+}
+
+/// Start of the budget period containing `now`
+pub fn period_start(period: &str, now: DateTime<Utc>) -> AppResult<i64> {
+    match period {
+        "day" | "week" | "month" => parse_range(period, now).map(|(from, _)| from),
+        other => Err(AppError::invalid_input(format!(
+            "Unknown budget period: {}",
+            other
+        ))),
+    }
+}
+
+/// Cost of an entry: the recorded cost, else the current price for its model
+fn entry_cost(
+    conn: &Connection,
+    prices: &mut HashMap<String, Option<ModelPrice>>,
+    entry: &UsageEntry,
+) -> AppResult<Option<f64>> {
+    if entry.cost_usd.is_some() {
+        return Ok(entry.cost_usd);
+    }
+    if entry.prompt_tokens == 0 && entry.completion_tokens == 0 {
+        return Ok(Some(0.0));
+    }
+    let price = match prices.get(&entry.model) {
+        Some(price) => price.clone(),
+        None => {
+            let price = if is_free_provider(&entry.provider) {
+                None
+            } else {
+                ModelPrice::find_for_model(conn, &entry.model)?
+            };
+            prices.insert(entry.model.clone(), price.clone());
+            price
+        }
+    };
+    Ok(match price {
+        Some(price) => Some(price.cost(entry.prompt_tokens, entry.completion_tokens)),
+        None if is_free_provider(&entry.provider) => Some(0.0),
+        None => None,
+    })
+}
+
+fn aggregate(conn: &Connection, entries: Vec<UsageEntry>) -> AppResult<Vec<UsageRow>> {
+    let mut prices = HashMap::new();
+    let mut rows: Vec<UsageRow> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for entry in entries {
+        let cost = entry_cost(conn, &mut prices, &entry)?;
+        let i = *index.entry(entry.key.clone()).or_insert_with(|| {
+            rows.push(UsageRow {
+                key: entry.key.clone(),
+                label: entry.label.clone(),
+                ..Default::default()
+            });
+            rows.len() - 1
+        });
+        let row = &mut rows[i];
+        row.messages += 1;
+        row.prompt_tokens += entry.prompt_tokens;
+        row.completion_tokens += entry.completion_tokens;
+        match cost {
+            Some(cost) => row.cost_usd += cost,
+            None => row.unpriced_messages += 1,
+        }
+    }
+    Ok(rows)
+}
+
+/// Spending over `range` grouped by `group_by` (`day`, `provider`, `model`,
+/// `profile` or `tag`). Days are listed in order; other groups by cost.
+pub fn usage_report(conn: &Connection, range: &str, group_by: &str) -> AppResult<UsageReport> {
+    let group = UsageGroup::parse(group_by).ok_or_else(|| {
+        AppError::invalid_input(format!(
+            "Unknown group '{}' (use day, provider, model, profile or tag)",
+            group_by
+        ))
+    })?;
+    let (from, to) = parse_range(range, Utc::now())?;
+
+    let entries = usage_entries(conn, from, to, Some(group))?;
+    let mut rows = aggregate(conn, entries)?;
+    if group == UsageGroup::Day {
+        rows.sort_by(|a, b| a.key.cmp(&b.key));
+    } else {
+        rows.sort_by(|a, b| {
+            b.cost_usd
+                .total_cmp(&a.cost_usd)
+                .then_with(|| a.label.cmp(&b.label))
+        });
+    }
+
+    let totals = usage_entries(conn, from, to, None)?;
+    let mut total = aggregate(conn, totals)?.pop().unwrap_or_default();
+    total.label = "Total".to_string();
+
+    Ok(UsageReport {
+        range: range.to_string(),
+        group_by: group,
+        from,
+        to,
+        rows,
+        total,
+    })
+}
+
+/// Budget and spending of a profile in its current period, if it has a budget
+pub fn budget_status(conn: &Connection, profile_id: &str) -> AppResult<Option<BudgetStatus>> {
+    let Some(budget) = ProfileBudget::get(conn, profile_id)? else {
+        return Ok(None);
+    };
+    let profile_name = Profile::get_by_id(conn, profile_id)?
+        .map(|p| p.name)
+        .unwrap_or_else(|| profile_id.to_string());
+    let start = period_start(&budget.period, Utc::now())?;
+    let entries = usage_entries(conn, start, i64::MAX, Some(UsageGroup::Profile))?
+        .into_iter()
+        .filter(|entry| entry.key == profile_id)
+        .collect();
+    let spent = aggregate(conn, entries)?.pop().unwrap_or_default();
+    let reached = |limit: Option<f64>| limit.is_some_and(|limit| spent.cost_usd >= limit);
+
+    Ok(Some(BudgetStatus {
+        profile_id: profile_id.to_string(),
+        profile_name,
+        period: budget.period,
+        period_start: start,
+        spent_usd: spent.cost_usd,
+        unpriced_messages: spent.unpriced_messages,
+        soft_limit_usd: budget.soft_limit_usd,
+        hard_limit_usd: budget.hard_limit_usd,
+        soft_exceeded: reached(budget.soft_limit_usd),
+        hard_exceeded: reached(budget.hard_limit_usd),
+    }))
+}
+
+/// Check the active profile's budget before a paid request to `model`.
+/// Returns an error once the hard limit is reached, or when the model has no
+/// price to count against it, and the status when only the soft limit is.
+pub fn check_budget(conn: &Connection, model: &str) -> AppResult<Option<BudgetStatus>> {
+    let Some(profile) = Profile::get_active(conn)? else {
+        return Ok(None);
+    };
+    match budget_status(conn, &profile.id)? {
+        Some(status) if status.hard_exceeded => Err(AppError::new(
+            ErrorCode::BudgetExceeded,
+            format!(
+                "Budget exceeded for profile '{}': spent ${:.2} of ${:.2} this {}",
+                status.profile_name,
+                status.spent_usd,
+                status.hard_limit_usd.unwrap_or_default(),
+                status.period
+            ),
+        )),
+        Some(status)
+            if status.hard_limit_usd.is_some()
+                && ModelPrice::find_for_model(conn, model)?.is_none() =>
+        {
+            Err(AppError::new(
+                ErrorCode::BudgetExceeded,
+                format!(
+                    "{} has no price, so its cost can't be counted against the budget of profile '{}'; add a price for it first",
+                    model, status.profile_name
+                ),
+            ))
+        }
+        Some(status) if status.soft_exceeded => Ok(Some(status)),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::conversations::{Conversation, NewConversation};
+    use crate::database::messages::{Message, NewMessage, UsageRecord};
+    use crate::database::Database;
+    use std::path::PathBuf;
+
+    #[test]
+    fn parses_ranges() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap();
+        let may_1 = Utc
+            .with_ymd_and_hms(2024, 5, 1, 0, 0, 0)
+            .unwrap()
+            .timestamp();
+        let may_13 = Utc
+            .with_ymd_and_hms(2024, 5, 13, 0, 0, 0)
+            .unwrap()
+            .timestamp();
+
+        assert_eq!(parse_range("month", now).unwrap().0, may_1);
+        // 2024-05-15 is a Wednesday
+        assert_eq!(parse_range("week", now).unwrap().0, may_13);
+        assert_eq!(
+            parse_range("7d", now).unwrap().0,
+            now.timestamp() - 7 * 86_400
+        );
+        assert_eq!(
+            parse_range("2024-05-01..2024-05-12", now).unwrap(),
+            (may_1, may_13)
+        );
+        assert!(parse_range("2024-05-12..2024-05-01", now).is_err());
+        assert!(parse_range("fortnight", now).is_err());
+    }
+
+    #[test]
+    fn prices_match_longest_prefix() {
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().unwrap();
+        let mini = ModelPrice::find_for_model(&conn, "gpt-4o-mini-2024-07-18")
+            .unwrap()
+            .unwrap();
+        assert_eq!(mini.model, "gpt-4o-mini");
+        assert!((mini.cost(1_000_000, 1_000_000) - 0.75).abs() < 1e-9);
+        assert!(ModelPrice::find_for_model(&conn, "llama3.2")
+            .unwrap()
+            .is_none());
+
+        // Neither case nor LIKE wildcards match
+        ModelPrice::upsert(&conn, "my_model", "openai", 1.0, 1.0).unwrap();
+        assert!(ModelPrice::find_for_model(&conn, "myXmodel")
+            .unwrap()
+            .is_none());
+        assert!(ModelPrice::find_for_model(&conn, "GPT-4O").unwrap().is_none());
+        ModelPrice::upsert(&conn, "%", "openai", 1.0, 1.0).unwrap();
+        assert!(ModelPrice::find_for_model(&conn, "llama3.2")
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            ModelPrice::find_for_model(&conn, "my_model-v2")
+                .unwrap()
+                .unwrap()
+                .model,
+            "my_model"
+        );
+    }
+
+    #[test]
+    fn report_groups_costs_and_budget_blocks_at_hard_limit() {
+        let db = Database::new(PathBuf::from(":memory:")).expect("db init");
+        let conn = db.conn().lock().unwrap();
+        let conv = Conversation::create(
+            &conn,
+            NewConversation {
+                title: "Costs".to_string(),
+                model: "gpt-4o".to_string(),
+                provider: "openai".to_string(),
+                system_prompt: None,
+            },
+        )
+        .unwrap();
+        let reply = |model: &str, cost_usd: Option<f64>| {
+            let message = Message::create(
+                &conn,
+                NewMessage {
+                    conversation_id: conv.id.clone(),
+                    role: "assistant".to_string(),
+                    content: "answer".to_string(),
+                    tokens_used: None,
+                },
+            )
+            .unwrap();
+            Message::record_usage(
+                &conn,
+                &message.id,
+                &UsageRecord {
+                    prompt_tokens: 1_000_000,
+                    completion_tokens: 0,
+                    provider: "openai".to_string(),
+                    model: model.to_string(),
+                    profile_id: Some("default".to_string()),
+                    cost_usd,
+                },
+            )
+            .unwrap();
+        };
+        reply("gpt-4o", Some(2.5));
+        reply("gpt-4o", Some(2.5));
+        // No stored cost: priced from the table at report time
+        reply("gpt-4o-mini", None);
+        reply("my-finetune", None);
+
+        let report = usage_report(&conn, "all", "model").unwrap();
+        assert_eq!(report.rows[0].key, "gpt-4o");
+        assert_eq!(report.rows[0].messages, 2);
+        assert!((report.rows[0].cost_usd - 5.0).abs() < 1e-9);
+        assert!((report.rows[1].cost_usd - 0.15).abs() < 1e-9);
+        assert_eq!(report.total.messages, 4);
+        assert_eq!(report.total.unpriced_messages, 1);
+
+        let by_tag = usage_report(&conn, "today", "tag").unwrap();
+        assert_eq!(by_tag.rows[0].label, "Untagged");
+
+        ProfileBudget::set(&conn, "default", Some(1.0), None, "month").unwrap();
+        let status = check_budget(&conn, "my-finetune").unwrap().unwrap();
+        assert!(status.soft_exceeded);
+        // The mini reply is priced from the table; the finetune's cost is unknown
+        assert!((status.spent_usd - 5.15).abs() < 1e-9);
+        assert_eq!(status.unpriced_messages, 1);
+
+        ProfileBudget::set(&conn, "default", None, Some(10.0), "day").unwrap();
+        assert!(check_budget(&conn, "gpt-4o").unwrap().is_none());
+        let err = check_budget(&conn, "my-finetune").unwrap_err();
+        assert_eq!(err.code, ErrorCode::BudgetExceeded);
+        assert!(err.message.contains("no price"), "{}", err);
+
+        ProfileBudget::set(&conn, "default", Some(1.0), Some(5.0), "day").unwrap();
+        let err = check_budget(&conn, "gpt-4o").unwrap_err();
+        assert_eq!(err.code, ErrorCode::BudgetExceeded);
+        assert!(err.message.contains("Budget exceeded"), "{}", err);
+    }
+}
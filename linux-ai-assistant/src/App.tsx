@@ -15,7 +15,9 @@ import ErrorMonitor from "./components/ErrorMonitor";
 import { FadeIn, AnimatedButton } from "./components/Animations";
 import { useKeyboardShortcuts, useCommandPalette } from "./lib/hooks";
 import { database } from "./lib/api/database";
+import type { AttachmentData } from "./lib/api/types";
 import Toaster from "./components/Toaster";
+import UnlockDatabaseModal from "./components/UnlockDatabaseModal";
 import { AppErrorBoundary } from "./components/AppErrorBoundary";
 
 // Lazy load heavy components to improve startup performance
@@ -38,6 +40,11 @@ import { useProjectStore } from "./lib/stores/projectStore";
 import { applyTheme, watchSystemTheme } from "./lib/utils/theme";
 import { useUiStore } from "./lib/stores/uiStore";
 import { withErrorHandling } from "./lib/utils/errorHandler";
+import type {
+  ApiMessage,
+  ShortcutAction,
+  ShortcutRegistration,
+} from "./lib/api/types";
 
 export default function App(): JSX.Element {
   const { loadSettings, registerGlobalShortcut, globalShortcut, theme } =
@@ -105,6 +112,9 @@ export default function App(): JSX.Element {
     let unlistenCliNotify: (() => void) | undefined;
     let unlistenCliAsk: (() => void) | undefined;
     let unlistenProject: (() => void) | undefined;
+    let unlistenClipboard: (() => void)[] = [];
+    let unlistenShortcuts: (() => void)[] = [];
+    let unlistenNotifications: (() => void)[] = [];
     (async () => {
       try {
         const mod = await import("@tauri-apps/api/event");
@@ -129,6 +139,7 @@ export default function App(): JSX.Element {
             let targetModel: string | undefined;
             let targetProvider: string | undefined;
             let forceNew = false;
+            let attachments: AttachmentData[] = [];
 
             if (typeof e.payload === "string") {
               prompt = (e.payload || "").trim();
@@ -145,6 +156,9 @@ export default function App(): JSX.Element {
               } catch {}
               targetProvider = obj.provider || undefined;
               forceNew = !!obj.new;
+              if (Array.isArray(obj.attachments)) {
+                attachments = obj.attachments;
+              }
             }
             if (!prompt) return;
             const chat = useChatStore.getState();
@@ -173,7 +187,7 @@ export default function App(): JSX.Element {
               }
             }
             try {
-              await chat.sendMessage(prompt);
+              await chat.sendMessage(prompt, attachments);
               useUiStore.getState().addToast({
                 message: "Sent CLI prompt to chat",
                 type: "success",
@@ -189,6 +203,111 @@ export default function App(): JSX.Element {
             }
           } catch {}
         });
+        // clipboard quick actions (QuickCapture shortcut or the API)
+        unlistenClipboard = [
+          await mod.listen<{ conversation_id: string }>(
+            "clipboard://action-started",
+            async (e) => {
+              try {
+                await useChatStore
+                  .getState()
+                  .selectConversation(e.payload.conversation_id);
+              } catch {}
+            },
+          ),
+          await mod.listen<{ conversation_id: string; replaced: boolean }>(
+            "clipboard://action-finished",
+            async (e) => {
+              const chat = useChatStore.getState();
+              if (chat.currentConversation?.id === e.payload.conversation_id) {
+                try {
+                  await chat.selectConversation(e.payload.conversation_id);
+                } catch {}
+              }
+              if (e.payload.replaced) {
+                useUiStore.getState().addToast({
+                  message: "Clipboard replaced with the result",
+                  type: "success",
+                  ttl: 1500,
+                });
+              }
+            },
+          ),
+          await mod.listen<{ message?: string }>("clipboard://error", (e) => {
+            useUiStore.getState().addToast({
+              message: e.payload?.message || "Clipboard action failed",
+              type: "error",
+              ttl: 2500,
+            });
+          }),
+        ];
+        // global shortcuts handled by the UI; other components can listen
+        // for the "app-shortcut" DOM event
+        unlistenShortcuts = [
+          await mod.listen<{ action: ShortcutAction }>(
+            "shortcut://action",
+            async (e) => {
+              switch (e.payload.action) {
+                case "NewConversation":
+                  try {
+                    await useChatStore
+                      .getState()
+                      .createConversation("New conversation", "gpt-4", "local");
+                  } catch (err) {
+                    console.error("failed to create conversation", err);
+                  }
+                  break;
+                case "OpenSettings":
+                  startTransition(() => setShowSettings(true));
+                  break;
+                default:
+                  window.dispatchEvent(
+                    new CustomEvent("app-shortcut", {
+                      detail: { action: e.payload.action },
+                    }),
+                  );
+              }
+            },
+          ),
+          await mod.listen<ShortcutRegistration[]>(
+            "shortcuts://registration-failed",
+            (e) => {
+              const failed = e.payload || [];
+              if (failed.length === 0) return;
+              useUiStore.getState().addToast({
+                message: `Shortcut not registered: ${failed
+                  .map((r) => `${r.shortcut} (${r.error || "unavailable"})`)
+                  .join(", ")}`,
+                type: "error",
+                ttl: 4000,
+              });
+            },
+          ),
+        ];
+        // Desktop notification actions: "Open" and a finished "Rerun"
+        unlistenNotifications = [
+          await mod.listen<{ conversation_id: string }>(
+            "notification://open",
+            async (e) => {
+              try {
+                await useChatStore
+                  .getState()
+                  .selectConversation(e.payload.conversation_id);
+              } catch (err) {
+                console.error("failed to open notified conversation", err);
+              }
+            },
+          ),
+          await mod.listen<ApiMessage>("notification://rerun", async (e) => {
+            const chat = useChatStore.getState();
+            if (chat.currentConversation?.id !== e.payload.conversation_id) {
+              return;
+            }
+            try {
+              await chat.selectConversation(e.payload.conversation_id);
+            } catch {}
+          }),
+        ];
         // new conversation
         const createConversation = useChatStore.getState().createConversation;
         unlistenNew = await mod.listen("tray://new-conversation", async () => {
@@ -229,6 +348,9 @@ export default function App(): JSX.Element {
         unlistenCliNotify && unlistenCliNotify();
         unlistenCliAsk && unlistenCliAsk();
         unlistenProject && unlistenProject();
+        unlistenClipboard.forEach((unlisten) => unlisten());
+        unlistenShortcuts.forEach((unlisten) => unlisten());
+        unlistenNotifications.forEach((unlisten) => unlisten());
       } catch {}
     };
   }, []);
@@ -427,6 +549,7 @@ export default function App(): JSX.Element {
 
       <Toaster />
       <ErrorMonitor />
+      <UnlockDatabaseModal />
       {/* Keyboard Debugger - Press F12 to toggle */}
       <KeyboardDebugger />
       <Suspense fallback={null}>
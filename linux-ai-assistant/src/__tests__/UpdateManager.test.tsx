@@ -53,7 +53,14 @@ describe("UpdateManager", () => {
           release_info: { is_critical: false, changelog: "a\nb\nc" },
         };
       if (cmd === "get_current_version") return "1.0.0";
-      if (cmd === "download_and_install_update") return "ok";
+      if (cmd === "download_and_install_update")
+        return {
+          version: "1.2.3",
+          path: "/opt/linux-ai-assistant.AppImage",
+          sha256: "ab".repeat(32),
+          installed: true,
+          backup_path: "/opt/linux-ai-assistant.AppImage.bak",
+        };
       return null;
     });
 
@@ -82,7 +89,13 @@ describe("UpdateManager", () => {
         version: "1.2.3",
       }),
     );
-    await waitFor(() => expect(addToast).toHaveBeenCalled());
+    await waitFor(() =>
+      expect(addToast).toHaveBeenCalledWith(
+        expect.objectContaining({
+          message: expect.stringContaining("Installed v1.2.3"),
+        }),
+      ),
+    );
 
     // after successful download the dialog should close
     expect(screen.queryByText(/Later/i)).toBeNull();
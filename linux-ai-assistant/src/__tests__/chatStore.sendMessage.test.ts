@@ -56,3 +56,45 @@ test("sendMessage creates user and assistant messages and updates store", async
   expect(msgs[1].role).toBe("assistant");
   expect(msgs[1].content).toBeDefined();
 });
+
+test("sendMessage attaches files to the persisted user message", async () => {
+  const conv = {
+    id: "conv1",
+    title: "Conv 1",
+    model: "gpt-4",
+    provider: "local",
+    created_at: Date.now(),
+    updated_at: Date.now(),
+  };
+
+  (db.messages as any).create = vi.fn(async (data: any) => ({
+    id: data.role === "user" ? "user-msg" : "assistant-msg",
+    conversation_id: data.conversation_id,
+    role: data.role,
+    content: data.content,
+    timestamp: Date.now(),
+  }));
+  const attachMock = vi.fn(async (messageId: string, fileName: string) => ({
+    id: `att-${fileName}`,
+    message_id: messageId,
+    kind: "image",
+    file_name: fileName,
+    mime_type: "image/png",
+    size_bytes: 4,
+    sha256: "0".repeat(64),
+    created_at: Date.now(),
+  }));
+  (db.attachments as any).attachData = attachMock;
+
+  useChatStore.setState({ currentConversation: conv, messages: [] });
+
+  await useChatStore
+    .getState()
+    .sendMessage("what is this?", [
+      { file_name: "shot.png", data_base64: "iVBORw==" },
+    ]);
+
+  expect(attachMock).toHaveBeenCalledWith("user-msg", "shot.png", "iVBORw==");
+  const user = useChatStore.getState().messages[0];
+  expect(user.attachments?.map((a) => a.id)).toEqual(["att-shot.png"]);
+});
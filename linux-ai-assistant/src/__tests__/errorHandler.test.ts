@@ -29,6 +29,30 @@ describe("errorHandler utilities", () => {
     expect(unknown.type).toBe(EH.ErrorType.UNKNOWN);
   });
 
+  it("classifies backend errors by code rather than message", () => {
+    const locked = EH.classifyError({
+      code: "database_locked",
+      message: "database is busy",
+    });
+    expect(locked.type).toBe(EH.ErrorType.DATABASE);
+
+    const auth = EH.createAppError({
+      code: "auth",
+      message: "OPENAI_API_KEY not set",
+      details: { provider: "openai" },
+    });
+    expect(auth.type).toBe(EH.ErrorType.AUTHENTICATION);
+    expect(auth.message).toBe("OPENAI_API_KEY not set");
+    expect(auth.retryable).toBe(false);
+
+    const budget = EH.createAppError({
+      code: "budget_exceeded",
+      message: "Budget exceeded for profile 'Default'",
+    });
+    expect(budget.userMessage).toContain("spending limit");
+    expect(budget.retryable).toBe(false);
+  });
+
   it("createAppError produces structured AppError with friendly message", () => {
     const appErr = EH.createAppError("disk full error", "DB", undefined);
     expect(appErr).toHaveProperty("id");
@@ -7,8 +7,6 @@ const addToastMock = vi.fn();
 
 vi.mock("../lib/utils/tauri", async () => {
   return {
-    registerGlobalShortcutSafe: vi.fn(),
-    unregisterAllShortcutsSafe: vi.fn(),
     invokeSafe: vi.fn(),
   };
 });
@@ -82,33 +80,52 @@ describe("settingsStore", () => {
     expect(state.projectRoot).toBe("/tmp/proj");
   });
 
-  it("registerGlobalShortcut shows success toast when registration succeeds", async () => {
-    // ensure unregister resolves
-    const tauri = await import("../lib/utils/tauri");
-    (tauri.unregisterAllShortcutsSafe as any).mockResolvedValue(undefined);
-    (tauri.registerGlobalShortcutSafe as any).mockResolvedValue(true);
+  const shortcutConfig = {
+    shortcuts: [
+      { action: "ToggleWindow", shortcut: "CommandOrControl+Space", enabled: true },
+      { action: "NewConversation", shortcut: "CommandOrControl+N", enabled: false },
+    ],
+  };
+
+  it("registerGlobalShortcut rebinds ToggleWindow in the backend config", async () => {
+    const db = await import("../lib/api/database");
+    const updateConfig = vi.fn().mockResolvedValue([
+      { action: "ToggleWindow", shortcut: "Ctrl+K", registered: true },
+    ]);
+    db.database.shortcuts = {
+      getConfig: vi.fn().mockResolvedValue(shortcutConfig),
+      updateConfig,
+    } as any;
 
     settingsModule = await import("../lib/stores/settingsStore");
-    // call the action
     await settingsModule.useSettingsStore
       .getState()
       .registerGlobalShortcut("Ctrl+K");
 
-    expect(tauri.registerGlobalShortcutSafe as any).toHaveBeenCalledWith(
-      "Ctrl+K",
-      expect.any(Function),
-    );
+    expect(updateConfig).toHaveBeenCalledWith({
+      shortcuts: [
+        { action: "ToggleWindow", shortcut: "Ctrl+K", enabled: true },
+        shortcutConfig.shortcuts[1],
+      ],
+    });
     expect(addToastMock).toHaveBeenCalledWith(
       expect.objectContaining({ type: "success" }),
     );
   });
 
-  it("registerGlobalShortcut adds error toast when registration throws", async () => {
-    const tauri = await import("../lib/utils/tauri");
-    (tauri.unregisterAllShortcutsSafe as any).mockResolvedValue(undefined);
-    (tauri.registerGlobalShortcutSafe as any).mockRejectedValue(
-      new Error("boom"),
-    );
+  it("registerGlobalShortcut adds error toast when the OS refuses the shortcut", async () => {
+    const db = await import("../lib/api/database");
+    db.database.shortcuts = {
+      getConfig: vi.fn().mockResolvedValue(shortcutConfig),
+      updateConfig: vi.fn().mockResolvedValue([
+        {
+          action: "ToggleWindow",
+          shortcut: "Ctrl+Z",
+          registered: false,
+          error: "already taken",
+        },
+      ]),
+    } as any;
 
     settingsModule = await import("../lib/stores/settingsStore");
     await settingsModule.useSettingsStore
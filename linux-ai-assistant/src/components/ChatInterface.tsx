@@ -94,6 +94,17 @@ export default function ChatInterface(): JSX.Element {
       window.removeEventListener("insert-command", handleInsertCommand as any);
   }, []);
 
+  // FocusInput global shortcut
+  useEffect(() => {
+    const handleShortcut = (e: Event) => {
+      if ((e as CustomEvent).detail?.action === "FocusInput") {
+        inputRef.current?.focus();
+      }
+    };
+    window.addEventListener("app-shortcut", handleShortcut);
+    return () => window.removeEventListener("app-shortcut", handleShortcut);
+  }, []);
+
   const handlePasteFromClipboard = async () => {
     await withErrorHandling(
       async () => {
@@ -56,7 +56,7 @@ export default function ConversationItem({
 
   const handleExport = async (
     e: MouseEvent,
-    format: "json" | "markdown" | "html" | "pdf",
+    format: "json" | "markdown" | "html" | "pdf" | "org" | "epub" | "text",
   ) => {
     e.stopPropagation();
     setExporting(true);
@@ -184,6 +184,30 @@ export default function ConversationItem({
             >
               📄
             </button>
+            <button
+              className="text-xs px-1 py-1 bg-teal-600 rounded hover:bg-teal-700 disabled:opacity-50"
+              onClick={(e) => handleExport(e, "org")}
+              disabled={exporting}
+              title="Export as Org"
+            >
+              🦄
+            </button>
+            <button
+              className="text-xs px-1 py-1 bg-indigo-600 rounded hover:bg-indigo-700 disabled:opacity-50"
+              onClick={(e) => handleExport(e, "epub")}
+              disabled={exporting}
+              title="Export as EPUB"
+            >
+              📚
+            </button>
+            <button
+              className="text-xs px-1 py-1 bg-gray-600 rounded hover:bg-gray-700 disabled:opacity-50"
+              onClick={(e) => handleExport(e, "text")}
+              disabled={exporting}
+              title="Export as plain text"
+            >
+              🗒️
+            </button>
             <button
               className="text-xs px-2 py-1 bg-red-600 rounded hover:bg-red-700"
               onClick={handleDelete}
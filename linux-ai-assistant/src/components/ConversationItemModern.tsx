@@ -56,7 +56,7 @@ export default function ConversationItem({
 
   const handleExport = async (
     e: MouseEvent,
-    format: "json" | "markdown" | "html" | "pdf",
+    format: "json" | "markdown" | "html" | "pdf" | "org" | "epub" | "text",
   ) => {
     e.stopPropagation();
     setExporting(true);
@@ -171,6 +171,33 @@ export default function ConversationItem({
                   <span>📄</span>
                   <span className="hidden lg:inline">PDF</span>
                 </button>
+                <button
+                  className="flex items-center space-x-1 px-2 py-1 text-xs bg-teal-100 dark:bg-teal-900/30 text-teal-700 dark:text-teal-400 rounded-md hover:bg-teal-200 dark:hover:bg-teal-900/50 transition-colors disabled:opacity-50"
+                  onClick={(e) => handleExport(e, "org")}
+                  disabled={exporting}
+                  title="Export as Org"
+                >
+                  <span>🦄</span>
+                  <span className="hidden lg:inline">ORG</span>
+                </button>
+                <button
+                  className="flex items-center space-x-1 px-2 py-1 text-xs bg-indigo-100 dark:bg-indigo-900/30 text-indigo-700 dark:text-indigo-400 rounded-md hover:bg-indigo-200 dark:hover:bg-indigo-900/50 transition-colors disabled:opacity-50"
+                  onClick={(e) => handleExport(e, "epub")}
+                  disabled={exporting}
+                  title="Export as EPUB"
+                >
+                  <span>📚</span>
+                  <span className="hidden lg:inline">EPUB</span>
+                </button>
+                <button
+                  className="flex items-center space-x-1 px-2 py-1 text-xs bg-gray-100 dark:bg-gray-900/30 text-gray-700 dark:text-gray-400 rounded-md hover:bg-gray-200 dark:hover:bg-gray-900/50 transition-colors disabled:opacity-50"
+                  onClick={(e) => handleExport(e, "text")}
+                  disabled={exporting}
+                  title="Export as plain text"
+                >
+                  <span>🗒️</span>
+                  <span className="hidden lg:inline">TXT</span>
+                </button>
               </div>
               <button
                 className="flex items-center space-x-1 px-2 py-1 text-xs bg-red-100 dark:bg-red-900/30 text-red-700 dark:text-red-400 rounded-md hover:bg-red-200 dark:hover:bg-red-900/50 transition-colors"
@@ -1,7 +1,13 @@
-import { useState, lazy, Suspense } from "react";
+import { useState, useEffect, lazy, Suspense } from "react";
 import { useSettingsStore } from "../lib/stores/settingsStore";
 import { useUiStore } from "../lib/stores/uiStore";
 import { withErrorHandling } from "../lib/utils/errorHandler";
+import { database } from "../lib/api/database";
+import type {
+  NotificationSettings,
+  UpdateChannel,
+  UpdateSettings,
+} from "../lib/api/types";
 import {
   FileText,
   Activity,
@@ -42,6 +48,62 @@ export default function Settings({ onClose }: Props): JSX.Element {
   const [showDocumentSearch, setShowDocumentSearch] = useState(false);
   const [showProfileSettings, setShowProfileSettings] = useState(false);
   const [showUsageAnalytics, setShowUsageAnalytics] = useState(false);
+  const [notifications, setNotifications] =
+    useState<NotificationSettings | null>(null);
+
+  const [updates, setUpdates] = useState<UpdateSettings | null>(null);
+  const [mirrorUrl, setMirrorUrl] = useState("");
+
+  useEffect(() => {
+    database.notifications
+      .getSettings()
+      .then(setNotifications)
+      .catch(() => setNotifications(null));
+    database.updates
+      .getSettings()
+      .then((s) => {
+        setUpdates(s);
+        setMirrorUrl(s.base_url || "");
+      })
+      .catch(() => setUpdates(null));
+  }, []);
+
+  // Like notifications, update settings apply immediately
+  const updateUpdates = async (patch: Partial<UpdateSettings>) => {
+    if (!updates) return;
+    try {
+      setUpdates(
+        await database.updates.updateSettings({ ...updates, ...patch }),
+      );
+    } catch (e: any) {
+      addToast({
+        message: e?.message || "Failed to update update settings",
+        type: "error",
+        ttl: 3000,
+      });
+    }
+  };
+
+  // Notification settings apply immediately rather than on "Save Changes"
+  const updateNotifications = async (
+    patch: Partial<NotificationSettings>,
+  ) => {
+    if (!notifications) return;
+    try {
+      setNotifications(
+        await database.notifications.updateSettings({
+          ...notifications,
+          ...patch,
+        }),
+      );
+    } catch (e: any) {
+      addToast({
+        message: e?.message || "Failed to update notification settings",
+        type: "error",
+        ttl: 3000,
+      });
+    }
+  };
 
   const validate = (s: string): string | null => {
     if (!s.trim()) return "Shortcut can't be empty";
@@ -196,6 +258,111 @@ export default function Settings({ onClose }: Props): JSX.Element {
               </p>
             </div>
           </div>
+
+          {/* Update Settings */}
+          {updates && (
+            <div className="bg-gray-50/50 dark:bg-gray-800/30 rounded-lg p-4 border border-gray-200/50 dark:border-gray-700/50">
+              <div className="space-y-3">
+                <label
+                  htmlFor="update-channel-select"
+                  className="block text-sm font-medium text-gray-700 dark:text-gray-300"
+                >
+                  Updates
+                </label>
+                <div className="flex space-x-2">
+                  <select
+                    id="update-channel-select"
+                    value={updates.channel}
+                    onChange={(e) =>
+                      updateUpdates({
+                        channel: e.target.value as UpdateChannel,
+                      })
+                    }
+                    className="flex-1 px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 text-sm text-gray-900 dark:text-white"
+                  >
+                    <option value="stable">Stable</option>
+                    <option value="beta">Beta</option>
+                    <option value="nightly">Nightly</option>
+                  </select>
+                  <select
+                    aria-label="Update check interval"
+                    value={updates.check_interval_hours}
+                    onChange={(e) =>
+                      updateUpdates({
+                        check_interval_hours: Number(e.target.value),
+                      })
+                    }
+                    className="flex-1 px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 text-sm text-gray-900 dark:text-white"
+                  >
+                    <option value={6}>Every 6 hours</option>
+                    <option value={24}>Daily</option>
+                    <option value={168}>Weekly</option>
+                    <option value={0}>Never</option>
+                  </select>
+                </div>
+                <input
+                  aria-label="Update mirror URL"
+                  value={mirrorUrl}
+                  onChange={(e) => setMirrorUrl(e.target.value)}
+                  onBlur={() => {
+                    const base_url = mirrorUrl.trim() || null;
+                    if (base_url !== (updates.base_url || null)) {
+                      updateUpdates({ base_url });
+                    }
+                  }}
+                  placeholder="Mirror releases URL (optional)"
+                  className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 text-sm text-gray-900 dark:text-white placeholder-gray-500 dark:placeholder-gray-400"
+                />
+                {updates.skipped_version && (
+                  <p className="text-xs text-gray-600 dark:text-gray-400">
+                    Skipping v{updates.skipped_version}.{" "}
+                    <button
+                      onClick={() => updateUpdates({ skipped_version: null })}
+                      className="underline"
+                    >
+                      Stop skipping
+                    </button>
+                  </p>
+                )}
+              </div>
+            </div>
+          )}
+
+          {/* Notification Settings */}
+          {notifications && (
+            <div className="bg-gray-50/50 dark:bg-gray-800/30 rounded-lg p-4 border border-gray-200/50 dark:border-gray-700/50">
+              <div className="space-y-3">
+                <span className="block text-sm font-medium text-gray-700 dark:text-gray-300">
+                  Desktop Notifications
+                </span>
+                <label className="flex items-center space-x-2 text-sm text-gray-700 dark:text-gray-300">
+                  <input
+                    type="checkbox"
+                    checked={notifications.enabled}
+                    onChange={(e) =>
+                      updateNotifications({ enabled: e.target.checked })
+                    }
+                  />
+                  <span>Notify when long work finishes in the background</span>
+                </label>
+                <label className="flex items-center space-x-2 text-sm text-gray-700 dark:text-gray-300">
+                  <input
+                    type="checkbox"
+                    checked={notifications.do_not_disturb}
+                    onChange={(e) =>
+                      updateNotifications({ do_not_disturb: e.target.checked })
+                    }
+                  />
+                  <span>Do not disturb</span>
+                </label>
+                <p className="text-xs text-gray-600 dark:text-gray-400">
+                  Shown for replies and code runs taking over{" "}
+                  {notifications.min_duration_secs}s while the window is hidden,
+                  at most {notifications.max_per_minute} per minute
+                </p>
+              </div>
+            </div>
+          )}
         </div>
 
         {/* Advanced Settings Section */}
@@ -9,6 +9,7 @@ import {
   Info,
 } from "lucide-react";
 import { database } from "../lib/api/database";
+import type { ShortcutDiagnostics } from "../lib/api/types";
 
 interface ShortcutAction {
   ToggleWindow: any;
@@ -58,7 +59,7 @@ const actionDescriptions: Record<keyof ShortcutAction, string> = {
   ToggleWindow: "Show/hide the main application window",
   NewConversation: "Create a new conversation",
   OpenSettings: "Open the settings panel",
-  QuickCapture: "Quick capture input without showing window",
+  QuickCapture: "Run a quick action on the clipboard contents",
   FocusInput: "Focus the chat input field",
   ClearConversation: "Clear the current conversation",
   ExportCurrent: "Export current conversation to file",
@@ -108,6 +109,9 @@ export default function ShortcutSettings({ onClose }: ShortcutSettingsProps) {
   >("idle");
   const [editingShortcut, setEditingShortcut] = useState<string | null>(null);
   const [tempShortcut, setTempShortcut] = useState("");
+  const [diagnostics, setDiagnostics] = useState<ShortcutDiagnostics | null>(
+    null,
+  );
 
   useEffect(() => {
     loadConfig();
@@ -119,6 +123,7 @@ export default function ShortcutSettings({ onClose }: ShortcutSettingsProps) {
       setIsLoading(true);
       const data = (await database.shortcuts.getConfig()) as ShortcutConfig;
       setConfig(data);
+      setDiagnostics(await database.shortcuts.getBackend());
     } catch (err) {
       setError(err instanceof Error ? err.message : "Failed to load shortcuts");
     } finally {
@@ -129,8 +134,16 @@ export default function ShortcutSettings({ onClose }: ShortcutSettingsProps) {
   const saveConfig = async (newConfig: ShortcutConfig) => {
     try {
       setSaveStatus("saving");
-      await database.shortcuts.updateConfig(newConfig);
+      const registrations = await database.shortcuts.updateConfig(newConfig);
       setConfig(newConfig);
+      const failed = registrations.filter((r) => !r.registered);
+      if (failed.length > 0) {
+        setError(
+          failed
+            .map((r) => `${r.shortcut}: ${r.error || "not registered"}`)
+            .join("; "),
+        );
+      }
       setSaveStatus("success");
       setTimeout(() => setSaveStatus("idle"), 2000);
     } catch (err) {
@@ -164,12 +177,13 @@ export default function ShortcutSettings({ onClose }: ShortcutSettingsProps) {
     try {
       // Validate shortcut first
       await database.shortcuts.validateShortcut(newShortcut);
+      const normalized = await database.shortcuts.normalizeShortcut(newShortcut);
 
       const newConfig = {
         ...config,
         shortcuts: config.shortcuts.map((shortcut) =>
           shortcut.action === action
-            ? { ...shortcut, shortcut: newShortcut }
+            ? { ...shortcut, shortcut: normalized }
             : shortcut,
         ),
       };
@@ -353,6 +367,20 @@ export default function ShortcutSettings({ onClose }: ShortcutSettingsProps) {
             <div className="text-sm text-blue-700 dark:text-blue-300">
               <strong>Tip:</strong> Click on a shortcut to edit it. Use standard
               keyboard notation like "CommandOrControl+Space" or "Alt+Shift+N".
+              {diagnostics?.backend === "portal" && (
+                <div className="mt-1">
+                  Using the desktop's global shortcuts portal (Wayland); your
+                  desktop may ask to confirm or change the keys.
+                </div>
+              )}
+              {diagnostics?.backend === "plugin" &&
+                diagnostics.fallback_reason && (
+                  <div className="mt-1">
+                    Global shortcuts portal unavailable (
+                    {diagnostics.fallback_reason}); shortcuts may not work in
+                    Wayland apps.
+                  </div>
+                )}
             </div>
           </div>
         </div>
@@ -0,0 +1,105 @@
+import { useState, useEffect } from "react";
+import { database } from "../lib/api/database";
+import type { EncryptionStatus } from "../lib/api/types";
+import { listen } from "../lib/tauri-shim";
+import { isTauriEnvironment } from "../lib/utils/tauri";
+import { errorMessage } from "../lib/utils/errorHandler";
+
+// Blocks the window while the encrypted database is locked, at startup or
+// after the idle timeout
+export default function UnlockDatabaseModal() {
+  const [status, setStatus] = useState<EncryptionStatus | null>(null);
+  const [passphrase, setPassphrase] = useState("");
+  const [error, setError] = useState<string | null>(null);
+  const [unlocking, setUnlocking] = useState(false);
+
+  useEffect(() => {
+    if (!isTauriEnvironment()) return;
+    let unlisten: (() => void) | undefined;
+    const refresh = () => {
+      database.encryption
+        .getStatus()
+        .then(setStatus)
+        .catch((e) => console.error("Failed to get encryption status:", e));
+    };
+    refresh();
+    listen("database://locked", refresh)
+      .then((fn) => (unlisten = fn))
+      .catch(() => {});
+    return () => unlisten?.();
+  }, []);
+
+  if (!status?.locked) return null;
+
+  const usesPassphrase = status.key_source !== "keyring";
+
+  const handleUnlock = async () => {
+    if (usesPassphrase && !passphrase) return;
+    setUnlocking(true);
+    setError(null);
+    try {
+      await database.encryption.unlock(usesPassphrase ? passphrase : undefined);
+      setPassphrase("");
+      // Stores loaded while locked hold nothing; start over with the data
+      window.location.reload();
+    } catch (e) {
+      setError(errorMessage(e));
+      setUnlocking(false);
+    }
+  };
+
+  return (
+    <div className="fixed inset-0 z-50 flex items-center justify-center bg-black bg-opacity-50">
+      <div className="w-96 bg-white dark:bg-gray-900 rounded shadow-lg p-4">
+        <h3 className="text-sm font-semibold mb-3">Database locked</h3>
+
+        <div className="space-y-3">
+          {usesPassphrase ? (
+            <div>
+              <label
+                htmlFor="database-passphrase-input"
+                className="text-xs text-gray-700 dark:text-gray-300 block mb-1"
+              >
+                Passphrase
+              </label>
+              <input
+                id="database-passphrase-input"
+                type="password"
+                value={passphrase}
+                onChange={(e) => setPassphrase(e.target.value)}
+                onKeyDown={(e) => {
+                  if (e.key === "Enter") {
+                    handleUnlock();
+                  }
+                }}
+                placeholder="Enter the database passphrase"
+                className="w-full px-2 py-1 rounded bg-white border border-gray-300 dark:bg-gray-800 dark:border-gray-700 text-sm outline-none focus:ring-2 focus:ring-blue-500"
+                autoFocus
+              />
+            </div>
+          ) : (
+            <p className="text-xs text-gray-700 dark:text-gray-300">
+              The database key is kept in the system keyring.
+            </p>
+          )}
+
+          {error && (
+            <p className="text-[11px] text-red-600 dark:text-red-400">
+              {error}
+            </p>
+          )}
+
+          <div className="flex justify-end gap-2 pt-1">
+            <button
+              onClick={handleUnlock}
+              disabled={unlocking || (usesPassphrase && !passphrase)}
+              className="px-3 py-1 text-xs rounded bg-blue-600 hover:bg-blue-500 disabled:opacity-60 disabled:cursor-not-allowed text-white"
+            >
+              {unlocking ? "Unlocking…" : "Unlock"}
+            </button>
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
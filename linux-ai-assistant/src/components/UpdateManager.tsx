@@ -1,7 +1,9 @@
 import React, { useEffect, useState } from "react";
 import { useUpdateStore } from "@/lib/stores/updateStore";
+import type { InstallOutcome } from "@/lib/stores/updateStore";
 import { useUiStore } from "@/lib/stores/uiStore";
 import { invokeSafe, isTauriEnvironment } from "@/lib/utils/tauri";
+import { listen } from "@/lib/tauri-shim";
 
 export const UpdateManager: React.FC = () => {
   const {
@@ -41,45 +43,49 @@ export const UpdateManager: React.FC = () => {
     initializeVersion();
   }, [setCurrentVersion]);
 
-  // Auto-check for updates on mount and periodically
+  // Show the last result on mount; the backend checks on the configured
+  // interval and reports each check
   useEffect(() => {
     // Skip if not in Tauri environment (web preview)
     if (!isTauriEnvironment()) return;
 
-    const checkUpdates = async () => {
-      await performUpdateCheck();
-    };
+    performUpdateCheck();
 
-    checkUpdates();
+    let unlisten: (() => void) | undefined;
+    listen("update://checked", (event) => showStatus(event.payload))
+      .then((fn) => (unlisten = fn))
+      .catch(() => {});
+    return () => unlisten?.();
+  }, []);
 
-    // Check for updates every hour (3600000 ms)
-    const interval = setInterval(checkUpdates, 3600000);
+  const showStatus = (status: any) => {
+    setUpdateStatus(status);
+    setLastCheckTime(Date.now());
 
-    return () => clearInterval(interval);
-  }, []);
+    // Show notification if update is available and not dismissed
+    if (
+      status.has_update &&
+      status.new_version &&
+      (status.release_info?.is_critical ||
+        !dismissedVersions.includes(status.new_version))
+    ) {
+      addToast({
+        message: `Update available: v${status.new_version}`,
+        type: "info",
+        ttl: 8000,
+      });
+      setShowDialog(true);
+    }
+  };
 
-  const performUpdateCheck = async () => {
+  // Without `force` this returns the last result instead of contacting the
+  // server (forced by "Check Again")
+  const performUpdateCheck = async (force = false) => {
     setIsChecking(true);
     try {
-      const status = await invokeSafe<any>("check_for_updates");
+      const status = await invokeSafe<any>("check_for_updates", { force });
       if (status) {
-        setUpdateStatus(status);
-      }
-      setLastCheckTime(Date.now());
-
-      // Show notification if update is available and not dismissed
-      if (
-        status &&
-        status.has_update &&
-        status.new_version &&
-        !dismissedVersions.includes(status.new_version)
-      ) {
-        addToast({
-          message: `Update available: v${status.new_version}`,
-          type: "info",
-          ttl: 8000,
-        });
-        setShowDialog(true);
+        showStatus(status);
       }
     } catch (error) {
       console.error("Failed to check for updates:", error);
@@ -98,12 +104,19 @@ export const UpdateManager: React.FC = () => {
 
     setIsDownloading(true);
     try {
-      await invokeSafe<string>("download_and_install_update", {
-        version: updateStatus.new_version,
-      });
+      const outcome = await invokeSafe<InstallOutcome>(
+        "download_and_install_update",
+        { version: updateStatus.new_version },
+      );
+      if (outcome === null) {
+        throw new Error("the download could not be verified");
+      }
 
+      // Updates are only kept once their signature and checksum verify
       addToast({
-        message: `Downloaded v${updateStatus.new_version}. Close the app and run the new AppImage to install.`,
+        message: outcome.installed
+          ? `Installed v${updateStatus.new_version}. Restart the app to use it.`
+          : `Downloaded and verified v${updateStatus.new_version}. Close the app and run the new AppImage to install.`,
         type: "success",
         ttl: 10000,
       });
@@ -122,6 +135,14 @@ export const UpdateManager: React.FC = () => {
     }
   };
 
+  const handleSkip = async () => {
+    if (!updateStatus?.new_version) return;
+    await invokeSafe("skip_update_version", {
+      version: updateStatus.new_version,
+    });
+    setShowDialog(false);
+  };
+
   const handleDismiss = () => {
     if (updateStatus?.new_version) {
       dismissUpdate(updateStatus.new_version);
@@ -201,8 +222,17 @@ export const UpdateManager: React.FC = () => {
           >
             Later
           </button>
+          {!updateStatus.release_info?.is_critical && (
+            <button
+              onClick={handleSkip}
+              disabled={isDownloading}
+              className="flex-1 px-4 py-2 text-slate-600 dark:text-slate-300 border border-slate-300 dark:border-slate-600 hover:bg-slate-50 dark:hover:bg-slate-700 rounded font-medium transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
+            >
+              Skip This Version
+            </button>
+          )}
           <button
-            onClick={performUpdateCheck}
+            onClick={() => performUpdateCheck(true)}
             disabled={isDownloading || isChecking}
             className="flex-1 px-4 py-2 text-slate-600 dark:text-slate-300 border border-slate-300 dark:border-slate-600 hover:bg-slate-50 dark:hover:bg-slate-700 rounded font-medium transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
           >
@@ -8,7 +8,41 @@ import type {
   NewMessage,
   ApiConversation,
   ApiMessage,
+  ApiAttachment,
+  BranchDiff,
+  BudgetPeriod,
+  BudgetStatus,
+  ClipboardActionResult,
+  ClipboardAnalysis,
+  ModelPrice,
+  QuickAction,
+  ProfileBudget,
+  ProjectIndexStats,
   Setting,
+  ShortcutConfig,
+  ShortcutDiagnostics,
+  NotificationSettings,
+  UpdateSettings,
+  BackupFile,
+  BackupInfo,
+  BackupManifest,
+  BackupSettings,
+  RestoreOutcome,
+  ConflictStrategy,
+  DirectoryExport,
+  EncryptionSettings,
+  EncryptionStatus,
+  KeySource,
+  ExportFilter,
+  ExportFormat,
+  ExportMirrorSettings,
+  ImportFormat,
+  ImportPreview,
+  ImportResult,
+  ShortcutRegistration,
+  ScoredProjectChunk,
+  UsageGroupBy,
+  UsageReport,
   ApiTag,
   NewTag,
   ApiWorkspaceTemplate,
@@ -176,6 +210,34 @@ async function callInvoke<T>(
       case "toggle_main_window":
         return undefined as unknown as T;
 
+      // Shortcuts (nothing can be bound outside Tauri)
+      case "get_shortcut_config":
+        return { shortcuts: [] } as unknown as T;
+      case "update_shortcut_config":
+      case "get_shortcut_status":
+        return [] as unknown as T;
+      case "get_shortcut_backend":
+        return { wayland: false, registrations: [] } as unknown as T;
+
+      // Notifications (the browser's own notifications are used instead)
+      case "get_notification_settings":
+        return {
+          enabled: true,
+          do_not_disturb: false,
+          min_duration_secs: 10,
+          max_per_minute: 4,
+        } as unknown as T;
+      case "update_notification_settings":
+        return (args as any)?.settings as T;
+
+      // Updates
+      case "get_update_settings":
+        return { channel: "stable", check_interval_hours: 24 } as unknown as T;
+      case "update_update_settings":
+        return (args as any)?.settings as T;
+      case "skip_update_version":
+        return undefined as unknown as T;
+
       default:
         throw new Error(`Command '${cmd}' not available in web preview`);
     }
@@ -191,13 +253,20 @@ async function callInvoke<T>(
     const msg = e?.message || (typeof e === "string" ? e : JSON.stringify(e));
     const error = new Error(`Database operation failed: ${cmd} - ${msg}`);
 
+    // Keep the backend's stable error code for classification
+    (error as any).code = e?.code;
+    (error as any).details = e?.details;
     // Add context about the operation
     (error as any).operation = cmd;
     (error as any).args = args;
     (error as any).duration = Date.now() - startTime;
 
     // Handle critical database errors
-    if (msg.includes("database is locked") || msg.includes("disk I/O error")) {
+    if (
+      e?.code === "database_locked" ||
+      msg.includes("database is locked") ||
+      msg.includes("disk I/O error")
+    ) {
       handleDatabaseError(error, `Database.${cmd}`);
       return null as unknown as T; // Return early for critical errors
     }
@@ -220,6 +289,7 @@ export const database = {
         model: data.model,
         provider: data.provider,
         system_prompt: data.system_prompt,
+        template_id: data.template_id,
       });
     },
 
@@ -270,6 +340,43 @@ export const database = {
         conversation_id: conversationId,
       });
     },
+
+    regenerateSummary: async (
+      conversationId: string,
+      retitle: boolean = false,
+    ): Promise<Conversation> => {
+      return callInvoke<Conversation>("regenerate_conversation_summary", {
+        conversation_id: conversationId,
+        retitle,
+      });
+    },
+
+    diffBranches: async (a: string, b: string): Promise<BranchDiff> => {
+      return callInvoke<BranchDiff>("diff_branches", { a, b });
+    },
+
+    mergeBranch: async (
+      sourceConversationId: string,
+      targetConversationId: string,
+      messageIds: string[],
+    ): Promise<Message[]> => {
+      return callInvoke<Message[]>("merge_branch", {
+        source_conversation_id: sourceConversationId,
+        target_conversation_id: targetConversationId,
+        message_ids: messageIds,
+      });
+    },
+
+    // Tie the conversation to a project so replies cite its indexed files
+    setProjectRoot: async (
+      id: string,
+      projectRoot: string | null,
+    ): Promise<void> => {
+      return callInvoke<void>("set_conversation_project_root", {
+        id,
+        project_root: projectRoot,
+      });
+    },
   },
   // Window / app-level commands
   window: {
@@ -335,6 +442,86 @@ export const database = {
         conversation_id: conversationId,
       });
     },
+
+    getTree: async (conversationId: string): Promise<Message[]> => {
+      return callInvoke<Message[]>("get_conversation_tree", {
+        conversation_id: conversationId,
+      });
+    },
+
+    listSiblings: async (messageId: string): Promise<Message[]> => {
+      return callInvoke<Message[]>("list_siblings", { message_id: messageId });
+    },
+
+    setActiveLeaf: async (messageId: string): Promise<Message[]> => {
+      return callInvoke<Message[]>("set_active_leaf", {
+        message_id: messageId,
+      });
+    },
+
+    regenerate: async (
+      messageId: string,
+      provider?: string,
+      model?: string,
+    ): Promise<Message> => {
+      return callInvoke<Message>("regenerate_message", {
+        message_id: messageId,
+        provider,
+        model,
+      });
+    },
+
+    editAndResubmit: async (
+      messageId: string,
+      content: string,
+      provider?: string,
+      model?: string,
+    ): Promise<Message[]> => {
+      return callInvoke<Message[]>("edit_and_resubmit", {
+        message_id: messageId,
+        content,
+        provider,
+        model,
+      });
+    },
+  },
+
+  // Image and text file attachments on messages
+  attachments: {
+    attachData: async (
+      messageId: string,
+      fileName: string,
+      dataBase64: string,
+    ): Promise<ApiAttachment> => {
+      return callInvoke<ApiAttachment>("attach_data", {
+        message_id: messageId,
+        file_name: fileName,
+        data_base64: dataBase64,
+      });
+    },
+
+    getByMessage: async (messageId: string): Promise<ApiAttachment[]> => {
+      return callInvoke<ApiAttachment[]>("get_message_attachments", {
+        message_id: messageId,
+      });
+    },
+
+    getByConversation: async (
+      conversationId: string,
+    ): Promise<ApiAttachment[]> => {
+      return callInvoke<ApiAttachment[]>("get_conversation_attachments", {
+        conversation_id: conversationId,
+      });
+    },
+
+    // Base64-encoded contents, e.g. for image previews
+    getData: async (id: string): Promise<string> => {
+      return callInvoke<string>("get_attachment_data", { id });
+    },
+
+    delete: async (id: string): Promise<void> => {
+      return callInvoke<void>("delete_attachment", { id });
+    },
   },
 
   // Settings operations
@@ -384,6 +571,23 @@ export const database = {
     },
   },
 
+  // Index of the current project root's files
+  project: {
+    index: async (): Promise<ProjectIndexStats> => {
+      return callInvoke<ProjectIndexStats>("index_project");
+    },
+
+    search: async (
+      query: string,
+      topK?: number,
+    ): Promise<ScoredProjectChunk[]> => {
+      return callInvoke<ScoredProjectChunk[]>("search_project_index", {
+        query,
+        top_k: topK,
+      });
+    },
+  },
+
   // Tags management
   tags: {
     create: async (data: NewTag): Promise<Tag> => {
@@ -512,22 +716,281 @@ export const database = {
     },
   },
 
+  // Usage reports, model pricing and profile budgets
+  usage: {
+    report: async (
+      range: string,
+      groupBy: UsageGroupBy,
+    ): Promise<UsageReport> => {
+      return callInvoke<UsageReport>("usage_report", {
+        range,
+        group_by: groupBy,
+      });
+    },
+
+    getPrices: async (): Promise<ModelPrice[]> => {
+      return callInvoke<ModelPrice[]>("get_model_prices");
+    },
+
+    setPrice: async (
+      model: string,
+      provider: string,
+      inputPerMtok: number,
+      outputPerMtok: number,
+    ): Promise<ModelPrice> => {
+      return callInvoke<ModelPrice>("set_model_price", {
+        model,
+        provider,
+        input_per_mtok: inputPerMtok,
+        output_per_mtok: outputPerMtok,
+      });
+    },
+
+    deletePrice: async (model: string): Promise<void> => {
+      return callInvoke<void>("delete_model_price", { model });
+    },
+
+    getBudget: async (profileId: string): Promise<ProfileBudget | null> => {
+      return callInvoke<ProfileBudget | null>("get_profile_budget", {
+        profile_id: profileId,
+      });
+    },
+
+    setBudget: async (
+      profileId: string,
+      softLimitUsd: number | null,
+      hardLimitUsd: number | null,
+      period: BudgetPeriod = "month",
+    ): Promise<ProfileBudget | null> => {
+      return callInvoke<ProfileBudget | null>("set_profile_budget", {
+        profile_id: profileId,
+        soft_limit_usd: softLimitUsd,
+        hard_limit_usd: hardLimitUsd,
+        period,
+      });
+    },
+
+    getBudgetStatus: async (profileId: string): Promise<BudgetStatus | null> => {
+      return callInvoke<BudgetStatus | null>("get_budget_status", {
+        profile_id: profileId,
+      });
+    },
+  },
+
+  // Clipboard quick actions
+  clipboard: {
+    analyze: async (): Promise<ClipboardAnalysis> => {
+      return callInvoke<ClipboardAnalysis>("analyze_clipboard");
+    },
+
+    runAction: async (
+      action?: QuickAction,
+      options: { targetLanguage?: string; replaceClipboard?: boolean } = {},
+    ): Promise<ClipboardActionResult> => {
+      return callInvoke<ClipboardActionResult>("run_clipboard_action", {
+        action,
+        target_language: options.targetLanguage,
+        replace_clipboard: options.replaceClipboard,
+      });
+    },
+  },
+
   // Shortcuts management
   shortcuts: {
-    getConfig: async () => {
+    getConfig: async (): Promise<ShortcutConfig> => {
       return callInvoke("get_shortcut_config");
     },
 
-    updateConfig: async (config: any) => {
+    // Rebinds the shortcuts immediately; rejects configs with duplicate shortcuts
+    updateConfig: async (
+      config: ShortcutConfig,
+    ): Promise<ShortcutRegistration[]> => {
       return callInvoke("update_shortcut_config", { config });
     },
 
+    getStatus: async (): Promise<ShortcutRegistration[]> => {
+      return callInvoke("get_shortcut_status");
+    },
+
+    // Portal (Wayland) or plugin (X11), and why the portal wasn't used
+    getBackend: async (): Promise<ShortcutDiagnostics> => {
+      return callInvoke("get_shortcut_backend");
+    },
+
     validateShortcut: async (shortcut: string) => {
       return callInvoke("validate_shortcut", { shortcut });
     },
 
+    // e.g. "shift+ctrl+k" -> "Control+Shift+K"; rejects unknown keys
+    normalizeShortcut: async (shortcut: string): Promise<string> => {
+      return callInvoke("normalize_shortcut", { shortcut });
+    },
+
     getAvailableActions: async () => {
       return callInvoke("get_available_actions");
     },
   },
+
+  notifications: {
+    getSettings: async (): Promise<NotificationSettings> => {
+      return callInvoke("get_notification_settings");
+    },
+
+    updateSettings: async (
+      settings: NotificationSettings,
+    ): Promise<NotificationSettings> => {
+      return callInvoke("update_notification_settings", { settings });
+    },
+  },
+
+  updates: {
+    getSettings: async (): Promise<UpdateSettings> => {
+      return callInvoke("get_update_settings");
+    },
+
+    // Rejects mirror URLs that aren't http(s)
+    updateSettings: async (settings: UpdateSettings): Promise<UpdateSettings> => {
+      return callInvoke("update_update_settings", { settings });
+    },
+
+    skipVersion: async (version: string): Promise<void> => {
+      return callInvoke("skip_update_version", { version });
+    },
+  },
+
+  backups: {
+    // Without a path the backup goes into the backup directory
+    create: async (
+      path?: string,
+      passphrase?: string,
+    ): Promise<BackupInfo> => {
+      return callInvoke("create_backup", { path, passphrase });
+    },
+
+    // Checks passphrase, checksums and database integrity only
+    verify: async (
+      path: string,
+      passphrase?: string,
+    ): Promise<BackupManifest> => {
+      return callInvoke("verify_backup", { path, passphrase });
+    },
+
+    // Replaces all data; the current data is backed up first
+    restore: async (
+      path: string,
+      passphrase?: string,
+    ): Promise<RestoreOutcome> => {
+      return callInvoke("restore_backup", { path, passphrase });
+    },
+
+    list: async (): Promise<BackupFile[]> => {
+      return callInvoke("list_backups");
+    },
+
+    getSettings: async (): Promise<BackupSettings> => {
+      return callInvoke("get_backup_settings");
+    },
+
+    // A passphrase is stored in the keyring; an empty one removes it
+    updateSettings: async (
+      settings: BackupSettings,
+      passphrase?: string,
+    ): Promise<BackupSettings> => {
+      return callInvoke("update_backup_settings", { settings, passphrase });
+    },
+  },
+
+  encryption: {
+    getStatus: async (): Promise<EncryptionStatus> => {
+      return callInvoke("get_encryption_status");
+    },
+
+    // Without a passphrase the key is read from the keyring
+    unlock: async (passphrase?: string): Promise<EncryptionStatus> => {
+      return callInvoke("unlock_database", { passphrase });
+    },
+
+    lock: async (): Promise<EncryptionStatus> => {
+      return callInvoke("lock_database");
+    },
+
+    // Rewrites the database file; no key source decrypts it
+    setEncryption: async (
+      keySource: KeySource | null,
+      passphrase?: string,
+    ): Promise<EncryptionStatus> => {
+      return callInvoke("set_database_encryption", { keySource, passphrase });
+    },
+
+    // The key source is only changed by setEncryption
+    updateSettings: async (
+      settings: EncryptionSettings,
+    ): Promise<EncryptionStatus> => {
+      return callInvoke("update_encryption_settings", { settings });
+    },
+  },
+
+  exports: {
+    // One file per conversation; only conversations changed since the last
+    // export into `path` are rewritten
+    toDirectory: async (
+      path: string,
+      format: ExportFormat,
+      filter?: ExportFilter,
+      wholeTree?: boolean,
+    ): Promise<DirectoryExport> => {
+      return callInvoke("export_to_directory", {
+        path,
+        format,
+        filter,
+        wholeTree,
+      });
+    },
+
+    getMirrorSettings: async (): Promise<ExportMirrorSettings> => {
+      return callInvoke("get_export_mirror_settings");
+    },
+
+    // Rejects relative directories
+    updateMirrorSettings: async (
+      settings: ExportMirrorSettings,
+    ): Promise<ExportMirrorSettings> => {
+      return callInvoke("update_export_mirror_settings", { settings });
+    },
+
+    // Runs the mirror now, whether or not it is due
+    runMirror: async (): Promise<DirectoryExport> => {
+      return callInvoke("run_export_mirror");
+    },
+  },
+
+  imports: {
+    // Dry run: nothing is written
+    preview: async (
+      content: string,
+      format?: ImportFormat,
+      conflict?: ConflictStrategy,
+    ): Promise<ImportPreview> => {
+      return callInvoke("preview_import", {
+        jsonContent: content,
+        format,
+        conflict,
+      });
+    },
+
+    // Format is detected from the content when omitted; existing
+    // conversations are skipped unless another strategy is given. Runs in one
+    // transaction, so a failure imports nothing.
+    run: async (
+      content: string,
+      format?: ImportFormat,
+      conflict?: ConflictStrategy,
+    ): Promise<ImportResult> => {
+      return callInvoke("import_conversations_json", {
+        jsonContent: content,
+        format,
+        conflict,
+      });
+    },
+  },
 };
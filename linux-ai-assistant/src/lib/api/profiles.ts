@@ -1,5 +1,5 @@
 import { invokeSafe } from "../utils/tauri";
-import type { ApiProfile, NewProfile } from "./types";
+import type { ApiProfile, FallbackTarget, NewProfile } from "./types";
 
 export async function createProfile(
   profileData: NewProfile,
@@ -34,3 +34,10 @@ export async function updateProfile(
 export async function deleteProfile(id: string): Promise<void> {
   await invokeSafe<void>("delete_profile", { id });
 }
+
+export async function setProfileFallbackChain(
+  id: string,
+  chain: FallbackTarget[],
+): Promise<ApiProfile | null> {
+  return invokeSafe<ApiProfile>("set_profile_fallback_chain", { id, chain });
+}
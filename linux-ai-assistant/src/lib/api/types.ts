@@ -1,6 +1,41 @@
 // src/lib/api/types.ts
 // TypeScript types matching the Rust structs
 
+// Stable error codes of the backend's AppError
+export type ApiErrorCode =
+  | "not_found"
+  | "invalid_input"
+  | "database"
+  | "database_locked"
+  | "io"
+  | "network"
+  | "auth"
+  | "rate_limited"
+  | "context_overflow"
+  | "provider"
+  | "budget_exceeded"
+  | "conflict"
+  | "unsupported"
+  | "cancelled"
+  | "internal"
+  | "locked";
+
+// Rejection value of every Tauri command
+export interface ApiError {
+  code: ApiErrorCode;
+  message: string;
+  details?: Record<string, unknown>;
+}
+
+export function isApiError(error: unknown): error is ApiError {
+  return (
+    typeof error === "object" &&
+    error !== null &&
+    typeof (error as ApiError).code === "string" &&
+    typeof (error as ApiError).message === "string"
+  );
+}
+
 export interface ApiConversation {
   id: string;
   title: string;
@@ -11,6 +46,14 @@ export interface ApiConversation {
   system_prompt?: string;
   parent_conversation_id?: string;
   branch_point_message_id?: string;
+  project_root?: string;
+  active_leaf_id?: string;
+  summary?: string;
+  summary_message_id?: string;
+  // Profile active when the conversation was created
+  profile_id?: string;
+  // Workspace template the conversation was started from
+  template_id?: string;
 }
 
 export interface ApiMessage {
@@ -19,10 +62,128 @@ export interface ApiMessage {
   role: "user" | "assistant" | "system";
   content: string;
   timestamp: number;
+  tokens_used?: number;
+  parent_message_id?: string;
+  prompt_tokens?: number;
+  completion_tokens?: number;
+  provider?: string;
+  model?: string;
+  cost_usd?: number;
+  // Attachments loaded alongside the message (fetched separately from the message itself)
+  attachments?: ApiAttachment[];
   // Optional UI-only status to represent per-message delivery state in the frontend
   status?: "pending" | "sent" | "failed";
 }
 
+export interface ApiAttachment {
+  id: string;
+  message_id: string;
+  kind: "image" | "file";
+  file_name: string;
+  mime_type: string;
+  size_bytes: number;
+  sha256: string;
+  created_at: number;
+}
+
+// File contents to attach to a message, e.g. from `lai ask --file`
+export interface AttachmentData {
+  file_name: string;
+  data_base64: string;
+}
+
+export interface BranchDiffEntry {
+  kind: "same" | "changed" | "only_a" | "only_b";
+  a?: ApiMessage;
+  b?: ApiMessage;
+}
+
+export interface BranchDiff {
+  conversation_a: string;
+  conversation_b: string;
+  common_ancestor_id?: string;
+  common_prefix_len: number;
+  entries: BranchDiffEntry[];
+}
+
+// Result of re-indexing the current project root
+export interface ProjectIndexStats {
+  project_root: string;
+  files_indexed: number;
+  files_unchanged: number;
+  files_removed: number;
+  total_chunks: number;
+  index_time_ms: number;
+}
+
+export interface ProjectChunk {
+  id: number;
+  project_root: string;
+  path: string;
+  start_line: number;
+  end_line: number;
+  content: string;
+  file_mtime: number;
+}
+
+export interface ScoredProjectChunk {
+  chunk: ProjectChunk;
+  score: number;
+}
+
+export type UsageGroupBy = "day" | "provider" | "model" | "profile" | "tag";
+
+export interface UsageRow {
+  key: string;
+  label: string;
+  messages: number;
+  prompt_tokens: number;
+  completion_tokens: number;
+  cost_usd: number;
+  unpriced_messages: number;
+}
+
+export interface UsageReport {
+  range: string;
+  group_by: UsageGroupBy;
+  from: number;
+  to: number;
+  rows: UsageRow[];
+  total: UsageRow;
+}
+
+export interface ModelPrice {
+  model: string;
+  provider: string;
+  input_per_mtok: number;
+  output_per_mtok: number;
+  updated_at: number;
+}
+
+export type BudgetPeriod = "day" | "week" | "month";
+
+export interface ProfileBudget {
+  profile_id: string;
+  soft_limit_usd?: number;
+  hard_limit_usd?: number;
+  period: BudgetPeriod;
+  updated_at: number;
+}
+
+export interface BudgetStatus {
+  profile_id: string;
+  profile_name: string;
+  period: BudgetPeriod;
+  period_start: number;
+  spent_usd: number;
+  // Replies in the period whose model has no price (not in spent_usd)
+  unpriced_messages: number;
+  soft_limit_usd?: number;
+  hard_limit_usd?: number;
+  soft_exceeded: boolean;
+  hard_exceeded: boolean;
+}
+
 // Document search types
 export interface FileMatch {
   path: string;
@@ -53,6 +214,7 @@ export interface NewConversation {
   model: string;
   provider: string;
   system_prompt?: string;
+  template_id?: string;
 }
 
 export interface NewMessage {
@@ -73,6 +235,13 @@ export interface ApiProfile {
   created_at: number;
   updated_at: number;
   is_active: boolean;
+  fallback_chain: FallbackTarget[];
+}
+
+export interface FallbackTarget {
+  provider: "openai" | "anthropic" | "gemini" | "ollama";
+  // Omitted to use the provider's default model
+  model?: string;
 }
 
 export interface NewProfile {
@@ -127,3 +296,258 @@ export interface NewWorkspaceTemplate {
   file_extensions?: string;
   context_instructions?: string;
 }
+
+// Clipboard quick actions
+export type ClipboardContentKind =
+  | "stack_trace"
+  | "json"
+  | "shell_command"
+  | "url"
+  | "code"
+  | "text";
+
+export type QuickAction = "explain" | "format" | "translate" | "fix" | "summarize";
+
+export interface ClipboardAnalysis {
+  kind: ClipboardContentKind;
+  language?: string;
+  // Suitable actions; the first is the default
+  actions: QuickAction[];
+  preview: string;
+}
+
+export interface ClipboardActionResult {
+  action: QuickAction;
+  analysis: ClipboardAnalysis;
+  // Absent when the action ran locally (formatting JSON)
+  conversation_id?: string;
+  reply?: ApiMessage;
+  replaced_with?: string;
+}
+
+// Global shortcuts
+export type ShortcutAction =
+  | "ToggleWindow"
+  | "NewConversation"
+  | "OpenSettings"
+  | "QuickCapture"
+  | "FocusInput"
+  | "ClearConversation"
+  | "ExportCurrent"
+  | "ToggleProfileMenu"
+  | "SearchDocuments"
+  | "ShowPerformance"
+  | "ToggleRecording"
+  | "QuickExport";
+
+export interface GlobalShortcut {
+  action: ShortcutAction;
+  shortcut: string;
+  enabled: boolean;
+}
+
+export interface ShortcutConfig {
+  shortcuts: GlobalShortcut[];
+}
+
+export interface ShortcutRegistration {
+  action: ShortcutAction;
+  shortcut: string;
+  registered: boolean;
+  // Why the shortcut is not bound (invalid, repeated or refused by the OS)
+  error?: string;
+  // Trigger reported by the desktop portal, which the user may have changed
+  trigger_description?: string;
+}
+
+export type ShortcutBackend = "portal" | "plugin";
+
+export interface ShortcutDiagnostics {
+  // Absent until shortcuts have been applied once
+  backend?: ShortcutBackend;
+  wayland: boolean;
+  session_type?: string;
+  portal_version?: number;
+  // Why the portal wasn't used on a Wayland session
+  fallback_reason?: string;
+  registrations: ShortcutRegistration[];
+}
+
+export type ImportFormat = "native" | "chatgpt" | "claude" | "markdown";
+
+// What to do with a conversation whose id already exists
+export type ConflictStrategy = "skip" | "overwrite" | "merge" | "copy";
+
+export type ImportAction =
+  | "created"
+  | "skipped"
+  | "overwritten"
+  | "merged"
+  | "copied";
+
+export interface ImportPreviewEntry {
+  id: string;
+  title: string;
+  message_count: number;
+  // Leaves of the message tree; 1 for a linear conversation
+  branch_count: number;
+  attachment_count: number;
+  exists: boolean;
+  action: ImportAction;
+  // Fewer than message_count when merging
+  messages_to_import: number;
+}
+
+export interface ImportPreview {
+  format: ImportFormat;
+  conflict: ConflictStrategy;
+  conversations: ImportPreviewEntry[];
+  conversations_to_create: number;
+  messages_to_create: number;
+  warnings: string[];
+}
+
+export interface ImportOutcome {
+  source_id: string;
+  // Differs from source_id for copies
+  conversation_id: string;
+  title: string;
+  action: ImportAction;
+  messages_imported: number;
+}
+
+export interface ImportResult {
+  format: ImportFormat;
+  conflict: ConflictStrategy;
+  outcomes: ImportOutcome[];
+  messages_imported: number;
+  warnings: string[];
+}
+
+export type ExportFormat =
+  | "json"
+  | "markdown"
+  | "html"
+  | "pdf"
+  | "org"
+  | "epub"
+  | "text";
+
+// Unset fields don't filter
+export interface ExportFilter {
+  conversation_ids?: string[] | null;
+  tag?: string | null;
+  // Case-insensitive text the title must contain
+  query?: string | null;
+  // Last update, as for usage reports: "week", "30d", "2024-01-01..2024-03-31"
+  range?: string | null;
+}
+
+export interface DirectoryExport {
+  directory: string;
+  // Files of new or changed conversations
+  written: string[];
+  unchanged: number;
+  // Old names of renamed files and files of deleted conversations
+  removed: string[];
+}
+
+// Scheduled mirror of conversations into a directory
+export interface ExportMirrorSettings {
+  enabled: boolean;
+  // Absolute path
+  directory?: string | null;
+  format: ExportFormat;
+  filter: ExportFilter;
+  whole_tree: boolean;
+  interval_minutes: number;
+  last_run?: number | null;
+  last_error?: string | null;
+}
+
+export type UpdateChannel = "stable" | "beta" | "nightly";
+
+export interface UpdateSettings {
+  channel: UpdateChannel;
+  // 0 turns automatic checks off
+  check_interval_hours: number;
+  skipped_version?: string | null;
+  // Releases API of a mirror, instead of GitHub
+  base_url?: string | null;
+  last_checked?: number | null;
+}
+
+export interface BackupManifest {
+  format_version: number;
+  // Version of the app that wrote the backup
+  app_version: string;
+  created_at: number;
+  database_sha256: string;
+  // Including conversations in the trash
+  conversations: number;
+  blobs: number;
+}
+
+export interface BackupInfo {
+  path: string;
+  encrypted: boolean;
+  size_bytes: number;
+  manifest: BackupManifest;
+  // Attachments whose file was missing, so aren't in the backup
+  missing_blobs: string[];
+}
+
+export interface BackupFile {
+  path: string;
+  file_name: string;
+  size_bytes: number;
+  modified?: number | null;
+  encrypted: boolean;
+  // Written by the daily schedule, so subject to rotation
+  automatic: boolean;
+}
+
+export interface RestoreOutcome {
+  manifest: BackupManifest;
+  blobs_restored: number;
+  // Backup of the data the restore replaced
+  safety_backup?: string | null;
+}
+
+export interface BackupSettings {
+  // Daily automatic backups
+  enabled: boolean;
+  // Absolute path; the app data directory's `backups` when unset
+  directory?: string | null;
+  // Automatic backups kept
+  keep: number;
+  // Encrypt automatic backups with the passphrase kept in the keyring
+  encrypt: boolean;
+  last_run?: number | null;
+  last_error?: string | null;
+}
+
+export type KeySource = "keyring" | "passphrase";
+
+export interface EncryptionSettings {
+  // Unset while the database is plaintext
+  key_source?: KeySource | null;
+  // Minutes without use after which an encrypted database locks; 0 never
+  idle_lock_minutes: number;
+}
+
+export interface EncryptionStatus {
+  encrypted: boolean;
+  key_source?: KeySource | null;
+  // Commands touching the database fail with "locked" until unlocked
+  locked: boolean;
+  idle_lock_minutes: number;
+}
+
+// Desktop notifications for work finishing while the window is hidden
+export interface NotificationSettings {
+  enabled: boolean;
+  do_not_disturb: boolean;
+  min_duration_secs: number;
+  max_per_minute: number;
+}
@@ -11,7 +11,9 @@ import { useSettingsStore } from "../stores/settingsStore";
 export type ProviderMessage = {
   role: "user" | "assistant" | "system";
   content: string;
-} & Partial<Message>;
+  // Attachment ids; the backend loads them into each provider's multimodal format
+  attachments?: string[];
+} & Partial<Omit<Message, "attachments">>;
 
 export interface Provider {
   // onChunk is an optional callback used for streaming partial responses. If provided,
@@ -55,7 +55,7 @@ export const slashCommands: SlashCommand[] = [
   {
     command: "export",
     description: "Export current conversation",
-    parameters: ["format (json|markdown|html|pdf)"],
+    parameters: ["format (json|markdown|html|pdf|org|epub|text)"],
     handler: async (args, context) => {
       if (!context.conversationId) {
         context.addToast({
@@ -68,9 +68,14 @@ export const slashCommands: SlashCommand[] = [
 
       try {
         const format = args[0] || "json";
-        if (!["json", "markdown", "html", "pdf"].includes(format)) {
+        if (
+          !["json", "markdown", "html", "pdf", "org", "epub", "text"].includes(
+            format,
+          )
+        ) {
           context.addToast({
-            message: "Invalid format. Use 'json', 'markdown', 'html', or 'pdf'",
+            message:
+              "Invalid format. Use 'json', 'markdown', 'html', 'pdf', 'org', 'epub', or 'text'",
             type: "error",
             ttl: 3000,
           });
@@ -6,8 +6,10 @@ import { database as db } from "../api/database";
 import type {
   ApiConversation as Conversation,
   ApiMessage as Message,
+  AttachmentData,
 } from "../api/types";
 import { getProvider } from "../providers/provider";
+import type { ProviderMessage } from "../providers/provider";
 import { notifySafe } from "../utils/tauri";
 import { useProjectStore } from "./projectStore";
 import { useRoutingStore } from "./routingStore";
@@ -43,7 +45,10 @@ interface ChatState {
   searchConversations: (query: string) => Promise<void>;
   clearSearch: () => void;
 
-  sendMessage: (content: string) => Promise<void>;
+  sendMessage: (
+    content: string,
+    attachments?: AttachmentData[],
+  ) => Promise<void>;
   retryMessage: (id: string) => Promise<void>;
   updateMessage: (id: string, content: string) => Promise<void>;
   deleteMessage: (id: string) => Promise<void>;
@@ -110,10 +115,16 @@ export const useChatStore = create<ChatState>((set, get) => ({
       }
 
       const messages = await db.messages.getByConversation(id);
+      const attachments = await db.attachments
+        .getByConversation(id)
+        .catch(() => []);
 
       set({
         currentConversation: conversation,
-        messages,
+        messages: messages.map((m) => {
+          const own = attachments.filter((a) => a.message_id === m.id);
+          return own.length ? { ...m, attachments: own } : m;
+        }),
         isLoading: false,
       });
     } catch (error) {
@@ -158,7 +169,7 @@ export const useChatStore = create<ChatState>((set, get) => ({
     }
   },
 
-  sendMessage: async (content) => {
+  sendMessage: async (content, files = []) => {
     const { currentConversation } = get();
     if (!currentConversation) {
       throw new Error("No conversation selected");
@@ -190,10 +201,28 @@ export const useChatStore = create<ChatState>((set, get) => ({
         content,
       });
 
+      // Attach files and images (e.g. from `lai ask --file/--image`) to the new message
+      const attachments = [];
+      for (const file of files) {
+        attachments.push(
+          await db.attachments.attachData(
+            userMessage.id,
+            file.file_name,
+            file.data_base64,
+          ),
+        );
+      }
+
       // Replace optimistic message with the one returned from DB and mark sent
       set((state) => ({
         messages: state.messages.map((m) =>
-          m.id === optimisticId ? { ...userMessage, status: "sent" } : m,
+          m.id === optimisticId
+            ? {
+                ...userMessage,
+                ...(attachments.length ? { attachments } : {}),
+                status: "sent",
+              }
+            : m,
         ),
       }));
 
@@ -263,12 +292,19 @@ export const useChatStore = create<ChatState>((set, get) => ({
         settingsStore.defaultModel = selectedModelId;
       }
 
-      const messagesForProvider = get().messages.map((m) => ({
-        role: m.role as "user" | "assistant" | "system",
-        content: m.content,
-      }));
+      const messagesForProvider: ProviderMessage[] = get()
+        .messages.filter((m) => m.id !== userMessage.id)
+        .map((m) => ({
+          role: m.role as "user" | "assistant" | "system",
+          content: m.content,
+          attachments: m.attachments?.map((a) => a.id),
+        }));
       // include the just persisted user message as last
-      messagesForProvider.push({ role: "user", content });
+      messagesForProvider.push({
+        role: "user",
+        content,
+        attachments: attachments.map((a) => a.id),
+      });
 
       // project-aware context: prepend a brief summary of recent file changes if available
       try {
@@ -341,10 +377,13 @@ export const useChatStore = create<ChatState>((set, get) => ({
         }));
 
         // Fire a desktop notification to inform the user the response is ready
+        // (in the app the backend raises it, with actions and rate limiting)
         try {
-          const title = get().currentConversation?.title || "Assistant";
-          const preview = finalContent?.slice(0, 100) || "Response ready";
-          await notifySafe(title, preview);
+          if (!isTauriEnvironment()) {
+            const title = get().currentConversation?.title || "Assistant";
+            const preview = finalContent?.slice(0, 100) || "Response ready";
+            await notifySafe(title, preview);
+          }
         } catch {}
       } catch (err) {
         // Restore original model setting on error
@@ -475,10 +514,13 @@ export const useChatStore = create<ChatState>((set, get) => ({
         }));
 
         // Notify on successful retry completion
+        // (in the app the backend raises it, with actions and rate limiting)
         try {
-          const title = get().currentConversation?.title || "Assistant";
-          const preview = finalContent?.slice(0, 100) || "Response ready";
-          await notifySafe(title, preview);
+          if (!isTauriEnvironment()) {
+            const title = get().currentConversation?.title || "Assistant";
+            const preview = finalContent?.slice(0, 100) || "Response ready";
+            await notifySafe(title, preview);
+          }
         } catch {}
       } catch (err) {
         set((state) => ({
@@ -4,10 +4,7 @@
 import { create } from "zustand";
 import { database as db } from "../api/database";
 import { useUiStore } from "./uiStore";
-import {
-  registerGlobalShortcutSafe,
-  unregisterAllShortcutsSafe,
-} from "../utils/tauri";
+import type { ShortcutRegistration } from "../api/types";
 import { applyTheme } from "../utils/theme";
 
 interface SettingsState {
@@ -161,30 +158,35 @@ export const useSettingsStore = create<SettingsState>((set) => ({
     set({ allowCodeExecution: allow });
   },
 
+  // The window toggle shortcut is the `ToggleWindow` entry of the backend
+  // shortcut config; the backend binds it and reports whether the OS accepted it.
   registerGlobalShortcut: async (shortcutOptional) => {
     const shortcut =
       shortcutOptional || useSettingsStore.getState().globalShortcut;
-    // Unregister all first to avoid duplicate binds
     try {
-      await unregisterAllShortcutsSafe();
-    } catch (e) {
-      // ignore
-    }
-    try {
-      const success = await registerGlobalShortcutSafe(shortcut, async () => {
-        try {
-          await db.window.toggle();
-        } catch (err) {
-          console.error("Failed to toggle window from shortcut:", err);
-        }
-      });
-      if (success) {
-        useUiStore.getState().addToast({
-          message: `Global shortcut set to ${shortcut}`,
-          type: "success",
-          ttl: 2500,
+      const config = await db.shortcuts.getConfig();
+      const current = config.shortcuts.find((s) => s.action === "ToggleWindow");
+      let registrations: ShortcutRegistration[];
+      if (current && current.enabled && current.shortcut === shortcut) {
+        registrations = await db.shortcuts.getStatus();
+      } else {
+        registrations = await db.shortcuts.updateConfig({
+          shortcuts: config.shortcuts.map((s) =>
+            s.action === "ToggleWindow" ? { ...s, shortcut, enabled: true } : s,
+          ),
         });
       }
+      const toggle = registrations.find((r) => r.action === "ToggleWindow");
+      // Nothing is bound in web preview
+      if (!toggle) return;
+      if (!toggle.registered) {
+        throw new Error(toggle.error || "registration failed");
+      }
+      useUiStore.getState().addToast({
+        message: `Global shortcut set to ${shortcut}`,
+        type: "success",
+        ttl: 2500,
+      });
     } catch (e) {
       console.error("Failed to register global shortcut:", e);
       useUiStore.getState().addToast({
@@ -10,6 +10,16 @@ export interface VersionInfo {
   is_critical: boolean;
 }
 
+// Result of download_and_install_update
+export interface InstallOutcome {
+  version: string;
+  path: string;
+  sha256: string;
+  // The running AppImage was replaced; restart to use the new version
+  installed: boolean;
+  backup_path?: string;
+}
+
 export interface UpdateStatus {
   has_update: boolean;
   current_version: string;
@@ -17,6 +27,8 @@ export interface UpdateStatus {
   release_info?: VersionInfo;
   is_installing: boolean;
   error?: string;
+  // The newest version was skipped in settings
+  skipped?: boolean;
 }
 
 export interface UpdateStore {
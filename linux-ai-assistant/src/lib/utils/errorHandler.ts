@@ -1,5 +1,6 @@
 import { useUiStore } from "../stores/uiStore";
 import React from "react";
+import { isApiError, type ApiErrorCode } from "../api/types";
 
 // Error types for categorization
 export enum ErrorType {
@@ -62,9 +63,14 @@ const ERROR_MESSAGES: Record<string, string> = {
   api_rate_limited:
     "Too many requests. Please wait a moment before trying again.",
   api_quota_exceeded: "API quota exceeded. Please check your usage limits.",
+  budget_exceeded:
+    "This profile's spending limit has been reached. Raise or clear the budget to continue.",
+  context_overflow:
+    "The conversation is too long for this model. Start a new one or pick a larger model.",
 
   // Validation errors
   invalid_input: "Please check your input and try again.",
+  not_found: "The requested item no longer exists.",
   missing_required_field: "Please fill in all required fields.",
 
   // System errors
@@ -77,12 +83,50 @@ const ERROR_MESSAGES: Record<string, string> = {
   unknown_error: "An unexpected error occurred. Please try again.",
 };
 
+// Classification of backend error codes
+const CODE_CLASSIFICATION: Record<
+  ApiErrorCode,
+  { type: ErrorType; severity: ErrorSeverity }
+> = {
+  not_found: { type: ErrorType.VALIDATION, severity: ErrorSeverity.LOW },
+  invalid_input: { type: ErrorType.VALIDATION, severity: ErrorSeverity.LOW },
+  cancelled: { type: ErrorType.VALIDATION, severity: ErrorSeverity.LOW },
+  conflict: { type: ErrorType.VALIDATION, severity: ErrorSeverity.MEDIUM },
+  database: { type: ErrorType.DATABASE, severity: ErrorSeverity.HIGH },
+  database_locked: { type: ErrorType.DATABASE, severity: ErrorSeverity.HIGH },
+  locked: { type: ErrorType.AUTHENTICATION, severity: ErrorSeverity.MEDIUM },
+  io: { type: ErrorType.SYSTEM, severity: ErrorSeverity.HIGH },
+  unsupported: { type: ErrorType.SYSTEM, severity: ErrorSeverity.MEDIUM },
+  internal: { type: ErrorType.UNKNOWN, severity: ErrorSeverity.MEDIUM },
+  network: { type: ErrorType.NETWORK, severity: ErrorSeverity.MEDIUM },
+  auth: { type: ErrorType.AUTHENTICATION, severity: ErrorSeverity.HIGH },
+  rate_limited: { type: ErrorType.API, severity: ErrorSeverity.MEDIUM },
+  context_overflow: { type: ErrorType.API, severity: ErrorSeverity.MEDIUM },
+  provider: { type: ErrorType.API, severity: ErrorSeverity.MEDIUM },
+  budget_exceeded: { type: ErrorType.API, severity: ErrorSeverity.MEDIUM },
+};
+
+// Message of a backend error object, Error or anything else
+export function errorMessage(error: any): string {
+  if (isApiError(error)) return error.message;
+  if (error instanceof Error) return error.message;
+  return String(error);
+}
+
 // Error classification function
 export function classifyError(error: any): {
   type: ErrorType;
   severity: ErrorSeverity;
 } {
-  const errorString = String(error).toLowerCase();
+  const code = isApiError(error) ? error.code : error?.code;
+  if (
+    typeof code === "string" &&
+    Object.prototype.hasOwnProperty.call(CODE_CLASSIFICATION, code)
+  ) {
+    return CODE_CLASSIFICATION[code as ApiErrorCode];
+  }
+
+  const errorString = errorMessage(error).toLowerCase();
 
   // Network errors
   if (
@@ -136,20 +180,39 @@ export function createAppError(
     id: `error_${Date.now()}_${Math.random().toString(36).slice(2, 11)}`,
     type,
     severity,
-    message: String(error),
+    message: errorMessage(error),
     userMessage:
       customMessage || ERROR_MESSAGES[errorKey] || ERROR_MESSAGES.unknown_error,
     details: error,
     timestamp: Date.now(),
     context,
     recoverable: severity !== ErrorSeverity.CRITICAL,
-    retryable: type === ErrorType.NETWORK || type === ErrorType.API,
+    retryable:
+      type === ErrorType.NETWORK ||
+      (type === ErrorType.API && errorKey !== "budget_exceeded"),
   };
 }
 
+// Backend error codes with their own user message
+const CODE_MESSAGE_KEYS: Partial<Record<ApiErrorCode, string>> = {
+  not_found: "not_found",
+  invalid_input: "invalid_input",
+  network: "connection_refused",
+  auth: "api_key_invalid",
+  rate_limited: "api_rate_limited",
+  budget_exceeded: "budget_exceeded",
+  context_overflow: "context_overflow",
+  database: "db_query_failed",
+  database_locked: "db_connection_failed",
+};
+
 // Extract error key for message lookup
 function extractErrorKey(error: any): string {
-  const errorString = String(error).toLowerCase();
+  const code = isApiError(error) ? error.code : error?.code;
+  const codeKey = code && CODE_MESSAGE_KEYS[code as ApiErrorCode];
+  if (codeKey) return codeKey;
+
+  const errorString = errorMessage(error).toLowerCase();
 
   if (errorString.includes("fetch") && errorString.includes("failed"))
     return "fetch_failed";